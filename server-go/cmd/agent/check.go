@@ -0,0 +1,140 @@
+package main
+
+import (
+	"fmt"
+	"net"
+	"net/url"
+	"os"
+	"os/exec"
+	"runtime"
+	"time"
+)
+
+// checkDialTimeout bounds the connection test to the dashboard
+const checkDialTimeout = 5 * time.Second
+
+// handleCheck prints what the agent would run with and whether it can reach
+// the dashboard, so users have one command to run when asking for support.
+// It exits non-zero if any check fails.
+func handleCheck() {
+	configPath := DefaultConfigPath()
+	for i := 2; i < len(os.Args); i++ {
+		if os.Args[i] == "--config" && i+1 < len(os.Args) {
+			configPath = os.Args[i+1]
+			i++
+		}
+	}
+
+	failed := false
+	report := func(name string, ok bool, detail string) {
+		status := "ok"
+		if !ok {
+			status = "FAIL"
+			failed = true
+		}
+		if detail != "" {
+			fmt.Printf("  %-20s %-4s  %s\n", name+":", status, detail)
+		} else {
+			fmt.Printf("  %-20s %s\n", name+":", status)
+		}
+	}
+
+	fmt.Printf("vstats-agent %s (%s/%s)\n", AgentVersion, runtime.GOOS, runtime.GOARCH)
+	fmt.Printf("Configuration file: %s\n", configPath)
+	fmt.Println()
+
+	fromEnv := LoadConfigFromEnv() != nil
+	if fromEnv {
+		fmt.Println("  Config is taken from VSTATS_* environment variables, not the file")
+	} else {
+		info, err := os.Stat(configPath)
+		if err != nil {
+			report("Config file", false, err.Error())
+		} else {
+			report("Config file", true, info.Mode().Perm().String())
+			if runtime.GOOS != "windows" && info.Mode().Perm()&0077 != 0 {
+				fmt.Println("    The config holds the agent token; chmod 600 keeps other users from reading it")
+			}
+		}
+	}
+
+	config, err := LoadConfig(configPath)
+	if err != nil {
+		report("Config parses", false, err.Error())
+		os.Exit(1)
+	}
+	report("Config parses", true, "")
+	if err := config.Validate(); err != nil {
+		report("Config valid", false, err.Error())
+	} else {
+		report("Config valid", true, "")
+	}
+	if config.ServerID == "" || config.AgentToken == "" {
+		report("Registered", false, "server_id or agent_token missing, run vstats-agent register")
+	} else {
+		report("Registered", true, config.ServerID)
+	}
+	fmt.Printf("  %-20s %s\n", "WebSocket URL:", config.WSUrl())
+
+	if err := SetProxy(config.ProxyURL); err != nil {
+		report("Proxy", false, err.Error())
+	} else if u := proxyURL.Load(); u != nil {
+		fmt.Printf("  %-20s %s\n", "Proxy:", u.Redacted())
+	}
+	if addr, err := dashboardAddr(config.DashboardURL); err != nil {
+		report("Dashboard reachable", false, err.Error())
+	} else {
+		// With a proxy only the proxy itself must be reachable from here
+		name, target := "Dashboard reachable", addr
+		if u := proxyURL.Load(); u != nil {
+			name, target = "Proxy reachable", u.Host
+		}
+		conn, err := net.DialTimeout("tcp", target, checkDialTimeout)
+		if err != nil {
+			report(name, false, err.Error())
+		} else {
+			conn.Close()
+			report(name, true, target)
+		}
+	}
+
+	if path, err := exec.LookPath("ping"); err != nil {
+		report("ping", false, "not found in PATH, ping targets will report errors")
+	} else {
+		report("ping", true, path)
+	}
+	if len(config.WatchedServices) > 0 && runtime.GOOS == "linux" {
+		if path, err := exec.LookPath("systemctl"); err != nil {
+			report("systemctl", false, "not found in PATH, watched_services cannot be checked")
+		} else {
+			report("systemctl", true, path)
+		}
+	}
+
+	if failed {
+		os.Exit(1)
+	}
+}
+
+// dashboardAddr returns the host:port the agent connects to for a dashboard
+// URL
+func dashboardAddr(dashboardURL string) (string, error) {
+	if dashboardURL == "" {
+		return "", fmt.Errorf("dashboard_url is not set")
+	}
+	u, err := url.Parse(dashboardURL)
+	if err != nil {
+		return "", err
+	}
+	if u.Host == "" {
+		return "", fmt.Errorf("dashboard_url has no host")
+	}
+	if u.Port() != "" {
+		return u.Host, nil
+	}
+	port := "80"
+	if u.Scheme == "https" {
+		port = "443"
+	}
+	return net.JoinHostPort(u.Hostname(), port), nil
+}
@@ -5,7 +5,10 @@ import (
 	"fmt"
 	"os"
 	"path/filepath"
+	"reflect"
+	"sort"
 	"strconv"
+	"strings"
 )
 
 const ConfigFilename = "vstats-agent.json"
@@ -17,6 +20,7 @@ type AgentConfig struct {
 	ServerName   string `json:"server_name"`
 	Location     string `json:"location"`
 	Provider     string `json:"provider"`
+	Tag          string `json:"tag,omitempty"`
 	IntervalSecs uint64 `json:"interval_secs"`
 	// Offline storage settings
 	EnableOfflineStorage bool   `json:"enable_offline_storage"` // Enable local storage when disconnected (default: true)
@@ -24,6 +28,41 @@ type AgentConfig struct {
 	MaxOfflineRecords    int    `json:"max_offline_records"`    // Max records to store offline (default: 10000)
 	AggregationSecs      int    `json:"aggregation_secs"`       // Aggregation interval in seconds (default: 60)
 	BatchSize            int    `json:"batch_size"`             // Max metrics per batch when syncing (default: 100)
+	// Disk filtering (glob patterns on mount point, fs type or device name).
+	// The exclude lists are written even when empty, so an explicit []
+	// (exclude nothing) survives a save instead of reverting to the defaults.
+	DiskExclude []string `json:"disk_exclude"`           // Default: tmpfs, devtmpfs, overlay, squashfs
+	DiskInclude []string `json:"disk_include,omitempty"` // Allowlist, takes precedence over disk_exclude
+	// Network interface filtering (glob patterns on the interface name);
+	// only counted interfaces contribute to total_rx/total_tx and speeds
+	NetInterfaceExclude []string `json:"net_interface_exclude"`           // Default: lo, docker*, veth*, br-* and other virtual interfaces
+	NetInterfaceInclude []string `json:"net_interface_include,omitempty"` // Allowlist, takes precedence over net_interface_exclude
+	// Self-update verification
+	RequireSignedUpdates bool   `json:"require_signed_updates,omitempty"` // Refuse updates without a valid ed25519 signature
+	UpdatePublicKey      string `json:"update_public_key,omitempty"`      // Base64 ed25519 public key for update signatures
+	// Negotiate permessage-deflate with the dashboard (falls back to
+	// uncompressed if the server doesn't support it)
+	Compression bool `json:"compression,omitempty"`
+	// Wire encoding for metrics: "json" (default) or "msgpack"
+	Encoding string `json:"encoding,omitempty"`
+	// Randomly offset the first metrics tick after connecting (default: true)
+	Jitter *bool `json:"jitter,omitempty"`
+	// Send only changed fields between periodic full snapshots when the
+	// server supports it (default: true)
+	DeltaMetrics *bool `json:"delta_metrics,omitempty"`
+	// Re-register with the dashboard when it no longer knows this server,
+	// using the admin token stored in admin_token_file, and rewrite the config
+	AutoReregister bool   `json:"auto_reregister,omitempty"`
+	AdminTokenFile string `json:"admin_token_file,omitempty"`
+	// systemd services whose state is reported with each sample
+	WatchedServices []string `json:"watched_services,omitempty"`
+	// Route all outbound connections through this proxy
+	// (http://[user:pass@]host:port or socks5://[user:pass@]host:port)
+	ProxyURL string `json:"proxy_url,omitempty"`
+	// Logging settings
+	LogFile    string `json:"log_file,omitempty"`     // Write logs to this file instead of stderr
+	LogMaxSize int    `json:"log_max_size,omitempty"` // Rotate after this many MB (default: 10)
+	LogKeep    *int   `json:"log_keep,omitempty"`     // Rotated files to keep, 0 for none (default: 3)
 }
 
 func DefaultConfigPath() string {
@@ -100,6 +139,39 @@ func LoadConfigFromEnv() *AgentConfig {
 	if dir := os.Getenv("VSTATS_DATA_DIR"); dir != "" {
 		config.DataDir = dir
 	}
+	if logFile := os.Getenv("VSTATS_LOG_FILE"); logFile != "" {
+		config.LogFile = logFile
+	}
+	if os.Getenv("VSTATS_COMPRESSION") == "true" {
+		config.Compression = true
+	}
+	if encoding := os.Getenv("VSTATS_ENCODING"); encoding != "" {
+		config.Encoding = encoding
+	}
+	if os.Getenv("VSTATS_JITTER") == "false" {
+		jitter := false
+		config.Jitter = &jitter
+	}
+	if os.Getenv("VSTATS_AUTO_REREGISTER") == "true" {
+		config.AutoReregister = true
+	}
+	if tokenFile := os.Getenv("VSTATS_ADMIN_TOKEN_FILE"); tokenFile != "" {
+		config.AdminTokenFile = tokenFile
+	}
+	if os.Getenv("VSTATS_DELTA") == "false" {
+		delta := false
+		config.DeltaMetrics = &delta
+	}
+	if proxy := os.Getenv("VSTATS_PROXY_URL"); proxy != "" {
+		config.ProxyURL = proxy
+	}
+	if services := os.Getenv("VSTATS_WATCHED_SERVICES"); services != "" {
+		for _, name := range strings.Split(services, ",") {
+			if name = strings.TrimSpace(name); name != "" {
+				config.WatchedServices = append(config.WatchedServices, name)
+			}
+		}
+	}
 	
 	return config
 }
@@ -111,6 +183,12 @@ func LoadConfig(path string) (*AgentConfig, error) {
 	}
 
 	// Fall back to config file
+	return LoadConfigFile(path)
+}
+
+// LoadConfigFile loads configuration from the given file only, ignoring
+// environment variables. Used by commands that edit the file in place.
+func LoadConfigFile(path string) (*AgentConfig, error) {
 	data, err := os.ReadFile(path)
 	if err != nil {
 		return nil, fmt.Errorf("failed to read config file %s: %w", path, err)
@@ -131,6 +209,24 @@ func LoadConfig(path string) (*AgentConfig, error) {
 	return &config, nil
 }
 
+// JitterEnabled reports whether the first metrics tick is randomly offset
+func (c *AgentConfig) JitterEnabled() bool {
+	return c.Jitter == nil || *c.Jitter
+}
+
+// DeltaEnabled reports whether delta metrics are offered to the server
+func (c *AgentConfig) DeltaEnabled() bool {
+	return c.DeltaMetrics == nil || *c.DeltaMetrics
+}
+
+// LogKeepFiles is the number of rotated log files to keep
+func (c *AgentConfig) LogKeepFiles() int {
+	if c.LogKeep == nil {
+		return DefaultLogKeep
+	}
+	return *c.LogKeep
+}
+
 // setConfigDefaults sets default values for config fields
 func setConfigDefaults(config *AgentConfig) {
 	// Enable offline storage by default
@@ -149,6 +245,16 @@ func setConfigDefaults(config *AgentConfig) {
 	if config.DataDir == "" {
 		config.DataDir = GetDataDir()
 	}
+	// Only an absent key is nil; an explicit [] unmarshals to an empty slice
+	if config.DiskExclude == nil {
+		config.DiskExclude = DefaultDiskExclude
+	}
+	if config.NetInterfaceExclude == nil {
+		config.NetInterfaceExclude = DefaultNetInterfaceExclude
+	}
+	if config.LogMaxSize == 0 {
+		config.LogMaxSize = DefaultLogMaxSizeMB
+	}
 }
 
 func SaveConfig(config *AgentConfig, path string) error {
@@ -181,3 +287,151 @@ func (c *AgentConfig) WSUrl() string {
 	return fmt.Sprintf("%s/ws/agent", url)
 }
 
+// ============================================================================
+// Field access by JSON key (used by the `set` subcommand)
+// ============================================================================
+
+// configField returns the settable struct field for a JSON key
+func (c *AgentConfig) configField(key string) (reflect.Value, bool) {
+	v := reflect.ValueOf(c).Elem()
+	t := v.Type()
+	for i := 0; i < t.NumField(); i++ {
+		name := strings.Split(t.Field(i).Tag.Get("json"), ",")[0]
+		if name == key {
+			return v.Field(i), true
+		}
+	}
+	return reflect.Value{}, false
+}
+
+// ConfigKeys returns all valid config keys in sorted order
+func ConfigKeys() []string {
+	t := reflect.TypeOf(AgentConfig{})
+	keys := make([]string, 0, t.NumField())
+	for i := 0; i < t.NumField(); i++ {
+		name := strings.Split(t.Field(i).Tag.Get("json"), ",")[0]
+		if name != "" && name != "-" {
+			keys = append(keys, name)
+		}
+	}
+	sort.Strings(keys)
+	return keys
+}
+
+// GetField returns the string form of a config value
+func (c *AgentConfig) GetField(key string) (string, bool) {
+	field, ok := c.configField(key)
+	if !ok {
+		return "", false
+	}
+	switch field.Kind() {
+	case reflect.Slice:
+		parts := make([]string, field.Len())
+		for i := 0; i < field.Len(); i++ {
+			parts[i] = fmt.Sprint(field.Index(i).Interface())
+		}
+		return strings.Join(parts, ","), true
+	case reflect.Ptr:
+		if field.IsNil() {
+			return "", true
+		}
+		return fmt.Sprint(field.Elem().Interface()), true
+	default:
+		return fmt.Sprint(field.Interface()), true
+	}
+}
+
+// SetField parses value according to the field's type and assigns it
+func (c *AgentConfig) SetField(key, value string) error {
+	field, ok := c.configField(key)
+	if !ok {
+		return fmt.Errorf("unknown key %q (valid keys: %s)", key, strings.Join(ConfigKeys(), ", "))
+	}
+
+	switch field.Kind() {
+	case reflect.String:
+		field.SetString(value)
+	case reflect.Bool:
+		b, err := strconv.ParseBool(value)
+		if err != nil {
+			return fmt.Errorf("%s expects true or false, got %q", key, value)
+		}
+		field.SetBool(b)
+	case reflect.Int, reflect.Int8, reflect.Int16, reflect.Int32, reflect.Int64:
+		n, err := strconv.ParseInt(value, 10, field.Type().Bits())
+		if err != nil {
+			return fmt.Errorf("%s expects an integer, got %q", key, value)
+		}
+		field.SetInt(n)
+	case reflect.Uint, reflect.Uint8, reflect.Uint16, reflect.Uint32, reflect.Uint64:
+		n, err := strconv.ParseUint(value, 10, field.Type().Bits())
+		if err != nil {
+			return fmt.Errorf("%s expects a non-negative integer, got %q", key, value)
+		}
+		field.SetUint(n)
+	case reflect.Float32, reflect.Float64:
+		f, err := strconv.ParseFloat(value, field.Type().Bits())
+		if err != nil {
+			return fmt.Errorf("%s expects a number, got %q", key, value)
+		}
+		field.SetFloat(f)
+	case reflect.Ptr:
+		switch field.Type().Elem().Kind() {
+		case reflect.Bool:
+			b, err := strconv.ParseBool(value)
+			if err != nil {
+				return fmt.Errorf("%s expects true or false, got %q", key, value)
+			}
+			field.Set(reflect.ValueOf(&b))
+		case reflect.Int:
+			n, err := strconv.Atoi(value)
+			if err != nil {
+				return fmt.Errorf("%s expects an integer, got %q", key, value)
+			}
+			field.Set(reflect.ValueOf(&n))
+		default:
+			return fmt.Errorf("%s cannot be set from the command line", key)
+		}
+	case reflect.Slice:
+		if field.Type().Elem().Kind() != reflect.String {
+			return fmt.Errorf("%s cannot be set from the command line", key)
+		}
+		// An empty value sets an empty list, not nil, so it is kept
+		items := []string{}
+		for _, item := range strings.Split(value, ",") {
+			if item = strings.TrimSpace(item); item != "" {
+				items = append(items, item)
+			}
+		}
+		field.Set(reflect.ValueOf(items))
+	default:
+		return fmt.Errorf("%s cannot be set from the command line", key)
+	}
+
+	return c.Validate()
+}
+
+// Validate checks values that would otherwise break agent startup
+func (c *AgentConfig) Validate() error {
+	if c.DashboardURL != "" && !strings.HasPrefix(c.DashboardURL, "http://") && !strings.HasPrefix(c.DashboardURL, "https://") {
+		return fmt.Errorf("dashboard_url must start with http:// or https://")
+	}
+	if c.IntervalSecs == 0 {
+		return fmt.Errorf("interval_secs must be at least 1")
+	}
+	if c.MaxOfflineRecords < 0 || c.AggregationSecs < 0 || c.BatchSize < 0 {
+		return fmt.Errorf("max_offline_records, aggregation_secs and batch_size must not be negative")
+	}
+	if c.LogMaxSize < 0 || c.LogKeepFiles() < 0 {
+		return fmt.Errorf("log_max_size and log_keep must not be negative")
+	}
+	if c.Encoding != "" && c.Encoding != EncodingJSON && c.Encoding != EncodingMsgpack {
+		return fmt.Errorf("encoding must be %q or %q", EncodingJSON, EncodingMsgpack)
+	}
+	if c.ProxyURL != "" {
+		if _, err := parseProxyURL(c.ProxyURL); err != nil {
+			return err
+		}
+	}
+	return nil
+}
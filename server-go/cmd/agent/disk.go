@@ -14,8 +14,57 @@ import (
 	"github.com/shirou/gopsutil/v4/disk"
 )
 
+// DefaultDiskExclude hides pseudo filesystems that clutter container hosts
+var DefaultDiskExclude = []string{"tmpfs", "devtmpfs", "overlay", "squashfs"}
+
+// DiskFilter decides which disks and partitions are reported. Patterns are
+// globs matched against the mount point, filesystem type or device name.
+// When Include is non-empty it takes precedence and Exclude is ignored.
+type DiskFilter struct {
+	Include []string
+	Exclude []string
+}
+
+func matchAnyPattern(patterns []string, values ...string) bool {
+	for _, pattern := range patterns {
+		for _, v := range values {
+			if v == "" {
+				continue
+			}
+			if ok, _ := filepath.Match(pattern, v); ok {
+				return true
+			}
+		}
+	}
+	return false
+}
+
+// allows reports whether a mount/device with the given fs type is reported
+func (f *DiskFilter) allows(values ...string) bool {
+	if f == nil {
+		return true
+	}
+	if len(f.Include) > 0 {
+		return matchAnyPattern(f.Include, values...)
+	}
+	return !matchAnyPattern(f.Exclude, values...)
+}
+
+// allowsDisk reports whether a physical disk is reported, based on its
+// device name and the mount points mapped to it
+func (f *DiskFilter) allowsDisk(d *DiskMetrics) bool {
+	if f == nil {
+		return true
+	}
+	values := append([]string{d.Name, "/dev/" + d.Name}, d.MountPoints...)
+	if len(f.Include) > 0 {
+		return matchAnyPattern(f.Include, values...)
+	}
+	return !matchAnyPattern(f.Exclude, d.Name, "/dev/"+d.Name)
+}
+
 // collectPhysicalDisks collects physical disk information with IO speed
-func collectPhysicalDisks(currentIO map[string]disk.IOCountersStat, lastIO map[string]disk.IOCountersStat, lastTime time.Time) []DiskMetrics {
+func collectPhysicalDisks(currentIO map[string]disk.IOCountersStat, lastIO map[string]disk.IOCountersStat, lastTime time.Time, filter *DiskFilter) []DiskMetrics {
 	var disks []DiskMetrics
 
 	switch runtime.GOOS {
@@ -83,6 +132,9 @@ func collectPhysicalDisks(currentIO map[string]disk.IOCountersStat, lastIO map[s
 				if strings.HasPrefix(mountPoint, "/snap") || strings.HasPrefix(mountPoint, "/boot/efi") {
 					continue
 				}
+				if !filter.allows(mountPoint, p.Fstype, partName) {
+					continue
+				}
 
 				// Find base device name
 				baseName := strings.TrimPrefix(partName, "/dev/")
@@ -102,6 +154,8 @@ func collectPhysicalDisks(currentIO map[string]disk.IOCountersStat, lastIO map[s
 					if usage, err := disk.Usage(p.Mountpoint); err == nil {
 						partUsed := usage.Total - usage.Free
 						diskMetrics.Used += partUsed
+						diskMetrics.InodesTotal += usage.InodesTotal
+						diskMetrics.InodesUsed += usage.InodesUsed
 					}
 				}
 			}
@@ -112,6 +166,9 @@ func collectPhysicalDisks(currentIO map[string]disk.IOCountersStat, lastIO map[s
 				if d.Total > 0 {
 					d.UsagePercent = float32(float64(d.Used) / float64(d.Total) * 100)
 				}
+				if d.InodesTotal > 0 {
+					d.InodesUsagePercent = float32(float64(d.InodesUsed) / float64(d.InodesTotal) * 100)
+				}
 
 				// Calculate IO speed for this disk
 				// On Linux, /proc/diskstats contains both physical disks (sda, nvme0n1) and partitions (sda1, nvme0n1p1)
@@ -171,6 +228,9 @@ func collectPhysicalDisks(currentIO map[string]disk.IOCountersStat, lastIO map[s
 					d.WriteSpeed = writeSpeed
 				}
 
+				if !filter.allowsDisk(d) {
+					continue
+				}
 				disks = append(disks, *d)
 			}
 		}
@@ -186,6 +246,9 @@ func collectPhysicalDisks(currentIO map[string]disk.IOCountersStat, lastIO map[s
 			if strings.HasPrefix(mount, "/System") || strings.Contains(name, "synthesized") {
 				continue
 			}
+			if !filter.allows(mount, p.Fstype, name) {
+				continue
+			}
 
 			usage, err := disk.Usage(mount)
 			if err != nil {
@@ -195,12 +258,15 @@ func collectPhysicalDisks(currentIO map[string]disk.IOCountersStat, lastIO map[s
 			diskName := strings.TrimPrefix(name, "/dev/")
 			if _, exists := physicalDisks[diskName]; !exists {
 				physicalDisks[diskName] = &DiskMetrics{
-					Name:         diskName,
-					Total:        usage.Total,
-					Used:         usage.Used,
-					UsagePercent: float32(usage.UsedPercent),
-					DiskType:     "SSD", // Most Macs use SSD
-					MountPoints:  []string{mount},
+					Name:               diskName,
+					Total:              usage.Total,
+					Used:               usage.Used,
+					UsagePercent:       float32(usage.UsedPercent),
+					DiskType:           "SSD", // Most Macs use SSD
+					MountPoints:        []string{mount},
+					InodesTotal:        usage.InodesTotal,
+					InodesUsed:         usage.InodesUsed,
+					InodesUsagePercent: float32(usage.InodesUsedPercent),
 				}
 			}
 		}
@@ -286,7 +352,7 @@ func collectPhysicalDisks(currentIO map[string]disk.IOCountersStat, lastIO map[s
 			partitions, _ := disk.Partitions(false)
 			for _, p := range partitions {
 				mount := p.Mountpoint
-				if mount != "" {
+				if mount != "" && filter.allows(mount, p.Fstype, p.Device) {
 					if usage, err := disk.Usage(mount); err == nil {
 						// On Windows, report partition usage directly if no physical disks found
 						if len(physicalDisks) == 0 {
@@ -340,6 +406,9 @@ func collectPhysicalDisks(currentIO map[string]disk.IOCountersStat, lastIO map[s
 					d.WriteSpeed = writeSpeed
 				}
 
+				if !filter.allowsDisk(d) {
+					continue
+				}
 				disks = append(disks, *d)
 			}
 		}
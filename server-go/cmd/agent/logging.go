@@ -0,0 +1,184 @@
+package main
+
+import (
+	"fmt"
+	"io"
+	"log"
+	"os"
+	"path/filepath"
+	"strings"
+	"sync"
+)
+
+const (
+	DefaultLogMaxSizeMB = 10
+	DefaultLogKeep      = 3
+	DefaultLogFilename  = "vstats-agent.log"
+)
+
+// Log levels, lowest to highest
+const (
+	LogLevelDebug = iota
+	LogLevelInfo
+	LogLevelWarn
+	LogLevelError
+)
+
+var logLevel = LogLevelInfo
+
+// parseLogLevel maps a level name to a log level. Accepts the RUST_LOG
+// values older service files still set.
+func parseLogLevel(s string) (int, bool) {
+	switch strings.ToLower(strings.TrimSpace(s)) {
+	case "trace", "debug":
+		return LogLevelDebug, true
+	case "info", "":
+		return LogLevelInfo, true
+	case "warn", "warning":
+		return LogLevelWarn, true
+	case "error":
+		return LogLevelError, true
+	}
+	return LogLevelInfo, false
+}
+
+// logAt logs when level is at or above the configured level. The running
+// agent logs through the helpers below; plain log.Printf always shows and is
+// left to the interactive subcommands.
+func logAt(level int, format string, args ...interface{}) {
+	if level >= logLevel {
+		log.Printf(format, args...)
+	}
+}
+
+// logDebugf logs only when the level is debug
+func logDebugf(format string, args ...interface{}) {
+	logAt(LogLevelDebug, "[DEBUG] "+format, args...)
+}
+
+func logInfof(format string, args ...interface{}) {
+	logAt(LogLevelInfo, format, args...)
+}
+
+func logWarnf(format string, args ...interface{}) {
+	logAt(LogLevelWarn, format, args...)
+}
+
+func logErrorf(format string, args ...interface{}) {
+	logAt(LogLevelError, format, args...)
+}
+
+// RotatingFile is an io.Writer that rotates the file once it exceeds maxSize,
+// keeping at most keep old files (name.1 is the newest).
+type RotatingFile struct {
+	mu      sync.Mutex
+	path    string
+	maxSize int64
+	keep    int
+	file    *os.File
+	size    int64
+}
+
+// NewRotatingFile opens (or creates) the log file for appending
+func NewRotatingFile(path string, maxSizeMB, keep int) (*RotatingFile, error) {
+	if maxSizeMB <= 0 {
+		maxSizeMB = DefaultLogMaxSizeMB
+	}
+	if keep < 0 {
+		keep = 0
+	}
+	rf := &RotatingFile{
+		path:    path,
+		maxSize: int64(maxSizeMB) * 1024 * 1024,
+		keep:    keep,
+	}
+	if err := rf.open(); err != nil {
+		return nil, err
+	}
+	return rf, nil
+}
+
+func (rf *RotatingFile) open() error {
+	if err := os.MkdirAll(filepath.Dir(rf.path), 0755); err != nil {
+		return fmt.Errorf("failed to create log directory: %w", err)
+	}
+	f, err := os.OpenFile(rf.path, os.O_CREATE|os.O_WRONLY|os.O_APPEND, 0640)
+	if err != nil {
+		return fmt.Errorf("failed to open log file: %w", err)
+	}
+	info, err := f.Stat()
+	if err != nil {
+		f.Close()
+		return err
+	}
+	rf.file = f
+	rf.size = info.Size()
+	return nil
+}
+
+func (rf *RotatingFile) Write(p []byte) (int, error) {
+	rf.mu.Lock()
+	defer rf.mu.Unlock()
+
+	if rf.size+int64(len(p)) > rf.maxSize && rf.size > 0 {
+		if err := rf.rotate(); err != nil {
+			fmt.Fprintf(os.Stderr, "Failed to rotate log file: %v\n", err)
+		}
+	}
+
+	n, err := rf.file.Write(p)
+	rf.size += int64(n)
+	return n, err
+}
+
+// rotate shifts name.N-1 -> name.N, name -> name.1 and prunes the oldest
+func (rf *RotatingFile) rotate() error {
+	rf.file.Close()
+
+	if rf.keep == 0 {
+		os.Remove(rf.path)
+	} else {
+		os.Remove(fmt.Sprintf("%s.%d", rf.path, rf.keep))
+		for i := rf.keep - 1; i >= 1; i-- {
+			os.Rename(fmt.Sprintf("%s.%d", rf.path, i), fmt.Sprintf("%s.%d", rf.path, i+1))
+		}
+		os.Rename(rf.path, rf.path+".1")
+	}
+
+	return rf.open()
+}
+
+// Close closes the underlying file
+func (rf *RotatingFile) Close() error {
+	rf.mu.Lock()
+	defer rf.mu.Unlock()
+	return rf.file.Close()
+}
+
+// SetupLogging configures the log level and optional file output.
+// Level priority: --log-level flag > VSTATS_LOG_LEVEL > RUST_LOG > info.
+func SetupLogging(config *AgentConfig, flagLevel string) {
+	level := flagLevel
+	if level == "" {
+		level = os.Getenv("VSTATS_LOG_LEVEL")
+	}
+	if level == "" {
+		level = os.Getenv("RUST_LOG")
+	}
+	if parsed, ok := parseLogLevel(level); ok {
+		logLevel = parsed
+	} else {
+		log.Printf("Warning: unknown log level %q, using info", level)
+	}
+
+	var out io.Writer = os.Stderr
+	if config.LogFile != "" {
+		rf, err := NewRotatingFile(config.LogFile, config.LogMaxSize, config.LogKeepFiles())
+		if err != nil {
+			log.Printf("Warning: file logging disabled: %v", err)
+		} else {
+			out = rf
+		}
+	}
+	log.SetOutput(out)
+}
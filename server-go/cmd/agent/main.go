@@ -2,14 +2,21 @@ package main
 
 import (
 	"bytes"
+	"crypto/sha256"
+	"encoding/hex"
 	"encoding/json"
 	"fmt"
 	"io"
 	"log"
+	"net"
 	"net/http"
 	"os"
 	"os/exec"
+	"path/filepath"
 	"runtime"
+	"slices"
+	"strconv"
+	"strings"
 	"time"
 
 	"github.com/shirou/gopsutil/v4/host"
@@ -31,7 +38,7 @@ func main() {
 			os.Exit(0)
 		case "register":
 			if len(os.Args) < 5 {
-				fmt.Println("Usage: vstats-agent register --server <server_url> --token <admin_token> [--name <server_name>]")
+				fmt.Println("Usage: vstats-agent register --server <server_url> --token <admin_token> [--name <server_name>] [--location <location>] [--provider <provider>] [--tag <tag>] [--interval <secs>] [--proxy <proxy_url>] [--config <path>] [--force | --replace]")
 				os.Exit(1)
 			}
 			handleRegister()
@@ -45,6 +52,15 @@ func main() {
 		case "show-config":
 			handleShowConfig()
 			return
+		case "check", "--check":
+			handleCheck()
+			return
+		case "set":
+			handleSet()
+			return
+		case "update":
+			handleUpdate()
+			return
 		}
 	}
 
@@ -54,34 +70,67 @@ func main() {
 
 func runAgent() {
 	configPath := DefaultConfigPath()
-	if len(os.Args) > 2 && os.Args[1] == "run" {
-		// Allow custom config path
-		for i, arg := range os.Args {
-			if arg == "--config" && i+1 < len(os.Args) {
-				configPath = os.Args[i+1]
-				break
-			}
+	var logLevelFlag, pidFile string
+	for i, arg := range os.Args {
+		if i+1 >= len(os.Args) {
+			break
+		}
+		switch arg {
+		case "--config":
+			// Allow custom config path
+			configPath = os.Args[i+1]
+		case "--log-level":
+			logLevelFlag = os.Args[i+1]
+		case "--pid-file":
+			pidFile = os.Args[i+1]
 		}
 	}
 
-	log.Printf("Loading config from %s", configPath)
-
 	config, err := LoadConfig(configPath)
 	if err != nil {
 		log.Fatalf("Failed to load config: %v", err)
 	}
 
-	log.Println("Starting vStats agent")
-	log.Printf("  Server ID: %s", config.ServerID)
-	log.Printf("  Dashboard: %s", config.DashboardURL)
-	log.Printf("  Interval: %ds", config.IntervalSecs)
+	SetupLogging(config, logLevelFlag)
+	logInfof("Loaded config from %s", configPath)
+	if err := SetProxy(config.ProxyURL); err != nil {
+		log.Fatalf("Invalid config: %v", err)
+	}
+	memoryModulesSnapshotDir = filepath.Dir(configPath)
+
+	if pidFile != "" {
+		if err := WritePIDFile(pidFile); err != nil {
+			log.Fatalf("%v", err)
+		}
+		logInfof("Wrote pid file %s", pidFile)
+	}
+
+	logInfof("Starting vStats agent")
+	logInfof("  Server ID: %s", config.ServerID)
+	logInfof("  Dashboard: %s", config.DashboardURL)
+	logInfof("  Interval: %ds", config.IntervalSecs)
+	if u := proxyURL.Load(); u != nil {
+		logInfof("  Proxy: %s", u.Redacted())
+	}
+
+	// Check whether the previous run installed an update that needs verifying
+	pendingUpdate := CheckPendingUpdate()
 
 	client := NewWebSocketClient(config)
+	client.configPath = configPath
+	if pendingUpdate != nil {
+		client.SetPendingUpdate(pendingUpdate)
+	}
+	SetupReloadHandler(client, configPath)
+	SetupShutdownHandler(client)
 	client.Run()
 }
 
 func handleRegister() {
-	var serverURL, token, name string
+	var serverURL, token, name, location, provider, tag, proxy string
+	var interval uint64 = 5
+	var force, replace bool
+	configPath := DefaultConfigPath()
 
 	for i := 2; i < len(os.Args); i++ {
 		switch os.Args[i] {
@@ -100,6 +149,45 @@ func handleRegister() {
 				name = os.Args[i+1]
 				i++
 			}
+		case "--location":
+			if i+1 < len(os.Args) {
+				location = os.Args[i+1]
+				i++
+			}
+		case "--provider":
+			if i+1 < len(os.Args) {
+				provider = os.Args[i+1]
+				i++
+			}
+		case "--tag":
+			if i+1 < len(os.Args) {
+				tag = os.Args[i+1]
+				i++
+			}
+		case "--proxy":
+			if i+1 < len(os.Args) {
+				proxy = os.Args[i+1]
+				i++
+			}
+		case "--interval":
+			if i+1 < len(os.Args) {
+				secs, err := strconv.ParseUint(os.Args[i+1], 10, 64)
+				if err != nil || secs == 0 {
+					fmt.Println("Error: --interval must be a positive number of seconds")
+					os.Exit(1)
+				}
+				interval = secs
+				i++
+			}
+		case "--config":
+			if i+1 < len(os.Args) {
+				configPath = os.Args[i+1]
+				i++
+			}
+		case "--force":
+			force = true
+		case "--replace":
+			replace = true
 		}
 	}
 
@@ -118,40 +206,56 @@ func handleRegister() {
 		}
 	}
 
+	// Registering again would orphan the server entry of the existing config
+	existing, err := LoadConfigFile(configPath)
+	if proxy == "" && err == nil {
+		proxy = existing.ProxyURL
+	}
+	if err := SetProxy(proxy); err != nil {
+		fmt.Printf("Error: %v\n", err)
+		os.Exit(1)
+	}
+	if err == nil && existing.ServerID != "" {
+		switch {
+		case replace:
+			oldURL := existing.DashboardURL
+			if oldURL == "" {
+				oldURL = serverURL
+			}
+			log.Printf("Unregistering existing server %s from %s", existing.ServerID, oldURL)
+			if err := unregisterFromDashboard(oldURL, token, existing.ServerID); err != nil {
+				log.Fatalf("%v", err)
+			}
+		case force:
+			log.Printf("Overwriting existing registration (server %s)", existing.ServerID)
+		default:
+			fmt.Printf("Error: %s already registers this agent as server %s\n", configPath, existing.ServerID)
+			fmt.Println("  --replace  remove the old server from the dashboard and register again")
+			fmt.Println("  --force    register anyway (the old server entry stays on the dashboard)")
+			os.Exit(1)
+		}
+	}
+
 	log.Printf("Registering with dashboard at %s", serverURL)
 	log.Printf("  Name: %s", name)
 
 	// Register with server
-	reqBody := map[string]string{
-		"name":     name,
-		"location": "",
-		"provider": "",
-	}
-
-	reqData, _ := json.Marshal(reqBody)
-	req, _ := http.NewRequest("POST", fmt.Sprintf("%s/api/agent/register", serverURL), bytes.NewBuffer(reqData))
-	req.Header.Set("Authorization", fmt.Sprintf("Bearer %s", token))
-	req.Header.Set("Content-Type", "application/json")
-
-	client := &http.Client{}
-	resp, err := client.Do(req)
+	registerResp, err := registerWithDashboard(serverURL, token, RegisterRequest{
+		Name:      name,
+		Location:  location,
+		Provider:  provider,
+		Tag:       tag,
+		MachineID: machineID(),
+	})
 	if err != nil {
-		log.Fatalf("Failed to send registration request: %v", err)
-	}
-	defer resp.Body.Close()
-
-	if resp.StatusCode != http.StatusOK {
-		body, _ := io.ReadAll(resp.Body)
-		log.Fatalf("Registration failed (%d): %s", resp.StatusCode, string(body))
-	}
-
-	var registerResp RegisterResponse
-	if err := json.NewDecoder(resp.Body).Decode(&registerResp); err != nil {
-		log.Fatalf("Failed to parse registration response: %v", err)
+		log.Fatalf("%v", err)
 	}
 
 	log.Println("Registration successful!")
 	log.Printf("  Server ID: %s", registerResp.ID)
+	if registerResp.Existing {
+		log.Println("  Matched the existing server for this machine; issued a new token")
+	}
 
 	// Create config
 	config := &AgentConfig{
@@ -159,12 +263,13 @@ func handleRegister() {
 		ServerID:     registerResp.ID,
 		AgentToken:   registerResp.Token,
 		ServerName:   name,
-		Location:     "",
-		Provider:     "",
-		IntervalSecs: 5,
+		Location:     location,
+		Provider:     provider,
+		Tag:          tag,
+		IntervalSecs: interval,
+		ProxyURL:     proxy,
 	}
 
-	configPath := DefaultConfigPath()
 	if err := SaveConfig(config, configPath); err != nil {
 		log.Fatalf("Failed to save config: %v", err)
 	}
@@ -177,18 +282,106 @@ func handleRegister() {
 	fmt.Println("  vstats-agent run")
 	fmt.Println()
 	fmt.Println("Or install as a service:")
-	fmt.Println("  sudo vstats-agent install")
+	fmt.Println("  sudo vstats-agent install [--user vstats|root]")
+}
+
+// registerWithDashboard creates a new server entry using an admin token and
+// returns its id and agent token
+func registerWithDashboard(serverURL, adminToken string, reg RegisterRequest) (*RegisterResponse, error) {
+	reqData, _ := json.Marshal(reg)
+	req, _ := http.NewRequest("POST", fmt.Sprintf("%s/api/agent/register", serverURL), bytes.NewBuffer(reqData))
+	req.Header.Set("Authorization", fmt.Sprintf("Bearer %s", adminToken))
+	req.Header.Set("Content-Type", "application/json")
+
+	client := newHTTPClient(30 * time.Second)
+	resp, err := client.Do(req)
+	if err != nil {
+		return nil, fmt.Errorf("failed to send registration request: %w", proxyError(err))
+	}
+	defer resp.Body.Close()
+
+	if resp.StatusCode != http.StatusOK {
+		body, _ := io.ReadAll(resp.Body)
+		return nil, fmt.Errorf("registration failed (%d): %s", resp.StatusCode, string(body))
+	}
+
+	var registerResp RegisterResponse
+	if err := json.NewDecoder(resp.Body).Decode(&registerResp); err != nil {
+		return nil, fmt.Errorf("failed to parse registration response: %w", err)
+	}
+	return &registerResp, nil
+}
+
+// unregisterFromDashboard deletes a server entry using an admin token.
+// A server that no longer exists is not an error.
+func unregisterFromDashboard(serverURL, adminToken, serverID string) error {
+	req, _ := http.NewRequest("DELETE", fmt.Sprintf("%s/api/agent/register/%s", serverURL, serverID), nil)
+	req.Header.Set("Authorization", fmt.Sprintf("Bearer %s", adminToken))
+
+	client := newHTTPClient(30 * time.Second)
+	resp, err := client.Do(req)
+	if err != nil {
+		return fmt.Errorf("failed to send unregister request: %w", proxyError(err))
+	}
+	defer resp.Body.Close()
+
+	switch resp.StatusCode {
+	case http.StatusOK:
+		return nil
+	case http.StatusNotFound:
+		log.Printf("Server %s was already removed from the dashboard", serverID)
+		return nil
+	}
+	body, _ := io.ReadAll(resp.Body)
+	return fmt.Errorf("unregister failed (%d): %s", resp.StatusCode, string(body))
+}
+
+// machineID returns a stable identifier for this host: a hash of the
+// systemd/dbus machine id, hostname and primary MAC address. VMs cloned from
+// one image share a machine id, so it is never used alone; otherwise a clone
+// registering would take over the original's server entry.
+func machineID() string {
+	var id string
+	for _, path := range []string{"/etc/machine-id", "/var/lib/dbus/machine-id"} {
+		if data, err := os.ReadFile(path); err == nil {
+			if id = strings.TrimSpace(string(data)); id != "" {
+				break
+			}
+		}
+	}
+
+	hostname, _ := os.Hostname()
+	var mac string
+	if ifaces, err := net.Interfaces(); err == nil {
+		for _, iface := range ifaces {
+			if iface.Flags&net.FlagLoopback == 0 && len(iface.HardwareAddr) > 0 {
+				mac = iface.HardwareAddr.String()
+				break
+			}
+		}
+	}
+	if id == "" && hostname == "" && mac == "" {
+		return ""
+	}
+	sum := sha256.Sum256([]byte(id + "|" + hostname + "|" + mac))
+	return hex.EncodeToString(sum[:])
 }
 
 func handleInstall() {
 	configPath := DefaultConfigPath()
+	serviceUser := DefaultServiceUser
 
-	// Check for --config flag
+	// Check for --config and --user flags
 	for i, arg := range os.Args {
-		if arg == "--config" && i+1 < len(os.Args) {
-			configPath = os.Args[i+1]
+		if i+1 >= len(os.Args) {
 			break
 		}
+		switch arg {
+		case "--config":
+			configPath = os.Args[i+1]
+		case "--user":
+			serviceUser = os.Args[i+1]
+		}
 	}
 
 	// Verify config file exists
@@ -199,7 +392,7 @@ func handleInstall() {
 	exe, _ := os.Executable()
 
 	if runtime.GOOS == "linux" {
-		installSystemd(exe, configPath)
+		installSystemd(exe, configPath, serviceUser)
 	} else if runtime.GOOS == "darwin" {
 		installLaunchd(exe, configPath)
 	} else if runtime.GOOS == "windows" {
@@ -240,11 +433,78 @@ func handleShowConfig() {
 	fmt.Printf("  Server Name:    %s\n", config.ServerName)
 	fmt.Printf("  Location:       %s\n", config.Location)
 	fmt.Printf("  Provider:       %s\n", config.Provider)
+	fmt.Printf("  Tag:            %s\n", config.Tag)
 	fmt.Printf("  Interval:       %ds\n", config.IntervalSecs)
 }
 
-func installSystemd(exe, configPath string) {
-	serviceContent := fmt.Sprintf(`[Unit]
+func handleSet() {
+	configPath := DefaultConfigPath()
+	var args []string
+	list, reload := false, false
+	for i := 2; i < len(os.Args); i++ {
+		switch os.Args[i] {
+		case "--config":
+			if i+1 < len(os.Args) {
+				configPath = os.Args[i+1]
+				i++
+			}
+		case "--list":
+			list = true
+		case "--reload":
+			reload = true
+		default:
+			args = append(args, os.Args[i])
+		}
+	}
+
+	config, err := LoadConfigFile(configPath)
+	if err != nil {
+		fmt.Printf("Error: %v\n", err)
+		os.Exit(1)
+	}
+
+	if list {
+		for _, key := range ConfigKeys() {
+			value, _ := config.GetField(key)
+			fmt.Printf("%s=%s\n", key, value)
+		}
+		return
+	}
+
+	if len(args) != 2 {
+		fmt.Println("Usage: vstats-agent set <key> <value> [--config <path>] [--reload]")
+		fmt.Println("       vstats-agent set --list [--config <path>]")
+		fmt.Printf("Valid keys: %s\n", strings.Join(ConfigKeys(), ", "))
+		os.Exit(1)
+	}
+
+	if err := config.SetField(args[0], args[1]); err != nil {
+		fmt.Printf("Error: %v\n", err)
+		os.Exit(1)
+	}
+
+	if err := SaveConfig(config, configPath); err != nil {
+		fmt.Printf("Error: %v\n", err)
+		os.Exit(1)
+	}
+
+	value, _ := config.GetField(args[0])
+	fmt.Printf("Set %s=%s in %s\n", args[0], value, configPath)
+
+	if reload {
+		if err := findAndSignalAgent(); err != nil {
+			fmt.Printf("Warning: %v\n", err)
+			fmt.Println("Restart the agent for the change to take effect.")
+		}
+	} else {
+		fmt.Println("Run with --reload (or restart the agent) for the change to take effect.")
+	}
+}
+
+func installSystemd(exe, configPath, serviceUser string) {
+	var serviceContent string
+	if serviceUser == "root" {
+		serviceContent = fmt.Sprintf(`[Unit]
 Description=vStats Monitoring Agent
 After=network-online.target
 Wants=network-online.target
@@ -260,6 +520,49 @@ Environment=RUST_LOG=info
 [Install]
 WantedBy=multi-user.target
 `, exe, configPath)
+	} else {
+		writablePaths := prepareUnprivilegedInstall(configPath, serviceUser)
+		serviceContent = fmt.Sprintf(`[Unit]
+Description=vStats Monitoring Agent
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+Type=simple
+User=%[1]s
+Group=%[1]s
+ExecStart=%[2]s run --config %[3]s
+Restart=always
+RestartSec=10
+Environment=VSTATS_LOG_LEVEL=info
+
+# ICMP ping is the only privileged operation the collector needs
+AmbientCapabilities=CAP_NET_RAW
+CapabilityBoundingSet=CAP_NET_RAW
+NoNewPrivileges=yes
+
+# Hardening
+ProtectSystem=strict
+ReadWritePaths=%[4]s
+ProtectHome=read-only
+PrivateTmp=yes
+PrivateDevices=yes
+ProtectKernelTunables=yes
+ProtectKernelModules=yes
+ProtectKernelLogs=yes
+ProtectControlGroups=yes
+ProtectClock=yes
+ProtectHostname=yes
+RestrictSUIDSGID=yes
+RestrictRealtime=yes
+RestrictNamespaces=yes
+LockPersonality=yes
+RestrictAddressFamilies=AF_INET AF_INET6 AF_UNIX AF_NETLINK
+
+[Install]
+WantedBy=multi-user.target
+`, serviceUser, exe, configPath, strings.Join(writablePaths, " "))
+	}
 
 	servicePath := "/etc/systemd/system/vstats-agent.service"
 	if err := os.WriteFile(servicePath, []byte(serviceContent), 0644); err != nil {
@@ -301,6 +604,77 @@ WantedBy=multi-user.target
 	fmt.Println("  journalctl -u vstats-agent -f   # View logs")
 }
 
+// DefaultServiceUser is the unprivileged account the systemd service runs as
+const DefaultServiceUser = "vstats"
+
+// serviceOwnedDirs are the only directories handed to the service user.
+// Anything else, such as a config in /etc or a log in /var/log, may hold
+// files that are not the agent's, so it is never chowned.
+var serviceOwnedDirs = []string{"/etc/vstats-agent", "/var/lib/vstats-agent", "/var/log/vstats-agent"}
+
+// prepareUnprivilegedInstall creates the service user and hands it the
+// config, data and log directories. The binary stays root-owned, so a
+// compromised agent cannot replace it; the agent reports that it cannot
+// self-update and updates need root (sudo vstats-agent update).
+// Returns the paths the unit may write.
+func prepareUnprivilegedInstall(configPath, serviceUser string) []string {
+	config, err := LoadConfigFile(configPath)
+	if err != nil {
+		log.Fatalf("Failed to load config: %v", err)
+	}
+
+	writable := []string{filepath.Dir(configPath), config.DataDir}
+	if config.LogFile != "" {
+		writable = append(writable, filepath.Dir(config.LogFile))
+	}
+	for i, dir := range writable {
+		abs, err := filepath.Abs(dir)
+		if err != nil || !slices.Contains(serviceOwnedDirs, abs) {
+			log.Fatalf("Refusing to give %s to user %s: only %s are managed by the installer. "+
+				"Move the config, data_dir or log_file there, or use --user root.",
+				dir, serviceUser, strings.Join(serviceOwnedDirs, ", "))
+		}
+		writable[i] = abs
+	}
+
+	if err := ensureServiceUser(serviceUser); err != nil {
+		log.Fatalf("Failed to create user %s: %v. Use --user root to run as root.", serviceUser, err)
+	}
+
+	// Snapshot root-only hardware details before dropping privileges
+	if err := saveMemoryModulesSnapshot(writable[0]); err != nil {
+		log.Printf("Warning: failed to save memory module details: %v", err)
+	}
+
+	for _, dir := range writable {
+		if err := os.MkdirAll(dir, 0750); err != nil {
+			log.Fatalf("Failed to create %s: %v", dir, err)
+		}
+		if err := exec.Command("chown", "-R", serviceUser+":"+serviceUser, dir).Run(); err != nil {
+			log.Fatalf("Failed to set ownership of %s: %v", dir, err)
+		}
+	}
+
+	log.Printf("Service will run as %s; self-updates need root (sudo vstats-agent update)", serviceUser)
+	return writable
+}
+
+// ensureServiceUser creates a system account without a login shell if needed
+func ensureServiceUser(name string) error {
+	if exec.Command("id", "-u", name).Run() == nil {
+		return nil
+	}
+	if _, err := exec.LookPath("useradd"); err == nil {
+		return exec.Command("useradd", "--system", "--no-create-home", "--user-group",
+			"--shell", "/usr/sbin/nologin", name).Run()
+	}
+	// BusyBox (Alpine)
+	if err := exec.Command("addgroup", "-S", name).Run(); err != nil {
+		return err
+	}
+	return exec.Command("adduser", "-S", "-D", "-H", "-G", name, "-s", "/sbin/nologin", name).Run()
+}
+
 func uninstallSystemd() {
 	exec.Command("systemctl", "stop", "vstats-agent").Run()
 	exec.Command("systemctl", "disable", "vstats-agent").Run()
@@ -310,6 +684,10 @@ func uninstallSystemd() {
 }
 
 func installLaunchd(exe, configPath string) {
+	// launchd has no log rotation, so use the agent's own rotating file log
+	// (stderr only catches crash output)
+	logFile := enableDefaultFileLogging(configPath)
+
 	plistContent := fmt.Sprintf(`<?xml version="1.0" encoding="UTF-8"?>
 <!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
 <plist version="1.0">
@@ -327,13 +705,11 @@ func installLaunchd(exe, configPath string) {
     <true/>
     <key>KeepAlive</key>
     <true/>
-    <key>StandardOutPath</key>
-    <string>/tmp/vstats-agent.log</string>
     <key>StandardErrorPath</key>
-    <string>/tmp/vstats-agent.error.log</string>
+    <string>%s</string>
 </dict>
 </plist>
-`, exe, configPath)
+`, exe, configPath, filepath.Join(filepath.Dir(logFile), "vstats-agent.crash.log"))
 
 	plistPath := "/Library/LaunchDaemons/cc.zsoft.vstats-agent.plist"
 	if err := os.WriteFile(plistPath, []byte(plistContent), 0644); err != nil {
@@ -349,7 +725,23 @@ func installLaunchd(exe, configPath string) {
 	fmt.Println("Useful commands:")
 	fmt.Println("  sudo launchctl list | grep vstats    # Check if running")
 	fmt.Printf("  sudo launchctl unload %s   # Stop\n", plistPath)
-	fmt.Println("  tail -f /tmp/vstats-agent.log        # View logs")
+	fmt.Printf("  tail -f %s   # View logs\n", logFile)
+}
+
+// enableDefaultFileLogging sets log_file next to the config if not already set
+// and returns the log file path
+func enableDefaultFileLogging(configPath string) string {
+	config, err := LoadConfigFile(configPath)
+	if err != nil {
+		log.Fatalf("Failed to load config: %v", err)
+	}
+	if config.LogFile == "" {
+		config.LogFile = filepath.Join(filepath.Dir(configPath), DefaultLogFilename)
+		if err := SaveConfig(config, configPath); err != nil {
+			log.Fatalf("Failed to save config: %v", err)
+		}
+	}
+	return config.LogFile
 }
 
 func uninstallLaunchd() {
@@ -3,16 +3,68 @@ package main
 import (
 	"bufio"
 	"encoding/json"
+	"os"
 	"os/exec"
+	"path/filepath"
 	"runtime"
 	"strconv"
 	"strings"
+	"sync"
 
 	"github.com/shirou/gopsutil/v4/mem"
 )
 
-// collectMemoryModules collects detailed memory module information
+var (
+	memoryModulesOnce   sync.Once
+	cachedMemoryModules []MemoryModule
+
+	// memoryModulesSnapshotDir is the config directory holding the snapshot
+	memoryModulesSnapshotDir = filepath.Dir(DefaultConfigPath())
+)
+
+// MemoryModulesSnapshotFile stores module details read at install time
+const MemoryModulesSnapshotFile = "memory-modules.json"
+
+// collectMemoryModules returns memory module details. Modules don't change
+// while running, so they are read once. On Linux dmidecode needs root; an
+// unprivileged agent falls back to the snapshot taken at install time.
 func collectMemoryModules() []MemoryModule {
+	memoryModulesOnce.Do(func() {
+		cachedMemoryModules = readMemoryModules()
+		if len(cachedMemoryModules) == 0 && runtime.GOOS == "linux" {
+			cachedMemoryModules = loadMemoryModulesSnapshot()
+		}
+	})
+	return cachedMemoryModules
+}
+
+// saveMemoryModulesSnapshot records module details while running as root
+func saveMemoryModulesSnapshot(configDir string) error {
+	modules := readMemoryModules()
+	if len(modules) == 0 {
+		return nil
+	}
+	data, err := json.Marshal(modules)
+	if err != nil {
+		return err
+	}
+	return os.WriteFile(filepath.Join(configDir, MemoryModulesSnapshotFile), data, 0644)
+}
+
+func loadMemoryModulesSnapshot() []MemoryModule {
+	data, err := os.ReadFile(filepath.Join(memoryModulesSnapshotDir, MemoryModulesSnapshotFile))
+	if err != nil {
+		return nil
+	}
+	var modules []MemoryModule
+	if err := json.Unmarshal(data, &modules); err != nil {
+		return nil
+	}
+	return modules
+}
+
+// readMemoryModules collects detailed memory module information
+func readMemoryModules() []MemoryModule {
 	var modules []MemoryModule
 
 	switch runtime.GOOS {
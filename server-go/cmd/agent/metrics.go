@@ -2,7 +2,7 @@ package main
 
 import (
 	"runtime"
-	"strings"
+	"slices"
 	"sync"
 	"time"
 
@@ -12,6 +12,8 @@ import (
 	"github.com/shirou/gopsutil/v4/load"
 	"github.com/shirou/gopsutil/v4/mem"
 	gopsutilnet "github.com/shirou/gopsutil/v4/net"
+
+	"vstats/internal/common"
 )
 
 // MetricsCollector collects system metrics
@@ -29,6 +31,12 @@ type MetricsCollector struct {
 	gatewayIP         string
 	ipAddresses       []string
 	dailyTrafficStats *DailyTrafficStats
+	// Collection watchdog state
+	collectMu       sync.Mutex
+	collectInflight chan SystemMetrics
+	diskFilter      *DiskFilter
+	netFilter       *NetInterfaceFilter
+	watchedServices []string
 }
 
 // NewMetricsCollector creates a new metrics collector
@@ -43,14 +51,7 @@ func NewMetricsCollector() *MetricsCollector {
 
 	// Get initial network totals
 	netIO, _ := gopsutilnet.IOCounters(true)
-	var totalRx, totalTx uint64
-	for _, io := range netIO {
-		name := strings.ToLower(io.Name)
-		if !isVirtualInterface(name) {
-			totalRx += io.BytesRecv
-			totalTx += io.BytesSent
-		}
-	}
+	totalRx, totalTx := sumNetworkCounters(netIO, nil)
 	mc.lastNetworkRx = totalRx
 	mc.lastNetworkTx = totalTx
 
@@ -82,6 +83,40 @@ func (mc *MetricsCollector) SetPingTargets(targets []PingTargetConfig) {
 	mc.customPingTargets = targets
 }
 
+// SetDiskFilter sets the include/exclude patterns applied to disks
+func (mc *MetricsCollector) SetDiskFilter(include, exclude []string) {
+	mc.mu.Lock()
+	defer mc.mu.Unlock()
+	mc.diskFilter = &DiskFilter{Include: include, Exclude: exclude}
+}
+
+// SetNetInterfaceFilter sets the include/exclude patterns applied to network
+// interfaces. A changed filter restarts the speed baseline, so the next
+// sample doesn't report the difference between the old and new totals.
+func (mc *MetricsCollector) SetNetInterfaceFilter(include, exclude []string) {
+	filter := &NetInterfaceFilter{Include: include, Exclude: exclude}
+
+	mc.mu.Lock()
+	defer mc.mu.Unlock()
+	if mc.netFilter != nil && slices.Equal(mc.netFilter.Include, include) && slices.Equal(mc.netFilter.Exclude, exclude) {
+		return
+	}
+	oldRx, oldTx := mc.lastNetworkRx, mc.lastNetworkTx
+	mc.netFilter = filter
+
+	netIO, _ := gopsutilnet.IOCounters(true)
+	mc.lastNetworkRx, mc.lastNetworkTx = sumNetworkCounters(netIO, filter)
+	mc.lastNetworkTime = time.Now()
+	mc.dailyTrafficStats.rebase(oldRx, oldTx, mc.lastNetworkRx, mc.lastNetworkTx)
+}
+
+// SetWatchedServices sets the systemd services reported with each sample
+func (mc *MetricsCollector) SetWatchedServices(names []string) {
+	mc.mu.Lock()
+	defer mc.mu.Unlock()
+	mc.watchedServices = names
+}
+
 // Collect collects all system metrics
 func (mc *MetricsCollector) Collect() SystemMetrics {
 	// CPU metrics
@@ -113,7 +148,7 @@ func (mc *MetricsCollector) Collect() SystemMetrics {
 	// Disk metrics - collect physical disks with IO speed
 	mc.mu.Lock()
 	diskIO, _ := disk.IOCounters()
-	diskMetrics := collectPhysicalDisks(diskIO, mc.lastDiskIO, mc.lastDiskIOTime)
+	diskMetrics := collectPhysicalDisks(diskIO, mc.lastDiskIO, mc.lastDiskIOTime, mc.diskFilter)
 	mc.lastDiskIO = diskIO
 	mc.lastDiskIOTime = time.Now()
 	mc.mu.Unlock()
@@ -123,6 +158,7 @@ func (mc *MetricsCollector) Collect() SystemMetrics {
 	mc.mu.Lock()
 	interfaces, totalRx, totalTx, rxSpeed, txSpeed, dailyRx, dailyTx, now := collectNetworkMetrics(
 		netIO,
+		mc.netFilter,
 		mc.lastNetworkRx,
 		mc.lastNetworkTx,
 		mc.lastNetworkTime,
@@ -160,8 +196,9 @@ func (mc *MetricsCollector) Collect() SystemMetrics {
 	}
 
 	metrics := SystemMetrics{
-		Timestamp: time.Now().UTC(),
-		Hostname:  hostInfo.Hostname,
+		Timestamp:     time.Now().UTC(),
+		Hostname:      hostInfo.Hostname,
+		SchemaVersion: common.MetricsSchemaVersion,
 		OS: OsInfo{
 			Name:    hostInfo.Platform,
 			Version: hostInfo.PlatformVersion,
@@ -169,11 +206,13 @@ func (mc *MetricsCollector) Collect() SystemMetrics {
 			Arch:    runtime.GOARCH,
 		},
 		CPU: CpuMetrics{
-			Brand:     cpuBrand,
-			Cores:     len(cpuPercent),
-			Usage:     totalCPU,
-			Frequency: cpuFreq,
-			PerCore:   perCore,
+			Brand:            cpuBrand,
+			Cores:            len(cpuPercent),
+			Usage:            totalCPU,
+			Frequency:        cpuFreq,
+			PerCore:          perCore,
+			PhysicalCores:    common.CollectPhysicalCores(),
+			PerCoreFrequency: common.CollectPerCoreFrequency(len(cpuPercent), cpuInfo),
 		},
 		Memory: MemoryMetrics{
 			Total:        memInfo.Total,
@@ -204,9 +243,44 @@ func (mc *MetricsCollector) Collect() SystemMetrics {
 		metrics.IPAddresses = mc.ipAddresses
 	}
 
+	mc.mu.RLock()
+	watched := mc.watchedServices
+	mc.mu.RUnlock()
+	metrics.Services = collectServices(watched)
+
 	return metrics
 }
 
+// CollectWithTimeout runs Collect but stops waiting after timeout. On timeout
+// the tick is skipped rather than resending an old sample the server would
+// store as history; heartbeats keep the server online meanwhile. A hung
+// collection (e.g. a stuck NFS mount) is not restarted until it returns.
+// Returns false if there is nothing to send.
+func (mc *MetricsCollector) CollectWithTimeout(timeout time.Duration) (SystemMetrics, bool) {
+	mc.collectMu.Lock()
+	defer mc.collectMu.Unlock()
+
+	if mc.collectInflight == nil {
+		ch := make(chan SystemMetrics, 1)
+		mc.collectInflight = ch
+		go func() {
+			ch <- mc.Collect()
+		}()
+	}
+
+	timer := time.NewTimer(timeout)
+	defer timer.Stop()
+
+	select {
+	case metrics := <-mc.collectInflight:
+		mc.collectInflight = nil
+		return metrics, true
+	case <-timer.C:
+		logWarnf("Warning: metrics collection exceeded %v, skipping tick", timeout)
+		return SystemMetrics{}, false
+	}
+}
+
 // pingLoop runs in the background to periodically collect ping metrics
 func (mc *MetricsCollector) pingLoop() {
 	ticker := time.NewTicker(10 * time.Second)
@@ -130,6 +130,15 @@ func (dts *DailyTrafficStats) updateDailyTraffic(totalRx, totalTx uint64) (daily
 	return dts.DailyRx, dts.DailyTx
 }
 
+// rebase moves the day's starting counters by the change in totals when the
+// counted interfaces change, keeping the traffic accumulated so far
+func (dts *DailyTrafficStats) rebase(oldRx, oldTx, newRx, newTx uint64) {
+	dts.mu.Lock()
+	defer dts.mu.Unlock()
+	dts.DayStartRx = dts.DayStartRx + newRx - oldRx
+	dts.DayStartTx = dts.DayStartTx + newTx - oldTx
+}
+
 // getDailyTraffic returns current daily traffic without updating
 func (dts *DailyTrafficStats) getDailyTraffic() (dailyRx, dailyTx uint64) {
 	dts.mu.RLock()
@@ -297,16 +306,39 @@ func collectIPAddresses() []string {
 	return ips
 }
 
-// isVirtualInterface checks if a network interface is virtual
-func isVirtualInterface(name string) bool {
-	return name == "lo" || name == "lo0" ||
-		strings.HasPrefix(name, "veth") ||
-		strings.HasPrefix(name, "docker") ||
-		strings.HasPrefix(name, "br-") ||
-		strings.HasPrefix(name, "virbr") ||
-		strings.HasPrefix(name, "utun") ||
-		strings.HasPrefix(name, "awdl") ||
-		strings.HasPrefix(name, "llw")
+// DefaultNetInterfaceExclude keeps loopback, container bridges and other
+// virtual interfaces out of the network totals
+var DefaultNetInterfaceExclude = []string{"lo", "lo0", "docker*", "veth*", "br-*", "virbr*", "utun*", "awdl*", "llw*"}
+
+// NetInterfaceFilter decides which interfaces are reported and counted in
+// total_rx/total_tx and the speeds. Patterns are globs matched against the
+// interface name (also lowercased). When Include is non-empty it takes
+// precedence and Exclude is ignored. A nil filter applies the defaults.
+type NetInterfaceFilter struct {
+	Include []string
+	Exclude []string
+}
+
+func (f *NetInterfaceFilter) allows(name string) bool {
+	if f == nil {
+		f = &NetInterfaceFilter{Exclude: DefaultNetInterfaceExclude}
+	}
+	values := []string{name, strings.ToLower(name)}
+	if len(f.Include) > 0 {
+		return matchAnyPattern(f.Include, values...)
+	}
+	return !matchAnyPattern(f.Exclude, values...)
+}
+
+// sumNetworkCounters totals the byte counters of the interfaces the filter allows
+func sumNetworkCounters(netIO []gopsutilnet.IOCountersStat, filter *NetInterfaceFilter) (totalRx, totalTx uint64) {
+	for _, io := range netIO {
+		if filter.allows(io.Name) {
+			totalRx += io.BytesRecv
+			totalTx += io.BytesSent
+		}
+	}
+	return totalRx, totalTx
 }
 
 // getInterfaceDetails gets MAC address and link speed for a network interface
@@ -391,14 +423,13 @@ func getInterfaceDetails(name string) (string, uint32) {
 }
 
 // collectNetworkMetrics collects network interface metrics
-func collectNetworkMetrics(netIO []gopsutilnet.IOCountersStat, lastRx, lastTx uint64, lastTime time.Time, dailyStats *DailyTrafficStats) ([]NetworkInterface, uint64, uint64, uint64, uint64, uint64, uint64, time.Time) {
+func collectNetworkMetrics(netIO []gopsutilnet.IOCountersStat, filter *NetInterfaceFilter, lastRx, lastTx uint64, lastTime time.Time, dailyStats *DailyTrafficStats) ([]NetworkInterface, uint64, uint64, uint64, uint64, uint64, uint64, time.Time) {
 	var interfaces []NetworkInterface
 	var totalRx, totalTx uint64
 
 	for _, io := range netIO {
-		// Filter out virtual interfaces
-		name := strings.ToLower(io.Name)
-		if isVirtualInterface(name) {
+		// Filter out virtual and excluded interfaces
+		if !filter.allows(io.Name) {
 			continue
 		}
 
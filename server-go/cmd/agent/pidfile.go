@@ -0,0 +1,54 @@
+package main
+
+import (
+	"fmt"
+	"os"
+	"path/filepath"
+	"runtime"
+	"strconv"
+	"strings"
+	"syscall"
+)
+
+// pidFilePath is set by `run --pid-file` and removed again on exit, for
+// supervisors without systemd (supervisord, runit, Docker entrypoints)
+var pidFilePath string
+
+// WritePIDFile records the agent's PID, refusing to start if the file points
+// at another live process. Stale files from a crash are overwritten.
+func WritePIDFile(path string) error {
+	if data, err := os.ReadFile(path); err == nil {
+		pid, err := strconv.Atoi(strings.TrimSpace(string(data)))
+		if err == nil && pid != os.Getpid() && processAlive(pid) {
+			return fmt.Errorf("agent already running with pid %d (%s)", pid, path)
+		}
+	}
+
+	if err := os.MkdirAll(filepath.Dir(path), 0755); err != nil {
+		return fmt.Errorf("failed to create pid file directory: %w", err)
+	}
+	if err := os.WriteFile(path, []byte(fmt.Sprintf("%d\n", os.Getpid())), 0644); err != nil {
+		return fmt.Errorf("failed to write pid file: %w", err)
+	}
+	pidFilePath = path
+	return nil
+}
+
+// RemovePIDFile deletes the PID file written by WritePIDFile, if any
+func RemovePIDFile() {
+	if pidFilePath != "" {
+		os.Remove(pidFilePath)
+	}
+}
+
+func processAlive(pid int) bool {
+	p, err := os.FindProcess(pid)
+	if err != nil {
+		return false
+	}
+	// FindProcess only succeeds for existing processes on Windows
+	if runtime.GOOS == "windows" {
+		return true
+	}
+	return p.Signal(syscall.Signal(0)) == nil
+}
@@ -11,6 +11,9 @@ import (
 	"time"
 )
 
+// PingCommandTimeout bounds a single ping invocation (3 probes, 2s wait each)
+const PingCommandTimeout = 6 * time.Second
+
 // collectPingMetrics collects ping metrics for configured targets
 func collectPingMetrics(gatewayIP string, customTargets []PingTargetConfig) *PingMetrics {
 	// If no custom targets configured, return nil (no ping)
@@ -91,7 +94,8 @@ func testTCPConnection(host string, port int) (*float64, string) {
 
 // pingHost performs ICMP ping to a host
 func pingHost(host string) (*float64, float64, string) {
-	ctx, cancel := context.WithTimeout(context.Background(), 3*time.Second)
+	// Hard deadline on top of ping's own -W flag, in case ping never returns
+	ctx, cancel := context.WithTimeout(context.Background(), PingCommandTimeout)
 	defer cancel()
 
 	var cmd *exec.Cmd
@@ -105,7 +109,13 @@ func pingHost(host string) (*float64, float64, string) {
 		cmd = exec.CommandContext(ctx, "ping", "-c", "3", "-W", "2", host)
 	}
 
+	// Don't wait on pipes held open by a killed ping
+	cmd.WaitDelay = time.Second
+
 	output, err := cmd.CombinedOutput()
+	if ctx.Err() == context.DeadlineExceeded {
+		return nil, 100.0, "timeout"
+	}
 	if err != nil {
 		return nil, 100.0, "error"
 	}
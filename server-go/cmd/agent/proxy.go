@@ -0,0 +1,77 @@
+package main
+
+import (
+	"fmt"
+	"net/http"
+	"net/url"
+	"sync/atomic"
+	"time"
+)
+
+// ============================================================================
+// Outbound Proxy
+// ============================================================================
+//
+// With proxy_url set, the dashboard connection, registration, update checks
+// and downloads all go through the proxy (HTTP CONNECT or SOCKS5). A proxy
+// that cannot be reached is reported as such; the agent never falls back to
+// connecting directly. Without proxy_url, HTTP_PROXY/HTTPS_PROXY apply as
+// before.
+
+// proxyURL is the configured proxy, nil for none
+var proxyURL atomic.Pointer[url.URL]
+
+// parseProxyURL checks a proxy_url value
+func parseProxyURL(raw string) (*url.URL, error) {
+	u, err := url.Parse(raw)
+	if err != nil {
+		return nil, fmt.Errorf("proxy_url: %w", err)
+	}
+	if u.Scheme != "http" && u.Scheme != "socks5" {
+		return nil, fmt.Errorf("proxy_url must start with http:// or socks5://")
+	}
+	if u.Host == "" {
+		return nil, fmt.Errorf("proxy_url has no host")
+	}
+	return u, nil
+}
+
+// SetProxy sets the proxy used for all outbound connections; "" clears it
+func SetProxy(raw string) error {
+	if raw == "" {
+		proxyURL.Store(nil)
+		return nil
+	}
+	u, err := parseProxyURL(raw)
+	if err != nil {
+		return err
+	}
+	proxyURL.Store(u)
+	return nil
+}
+
+// proxyFunc returns the proxy selection for HTTP clients and the WebSocket
+// dialer
+func proxyFunc() func(*http.Request) (*url.URL, error) {
+	if u := proxyURL.Load(); u != nil {
+		return http.ProxyURL(u)
+	}
+	return http.ProxyFromEnvironment
+}
+
+// newHTTPClient returns a client that honours proxy_url. A zero timeout
+// means none.
+func newHTTPClient(timeout time.Duration) *http.Client {
+	transport := http.DefaultTransport.(*http.Transport).Clone()
+	transport.Proxy = proxyFunc()
+	return &http.Client{Timeout: timeout, Transport: transport}
+}
+
+// proxyError names the proxy in a connection error, so a dead proxy is not
+// mistaken for an unreachable dashboard
+func proxyError(err error) error {
+	if u := proxyURL.Load(); u != nil && err != nil {
+		return fmt.Errorf("via proxy %s: %w", u.Redacted(), err)
+	}
+	return err
+}
@@ -0,0 +1,65 @@
+package main
+
+import (
+	"bufio"
+	"bytes"
+	"context"
+	"os"
+	"os/exec"
+	"runtime"
+	"strings"
+	"sync"
+	"time"
+)
+
+// serviceCheckTimeout bounds a systemctl call so a hung systemd cannot stall
+// metrics collection
+const serviceCheckTimeout = 2 * time.Second
+
+var systemdNoticeOnce sync.Once
+
+// hasSystemd reports whether the host was booted with systemd, using the same
+// check as sd_booted(3)
+func hasSystemd() bool {
+	if runtime.GOOS != "linux" {
+		return false
+	}
+	info, err := os.Stat("/run/systemd/system")
+	return err == nil && info.IsDir()
+}
+
+// collectServices reports the state of each watched service with a single
+// `systemctl is-active` call. On hosts without systemd it returns nil and
+// logs a notice once.
+func collectServices(names []string) []ServiceStatus {
+	if len(names) == 0 {
+		return nil
+	}
+	if !hasSystemd() {
+		systemdNoticeOnce.Do(func() {
+			logWarnf("watched_services is set but this host does not run systemd; not reporting services")
+		})
+		return nil
+	}
+
+	ctx, cancel := context.WithTimeout(context.Background(), serviceCheckTimeout)
+	defer cancel()
+
+	// is-active prints one state per unit in argument order and exits
+	// non-zero if any unit is inactive, so only the output matters
+	args := append([]string{"is-active", "--"}, names...)
+	out, _ := exec.CommandContext(ctx, "systemctl", args...).Output()
+
+	services := make([]ServiceStatus, len(names))
+	scanner := bufio.NewScanner(bytes.NewReader(out))
+	for i, name := range names {
+		state := "unknown"
+		if scanner.Scan() {
+			if line := strings.TrimSpace(scanner.Text()); line != "" {
+				state = line
+			}
+		}
+		services[i] = ServiceStatus{Name: name, Active: state == "active", State: state}
+	}
+	return services
+}
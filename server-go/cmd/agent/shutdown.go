@@ -0,0 +1,26 @@
+package main
+
+import (
+	"os"
+	"os/signal"
+	"syscall"
+	"time"
+)
+
+// ShutdownTimeout bounds how long a stop waits for the goodbye to be sent
+const ShutdownTimeout = 3 * time.Second
+
+// SetupShutdownHandler sends a goodbye to the server on SIGTERM/SIGINT so a
+// deliberate stop shows up as offline right away rather than as an outage
+func SetupShutdownHandler(client *WebSocketClient) {
+	sigs := make(chan os.Signal, 1)
+	signal.Notify(sigs, os.Interrupt, syscall.SIGTERM)
+
+	go func() {
+		sig := <-sigs
+		logInfof("Received %v, shutting down...", sig)
+		client.Shutdown("shutdown", ShutdownTimeout)
+		RemovePIDFile()
+		os.Exit(0)
+	}()
+}
@@ -0,0 +1,81 @@
+//go:build !windows
+// +build !windows
+
+package main
+
+import (
+	"fmt"
+	"os"
+	"os/signal"
+	"strings"
+	"syscall"
+)
+
+// SetupReloadHandler reloads the config file on SIGHUP
+func SetupReloadHandler(client *WebSocketClient, configPath string) {
+	sigs := make(chan os.Signal, 1)
+	signal.Notify(sigs, syscall.SIGHUP)
+
+	go func() {
+		for range sigs {
+			logInfof("Received SIGHUP, reloading config...")
+			config, err := LoadConfig(configPath)
+			if err != nil {
+				logErrorf("Failed to reload config: %v", err)
+				continue
+			}
+			if err := SetProxy(config.ProxyURL); err != nil {
+				logErrorf("Failed to reload config: %v", err)
+				continue
+			}
+			client.Reload(config)
+		}
+	}()
+}
+
+// findAndSignalAgent finds running vstats-agent processes and sends SIGHUP
+func findAndSignalAgent() error {
+	currentPID := os.Getpid()
+
+	entries, err := os.ReadDir("/proc")
+	if err != nil {
+		return fmt.Errorf("cannot read /proc: %v", err)
+	}
+
+	signaled := 0
+	for _, entry := range entries {
+		pid := 0
+		if _, err := fmt.Sscanf(entry.Name(), "%d", &pid); err != nil || pid == currentPID {
+			continue
+		}
+
+		cmdline, err := os.ReadFile(fmt.Sprintf("/proc/%d/cmdline", pid))
+		if err != nil {
+			continue
+		}
+
+		// Only signal agents that are actually running, not other subcommands
+		args := strings.Split(strings.TrimRight(string(cmdline), "\x00"), "\x00")
+		if !strings.Contains(args[0], "vstats-agent") {
+			continue
+		}
+		if len(args) > 1 && args[1] != "run" {
+			continue
+		}
+
+		proc, err := os.FindProcess(pid)
+		if err != nil {
+			continue
+		}
+		if err := proc.Signal(syscall.SIGHUP); err != nil {
+			return fmt.Errorf("failed to signal process %d: %v", pid, err)
+		}
+		fmt.Printf("Sent SIGHUP to vstats-agent (PID: %d)\n", pid)
+		signaled++
+	}
+
+	if signaled == 0 {
+		return fmt.Errorf("no running vstats-agent found")
+	}
+	return nil
+}
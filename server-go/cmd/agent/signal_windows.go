@@ -0,0 +1,17 @@
+//go:build windows
+// +build windows
+
+package main
+
+import "fmt"
+
+// SetupReloadHandler is a no-op on Windows
+// Windows doesn't support SIGHUP
+func SetupReloadHandler(client *WebSocketClient, configPath string) {
+	// No-op on Windows
+}
+
+// findAndSignalAgent is not supported on Windows
+func findAndSignalAgent() error {
+	return fmt.Errorf("reload is not supported on Windows, restart the service instead: sc stop vstats-agent && sc start vstats-agent")
+}
@@ -3,7 +3,6 @@ package main
 import (
 	"database/sql"
 	"encoding/json"
-	"log"
 	"os"
 	"path/filepath"
 	"sync"
@@ -1030,11 +1029,11 @@ func (mb *MetricsBuffer) flushLocked() {
 		agg.StartTime = mb.buffer[0].Timestamp
 		agg.EndTime = mb.buffer[len(mb.buffer)-1].Timestamp
 		if err := mb.store.StoreAggregated(agg); err != nil {
-			log.Printf("Failed to store aggregated metrics: %v", err)
+			logErrorf("Failed to store aggregated metrics: %v", err)
 		}
 	} else if len(mb.buffer) == 1 {
 		if err := mb.store.Store(mb.buffer[0]); err != nil {
-			log.Printf("Failed to store metrics: %v", err)
+			logErrorf("Failed to store metrics: %v", err)
 		}
 	}
 
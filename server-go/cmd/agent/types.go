@@ -17,14 +17,24 @@ type LoadAverage = common.LoadAverage
 type PingMetrics = common.PingMetrics
 type PingTarget = common.PingTarget
 type PingTargetConfig = common.PingTargetConfig
+type ServiceStatus = common.ServiceStatus
 type AuthMessage = common.AuthMessage
 type MetricsMessage = common.MetricsMessage
+type MetricsDeltaMessage = common.MetricsDeltaMessage
 type ServerResponse = common.ServerResponse
 type RegisterRequest = common.RegisterRequest
 type RegisterResponse = common.RegisterResponse
+type UpdateFailedMessage = common.UpdateFailedMessage
+type GoodbyeMessage = common.GoodbyeMessage
 
 // Batch metrics types for offline sync
 type BatchMetricsMessage = common.BatchMetricsMessage
 type TimestampedMetrics = common.TimestampedMetrics
 type CommonAggregatedMetrics = common.AggregatedMetrics
 type BatchMetricsResponse = common.BatchMetricsResponse
+
+// Wire encodings for the agent protocol
+const (
+	EncodingJSON    = common.EncodingJSON
+	EncodingMsgpack = common.EncodingMsgpack
+)
@@ -0,0 +1,623 @@
+package main
+
+import (
+	"crypto/ed25519"
+	"crypto/sha256"
+	"encoding/base64"
+	"encoding/hex"
+	"encoding/json"
+	"fmt"
+	"io"
+	"net/http"
+	"net/url"
+	"os"
+	"os/exec"
+	"path"
+	"path/filepath"
+	"runtime"
+	"strconv"
+	"strings"
+	"time"
+)
+
+// ============================================================================
+// Self-update (shared by the server `update` command and `vstats-agent update`)
+// ============================================================================
+
+// UpdateInfo describes the result of an update check
+type UpdateInfo struct {
+	Current   string
+	Latest    string
+	Tag       string // Release tag used in the download URL
+	Source    string // "dashboard" or "github"
+	Available bool
+}
+
+// CheckForUpdate finds the latest agent version. The dashboard's version is
+// preferred since agents should match the server they report to; GitHub
+// releases are used if the dashboard can't be reached or config is nil.
+func CheckForUpdate(config *AgentConfig) (*UpdateInfo, error) {
+	info := &UpdateInfo{Current: AgentVersion}
+
+	if config != nil && config.DashboardURL != "" {
+		if version, err := fetchDashboardVersion(config.DashboardURL); err == nil && version != "" && version != "dev" {
+			info.Latest = strings.TrimPrefix(version, "v")
+			info.Tag = "v" + info.Latest
+			info.Source = "dashboard"
+		} else if err != nil {
+			logWarnf("Failed to query dashboard version, falling back to GitHub: %v", err)
+		}
+	}
+
+	if info.Latest == "" {
+		latest, err := fetchLatestGitHubVersion("zsai001", "vstats")
+		if err != nil {
+			return nil, err
+		}
+		info.Tag = *latest
+		info.Latest = strings.TrimPrefix(*latest, "v")
+		info.Source = "github"
+	}
+
+	info.Available = compareVersions(info.Latest, AgentVersion) > 0
+	return info, nil
+}
+
+// compareVersions compares two dotted versions ("1.2.3", "v1.2.10-beta").
+// Pre-release suffixes are ignored. A "dev" build is older than any release.
+func compareVersions(a, b string) int {
+	parse := func(v string) []int {
+		v = strings.TrimPrefix(strings.TrimSpace(v), "v")
+		if i := strings.IndexAny(v, "-+"); i >= 0 {
+			v = v[:i]
+		}
+		var parts []int
+		for _, p := range strings.Split(v, ".") {
+			n, err := strconv.Atoi(p)
+			if err != nil {
+				return nil
+			}
+			parts = append(parts, n)
+		}
+		return parts
+	}
+
+	pa, pb := parse(a), parse(b)
+	if pa == nil || pb == nil {
+		switch {
+		case pa == nil && pb == nil:
+			return 0
+		case pa == nil:
+			return -1
+		default:
+			return 1
+		}
+	}
+	for i := 0; i < len(pa) || i < len(pb); i++ {
+		var x, y int
+		if i < len(pa) {
+			x = pa[i]
+		}
+		if i < len(pb) {
+			y = pb[i]
+		}
+		if x != y {
+			if x > y {
+				return 1
+			}
+			return -1
+		}
+	}
+	return 0
+}
+
+// releaseBinaryName returns the release asset name for this platform
+func releaseBinaryName() string {
+	name := fmt.Sprintf("vstats-agent-%s-%s", runtime.GOOS, runtime.GOARCH)
+	if runtime.GOOS == "windows" {
+		name += ".exe"
+	}
+	return name
+}
+
+// releaseDownloadURL returns the GitHub Releases download URL for a tag
+func releaseDownloadURL(tag string) string {
+	if tag == "latest" {
+		return fmt.Sprintf("https://github.com/zsai001/vstats/releases/latest/download/%s", releaseBinaryName())
+	}
+	return fmt.Sprintf("https://github.com/zsai001/vstats/releases/download/%s/%s", tag, releaseBinaryName())
+}
+
+// UpdateVerification carries the expected checksum of an update binary
+type UpdateVerification struct {
+	SHA256    string // Hex-encoded SHA-256 of the binary
+	Signature string // Base64 ed25519 signature over the lowercase hex checksum
+}
+
+// isOfficialReleaseURL reports whether rawURL points at this project's GitHub
+// releases. The URL is parsed rather than prefix-matched, so dot segments
+// ("/releases/../../other/repo", also percent-encoded) and userinfo or port
+// tricks don't pass.
+func isOfficialReleaseURL(rawURL string) bool {
+	u, err := url.Parse(rawURL)
+	if err != nil || u.Scheme != "https" || u.Host != "github.com" || u.User != nil {
+		return false
+	}
+	if u.RawPath != "" || path.Clean(u.Path) != u.Path {
+		return false
+	}
+	return strings.HasPrefix(u.Path, "/zsai001/vstats/releases/")
+}
+
+// checkUpdatePolicy rejects updates that can't be verified before downloading.
+// A checksum is mandatory for any URL other than the official releases, so a
+// compromised dashboard can't point agents at arbitrary binaries.
+func checkUpdatePolicy(config *AgentConfig, url string, v UpdateVerification) error {
+	if config != nil && config.RequireSignedUpdates {
+		if config.UpdatePublicKey == "" {
+			return fmt.Errorf("require_signed_updates is set but update_public_key is empty")
+		}
+		if v.SHA256 == "" || v.Signature == "" {
+			return fmt.Errorf("refusing unsigned update (require_signed_updates is set)")
+		}
+		return nil
+	}
+	if v.SHA256 == "" && !isOfficialReleaseURL(url) {
+		return fmt.Errorf("refusing update from %s without a sha256 checksum", url)
+	}
+	return nil
+}
+
+// verifyDownload checks the downloaded file's hash and signature
+func verifyDownload(config *AgentConfig, actualSHA256 string, v UpdateVerification) error {
+	if v.SHA256 != "" && !strings.EqualFold(actualSHA256, v.SHA256) {
+		return fmt.Errorf("checksum mismatch: expected %s, got %s", v.SHA256, actualSHA256)
+	}
+	if v.Signature == "" {
+		return nil
+	}
+
+	var publicKey string
+	if config != nil {
+		publicKey = config.UpdatePublicKey
+	}
+	if publicKey == "" {
+		return fmt.Errorf("update is signed but update_public_key is not configured")
+	}
+
+	key, err := base64.StdEncoding.DecodeString(publicKey)
+	if err != nil || len(key) != ed25519.PublicKeySize {
+		return fmt.Errorf("invalid update_public_key")
+	}
+	sig, err := base64.StdEncoding.DecodeString(v.Signature)
+	if err != nil {
+		return fmt.Errorf("invalid signature encoding: %w", err)
+	}
+	if !ed25519.Verify(ed25519.PublicKey(key), []byte(strings.ToLower(v.SHA256)), sig) {
+		return fmt.Errorf("signature verification failed")
+	}
+	return nil
+}
+
+// CanSelfUpdate reports whether this process may replace its own binary.
+// The download, backup and swap all happen next to the executable, so the
+// directory must be writable; an unprivileged service with a root-owned
+// binary cannot update itself and tells the server so at auth.
+func CanSelfUpdate() bool {
+	exe, err := os.Executable()
+	if err != nil {
+		return false
+	}
+	f, err := os.CreateTemp(filepath.Dir(exe), ".vstats-agent-update-check-*")
+	if err != nil {
+		return false
+	}
+	f.Close()
+	os.Remove(f.Name())
+	return true
+}
+
+// ApplyUpdate downloads the binary at url, verifies it, swaps it in for the
+// running executable and restarts the service. On success the process exits.
+func ApplyUpdate(config *AgentConfig, url string, v UpdateVerification) error {
+	if err := checkUpdatePolicy(config, url, v); err != nil {
+		return err
+	}
+
+	currentExe, err := os.Executable()
+	if err != nil {
+		return fmt.Errorf("failed to get current executable path: %w", err)
+	}
+
+	logInfof("Downloading update from: %s", url)
+
+	// Download to a temporary file
+	tempPath := currentExe + ".new"
+	actualSHA256, err := downloadFile(url, tempPath)
+	if err != nil {
+		return fmt.Errorf("failed to download update: %w", err)
+	}
+
+	// Verify before touching the installed binary
+	if err := verifyDownload(config, actualSHA256, v); err != nil {
+		os.Remove(tempPath)
+		return fmt.Errorf("update verification failed: %w", err)
+	}
+
+	logInfof("Download complete, applying update...")
+
+	// On Unix, set execute permissions
+	if runtime.GOOS != "windows" {
+		if err := os.Chmod(tempPath, 0755); err != nil {
+			os.Remove(tempPath)
+			return fmt.Errorf("failed to set permissions: %w", err)
+		}
+	}
+
+	// Backup current executable
+	backupPath := currentExe + ".backup"
+	os.Remove(backupPath)
+	if err := os.Rename(currentExe, backupPath); err != nil {
+		os.Remove(tempPath)
+		return fmt.Errorf("failed to backup current executable: %w", err)
+	}
+
+	// Move new executable to current path
+	if err := os.Rename(tempPath, currentExe); err != nil {
+		// Try to restore backup
+		os.Rename(backupPath, currentExe)
+		return fmt.Errorf("failed to install new executable: %w", err)
+	}
+
+	// Keep the backup until the new binary proves it can connect; the marker
+	// tells the next startup to verify and roll back if needed
+	marker := &UpdateMarker{
+		PreviousVersion: AgentVersion,
+		DownloadURL:     url,
+		BackupPath:      backupPath,
+		StartedAt:       time.Now().UTC(),
+	}
+	if err := marker.Save(); err != nil {
+		logWarnf("Warning: failed to write update marker, rollback disabled: %v", err)
+	}
+
+	logInfof("Update installed successfully! Restarting...")
+	restartService()
+
+	// Exit to allow restart
+	os.Exit(0)
+	return nil
+}
+
+// ============================================================================
+// Update health check and rollback
+// ============================================================================
+
+const (
+	// UpdateGracePeriod is how long a freshly updated agent has to authenticate
+	UpdateGracePeriod = 2 * time.Minute
+	// MaxUpdateStartAttempts rolls back if the new binary keeps crashing on startup
+	MaxUpdateStartAttempts = 3
+)
+
+// UpdateMarker records an in-flight self-update next to the executable
+type UpdateMarker struct {
+	PreviousVersion string    `json:"previous_version"`
+	DownloadURL     string    `json:"download_url,omitempty"`
+	BackupPath      string    `json:"backup_path"`
+	StartedAt       time.Time `json:"started_at"`
+	Attempts        int       `json:"attempts"`
+	// Set once the backup has been restored; the old binary then reports it
+	RolledBack    bool   `json:"rolled_back,omitempty"`
+	FailedVersion string `json:"failed_version,omitempty"`
+	FailureReason string `json:"failure_reason,omitempty"`
+}
+
+func updateMarkerPath() string {
+	exe, err := os.Executable()
+	if err != nil {
+		return ""
+	}
+	return exe + ".update"
+}
+
+// Save writes the marker file
+func (m *UpdateMarker) Save() error {
+	path := updateMarkerPath()
+	if path == "" {
+		return fmt.Errorf("cannot determine executable path")
+	}
+	data, err := json.MarshalIndent(m, "", "  ")
+	if err != nil {
+		return err
+	}
+	return os.WriteFile(path, data, 0600)
+}
+
+// loadUpdateMarker returns the pending update marker, or nil if none
+func loadUpdateMarker() *UpdateMarker {
+	path := updateMarkerPath()
+	if path == "" {
+		return nil
+	}
+	data, err := os.ReadFile(path)
+	if err != nil {
+		return nil
+	}
+	var m UpdateMarker
+	if err := json.Unmarshal(data, &m); err != nil {
+		logWarnf("Warning: ignoring corrupt update marker: %v", err)
+		os.Remove(path)
+		return nil
+	}
+	return &m
+}
+
+func removeUpdateMarker() {
+	if path := updateMarkerPath(); path != "" {
+		os.Remove(path)
+	}
+}
+
+// CheckPendingUpdate runs at startup. If the previous run installed an
+// update, it counts the start attempt and rolls back straight away if the
+// new binary has already crashed too many times. Returns the marker for the
+// caller to confirm (after auth) or report (after a rollback).
+func CheckPendingUpdate() *UpdateMarker {
+	marker := loadUpdateMarker()
+	if marker == nil {
+		return nil
+	}
+
+	if marker.RolledBack {
+		logWarnf("Previous update to %s was rolled back: %s", marker.FailedVersion, marker.FailureReason)
+		return marker
+	}
+
+	marker.Attempts++
+	if err := marker.Save(); err != nil {
+		logWarnf("Warning: failed to update marker: %v", err)
+	}
+
+	logInfof("Verifying update from %s (start attempt %d/%d)", marker.PreviousVersion, marker.Attempts, MaxUpdateStartAttempts)
+	if marker.Attempts > MaxUpdateStartAttempts {
+		rollbackUpdate(marker, fmt.Sprintf("agent restarted %d times without authenticating", marker.Attempts-1))
+	}
+	return marker
+}
+
+// StartUpdateWatchdog rolls back unless confirmed is closed within the grace period
+func StartUpdateWatchdog(marker *UpdateMarker, confirmed <-chan struct{}) {
+	go func() {
+		select {
+		case <-confirmed:
+		case <-time.After(UpdateGracePeriod):
+			rollbackUpdate(marker, fmt.Sprintf("failed to connect and authenticate within %v", UpdateGracePeriod))
+		}
+	}()
+}
+
+// ConfirmUpdate marks the update as healthy and removes the backup
+func ConfirmUpdate(marker *UpdateMarker) {
+	os.Remove(marker.BackupPath)
+	removeUpdateMarker()
+	logInfof("Update from %s to %s confirmed", marker.PreviousVersion, AgentVersion)
+}
+
+// rollbackUpdate restores the backup binary and restarts. Does not return.
+func rollbackUpdate(marker *UpdateMarker, reason string) {
+	logWarnf("Update health check failed (%s), rolling back to %s", reason, marker.PreviousVersion)
+
+	currentExe, err := os.Executable()
+	if err != nil {
+		logErrorf("Rollback failed: %v", err)
+		return
+	}
+	if _, err := os.Stat(marker.BackupPath); err != nil {
+		logErrorf("Rollback failed, backup missing: %v", err)
+		removeUpdateMarker()
+		return
+	}
+
+	failedPath := currentExe + ".failed"
+	os.Remove(failedPath)
+	if err := os.Rename(currentExe, failedPath); err != nil {
+		logErrorf("Rollback failed: %v", err)
+		return
+	}
+	if err := os.Rename(marker.BackupPath, currentExe); err != nil {
+		os.Rename(failedPath, currentExe)
+		logErrorf("Rollback failed: %v", err)
+		return
+	}
+	os.Remove(failedPath)
+
+	marker.RolledBack = true
+	marker.FailedVersion = AgentVersion
+	marker.FailureReason = reason
+	if err := marker.Save(); err != nil {
+		logWarnf("Warning: failed to record rollback: %v", err)
+	}
+
+	logInfof("Rollback complete, restarting...")
+	restartService()
+	os.Exit(1)
+}
+
+// restartService asks the service manager to restart the agent. The caller
+// exits right after, so the PID file is released here.
+func restartService() {
+	RemovePIDFile()
+
+	// Restart the agent using systemd-run to avoid being killed by cgroup
+	if runtime.GOOS == "linux" {
+		// Use systemd-run --no-block to run restart in an independent transient unit
+		// This prevents the restart command from being killed when vstats-agent stops
+		cmd := exec.Command("systemd-run", "--no-block", "systemctl", "restart", "vstats-agent")
+		if err := cmd.Start(); err != nil {
+			logErrorf("Failed to schedule restart via systemd-run: %v", err)
+			// Fallback to direct systemctl (may not work in all cases)
+			exec.Command("systemctl", "restart", "vstats-agent").Start()
+		} else {
+			logInfof("Restart scheduled via systemd-run")
+		}
+	} else if runtime.GOOS == "windows" {
+		// On Windows, use sc.exe to restart the service
+		cmd := exec.Command("cmd", "/C", "sc", "stop", "vstats-agent", "&&", "timeout", "/t", "2", "&&", "sc", "start", "vstats-agent")
+		cmd.Start()
+	}
+
+	// Give systemd-run a moment to register the restart command
+	time.Sleep(500 * time.Millisecond)
+}
+
+// downloadFile downloads a file from URL to path and returns its hex SHA-256
+func downloadFile(url, path string) (string, error) {
+	resp, err := newHTTPClient(0).Get(url)
+	if err != nil {
+		return "", fmt.Errorf("HTTP request failed: %w", proxyError(err))
+	}
+	defer resp.Body.Close()
+
+	if resp.StatusCode != http.StatusOK {
+		return "", fmt.Errorf("download failed with status: %d", resp.StatusCode)
+	}
+
+	out, err := os.Create(path)
+	if err != nil {
+		return "", fmt.Errorf("failed to create file: %w", err)
+	}
+	defer out.Close()
+
+	hash := sha256.New()
+	_, err = io.Copy(io.MultiWriter(out, hash), resp.Body)
+	if err != nil {
+		os.Remove(path)
+		return "", fmt.Errorf("failed to write file: %w", err)
+	}
+
+	return hex.EncodeToString(hash.Sum(nil)), nil
+}
+
+// fetchDashboardVersion returns the version reported by the dashboard
+func fetchDashboardVersion(dashboardURL string) (string, error) {
+	client := newHTTPClient(10 * time.Second)
+	resp, err := client.Get(strings.TrimSuffix(dashboardURL, "/") + "/api/version")
+	if err != nil {
+		return "", proxyError(err)
+	}
+	defer resp.Body.Close()
+
+	if resp.StatusCode != http.StatusOK {
+		return "", fmt.Errorf("dashboard returned status: %d", resp.StatusCode)
+	}
+
+	var result struct {
+		Version string `json:"version"`
+	}
+	if err := json.NewDecoder(resp.Body).Decode(&result); err != nil {
+		return "", err
+	}
+	return result.Version, nil
+}
+
+// fetchLatestGitHubVersion fetches the latest release version from GitHub
+func fetchLatestGitHubVersion(owner, repo string) (*string, error) {
+	url := fmt.Sprintf("https://api.github.com/repos/%s/%s/releases/latest", owner, repo)
+
+	client := newHTTPClient(10 * time.Second)
+	req, _ := http.NewRequest("GET", url, nil)
+	req.Header.Set("User-Agent", "vstats-agent")
+
+	resp, err := client.Do(req)
+	if err != nil {
+		return nil, proxyError(err)
+	}
+	defer resp.Body.Close()
+
+	if resp.StatusCode != http.StatusOK {
+		return nil, fmt.Errorf("GitHub API returned status: %d", resp.StatusCode)
+	}
+
+	body, _ := io.ReadAll(resp.Body)
+	var result map[string]interface{}
+	if err := json.Unmarshal(body, &result); err != nil {
+		return nil, err
+	}
+
+	tagName, ok := result["tag_name"].(string)
+	if !ok {
+		return nil, fmt.Errorf("no tag_name in response")
+	}
+
+	// Keep the original tag name (with 'v' prefix) for download URL
+	return &tagName, nil
+}
+
+// handleUpdate implements `vstats-agent update [--check] [--force]`
+func handleUpdate() {
+	configPath := DefaultConfigPath()
+	check, force := false, false
+	var verify UpdateVerification
+	for i := 2; i < len(os.Args); i++ {
+		switch os.Args[i] {
+		case "--check":
+			check = true
+		case "--force":
+			force = true
+		case "--sha256":
+			if i+1 < len(os.Args) {
+				verify.SHA256 = os.Args[i+1]
+				i++
+			}
+		case "--signature":
+			if i+1 < len(os.Args) {
+				verify.Signature = os.Args[i+1]
+				i++
+			}
+		case "--config":
+			if i+1 < len(os.Args) {
+				configPath = os.Args[i+1]
+				i++
+			}
+		}
+	}
+
+	// Config is optional: without it we only consult GitHub
+	config, err := LoadConfig(configPath)
+	if err != nil {
+		config = nil
+	} else if err := SetProxy(config.ProxyURL); err != nil {
+		fmt.Printf("Error: %v\n", err)
+		os.Exit(1)
+	}
+
+	info, err := CheckForUpdate(config)
+	if err != nil {
+		fmt.Printf("Error: failed to check for updates: %v\n", err)
+		os.Exit(2)
+	}
+
+	fmt.Printf("Current version: %s\n", info.Current)
+	fmt.Printf("Latest version:  %s (from %s)\n", info.Latest, info.Source)
+
+	if check {
+		// Exit 0 when an update is available so cron can chain on it
+		if info.Available {
+			fmt.Println("Update available")
+			os.Exit(0)
+		}
+		fmt.Println("Already up to date")
+		os.Exit(1)
+	}
+
+	if !info.Available && !force {
+		fmt.Println("Already up to date (use --force to reinstall)")
+		return
+	}
+
+	if err := ApplyUpdate(config, releaseDownloadURL(info.Tag), verify); err != nil {
+		fmt.Printf("Error: %v\n", err)
+		os.Exit(1)
+	}
+}
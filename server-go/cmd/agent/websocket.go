@@ -2,17 +2,17 @@ package main
 
 import (
 	"encoding/json"
+	"errors"
 	"fmt"
-	"io"
-	"log"
-	"net/http"
+	"math/rand"
 	"os"
-	"os/exec"
-	"runtime"
 	"strings"
 	"sync"
+	"sync/atomic"
 	"time"
 
+	"vstats/internal/common"
+
 	"github.com/google/uuid"
 	"github.com/gorilla/websocket"
 )
@@ -24,30 +24,112 @@ const (
 	PingInterval           = 30 * time.Second
 	BatchSyncInterval      = 30 * time.Second  // How often to sync offline data
 	AggregationSyncInterval = 60 * time.Second // How often to sync aggregated data
+	CollectTimeout          = 10 * time.Second // Max time to wait for a metrics collection
+	HeartbeatInterval       = 10 * time.Second // Max silence before a heartbeat keeps the agent online
+)
+
+const (
+	// A connection that stayed up this long resets the backoff, however it ended
+	StableConnectionThreshold = 60 * time.Second
+	// Rejected credentials won't fix themselves quickly, so back off harder
+	InitialAuthFailureDelay = 2 * time.Minute
+	MaxAuthFailureDelay     = 30 * time.Minute
+	// Fraction of the delay randomized so a fleet doesn't reconnect in lockstep
+	ReconnectJitter = 0.3
+	// The server was deleted or its token replaced; retrying won't help until
+	// someone changes the config, which a SIGHUP reload picks up immediately
+	RejectedAuthDelay = 6 * time.Hour
 )
 
+// FullSnapshotInterval is how often a full metrics snapshot is sent when
+// delta metrics are in use, bounding how long any drift can persist
+const FullSnapshotInterval = 5 * time.Minute
+
+// Connection error categories
+const (
+	ErrCategoryNetwork  = "network"
+	ErrCategoryAuth     = "auth"
+	ErrCategoryRejected = "rejected" // Permanent auth failure (server deleted, token replaced)
+	ErrCategoryProtocol = "protocol"
+)
+
+// connError tags a connection failure with its category
+type connError struct {
+	category string
+	err      error
+}
+
+func (e *connError) Error() string { return e.err.Error() }
+func (e *connError) Unwrap() error { return e.err }
+
+// errorCategory returns the category of a connection error; untagged errors
+// are treated as network errors
+func errorCategory(err error) string {
+	var ce *connError
+	if errors.As(err, &ce) {
+		return ce.category
+	}
+	return ErrCategoryNetwork
+}
+
+// withJitter spreads d randomly by ±ReconnectJitter
+func withJitter(d time.Duration) time.Duration {
+	factor := 1 + ReconnectJitter*(2*rand.Float64()-1)
+	return time.Duration(float64(d) * factor)
+}
+
 type WebSocketClient struct {
-	config       *AgentConfig
+	config       *AgentConfig // Swapped on reload; use cfg() and setConfig()
+	configMu     sync.RWMutex
 	collector    *MetricsCollector
 	store        *LocalStore
 	connected    bool
 	connectedMu  sync.RWMutex
 	lastSentTime time.Time
+	authedAt     time.Time // When the current connection authenticated
+	useMsgpack   bool      // Server accepted msgpack for this connection
+	// Delta metrics: last snapshot sent (what the server merges into)
+	useDelta   bool
+	deltaBase  map[string]interface{}
+	lastFullAt time.Time
+	forceFull  atomic.Bool // Server asked for a full snapshot
+	// Reconnect history, reported to the server with each auth
+	startedAt            time.Time
+	reconnects           uint64
+	lastDisconnectReason string
+	lastDisconnectAt     time.Time
+	reloadCh             chan *AgentConfig
+	configPath           string    // Rewritten after auto re-registration
+	reregistered         time.Time // Last auto re-registration, to avoid loops
+	// Graceful shutdown: stopCh carries the reason, stopped closes once the
+	// goodbye has been sent
+	stopCh  chan string
+	stopped chan struct{}
+	// Self-update awaiting health confirmation (or rollback report)
+	pendingUpdate   *UpdateMarker
+	updateConfirmed chan struct{}
 }
 
 func NewWebSocketClient(config *AgentConfig) *WebSocketClient {
 	wsc := &WebSocketClient{
 		config:    config,
 		collector: NewMetricsCollector(),
+		startedAt: time.Now(),
+		reloadCh:  make(chan *AgentConfig, 1),
+		stopCh:    make(chan string, 1),
+		stopped:   make(chan struct{}),
 	}
+	wsc.collector.SetDiskFilter(config.DiskInclude, config.DiskExclude)
+	wsc.collector.SetNetInterfaceFilter(config.NetInterfaceInclude, config.NetInterfaceExclude)
+	wsc.collector.SetWatchedServices(config.WatchedServices)
 
 	// Initialize local storage if enabled
 	if config.EnableOfflineStorage {
 		store, err := NewLocalStore(config.DataDir)
 		if err != nil {
-			log.Printf("Warning: Failed to initialize offline storage: %v", err)
+			logWarnf("Warning: Failed to initialize offline storage: %v", err)
 		} else {
-			log.Printf("Offline storage enabled at %s", config.DataDir)
+			logInfof("Offline storage enabled at %s", config.DataDir)
 			wsc.store = store
 		}
 	}
@@ -67,51 +149,309 @@ func (wsc *WebSocketClient) setConnected(connected bool) {
 	wsc.connected = connected
 }
 
+// SetPendingUpdate arms the post-update health check. A healthy update is
+// confirmed on the first successful auth; otherwise the watchdog rolls back.
+func (wsc *WebSocketClient) SetPendingUpdate(marker *UpdateMarker) {
+	wsc.pendingUpdate = marker
+	if !marker.RolledBack {
+		wsc.updateConfirmed = make(chan struct{})
+		StartUpdateWatchdog(marker, wsc.updateConfirmed)
+	}
+}
+
+// resolvePendingUpdate confirms a healthy update or reports a rollback
+func (wsc *WebSocketClient) resolvePendingUpdate(conn *websocket.Conn) {
+	marker := wsc.pendingUpdate
+	if marker == nil {
+		return
+	}
+
+	if !marker.RolledBack {
+		close(wsc.updateConfirmed)
+		ConfirmUpdate(marker)
+		wsc.pendingUpdate = nil
+		return
+	}
+
+	msg := UpdateFailedMessage{
+		Type:        "update_failed",
+		FromVersion: marker.PreviousVersion,
+		ToVersion:   marker.FailedVersion,
+		Reason:      marker.FailureReason,
+	}
+	data, err := json.Marshal(msg)
+	if err != nil {
+		return
+	}
+	if err := conn.WriteMessage(websocket.TextMessage, data); err != nil {
+		logErrorf("Failed to report update failure: %v", err)
+		return
+	}
+	removeUpdateMarker()
+	wsc.pendingUpdate = nil
+	logInfof("Reported failed update to server")
+}
+
+// cfg returns the current config. The connection loop swaps it on reload
+// while the offline collector and self-update read it.
+func (wsc *WebSocketClient) cfg() *AgentConfig {
+	wsc.configMu.RLock()
+	defer wsc.configMu.RUnlock()
+	return wsc.config
+}
+
+func (wsc *WebSocketClient) setConfig(config *AgentConfig) {
+	wsc.configMu.Lock()
+	wsc.config = config
+	wsc.configMu.Unlock()
+}
+
+// applyConfig swaps in a reloaded config along with its collector filters
+func (wsc *WebSocketClient) applyConfig(config *AgentConfig) {
+	wsc.setConfig(config)
+	wsc.collector.SetDiskFilter(config.DiskInclude, config.DiskExclude)
+	wsc.collector.SetNetInterfaceFilter(config.NetInterfaceInclude, config.NetInterfaceExclude)
+	wsc.collector.SetWatchedServices(config.WatchedServices)
+}
+
+// Reload swaps in a new config and reconnects so it takes effect
+func (wsc *WebSocketClient) Reload(config *AgentConfig) {
+	select {
+	case wsc.reloadCh <- config:
+	default:
+		logInfof("Config reload already pending")
+	}
+}
+
+// errShutdown ends the connection loop after a deliberate stop
+var errShutdown = errors.New("agent shutting down")
+
+// Shutdown tells the server the agent is going away on purpose and closes
+// the connection. It returns once the goodbye is sent or timeout passes.
+func (wsc *WebSocketClient) Shutdown(reason string, timeout time.Duration) {
+	select {
+	case wsc.stopCh <- reason:
+	default:
+	}
+	select {
+	case <-wsc.stopped:
+	case <-time.After(timeout):
+		logWarnf("Timed out waiting for connection to close")
+	}
+}
+
+// sendGoodbye announces a deliberate disconnect and closes the socket cleanly
+func sendGoodbye(conn *websocket.Conn, reason string) {
+	data, err := json.Marshal(GoodbyeMessage{Type: "goodbye", Reason: reason})
+	if err == nil {
+		if err := conn.WriteMessage(websocket.TextMessage, data); err != nil {
+			logErrorf("Failed to send goodbye: %v", err)
+		}
+	}
+	conn.WriteControl(websocket.CloseMessage,
+		websocket.FormatCloseMessage(websocket.CloseNormalClosure, reason),
+		time.Now().Add(time.Second))
+}
+
 func (wsc *WebSocketClient) Run() {
 	reconnectDelay := InitialReconnectDelay
+	authFailureDelay := InitialAuthFailureDelay
 
 	// Start offline metrics collection goroutine
 	offlineMetricsCh := make(chan *SystemMetrics, 100)
 	go wsc.offlineCollector(offlineMetricsCh)
 
 	for {
-		log.Printf("Connecting to %s...", wsc.config.WSUrl())
+		// Pick up a config reload that arrived while disconnected
+		select {
+		case config := <-wsc.reloadCh:
+			wsc.applyConfig(config)
+		default:
+		}
+
+		logInfof("Connecting to %s...", wsc.cfg().WSUrl())
 
-		if err := wsc.connectAndRun(offlineMetricsCh); err != nil {
-			log.Printf("Connection error: %v", err)
-			wsc.setConnected(false)
+		wsc.authedAt = time.Time{}
+		err := wsc.connectAndRun(offlineMetricsCh)
+		wsc.setConnected(false)
+		if err == errShutdown {
+			logInfof("Disconnected from server")
+			close(wsc.stopped)
+			return
+		}
+		wsc.recordDisconnect(err)
+
+		// A long-lived connection means the server is healthy again
+		if !wsc.authedAt.IsZero() && time.Since(wsc.authedAt) >= StableConnectionThreshold {
+			reconnectDelay = InitialReconnectDelay
+			authFailureDelay = InitialAuthFailureDelay
+		}
+
+		var delay time.Duration
+		if err == nil {
+			logInfof("Connection closed normally")
+			reconnectDelay = InitialReconnectDelay
+			delay = reconnectDelay
+		} else if category := errorCategory(err); category == ErrCategoryRejected {
+			logWarnf("Connection error (%s): %v", category, err)
+			if wsc.tryReregister() {
+				reconnectDelay = InitialReconnectDelay
+				authFailureDelay = InitialAuthFailureDelay
+				delay = reconnectDelay
+			} else {
+				logErrorf("ERROR: the dashboard rejected server %s permanently; it was probably deleted or its token replaced", wsc.cfg().ServerID)
+				logErrorf("ERROR: re-register with 'vstats-agent register --server %s --token <admin_token>' and restart,", wsc.cfg().DashboardURL)
+				logErrorf("ERROR: or set auto_reregister and admin_token_file in the config to do this automatically")
+				delay = RejectedAuthDelay
+			}
+		} else if category == ErrCategoryAuth {
+			logWarnf("Connection error (%s): %v", category, err)
+			if errors.Is(err, errUpgradeRequired) {
+				logInfof("The dashboard requires a newer agent; run 'vstats-agent update' to upgrade")
+			} else {
+				logInfof("Check the agent token, or re-register the agent if it was removed from the dashboard")
+			}
+			delay = authFailureDelay
+			authFailureDelay = min(authFailureDelay*2, MaxAuthFailureDelay)
 		} else {
-			log.Println("Connection closed normally")
-			wsc.setConnected(false)
+			logWarnf("Connection error (%s): %v", category, err)
+			delay = reconnectDelay
+			reconnectDelay = min(reconnectDelay*2, MaxReconnectDelay)
+		}
+
+		delay = withJitter(delay)
+		logInfof("Reconnecting in %v...", delay.Round(time.Second))
+		select {
+		case <-time.After(delay):
+		case config := <-wsc.reloadCh:
+			// A fixed config shouldn't have to wait out a long backoff
+			wsc.applyConfig(config)
+			logInfof("Config reloaded, reconnecting now")
 			reconnectDelay = InitialReconnectDelay
+			authFailureDelay = InitialAuthFailureDelay
+		case <-wsc.stopCh:
+			// Not connected, so there is nobody to say goodbye to
+			close(wsc.stopped)
+			return
 		}
+	}
+}
+
+// recordDisconnect counts a lost connection (or failed attempt) for the
+// connection stats sent with the next auth
+func (wsc *WebSocketClient) recordDisconnect(err error) {
+	wsc.reconnects++
+	wsc.lastDisconnectAt = time.Now()
+	if err == nil {
+		wsc.lastDisconnectReason = "closed by server"
+	} else {
+		wsc.lastDisconnectReason = fmt.Sprintf("%s: %v", errorCategory(err), err)
+	}
+}
 
-		log.Printf("Reconnecting in %v...", reconnectDelay)
-		time.Sleep(reconnectDelay)
+// connectionStats returns the reconnect history for the auth message
+func (wsc *WebSocketClient) connectionStats() *common.ConnectionStats {
+	stats := &common.ConnectionStats{
+		Reconnects:           wsc.reconnects,
+		LastDisconnectReason: wsc.lastDisconnectReason,
+		StartedAt:            wsc.startedAt.UTC().Format(time.RFC3339),
+	}
+	if !wsc.lastDisconnectAt.IsZero() {
+		stats.LastDisconnectAt = wsc.lastDisconnectAt.UTC().Format(time.RFC3339)
+	}
+	return stats
+}
 
-		// Exponential backoff
-		reconnectDelay *= 2
-		if reconnectDelay > MaxReconnectDelay {
-			reconnectDelay = MaxReconnectDelay
+// errUpgradeRequired is returned when the server refuses this agent's protocol version
+var errUpgradeRequired = errors.New("authentication failed: upgrade required")
+
+// isPermanentAuthError reports whether an auth failure means the credentials
+// will never work again. Servers without error codes are matched by message.
+func isPermanentAuthError(response *ServerResponse) bool {
+	switch response.Code {
+	case common.AuthErrorServerNotFound, common.AuthErrorInvalidToken:
+		return true
+	case "":
+		return response.Message == "Server not found" || response.Message == "Invalid token"
+	}
+	return false
+}
+
+// tryReregister registers this host as a new server using the configured
+// admin token and rewrites the config. Returns true if the agent now has
+// fresh credentials.
+func (wsc *WebSocketClient) tryReregister() bool {
+	current := wsc.cfg()
+	if !current.AutoReregister || current.AdminTokenFile == "" {
+		return false
+	}
+	// Fresh credentials that are rejected again point at a server-side problem;
+	// don't keep creating new servers
+	if !wsc.reregistered.IsZero() && time.Since(wsc.reregistered) < RejectedAuthDelay {
+		logWarnf("Already re-registered recently, not trying again")
+		return false
+	}
+
+	data, err := os.ReadFile(current.AdminTokenFile)
+	if err != nil {
+		logErrorf("Auto re-registration failed: cannot read admin token: %v", err)
+		return false
+	}
+	adminToken := strings.TrimSpace(string(data))
+
+	name := current.ServerName
+	if name == "" {
+		name, _ = os.Hostname()
+	}
+	logInfof("Re-registering with %s as %q", current.DashboardURL, name)
+	wsc.reregistered = time.Now()
+
+	resp, err := registerWithDashboard(current.DashboardURL, adminToken, RegisterRequest{
+		Name:      name,
+		Location:  current.Location,
+		Provider:  current.Provider,
+		Tag:       current.Tag,
+		MachineID: machineID(),
+	})
+	if err != nil {
+		logErrorf("Auto re-registration failed: %v", err)
+		return false
+	}
+
+	config := *current
+	config.ServerID = resp.ID
+	config.AgentToken = resp.Token
+	wsc.setConfig(&config)
+	logInfof("Re-registered as server %s", resp.ID)
+
+	if wsc.configPath != "" {
+		if err := SaveConfig(&config, wsc.configPath); err != nil {
+			logWarnf("Warning: failed to save new credentials to %s: %v", wsc.configPath, err)
+		} else {
+			logInfof("Configuration saved to %s", wsc.configPath)
 		}
 	}
+	return true
 }
 
 // offlineCollector collects metrics and stores them locally when disconnected
 func (wsc *WebSocketClient) offlineCollector(metricsCh chan<- *SystemMetrics) {
-	ticker := time.NewTicker(time.Duration(wsc.config.IntervalSecs) * time.Second)
+	ticker := time.NewTicker(time.Duration(wsc.cfg().IntervalSecs) * time.Second)
 	defer ticker.Stop()
 
 	for range ticker.C {
 		if !wsc.isConnected() && wsc.store != nil {
 			// Collect metrics while offline and store with aggregation
-			metrics := wsc.collector.Collect()
+			metrics, ok := wsc.collector.CollectWithTimeout(CollectTimeout)
+			if !ok {
+				continue
+			}
 			if err := wsc.store.StoreWithAggregation(&metrics); err != nil {
-				log.Printf("Failed to store offline metrics: %v", err)
+				logErrorf("Failed to store offline metrics: %v", err)
 			} else {
 				pending := wsc.store.GetPendingCount()
 				if pending%10 == 0 { // Log every 10 metrics
-					log.Printf("Stored offline metrics (pending: %d)", pending)
+					logInfof("Stored offline metrics (pending: %d)", pending)
 				}
 			}
 		}
@@ -119,22 +459,36 @@ func (wsc *WebSocketClient) offlineCollector(metricsCh chan<- *SystemMetrics) {
 }
 
 func (wsc *WebSocketClient) connectAndRun(offlineMetricsCh chan<- *SystemMetrics) error {
-	wsURL := wsc.config.WSUrl()
+	wsURL := wsc.cfg().WSUrl()
 
-	conn, _, err := websocket.DefaultDialer.Dial(wsURL, nil)
+	dialer := *websocket.DefaultDialer
+	dialer.EnableCompression = wsc.cfg().Compression
+	dialer.Proxy = proxyFunc()
+	conn, _, err := dialer.Dial(wsURL, nil)
 	if err != nil {
-		return fmt.Errorf("failed to connect: %w", err)
+		return &connError{ErrCategoryNetwork, fmt.Errorf("failed to connect: %w", proxyError(err))}
 	}
 	defer conn.Close()
 
-	log.Println("Connected to WebSocket server")
+	logInfof("Connected to WebSocket server")
 
 	// Send authentication message
 	authMsg := AuthMessage{
 		Type:     "auth",
-		ServerID: wsc.config.ServerID,
-		Token:    wsc.config.AgentToken,
+		ServerID: wsc.cfg().ServerID,
+		Token:    wsc.cfg().AgentToken,
 		Version:  AgentVersion,
+		// Lets the server refuse this agent cleanly if it is too old
+		ProtocolVersion: common.ProtocolVersion,
+		ConnectionStats: wsc.connectionStats(),
+		// Lets the server refuse update commands this agent cannot carry out
+		SelfUpdateUnsupported: !CanSelfUpdate(),
+	}
+	if wsc.cfg().Encoding == EncodingMsgpack {
+		authMsg.Encoding = EncodingMsgpack
+	}
+	if wsc.cfg().DeltaEnabled() {
+		authMsg.DeltaVersion = common.DeltaProtocolVersion
 	}
 
 	authData, err := json.Marshal(authMsg)
@@ -146,7 +500,7 @@ func (wsc *WebSocketClient) connectAndRun(offlineMetricsCh chan<- *SystemMetrics
 		return fmt.Errorf("failed to send auth message: %w", err)
 	}
 
-	log.Println("Sent authentication message")
+	logInfof("Sent authentication message")
 
 	// Wait for auth response
 	conn.SetReadDeadline(time.Now().Add(AuthTimeout))
@@ -157,32 +511,54 @@ func (wsc *WebSocketClient) connectAndRun(offlineMetricsCh chan<- *SystemMetrics
 
 	var response ServerResponse
 	if err := json.Unmarshal(message, &response); err != nil {
-		return fmt.Errorf("failed to parse auth response: %w", err)
+		return &connError{ErrCategoryProtocol, fmt.Errorf("failed to parse auth response: %w", err)}
 	}
 
 	if response.Status != "ok" {
-		return fmt.Errorf("authentication failed: %s", response.Message)
+		if response.Code == common.AuthErrorUpgradeRequired {
+			return &connError{ErrCategoryAuth, errUpgradeRequired}
+		}
+		category := ErrCategoryAuth
+		if isPermanentAuthError(&response) {
+			category = ErrCategoryRejected
+		}
+		return &connError{category, fmt.Errorf("authentication failed: %s", response.Message)}
 	}
 
 	// Update ping targets from server config if provided
 	if len(response.PingTargets) > 0 {
-		log.Printf("Received %d ping targets from server", len(response.PingTargets))
+		logInfof("Received %d ping targets from server", len(response.PingTargets))
 		wsc.collector.SetPingTargets(response.PingTargets)
 	}
 
 	// Store last seen timestamp from server (for deduplication)
 	if response.LastSeen != nil {
-		log.Printf("Server last seen timestamp: %s", *response.LastSeen)
+		logInfof("Server last seen timestamp: %s", *response.LastSeen)
 	}
 	
 	// Store last buckets for resumable sync
 	var lastBuckets map[string]int64
 	if len(response.LastBuckets) > 0 {
 		lastBuckets = response.LastBuckets
-		log.Printf("Server last buckets: %v", lastBuckets)
+		logInfof("Server last buckets: %v", lastBuckets)
+	}
+
+	logInfof("Authentication successful!")
+	wsc.authedAt = time.Now()
+
+	// Older servers ignore the encoding request, so only switch when accepted
+	wsc.useMsgpack = response.Encoding == EncodingMsgpack
+	if wsc.cfg().Encoding == EncodingMsgpack && !wsc.useMsgpack {
+		logInfof("Server does not support msgpack, sending JSON")
 	}
 
-	log.Println("Authentication successful!")
+	// Deltas are diffed against the last full snapshot of this connection
+	wsc.useDelta = wsc.cfg().DeltaEnabled() && response.DeltaVersion == common.DeltaProtocolVersion
+	wsc.deltaBase = nil
+	wsc.forceFull.Store(false)
+
+	// Finish any self-update health check now that we've authenticated
+	wsc.resolvePendingUpdate(conn)
 
 	// Reset read deadline
 	conn.SetReadDeadline(time.Time{})
@@ -197,12 +573,24 @@ func (wsc *WebSocketClient) connectAndRun(offlineMetricsCh chan<- *SystemMetrics
 	go wsc.syncOfflineData(conn)
 
 	// Start metrics sending loop
-	metricsTicker := time.NewTicker(time.Duration(wsc.config.IntervalSecs) * time.Second)
+	// With jitter the first tick lands at a random point within the interval,
+	// so agents restarted together don't report in lockstep
+	interval := time.Duration(wsc.cfg().IntervalSecs) * time.Second
+	firstTick := interval
+	if wsc.cfg().JitterEnabled() {
+		firstTick = time.Duration(rand.Int63n(int64(interval))) + time.Millisecond
+	}
+	metricsTicker := time.NewTicker(firstTick)
 	defer metricsTicker.Stop()
+	tickerAligned := firstTick == interval
 
 	pingTicker := time.NewTicker(PingInterval)
 	defer pingTicker.Stop()
 
+	// Heartbeats keep the agent online while metrics are slow or sparse
+	heartbeatTicker := time.NewTicker(HeartbeatInterval)
+	defer heartbeatTicker.Stop()
+
 	// Aggregation sync ticker (send aggregated data periodically)
 	aggSyncTicker := time.NewTicker(AggregationSyncInterval)
 	defer aggSyncTicker.Stop()
@@ -210,6 +598,9 @@ func (wsc *WebSocketClient) connectAndRun(offlineMetricsCh chan<- *SystemMetrics
 	// Handle incoming messages
 	done := make(chan error, 1)
 	batchAckCh := make(chan *ServerResponse, 10)
+	tokenCh := make(chan string, 1)
+	// Reasons of update commands that failed before the binary was swapped
+	updateFailedCh := make(chan string, 1)
 
 	go func() {
 		for {
@@ -226,7 +617,7 @@ func (wsc *WebSocketClient) connectAndRun(offlineMetricsCh chan<- *SystemMetrics
 
 			switch response.Type {
 			case "error":
-				log.Printf("Server error: %s", response.Message)
+				logErrorf("Server error: %s", response.Message)
 			case "batch_ack":
 				// Handle batch acknowledgment
 				select {
@@ -236,19 +627,36 @@ func (wsc *WebSocketClient) connectAndRun(offlineMetricsCh chan<- *SystemMetrics
 			case "command":
 				if response.Command == "update" {
 					if response.Force {
-						log.Println("Received FORCE update command from server")
+						logInfof("Received FORCE update command from server")
 					} else {
-						log.Println("Received update command from server")
+						logInfof("Received update command from server")
+					}
+					err := wsc.handleUpdateCommand(response.DownloadURL, response.Force, UpdateVerification{
+						SHA256:    response.SHA256,
+						Signature: response.Signature,
+					})
+					if err != nil {
+						logErrorf("Update failed: %v", err)
+						select {
+						case updateFailedCh <- err.Error():
+						default:
+						}
+					}
+				} else if response.Command == "full_snapshot" {
+					wsc.forceFull.Store(true)
+				} else if response.Command == "rotate_token" && response.Token != "" {
+					select {
+					case tokenCh <- response.Token:
+					default:
 					}
-					wsc.handleUpdateCommand(response.DownloadURL, response.Force)
 				}
 			case "config":
 				// Handle runtime config update (e.g., ping targets)
 				if len(response.PingTargets) > 0 {
-					log.Printf("Received updated ping targets from server: %d targets", len(response.PingTargets))
+					logInfof("Received updated ping targets from server: %d targets", len(response.PingTargets))
 					wsc.collector.SetPingTargets(response.PingTargets)
 				} else {
-					log.Println("Received config update: clearing ping targets")
+					logInfof("Received config update: clearing ping targets")
 					wsc.collector.SetPingTargets(nil)
 				}
 			}
@@ -258,25 +666,27 @@ func (wsc *WebSocketClient) connectAndRun(offlineMetricsCh chan<- *SystemMetrics
 	for {
 		select {
 		case <-metricsTicker.C:
-			metrics := wsc.collector.Collect()
+			if !tickerAligned {
+				metricsTicker.Reset(interval)
+				tickerAligned = true
+			}
+			metrics, ok := wsc.collector.CollectWithTimeout(CollectTimeout)
+			if !ok {
+				continue
+			}
 			
 			// Store metrics with aggregation locally
 			if wsc.store != nil {
 				wsc.store.StoreWithAggregation(&metrics)
 			}
 			
-			msg := MetricsMessage{
-				Type:    "metrics",
-				Metrics: metrics,
-			}
-
-			data, err := json.Marshal(msg)
+			frameType, data, err := wsc.encodeMessage(wsc.metricsMessage(&metrics))
 			if err != nil {
-				log.Printf("Failed to serialize metrics: %v", err)
+				logErrorf("Failed to serialize metrics: %v", err)
 				continue
 			}
 
-			if err := conn.WriteMessage(websocket.TextMessage, data); err != nil {
+			if err := conn.WriteMessage(frameType, data); err != nil {
 				return fmt.Errorf("failed to send metrics: %w", err)
 			}
 			wsc.lastSentTime = time.Now()
@@ -290,12 +700,98 @@ func (wsc *WebSocketClient) connectAndRun(offlineMetricsCh chan<- *SystemMetrics
 				return fmt.Errorf("failed to send ping: %w", err)
 			}
 
+		case <-heartbeatTicker.C:
+			if time.Since(wsc.lastSentTime) < HeartbeatInterval {
+				continue
+			}
+			if err := conn.WriteMessage(websocket.TextMessage, []byte(`{"type":"heartbeat"}`)); err != nil {
+				return fmt.Errorf("failed to send heartbeat: %w", err)
+			}
+
+		case config := <-wsc.reloadCh:
+			wsc.applyConfig(config)
+			logInfof("Config reloaded, reconnecting")
+			return nil
+
+		case reason := <-updateFailedCh:
+			data, err := json.Marshal(UpdateFailedMessage{
+				Type:        "update_failed",
+				FromVersion: AgentVersion,
+				Reason:      reason,
+			})
+			if err != nil {
+				continue
+			}
+			if err := conn.WriteMessage(websocket.TextMessage, data); err != nil {
+				return fmt.Errorf("failed to report update failure: %w", err)
+			}
+
+		case token := <-tokenCh:
+			// The old token is already revoked; save the new one before
+			// reconnecting with it
+			config := *wsc.cfg()
+			config.AgentToken = token
+			wsc.setConfig(&config)
+			if wsc.configPath != "" {
+				if err := SaveConfig(&config, wsc.configPath); err != nil {
+					logWarnf("Warning: failed to save rotated token to %s: %v", wsc.configPath, err)
+				}
+			}
+			logInfof("Agent token rotated by server, reconnecting")
+			return nil
+
+		case reason := <-wsc.stopCh:
+			logInfof("Sending goodbye to server (%s)", reason)
+			sendGoodbye(conn, reason)
+			return errShutdown
+
 		case err := <-done:
 			return err
 		}
 	}
 }
 
+// metricsMessage returns a full snapshot or, when delta metrics are in use,
+// a merge patch against the previous snapshot
+func (wsc *WebSocketClient) metricsMessage(metrics *SystemMetrics) interface{} {
+	full := MetricsMessage{
+		Type:    "metrics",
+		Metrics: *metrics,
+	}
+	if !wsc.useDelta {
+		return full
+	}
+
+	current, err := common.MetricsToMap(metrics)
+	if err != nil {
+		wsc.deltaBase = nil
+		return full
+	}
+	if wsc.deltaBase == nil || wsc.forceFull.Swap(false) || time.Since(wsc.lastFullAt) >= FullSnapshotInterval {
+		wsc.deltaBase = current
+		wsc.lastFullAt = time.Now()
+		return full
+	}
+
+	delta := common.DiffPatch(wsc.deltaBase, current)
+	wsc.deltaBase = current
+	return MetricsDeltaMessage{
+		Type:  "metrics_delta",
+		Delta: delta,
+	}
+}
+
+// encodeMessage serializes a message with the negotiated encoding and returns
+// the matching frame type
+func (wsc *WebSocketClient) encodeMessage(msg interface{}) (int, []byte, error) {
+	if wsc.useMsgpack {
+		data, err := common.EncodeMsgpack(msg)
+		return websocket.BinaryMessage, data, err
+	}
+	data, err := json.Marshal(msg)
+	return websocket.TextMessage, data, err
+}
+
 // sendAggregatedData sends all aggregated data to the server
 func (wsc *WebSocketClient) sendAggregatedData(conn *websocket.Conn) {
 	if wsc.store == nil {
@@ -304,7 +800,7 @@ func (wsc *WebSocketClient) sendAggregatedData(conn *websocket.Conn) {
 
 	aggData, err := wsc.store.GetAllAggregatedData()
 	if err != nil {
-		log.Printf("Failed to get aggregated data: %v", err)
+		logErrorf("Failed to get aggregated data: %v", err)
 		return
 	}
 
@@ -314,12 +810,12 @@ func (wsc *WebSocketClient) sendAggregatedData(conn *websocket.Conn) {
 
 	data, err := json.Marshal(aggData)
 	if err != nil {
-		log.Printf("Failed to serialize aggregated data: %v", err)
+		logErrorf("Failed to serialize aggregated data: %v", err)
 		return
 	}
 
 	if err := conn.WriteMessage(websocket.TextMessage, data); err != nil {
-		log.Printf("Failed to send aggregated data: %v", err)
+		logErrorf("Failed to send aggregated data: %v", err)
 	}
 }
 
@@ -331,22 +827,22 @@ func (wsc *WebSocketClient) syncMissingData(conn *websocket.Conn, lastBuckets ma
 	
 	// If no last buckets info, just do a full sync
 	if len(lastBuckets) == 0 {
-		log.Println("No server checkpoint, sending full aggregated data...")
+		logInfof("No server checkpoint, sending full aggregated data...")
 		wsc.sendAggregatedData(conn)
 		return
 	}
 	
-	log.Println("Syncing missing data since server checkpoint...")
+	logInfof("Syncing missing data since server checkpoint...")
 	
 	// Get data since the server's last known buckets
 	result, err := wsc.store.GetAggregatedDataSince(lastBuckets)
 	if err != nil {
-		log.Printf("Failed to get missing data: %v", err)
+		logErrorf("Failed to get missing data: %v", err)
 		return
 	}
 	
 	if result == nil || len(result.Granularities) == 0 {
-		log.Println("No missing data to sync")
+		logInfof("No missing data to sync")
 		return
 	}
 	
@@ -357,24 +853,24 @@ func (wsc *WebSocketClient) syncMissingData(conn *websocket.Conn, lastBuckets ma
 	}
 	
 	if totalBuckets == 0 {
-		log.Println("No missing data to sync")
+		logInfof("No missing data to sync")
 		return
 	}
 	
-	log.Printf("Syncing %d missing buckets across %d granularities...", totalBuckets, len(result.Granularities))
+	logInfof("Syncing %d missing buckets across %d granularities...", totalBuckets, len(result.Granularities))
 	
 	data, err := json.Marshal(result)
 	if err != nil {
-		log.Printf("Failed to serialize missing data: %v", err)
+		logErrorf("Failed to serialize missing data: %v", err)
 		return
 	}
 	
 	if err := conn.WriteMessage(websocket.TextMessage, data); err != nil {
-		log.Printf("Failed to send missing data: %v", err)
+		logErrorf("Failed to send missing data: %v", err)
 		return
 	}
 	
-	log.Println("Missing data sync complete")
+	logInfof("Missing data sync complete")
 }
 
 // syncOfflineData sends buffered offline data to the server
@@ -391,10 +887,10 @@ func (wsc *WebSocketClient) syncOfflineData(conn *websocket.Conn) {
 		return
 	}
 
-	log.Printf("Syncing %d offline metrics to server...", pendingCount)
+	logInfof("Syncing %d offline metrics to server...", pendingCount)
 
 	// Send in batches
-	batchSize := wsc.config.BatchSize
+	batchSize := wsc.cfg().BatchSize
 	if batchSize <= 0 {
 		batchSize = 100
 	}
@@ -409,7 +905,7 @@ func (wsc *WebSocketClient) syncOfflineData(conn *websocket.Conn) {
 		// Also get aggregated metrics
 		aggregated, aggIDs, err := wsc.store.GetPendingAggregated(batchSize)
 		if err != nil {
-			log.Printf("Failed to get aggregated metrics: %v", err)
+			logErrorf("Failed to get aggregated metrics: %v", err)
 		}
 
 		// Build batch message
@@ -462,23 +958,23 @@ func (wsc *WebSocketClient) syncOfflineData(conn *websocket.Conn) {
 		// Send batch
 		data, err := json.Marshal(batch)
 		if err != nil {
-			log.Printf("Failed to serialize batch: %v", err)
+			logErrorf("Failed to serialize batch: %v", err)
 			break
 		}
 
 		if err := conn.WriteMessage(websocket.TextMessage, data); err != nil {
-			log.Printf("Failed to send batch: %v", err)
+			logErrorf("Failed to send batch: %v", err)
 			break
 		}
 
-		log.Printf("Sent batch %s with %d raw metrics and %d aggregated metrics",
+		logInfof("Sent batch %s with %d raw metrics and %d aggregated metrics",
 			batchID, len(batch.Metrics), len(batch.Aggregated))
 
 		// Delete sent metrics (optimistic - assume server will accept)
 		// In production, you might want to wait for batch_ack
 		allIDs := append(rawIDs, aggIDs...)
 		if err := wsc.store.DeleteByIDs(allIDs); err != nil {
-			log.Printf("Failed to delete sent metrics: %v", err)
+			logErrorf("Failed to delete sent metrics: %v", err)
 		}
 
 		// Small delay between batches
@@ -487,199 +983,46 @@ func (wsc *WebSocketClient) syncOfflineData(conn *websocket.Conn) {
 
 	remaining := wsc.store.GetPendingCount()
 	if remaining > 0 {
-		log.Printf("Sync incomplete: %d metrics remaining", remaining)
+		logWarnf("Sync incomplete: %d metrics remaining", remaining)
 	} else {
-		log.Println("Offline sync complete")
+		logInfof("Offline sync complete")
 	}
 }
 
-func (wsc *WebSocketClient) handleUpdateCommand(downloadURL string, force bool) {
+func (wsc *WebSocketClient) handleUpdateCommand(downloadURL string, force bool, verify UpdateVerification) error {
 	if force {
-		log.Println("Starting FORCE self-update process (will update regardless of version)...")
+		logInfof("Starting FORCE self-update process (will update regardless of version)...")
 	} else {
-		log.Println("Starting self-update process...")
-	}
-
-	// Get the current executable path
-	currentExe, err := os.Executable()
-	if err != nil {
-		log.Printf("Failed to get current executable path: %v", err)
-		return
+		logInfof("Starting self-update process...")
 	}
 
-	// Determine download URL and check version
 	url := downloadURL
-	var latestVersion string
 	if url == "" {
-		// Build GitHub Releases URL based on OS and architecture
-		osName := runtime.GOOS
-		arch := runtime.GOARCH
-		
-		// Map Go architecture names to release naming
-		if arch == "amd64" {
-			arch = "amd64"
-		} else if arch == "arm64" {
-			arch = "arm64"
-		} else if arch == "386" {
-			arch = "386"
-		}
-		
-		// Determine binary name
-		binaryName := fmt.Sprintf("vstats-agent-%s-%s", osName, arch)
-		if osName == "windows" {
-			binaryName += ".exe"
+		// No URL from the server: resolve the latest GitHub release
+		info, err := CheckForUpdate(nil)
+		if err == nil && !force && !info.Available {
+			logInfof("Already on latest version %s, skipping update", AgentVersion)
+			return nil
 		}
-		
-		// Try to get latest version from GitHub API
-		latestVersion = "latest"
-		if latest, err := fetchLatestGitHubVersion("zsai001", "vstats"); err == nil && latest != nil {
-			latestVersion = *latest
-			
-			// Skip update if already on latest version (unless force is true)
-			// Compare versions without 'v' prefix
-			latestVersionClean := strings.TrimPrefix(latestVersion, "v")
-			currentVersionClean := strings.TrimPrefix(AgentVersion, "v")
-			if !force && latestVersionClean == currentVersionClean {
-				log.Printf("Already on latest version %s, skipping update", AgentVersion)
-				return
-			}
-			log.Printf("Update available: current=%s, latest=%s", AgentVersion, latestVersion)
+		tag := "latest"
+		if err == nil {
+			tag = info.Tag
+			logInfof("Update available: current=%s, latest=%s", AgentVersion, info.Latest)
 		}
-		
-		// Build GitHub Releases download URL
-		url = fmt.Sprintf("https://github.com/zsai001/vstats/releases/download/%s/%s", latestVersion, binaryName)
-		log.Printf("No download URL provided, using GitHub Releases: %s", url)
+		url = releaseDownloadURL(tag)
+		logInfof("No download URL provided, using GitHub Releases: %s", url)
 	} else {
-		log.Printf("Using provided download URL: %s", url)
-	}
-	
-	if force {
-		log.Printf("Force update enabled, current version: %s", AgentVersion)
-	}
-
-	log.Printf("Downloading update from: %s", url)
-
-	// Download to a temporary file
-	tempPath := currentExe + ".new"
-
-	if err := downloadFile(url, tempPath); err != nil {
-		log.Printf("Failed to download update: %v", err)
-		return
-	}
-
-	log.Println("Download complete, applying update...")
-
-	// On Unix, set execute permissions
-	if runtime.GOOS != "windows" {
-		if err := os.Chmod(tempPath, 0755); err != nil {
-			log.Printf("Failed to set permissions: %v", err)
-			os.Remove(tempPath)
-			return
+		// The dashboard picked the URL, so it must also vouch for the binary
+		if verify.SHA256 == "" {
+			return fmt.Errorf("refusing download URL %s from the dashboard without a sha256 checksum", url)
 		}
+		logInfof("Using provided download URL: %s", url)
 	}
 
-	// Backup current executable
-	backupPath := currentExe + ".backup"
-	if err := os.Rename(currentExe, backupPath); err != nil {
-		log.Printf("Failed to backup current executable: %v", err)
-		os.Remove(tempPath)
-		return
-	}
-
-	// Move new executable to current path
-	if err := os.Rename(tempPath, currentExe); err != nil {
-		log.Printf("Failed to install new executable: %v", err)
-		// Try to restore backup
-		os.Rename(backupPath, currentExe)
-		return
-	}
-
-	// Remove backup
-	os.Remove(backupPath)
-
-	log.Println("Update installed successfully! Restarting...")
-
-	// Restart the agent using systemd-run to avoid being killed by cgroup
-	if runtime.GOOS == "linux" {
-		// Use systemd-run --no-block to run restart in an independent transient unit
-		// This prevents the restart command from being killed when vstats-agent stops
-		cmd := exec.Command("systemd-run", "--no-block", "systemctl", "restart", "vstats-agent")
-		if err := cmd.Start(); err != nil {
-			log.Printf("Failed to schedule restart via systemd-run: %v", err)
-			// Fallback to direct systemctl (may not work in all cases)
-			exec.Command("systemctl", "restart", "vstats-agent").Start()
-		} else {
-			log.Println("Restart scheduled via systemd-run")
-		}
-	} else if runtime.GOOS == "windows" {
-		// On Windows, use sc.exe to restart the service
-		cmd := exec.Command("cmd", "/C", "sc", "stop", "vstats-agent", "&&", "timeout", "/t", "2", "&&", "sc", "start", "vstats-agent")
-		cmd.Start()
-	}
-
-	// Give systemd-run a moment to register the restart command
-	time.Sleep(500 * time.Millisecond)
-
-	// Exit to allow restart
-	os.Exit(0)
-}
-
-// downloadFile downloads a file from URL to path
-func downloadFile(url, path string) error {
-	resp, err := http.Get(url)
-	if err != nil {
-		return fmt.Errorf("HTTP request failed: %w", err)
-	}
-	defer resp.Body.Close()
-
-	if resp.StatusCode != http.StatusOK {
-		return fmt.Errorf("download failed with status: %d", resp.StatusCode)
-	}
-
-	out, err := os.Create(path)
-	if err != nil {
-		return fmt.Errorf("failed to create file: %w", err)
-	}
-	defer out.Close()
-
-	_, err = io.Copy(out, resp.Body)
-	if err != nil {
-		os.Remove(path)
-		return fmt.Errorf("failed to write file: %w", err)
-	}
-
-	return nil
-}
-
-// fetchLatestGitHubVersion fetches the latest release version from GitHub
-func fetchLatestGitHubVersion(owner, repo string) (*string, error) {
-	url := fmt.Sprintf("https://api.github.com/repos/%s/%s/releases/latest", owner, repo)
-
-	client := &http.Client{Timeout: 10 * time.Second}
-	req, _ := http.NewRequest("GET", url, nil)
-	req.Header.Set("User-Agent", "vstats-agent")
-
-	resp, err := client.Do(req)
-	if err != nil {
-		return nil, err
-	}
-	defer resp.Body.Close()
-
-	if resp.StatusCode != http.StatusOK {
-		return nil, fmt.Errorf("GitHub API returned status: %d", resp.StatusCode)
-	}
-
-	body, _ := io.ReadAll(resp.Body)
-	var result map[string]interface{}
-	if err := json.Unmarshal(body, &result); err != nil {
-		return nil, err
-	}
-
-	tagName, ok := result["tag_name"].(string)
-	if !ok {
-		return nil, fmt.Errorf("no tag_name in response")
+	if force {
+		logInfof("Force update enabled, current version: %s", AgentVersion)
 	}
 
-	// Keep the original tag name (with 'v' prefix) for download URL
-	return &tagName, nil
+	// Returns only on failure; a successful update exits the process
+	return ApplyUpdate(wsc.cfg(), url, verify)
 }
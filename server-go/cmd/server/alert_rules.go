@@ -0,0 +1,144 @@
+package main
+
+import (
+	"fmt"
+	"time"
+)
+
+// ============================================================================
+// Threshold Alerts
+// ============================================================================
+//
+// alert_rules open an alert when a server's CPU, memory or primary disk usage
+// reaches the rule's threshold, and resolve it only once usage drops below
+// the lower recovery threshold, so a metric hovering around the limit does
+// not flap. After a rule fired for a server it stays quiet for cooldown_secs
+// even if it resolves and crosses again. The last firing time is the
+// opened_at of the newest alert in the alerts table, so a restart does not
+// re-alert everything at once.
+
+const (
+	AlertRuleCPU    = "cpu"
+	AlertRuleMemory = "memory"
+	AlertRuleDisk   = "disk"
+
+	AlertSeverityWarning = "warning"
+)
+
+// AlertRule is a usage threshold checked against every server's samples
+type AlertRule struct {
+	Metric    string  `json:"metric"`    // cpu, memory or disk (percent)
+	Threshold float64 `json:"threshold"` // Fires at or above this
+	// Resolves below this (default: the threshold)
+	RecoveryThreshold float64 `json:"recovery_threshold,omitempty"`
+	// Minimum time between two firings for the same server (default: 0)
+	CooldownSecs int    `json:"cooldown_secs,omitempty"`
+	Severity     string `json:"severity,omitempty"` // Default: warning
+}
+
+// Validate checks a single rule
+func (r *AlertRule) Validate() error {
+	switch r.Metric {
+	case AlertRuleCPU, AlertRuleMemory, AlertRuleDisk:
+	default:
+		return fmt.Errorf("unknown metric %q, expected cpu, memory or disk", r.Metric)
+	}
+	if r.Threshold <= 0 || r.Threshold > 100 {
+		return fmt.Errorf("%s: threshold must be between 0 and 100", r.Metric)
+	}
+	if r.RecoveryThreshold < 0 || r.RecoveryThreshold > r.Threshold {
+		return fmt.Errorf("%s: recovery_threshold must be between 0 and the threshold", r.Metric)
+	}
+	if r.CooldownSecs < 0 {
+		return fmt.Errorf("%s: cooldown_secs must not be negative", r.Metric)
+	}
+	return nil
+}
+
+// validateAlertRules checks every rule, and that no metric has two
+func validateAlertRules(rules []AlertRule) error {
+	seen := make(map[string]bool)
+	for i := range rules {
+		if err := rules[i].Validate(); err != nil {
+			return err
+		}
+		if seen[rules[i].Metric] {
+			return fmt.Errorf("more than one rule for %s", rules[i].Metric)
+		}
+		seen[rules[i].Metric] = true
+	}
+	return nil
+}
+
+func (r *AlertRule) recovery() float64 {
+	if r.RecoveryThreshold == 0 {
+		return r.Threshold
+	}
+	return r.RecoveryThreshold
+}
+
+func (r *AlertRule) severity() string {
+	if r.Severity == "" {
+		return AlertSeverityWarning
+	}
+	return r.Severity
+}
+
+// value returns the rule's metric from a sample, false if it has none
+func (r *AlertRule) value(serverID string, metrics *SystemMetrics) (float64, bool) {
+	switch r.Metric {
+	case AlertRuleCPU:
+		return float64(metrics.CPU.Usage), true
+	case AlertRuleMemory:
+		return float64(metrics.Memory.UsagePercent), true
+	case AlertRuleDisk:
+		usage, ok := primaryDiskUsage(serverID, metrics)
+		return float64(usage), ok
+	}
+	return 0, false
+}
+
+func hasAlertRule(rules []AlertRule, metric string) bool {
+	for i := range rules {
+		if rules[i].Metric == metric {
+			return true
+		}
+	}
+	return false
+}
+
+// ObserveMetrics checks a server's latest sample against the alert rules,
+// opening and resolving its threshold alerts. Invalid rules, and all but the
+// first rule for a metric, are skipped.
+func (m *AlertManager) ObserveMetrics(rules []AlertRule, serverID, name string, metrics *SystemMetrics, at time.Time) {
+	for i := range rules {
+		rule := &rules[i]
+		if rule.Validate() != nil || hasAlertRule(rules[:i], rule.Metric) {
+			continue
+		}
+		value, ok := rule.value(serverID, metrics)
+		if !ok {
+			continue
+		}
+
+		key := alertKey{serverID, rule.Metric}
+		m.mu.Lock()
+		open := m.open[key]
+		if open && value > m.peaks[key] {
+			m.peaks[key] = value
+		}
+		lastFired, fired := m.lastFired[key]
+		m.mu.Unlock()
+
+		switch {
+		case open && value < rule.recovery():
+			m.Resolve(serverID, rule.Metric, at)
+		case !open && value >= rule.Threshold:
+			if fired && at.Sub(lastFired) < time.Duration(rule.CooldownSecs)*time.Second {
+				continue
+			}
+			message := fmt.Sprintf("%s %s usage is %.1f%% (threshold %g%%)", name, rule.Metric, value, rule.Threshold)
+			m.Open(serverID, rule.Metric, rule.severity(), message, at, &value)
+		}
+	}
+}
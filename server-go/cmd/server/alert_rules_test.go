@@ -0,0 +1,86 @@
+package main
+
+import (
+	"testing"
+	"time"
+)
+
+func TestAlertRuleHysteresis(t *testing.T) {
+	rules := []AlertRule{{Metric: AlertRuleCPU, Threshold: 90, RecoveryThreshold: 80, CooldownSecs: 600}}
+	m := NewAlertManager()
+	key := alertKey{"s1", AlertRuleCPU}
+	now := time.Date(2026, 1, 1, 0, 0, 0, 0, time.UTC)
+	observe := func(cpu float32) bool {
+		metrics := &SystemMetrics{}
+		metrics.CPU.Usage = cpu
+		m.ObserveMetrics(rules, "s1", "web-1", metrics, now)
+		return m.open[key]
+	}
+
+	if observe(89) {
+		t.Error("opened below the threshold")
+	}
+	if !observe(91) {
+		t.Fatal("not opened at the threshold")
+	}
+	// Between the two thresholds it stays open, keeping the worst value
+	if !observe(85) || !observe(97) || !observe(80) {
+		t.Error("resolved above the recovery threshold")
+	}
+	if m.peaks[key] != 97 {
+		t.Errorf("peak = %v, want 97", m.peaks[key])
+	}
+	if observe(79) {
+		t.Error("still open below the recovery threshold")
+	}
+
+	// Crossing again within the cooldown stays quiet
+	now = now.Add(5 * time.Minute)
+	if observe(95) {
+		t.Error("fired again within the cooldown")
+	}
+	now = now.Add(5 * time.Minute)
+	if !observe(95) {
+		t.Error("not fired after the cooldown")
+	}
+}
+
+func TestAlertRuleCooldownAfterRestart(t *testing.T) {
+	db := openTestDB(t)
+	now := time.Now().UTC()
+	execAll(t, db, `INSERT INTO alerts (server_id, rule, severity, opened_at, resolved_at, peak_value, message)
+		VALUES ('s1', 'memory', 'warning', '`+now.Add(-2*time.Minute).Format(time.RFC3339)+`', '`+now.Add(-time.Minute).Format(time.RFC3339)+`', 95, '')`)
+
+	rules := []AlertRule{{Metric: AlertRuleMemory, Threshold: 90, CooldownSecs: 600}}
+	metrics := &SystemMetrics{}
+	metrics.Memory.UsagePercent = 99
+	m := NewAlertManager()
+	m.Load(db)
+	m.ObserveMetrics(rules, "s1", "web-1", metrics, now)
+	if m.open[alertKey{"s1", AlertRuleMemory}] {
+		t.Error("re-alerted within the cooldown after a restart")
+	}
+	m.ObserveMetrics(rules, "s2", "web-2", metrics, now)
+	if !m.open[alertKey{"s2", AlertRuleMemory}] {
+		t.Error("cooldown applied to another server")
+	}
+}
+
+func TestValidateAlertRules(t *testing.T) {
+	cases := []struct {
+		rules []AlertRule
+		valid bool
+	}{
+		{[]AlertRule{{Metric: AlertRuleCPU, Threshold: 90, RecoveryThreshold: 80}, {Metric: AlertRuleDisk, Threshold: 95}}, true},
+		{[]AlertRule{{Metric: "load", Threshold: 90}}, false},
+		{[]AlertRule{{Metric: AlertRuleCPU, Threshold: 0}}, false},
+		{[]AlertRule{{Metric: AlertRuleCPU, Threshold: 80, RecoveryThreshold: 90}}, false},
+		{[]AlertRule{{Metric: AlertRuleCPU, Threshold: 90, CooldownSecs: -1}}, false},
+		{[]AlertRule{{Metric: AlertRuleCPU, Threshold: 90}, {Metric: AlertRuleCPU, Threshold: 95}}, false},
+	}
+	for _, c := range cases {
+		if err := validateAlertRules(c.rules); (err == nil) != c.valid {
+			t.Errorf("%+v: %v", c.rules, err)
+		}
+	}
+}
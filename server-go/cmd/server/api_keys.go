@@ -0,0 +1,198 @@
+package main
+
+import (
+	"crypto/sha256"
+	"crypto/subtle"
+	"encoding/hex"
+	"net/http"
+	"strings"
+	"time"
+
+	"github.com/gin-gonic/gin"
+	"github.com/google/uuid"
+)
+
+// ============================================================================
+// API Keys
+// ============================================================================
+//
+// API keys give scripts and datasources such as Grafana read access without
+// the admin password: "Authorization: Bearer vsk_..." is accepted in place of
+// the admin JWT, but only on the read routes listed in apiKeyReadRoutes. Only
+// a SHA-256 hash of each key is kept in the config; the key itself is
+// returned once, by POST /api/keys. last_used is kept in memory and saved
+// with the next delayed config save (saveConfigLater).
+
+const (
+	APIKeyPrefix        = "vsk_"
+	apiKeyRandomLength  = 40 // Characters after the prefix
+	apiKeyDisplayLength = 12 // Leading characters kept to tell keys apart
+	MaxAPIKeyNameLength = 64
+	// last_used is updated at most this often per key
+	apiKeyLastUsedInterval = time.Minute
+)
+
+// apiKeyReadRoutes are the authenticated GET routes API keys may call. They
+// only read monitoring data; routes that change state or return
+// credentials, settings or the full config need the admin JWT. A route added
+// to the protected group stays admin-only until it is listed here.
+var apiKeyReadRoutes = map[string]bool{
+	"/api/servers/:id/update":        true,
+	"/api/servers/:id/connections":   true,
+	"/api/agents/update-all":         true,
+	"/api/agents/connections":        true,
+	"/api/events/agents":             true,
+	"/api/alerts":                    true,
+	"/api/admin/stats":               true,
+	"/api/admin/storage":             true,
+	"/api/history/:server_id/export": true,
+}
+
+// APIKey is a stored API key
+type APIKey struct {
+	ID        string `json:"id"`
+	Name      string `json:"name"`
+	Prefix    string `json:"prefix"`
+	Hash      string `json:"hash"` // Hex SHA-256 of the full key
+	CreatedAt string `json:"created_at"`
+	LastUsed  string `json:"last_used,omitempty"`
+}
+
+// APIKeyInfo is an API key as listed, without its hash
+type APIKeyInfo struct {
+	ID        string `json:"id"`
+	Name      string `json:"name"`
+	Prefix    string `json:"prefix"`
+	CreatedAt string `json:"created_at"`
+	LastUsed  string `json:"last_used,omitempty"`
+}
+
+func (k *APIKey) info() APIKeyInfo {
+	return APIKeyInfo{ID: k.ID, Name: k.Name, Prefix: k.Prefix, CreatedAt: k.CreatedAt, LastUsed: k.LastUsed}
+}
+
+type CreateAPIKeyRequest struct {
+	Name string `json:"name"`
+}
+
+// CreateAPIKeyResponse carries the full key, which is never shown again
+type CreateAPIKeyResponse struct {
+	APIKeyInfo
+	Key string `json:"key"`
+}
+
+func hashAPIKey(key string) string {
+	sum := sha256.Sum256([]byte(key))
+	return hex.EncodeToString(sum[:])
+}
+
+// useAPIKey reports whether key is a configured API key and records its use
+func (s *AppState) useAPIKey(key string) bool {
+	hash := hashAPIKey(key)
+	now := time.Now().UTC()
+
+	s.ConfigMu.RLock()
+	index, stale := -1, false
+	for i := range s.Config.APIKeys {
+		if subtle.ConstantTimeCompare([]byte(s.Config.APIKeys[i].Hash), []byte(hash)) == 1 {
+			index = i
+			last, err := time.Parse(time.RFC3339, s.Config.APIKeys[i].LastUsed)
+			stale = err != nil || now.Sub(last) >= apiKeyLastUsedInterval
+			break
+		}
+	}
+	s.ConfigMu.RUnlock()
+	if index < 0 {
+		return false
+	}
+
+	if stale {
+		s.ConfigMu.Lock()
+		// The key may have been deleted meanwhile, moving the others
+		for i := range s.Config.APIKeys {
+			if s.Config.APIKeys[i].Hash == hash {
+				s.Config.APIKeys[i].LastUsed = now.Format(time.RFC3339)
+				s.saveConfigLater()
+				break
+			}
+		}
+		s.ConfigMu.Unlock()
+	}
+	return true
+}
+
+// authorizeAPIKey checks an API key and its read-only scope, aborting the
+// request if either fails
+func (s *AppState) authorizeAPIKey(c *gin.Context, key string) bool {
+	if !s.useAPIKey(key) {
+		c.AbortWithStatusJSON(http.StatusUnauthorized, gin.H{"error": "Invalid API key"})
+		return false
+	}
+	path := strings.TrimPrefix(c.FullPath(), BasePath())
+	if c.Request.Method != http.MethodGet || !apiKeyReadRoutes[path] {
+		c.AbortWithStatusJSON(http.StatusForbidden, gin.H{"error": "API keys are read-only"})
+		return false
+	}
+	return true
+}
+
+// ============================================================================
+// API Key Handlers
+// ============================================================================
+
+func (s *AppState) GetAPIKeys(c *gin.Context) {
+	s.ConfigMu.RLock()
+	keys := make([]APIKeyInfo, 0, len(s.Config.APIKeys))
+	for i := range s.Config.APIKeys {
+		keys = append(keys, s.Config.APIKeys[i].info())
+	}
+	s.ConfigMu.RUnlock()
+
+	c.JSON(http.StatusOK, keys)
+}
+
+func (s *AppState) CreateAPIKey(c *gin.Context) {
+	var req CreateAPIKeyRequest
+	if err := c.ShouldBindJSON(&req); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request"})
+		return
+	}
+	req.Name = strings.TrimSpace(req.Name)
+	if req.Name == "" || len(req.Name) > MaxAPIKeyNameLength {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Name is required and must be at most 64 characters"})
+		return
+	}
+
+	key := APIKeyPrefix + GenerateRandomString(apiKeyRandomLength)
+	stored := APIKey{
+		ID:        uuid.New().String(),
+		Name:      req.Name,
+		Prefix:    key[:apiKeyDisplayLength],
+		Hash:      hashAPIKey(key),
+		CreatedAt: time.Now().UTC().Format(time.RFC3339),
+	}
+
+	s.ConfigMu.Lock()
+	s.Config.APIKeys = append(s.Config.APIKeys, stored)
+	SaveConfig(s.Config)
+	s.ConfigMu.Unlock()
+
+	c.JSON(http.StatusOK, CreateAPIKeyResponse{APIKeyInfo: stored.info(), Key: key})
+}
+
+func (s *AppState) DeleteAPIKey(c *gin.Context) {
+	id := c.Param("id")
+
+	s.ConfigMu.Lock()
+	defer s.ConfigMu.Unlock()
+
+	for i := range s.Config.APIKeys {
+		if s.Config.APIKeys[i].ID == id {
+			s.Config.APIKeys = append(s.Config.APIKeys[:i], s.Config.APIKeys[i+1:]...)
+			SaveConfig(s.Config)
+			c.Status(http.StatusOK)
+			return
+		}
+	}
+	c.JSON(http.StatusNotFound, gin.H{"error": "API key not found"})
+}
@@ -0,0 +1,76 @@
+package main
+
+import (
+	"net/http"
+	"net/http/httptest"
+	"os"
+	"path/filepath"
+	"strings"
+	"testing"
+
+	"github.com/gin-gonic/gin"
+)
+
+func TestAPIKeyScope(t *testing.T) {
+	configPath := filepath.Join(t.TempDir(), ConfigFilename)
+	t.Setenv("VSTATS_CONFIG_PATH", configPath)
+	gin.SetMode(gin.TestMode)
+
+	key := APIKeyPrefix + GenerateRandomString(apiKeyRandomLength)
+	state := NewAppState(&AppConfig{
+		AdminPasswordHash: "unchanged",
+		APIKeys:           []APIKey{{ID: "k1", Name: "grafana", Prefix: key[:apiKeyDisplayLength], Hash: hashAPIKey(key)}},
+	}, openTestDB(t))
+
+	router := gin.New()
+	protected := router.Group("/")
+	protected.Use(AuthMiddleware(state))
+	protected.POST("/api/servers", state.AddServer)
+	protected.POST("/api/auth/password", state.ChangePassword)
+	protected.GET("/api/keys", state.GetAPIKeys)
+	protected.GET("/api/settings/oauth", func(c *gin.Context) { c.Status(http.StatusOK) })
+	protected.GET("/api/alerts", func(c *gin.Context) { c.Status(http.StatusOK) })
+	request := func(method, path, token, body string) int {
+		w := httptest.NewRecorder()
+		req := httptest.NewRequest(method, path, strings.NewReader(body))
+		req.Header.Set("Authorization", "Bearer "+token)
+		req.Header.Set("Content-Type", "application/json")
+		router.ServeHTTP(w, req)
+		return w.Code
+	}
+
+	cases := []struct {
+		method, path, body string
+		want               int
+	}{
+		{http.MethodPost, "/api/servers", `{"name": "web-1", "url": "http://10.0.0.1"}`, http.StatusForbidden},
+		{http.MethodPost, "/api/auth/password", `{"current_password": "", "new_password": "taken-over"}`, http.StatusForbidden},
+		{http.MethodGet, "/api/keys", "", http.StatusForbidden},
+		{http.MethodGet, "/api/settings/oauth", "", http.StatusForbidden},
+		{http.MethodGet, "/api/alerts", "", http.StatusOK},
+	}
+	for _, c := range cases {
+		if code := request(c.method, c.path, key, c.body); code != c.want {
+			t.Errorf("%s %s: %d, want %d", c.method, c.path, code, c.want)
+		}
+	}
+	if len(state.Config.Servers) != 0 || state.Config.AdminPasswordHash != "unchanged" {
+		t.Errorf("read key changed the config: %d servers, password hash %q", len(state.Config.Servers), state.Config.AdminPasswordHash)
+	}
+	if code := request(http.MethodGet, "/api/alerts", APIKeyPrefix+"unknown", ""); code != http.StatusUnauthorized {
+		t.Errorf("unknown key: %d, want %d", code, http.StatusUnauthorized)
+	}
+
+	// Use is recorded in memory, and written with the next delayed save
+	if state.Config.APIKeys[0].LastUsed == "" {
+		t.Error("last_used not recorded")
+	}
+	if _, err := os.Stat(configPath); !os.IsNotExist(err) {
+		t.Errorf("config written on a request: %v", err)
+	}
+	state.flushConfig()
+	data, err := os.ReadFile(configPath)
+	if err != nil || !strings.Contains(string(data), `"last_used"`) {
+		t.Errorf("last_used not saved: %v", err)
+	}
+}
@@ -0,0 +1,66 @@
+package main
+
+import (
+	"encoding/json"
+	"os"
+	"strings"
+
+	"github.com/gin-gonic/gin"
+)
+
+// ============================================================================
+// Base Path
+// ============================================================================
+//
+// With base_path (or VSTATS_BASE_PATH) set to e.g. "/monitor", every route,
+// the WebSocket endpoints and the web UI are served under that prefix, for
+// reverse proxies that pass the full path through. Generated URLs (install
+// command, OAuth callbacks) include it too.
+
+var basePath string
+
+// NormalizeBasePath returns p as "/prefix" without a trailing slash, or ""
+// for the root
+func NormalizeBasePath(p string) string {
+	p = strings.Trim(strings.TrimSpace(p), "/")
+	if p == "" {
+		return ""
+	}
+	return "/" + p
+}
+
+// SetBasePath sets the URL prefix from the config, falling back to
+// VSTATS_BASE_PATH
+func SetBasePath(configured string) {
+	if configured == "" {
+		configured = os.Getenv("VSTATS_BASE_PATH")
+	}
+	basePath = NormalizeBasePath(configured)
+}
+
+// BasePath returns the URL prefix, "" when served at the root
+func BasePath() string {
+	return basePath
+}
+
+// serveIndexHTML serves the SPA entry point. Under a base path the asset
+// URLs are rewritten and the prefix is handed to the frontend, which uses it
+// for API calls, the WebSocket and client-side routes.
+func serveIndexHTML(c *gin.Context, file string) {
+	if basePath == "" {
+		c.File(file)
+		return
+	}
+	data, err := os.ReadFile(file)
+	if err != nil {
+		c.Status(404)
+		return
+	}
+	quoted, _ := json.Marshal(basePath)
+	html := strings.NewReplacer(
+		`="/assets/`, `="`+basePath+`/assets/`,
+		`="/vite.svg"`, `="`+basePath+`/vite.svg"`,
+		"</head>", "<script>window.__VSTATS_BASE_PATH__ = "+string(quoted)+";</script>\n</head>",
+	).Replace(string(data))
+	c.Data(200, "text/html; charset=utf-8", []byte(html))
+}
@@ -204,6 +204,13 @@ func (c *HistoryCache) Invalidate(serverID, rangeStr string) {
 	delete(c.entries, cacheKey(serverID, rangeStr))
 }
 
+// Clear removes all cache entries
+func (c *HistoryCache) Clear() {
+	c.mu.Lock()
+	defer c.mu.Unlock()
+	c.entries = make(map[string]*HistoryCacheEntry)
+}
+
 // cleanup periodically removes expired entries
 func (c *HistoryCache) cleanup() {
 	ticker := time.NewTicker(time.Minute)
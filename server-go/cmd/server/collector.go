@@ -28,16 +28,42 @@ type LocalMetricsCollector struct {
 	lastNetworkRx   uint64
 	lastNetworkTx   uint64
 	lastNetworkTime time.Time
+	rxSpeed         uint64 // Last computed speeds, reused by calls within localSpeedWindow
+	txSpeed         uint64
 	pingResults     *PingMetrics
 	pingResultsMu   sync.RWMutex
 	pingTargets     []common.PingTargetConfig
 	pingTargetsMu   sync.RWMutex
 	gatewayIP       string
+	snapshot        *SystemMetrics // Latest sample from the refresh loop
+	snapshotMu      sync.RWMutex
 }
 
 var localCollector *LocalMetricsCollector
 var localCollectorOnce sync.Once
 
+// LocalMetricsInterval is how often the local node is sampled
+const LocalMetricsInterval = 1 * time.Second
+
+// localSpeedWindow is the shortest interval network speeds are computed over;
+// samples closer together than this reuse the previous speeds instead of
+// reporting 0
+const localSpeedWindow = 500 * time.Millisecond
+
+// isVirtualInterface reports whether a network interface is excluded from
+// the local node's totals (loopback, containers, bridges, tunnels)
+func isVirtualInterface(name string) bool {
+	name = strings.ToLower(name)
+	return name == "lo" || name == "lo0" ||
+		strings.HasPrefix(name, "veth") ||
+		strings.HasPrefix(name, "docker") ||
+		strings.HasPrefix(name, "br-") ||
+		strings.HasPrefix(name, "virbr") ||
+		strings.HasPrefix(name, "utun") ||
+		strings.HasPrefix(name, "awdl") ||
+		strings.HasPrefix(name, "llw")
+}
+
 // GetLocalCollector returns the singleton local metrics collector
 func GetLocalCollector() *LocalMetricsCollector {
 	localCollectorOnce.Do(func() {
@@ -45,9 +71,12 @@ func GetLocalCollector() *LocalMetricsCollector {
 			lastNetworkTime: time.Now(),
 		}
 
-		// Get initial network totals
+		// Get initial network totals (same interfaces CollectMetrics counts)
 		netIO, _ := gopsutilnet.IOCounters(true)
 		for _, io := range netIO {
+			if isVirtualInterface(io.Name) {
+				continue
+			}
 			localCollector.lastNetworkRx += io.BytesRecv
 			localCollector.lastNetworkTx += io.BytesSent
 		}
@@ -331,9 +360,42 @@ func detectGateway() string {
 	return ""
 }
 
+// StartMetricsLoop samples the local node every LocalMetricsInterval so
+// readers get a cached snapshot instead of blocking on a CPU measurement
+func (lc *LocalMetricsCollector) StartMetricsLoop() {
+	lc.refresh()
+	go func() {
+		ticker := time.NewTicker(LocalMetricsInterval)
+		defer ticker.Stop()
+		for range ticker.C {
+			lc.refresh()
+		}
+	}()
+}
+
+func (lc *LocalMetricsCollector) refresh() {
+	m := collectLocalMetrics()
+	lc.snapshotMu.Lock()
+	lc.snapshot = &m
+	lc.snapshotMu.Unlock()
+}
+
+// CollectMetrics returns the latest local node sample. Before the refresh
+// loop has run it samples synchronously.
 func CollectMetrics() SystemMetrics {
-	// CPU metrics
-	cpuPercent, _ := cpu.Percent(200*time.Millisecond, true)
+	lc := GetLocalCollector()
+	lc.snapshotMu.RLock()
+	snapshot := lc.snapshot
+	lc.snapshotMu.RUnlock()
+	if snapshot != nil {
+		return *snapshot
+	}
+	return collectLocalMetrics()
+}
+
+func collectLocalMetrics() SystemMetrics {
+	// CPU metrics (usage since the previous sample, no blocking interval)
+	cpuPercent, _ := cpu.Percent(0, true)
 	cpuInfo, _ := cpu.Info()
 
 	var cpuBrand string
@@ -370,12 +432,15 @@ func CollectMetrics() SystemMetrics {
 			continue
 		}
 		diskMetrics = append(diskMetrics, DiskMetrics{
-			Name:         p.Device,
-			Total:        usage.Total,
-			Used:         usage.Used,
-			UsagePercent: float32(usage.UsedPercent),
-			DiskType:     "SSD",
-			MountPoints:  []string{p.Mountpoint},
+			Name:               p.Device,
+			Total:              usage.Total,
+			Used:               usage.Used,
+			UsagePercent:       float32(usage.UsedPercent),
+			DiskType:           "SSD",
+			MountPoints:        []string{p.Mountpoint},
+			InodesTotal:        usage.InodesTotal,
+			InodesUsed:         usage.InodesUsed,
+			InodesUsagePercent: float32(usage.InodesUsedPercent),
 		})
 	}
 
@@ -386,15 +451,7 @@ func CollectMetrics() SystemMetrics {
 
 	for _, io := range netIO {
 		// Filter out virtual interfaces
-		name := strings.ToLower(io.Name)
-		if name == "lo" || name == "lo0" ||
-			strings.HasPrefix(name, "veth") ||
-			strings.HasPrefix(name, "docker") ||
-			strings.HasPrefix(name, "br-") ||
-			strings.HasPrefix(name, "virbr") ||
-			strings.HasPrefix(name, "utun") ||
-			strings.HasPrefix(name, "awdl") ||
-			strings.HasPrefix(name, "llw") {
+		if isVirtualInterface(io.Name) {
 			continue
 		}
 
@@ -428,24 +485,24 @@ func CollectMetrics() SystemMetrics {
 	lc := GetLocalCollector()
 	pingResults := lc.getPingResults()
 
-	// Calculate network speed
+	// Calculate network speed (bytes/sec) from the counters kept between calls
 	lc.mu.Lock()
 	now := time.Now()
-	elapsed := now.Sub(lc.lastNetworkTime).Seconds()
-	var rxSpeed, txSpeed uint64
-	if elapsed > 0.1 {
-		rxDiff := totalRx - lc.lastNetworkRx
-		txDiff := totalTx - lc.lastNetworkTx
+	elapsed := now.Sub(lc.lastNetworkTime)
+	if elapsed >= localSpeedWindow {
+		lc.rxSpeed, lc.txSpeed = 0, 0
+		// Counters going backwards (interface reset) yield 0 for one window
 		if totalRx >= lc.lastNetworkRx {
-			rxSpeed = uint64(float64(rxDiff) / elapsed)
+			lc.rxSpeed = uint64(float64(totalRx-lc.lastNetworkRx) / elapsed.Seconds())
 		}
 		if totalTx >= lc.lastNetworkTx {
-			txSpeed = uint64(float64(txDiff) / elapsed)
+			lc.txSpeed = uint64(float64(totalTx-lc.lastNetworkTx) / elapsed.Seconds())
 		}
 		lc.lastNetworkRx = totalRx
 		lc.lastNetworkTx = totalTx
 		lc.lastNetworkTime = now
 	}
+	rxSpeed, txSpeed := lc.rxSpeed, lc.txSpeed
 	lc.mu.Unlock()
 
 	return SystemMetrics{
@@ -458,11 +515,13 @@ func CollectMetrics() SystemMetrics {
 			Arch:    runtime.GOARCH,
 		},
 		CPU: CpuMetrics{
-			Brand:     cpuBrand,
-			Cores:     len(cpuPercent),
-			Usage:     totalCPU,
-			Frequency: cpuFreq,
-			PerCore:   perCore,
+			Brand:            cpuBrand,
+			Cores:            len(cpuPercent),
+			Usage:            totalCPU,
+			Frequency:        cpuFreq,
+			PerCore:          perCore,
+			PhysicalCores:    common.CollectPhysicalCores(),
+			PerCoreFrequency: common.CollectPerCoreFrequency(len(cpuPercent), cpuInfo),
 		},
 		Memory: MemoryMetrics{
 			Total:        memInfo.Total,
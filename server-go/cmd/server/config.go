@@ -7,10 +7,11 @@ import (
 	"math/big"
 	"os"
 	"path/filepath"
+	"strings"
 	"sync"
+	"time"
 
 	"vstats/internal/common"
-	"golang.org/x/crypto/bcrypt"
 )
 
 const (
@@ -53,10 +54,25 @@ type ThemeSettings struct {
 }
 
 type SiteSettings struct {
-	SiteName        string         `json:"site_name"`
-	SiteDescription string         `json:"site_description"`
-	SocialLinks     []SocialLink   `json:"social_links"`
-	Theme           *ThemeSettings `json:"theme,omitempty"`
+	SiteName        string              `json:"site_name"`
+	SiteDescription string              `json:"site_description"`
+	SocialLinks     []SocialLink        `json:"social_links"`
+	Theme           *ThemeSettings      `json:"theme,omitempty"`
+	PublicMode      *PublicModeSettings `json:"public_mode,omitempty"`
+	ShowLocalNode   *bool               `json:"show_local_node,omitempty"` // Default: true
+}
+
+// LocalNodeVisible reports whether the dashboard server itself is listed
+func (s *SiteSettings) LocalNodeVisible() bool {
+	return s.ShowLocalNode == nil || *s.ShowLocalNode
+}
+
+// PublicModeSettings controls the unauthenticated /api/public/status page
+type PublicModeSettings struct {
+	Enabled bool `json:"enabled"`
+	// Fields exposed per server in addition to name and online status.
+	// Valid: location, provider, tag, version, uptime, cpu, memory, disk, load, network
+	Fields []string `json:"fields,omitempty"`
 }
 
 type SocialLink struct {
@@ -67,6 +83,9 @@ type SocialLink struct {
 
 type ProbeSettings struct {
 	PingTargets []common.PingTargetConfig `json:"ping_targets"`
+	// Name or host of the target used as the headline latency; servers can
+	// override it (default: first non-gateway target)
+	PrimaryPingTarget string `json:"primary_ping_target,omitempty"`
 }
 
 // OAuth 2.0 Configuration
@@ -130,9 +149,29 @@ type RemoteServer struct {
 	PricePeriod  string            `json:"price_period,omitempty"`
 	PurchaseDate string            `json:"purchase_date,omitempty"`
 	TipBadge     string            `json:"tip_badge,omitempty"`
+	MachineID    string            `json:"machine_id,omitempty"` // Reported at registration, for idempotent re-registration
+	// Day of the month (1-28) on which the provider's transfer allowance
+	// resets; 0 uses calendar months
+	BillingCycleDay int `json:"billing_cycle_day,omitempty"`
+	// Poll this server over SNMP instead of waiting for an agent
+	SNMP *SNMPSettings `json:"snmp,omitempty"`
+	// Mount whose usage is reported as the server's disk usage; empty uses
+	// "/" or the largest filesystem
+	PrimaryMount string `json:"primary_mount,omitempty"`
+	// Ping target (name or host) used as the headline latency; empty uses
+	// probe_settings.primary_ping_target
+	PrimaryPingTarget string `json:"primary_ping_target,omitempty"`
+	// Free-form notes and key/value metadata for operators (SSH command,
+	// owner, runbook link); not used by the server itself
+	Notes    string            `json:"notes,omitempty"`
+	Metadata map[string]string `json:"metadata,omitempty"`
+	// Delete this server's history older than N days in every resolution;
+	// 0 uses the global retention, which also caps the override
+	RetentionDays int `json:"retention_days,omitempty"`
 }
 
 type AppConfig struct {
+	ConfigVersion     int              `json:"config_version"` // See CurrentConfigVersion
 	AdminPasswordHash string           `json:"admin_password_hash"`
 	JWTSecret         string           `json:"jwt_secret"`
 	Port              string           `json:"port,omitempty"`
@@ -143,6 +182,127 @@ type AppConfig struct {
 	LocalNode         LocalNodeConfig  `json:"local_node"`
 	ProbeSettings     ProbeSettings    `json:"probe_settings"`
 	OAuth             *OAuthConfig     `json:"oauth,omitempty"`
+	// Persist a metrics_raw row at most every N seconds per server
+	// (default: 1, every sample). Live updates and aggregates are unaffected.
+	RawSampleIntervalSecs int `json:"raw_sample_interval_secs,omitempty"`
+	// Agent timestamps further than this from server time are replaced with
+	// the receive time (default: 120)
+	MaxClockSkewSecs int `json:"max_clock_skew_secs,omitempty"`
+	// Agents reporting an older protocol_version are refused with
+	// "upgrade required" (default: 0, accept all)
+	MinAgentProtocolVersion int `json:"min_agent_protocol_version,omitempty"`
+	// Live metrics are buffered and written every N milliseconds or once
+	// metrics_flush_max_rows rows are pending, whichever comes first
+	// (defaults: 2000 and 1000)
+	MetricsFlushIntervalMs int `json:"metrics_flush_interval_ms,omitempty"`
+	MetricsFlushMaxRows    int `json:"metrics_flush_max_rows,omitempty"`
+	// Days of login_audit entries to keep (default: 90)
+	LoginAuditRetentionDays int `json:"login_audit_retention_days,omitempty"`
+	// Algorithm and cost for new password hashes (default: bcrypt, cost 10)
+	PasswordHashing *PasswordHashSettings `json:"password_hashing,omitempty"`
+	// Persist per-mount disk usage at most every N seconds per server
+	// (default: 60), only for the listed mount points if any are set
+	DiskHistoryIntervalSecs int      `json:"disk_history_interval_secs,omitempty"`
+	DiskHistoryMounts       []string `json:"disk_history_mounts,omitempty"`
+	// Persist per-target ping results at most every N seconds per server
+	// (default: 30)
+	PingHistoryIntervalSecs int `json:"ping_history_interval_secs,omitempty"`
+	// URL prefix when served under a subpath, e.g. "/monitor" (default: "",
+	// or VSTATS_BASE_PATH)
+	BasePath string `json:"base_path,omitempty"`
+	// Proxy IPs or CIDR ranges whose X-Forwarded-For/X-Real-IP headers are
+	// trusted for the client IP (default: 127.0.0.1 and ::1)
+	TrustedProxies []string `json:"trusted_proxies,omitempty"`
+	// IANA timezone of server-side day boundaries such as daily bandwidth
+	// and billing cycles (default: UTC)
+	Timezone string `json:"timezone,omitempty"`
+	// Servers whose last sample is older than this many of their report
+	// intervals show as degraded (default: 1.5, negative: never)
+	DegradedAfterIntervals float64 `json:"degraded_after_intervals,omitempty"`
+	// Push metrics to an OpenTelemetry collector (default: off)
+	OTLP *OTLPSettings `json:"otlp,omitempty"`
+	// Agent connections are pinged every N seconds and dropped when nothing,
+	// not even a pong, arrives within the interval plus the pong timeout
+	// (defaults: 20 and 10; a negative interval disables pings)
+	AgentPingIntervalSecs int `json:"agent_ping_interval_secs,omitempty"`
+	AgentPongTimeoutSecs  int `json:"agent_pong_timeout_secs,omitempty"`
+	// Called when a server goes down or comes back (default: off)
+	StatusWebhook *StatusWebhookSettings `json:"status_webhook,omitempty"`
+	// Usage thresholds that open alerts, at most one per metric (default: none)
+	AlertRules []AlertRule `json:"alert_rules,omitempty"`
+	// Keep each metrics_raw sample's full metrics, compressed (default: off)
+	MetricsBlobs *MetricsBlobSettings `json:"metrics_blobs,omitempty"`
+	// Scheduled database backups (default: off)
+	Backup *BackupSettings `json:"backup,omitempty"`
+	// WebSocket message size and metrics list limits (default: see WSLimitSettings)
+	WSLimits *WSLimitSettings `json:"ws_limits,omitempty"`
+	// Read-only API keys, managed through /api/keys
+	APIKeys []APIKey `json:"api_keys,omitempty"`
+	// Embedded in dashboard tokens; bumped by /api/auth/revoke-all to
+	// invalidate all of them
+	TokenGeneration int `json:"token_generation,omitempty"`
+	// TOTP second factor of the password login, set up through /api/auth/2fa
+	TwoFactor *TwoFactorConfig `json:"two_factor,omitempty"`
+}
+
+// VisibleServerCount returns the number of servers in the dashboard feed
+func (c *AppConfig) VisibleServerCount() int {
+	if c.SiteSettings.LocalNodeVisible() {
+		return 1 + len(c.Servers)
+	}
+	return len(c.Servers)
+}
+
+// MaxClockSkew returns the tolerated agent clock skew
+func (c *AppConfig) MaxClockSkew() time.Duration {
+	if c.MaxClockSkewSecs <= 0 {
+		return DefaultMaxClockSkew
+	}
+	return time.Duration(c.MaxClockSkewSecs) * time.Second
+}
+
+// MetricsFlushInterval returns how long live metrics may stay buffered
+func (c *AppConfig) MetricsFlushInterval() time.Duration {
+	if c.MetricsFlushIntervalMs <= 0 {
+		return DefaultMetricsFlushInterval
+	}
+	return time.Duration(c.MetricsFlushIntervalMs) * time.Millisecond
+}
+
+// MetricsFlushRows returns how many live metrics rows trigger an early flush
+func (c *AppConfig) MetricsFlushRows() int {
+	if c.MetricsFlushMaxRows <= 0 {
+		return DefaultMetricsFlushMaxRows
+	}
+	return c.MetricsFlushMaxRows
+}
+
+// AgentKeepalive returns the agent ping interval and how long after a ping
+// the pong may take; a zero interval disables keepalive
+func (c *AppConfig) AgentKeepalive() (interval, timeout time.Duration) {
+	if c.AgentPingIntervalSecs < 0 {
+		return 0, 0
+	}
+	interval, timeout = DefaultAgentPingInterval, DefaultAgentPongTimeout
+	if c.AgentPingIntervalSecs > 0 {
+		interval = time.Duration(c.AgentPingIntervalSecs) * time.Second
+	}
+	if c.AgentPongTimeoutSecs > 0 {
+		timeout = time.Duration(c.AgentPongTimeoutSecs) * time.Second
+	}
+	return interval, timeout
+}
+
+// DegradedAfter returns the number of overdue report intervals after which
+// a server is degraded, 0 to never mark it degraded
+func (c *AppConfig) DegradedAfter() float64 {
+	if c.DegradedAfterIntervals < 0 {
+		return 0
+	}
+	if c.DegradedAfterIntervals == 0 {
+		return DefaultDegradedAfterIntervals
+	}
+	return c.DegradedAfterIntervals
 }
 
 func getExeDir() string {
@@ -153,20 +313,110 @@ func getExeDir() string {
 	return filepath.Dir(exe)
 }
 
-func GetConfigPath() string {
-	// Allow override via environment variable
+// Set by the --config and --data-dir flags
+var configPathFlag, dataDirFlag string
+
+// ParsePathFlags consumes --config and --data-dir (as "--flag value" or
+// "--flag=value") from args and returns the remaining arguments
+func ParsePathFlags(args []string) ([]string, error) {
+	var rest []string
+	for i := 0; i < len(args); i++ {
+		var dest *string
+		name, value, hasValue := strings.Cut(args[i], "=")
+		switch name {
+		case "--config":
+			dest = &configPathFlag
+		case "--data-dir":
+			dest = &dataDirFlag
+		default:
+			rest = append(rest, args[i])
+			continue
+		}
+		if !hasValue {
+			if i+1 >= len(args) {
+				return nil, fmt.Errorf("%s requires a path", name)
+			}
+			i++
+			value = args[i]
+		}
+		*dest = value
+	}
+	return rest, nil
+}
+
+// dataDir returns the --data-dir or VSTATS_DATA_DIR directory and where it
+// came from, or "" if neither is set
+func dataDir() (string, string) {
+	if dataDirFlag != "" {
+		return dataDirFlag, "--data-dir"
+	}
+	if dir := os.Getenv("VSTATS_DATA_DIR"); dir != "" {
+		return dir, "VSTATS_DATA_DIR"
+	}
+	return "", ""
+}
+
+// ConfigPathSource returns the config path and where it came from: --config,
+// VSTATS_CONFIG_PATH, the data directory, or next to the executable
+func ConfigPathSource() (string, string) {
+	if configPathFlag != "" {
+		return configPathFlag, "--config"
+	}
 	if configPath := os.Getenv("VSTATS_CONFIG_PATH"); configPath != "" {
-		return configPath
+		return configPath, "VSTATS_CONFIG_PATH"
+	}
+	if dir, source := dataDir(); dir != "" {
+		return filepath.Join(dir, ConfigFilename), source
 	}
-	return filepath.Join(getExeDir(), ConfigFilename)
+	return filepath.Join(getExeDir(), ConfigFilename), "executable directory"
 }
 
-func GetDBPath() string {
-	// Allow override via environment variable
+// DBPathSource returns the database path and where it came from:
+// VSTATS_DB_PATH, the data directory, or next to the executable
+func DBPathSource() (string, string) {
 	if dbPath := os.Getenv("VSTATS_DB_PATH"); dbPath != "" {
-		return dbPath
+		return dbPath, "VSTATS_DB_PATH"
+	}
+	if dir, source := dataDir(); dir != "" {
+		return filepath.Join(dir, DBFilename), source
+	}
+	return filepath.Join(getExeDir(), DBFilename), "executable directory"
+}
+
+func GetConfigPath() string {
+	path, _ := ConfigPathSource()
+	return path
+}
+
+func GetDBPath() string {
+	path, _ := DBPathSource()
+	return path
+}
+
+// PrepareDataPaths creates the data directory if one is set and checks that
+// the config can be written, so a read-only location fails at startup
+// instead of silently losing every config change
+func PrepareDataPaths() error {
+	if dir, source := dataDir(); dir != "" {
+		if err := os.MkdirAll(dir, 0755); err != nil {
+			return fmt.Errorf("cannot create data directory %s (from %s): %w", dir, source, err)
+		}
+	}
+
+	path, source := ConfigPathSource()
+	if _, err := os.Stat(path); err == nil {
+		f, err := os.OpenFile(path, os.O_WRONLY|os.O_APPEND, 0)
+		if err != nil {
+			return fmt.Errorf("config file %s (from %s) is not writable: %w", path, source, err)
+		}
+		return f.Close()
 	}
-	return filepath.Join(getExeDir(), DBFilename)
+	f, err := os.CreateTemp(filepath.Dir(path), ".vstats-write-test-*")
+	if err != nil {
+		return fmt.Errorf("config directory %s (from %s) is not writable: %w", filepath.Dir(path), source, err)
+	}
+	f.Close()
+	return os.Remove(f.Name())
 }
 
 func GetJWTSecret() string {
@@ -236,9 +486,10 @@ func GetDefaultGroupDimensions() []GroupDimension {
 
 func NewAppConfigWithRandomPassword() (*AppConfig, string) {
 	password := GenerateRandomString(16)
-	hash, _ := bcrypt.GenerateFromPassword([]byte(password), bcrypt.DefaultCost)
+	hash, _ := HashPassword(password, nil)
 	config := &AppConfig{
-		AdminPasswordHash: string(hash),
+		ConfigVersion:     CurrentConfigVersion,
+		AdminPasswordHash: hash,
 		JWTSecret:         GenerateRandomString(64),
 		Servers:           []RemoteServer{},
 		Groups:            []ServerGroup{},
@@ -256,8 +507,8 @@ func NewAppConfigWithRandomPassword() (*AppConfig, string) {
 
 func (c *AppConfig) ResetPassword() string {
 	password := GenerateRandomString(16)
-	hash, _ := bcrypt.GenerateFromPassword([]byte(password), bcrypt.DefaultCost)
-	c.AdminPasswordHash = string(hash)
+	hash, _ := HashPassword(password, c.PasswordHashing)
+	c.AdminPasswordHash = hash
 	return password
 }
 
@@ -284,31 +535,49 @@ func LoadConfig() (*AppConfig, *string) {
 			return newConfig, &password
 		}
 
+		// Upgrade older files before anything below saves them
+		if migrateConfig(&config) {
+			if err := writeConfigFile(path, &config); err != nil {
+				fmt.Printf("⚠️  Failed to write migrated config: %v\n", err)
+			}
+		}
+
 		// Verify password hash looks valid
-		if len(config.AdminPasswordHash) < 4 || config.AdminPasswordHash[:3] != "$2a" && config.AdminPasswordHash[:3] != "$2b" {
+		if err := config.PasswordHashing.Validate(); err != nil {
+			fmt.Printf("⚠️  Invalid password_hashing config: %v; new hashes will use bcrypt\n", err)
+		}
+		if err := config.OTLP.Validate(); err != nil {
+			fmt.Printf("⚠️  Invalid otlp config: %v; metrics export disabled\n", err)
+		}
+		if err := config.StatusWebhook.Validate(); err != nil {
+			fmt.Printf("⚠️  Invalid status_webhook config: %v; webhook disabled\n", err)
+		}
+		if err := config.WSLimits.Validate(); err != nil {
+			fmt.Printf("⚠️  Invalid ws_limits config: %v; using defaults for those limits\n", err)
+		}
+		if err := config.MetricsBlobs.Validate(); err != nil {
+			fmt.Printf("⚠️  Invalid metrics_blobs config: %v; using level %d\n", err, DefaultMetricsBlobLevel)
+		}
+		if err := validateAlertRules(config.AlertRules); err != nil {
+			fmt.Printf("⚠️  Invalid alert_rules config: %v; invalid and duplicate rules are ignored\n", err)
+		}
+		if !IsValidPasswordHash(config.AdminPasswordHash) {
 			fmt.Println("⚠️  Invalid password hash format, regenerating...")
 			password := GenerateRandomString(16)
-			hash, _ := bcrypt.GenerateFromPassword([]byte(password), bcrypt.DefaultCost)
-			config.AdminPasswordHash = string(hash)
+			hash, _ := HashPassword(password, config.PasswordHashing)
+			config.AdminPasswordHash = hash
 			SaveConfig(&config)
 			fmt.Printf("🔑 New password: %s\n", password)
 		} else {
 			fmt.Printf("✅ Password hash loaded (%d chars)\n", len(config.AdminPasswordHash))
 		}
 
-		// Ensure jwt_secret exists
+		// Never sign tokens with an empty secret, e.g. after a hand edit
 		if config.JWTSecret == "" {
 			config.JWTSecret = GenerateRandomString(64)
 			SaveConfig(&config)
 		}
 
-		// Initialize default group dimensions if not present
-		if len(config.GroupDimensions) == 0 {
-			config.GroupDimensions = GetDefaultGroupDimensions()
-			SaveConfig(&config)
-			fmt.Println("✅ Initialized default group dimensions")
-		}
-
 		InitJWTSecret(config.JWTSecret)
 		return &config, nil
 	}
@@ -329,6 +598,7 @@ func ResetAdminPassword() string {
 		if err == nil {
 			var c AppConfig
 			if json.Unmarshal(data, &c) == nil {
+				migrateConfig(&c)
 				config = &c
 			}
 		}
@@ -354,13 +624,83 @@ func ResetAdminPassword() string {
 }
 
 func SaveConfig(config *AppConfig) {
-	path := GetConfigPath()
+	SetPrimaryMounts(config.Servers)
+	SetPrimaryPingTargets(config)
+	SetServerRetention(config.Servers)
+	SetMetricsListLimits(config.WSLimits)
+	SetTokenGeneration(config.TokenGeneration)
+	if err := writeConfigFile(GetConfigPath(), config); err != nil {
+		fmt.Printf("Failed to write config: %v\n", err)
+	}
+}
+
+// configSaveDelay bounds how long volatile config changes, such as agents
+// reporting a new IP or version, stay unsaved
+const configSaveDelay = time.Minute
+
+// saveConfigLater saves the config within configSaveDelay, so a flapping
+// agent IP costs one write a minute rather than one per sample. Changes
+// made meanwhile are saved by the same write. Callers hold ConfigMu.
+func (s *AppState) saveConfigLater() {
+	s.configSaveMu.Lock()
+	defer s.configSaveMu.Unlock()
+	if s.configSaveTimer == nil {
+		s.configSaveTimer = time.AfterFunc(configSaveDelay, s.flushConfig)
+	}
+}
+
+// flushConfig performs a pending saveConfigLater now
+func (s *AppState) flushConfig() {
+	s.configSaveMu.Lock()
+	pending := s.configSaveTimer != nil
+	if pending {
+		s.configSaveTimer.Stop()
+		s.configSaveTimer = nil
+	}
+	s.configSaveMu.Unlock()
+	if !pending {
+		return
+	}
+
+	s.ConfigMu.RLock()
+	SaveConfig(s.Config)
+	s.ConfigMu.RUnlock()
+}
+
+// configWriteMu serializes config writes, so the file always ends up with
+// the last config saved rather than whichever write finished last
+var configWriteMu sync.Mutex
+
+// writeConfigFile writes the config to path through a temporary file in the
+// same directory, so a crash mid-write leaves either the old file or the new
+// one, never a truncated mix
+func writeConfigFile(path string, config *AppConfig) error {
+	configWriteMu.Lock()
+	defer configWriteMu.Unlock()
+
 	data, err := json.MarshalIndent(config, "", "  ")
 	if err != nil {
-		fmt.Printf("Failed to serialize config: %v\n", err)
-		return
+		return fmt.Errorf("failed to serialize config: %w", err)
 	}
-	if err := os.WriteFile(path, data, 0600); err != nil {
-		fmt.Printf("Failed to write config: %v\n", err)
+	tmp, err := os.CreateTemp(filepath.Dir(path), filepath.Base(path)+".*.tmp")
+	if err != nil {
+		return err
+	}
+	defer os.Remove(tmp.Name())
+	if err := tmp.Chmod(0600); err != nil {
+		tmp.Close()
+		return err
+	}
+	if _, err := tmp.Write(data); err != nil {
+		tmp.Close()
+		return err
+	}
+	if err := tmp.Sync(); err != nil {
+		tmp.Close()
+		return err
+	}
+	if err := tmp.Close(); err != nil {
+		return err
 	}
+	return os.Rename(tmp.Name(), path)
 }
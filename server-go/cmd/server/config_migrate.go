@@ -0,0 +1,61 @@
+package main
+
+import (
+	"fmt"
+
+	"vstats/internal/common"
+)
+
+// ============================================================================
+// Config Migrations
+// ============================================================================
+//
+// config_version records which migrations a config file has been through.
+// LoadConfig runs the missing ones in order and writes the result back
+// before anything else touches the file. Files without config_version are
+// version 0. A file from a newer build is loaded as is: its version is kept,
+// so a later downgrade-and-upgrade does not run migrations twice.
+
+// CurrentConfigVersion is the config_version written by this build
+const CurrentConfigVersion = 1
+
+// configMigrations[i] upgrades a config from version i to i+1
+var configMigrations = []func(*AppConfig){
+	migrateConfigV1,
+}
+
+// migrateConfigV1 fills in what older builds created lazily on load
+func migrateConfigV1(c *AppConfig) {
+	if c.JWTSecret == "" {
+		c.JWTSecret = GenerateRandomString(64)
+	}
+	if c.Servers == nil {
+		c.Servers = []RemoteServer{}
+	}
+	if len(c.GroupDimensions) == 0 {
+		c.GroupDimensions = GetDefaultGroupDimensions()
+	}
+	if c.ProbeSettings.PingTargets == nil {
+		c.ProbeSettings.PingTargets = []common.PingTargetConfig{}
+	}
+}
+
+// migrateConfig applies the migrations the config has not been through and
+// reports whether any ran
+func migrateConfig(c *AppConfig) bool {
+	if c.ConfigVersion > CurrentConfigVersion {
+		fmt.Printf("⚠️  Config version %d is newer than this build supports (%d); unknown settings are dropped on save\n",
+			c.ConfigVersion, CurrentConfigVersion)
+		return false
+	}
+	from := c.ConfigVersion
+	for c.ConfigVersion < CurrentConfigVersion {
+		configMigrations[c.ConfigVersion](c)
+		c.ConfigVersion++
+	}
+	if c.ConfigVersion != from {
+		fmt.Printf("✅ Migrated config from version %d to %d\n", from, c.ConfigVersion)
+		return true
+	}
+	return false
+}
@@ -3,8 +3,10 @@ package main
 import (
 	"database/sql"
 	"fmt"
+	"strconv"
 	"strings"
 	"sync"
+	"sync/atomic"
 	"time"
 
 	"vstats/internal/common"
@@ -54,6 +56,7 @@ type AggBuffer struct {
 	ping        map[PingBufferKey]*common.PingBucketData
 	flushTicker *time.Ticker
 	done        chan struct{}
+	loopDone    chan struct{}
 }
 
 // Global aggregation buffer
@@ -69,25 +72,96 @@ type MetricsBufferItem struct {
 	Metrics  *SystemMetrics
 }
 
+// Defaults for how much live metrics data may sit in memory before it is
+// written. A crash loses at most one flush interval of samples.
+const (
+	DefaultMetricsFlushInterval = 2 * time.Second
+	DefaultMetricsFlushMaxRows  = 1000
+)
+
 // MetricsBuffer accumulates real-time metrics for batch writing
 type MetricsBuffer struct {
-	mu          sync.Mutex
-	items       []MetricsBufferItem
-	flushTicker *time.Ticker
-	done        chan struct{}
-	maxSize     int
+	mu            sync.Mutex
+	items         []MetricsBufferItem
+	flushTicker   *time.Ticker
+	flushInterval time.Duration
+	done          chan struct{}
+	loopDone      chan struct{}
+	maxSize       int
+
+	// Row counters, exposed through GET /api/admin/stats
+	buffered atomic.Uint64 // Rows accepted into the buffer
+	flushed  atomic.Uint64 // Rows committed to the database
+	failed   atomic.Uint64 // Rows lost to a write error or a full write queue
+	flushes  atomic.Uint64 // Batches committed
+}
+
+// MetricsBufferStats is a snapshot of the metrics buffer counters
+type MetricsBufferStats struct {
+	Pending         int    `json:"pending"`
+	Buffered        uint64 `json:"buffered"`
+	Flushed         uint64 `json:"flushed"`
+	Failed          uint64 `json:"failed"`
+	Flushes         uint64 `json:"flushes"`
+	FlushIntervalMs int64  `json:"flush_interval_ms"`
+	FlushMaxRows    int    `json:"flush_max_rows"`
 }
 
 // Global metrics buffer
 var metricsBuffer *MetricsBuffer
 
+// ============================================================================
+// Raw Metrics Sampling
+// ============================================================================
+
+// RawSampler limits metrics_raw to one row per server per interval. Windows
+// are aligned to the Unix epoch (timestamp / interval), so the kept rows are
+// evenly spaced no matter when samples arrive. The 5-second and 2-minute
+// aggregation tables still receive every sample.
+type RawSampler struct {
+	mu       sync.Mutex
+	interval int64
+	last     map[string]int64 // server_id -> last stored window
+}
+
+var rawSampler = &RawSampler{interval: 1, last: make(map[string]int64)}
+
+// SetRawSampleInterval sets the metrics_raw sampling interval in seconds
+func SetRawSampleInterval(secs int) {
+	if secs < 1 {
+		secs = 1
+	}
+	rawSampler.mu.Lock()
+	defer rawSampler.mu.Unlock()
+	rawSampler.interval = int64(secs)
+	rawSampler.last = make(map[string]int64)
+}
+
+// ShouldStore reports whether a sample opens a new window and should be
+// written to metrics_raw
+func (rs *RawSampler) ShouldStore(serverID string, ts time.Time) bool {
+	rs.mu.Lock()
+	defer rs.mu.Unlock()
+	if rs.interval <= 1 {
+		return true
+	}
+	window := ts.Unix() / rs.interval
+	if last, ok := rs.last[serverID]; ok && last == window {
+		return false
+	}
+	rs.last[serverID] = window
+	return true
+}
+
 // NewMetricsBuffer creates a new metrics buffer
 func NewMetricsBuffer(flushInterval time.Duration, maxSize int) *MetricsBuffer {
 	mb := &MetricsBuffer{
-		items:       make([]MetricsBufferItem, 0, maxSize),
-		flushTicker: time.NewTicker(flushInterval),
-		done:        make(chan struct{}),
-		maxSize:     maxSize,
+		items:         make([]MetricsBufferItem, 0, maxSize),
+		flushTicker:   time.NewTicker(flushInterval),
+		flushInterval: flushInterval,
+		done:          make(chan struct{}),
+		loopDone:      make(chan struct{}),
+		maxSize:       maxSize,
 	}
 	go mb.flushLoop()
 	return mb
@@ -103,6 +177,7 @@ func (mb *MetricsBuffer) Add(serverID string, metrics *SystemMetrics) {
 		ServerID: serverID,
 		Metrics:  &copied,
 	})
+	mb.buffered.Add(1)
 	
 	// Force flush if buffer is full
 	if len(mb.items) >= mb.maxSize {
@@ -118,6 +193,7 @@ func (mb *MetricsBuffer) Add(serverID string, metrics *SystemMetrics) {
 
 // flushLoop periodically flushes the buffer
 func (mb *MetricsBuffer) flushLoop() {
+	defer close(mb.loopDone)
 	for {
 		select {
 		case <-mb.flushTicker.C:
@@ -150,15 +226,43 @@ func (mb *MetricsBuffer) flushItems(items []MetricsBufferItem) {
 		return
 	}
 	
-	dbWriter.WriteAsync(func(db *sql.DB) error {
-		return batchStoreMetrics(db, items)
+	n := uint64(len(items))
+	queued := dbWriter.WriteAsync(func(db *sql.DB) error {
+		if err := batchStoreMetrics(db, items); err != nil {
+			mb.failed.Add(n)
+			return err
+		}
+		mb.flushed.Add(n)
+		mb.flushes.Add(1)
+		return nil
 	})
+	if !queued {
+		mb.failed.Add(n)
+	}
+}
+
+// Stats returns the buffer counters
+func (mb *MetricsBuffer) Stats() MetricsBufferStats {
+	mb.mu.Lock()
+	pending := len(mb.items)
+	mb.mu.Unlock()
+	return MetricsBufferStats{
+		Pending:         pending,
+		Buffered:        mb.buffered.Load(),
+		Flushed:         mb.flushed.Load(),
+		Failed:          mb.failed.Load(),
+		Flushes:         mb.flushes.Load(),
+		FlushIntervalMs: mb.flushInterval.Milliseconds(),
+		FlushMaxRows:    mb.maxSize,
+	}
 }
 
-// Close stops the buffer
+// Close stops the buffer and waits until remaining items are queued on the
+// DB writer. Close the DB writer afterwards to get them on disk.
 func (mb *MetricsBuffer) Close() {
 	mb.flushTicker.Stop()
 	close(mb.done)
+	<-mb.loopDone
 }
 
 // GetLastMetricsTime returns the last metrics timestamp for a server
@@ -250,16 +354,17 @@ func batchStoreMetrics(db *sql.DB, items []MetricsBufferItem) error {
 	
 	// Prepare statements for batch insert
 	rawStmt, err := tx.Prepare(`
-		INSERT INTO metrics_raw (server_id, timestamp, cpu_usage, memory_usage, disk_usage, net_rx, net_tx, load_1, load_5, load_15, ping_ms, bucket_5min, bucket_5sec)
-		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)`)
+		INSERT INTO metrics_raw (server_id, timestamp, cpu_usage, memory_usage, disk_usage, net_rx, net_tx, net_rx_rate, net_tx_rate, load_1, load_5, load_15, swap_usage, ping_ms, bucket_5min, bucket_5sec, metrics_blob)
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)`)
 	if err != nil {
 		return err
 	}
 	defer rawStmt.Close()
 	
 	stmt5sec, err := tx.Prepare(`
-		INSERT INTO metrics_5sec (server_id, bucket, cpu_sum, cpu_max, memory_sum, memory_max, disk_sum, net_rx, net_tx, ping_sum, ping_count, sample_count)
-		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1)
+		INSERT INTO metrics_5sec (server_id, bucket, cpu_sum, cpu_max, memory_sum, memory_max, disk_sum, net_rx, net_tx, ping_sum, ping_count,
+			load_1_sum, load_5_sum, load_15_sum, swap_sum, net_rx_rate_sum, net_tx_rate_sum, load_count, net_rate_count, sample_count)
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 1, 1)
 		ON CONFLICT(server_id, bucket) DO UPDATE SET
 			cpu_sum = cpu_sum + excluded.cpu_sum,
 			cpu_max = MAX(cpu_max, excluded.cpu_max),
@@ -270,6 +375,14 @@ func batchStoreMetrics(db *sql.DB, items []MetricsBufferItem) error {
 			net_tx = MAX(net_tx, excluded.net_tx),
 			ping_sum = ping_sum + excluded.ping_sum,
 			ping_count = ping_count + excluded.ping_count,
+			load_1_sum = load_1_sum + excluded.load_1_sum,
+			load_5_sum = load_5_sum + excluded.load_5_sum,
+			load_15_sum = load_15_sum + excluded.load_15_sum,
+			swap_sum = swap_sum + excluded.swap_sum,
+			net_rx_rate_sum = net_rx_rate_sum + excluded.net_rx_rate_sum,
+			net_tx_rate_sum = net_tx_rate_sum + excluded.net_tx_rate_sum,
+			load_count = load_count + 1,
+			net_rate_count = net_rate_count + 1,
 			sample_count = sample_count + 1`)
 	if err != nil {
 		return err
@@ -277,8 +390,9 @@ func batchStoreMetrics(db *sql.DB, items []MetricsBufferItem) error {
 	defer stmt5sec.Close()
 	
 	stmt2min, err := tx.Prepare(`
-		INSERT INTO metrics_2min (server_id, bucket, cpu_sum, cpu_max, memory_sum, memory_max, disk_sum, net_rx, net_tx, ping_sum, ping_count, sample_count)
-		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1)
+		INSERT INTO metrics_2min (server_id, bucket, cpu_sum, cpu_max, memory_sum, memory_max, disk_sum, net_rx, net_tx, ping_sum, ping_count,
+			load_1_sum, load_5_sum, load_15_sum, swap_sum, net_rx_rate_sum, net_tx_rate_sum, load_count, net_rate_count, sample_count)
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 1, 1)
 		ON CONFLICT(server_id, bucket) DO UPDATE SET
 			cpu_sum = cpu_sum + excluded.cpu_sum,
 			cpu_max = MAX(cpu_max, excluded.cpu_max),
@@ -289,54 +403,84 @@ func batchStoreMetrics(db *sql.DB, items []MetricsBufferItem) error {
 			net_tx = MAX(net_tx, excluded.net_tx),
 			ping_sum = ping_sum + excluded.ping_sum,
 			ping_count = ping_count + excluded.ping_count,
+			load_1_sum = load_1_sum + excluded.load_1_sum,
+			load_5_sum = load_5_sum + excluded.load_5_sum,
+			load_15_sum = load_15_sum + excluded.load_15_sum,
+			swap_sum = swap_sum + excluded.swap_sum,
+			net_rx_rate_sum = net_rx_rate_sum + excluded.net_rx_rate_sum,
+			net_tx_rate_sum = net_tx_rate_sum + excluded.net_tx_rate_sum,
+			load_count = load_count + 1,
+			net_rate_count = net_rate_count + 1,
 			sample_count = sample_count + 1`)
 	if err != nil {
 		return err
 	}
 	defer stmt2min.Close()
 	
+	diskStmt, err := tx.Prepare(insertDiskRowSQL)
+	if err != nil {
+		return err
+	}
+	defer diskStmt.Close()
+	
+	pingStmt, err := tx.Prepare(insertPingRowSQL)
+	if err != nil {
+		return err
+	}
+	defer pingStmt.Close()
+	
+	bandwidthStmt, err := tx.Prepare(insertBandwidthDailySQL)
+	if err != nil {
+		return err
+	}
+	defer bandwidthStmt.Close()
+	
+	counterStmt, err := tx.Prepare(upsertBandwidthCounterSQL)
+	if err != nil {
+		return err
+	}
+	defer counterStmt.Close()
+	
 	for _, item := range items {
 		metrics := item.Metrics
 		serverID := item.ServerID
 		
-		var diskUsage float32 = 0
-		if len(metrics.Disks) > 0 {
-			diskUsage = metrics.Disks[0].UsagePercent
-		}
+		diskUsage, _ := primaryDiskUsage(serverID, metrics)
 		
-		timestamp := metrics.Timestamp.Format(time.RFC3339)
+		timestamp := storeTimestamp(metrics.Timestamp)
 		bucket5min := metrics.Timestamp.Unix() / 120
 		bucket5sec := metrics.Timestamp.Unix() / 5
+		swapUsage := swapUsagePercent(metrics)
 		
-		// Get ping
-		var pingMs *float64
+		// Get headline ping
+		pingMs := headlinePing(serverID, metrics.Ping)
 		var pingVal float64
 		var pingCnt int
-		if metrics.Ping != nil && len(metrics.Ping.Targets) > 0 {
-			var sum float64
-			var count int
-			for _, t := range metrics.Ping.Targets {
-				if t.LatencyMs != nil {
-					sum += *t.LatencyMs
-					count++
-				}
-			}
-			if count > 0 {
-				avg := sum / float64(count)
-				pingMs = &avg
-				pingVal = avg
-				pingCnt = 1
-			}
+		if pingMs != nil {
+			pingVal = *pingMs
+			pingCnt = 1
 		}
 		
-		// Insert raw
-		rawStmt.Exec(
-			serverID, timestamp,
-			metrics.CPU.Usage, metrics.Memory.UsagePercent, diskUsage,
-			metrics.Network.TotalRx, metrics.Network.TotalTx,
-			metrics.LoadAverage.One, metrics.LoadAverage.Five, metrics.LoadAverage.Fifteen,
-			pingMs, bucket5min, bucket5sec,
-		)
+		// Insert raw (sampled per raw_sample_interval_secs)
+		if rawSampler.ShouldStore(serverID, metrics.Timestamp) {
+			rawStmt.Exec(
+				serverID, timestamp,
+				metrics.CPU.Usage, metrics.Memory.UsagePercent, diskUsage,
+				metrics.Network.TotalRx, metrics.Network.TotalTx,
+				metrics.Network.RxSpeed, metrics.Network.TxSpeed,
+				metrics.LoadAverage.One, metrics.LoadAverage.Five, metrics.LoadAverage.Fifteen, swapUsage,
+				pingMs, bucket5min, bucket5sec, encodeMetricsBlob(metrics),
+			)
+		}
+		
+		// Insert per-mount disk rows (sampled per disk_history_interval_secs)
+		storeDiskRows(diskStmt.Exec, serverID, metrics, timestamp)
+		
+		// Insert per-target ping rows (sampled per ping_history_interval_secs)
+		storePingRows(pingStmt.Exec, serverID, metrics, timestamp)
+		
+		// Account traffic since the previous sample
+		storeBandwidth(bandwidthStmt.Exec, counterStmt.Exec, serverID, metrics)
 		
 		// Insert to 5sec aggregation
 		stmt5sec.Exec(
@@ -346,6 +490,8 @@ func batchStoreMetrics(db *sql.DB, items []MetricsBufferItem) error {
 			float64(diskUsage),
 			metrics.Network.TotalRx, metrics.Network.TotalTx,
 			pingVal, pingCnt,
+			metrics.LoadAverage.One, metrics.LoadAverage.Five, metrics.LoadAverage.Fifteen, swapUsage,
+			metrics.Network.RxSpeed, metrics.Network.TxSpeed,
 		)
 		
 		// Insert to 2min aggregation
@@ -356,6 +502,8 @@ func batchStoreMetrics(db *sql.DB, items []MetricsBufferItem) error {
 			float64(diskUsage),
 			metrics.Network.TotalRx, metrics.Network.TotalTx,
 			pingVal, pingCnt,
+			metrics.LoadAverage.One, metrics.LoadAverage.Five, metrics.LoadAverage.Fifteen, swapUsage,
+			metrics.Network.RxSpeed, metrics.Network.TxSpeed,
 		)
 	}
 	
@@ -369,6 +517,7 @@ func NewAggBuffer(flushInterval time.Duration) *AggBuffer {
 		ping:        make(map[PingBufferKey]*common.PingBucketData),
 		flushTicker: time.NewTicker(flushInterval),
 		done:        make(chan struct{}),
+		loopDone:    make(chan struct{}),
 	}
 	go ab.flushLoop()
 	return ab
@@ -444,6 +593,7 @@ func (ab *AggBuffer) Add(serverID string, granularities []common.GranularityData
 
 // flushLoop periodically flushes the buffer to database
 func (ab *AggBuffer) flushLoop() {
+	defer close(ab.loopDone)
 	for {
 		select {
 		case <-ab.flushTicker.C:
@@ -496,6 +646,7 @@ func (ab *AggBuffer) Stats() (metricsCount, pingCount int) {
 func (ab *AggBuffer) Close() {
 	ab.flushTicker.Stop()
 	close(ab.done)
+	<-ab.loopDone
 }
 
 // flushAggBufferToDB writes buffered data to database using batch inserts
@@ -756,12 +907,15 @@ func (w *DBWriter) processWrites() {
 	}
 }
 
-// WriteAsync queues a write operation (fire-and-forget)
-func (w *DBWriter) WriteAsync(fn func(*sql.DB) error) {
+// WriteAsync queues a write operation (fire-and-forget). It returns false if
+// the queue was full and the write was dropped.
+func (w *DBWriter) WriteAsync(fn func(*sql.DB) error) bool {
 	select {
 	case w.writeCh <- writeJob{fn: fn, result: nil}:
+		return true
 	default:
 		fmt.Println("Warning: write queue full, dropping write")
+		return false
 	}
 }
 
@@ -783,21 +937,71 @@ func (w *DBWriter) GetDB() *sql.DB {
 	return w.db
 }
 
+// sqlitePragmas are applied to every pooled connection. Running them once via
+// db.Exec would only configure whichever connection happened to execute it.
+var sqlitePragmas = []string{
+	"busy_timeout(5000)",  // Wait for locks instead of failing with "database is locked"
+	"journal_mode(WAL)",   // Readers don't block the writer and vice versa
+	"synchronous(NORMAL)", // Safe with WAL, far fewer fsyncs than FULL
+	"cache_size(-20000)",  // ~20MB page cache per connection
+	"temp_store(MEMORY)",  // Sorts and temp indexes for history queries stay in RAM
+}
+
+// sqliteDSN builds the connection string for the modernc driver
+func sqliteDSN(path string) string {
+	params := make([]string, len(sqlitePragmas))
+	for i, p := range sqlitePragmas {
+		params[i] = "_pragma=" + p
+	}
+	return path + "?" + strings.Join(params, "&")
+}
+
+// MemoryDBPath makes InitDatabaseAt open an empty in-memory database, e.g.
+// for tests
+const MemoryDBPath = ":memory:"
+
+// InitDatabase opens and migrates the database at GetDBPath()
 func InitDatabase() (*sql.DB, error) {
-	// Open database with busy_timeout as fallback
-	db, err := sql.Open("sqlite", GetDBPath()+"?_busy_timeout=5000")
+	return InitDatabaseAt(GetDBPath())
+}
+
+// InitDatabaseAt opens and migrates the database at path. An in-memory
+// database (MemoryDBPath) lives until it is closed and is limited to one
+// connection, since every connection to ":memory:" gets a database of its
+// own; callers must not run a query while still reading rows from another,
+// and must query through a transaction while they hold one open.
+func InitDatabaseAt(path string) (*sql.DB, error) {
+	memory := path == MemoryDBPath
+
+	// A corrupt database is moved aside rather than failing every start
+	if !memory {
+		if err := quarantineCorruptDatabase(path); err != nil {
+			return nil, err
+		}
+	}
+
+	db, err := sql.Open("sqlite", sqliteDSN(path))
 	if err != nil {
 		return nil, err
 	}
+	if memory {
+		db.SetMaxOpenConns(1)
+	}
 
-	// Enable WAL mode for better concurrent read access
-	if _, err := db.Exec("PRAGMA journal_mode=WAL"); err != nil {
-		fmt.Printf("Warning: Failed to enable WAL mode: %v\n", err)
+	// WAL is persistent, so check it actually took effect (it can't on some
+	// network filesystems)
+	var journalMode string
+	if err := db.QueryRow("PRAGMA journal_mode").Scan(&journalMode); err != nil {
+		return nil, fmt.Errorf("failed to open database: %w", err)
+	}
+	if !memory && !strings.EqualFold(journalMode, "wal") {
+		fmt.Printf("Warning: SQLite is using journal_mode=%s instead of WAL; expect lock contention\n", journalMode)
 	}
 
-	// Set synchronous to NORMAL for better performance while still being safe
-	if _, err := db.Exec("PRAGMA synchronous=NORMAL"); err != nil {
-		fmt.Printf("Warning: Failed to set synchronous mode: %v\n", err)
+	if !memory {
+		if err := backupBeforeMigration(db, path); err != nil {
+			return nil, err
+		}
 	}
 
 	// Create tables
@@ -852,6 +1056,9 @@ func InitDatabase() (*sql.DB, error) {
 			net_rx_total INTEGER NOT NULL,
 			net_tx_total INTEGER NOT NULL,
 			ping_avg REAL,
+			cpu_p95 REAL,
+			memory_p95 REAL,
+			ping_max REAL,
 			sample_count INTEGER NOT NULL,
 			UNIQUE(server_id, hour_start)
 		);
@@ -870,6 +1077,9 @@ func InitDatabase() (*sql.DB, error) {
 			net_tx_total INTEGER NOT NULL,
 			uptime_percent REAL NOT NULL,
 			ping_avg REAL,
+			cpu_p95 REAL,
+			memory_p95 REAL,
+			ping_max REAL,
 			sample_count INTEGER NOT NULL,
 			UNIQUE(server_id, date)
 		);
@@ -1002,6 +1212,10 @@ func InitDatabase() (*sql.DB, error) {
 		db.Exec("UPDATE ping_raw SET bucket_5sec = CAST(strftime('%s', timestamp) AS INTEGER) / 5 WHERE bucket_5sec IS NULL")
 	}
 
+	// Remove rows dated in the future by agents with broken clocks (one-time cleanup;
+	// such rows sort above everything in charts and are never reached by retention)
+	deleteFutureMetrics(db)
+
 	// Create real-time aggregation tables for fast queries
 	db.Exec(`
 		-- 5-second aggregated metrics (for 1h queries, ~720 points per server)
@@ -1057,6 +1271,9 @@ func InitDatabase() (*sql.DB, error) {
 			ping_sum REAL NOT NULL DEFAULT 0,
 			ping_count INTEGER NOT NULL DEFAULT 0,
 			sample_count INTEGER NOT NULL DEFAULT 0,
+			cpu_p95 REAL, -- Filled from metrics_raw once the bucket closes
+			memory_p95 REAL,
+			ping_max REAL,
 			PRIMARY KEY (server_id, bucket)
 		) WITHOUT ROWID
 	`)
@@ -1076,6 +1293,9 @@ func InitDatabase() (*sql.DB, error) {
 			ping_sum REAL NOT NULL DEFAULT 0,
 			ping_count INTEGER NOT NULL DEFAULT 0,
 			sample_count INTEGER NOT NULL DEFAULT 0,
+			cpu_p95 REAL, -- Filled from metrics_raw once the bucket closes
+			memory_p95 REAL,
+			ping_max REAL,
 			PRIMARY KEY (server_id, bucket)
 		) WITHOUT ROWID
 	`)
@@ -1095,6 +1315,9 @@ func InitDatabase() (*sql.DB, error) {
 			ping_sum REAL NOT NULL DEFAULT 0,
 			ping_count INTEGER NOT NULL DEFAULT 0,
 			sample_count INTEGER NOT NULL DEFAULT 0,
+			cpu_p95 REAL, -- Filled from metrics_raw once the bucket closes
+			memory_p95 REAL,
+			ping_max REAL,
 			PRIMARY KEY (server_id, bucket)
 		) WITHOUT ROWID
 	`)
@@ -1180,15 +1403,84 @@ func InitDatabase() (*sql.DB, error) {
 		) WITHOUT ROWID
 	`)
 
+	db.Exec(`
+		-- Login attempts (password and OAuth), pruned after login_audit_retention_days
+		CREATE TABLE IF NOT EXISTS login_audit (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			timestamp TEXT NOT NULL,
+			username TEXT NOT NULL,
+			method TEXT NOT NULL,
+			client_ip TEXT NOT NULL,
+			success INTEGER NOT NULL,
+			reason TEXT NOT NULL DEFAULT ''
+		);
+		CREATE INDEX IF NOT EXISTS idx_login_audit_timestamp ON login_audit(timestamp);
+	`)
+
+	// Per-mount disk history
+	createDiskTables(db)
+	createPingTables(db)
+	createBandwidthTables(db)
+	loadBandwidthCounters(db)
+	createAvailabilityTables(db)
+	markAvailabilityGap(db)
+	createAgentEventsTables(db)
+	createAlertTables(db)
+	createRevokedTokensTable(db)
+	loadRevokedTokens(db)
+
+	// Migration: Add p95/max columns to aggregate tables (NULL for older rows)
+	migratePercentileColumns(db)
+	migrateLoadSwapColumns(db)
+	migrateNetRateColumns(db)
+	migrateMetricsBlobColumn(db)
+	migrateHistoryIndexes(db)
+	setSchemaVersion(db)
+
 	// Run ANALYZE in background to avoid slow startup
 	go func() {
 		time.Sleep(10 * time.Second) // Wait for server to fully start
 		db.Exec("ANALYZE")
+		for _, problem := range checkHistoryQueryPlans(db) {
+			fmt.Printf("⚠️  History query plan: %s\n", problem)
+		}
 	}()
 
 	return db, nil
 }
 
+// deleteFutureMetrics deletes metrics rows timestamped beyond the tolerated
+// clock skew. New samples are corrected on receipt, so this only finds rows
+// written before skew correction existed.
+func deleteFutureMetrics(db *sql.DB) {
+	now := time.Now().UTC()
+	future := now.Add(DefaultMaxClockSkew)
+	cleanups := []struct {
+		table  string
+		column string
+		cutoff string
+	}{
+		{"metrics_raw", "timestamp", future.Format(time.RFC3339)},
+		{"metrics_hourly", "hour_start", now.Format("2006-01-02T15:00:00Z")},
+		{"metrics_daily", "date", now.Format("2006-01-02")},
+	}
+
+	for _, c := range cleanups {
+		var exists int
+		db.QueryRow(fmt.Sprintf("SELECT 1 FROM %s WHERE %s > ? LIMIT 1", c.table, c.column), c.cutoff).Scan(&exists)
+		if exists != 1 {
+			continue
+		}
+		result, err := db.Exec(fmt.Sprintf("DELETE FROM %s WHERE %s > ?", c.table, c.column), c.cutoff)
+		if err != nil {
+			fmt.Printf("Failed to delete future-dated rows from %s: %v\n", c.table, err)
+			continue
+		}
+		n, _ := result.RowsAffected()
+		fmt.Printf("🕐 Deleted %d future-dated rows from %s\n", n, c.table)
+	}
+}
+
 // StoreMetricsAsync queues metrics storage (fire-and-forget)
 func StoreMetricsAsync(serverID string, metrics *SystemMetrics) {
 	if dbWriter == nil {
@@ -1356,7 +1648,7 @@ func storeMultiGranularityMetricsInternal(db *sql.DB, serverID string, granulari
 
 // storeMetricsWithDedupInternal stores metrics with timestamp-based deduplication
 func storeMetricsWithDedupInternal(db *sql.DB, serverID string, metrics *SystemMetrics) error {
-	timestamp := metrics.Timestamp.Format(time.RFC3339)
+	timestamp := storeTimestamp(metrics.Timestamp)
 	bucket5sec := metrics.Timestamp.Unix() / 5
 	
 	// Check if we already have data for this exact timestamp
@@ -1371,10 +1663,11 @@ func storeMetricsWithDedupInternal(db *sql.DB, serverID string, metrics *SystemM
 		return nil
 	}
 	
-	// Also check if we have data in the same 5-second bucket to avoid near-duplicates
+	// Also check if we have data in the same 5-second bucket to avoid near-duplicates.
+	// metrics_5sec sees every sample, while metrics_raw may be sampled.
 	err = db.QueryRow(`
-		SELECT 1 FROM metrics_raw 
-		WHERE server_id = ? AND bucket_5sec = ?
+		SELECT 1 FROM metrics_5sec 
+		WHERE server_id = ? AND bucket = ?
 		LIMIT 1`, serverID, bucket5sec).Scan(&exists)
 	
 	if err == nil && exists == 1 {
@@ -1470,55 +1763,62 @@ func StoreMetrics(db *sql.DB, serverID string, metrics *SystemMetrics) error {
 }
 
 func storeMetricsInternal(db *sql.DB, serverID string, metrics *SystemMetrics) error {
-	var diskUsage float32 = 0
-	if len(metrics.Disks) > 0 {
-		diskUsage = metrics.Disks[0].UsagePercent
-	}
+	diskUsage, _ := primaryDiskUsage(serverID, metrics)
 
-	timestamp := metrics.Timestamp.Format(time.RFC3339)
+	timestamp := storeTimestamp(metrics.Timestamp)
 	// Pre-compute 2-minute bucket for efficient 24h sampling (720 points over 24h)
 	bucket5min := metrics.Timestamp.Unix() / 120
 	// Pre-compute 5-second bucket for efficient 1h sampling (720 points over 1h)
 	bucket5sec := metrics.Timestamp.Unix() / 5
-
-	// Get average ping latency from all targets
-	var pingMs *float64
-	if metrics.Ping != nil && len(metrics.Ping.Targets) > 0 {
-		var sum float64
-		var count int
-		for _, t := range metrics.Ping.Targets {
-			if t.LatencyMs != nil {
-				sum += *t.LatencyMs
-				count++
-			}
-		}
-		if count > 0 {
-			avg := sum / float64(count)
-			pingMs = &avg
+	swapUsage := swapUsagePercent(metrics)
+
+	// Get the headline ping latency (primary target)
+	pingMs := headlinePing(serverID, metrics.Ping)
+
+	// Insert raw data (for debugging and fallback), sampled per raw_sample_interval_secs
+	if rawSampler.ShouldStore(serverID, metrics.Timestamp) {
+		_, err := db.Exec(`
+			INSERT INTO metrics_raw (server_id, timestamp, cpu_usage, memory_usage, disk_usage, net_rx, net_tx, net_rx_rate, net_tx_rate, load_1, load_5, load_15, swap_usage, ping_ms, bucket_5min, bucket_5sec, metrics_blob)
+			VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)`,
+			serverID,
+			timestamp,
+			metrics.CPU.Usage,
+			metrics.Memory.UsagePercent,
+			diskUsage,
+			metrics.Network.TotalRx,
+			metrics.Network.TotalTx,
+			metrics.Network.RxSpeed,
+			metrics.Network.TxSpeed,
+			metrics.LoadAverage.One,
+			metrics.LoadAverage.Five,
+			metrics.LoadAverage.Fifteen,
+			swapUsage,
+			pingMs,
+			bucket5min,
+			bucket5sec,
+			encodeMetricsBlob(metrics),
+		)
+		if err != nil {
+			return err
 		}
 	}
 
-	// Insert raw data (for debugging and fallback)
-	_, err := db.Exec(`
-		INSERT INTO metrics_raw (server_id, timestamp, cpu_usage, memory_usage, disk_usage, net_rx, net_tx, load_1, load_5, load_15, ping_ms, bucket_5min, bucket_5sec)
-		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)`,
-		serverID,
-		timestamp,
-		metrics.CPU.Usage,
-		metrics.Memory.UsagePercent,
-		diskUsage,
-		metrics.Network.TotalRx,
-		metrics.Network.TotalTx,
-		metrics.LoadAverage.One,
-		metrics.LoadAverage.Five,
-		metrics.LoadAverage.Fifteen,
-		pingMs,
-		bucket5min,
-		bucket5sec,
-	)
-	if err != nil {
-		return err
-	}
+	// Insert per-mount disk rows, sampled per disk_history_interval_secs
+	storeDiskRows(func(args ...interface{}) (sql.Result, error) {
+		return db.Exec(insertDiskRowSQL, args...)
+	}, serverID, metrics, timestamp)
+
+	// Insert per-target ping rows, sampled per ping_history_interval_secs
+	storePingRows(func(args ...interface{}) (sql.Result, error) {
+		return db.Exec(insertPingRowSQL, args...)
+	}, serverID, metrics, timestamp)
+
+	// Account traffic since the previous sample
+	storeBandwidth(func(args ...interface{}) (sql.Result, error) {
+		return db.Exec(insertBandwidthDailySQL, args...)
+	}, func(args ...interface{}) (sql.Result, error) {
+		return db.Exec(upsertBandwidthCounterSQL, args...)
+	}, serverID, metrics)
 
 	// UPSERT to 5-second aggregation table (for 1h queries)
 	pingVal := float64(0)
@@ -1528,8 +1828,9 @@ func storeMetricsInternal(db *sql.DB, serverID string, metrics *SystemMetrics) e
 		pingCnt = 1
 	}
 	db.Exec(`
-		INSERT INTO metrics_5sec (server_id, bucket, cpu_sum, cpu_max, memory_sum, memory_max, disk_sum, net_rx, net_tx, ping_sum, ping_count, sample_count)
-		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1)
+		INSERT INTO metrics_5sec (server_id, bucket, cpu_sum, cpu_max, memory_sum, memory_max, disk_sum, net_rx, net_tx, ping_sum, ping_count,
+			load_1_sum, load_5_sum, load_15_sum, swap_sum, net_rx_rate_sum, net_tx_rate_sum, load_count, net_rate_count, sample_count)
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 1, 1)
 		ON CONFLICT(server_id, bucket) DO UPDATE SET
 			cpu_sum = cpu_sum + excluded.cpu_sum,
 			cpu_max = MAX(cpu_max, excluded.cpu_max),
@@ -1540,6 +1841,14 @@ func storeMetricsInternal(db *sql.DB, serverID string, metrics *SystemMetrics) e
 			net_tx = MAX(net_tx, excluded.net_tx),
 			ping_sum = ping_sum + excluded.ping_sum,
 			ping_count = ping_count + excluded.ping_count,
+			load_1_sum = load_1_sum + excluded.load_1_sum,
+			load_5_sum = load_5_sum + excluded.load_5_sum,
+			load_15_sum = load_15_sum + excluded.load_15_sum,
+			swap_sum = swap_sum + excluded.swap_sum,
+			net_rx_rate_sum = net_rx_rate_sum + excluded.net_rx_rate_sum,
+			net_tx_rate_sum = net_tx_rate_sum + excluded.net_tx_rate_sum,
+			load_count = load_count + 1,
+			net_rate_count = net_rate_count + 1,
 			sample_count = sample_count + 1`,
 		serverID, bucket5sec,
 		float64(metrics.CPU.Usage), float64(metrics.CPU.Usage),
@@ -1547,12 +1856,15 @@ func storeMetricsInternal(db *sql.DB, serverID string, metrics *SystemMetrics) e
 		float64(diskUsage),
 		metrics.Network.TotalRx, metrics.Network.TotalTx,
 		pingVal, pingCnt,
+		metrics.LoadAverage.One, metrics.LoadAverage.Five, metrics.LoadAverage.Fifteen, swapUsage,
+		metrics.Network.RxSpeed, metrics.Network.TxSpeed,
 	)
 
 	// UPSERT to 2-minute aggregation table (for 24h queries)
 	db.Exec(`
-		INSERT INTO metrics_2min (server_id, bucket, cpu_sum, cpu_max, memory_sum, memory_max, disk_sum, net_rx, net_tx, ping_sum, ping_count, sample_count)
-		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1)
+		INSERT INTO metrics_2min (server_id, bucket, cpu_sum, cpu_max, memory_sum, memory_max, disk_sum, net_rx, net_tx, ping_sum, ping_count,
+			load_1_sum, load_5_sum, load_15_sum, swap_sum, net_rx_rate_sum, net_tx_rate_sum, load_count, net_rate_count, sample_count)
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 1, 1)
 		ON CONFLICT(server_id, bucket) DO UPDATE SET
 			cpu_sum = cpu_sum + excluded.cpu_sum,
 			cpu_max = MAX(cpu_max, excluded.cpu_max),
@@ -1563,6 +1875,14 @@ func storeMetricsInternal(db *sql.DB, serverID string, metrics *SystemMetrics) e
 			net_tx = MAX(net_tx, excluded.net_tx),
 			ping_sum = ping_sum + excluded.ping_sum,
 			ping_count = ping_count + excluded.ping_count,
+			load_1_sum = load_1_sum + excluded.load_1_sum,
+			load_5_sum = load_5_sum + excluded.load_5_sum,
+			load_15_sum = load_15_sum + excluded.load_15_sum,
+			swap_sum = swap_sum + excluded.swap_sum,
+			net_rx_rate_sum = net_rx_rate_sum + excluded.net_rx_rate_sum,
+			net_tx_rate_sum = net_tx_rate_sum + excluded.net_tx_rate_sum,
+			load_count = load_count + 1,
+			net_rate_count = net_rate_count + 1,
 			sample_count = sample_count + 1`,
 		serverID, bucket5min,
 		float64(metrics.CPU.Usage), float64(metrics.CPU.Usage),
@@ -1570,6 +1890,8 @@ func storeMetricsInternal(db *sql.DB, serverID string, metrics *SystemMetrics) e
 		float64(diskUsage),
 		metrics.Network.TotalRx, metrics.Network.TotalTx,
 		pingVal, pingCnt,
+		metrics.LoadAverage.One, metrics.LoadAverage.Five, metrics.LoadAverage.Fifteen, swapUsage,
+		metrics.Network.RxSpeed, metrics.Network.TxSpeed,
 	)
 
 	// Store individual ping targets
@@ -1665,7 +1987,7 @@ func aggregate15MinInternal(db *sql.DB) error {
 			MAX(net_rx) - MIN(net_rx),
 			MAX(net_tx) - MIN(net_tx),
 			AVG(ping_ms),
-			COUNT(*)
+			COUNT(*) -- stored raw rows, fewer than samples if raw_sample_interval_secs > 1
 		FROM metrics_raw
 		WHERE timestamp >= ? AND timestamp < ?
 		GROUP BY server_id`,
@@ -1731,6 +2053,11 @@ func aggregateHourlyInternal(db *sql.DB) error {
 		return err
 	}
 
+	hour := hourAgo.Truncate(time.Hour)
+	if err := fillPercentiles(db, "metrics_hourly", "hour_start", hourStart, hour, hour.Add(time.Hour)); err != nil {
+		return err
+	}
+
 	// Aggregate ping data into hourly buckets
 	_, err = db.Exec(`
 		INSERT OR REPLACE INTO ping_hourly (server_id, hour_start, target_name, target_host, latency_avg, latency_max, packet_loss_avg, ok_count, fail_count, sample_count)
@@ -1759,7 +2086,8 @@ func AggregateDaily(db *sql.DB) error {
 }
 
 func aggregateDailyInternal(db *sql.DB) error {
-	yesterday := time.Now().UTC().AddDate(0, 0, -1).Format("2006-01-02")
+	day := time.Now().UTC().AddDate(0, 0, -1).Truncate(24 * time.Hour)
+	yesterday := day.Format("2006-01-02")
 
 	_, err := db.Exec(`
 		INSERT OR REPLACE INTO metrics_daily (server_id, date, cpu_avg, cpu_max, memory_avg, memory_max, disk_avg, net_rx_total, net_tx_total, uptime_percent, sample_count)
@@ -1782,6 +2110,10 @@ func aggregateDailyInternal(db *sql.DB) error {
 		return err
 	}
 
+	if err := fillPercentiles(db, "metrics_daily", "date", yesterday, day, day.AddDate(0, 0, 1)); err != nil {
+		return err
+	}
+
 	// Aggregate ping data into daily buckets
 	_, err = db.Exec(`
 		INSERT OR REPLACE INTO ping_daily (server_id, date, target_name, target_host, latency_avg, latency_max, packet_loss_avg, uptime_percent, sample_count)
@@ -1801,11 +2133,103 @@ func aggregateDailyInternal(db *sql.DB) error {
 	return err
 }
 
+// PurgeResult reports the rows removed per table by PurgeServerData
+type PurgeResult struct {
+	Rows   int64            `json:"rows"`
+	Tables map[string]int64 `json:"tables"`
+}
+
+// PurgeServerData deletes every row belonging to a server from all tables
+// with a server_id column, in one transaction on the DB writer
+func PurgeServerData(db *sql.DB, serverID string) (*PurgeResult, error) {
+	// Buffered samples would otherwise be written after the purge
+	if metricsBuffer != nil {
+		metricsBuffer.Flush()
+	}
+	if aggBuffer != nil {
+		aggBuffer.Flush()
+	}
+
+	var result *PurgeResult
+	run := func(db *sql.DB) error {
+		var err error
+		result, err = purgeServerDataInternal(db, serverID)
+		return err
+	}
+
+	var err error
+	if dbWriter != nil {
+		err = dbWriter.WriteSync(run)
+	} else {
+		err = run(db)
+	}
+	if err != nil {
+		return nil, err
+	}
+
+	if historyCache != nil {
+		historyCache.Clear()
+	}
+	return result, nil
+}
+
+func purgeServerDataInternal(db *sql.DB, serverID string) (*PurgeResult, error) {
+	tx, err := db.Begin()
+	if err != nil {
+		return nil, err
+	}
+	defer tx.Rollback()
+
+	tables, err := listTables(tx)
+	if err != nil {
+		return nil, err
+	}
+
+	result := &PurgeResult{Tables: make(map[string]int64)}
+	for _, table := range tables {
+		columns, err := tableColumns(tx, table)
+		if err != nil {
+			return nil, err
+		}
+		hasServerID := false
+		for _, col := range columns {
+			if col == "server_id" {
+				hasServerID = true
+				break
+			}
+		}
+		if !hasServerID {
+			continue
+		}
+
+		res, err := tx.Exec(fmt.Sprintf("DELETE FROM %q WHERE server_id = ?", table), serverID)
+		if err != nil {
+			return nil, fmt.Errorf("failed to purge %s: %w", table, err)
+		}
+		if n, _ := res.RowsAffected(); n > 0 {
+			result.Tables[table] = n
+			result.Rows += n
+		}
+	}
+
+	if err := tx.Commit(); err != nil {
+		return nil, err
+	}
+	return result, nil
+}
+
 func CleanupOldData(db *sql.DB) error {
+	var err error
 	if dbWriter != nil {
-		return dbWriter.WriteSync(cleanupOldDataInternal)
+		err = dbWriter.WriteSync(cleanupOldDataInternal)
+	} else {
+		err = cleanupOldDataInternal(db)
 	}
-	return cleanupOldDataInternal(db)
+	if err != nil {
+		return err
+	}
+	// Per-server overrides write in batches of their own
+	return cleanupServerRetention(db)
 }
 
 func cleanupOldDataInternal(db *sql.DB) error {
@@ -1845,6 +2269,29 @@ func cleanupOldDataInternal(db *sql.DB) error {
 	db.Exec("DELETE FROM metrics_daily_agg WHERE bucket < ?", cutoffDailyAgg)
 	db.Exec("DELETE FROM ping_daily_agg WHERE bucket < ?", cutoffDailyAgg)
 
+	// Delete login audit entries past their retention
+	pruneLoginAudit(db)
+
+	// Roll up per-disk history, then delete rows past retention
+	if err := aggregateDiskDaily(db); err != nil {
+		return err
+	}
+	cleanupDiskHistory(db)
+
+	// Roll up per-target ping history, then delete rows past retention
+	if err := aggregatePingHourly(db); err != nil {
+		return err
+	}
+	cleanupPingHistory(db)
+
+	// Delete daily traffic, availability, agent events, alerts and revoked
+	// tokens past retention
+	cleanupBandwidth(db)
+	cleanupAvailability(db)
+	cleanupAgentEvents(db)
+	cleanupAlerts(db)
+	cleanupRevokedTokens(db)
+
 	// Delete old pre-aggregated 15-min data older than 7 days (legacy)
 	cutoff15min := time.Now().UTC().Add(-7 * 24 * time.Hour).Format(time.RFC3339)
 	db.Exec("DELETE FROM metrics_15min WHERE bucket_start < ?", cutoff15min)
@@ -1865,33 +2312,159 @@ func GetHistory(db *sql.DB, serverID, rangeStr string) ([]HistoryPoint, error) {
 	return GetHistorySince(db, serverID, rangeStr, 0)
 }
 
+// History stats select how CPU and memory are summarized per bucket
+const (
+	HistoryStatAvg = "avg"
+	HistoryStatMax = "max"
+)
+
+// IsValidHistoryStat reports whether stat is a supported history stat
+func IsValidHistoryStat(stat string) bool {
+	return stat == HistoryStatAvg || stat == HistoryStatMax
+}
+
+// historyStatReplacer fills the CPU/memory column expressions of the history
+// queries for the requested stat, and their row limit (-1 for none). Disk,
+// network and ping stay averaged since only CPU and memory keep per-bucket
+// maxima. Load, swap and network rates are filled from historyLoadSwapColumns
+// and historyNetRateColumns.
+func historyStatReplacer(stat string, limit int) *strings.Replacer {
+	if stat == HistoryStatMax {
+		return strings.NewReplacer(append([]string{
+			"{limit}", strconv.Itoa(limit),
+			"{cpu_bucket}", "cpu_max",
+			"{memory_bucket}", "memory_max",
+			"{cpu_rollup}", "cpu_max",
+			"{memory_rollup}", "memory_max",
+			"{cpu_rollup_group}", "MAX(cpu_max)",
+			"{memory_rollup_group}", "MAX(memory_max)",
+			"{cpu_raw_group}", "MAX(cpu_usage)",
+			"{memory_raw_group}", "MAX(memory_usage)",
+		}, append(historyLoadSwapColumns, historyNetRateColumns...)...)...)
+	}
+	return strings.NewReplacer(append([]string{
+		"{limit}", strconv.Itoa(limit),
+		"{cpu_bucket}", "CASE WHEN sample_count > 0 THEN cpu_sum / sample_count ELSE 0 END",
+		"{memory_bucket}", "CASE WHEN sample_count > 0 THEN memory_sum / sample_count ELSE 0 END",
+		"{cpu_rollup}", "cpu_avg",
+		"{memory_rollup}", "memory_avg",
+		"{cpu_rollup_group}", "AVG(cpu_avg)",
+		"{memory_rollup_group}", "AVG(memory_avg)",
+		"{cpu_raw_group}", "AVG(cpu_usage)",
+		"{memory_raw_group}", "AVG(memory_usage)",
+	}, append(historyLoadSwapColumns, historyNetRateColumns...)...)...)
+}
+
 // GetHistorySince returns history data since a specific bucket (for incremental queries)
 func GetHistorySince(db *sql.DB, serverID, rangeStr string, sinceBucket int64) ([]HistoryPoint, error) {
+	return GetHistoryStatSince(db, serverID, rangeStr, HistoryStatAvg, sinceBucket)
+}
+
+// GetHistoryStatSince is GetHistorySince with CPU/memory summarized by stat
+func GetHistoryStatSince(db *sql.DB, serverID, rangeStr, stat string, sinceBucket int64) ([]HistoryPoint, error) {
+	data, _, err := GetHistoryPage(db, serverID, rangeStr, stat, sinceBucket, nil)
+	return data, err
+}
+
+// MaxHistoryPageLimit caps the points returned per history page
+const MaxHistoryPageLimit = 1000
+
+// HistoryPage selects a window of history points by timestamp cursor.
+// Timestamps are RFC 3339 UTC, as in HistoryPoint, and both bounds are
+// exclusive. Without After the page holds the newest points before Before.
+type HistoryPage struct {
+	Limit  int
+	Before string
+	After  string
+}
+
+// GetHistoryPage is GetHistoryStatSince restricted to a page of points. It
+// also returns the cursor of the next page, or "" if there is none: pass it
+// as After when After was set, else as Before. A nil page returns the whole
+// range as GetHistoryStatSince does.
+func GetHistoryPage(db *sql.DB, serverID, rangeStr, stat string, sinceBucket int64, page *HistoryPage) ([]HistoryPoint, string, error) {
 	var data []HistoryPoint
+	// Pages are cut while reading rows, so the queries' own caps are lifted
+	limit := 720
+	if rangeStr == "1y" {
+		limit = 730
+	}
+	if page != nil {
+		limit = -1
+	}
+	rows, err := queryHistory(db, serverID, rangeStr, stat, sinceBucket, limit)
+	if err != nil {
+		return nil, "", err
+	}
+	defer rows.Close()
+
+	hasMore := false
+	for rows.Next() {
+		point, err := scanHistoryPoint(rows, rangeStr)
+		if err != nil {
+			continue
+		}
+
+		if page == nil {
+			data = append(data, point)
+			continue
+		}
+		if page.After != "" && point.Timestamp <= page.After {
+			continue
+		}
+		if page.Before != "" && point.Timestamp >= page.Before {
+			break
+		}
+		data = append(data, point)
+		if len(data) > page.Limit {
+			if page.After != "" {
+				// Forward paging: the extra point means there is a next page
+				data = data[:page.Limit]
+				return data, data[page.Limit-1].Timestamp, nil
+			}
+			// Backward paging: keep only the newest Limit points seen so far
+			data = data[1:]
+			hasMore = true
+		}
+	}
+
+	if hasMore {
+		return data, data[0].Timestamp, nil
+	}
+	return data, "", nil
+}
+
+// queryHistory runs the history query of a range, returning at most limit
+// rows (-1 for no limit). Read the rows with scanHistoryPoint.
+func queryHistory(db *sql.DB, serverID, rangeStr, stat string, sinceBucket int64, limit int) (*sql.Rows, error) {
+	statReplacer := historyStatReplacer(stat, limit)
 	var rows *sql.Rows
 	var err error
 
 	switch rangeStr {
 	case "1h":
-		// Read directly from pre-aggregated 5-second table (no GROUP BY needed!)
+		// Read directly from pre-aggregated 5-second table (no GROUP BY needed!).
+		// Every sample is folded into metrics_5sec, so the 1h view keeps its
+		// resolution even when metrics_raw is sampled (raw_sample_interval_secs).
 		cutoffBucket := time.Now().UTC().Add(-time.Hour).Unix() / 5
 		if sinceBucket > cutoffBucket {
 			cutoffBucket = sinceBucket
 		}
-		rows, err = db.Query(`
+		rows, err = db.Query(statReplacer.Replace(`
 			SELECT 
 				strftime('%Y-%m-%dT%H:%M:%SZ', bucket * 5, 'unixepoch') as timestamp,
-				CASE WHEN sample_count > 0 THEN cpu_sum / sample_count ELSE 0 END as cpu_usage,
-				CASE WHEN sample_count > 0 THEN memory_sum / sample_count ELSE 0 END as memory_usage,
+				{cpu_bucket} as cpu_usage,
+				{memory_bucket} as memory_usage,
 				CASE WHEN sample_count > 0 THEN disk_sum / sample_count ELSE 0 END as disk_usage,
 				net_rx,
 				net_tx,
 				CASE WHEN ping_count > 0 THEN ping_sum / ping_count ELSE NULL END as ping_ms,
-				bucket
+				bucket,
+				{load_swap_bucket}, {net_rate_bucket}
 			FROM metrics_5sec 
 			WHERE server_id = ? AND bucket >= ?
 			ORDER BY bucket ASC
-			LIMIT 720`, serverID, cutoffBucket)
+			LIMIT {limit}`), serverID, cutoffBucket)
 
 	case "24h":
 		// Read directly from pre-aggregated 2-minute table (no GROUP BY needed!)
@@ -1899,20 +2472,21 @@ func GetHistorySince(db *sql.DB, serverID, rangeStr string, sinceBucket int64) (
 		if sinceBucket > cutoffBucket {
 			cutoffBucket = sinceBucket
 		}
-		rows, err = db.Query(`
+		rows, err = db.Query(statReplacer.Replace(`
 			SELECT 
 				strftime('%Y-%m-%dT%H:%M:%SZ', bucket * 120, 'unixepoch') as timestamp,
-				CASE WHEN sample_count > 0 THEN cpu_sum / sample_count ELSE 0 END as cpu_usage,
-				CASE WHEN sample_count > 0 THEN memory_sum / sample_count ELSE 0 END as memory_usage,
+				{cpu_bucket} as cpu_usage,
+				{memory_bucket} as memory_usage,
 				CASE WHEN sample_count > 0 THEN disk_sum / sample_count ELSE 0 END as disk_usage,
 				net_rx,
 				net_tx,
 				CASE WHEN ping_count > 0 THEN ping_sum / ping_count ELSE NULL END as ping_ms,
-				bucket
+				bucket,
+				{load_swap_bucket}, {net_rate_bucket}
 			FROM metrics_2min 
 			WHERE server_id = ? AND bucket >= ?
 			ORDER BY bucket ASC
-			LIMIT 720`, serverID, cutoffBucket)
+			LIMIT {limit}`), serverID, cutoffBucket)
 
 	case "7d":
 		// 7d with 15-min buckets (672 points max) - try agent-aggregated data first
@@ -1923,19 +2497,25 @@ func GetHistorySince(db *sql.DB, serverID, rangeStr string, sinceBucket int64) (
 
 		if count > 0 {
 			// Use agent-aggregated 15-min data
-			rows, err = db.Query(`
+			rows, err = db.Query(statReplacer.Replace(`
 				SELECT 
 					strftime('%Y-%m-%dT%H:%M:%SZ', bucket * 900, 'unixepoch') as timestamp,
-					CASE WHEN sample_count > 0 THEN cpu_sum / sample_count ELSE 0 END as cpu_usage,
-					CASE WHEN sample_count > 0 THEN memory_sum / sample_count ELSE 0 END as memory_usage,
+					{cpu_bucket} as cpu_usage,
+					{memory_bucket} as memory_usage,
 					CASE WHEN sample_count > 0 THEN disk_sum / sample_count ELSE 0 END as disk_usage,
 					net_rx,
 					net_tx,
-					CASE WHEN ping_count > 0 THEN ping_sum / ping_count ELSE NULL END as ping_ms
+					CASE WHEN ping_count > 0 THEN ping_sum / ping_count ELSE NULL END as ping_ms,
+					cpu_max,
+					cpu_p95,
+					memory_max,
+					memory_p95,
+					ping_max,
+					{load_swap_rollup}, {net_rate_rollup}
 				FROM metrics_15min_agg 
 				WHERE server_id = ? AND bucket >= ?
 				ORDER BY bucket ASC
-				LIMIT 720`, serverID, cutoffBucket)
+				LIMIT {limit}`), serverID, cutoffBucket)
 		} else {
 			// Fall back to old pre-aggregated 15-min data (for backward compatibility)
 			cutoff := time.Now().UTC().Add(-7 * 24 * time.Hour).Format(time.RFC3339)
@@ -1943,28 +2523,30 @@ func GetHistorySince(db *sql.DB, serverID, rangeStr string, sinceBucket int64) (
 				serverID, cutoff).Scan(&count)
 			
 			if count > 0 {
-				rows, err = db.Query(`
-					SELECT bucket_start, cpu_avg, memory_avg, disk_avg, net_rx_total, net_tx_total, ping_avg
+				rows, err = db.Query(statReplacer.Replace(`
+					SELECT bucket_start, {cpu_rollup}, {memory_rollup}, disk_avg, net_rx_total, net_tx_total, ping_avg,
+						cpu_max, NULL, memory_max, NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL
 					FROM metrics_15min 
 					WHERE server_id = ? AND bucket_start >= ?
 					ORDER BY bucket_start ASC
-					LIMIT 720`, serverID, cutoff)
+					LIMIT {limit}`), serverID, cutoff)
 			} else {
 				// Fall back to real-time aggregation from raw data (15-min buckets = 900 seconds)
-				rows, err = db.Query(`
+				rows, err = db.Query(statReplacer.Replace(`
 					SELECT 
 						strftime('%Y-%m-%dT%H:%M:%SZ', (strftime('%s', timestamp) / 900) * 900, 'unixepoch') as bucket_start,
-						AVG(cpu_usage) as cpu_avg,
-						AVG(memory_usage) as memory_avg,
+						{cpu_raw_group} as cpu_avg,
+						{memory_raw_group} as memory_avg,
 						AVG(disk_usage) as disk_avg,
 						MAX(net_rx) - MIN(net_rx) as net_rx_total,
 						MAX(net_tx) - MIN(net_tx) as net_tx_total,
-						AVG(ping_ms) as ping_avg
+						AVG(ping_ms) as ping_avg,
+						MAX(cpu_usage), NULL, MAX(memory_usage), NULL, MAX(ping_ms), {load_swap_raw_group}, {net_rate_raw_group}
 					FROM metrics_raw 
 					WHERE server_id = ? AND timestamp >= ?
 					GROUP BY strftime('%s', timestamp) / 900
 					ORDER BY bucket_start ASC
-					LIMIT 720`, serverID, cutoff)
+					LIMIT {limit}`), serverID, cutoff)
 			}
 		}
 
@@ -1977,19 +2559,25 @@ func GetHistorySince(db *sql.DB, serverID, rangeStr string, sinceBucket int64) (
 
 		if count > 0 {
 			// Use agent-aggregated hourly data
-			rows, err = db.Query(`
+			rows, err = db.Query(statReplacer.Replace(`
 				SELECT 
 					strftime('%Y-%m-%dT%H:00:00Z', bucket * 3600, 'unixepoch') as timestamp,
-					CASE WHEN sample_count > 0 THEN cpu_sum / sample_count ELSE 0 END as cpu_usage,
-					CASE WHEN sample_count > 0 THEN memory_sum / sample_count ELSE 0 END as memory_usage,
+					{cpu_bucket} as cpu_usage,
+					{memory_bucket} as memory_usage,
 					CASE WHEN sample_count > 0 THEN disk_sum / sample_count ELSE 0 END as disk_usage,
 					net_rx,
 					net_tx,
-					CASE WHEN ping_count > 0 THEN ping_sum / ping_count ELSE NULL END as ping_ms
+					CASE WHEN ping_count > 0 THEN ping_sum / ping_count ELSE NULL END as ping_ms,
+					cpu_max,
+					cpu_p95,
+					memory_max,
+					memory_p95,
+					ping_max,
+					{load_swap_rollup}, {net_rate_rollup}
 				FROM metrics_hourly_agg 
 				WHERE server_id = ? AND bucket >= ?
 				ORDER BY bucket ASC
-				LIMIT 720`, serverID, cutoffBucket)
+				LIMIT {limit}`), serverID, cutoffBucket)
 		} else {
 			// Fall back to old pre-aggregated hourly data (for backward compatibility)
 			cutoff := time.Now().UTC().AddDate(0, 0, -30).Format(time.RFC3339)
@@ -1997,11 +2585,12 @@ func GetHistorySince(db *sql.DB, serverID, rangeStr string, sinceBucket int64) (
 				serverID, cutoff).Scan(&count)
 
 			if count > 0 {
-				rows, err = db.Query(`
-					SELECT hour_start, cpu_avg, memory_avg, disk_avg, net_rx_total, net_tx_total, ping_avg
+				rows, err = db.Query(statReplacer.Replace(`
+					SELECT hour_start, {cpu_rollup}, {memory_rollup}, disk_avg, net_rx_total, net_tx_total, ping_avg,
+						cpu_max, cpu_p95, memory_max, memory_p95, ping_max, {load_swap_rollup}, {net_rate_rollup}
 					FROM metrics_hourly WHERE server_id = ? AND hour_start >= ?
 					ORDER BY hour_start ASC
-					LIMIT 720`, serverID, cutoff)
+					LIMIT {limit}`), serverID, cutoff)
 			} else {
 				// Try 15-min table
 				var count15 int
@@ -2009,36 +2598,38 @@ func GetHistorySince(db *sql.DB, serverID, rangeStr string, sinceBucket int64) (
 					serverID, cutoff).Scan(&count15)
 
 				if count15 > 0 {
-					rows, err = db.Query(`
+					rows, err = db.Query(statReplacer.Replace(`
 						SELECT 
 							strftime('%Y-%m-%dT%H:00:00Z', bucket_start) as hour_start,
-							AVG(cpu_avg) as cpu_avg,
-							AVG(memory_avg) as memory_avg,
+							{cpu_rollup_group} as cpu_avg,
+							{memory_rollup_group} as memory_avg,
 							AVG(disk_avg) as disk_avg,
 							SUM(net_rx_total) as net_rx_total,
 							SUM(net_tx_total) as net_tx_total,
-							AVG(ping_avg) as ping_avg
+							AVG(ping_avg) as ping_avg,
+							MAX(cpu_max), NULL, MAX(memory_max), NULL, NULL, NULL, NULL, NULL, NULL, NULL, NULL
 						FROM metrics_15min 
 						WHERE server_id = ? AND bucket_start >= ?
 						GROUP BY strftime('%Y-%m-%dT%H:00:00Z', bucket_start)
 						ORDER BY hour_start ASC
-						LIMIT 720`, serverID, cutoff)
+						LIMIT {limit}`), serverID, cutoff)
 				} else {
 					// Fall back to raw data with hourly aggregation
-					rows, err = db.Query(`
+					rows, err = db.Query(statReplacer.Replace(`
 						SELECT 
 							strftime('%Y-%m-%dT%H:00:00Z', timestamp) as hour_start,
-							AVG(cpu_usage) as cpu_avg,
-							AVG(memory_usage) as memory_avg,
+							{cpu_raw_group} as cpu_avg,
+							{memory_raw_group} as memory_avg,
 							AVG(disk_usage) as disk_avg,
 							MAX(net_rx) - MIN(net_rx) as net_rx_total,
 							MAX(net_tx) - MIN(net_tx) as net_tx_total,
-							AVG(ping_ms) as ping_avg
+							AVG(ping_ms) as ping_avg,
+							MAX(cpu_usage), NULL, MAX(memory_usage), NULL, MAX(ping_ms), {load_swap_raw_group}, {net_rate_raw_group}
 						FROM metrics_raw 
 						WHERE server_id = ? AND timestamp >= ?
 						GROUP BY strftime('%Y-%m-%dT%H:00:00Z', timestamp)
 						ORDER BY hour_start ASC
-						LIMIT 720`, serverID, cutoff)
+						LIMIT {limit}`), serverID, cutoff)
 				}
 			}
 		}
@@ -2052,19 +2643,25 @@ func GetHistorySince(db *sql.DB, serverID, rangeStr string, sinceBucket int64) (
 
 		if count > 0 {
 			// Use agent-aggregated daily data
-			rows, err = db.Query(`
+			rows, err = db.Query(statReplacer.Replace(`
 				SELECT 
 					strftime('%Y-%m-%dT00:00:00Z', bucket * 86400, 'unixepoch') as timestamp,
-					CASE WHEN sample_count > 0 THEN cpu_sum / sample_count ELSE 0 END as cpu_usage,
-					CASE WHEN sample_count > 0 THEN memory_sum / sample_count ELSE 0 END as memory_usage,
+					{cpu_bucket} as cpu_usage,
+					{memory_bucket} as memory_usage,
 					CASE WHEN sample_count > 0 THEN disk_sum / sample_count ELSE 0 END as disk_usage,
 					net_rx,
 					net_tx,
-					CASE WHEN ping_count > 0 THEN ping_sum / ping_count ELSE NULL END as ping_ms
+					CASE WHEN ping_count > 0 THEN ping_sum / ping_count ELSE NULL END as ping_ms,
+					cpu_max,
+					cpu_p95,
+					memory_max,
+					memory_p95,
+					ping_max,
+					{load_swap_rollup}, {net_rate_rollup}
 				FROM metrics_daily_agg 
 				WHERE server_id = ? AND bucket >= ?
 				ORDER BY bucket ASC
-				LIMIT 365`, serverID, cutoffBucket)
+				LIMIT {limit}`), serverID, cutoffBucket)
 		} else {
 			// Fall back to old pre-aggregated hourly data (for backward compatibility)
 			cutoff := time.Now().UTC().AddDate(0, 0, -365).Format(time.RFC3339)
@@ -2073,36 +2670,38 @@ func GetHistorySince(db *sql.DB, serverID, rangeStr string, sinceBucket int64) (
 
 			if count > 0 {
 				// Use hourly data with 12-hour grouping
-				rows, err = db.Query(`
+				rows, err = db.Query(statReplacer.Replace(`
 					SELECT 
 						MIN(hour_start) as timestamp,
-						AVG(cpu_avg) as cpu_avg,
-						AVG(memory_avg) as memory_avg,
+						{cpu_rollup_group} as cpu_avg,
+						{memory_rollup_group} as memory_avg,
 						AVG(disk_avg) as disk_avg,
 						SUM(net_rx_total) as net_rx_total,
 						SUM(net_tx_total) as net_tx_total,
-						AVG(ping_avg) as ping_avg
+						AVG(ping_avg) as ping_avg,
+						MAX(cpu_max), NULL, MAX(memory_max), NULL, MAX(ping_max), {load_swap_rollup_group}, {net_rate_rollup_group}
 					FROM metrics_hourly 
 					WHERE server_id = ? AND hour_start >= ?
 					GROUP BY date(hour_start), (CAST(strftime('%H', hour_start) AS INTEGER) / 12)
 					ORDER BY MIN(hour_start) ASC
-					LIMIT 730`, serverID, cutoff)
+					LIMIT {limit}`), serverID, cutoff)
 			} else {
 				// Fall back to raw data with 12-hour aggregation
-				rows, err = db.Query(`
+				rows, err = db.Query(statReplacer.Replace(`
 					SELECT 
 						MIN(timestamp) as timestamp,
-						AVG(cpu_usage) as cpu_avg,
-						AVG(memory_usage) as memory_avg,
+						{cpu_raw_group} as cpu_avg,
+						{memory_raw_group} as memory_avg,
 						AVG(disk_usage) as disk_avg,
 						MAX(net_rx) - MIN(net_rx) as net_rx_total,
 						MAX(net_tx) - MIN(net_tx) as net_tx_total,
-						AVG(ping_ms) as ping_avg
+						AVG(ping_ms) as ping_avg,
+						MAX(cpu_usage), NULL, MAX(memory_usage), NULL, MAX(ping_ms), {load_swap_raw_group}, {net_rate_raw_group}
 					FROM metrics_raw 
 					WHERE server_id = ? AND timestamp >= ?
 					GROUP BY date(timestamp), (CAST(strftime('%H', timestamp) AS INTEGER) / 12)
 					ORDER BY MIN(timestamp) ASC
-					LIMIT 730`, serverID, cutoff)
+					LIMIT {limit}`), serverID, cutoff)
 			}
 		}
 
@@ -2112,46 +2711,45 @@ func GetHistorySince(db *sql.DB, serverID, rangeStr string, sinceBucket int64) (
 		if sinceBucket > cutoffBucket {
 			cutoffBucket = sinceBucket
 		}
-		rows, err = db.Query(`
+		rows, err = db.Query(statReplacer.Replace(`
 			SELECT 
 				strftime('%Y-%m-%dT%H:%M:%SZ', bucket * 120, 'unixepoch') as timestamp,
-				CASE WHEN sample_count > 0 THEN cpu_sum / sample_count ELSE 0 END as cpu_usage,
-				CASE WHEN sample_count > 0 THEN memory_sum / sample_count ELSE 0 END as memory_usage,
+				{cpu_bucket} as cpu_usage,
+				{memory_bucket} as memory_usage,
 				CASE WHEN sample_count > 0 THEN disk_sum / sample_count ELSE 0 END as disk_usage,
 				net_rx,
 				net_tx,
 				CASE WHEN ping_count > 0 THEN ping_sum / ping_count ELSE NULL END as ping_ms,
-				bucket
+				bucket,
+				{load_swap_bucket}, {net_rate_bucket}
 			FROM metrics_2min 
 			WHERE server_id = ? AND bucket >= ?
 			ORDER BY bucket ASC
-			LIMIT 720`, serverID, cutoffBucket)
-	}
-
-	if err != nil {
-		return nil, err
+			LIMIT {limit}`), serverID, cutoffBucket)
 	}
-	defer rows.Close()
 
-	// Check if we're reading from aggregated tables (1h or 24h) which have bucket column
-	useAggregated := rangeStr == "1h" || rangeStr == "24h" || rangeStr == ""
+	return rows, err
+}
 
-	for rows.Next() {
-		var point HistoryPoint
+// scanHistoryPoint reads a row of queryHistory. 1h and 24h read the
+// aggregated tables, whose rows have the bucket instead of the peaks. Every
+// row ends with the load and swap columns, then the network rates.
+func scanHistoryPoint(rows *sql.Rows, rangeStr string) (HistoryPoint, error) {
+	var point HistoryPoint
+	var loadSwap historyLoadSwap
+	var netRate historyNetRate
+	dest := []interface{}{&point.Timestamp, &point.CPU, &point.Memory, &point.Disk, &point.NetRx, &point.NetTx, &point.PingMs}
+	if rangeStr == "1h" || rangeStr == "24h" || rangeStr == "" {
 		var bucket int64
-		var scanErr error
-		if useAggregated {
-			scanErr = rows.Scan(&point.Timestamp, &point.CPU, &point.Memory, &point.Disk, &point.NetRx, &point.NetTx, &point.PingMs, &bucket)
-		} else {
-			scanErr = rows.Scan(&point.Timestamp, &point.CPU, &point.Memory, &point.Disk, &point.NetRx, &point.NetTx, &point.PingMs)
-		}
-		if scanErr != nil {
-			continue
-		}
-		data = append(data, point)
-	}
-
-	return data, nil
+		dest = append(dest, &bucket)
+	} else {
+		dest = append(dest, &point.CPUMax, &point.CPUP95, &point.MemoryMax, &point.MemoryP95, &point.PingMax)
+	}
+	dest = append(dest, loadSwap.dest()...)
+	err := rows.Scan(append(dest, netRate.dest()...)...)
+	loadSwap.apply(&point)
+	netRate.apply(&point)
+	return point, err
 }
 
 func GetPingHistory(db *sql.DB, serverID, rangeStr string) ([]PingHistoryTarget, error) {
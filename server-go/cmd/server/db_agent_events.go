@@ -0,0 +1,145 @@
+package main
+
+import (
+	"database/sql"
+	"time"
+
+	"vstats/internal/common"
+)
+
+// ============================================================================
+// Agent Connection Events
+// ============================================================================
+//
+// Every agent WebSocket connect, auth result and disconnect is recorded in
+// agent_events, for debugging flapping agents. Connects are recorded before
+// the agent says who it is, so they only carry the client IP. Failed auths
+// also drive the per-IP rate limit on the agent endpoint. Events are kept
+// for 30 days, and at most maxAgentEvents rows.
+
+const (
+	AgentEventConnect    = "connect"
+	AgentEventAuthOK     = "auth_ok"
+	AgentEventAuthFailed = "auth_failed"
+	AgentEventDisconnect = "disconnect"
+
+	// Disconnect reasons
+	AgentDisconnectClean   = "clean"           // Close frame from the agent
+	AgentDisconnectError   = "error"           // Read failed without a close frame
+	AgentDisconnectCommand = "command_channel" // Sending to the agent failed
+	AgentDisconnectTimeout = "timeout"         // Nothing received, not even a pong, in time
+	AgentDisconnectTooBig  = "message_too_big" // A message exceeded ws_limits
+
+	// MaxAgentEventEntries caps the number of events returned per request
+	MaxAgentEventEntries = 1000
+
+	agentEventRetentionDays = 30
+	maxAgentEvents          = 100000
+
+	// AgentAuthFailureLimit failed auths from one IP within
+	// AgentAuthFailureWindow get further attempts refused
+	AgentAuthFailureLimit  = 10
+	AgentAuthFailureWindow = 10 * time.Minute
+)
+
+// createAgentEventsTables creates the agent connection event log
+func createAgentEventsTables(db *sql.DB) {
+	db.Exec(`
+		-- Agent connects, auth results and disconnects (keep 30 days)
+		CREATE TABLE IF NOT EXISTS agent_events (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			timestamp TEXT NOT NULL,
+			server_id TEXT NOT NULL DEFAULT '',
+			event TEXT NOT NULL,
+			reason TEXT NOT NULL DEFAULT '',
+			client_ip TEXT NOT NULL DEFAULT '',
+			message TEXT NOT NULL DEFAULT ''
+		);
+
+		CREATE INDEX IF NOT EXISTS idx_agent_events_timestamp ON agent_events(timestamp);
+		CREATE INDEX IF NOT EXISTS idx_agent_events_server ON agent_events(server_id, timestamp);
+		CREATE INDEX IF NOT EXISTS idx_agent_events_ip ON agent_events(client_ip, event, timestamp);
+	`)
+}
+
+// AgentEvent is a single agent connection event
+type AgentEvent struct {
+	ID        int64  `json:"id"`
+	Timestamp string `json:"timestamp"`
+	ServerID  string `json:"server_id"` // Empty for connects; the claimed ID for failed auths
+	Event     string `json:"event"`     // connect, auth_ok, auth_failed or disconnect
+	Reason    string `json:"reason,omitempty"`
+	ClientIP  string `json:"client_ip"`
+	Message   string `json:"message,omitempty"` // Error text of failed disconnects
+}
+
+// RecordAgentEvent queues an agent connection event
+func RecordAgentEvent(serverID, event, reason, clientIP, message string) {
+	if dbWriter == nil {
+		return
+	}
+	timestamp := time.Now().UTC().Format(time.RFC3339)
+	dbWriter.WriteAsync(func(db *sql.DB) error {
+		_, err := db.Exec(`INSERT INTO agent_events (timestamp, server_id, event, reason, client_ip, message)
+			VALUES (?, ?, ?, ?, ?, ?)`, timestamp, serverID, event, reason, clientIP, message)
+		return err
+	})
+}
+
+// GetAgentEvents returns the most recent events, newest first, of one server
+// or of all servers if serverID is empty
+func GetAgentEvents(db *sql.DB, serverID, event string, limit int) ([]AgentEvent, error) {
+	query := `SELECT id, timestamp, server_id, event, reason, client_ip, message FROM agent_events WHERE 1 = 1`
+	var args []interface{}
+	if serverID != "" {
+		query += ` AND server_id = ?`
+		args = append(args, serverID)
+	}
+	if event != "" {
+		query += ` AND event = ?`
+		args = append(args, event)
+	}
+	query += ` ORDER BY timestamp DESC, id DESC LIMIT ?`
+	args = append(args, limit)
+
+	rows, err := db.Query(query, args...)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	events := []AgentEvent{}
+	for rows.Next() {
+		var e AgentEvent
+		if err := rows.Scan(&e.ID, &e.Timestamp, &e.ServerID, &e.Event, &e.Reason, &e.ClientIP, &e.Message); err != nil {
+			return nil, err
+		}
+		events = append(events, e)
+	}
+	return events, rows.Err()
+}
+
+// agentAuthRateLimited reports whether clientIP has failed to authenticate
+// AgentAuthFailureLimit times within AgentAuthFailureWindow. Refused attempts
+// are not counted, so the limit lifts once the agent backs off long enough.
+func agentAuthRateLimited(db *sql.DB, clientIP string) bool {
+	if db == nil {
+		return false
+	}
+	since := time.Now().UTC().Add(-AgentAuthFailureWindow).Format(time.RFC3339)
+	var failures int
+	err := db.QueryRow(`
+		SELECT COUNT(*) FROM agent_events
+		WHERE client_ip = ? AND event = ? AND timestamp >= ? AND reason != ?`,
+		clientIP, AgentEventAuthFailed, since, common.AuthErrorRateLimited).Scan(&failures)
+	return err == nil && failures >= AgentAuthFailureLimit
+}
+
+// cleanupAgentEvents deletes events past their retention, then the oldest
+// ones beyond maxAgentEvents
+func cleanupAgentEvents(db *sql.DB) {
+	cutoff := time.Now().UTC().AddDate(0, 0, -agentEventRetentionDays).Format(time.RFC3339)
+	db.Exec("DELETE FROM agent_events WHERE timestamp < ?", cutoff)
+	db.Exec(`DELETE FROM agent_events WHERE id <= (
+		SELECT id FROM agent_events ORDER BY id DESC LIMIT 1 OFFSET ?)`, maxAgentEvents)
+}
@@ -0,0 +1,279 @@
+package main
+
+import (
+	"database/sql"
+	"fmt"
+	"sync"
+	"time"
+)
+
+// ============================================================================
+// Alerts
+// ============================================================================
+//
+// Alerts are opened and resolved from the broadcast loop. The "offline" rule
+// is driven by the debounced transitions of the AvailabilityTracker, so agent
+// restarts and other brief flaps do not raise one; the usage thresholds of
+// alert_rules are in alert_rules.go. Open alerts are tracked in memory per server and rule, and reloaded
+// from the alerts table at startup so a restart neither duplicates nor
+// forgets them. Resolved alerts are kept for 400 days.
+
+const (
+	AlertRuleOffline      = "offline"
+	AlertSeverityCritical = "critical"
+
+	AlertStateOpen     = "open"
+	AlertStateResolved = "resolved"
+
+	// MaxAlertEntries caps the number of alerts returned per request
+	MaxAlertEntries = 1000
+
+	alertRetentionDays = 400
+)
+
+// createAlertTables creates the alert history table
+func createAlertTables(db *sql.DB) {
+	db.Exec(`
+		-- Alert history; resolved_at is NULL while open (keep resolved ones 400 days)
+		CREATE TABLE IF NOT EXISTS alerts (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			server_id TEXT NOT NULL,
+			rule TEXT NOT NULL,
+			severity TEXT NOT NULL,
+			opened_at TEXT NOT NULL,
+			resolved_at TEXT,
+			peak_value REAL,
+			message TEXT NOT NULL DEFAULT '',
+			acked_at TEXT
+		);
+
+		CREATE INDEX IF NOT EXISTS idx_alerts_opened ON alerts(opened_at);
+		CREATE INDEX IF NOT EXISTS idx_alerts_server ON alerts(server_id, opened_at);
+		CREATE INDEX IF NOT EXISTS idx_alerts_open ON alerts(server_id, rule) WHERE resolved_at IS NULL;
+	`)
+}
+
+// cleanupAlerts deletes resolved alerts past their retention
+func cleanupAlerts(db *sql.DB) {
+	cutoff := time.Now().UTC().AddDate(0, 0, -alertRetentionDays).Format(time.RFC3339)
+	db.Exec("DELETE FROM alerts WHERE resolved_at IS NOT NULL AND resolved_at < ?", cutoff)
+}
+
+type Alert struct {
+	ID         int64    `json:"id"`
+	ServerID   string   `json:"server_id"`
+	Rule       string   `json:"rule"`
+	Severity   string   `json:"severity"`
+	State      string   `json:"state"` // open or resolved
+	OpenedAt   string   `json:"opened_at"`
+	ResolvedAt *string  `json:"resolved_at"`
+	PeakValue  *float64 `json:"peak_value"` // Worst value while open; null for offline alerts
+	Message    string   `json:"message"`
+	AckedAt    *string  `json:"acked_at"`
+}
+
+type alertKey struct {
+	serverID, rule string
+}
+
+// AlertManager tracks the open alerts and writes their changes to the alerts
+// table
+type AlertManager struct {
+	mu        sync.Mutex
+	open      map[alertKey]bool
+	peaks     map[alertKey]float64   // Worst value of open threshold alerts
+	lastFired map[alertKey]time.Time // For the alert_rules cooldown
+}
+
+func NewAlertManager() *AlertManager {
+	return &AlertManager{
+		open:      make(map[alertKey]bool),
+		peaks:     make(map[alertKey]float64),
+		lastFired: make(map[alertKey]time.Time),
+	}
+}
+
+// Load marks the alerts left open in the alerts table as open, and picks up
+// when each rule last fired
+func (m *AlertManager) Load(db *sql.DB) {
+	m.mu.Lock()
+	defer m.mu.Unlock()
+
+	rows, err := db.Query("SELECT server_id, rule, peak_value FROM alerts WHERE resolved_at IS NULL")
+	if err != nil {
+		return
+	}
+	for rows.Next() {
+		var key alertKey
+		var peak sql.NullFloat64
+		if err := rows.Scan(&key.serverID, &key.rule, &peak); err != nil {
+			continue
+		}
+		m.open[key] = true
+		if peak.Valid {
+			m.peaks[key] = peak.Float64
+		}
+	}
+	rows.Close()
+
+	rows, err = db.Query("SELECT server_id, rule, MAX(opened_at) FROM alerts GROUP BY server_id, rule")
+	if err != nil {
+		return
+	}
+	defer rows.Close()
+	for rows.Next() {
+		var key alertKey
+		var openedAt string
+		if err := rows.Scan(&key.serverID, &key.rule, &openedAt); err != nil {
+			continue
+		}
+		if t, err := time.Parse(time.RFC3339, openedAt); err == nil {
+			m.lastFired[key] = t
+		}
+	}
+}
+
+// Open records an alert unless the server already has one open for the
+// rule, and reports whether it did
+func (m *AlertManager) Open(serverID, rule, severity, message string, at time.Time, peak *float64) bool {
+	key := alertKey{serverID, rule}
+	m.mu.Lock()
+	if m.open[key] {
+		m.mu.Unlock()
+		return false
+	}
+	m.open[key] = true
+	m.lastFired[key] = at
+	if peak != nil {
+		m.peaks[key] = *peak
+	}
+	m.mu.Unlock()
+
+	fmt.Printf("🚨 Alert (%s): %s\n", severity, message)
+	if dbWriter == nil {
+		return true
+	}
+	dbWriter.WriteAsync(func(db *sql.DB) error {
+		_, err := db.Exec(`INSERT INTO alerts (server_id, rule, severity, opened_at, peak_value, message)
+			VALUES (?, ?, ?, ?, ?, ?)`, serverID, rule, severity, at.UTC().Format(time.RFC3339), peak, message)
+		return err
+	})
+	return true
+}
+
+// Resolve records the end of the server's open alert for the rule, if any,
+// and reports whether there was one
+func (m *AlertManager) Resolve(serverID, rule string, at time.Time) bool {
+	key := alertKey{serverID, rule}
+	m.mu.Lock()
+	if !m.open[key] {
+		m.mu.Unlock()
+		return false
+	}
+	delete(m.open, key)
+	var peak *float64
+	if value, ok := m.peaks[key]; ok {
+		peak = &value
+		delete(m.peaks, key)
+	}
+	m.mu.Unlock()
+
+	fmt.Printf("✅ Alert resolved: %s on %s\n", rule, serverID)
+	if dbWriter == nil {
+		return true
+	}
+	dbWriter.WriteAsync(func(db *sql.DB) error {
+		_, err := db.Exec(`UPDATE alerts SET resolved_at = ?, peak_value = COALESCE(?, peak_value)
+			WHERE server_id = ? AND rule = ? AND resolved_at IS NULL`,
+			at.UTC().Format(time.RFC3339), peak, serverID, rule)
+		return err
+	})
+	return true
+}
+
+// ObserveAvailability opens or resolves the offline alert of a server on a
+// state recorded by the AvailabilityTracker. It reports whether the server
+// went down or came back, i.e. not for the first state after startup.
+func (m *AlertManager) ObserveAvailability(serverID, name, state string, at time.Time) bool {
+	switch state {
+	case AvailabilityOffline:
+		return m.Open(serverID, AlertRuleOffline, AlertSeverityCritical, fmt.Sprintf("%s is offline", name), at, nil)
+	case AvailabilityOnline:
+		return m.Resolve(serverID, AlertRuleOffline, at)
+	}
+	return false
+}
+
+// GetAlerts returns alerts opened since `since`, plus every alert still
+// open, newest first. state is open, resolved or "" for both; serverID ""
+// matches every server.
+func GetAlerts(db *sql.DB, state, serverID string, since time.Time, limit int) ([]Alert, error) {
+	query := `SELECT id, server_id, rule, severity, opened_at, resolved_at, peak_value, message, acked_at
+		FROM alerts WHERE (opened_at >= ? OR resolved_at IS NULL)`
+	args := []interface{}{since.UTC().Format(time.RFC3339)}
+	switch state {
+	case AlertStateOpen:
+		query += ` AND resolved_at IS NULL`
+	case AlertStateResolved:
+		query += ` AND resolved_at IS NOT NULL`
+	}
+	if serverID != "" {
+		query += ` AND server_id = ?`
+		args = append(args, serverID)
+	}
+	query += ` ORDER BY opened_at DESC, id DESC LIMIT ?`
+	args = append(args, limit)
+
+	rows, err := db.Query(query, args...)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	alerts := []Alert{}
+	for rows.Next() {
+		var a Alert
+		var resolvedAt, ackedAt sql.NullString
+		var peak sql.NullFloat64
+		if err := rows.Scan(&a.ID, &a.ServerID, &a.Rule, &a.Severity, &a.OpenedAt, &resolvedAt, &peak, &a.Message, &ackedAt); err != nil {
+			return nil, err
+		}
+		a.State = AlertStateOpen
+		if resolvedAt.Valid {
+			a.State = AlertStateResolved
+			a.ResolvedAt = &resolvedAt.String
+		}
+		if peak.Valid {
+			a.PeakValue = &peak.Float64
+		}
+		if ackedAt.Valid {
+			a.AckedAt = &ackedAt.String
+		}
+		alerts = append(alerts, a)
+	}
+	return alerts, rows.Err()
+}
+
+// AckAlert marks an alert acknowledged, keeping the first acknowledgement
+// time. Returns false if there is no such alert.
+func AckAlert(db *sql.DB, id int64) (bool, error) {
+	var found bool
+	run := func(db *sql.DB) error {
+		result, err := db.Exec("UPDATE alerts SET acked_at = COALESCE(acked_at, ?) WHERE id = ?",
+			time.Now().UTC().Format(time.RFC3339), id)
+		if err != nil {
+			return err
+		}
+		n, err := result.RowsAffected()
+		found = n > 0
+		return err
+	}
+
+	var err error
+	if dbWriter != nil {
+		err = dbWriter.WriteSync(run)
+	} else {
+		err = run(db)
+	}
+	return found, err
+}
@@ -0,0 +1,259 @@
+package main
+
+import (
+	"database/sql"
+	"sync"
+	"time"
+)
+
+// ============================================================================
+// Availability Tracking
+// ============================================================================
+//
+// The broadcast loop reports every server's online state to the
+// AvailabilityTracker, which records changes in availability_events once the
+// new state has held for availabilityDebounce, so agent restarts and other
+// brief flaps are not counted as outages. Time the dashboard itself was not
+// running is unknown rather than downtime: a checkpoint is written every
+// minute, and on startup each server gets an "unknown" event at the last
+// checkpoint. Events are kept for 400 days.
+
+const (
+	AvailabilityOnline  = "online"
+	AvailabilityOffline = "offline"
+	AvailabilityUnknown = "unknown"
+
+	// availabilityDebounce is how long a new state must hold to be recorded
+	availabilityDebounce = AgentOnlineTimeout
+	// availabilityCheckpointInterval bounds the time counted as up or down
+	// after the dashboard stops
+	availabilityCheckpointInterval = time.Minute
+)
+
+// createAvailabilityTables creates the availability tables
+func createAvailabilityTables(db *sql.DB) {
+	db.Exec(`
+		-- Online/offline transitions per server (keep 400 days)
+		CREATE TABLE IF NOT EXISTS availability_events (
+			server_id TEXT NOT NULL,
+			timestamp TEXT NOT NULL,
+			state TEXT NOT NULL
+		);
+
+		CREATE INDEX IF NOT EXISTS idx_availability_events_server ON availability_events(server_id, timestamp);
+
+		-- Last time the dashboard was known to be tracking availability
+		CREATE TABLE IF NOT EXISTS availability_checkpoint (
+			id INTEGER PRIMARY KEY CHECK (id = 1),
+			timestamp TEXT NOT NULL
+		);
+	`)
+}
+
+// markAvailabilityGap records the time since the last checkpoint, while the
+// dashboard was not running, as unknown for every tracked server
+func markAvailabilityGap(db *sql.DB) {
+	var checkpoint string
+	if err := db.QueryRow("SELECT timestamp FROM availability_checkpoint WHERE id = 1").Scan(&checkpoint); err != nil {
+		return
+	}
+	db.Exec(`
+		INSERT INTO availability_events (server_id, timestamp, state)
+		SELECT server_id, MAX(?, timestamp), ?
+		FROM availability_events e
+		WHERE rowid = (SELECT rowid FROM availability_events WHERE server_id = e.server_id ORDER BY timestamp DESC, rowid DESC LIMIT 1)
+			AND state != ?`, checkpoint, AvailabilityUnknown, AvailabilityUnknown)
+}
+
+// cleanupAvailability deletes events past their retention
+func cleanupAvailability(db *sql.DB) {
+	cutoff := time.Now().UTC().AddDate(0, 0, -400).Format(time.RFC3339)
+	db.Exec("DELETE FROM availability_events WHERE timestamp < ?", cutoff)
+}
+
+type availabilityState struct {
+	recorded     string // "" until the first state after startup is recorded
+	pending      string
+	pendingSince time.Time
+}
+
+// AvailabilityTracker debounces online state changes into availability_events
+type AvailabilityTracker struct {
+	mu             sync.Mutex
+	servers        map[string]*availabilityState
+	lastCheckpoint time.Time
+}
+
+func NewAvailabilityTracker() *AvailabilityTracker {
+	return &AvailabilityTracker{servers: make(map[string]*availabilityState)}
+}
+
+// Observe reports a server's current state. since is when the state began
+// if known, e.g. the last sample of a server that timed out. When this
+// records a new state, it returns the state and when it began.
+func (t *AvailabilityTracker) Observe(serverID string, online bool, since time.Time) (string, time.Time) {
+	state := AvailabilityOffline
+	if online {
+		state = AvailabilityOnline
+	}
+
+	t.mu.Lock()
+	s := t.servers[serverID]
+	if s == nil {
+		s = &availabilityState{}
+		t.servers[serverID] = s
+	}
+	if state == s.recorded {
+		s.pending = ""
+		t.mu.Unlock()
+		return "", time.Time{}
+	}
+	if state != s.pending {
+		s.pending, s.pendingSince = state, since
+	}
+	if time.Since(s.pendingSince) < availabilityDebounce {
+		t.mu.Unlock()
+		return "", time.Time{}
+	}
+	at := s.pendingSince
+	s.recorded, s.pending = state, ""
+	t.mu.Unlock()
+
+	if dbWriter != nil {
+		dbWriter.WriteAsync(func(db *sql.DB) error {
+			_, err := db.Exec("INSERT INTO availability_events (server_id, timestamp, state) VALUES (?, ?, ?)",
+				serverID, at.UTC().Format(time.RFC3339), state)
+			return err
+		})
+	}
+	return state, at
+}
+
+// Checkpoint records that availability is being tracked, at most once per
+// availabilityCheckpointInterval
+func (t *AvailabilityTracker) Checkpoint() {
+	now := time.Now()
+	t.mu.Lock()
+	if now.Sub(t.lastCheckpoint) < availabilityCheckpointInterval {
+		t.mu.Unlock()
+		return
+	}
+	t.lastCheckpoint = now
+	t.mu.Unlock()
+
+	if dbWriter == nil {
+		return
+	}
+	dbWriter.WriteAsync(func(db *sql.DB) error {
+		_, err := db.Exec("INSERT OR REPLACE INTO availability_checkpoint (id, timestamp) VALUES (1, ?)",
+			now.UTC().Format(time.RFC3339))
+		return err
+	})
+}
+
+// uptimeRanges are the ranges accepted by the uptime endpoint
+var uptimeRanges = map[string]time.Duration{
+	"24h": 24 * time.Hour,
+	"7d":  7 * 24 * time.Hour,
+	"30d": 30 * 24 * time.Hour,
+	"90d": 90 * 24 * time.Hour,
+	"1y":  365 * 24 * time.Hour,
+}
+
+// OutageWindow is a recorded offline period, clipped to the queried range
+type OutageWindow struct {
+	Start        string  `json:"start"`
+	End          *string `json:"end"` // nil while the server is still offline
+	DurationSecs int64   `json:"duration_secs"`
+}
+
+type UptimeResponse struct {
+	ServerID      string         `json:"server_id"`
+	Range         string         `json:"range"`
+	UptimePercent *float64       `json:"uptime_percent"` // Of the time with data; nil without any
+	OutageCount   int            `json:"outage_count"`
+	DowntimeSecs  int64          `json:"downtime_secs"`
+	NoDataSecs    int64          `json:"no_data_secs"` // Before tracking began or while the dashboard was down
+	Outages       []OutageWindow `json:"outages"`
+}
+
+// GetUptime replays the availability events of a server over the last span
+func GetUptime(db *sql.DB, serverID string, span time.Duration) (*UptimeResponse, error) {
+	now := time.Now().UTC()
+	start := now.Add(-span)
+
+	// State at the start of the range; none if tracking began later
+	var state string
+	err := db.QueryRow(`
+		SELECT state FROM availability_events
+		WHERE server_id = ? AND timestamp <= ?
+		ORDER BY timestamp DESC, rowid DESC LIMIT 1`, serverID, start.Format(time.RFC3339)).Scan(&state)
+	if err != nil && err != sql.ErrNoRows {
+		return nil, err
+	}
+
+	rows, err := db.Query(`
+		SELECT timestamp, state FROM availability_events
+		WHERE server_id = ? AND timestamp > ?
+		ORDER BY timestamp ASC, rowid ASC`, serverID, start.Format(time.RFC3339))
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	resp := &UptimeResponse{ServerID: serverID, Outages: []OutageWindow{}}
+	var up, down, noData time.Duration
+	cursor, outageStart := start, start
+	advance := func(until time.Time) {
+		switch state {
+		case AvailabilityOnline:
+			up += until.Sub(cursor)
+		case AvailabilityOffline:
+			down += until.Sub(cursor)
+		default:
+			noData += until.Sub(cursor)
+		}
+		cursor = until
+	}
+	for rows.Next() {
+		var tsStr, next string
+		if err := rows.Scan(&tsStr, &next); err != nil {
+			continue
+		}
+		ts, err := time.Parse(time.RFC3339, tsStr)
+		if err != nil || ts.Before(cursor) {
+			continue
+		}
+		advance(ts)
+		if state != AvailabilityOffline && next == AvailabilityOffline {
+			outageStart = ts
+		} else if state == AvailabilityOffline && next != AvailabilityOffline {
+			end := ts.Format(time.RFC3339)
+			resp.Outages = append(resp.Outages, OutageWindow{
+				Start:        outageStart.Format(time.RFC3339),
+				End:          &end,
+				DurationSecs: int64(ts.Sub(outageStart) / time.Second),
+			})
+		}
+		state = next
+	}
+	if err := rows.Err(); err != nil {
+		return nil, err
+	}
+	advance(now)
+	if state == AvailabilityOffline {
+		resp.Outages = append(resp.Outages, OutageWindow{
+			Start:        outageStart.Format(time.RFC3339),
+			DurationSecs: int64(now.Sub(outageStart) / time.Second),
+		})
+	}
+
+	resp.OutageCount = len(resp.Outages)
+	resp.DowntimeSecs = int64(down / time.Second)
+	resp.NoDataSecs = int64(noData / time.Second)
+	if up+down > 0 {
+		percent := float64(up) / float64(up+down) * 100
+		resp.UptimePercent = &percent
+	}
+	return resp, nil
+}
@@ -0,0 +1,233 @@
+package main
+
+import (
+	"database/sql"
+	"fmt"
+	"os"
+	"path/filepath"
+	"sort"
+	"strings"
+	"sync"
+	"sync/atomic"
+	"time"
+)
+
+// ============================================================================
+// Scheduled Backups
+// ============================================================================
+//
+// With backup.enabled, the database is copied to backup.directory every
+// interval_hours and only the newest `keep` copies are kept. Copies are made
+// with VACUUM INTO, which writes a consistent snapshot from a single read
+// transaction: agents keep streaming metrics meanwhile, since in WAL mode
+// the writer does not wait for readers. POST /api/admin/backup makes one
+// immediately, whether or not scheduled backups are enabled.
+
+const (
+	DefaultBackupIntervalHours = 24
+	DefaultBackupKeep          = 7
+	backupPrefix               = "vstats-"
+	backupSuffix               = ".db"
+)
+
+// BackupSettings configure scheduled database backups
+type BackupSettings struct {
+	Enabled bool `json:"enabled"`
+	// Default: "backups" next to the database
+	Directory     string `json:"directory,omitempty"`
+	IntervalHours int    `json:"interval_hours,omitempty"` // Default: 24
+	Keep          int    `json:"keep,omitempty"`           // Default: 7
+}
+
+func (s *BackupSettings) directory() string {
+	if s != nil && s.Directory != "" {
+		return s.Directory
+	}
+	return filepath.Join(filepath.Dir(GetDBPath()), "backups")
+}
+
+func (s *BackupSettings) interval() time.Duration {
+	if s == nil || s.IntervalHours <= 0 {
+		return DefaultBackupIntervalHours * time.Hour
+	}
+	return time.Duration(s.IntervalHours) * time.Hour
+}
+
+func (s *BackupSettings) keep() int {
+	if s == nil || s.Keep <= 0 {
+		return DefaultBackupKeep
+	}
+	return s.Keep
+}
+
+// BackupResult describes a created backup
+type BackupResult struct {
+	File       string `json:"file"`
+	SizeBytes  int64  `json:"size_bytes"`
+	Size       string `json:"size"` // Human readable, e.g. "1.2 GB"
+	DurationMs int64  `json:"duration_ms"`
+	Pruned     int    `json:"pruned"` // Older backups deleted beyond keep
+}
+
+// BackupStatus is the outcome of the last backup since startup
+type BackupStatus struct {
+	At     string `json:"at"`
+	OK     bool   `json:"ok"`
+	File   string `json:"file,omitempty"`
+	Size   int64  `json:"size_bytes,omitempty"`
+	Error  string `json:"error,omitempty"`
+	Manual bool   `json:"manual"`
+}
+
+// ErrBackupRunning is returned when a backup is already in progress
+var ErrBackupRunning = fmt.Errorf("database backup already running")
+
+var backupRunning atomic.Bool
+
+var lastBackup struct {
+	sync.Mutex
+	status *BackupStatus
+}
+
+// LastBackupStatus returns the outcome of the last backup, nil if none ran
+func LastBackupStatus() *BackupStatus {
+	lastBackup.Lock()
+	defer lastBackup.Unlock()
+	if lastBackup.status == nil {
+		return nil
+	}
+	status := *lastBackup.status
+	return &status
+}
+
+// RunBackup copies the database into the backup directory and prunes copies
+// beyond the keep count
+func RunBackup(db *sql.DB, settings *BackupSettings, manual bool) (*BackupResult, error) {
+	if !backupRunning.CompareAndSwap(false, true) {
+		return nil, ErrBackupRunning
+	}
+	defer backupRunning.Store(false)
+
+	start := time.Now()
+	result, err := runBackup(db, settings, start)
+	status := &BackupStatus{At: start.UTC().Format(time.RFC3339), OK: err == nil, Manual: manual}
+	if err != nil {
+		status.Error = err.Error()
+		fmt.Printf("❌ Database backup failed: %v\n", err)
+	} else {
+		status.File, status.Size = result.File, result.SizeBytes
+		fmt.Printf("💾 Backed up database to %s (%s)\n", result.File, result.Size)
+	}
+	lastBackup.Lock()
+	lastBackup.status = status
+	lastBackup.Unlock()
+	return result, err
+}
+
+func runBackup(db *sql.DB, settings *BackupSettings, start time.Time) (*BackupResult, error) {
+	dir := settings.directory()
+	if err := os.MkdirAll(dir, 0700); err != nil {
+		return nil, fmt.Errorf("failed to create backup directory: %w", err)
+	}
+
+	// Written under a temporary name so a failed copy is never mistaken for
+	// a backup, or pruned in place of a good one
+	name := backupPrefix + start.UTC().Format("20060102-150405") + backupSuffix
+	path := filepath.Join(dir, name)
+	partial := path + ".partial"
+	os.Remove(partial)
+	if _, err := db.Exec("VACUUM INTO ?", partial); err != nil {
+		os.Remove(partial)
+		return nil, fmt.Errorf("failed to copy database: %w", err)
+	}
+	if err := os.Rename(partial, path); err != nil {
+		os.Remove(partial)
+		return nil, err
+	}
+
+	result := &BackupResult{File: path, DurationMs: time.Since(start).Milliseconds()}
+	if info, err := os.Stat(path); err == nil {
+		result.SizeBytes = info.Size()
+		result.Size = FormatBytes(uint64(info.Size()))
+	}
+	result.Pruned = pruneBackups(dir, settings.keep())
+	return result, nil
+}
+
+// listBackups returns the backup files in dir, oldest first. The timestamped
+// names sort chronologically.
+func listBackups(dir string) []string {
+	entries, err := os.ReadDir(dir)
+	if err != nil {
+		return nil
+	}
+	var names []string
+	for _, e := range entries {
+		name := e.Name()
+		if !e.IsDir() && strings.HasPrefix(name, backupPrefix) && strings.HasSuffix(name, backupSuffix) {
+			names = append(names, name)
+		}
+	}
+	sort.Strings(names)
+	return names
+}
+
+// pruneBackups deletes the oldest backups beyond keep and returns how many
+func pruneBackups(dir string, keep int) int {
+	names := listBackups(dir)
+	pruned := 0
+	for len(names) > keep {
+		if err := os.Remove(filepath.Join(dir, names[0])); err != nil {
+			fmt.Printf("⚠️  Failed to delete old backup %s: %v\n", names[0], err)
+		} else {
+			pruned++
+		}
+		names = names[1:]
+	}
+	return pruned
+}
+
+// lastBackupTime returns when the newest backup in dir was made, zero if none
+func lastBackupTime(dir string) time.Time {
+	names := listBackups(dir)
+	if len(names) == 0 {
+		return time.Time{}
+	}
+	info, err := os.Stat(filepath.Join(dir, names[len(names)-1]))
+	if err != nil {
+		return time.Time{}
+	}
+	return info.ModTime()
+}
+
+// backupLoop makes scheduled backups while backup.enabled is set, continuing
+// from the newest existing backup after a restart
+func backupLoop(state *AppState) {
+	ticker := time.NewTicker(time.Minute)
+	defer ticker.Stop()
+
+	var last time.Time
+	var lastDir string
+	for now := range ticker.C {
+		state.ConfigMu.RLock()
+		var settings *BackupSettings
+		if state.Config.Backup != nil && state.Config.Backup.Enabled {
+			s := *state.Config.Backup
+			settings = &s
+		}
+		state.ConfigMu.RUnlock()
+		if settings == nil {
+			continue
+		}
+
+		if dir := settings.directory(); dir != lastDir {
+			lastDir, last = dir, lastBackupTime(dir)
+		}
+		if now.Sub(last) < settings.interval() {
+			continue
+		}
+		// Failures are retried at the next interval, not every minute
+		last = now
+		RunBackup(state.DB, settings, false)
+	}
+}
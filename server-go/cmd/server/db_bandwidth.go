@@ -0,0 +1,215 @@
+package main
+
+import (
+	"database/sql"
+	"sync"
+	"time"
+)
+
+// ============================================================================
+// Monthly Bandwidth
+// ============================================================================
+//
+// Network totals are counters since boot, so subtracting the first sample of
+// a month from the last one breaks on every reboot. Instead each sample adds
+// its increase over the previous sample to bandwidth_daily (days in the
+// aggregation timezone, see timezone.go); a
+// counter that went down was reset, and its new value is the traffic since.
+// Days are kept for two years independently of metrics_raw and summed into
+// billing cycles when queried, so a changed billing_cycle_day also applies to
+// past months. An increase faster than maxPlausibleRate is not traffic but a
+// changed set of counted interfaces (e.g. new net_interface filters or an
+// interface that went away), and only moves the baseline.
+
+const (
+	// MaxBandwidthMonths is the most billing cycles a query returns
+	MaxBandwidthMonths = 24
+	// bandwidthRetentionDays covers MaxBandwidthMonths plus a partial cycle
+	bandwidthRetentionDays = 750
+	// maxPlausibleRate is the fastest traffic accounted, in bytes per second
+	// (100 Gbit/s)
+	maxPlausibleRate = 100e9 / 8
+)
+
+// bandwidthCounter is the last network counters seen from a server
+type bandwidthCounter struct {
+	rx, tx    uint64
+	timestamp int64
+}
+
+// bandwidthCounters caches bandwidth_counters. It is loaded once at startup
+// so the store paths never read from the database.
+var bandwidthCounters = struct {
+	sync.Mutex
+	last map[string]bandwidthCounter
+}{last: make(map[string]bandwidthCounter)}
+
+// createBandwidthTables creates the traffic accounting tables
+func createBandwidthTables(db *sql.DB) {
+	db.Exec(`
+		-- Traffic per day in the aggregation timezone, from counter increases (keep 750 days)
+		CREATE TABLE IF NOT EXISTS bandwidth_daily (
+			server_id TEXT NOT NULL,
+			date TEXT NOT NULL,
+			rx_bytes INTEGER NOT NULL DEFAULT 0,
+			tx_bytes INTEGER NOT NULL DEFAULT 0,
+			PRIMARY KEY (server_id, date)
+		) WITHOUT ROWID;
+
+		-- Last counters per server, to resume accounting after a restart
+		CREATE TABLE IF NOT EXISTS bandwidth_counters (
+			server_id TEXT PRIMARY KEY,
+			rx INTEGER NOT NULL,
+			tx INTEGER NOT NULL,
+			timestamp INTEGER NOT NULL
+		);
+	`)
+}
+
+// loadBandwidthCounters fills the counter cache from bandwidth_counters
+func loadBandwidthCounters(db *sql.DB) {
+	rows, err := db.Query("SELECT server_id, rx, tx, timestamp FROM bandwidth_counters")
+	if err != nil {
+		return
+	}
+	defer rows.Close()
+
+	bandwidthCounters.Lock()
+	defer bandwidthCounters.Unlock()
+	for rows.Next() {
+		var serverID string
+		var rx, tx, timestamp int64
+		if err := rows.Scan(&serverID, &rx, &tx, &timestamp); err != nil {
+			continue
+		}
+		bandwidthCounters.last[serverID] = bandwidthCounter{uint64(rx), uint64(tx), timestamp}
+	}
+}
+
+const (
+	insertBandwidthDailySQL = `
+		INSERT INTO bandwidth_daily (server_id, date, rx_bytes, tx_bytes)
+		VALUES (?, ?, ?, ?)
+		ON CONFLICT(server_id, date) DO UPDATE SET
+			rx_bytes = rx_bytes + excluded.rx_bytes,
+			tx_bytes = tx_bytes + excluded.tx_bytes`
+	upsertBandwidthCounterSQL = `
+		INSERT OR REPLACE INTO bandwidth_counters (server_id, rx, tx, timestamp)
+		VALUES (?, ?, ?, ?)`
+)
+
+// storeBandwidth accounts the traffic since the previous sample of a server
+// through execDaily and execCounter (prepared insertBandwidthDailySQL and
+// upsertBandwidthCounterSQL statements or db.Exec). Samples older than the
+// last one, e.g. replayed from an agent's offline buffer, are skipped.
+func storeBandwidth(execDaily, execCounter func(args ...interface{}) (sql.Result, error), serverID string, metrics *SystemMetrics) {
+	rx, tx := metrics.Network.TotalRx, metrics.Network.TotalTx
+	timestamp := metrics.Timestamp.Unix()
+
+	bandwidthCounters.Lock()
+	prev, seen := bandwidthCounters.last[serverID]
+	if seen && timestamp <= prev.timestamp {
+		bandwidthCounters.Unlock()
+		return
+	}
+	bandwidthCounters.last[serverID] = bandwidthCounter{rx, tx, timestamp}
+	bandwidthCounters.Unlock()
+
+	execCounter(serverID, int64(rx), int64(tx), timestamp)
+	if !seen {
+		return
+	}
+
+	elapsed := max(timestamp-prev.timestamp, 1)
+	deltaRx, deltaTx := counterDelta(prev.rx, rx, elapsed), counterDelta(prev.tx, tx, elapsed)
+	if deltaRx == 0 && deltaTx == 0 {
+		return
+	}
+	execDaily(serverID, aggregationDate(metrics.Timestamp), int64(deltaRx), int64(deltaTx))
+}
+
+// counterDelta returns the traffic between two counter values elapsed
+// seconds apart. A counter below the previous one restarted from zero, so
+// its value is the traffic since; an implausible increase counts as none.
+func counterDelta(prev, cur uint64, elapsed int64) uint64 {
+	delta := cur
+	if cur >= prev {
+		delta = cur - prev
+	}
+	if float64(delta) > float64(elapsed)*maxPlausibleRate {
+		return 0
+	}
+	return delta
+}
+
+// cleanupBandwidth deletes daily traffic past its retention
+func cleanupBandwidth(db *sql.DB) {
+	cutoff := time.Now().UTC().AddDate(0, 0, -bandwidthRetentionDays).Format("2006-01-02")
+	db.Exec("DELETE FROM bandwidth_daily WHERE date < ?", cutoff)
+}
+
+// BandwidthCycle is the traffic of one billing cycle
+type BandwidthCycle struct {
+	Start   string `json:"start"` // YYYY-MM-DD, inclusive
+	End     string `json:"end"`   // YYYY-MM-DD, exclusive
+	RxBytes uint64 `json:"rx_bytes"`
+	TxBytes uint64 `json:"tx_bytes"`
+}
+
+type BandwidthResponse struct {
+	ServerID        string           `json:"server_id"`
+	BillingCycleDay int              `json:"billing_cycle_day"`
+	Cycles          []BandwidthCycle `json:"cycles"`
+}
+
+// billingCycleStart returns the start of the billing cycle containing t for
+// cycles starting on day (1-28) of each month, in t's timezone
+func billingCycleStart(t time.Time, day int) time.Time {
+	start := time.Date(t.Year(), t.Month(), day, 0, 0, 0, 0, t.Location())
+	if t.Day() < day {
+		start = start.AddDate(0, -1, 0)
+	}
+	return start
+}
+
+// GetBandwidthCycles returns the traffic of the last `months` billing cycles,
+// oldest first; the last one is the current, partial cycle
+func GetBandwidthCycles(db *sql.DB, serverID string, months, cycleDay int) ([]BandwidthCycle, error) {
+	if cycleDay < 1 || cycleDay > 28 {
+		cycleDay = 1
+	}
+	current := billingCycleStart(time.Now().In(aggregationTZ()), cycleDay)
+	cycles := make([]BandwidthCycle, months)
+	for i := range cycles {
+		start := current.AddDate(0, i-months+1, 0)
+		cycles[i] = BandwidthCycle{
+			Start: start.Format("2006-01-02"),
+			End:   start.AddDate(0, 1, 0).Format("2006-01-02"),
+		}
+	}
+
+	rows, err := db.Query(`
+		SELECT date, rx_bytes, tx_bytes
+		FROM bandwidth_daily
+		WHERE server_id = ? AND date >= ?
+		ORDER BY date ASC`, serverID, cycles[0].Start)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	i := 0
+	for rows.Next() {
+		var date string
+		var rx, tx int64
+		if err := rows.Scan(&date, &rx, &tx); err != nil {
+			continue
+		}
+		for i < len(cycles)-1 && date >= cycles[i].End {
+			i++
+		}
+		cycles[i].RxBytes += uint64(rx)
+		cycles[i].TxBytes += uint64(tx)
+	}
+	return cycles, rows.Err()
+}
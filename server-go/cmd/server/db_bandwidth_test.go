@@ -0,0 +1,76 @@
+package main
+
+import (
+	"math"
+	"testing"
+	"time"
+)
+
+func TestBandwidthRebootSequence(t *testing.T) {
+	db := openTestDB(t)
+	const serverID = "rebooting"
+	start := time.Now().Add(-20 * time.Minute).Truncate(time.Minute)
+
+	// Counters as reported a minute apart: two reboots, a counter beyond
+	// what SQLite stores, and a real burst of traffic
+	counters := []uint64{
+		1000,
+		5000,
+		9000,
+		200, // reboot
+		700,
+		math.MaxUint64, // clamped, and too fast to be traffic
+		math.MaxInt64,
+		100, // reboot
+		100 + 60e9,
+	}
+	const wantTotal = 4000 + 4000 + 200 + 500 + 100 + 60e9
+
+	for i, c := range counters {
+		m := &SystemMetrics{Timestamp: start.Add(time.Duration(i) * time.Minute).UTC()}
+		m.Network.TotalRx = c
+		m.Network.TotalTx = c / 2
+		if !SanitizeMetrics(serverID, m) {
+			t.Fatalf("sample %d rejected", i)
+		}
+		if err := batchStoreMetrics(db, []MetricsBufferItem{{ServerID: serverID, Metrics: m}}); err != nil {
+			t.Fatal(err)
+		}
+	}
+
+	var rx, tx, negative int64
+	if err := db.QueryRow(`SELECT SUM(rx_bytes), SUM(tx_bytes), SUM(rx_bytes < 0 OR tx_bytes < 0)
+		FROM bandwidth_daily WHERE server_id = ?`, serverID).Scan(&rx, &tx, &negative); err != nil {
+		t.Fatal(err)
+	}
+	if rx != wantTotal || tx != wantTotal/2 || negative != 0 {
+		t.Errorf("daily traffic: rx %d, tx %d, %d negative days; want rx %d, tx %d", rx, tx, negative, int64(wantTotal), int64(wantTotal/2))
+	}
+
+	cycles, err := GetBandwidthCycles(db, serverID, 2, 1)
+	if err != nil {
+		t.Fatal(err)
+	}
+	var cycleRx uint64
+	for _, c := range cycles {
+		cycleRx += c.RxBytes
+	}
+	if cycleRx != wantTotal {
+		t.Errorf("billing cycles: rx %d, want %d", cycleRx, int64(wantTotal))
+	}
+
+	// The stored counters never wrap negative
+	if err := db.QueryRow(`SELECT COUNT(*) FROM metrics_2min WHERE server_id = ? AND (net_rx < 0 OR net_tx < 0)`,
+		serverID).Scan(&negative); err != nil || negative != 0 {
+		t.Errorf("%d negative 2-minute buckets, %v", negative, err)
+	}
+	points, err := GetHistory(db, serverID, "24h")
+	if err != nil {
+		t.Fatal(err)
+	}
+	for _, p := range points {
+		if p.NetRx < 0 || p.NetTx < 0 {
+			t.Errorf("negative counters at %s: %d, %d", p.Timestamp, p.NetRx, p.NetTx)
+		}
+	}
+}
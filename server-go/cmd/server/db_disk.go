@@ -0,0 +1,324 @@
+package main
+
+import (
+	"database/sql"
+	"fmt"
+	"sync"
+	"time"
+)
+
+// ============================================================================
+// Per-Disk History
+// ============================================================================
+//
+// metrics_raw only keeps the first disk's usage. metrics_disk keeps one row
+// per mount point, sampled every disk_history_interval_secs so the write
+// volume stays bounded while metrics arrive every second. Rows are kept for
+// 8 days and rolled up into metrics_disk_daily for longer ranges.
+
+const (
+	DefaultDiskHistoryIntervalSecs = 60
+	// MaxDiskHistoryMounts caps the mount points stored per sample
+	MaxDiskHistoryMounts = 16
+)
+
+var diskSampler = &RawSampler{interval: DefaultDiskHistoryIntervalSecs, last: make(map[string]int64)}
+
+// diskHistoryMounts restricts stored mount points; empty stores all
+var diskHistoryMounts map[string]bool
+
+// SetDiskHistory sets the metrics_disk sampling interval and mount filter
+func SetDiskHistory(intervalSecs int, mounts []string) {
+	if intervalSecs < 1 {
+		intervalSecs = DefaultDiskHistoryIntervalSecs
+	}
+	diskSampler.mu.Lock()
+	diskSampler.interval = int64(intervalSecs)
+	diskSampler.last = make(map[string]int64)
+	diskSampler.mu.Unlock()
+
+	diskHistoryMounts = nil
+	if len(mounts) > 0 {
+		diskHistoryMounts = make(map[string]bool, len(mounts))
+		for _, m := range mounts {
+			diskHistoryMounts[m] = true
+		}
+	}
+}
+
+// diskMountPoint returns the mount point a disk is stored under, or "" if
+// it is filtered out. Disks without mount points use their name.
+func diskMountPoint(d *DiskMetrics) string {
+	if len(d.MountPoints) == 0 {
+		if diskHistoryMounts == nil || diskHistoryMounts[d.Name] {
+			return d.Name
+		}
+		return ""
+	}
+	for _, m := range d.MountPoints {
+		if diskHistoryMounts == nil || diskHistoryMounts[m] {
+			return m
+		}
+	}
+	return ""
+}
+
+// primaryMounts maps server IDs to the mount whose usage is reported as the
+// server's disk usage
+var (
+	primaryMountsMu sync.RWMutex
+	primaryMounts   map[string]string
+)
+
+// SetPrimaryMounts records the configured primary_mount of each server
+func SetPrimaryMounts(servers []RemoteServer) {
+	mounts := make(map[string]string)
+	for _, s := range servers {
+		if s.PrimaryMount != "" {
+			mounts[s.ID] = s.PrimaryMount
+		}
+	}
+	primaryMountsMu.Lock()
+	primaryMounts = mounts
+	primaryMountsMu.Unlock()
+}
+
+// primaryDisk returns the disk that stands for a server's disk usage and the
+// mount it was picked by: the configured primary_mount, else "/", else the
+// largest filesystem. Returns nil when the server reports no disks.
+func primaryDisk(serverID string, disks []DiskMetrics) (*DiskMetrics, string) {
+	primaryMountsMu.RLock()
+	want := primaryMounts[serverID]
+	primaryMountsMu.RUnlock()
+
+	find := func(mount string) *DiskMetrics {
+		for i := range disks {
+			if disks[i].Name == mount {
+				return &disks[i]
+			}
+			for _, m := range disks[i].MountPoints {
+				if m == mount {
+					return &disks[i]
+				}
+			}
+		}
+		return nil
+	}
+	if want != "" {
+		if d := find(want); d != nil {
+			return d, want
+		}
+	}
+	if d := find("/"); d != nil {
+		return d, "/"
+	}
+
+	var largest *DiskMetrics
+	for i := range disks {
+		if largest == nil || disks[i].Total > largest.Total {
+			largest = &disks[i]
+		}
+	}
+	if largest == nil {
+		return nil, ""
+	}
+	mount := largest.Name
+	if len(largest.MountPoints) > 0 {
+		mount = largest.MountPoints[0]
+	}
+	return largest, mount
+}
+
+// primaryDiskUsage returns the usage percent of a server's primary disk
+func primaryDiskUsage(serverID string, metrics *SystemMetrics) (float32, bool) {
+	d, _ := primaryDisk(serverID, metrics.Disks)
+	if d == nil {
+		return 0, false
+	}
+	return d.UsagePercent, true
+}
+
+// primaryDiskMount returns the mount a server's disk usage refers to
+func primaryDiskMount(serverID string, metrics *SystemMetrics) string {
+	if metrics == nil {
+		return ""
+	}
+	_, mount := primaryDisk(serverID, metrics.Disks)
+	return mount
+}
+
+// createDiskTables creates the per-disk history tables
+func createDiskTables(db *sql.DB) {
+	db.Exec(`
+		-- Per-mount disk usage, sampled every disk_history_interval_secs (keep 8 days)
+		CREATE TABLE IF NOT EXISTS metrics_disk (
+			server_id TEXT NOT NULL,
+			timestamp TEXT NOT NULL,
+			mount_point TEXT NOT NULL,
+			used INTEGER NOT NULL,
+			total INTEGER NOT NULL,
+			usage_percent REAL NOT NULL,
+			PRIMARY KEY (server_id, timestamp, mount_point)
+		) WITHOUT ROWID;
+
+		-- Daily per-mount disk usage (keep 400 days)
+		CREATE TABLE IF NOT EXISTS metrics_disk_daily (
+			server_id TEXT NOT NULL,
+			date TEXT NOT NULL,
+			mount_point TEXT NOT NULL,
+			used_max INTEGER NOT NULL,
+			total INTEGER NOT NULL,
+			usage_avg REAL NOT NULL,
+			usage_max REAL NOT NULL,
+			sample_count INTEGER NOT NULL,
+			PRIMARY KEY (server_id, date, mount_point)
+		) WITHOUT ROWID;
+	`)
+}
+
+const insertDiskRowSQL = `
+	INSERT OR REPLACE INTO metrics_disk (server_id, timestamp, mount_point, used, total, usage_percent)
+	VALUES (?, ?, ?, ?, ?, ?)`
+
+// storeDiskRows writes the per-mount rows of a sample through exec (a
+// prepared insertDiskRowSQL statement or db.Exec) if its sampling window is new
+func storeDiskRows(exec func(args ...interface{}) (sql.Result, error), serverID string, metrics *SystemMetrics, timestamp string) {
+	if len(metrics.Disks) == 0 || !diskSampler.ShouldStore(serverID, metrics.Timestamp) {
+		return
+	}
+	seen := make(map[string]bool)
+	for i := range metrics.Disks {
+		mount := diskMountPoint(&metrics.Disks[i])
+		if mount == "" || seen[mount] {
+			continue
+		}
+		if len(seen) >= MaxDiskHistoryMounts {
+			break
+		}
+		seen[mount] = true
+		d := &metrics.Disks[i]
+		exec(serverID, timestamp, mount, d.Used, d.Total, d.UsagePercent)
+	}
+}
+
+// aggregateDiskDaily rolls metrics_disk up into metrics_disk_daily for
+// yesterday and today (in the aggregation timezone), which are still
+// complete in metrics_disk
+func aggregateDiskDaily(db *sql.DB) error {
+	now := time.Now().In(aggregationTZ())
+	yesterday := time.Date(now.Year(), now.Month(), now.Day()-1, 0, 0, 0, 0, now.Location())
+	_, err := db.Exec(`
+		INSERT OR REPLACE INTO metrics_disk_daily (server_id, date, mount_point, used_max, total, usage_avg, usage_max, sample_count)
+		SELECT
+			server_id,
+			date(timestamp, ?) as day,
+			mount_point,
+			MAX(used),
+			MAX(total),
+			AVG(usage_percent),
+			MAX(usage_percent),
+			COUNT(*)
+		FROM metrics_disk
+		WHERE timestamp >= ?
+		GROUP BY server_id, day, mount_point`, aggregationOffsetModifier(now, false), yesterday.UTC().Format(time.RFC3339))
+	return err
+}
+
+// cleanupDiskHistory deletes per-disk rows past their retention
+func cleanupDiskHistory(db *sql.DB) {
+	now := time.Now().UTC()
+	db.Exec("DELETE FROM metrics_disk WHERE timestamp < ?", now.Add(-8*24*time.Hour).Format(time.RFC3339))
+	db.Exec("DELETE FROM metrics_disk_daily WHERE date < ?", now.AddDate(0, 0, -400).Format("2006-01-02"))
+}
+
+// DiskHistoryPoint is a mount point's usage at a point in time
+type DiskHistoryPoint struct {
+	Timestamp    string  `json:"timestamp"`
+	Used         int64   `json:"used"`
+	Total        int64   `json:"total"`
+	UsagePercent float64 `json:"usage_percent"`
+}
+
+// DiskHistorySeries is the usage history of one mount point
+type DiskHistorySeries struct {
+	MountPoint string             `json:"mount_point"`
+	Data       []DiskHistoryPoint `json:"data"`
+}
+
+type DiskHistoryResponse struct {
+	ServerID string              `json:"server_id"`
+	Range    string              `json:"range"`
+	Mounts   []DiskHistorySeries `json:"mounts"`
+}
+
+// GetDiskHistory returns per-mount usage for a range, optionally for a
+// single mount point. Short ranges are grouped into fixed buckets from
+// metrics_disk; 30d and 1y read metrics_disk_daily.
+func GetDiskHistory(db *sql.DB, serverID, rangeStr, mount string) ([]DiskHistorySeries, error) {
+	now := time.Now().UTC()
+	var query, groupBy string
+	var args []interface{}
+
+	switch rangeStr {
+	case "30d", "1y":
+		days := 30
+		if rangeStr == "1y" {
+			days = 365
+		}
+		// Days start at midnight in the aggregation timezone
+		query = `
+			SELECT mount_point, strftime('%Y-%m-%dT%H:%M:%SZ', date, ?), used_max, total, usage_avg
+			FROM metrics_disk_daily
+			WHERE server_id = ? AND date >= ?`
+		args = []interface{}{aggregationOffsetModifier(now, true), serverID, aggregationDate(now.AddDate(0, 0, -days))}
+	default:
+		// Bucket sizes keep each series under ~170 points
+		span, bucketSecs := 24*time.Hour, 600
+		switch rangeStr {
+		case "1h":
+			span, bucketSecs = time.Hour, 60
+		case "7d":
+			span, bucketSecs = 7*24*time.Hour, 3600
+		}
+		query = fmt.Sprintf(`
+			SELECT
+				mount_point,
+				strftime('%%Y-%%m-%%dT%%H:%%M:%%SZ', (strftime('%%s', timestamp) / %[1]d) * %[1]d, 'unixepoch'),
+				MAX(used),
+				MAX(total),
+				AVG(usage_percent)
+			FROM metrics_disk
+			WHERE server_id = ? AND timestamp >= ?`, bucketSecs)
+		args = []interface{}{serverID, now.Add(-span).Format(time.RFC3339)}
+		groupBy = fmt.Sprintf(" GROUP BY mount_point, strftime('%%s', timestamp) / %d", bucketSecs)
+	}
+	if mount != "" {
+		query += " AND mount_point = ?"
+		args = append(args, mount)
+	}
+	query += groupBy + " ORDER BY 1, 2"
+
+	rows, err := db.Query(query, args...)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	series := []DiskHistorySeries{}
+	index := make(map[string]int)
+	for rows.Next() {
+		var mountPoint string
+		var p DiskHistoryPoint
+		if err := rows.Scan(&mountPoint, &p.Timestamp, &p.Used, &p.Total, &p.UsagePercent); err != nil {
+			continue
+		}
+		i, ok := index[mountPoint]
+		if !ok {
+			i = len(series)
+			index[mountPoint] = i
+			series = append(series, DiskHistorySeries{MountPoint: mountPoint})
+		}
+		series[i].Data = append(series[i].Data, p)
+	}
+	return series, rows.Err()
+}
@@ -0,0 +1,878 @@
+package main
+
+import (
+	"bufio"
+	"compress/gzip"
+	"database/sql"
+	"encoding/json"
+	"errors"
+	"fmt"
+	"io"
+	"os"
+	"slices"
+	"strings"
+	"time"
+)
+
+// ============================================================================
+// Export / Import (instance migration)
+// ============================================================================
+
+// Export versions: 1 is a single JSON document (restored by replacing all
+// data), 2 is gzip-compressed NDJSON (streamed and merged).
+const (
+	ExportFormat  = "vstats-export"
+	ExportVersion = 2
+)
+
+// ErrInvalidExport is returned for files that are not a readable export
+var ErrInvalidExport = errors.New("invalid export file")
+
+// importBatchRows is how many rows are written per transaction on import, so
+// a large history neither sits in memory nor blocks metric writes for long
+const importBatchRows = 5000
+
+// importMergeKeys identifies duplicate rows in tables whose only key is an
+// autoincrement id, or that have no key at all. Other tables dedupe on their
+// own UNIQUE/PRIMARY keys.
+var importMergeKeys = map[string][]string{
+	"metrics_raw":         {"server_id", "timestamp"},
+	"ping_raw":            {"server_id", "timestamp", "target_name"},
+	"login_audit":         {"timestamp", "username", "client_ip"},
+	"agent_events":        {"timestamp", "server_id", "event", "client_ip"},
+	"alerts":              {"server_id", "rule", "opened_at"},
+	"availability_events": {"server_id", "timestamp", "state"},
+}
+
+// exportSkippedColumns are left out of exports: the compressed snapshots of
+// metrics_blobs are binary, and only kept for a day anyway
+var exportSkippedColumns = map[string]bool{
+	"metrics_raw.metrics_blob": true,
+}
+
+// ExportHeader is the first line of a v2 export
+type ExportHeader struct {
+	Format        string     `json:"format"`
+	Version       int        `json:"version"`
+	ExportedAt    string     `json:"exported_at"`
+	ServerVersion string     `json:"server_version"`
+	Config        *AppConfig `json:"config"`
+}
+
+// ExportTableHeader starts a table in a v2 export; each following line holds
+// one row as a JSON array in column order
+type ExportTableHeader struct {
+	Table   string   `json:"table"`
+	Columns []string `json:"columns"`
+}
+
+// ExportArchive is a v1 export document. Importing streams its tables
+// rather than decoding them into Tables.
+type ExportArchive struct {
+	Format        string                 `json:"format"`
+	Version       int                    `json:"version"`
+	ExportedAt    string                 `json:"exported_at"`
+	ServerVersion string                 `json:"server_version"`
+	Config        *AppConfig             `json:"config"`
+	Tables        map[string]ExportTable `json:"tables"`
+}
+
+// ExportTable holds the rows of a single table
+type ExportTable struct {
+	Columns []string        `json:"columns"`
+	Rows    [][]interface{} `json:"rows"`
+}
+
+// ImportResult summarizes an import
+type ImportResult struct {
+	Servers       int            `json:"servers"`
+	Tables        map[string]int `json:"tables"`
+	SkippedTables []string       `json:"skipped_tables,omitempty"`
+}
+
+// sqlQuerier is a *sql.DB or a *sql.Tx. Schema lookups made while a
+// transaction is open must go through it: an in-memory database has a single
+// connection, which the transaction holds.
+type sqlQuerier interface {
+	Query(query string, args ...interface{}) (*sql.Rows, error)
+}
+
+// listTables returns all user tables in the database
+func listTables(db sqlQuerier) ([]string, error) {
+	rows, err := db.Query(`SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name`)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	var tables []string
+	for rows.Next() {
+		var name string
+		if err := rows.Scan(&name); err != nil {
+			return nil, err
+		}
+		tables = append(tables, name)
+	}
+	return tables, rows.Err()
+}
+
+// columnInfo is a column as reported by PRAGMA table_info
+type columnInfo struct {
+	Name string
+	Type string
+	PK   int // Position in the primary key, 0 if not part of it
+}
+
+// tableInfo returns the columns of a table
+func tableInfo(db sqlQuerier, table string) ([]columnInfo, error) {
+	rows, err := db.Query(fmt.Sprintf("PRAGMA table_info(%q)", table))
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	var columns []columnInfo
+	for rows.Next() {
+		var cid, notNull int
+		var col columnInfo
+		var dflt sql.NullString
+		if err := rows.Scan(&cid, &col.Name, &col.Type, &notNull, &dflt, &col.PK); err != nil {
+			return nil, err
+		}
+		columns = append(columns, col)
+	}
+	return columns, rows.Err()
+}
+
+// tableColumns returns the column names of a table
+func tableColumns(db sqlQuerier, table string) ([]string, error) {
+	info, err := tableInfo(db, table)
+	if err != nil {
+		return nil, err
+	}
+	columns := make([]string, len(info))
+	for i, col := range info {
+		columns[i] = col.Name
+	}
+	return columns, nil
+}
+
+// rowidAlias returns the column SQLite makes an alias of the rowid, i.e. a
+// sole primary key declared INTEGER, or "" if the table has none
+func rowidAlias(columns []columnInfo) string {
+	alias, keys := "", 0
+	for _, col := range columns {
+		if col.PK == 0 {
+			continue
+		}
+		keys++
+		if strings.EqualFold(col.Type, "INTEGER") {
+			alias = col.Name
+		}
+	}
+	if keys != 1 {
+		return ""
+	}
+	return alias
+}
+
+// ExportDatabase streams config and every table as gzip-compressed NDJSON:
+// a header line, then per table a table line followed by one line per row.
+// Rows are written one at a time so memory use stays flat for large databases.
+func ExportDatabase(db *sql.DB, config *AppConfig, out io.Writer) error {
+	// Make sure buffered metrics are on disk before reading
+	if metricsBuffer != nil {
+		metricsBuffer.Flush()
+	}
+	if aggBuffer != nil {
+		aggBuffer.Flush()
+	}
+	if dbWriter != nil {
+		dbWriter.WriteSync(func(*sql.DB) error { return nil })
+	}
+
+	gz := gzip.NewWriter(out)
+	w := bufio.NewWriter(gz)
+	enc := json.NewEncoder(w)
+
+	err := enc.Encode(ExportHeader{
+		Format:        ExportFormat,
+		Version:       ExportVersion,
+		ExportedAt:    time.Now().UTC().Format(time.RFC3339),
+		ServerVersion: ServerVersion,
+		Config:        config,
+	})
+	if err != nil {
+		return err
+	}
+
+	tables, err := listTables(db)
+	if err != nil {
+		return err
+	}
+
+	for _, table := range tables {
+		columns, err := tableColumns(db, table)
+		if err != nil {
+			return err
+		}
+		columns = slices.DeleteFunc(columns, func(col string) bool {
+			return exportSkippedColumns[table+"."+col]
+		})
+		if err := enc.Encode(ExportTableHeader{Table: table, Columns: columns}); err != nil {
+			return err
+		}
+		if err := exportTableRows(db, table, columns, enc); err != nil {
+			return fmt.Errorf("failed to export %s: %w", table, err)
+		}
+	}
+
+	if err := w.Flush(); err != nil {
+		return err
+	}
+	return gz.Close()
+}
+
+func exportTableRows(db *sql.DB, table string, columns []string, enc *json.Encoder) error {
+	quoted := make([]string, len(columns))
+	for i, col := range columns {
+		quoted[i] = fmt.Sprintf("%q", col)
+	}
+	rows, err := db.Query(fmt.Sprintf("SELECT %s FROM %q", strings.Join(quoted, ","), table))
+	if err != nil {
+		return err
+	}
+	defer rows.Close()
+
+	values := make([]interface{}, len(columns))
+	ptrs := make([]interface{}, len(columns))
+	for i := range values {
+		ptrs[i] = &values[i]
+	}
+
+	for rows.Next() {
+		if err := rows.Scan(ptrs...); err != nil {
+			return err
+		}
+		for i, v := range values {
+			if b, ok := v.([]byte); ok {
+				values[i] = string(b)
+			}
+		}
+		if err := enc.Encode(values); err != nil {
+			return err
+		}
+	}
+	return rows.Err()
+}
+
+// IsGzipExport reports whether the stream starts with the gzip magic bytes,
+// i.e. is a v2 export rather than a v1 JSON document
+func IsGzipExport(r *bufio.Reader) bool {
+	magic, err := r.Peek(2)
+	return err == nil && magic[0] == 0x1f && magic[1] == 0x8b
+}
+
+// ImportDatabase replaces the contents of every table present in a v1 export
+// document. The document is streamed, rows being inserted as they are read,
+// all in a single transaction, so a failure or an invalid document leaves the
+// existing data untouched. Server ids are preserved as-is. The archive's
+// config is returned for the caller to apply.
+func ImportDatabase(db *sql.DB, r io.Reader) (*AppConfig, *ImportResult, error) {
+	var config *AppConfig
+	var result *ImportResult
+	run := func(db *sql.DB) error {
+		var err error
+		config, result, err = importDatabaseInternal(db, r)
+		return err
+	}
+
+	if metricsBuffer != nil {
+		metricsBuffer.Flush()
+	}
+	if aggBuffer != nil {
+		aggBuffer.Flush()
+	}
+
+	var err error
+	if dbWriter != nil {
+		err = dbWriter.WriteSync(run)
+	} else {
+		err = run(db)
+	}
+	if err != nil {
+		return nil, nil, err
+	}
+
+	// Cached history would still point at the old data
+	if historyCache != nil {
+		historyCache.Clear()
+	}
+	return config, result, nil
+}
+
+func importDatabaseInternal(db *sql.DB, r io.Reader) (*AppConfig, *ImportResult, error) {
+	tx, err := db.Begin()
+	if err != nil {
+		return nil, nil, err
+	}
+	defer tx.Rollback()
+
+	localTables, err := listTables(tx)
+	if err != nil {
+		return nil, nil, err
+	}
+	known := make(map[string]bool, len(localTables))
+	for _, t := range localTables {
+		known[t] = true
+	}
+
+	dec := json.NewDecoder(r)
+	dec.UseNumber()
+
+	var archive ExportArchive
+	result := &ImportResult{Tables: make(map[string]int)}
+	if err := expectJSONDelim(dec, '{'); err != nil {
+		return nil, nil, err
+	}
+	for dec.More() {
+		key, err := jsonKey(dec)
+		if err != nil {
+			return nil, nil, err
+		}
+		switch key {
+		case "tables":
+			err = importArchiveTables(tx, dec, known, result)
+		case "format":
+			err = decodeArchiveValue(dec, &archive.Format)
+		case "version":
+			err = decodeArchiveValue(dec, &archive.Version)
+		case "config":
+			err = decodeArchiveValue(dec, &archive.Config)
+		default:
+			err = skipJSONValue(dec)
+		}
+		if err != nil {
+			return nil, nil, err
+		}
+	}
+	if err := expectJSONDelim(dec, '}'); err != nil {
+		return nil, nil, err
+	}
+
+	// The header may follow the tables; nothing is committed until it checks out
+	if archive.Format != ExportFormat {
+		return nil, nil, fmt.Errorf("%w: not a vstats export (format %q)", ErrInvalidExport, archive.Format)
+	}
+	if archive.Version != 1 {
+		return nil, nil, fmt.Errorf("%w: expected a version 1 document, got version %d", ErrInvalidExport, archive.Version)
+	}
+	if archive.Config == nil {
+		return nil, nil, fmt.Errorf("%w: no config", ErrInvalidExport)
+	}
+	result.Servers = len(archive.Config.Servers)
+
+	if err := tx.Commit(); err != nil {
+		return nil, nil, err
+	}
+
+	db.Exec("ANALYZE")
+	return archive.Config, result, nil
+}
+
+// importArchiveTables streams the "tables" object of a v1 export, replacing
+// the rows of every table this schema has
+func importArchiveTables(tx *sql.Tx, dec *json.Decoder, known map[string]bool, result *ImportResult) error {
+	if err := expectJSONDelim(dec, '{'); err != nil {
+		return err
+	}
+	for dec.More() {
+		table, err := jsonKey(dec)
+		if err != nil {
+			return err
+		}
+		if !known[table] {
+			result.SkippedTables = append(result.SkippedTables, table)
+			if err := skipJSONValue(dec); err != nil {
+				return err
+			}
+			continue
+		}
+		n, err := importArchiveTable(tx, dec, table)
+		if err != nil {
+			return err
+		}
+		result.Tables[table] = n
+	}
+	return expectJSONDelim(dec, '}')
+}
+
+// importArchiveTable replaces the rows of one table with those of its
+// {"columns": [...], "rows": [[...], ...]} object, and returns how many there
+// were. Columns must come first, as ExportTable encodes them.
+func importArchiveTable(tx *sql.Tx, dec *json.Decoder, table string) (int, error) {
+	if err := expectJSONDelim(dec, '{'); err != nil {
+		return 0, err
+	}
+	var columns []string
+	imported := 0
+	for dec.More() {
+		key, err := jsonKey(dec)
+		if err != nil {
+			return 0, err
+		}
+		switch key {
+		case "columns":
+			err = decodeArchiveValue(dec, &columns)
+		case "rows":
+			imported, err = importArchiveRows(tx, dec, table, columns)
+		default:
+			err = skipJSONValue(dec)
+		}
+		if err != nil {
+			return 0, err
+		}
+	}
+	return imported, expectJSONDelim(dec, '}')
+}
+
+// importArchiveRows clears a table and inserts the rows array that follows
+func importArchiveRows(tx *sql.Tx, dec *json.Decoder, table string, columns []string) (int, error) {
+	if columns == nil {
+		return 0, fmt.Errorf("%w: rows of %s before its columns", ErrInvalidExport, table)
+	}
+	localCols, err := tableColumns(tx, table)
+	if err != nil {
+		return 0, err
+	}
+	localSet := make(map[string]bool, len(localCols))
+	for _, col := range localCols {
+		localSet[col] = true
+	}
+
+	// Only restore columns that still exist in this schema
+	var cols []string
+	var idx []int
+	for i, col := range columns {
+		if localSet[col] {
+			cols = append(cols, fmt.Sprintf("%q", col))
+			idx = append(idx, i)
+		}
+	}
+
+	if _, err := tx.Exec(fmt.Sprintf("DELETE FROM %q", table)); err != nil {
+		return 0, fmt.Errorf("failed to clear %s: %w", table, err)
+	}
+
+	// An empty table is encoded as null
+	tok, err := dec.Token()
+	if err != nil {
+		return 0, fmt.Errorf("%w: %v", ErrInvalidExport, err)
+	}
+	if tok == nil {
+		return 0, nil
+	}
+	if tok != json.Delim('[') {
+		return 0, fmt.Errorf("%w: rows of %s are not an array", ErrInvalidExport, table)
+	}
+
+	var stmt *sql.Stmt
+	if len(cols) > 0 {
+		placeholders := strings.TrimSuffix(strings.Repeat("?,", len(cols)), ",")
+		stmt, err = tx.Prepare(fmt.Sprintf("INSERT OR REPLACE INTO %q (%s) VALUES (%s)",
+			table, strings.Join(cols, ","), placeholders))
+		if err != nil {
+			return 0, err
+		}
+		defer stmt.Close()
+	}
+
+	n := 0
+	args := make([]interface{}, len(idx))
+	for dec.More() {
+		var row []interface{}
+		if err := decodeArchiveValue(dec, &row); err != nil {
+			return 0, err
+		}
+		n++
+		if stmt == nil {
+			continue
+		}
+		for j, i := range idx {
+			if i < len(row) {
+				args[j] = importValue(row[i])
+			} else {
+				args[j] = nil
+			}
+		}
+		if _, err := stmt.Exec(args...); err != nil {
+			return 0, fmt.Errorf("failed to import %s: %w", table, err)
+		}
+	}
+	return n, expectJSONDelim(dec, ']')
+}
+
+// decodeArchiveValue decodes the next value of an export document into v
+func decodeArchiveValue(dec *json.Decoder, v interface{}) error {
+	if err := dec.Decode(v); err != nil {
+		return fmt.Errorf("%w: %v", ErrInvalidExport, err)
+	}
+	return nil
+}
+
+// jsonKey reads the next key of an object
+func jsonKey(dec *json.Decoder) (string, error) {
+	tok, err := dec.Token()
+	if err != nil {
+		return "", fmt.Errorf("%w: %v", ErrInvalidExport, err)
+	}
+	key, ok := tok.(string)
+	if !ok {
+		return "", fmt.Errorf("%w: expected an object key", ErrInvalidExport)
+	}
+	return key, nil
+}
+
+// expectJSONDelim reads the next token, which must be delim
+func expectJSONDelim(dec *json.Decoder, delim json.Delim) error {
+	tok, err := dec.Token()
+	if err != nil {
+		return fmt.Errorf("%w: %v", ErrInvalidExport, err)
+	}
+	if tok != delim {
+		return fmt.Errorf("%w: expected %v", ErrInvalidExport, delim)
+	}
+	return nil
+}
+
+// skipJSONValue reads past the next value without keeping it in memory
+func skipJSONValue(dec *json.Decoder) error {
+	depth := 0
+	for {
+		tok, err := dec.Token()
+		if err != nil {
+			return fmt.Errorf("%w: %v", ErrInvalidExport, err)
+		}
+		switch tok {
+		case json.Delim('{'), json.Delim('['):
+			depth++
+		case json.Delim('}'), json.Delim(']'):
+			depth--
+		}
+		if depth == 0 {
+			return nil
+		}
+	}
+}
+
+// importValue converts decoded JSON numbers back to SQLite integer/real values
+func importValue(v interface{}) interface{} {
+	n, ok := v.(json.Number)
+	if !ok {
+		return v
+	}
+	if i, err := n.Int64(); err == nil {
+		return i
+	}
+	if f, err := n.Float64(); err == nil {
+		return f
+	}
+	return n.String()
+}
+
+// ============================================================================
+// Streaming merge import (v2)
+// ============================================================================
+
+// importBatch holds pending rows of one table and the statement to insert them
+type importBatch struct {
+	table string
+	query string
+	idx   []int // Archive column index of each inserted column
+	keys  []int // Archive column index of each merge key (NOT EXISTS check)
+	rows  [][]interface{}
+}
+
+// newImportBatch maps archive columns onto the local schema. A rowid alias
+// such as the autoincrement id is dropped so rows never clash with local ids;
+// duplicates are detected through natural keys instead. Other primary keys,
+// e.g. the token ID of revoked_tokens, are data and kept.
+func newImportBatch(db *sql.DB, header ExportTableHeader) (*importBatch, error) {
+	localCols, err := tableInfo(db, header.Table)
+	if err != nil {
+		return nil, err
+	}
+	localSet := make(map[string]bool, len(localCols))
+	for _, col := range localCols {
+		localSet[col.Name] = true
+	}
+	rowid := rowidAlias(localCols)
+
+	b := &importBatch{table: header.Table}
+	var cols []string
+	position := make(map[string]int, len(header.Columns))
+	for i, col := range header.Columns {
+		position[col] = i
+		if localSet[col] && col != rowid {
+			cols = append(cols, fmt.Sprintf("%q", col))
+			b.idx = append(b.idx, i)
+		}
+	}
+	if len(cols) == 0 {
+		return b, nil
+	}
+
+	placeholders := strings.TrimSuffix(strings.Repeat("?,", len(cols)), ",")
+	keys, hasKeys := importMergeKeys[header.Table]
+	if !hasKeys {
+		b.query = fmt.Sprintf("INSERT OR IGNORE INTO %q (%s) VALUES (%s)",
+			header.Table, strings.Join(cols, ","), placeholders)
+		return b, nil
+	}
+
+	conds := make([]string, len(keys))
+	for i, key := range keys {
+		pos, ok := position[key]
+		if !ok {
+			return nil, fmt.Errorf("%w: table %s has no %s column", ErrInvalidExport, header.Table, key)
+		}
+		b.keys = append(b.keys, pos)
+		conds[i] = fmt.Sprintf("%q = ?", key)
+	}
+	b.query = fmt.Sprintf("INSERT INTO %[1]q (%[2]s) SELECT %[3]s WHERE NOT EXISTS (SELECT 1 FROM %[1]q WHERE %[4]s)",
+		header.Table, strings.Join(cols, ","), placeholders, strings.Join(conds, " AND "))
+	return b, nil
+}
+
+// flush writes the pending rows in one transaction and returns how many
+// were inserted
+func (b *importBatch) flush(db *sql.DB) (int, error) {
+	if len(b.rows) == 0 || b.query == "" {
+		b.rows = b.rows[:0]
+		return 0, nil
+	}
+
+	inserted := 0
+	run := func(db *sql.DB) error {
+		tx, err := db.Begin()
+		if err != nil {
+			return err
+		}
+		defer tx.Rollback()
+
+		stmt, err := tx.Prepare(b.query)
+		if err != nil {
+			return err
+		}
+		defer stmt.Close()
+
+		positions := append(append([]int{}, b.idx...), b.keys...)
+		args := make([]interface{}, len(positions))
+		for _, row := range b.rows {
+			for j, i := range positions {
+				if i < len(row) {
+					args[j] = importValue(row[i])
+				} else {
+					args[j] = nil
+				}
+			}
+			res, err := stmt.Exec(args...)
+			if err != nil {
+				return fmt.Errorf("failed to import %s: %w", b.table, err)
+			}
+			if n, err := res.RowsAffected(); err == nil {
+				inserted += int(n)
+			}
+		}
+		return tx.Commit()
+	}
+
+	var err error
+	if dbWriter != nil {
+		err = dbWriter.WriteSync(run)
+	} else {
+		err = run(db)
+	}
+	b.rows = b.rows[:0]
+	return inserted, err
+}
+
+// ImportStream merges a v2 export into the database. Rows are streamed and
+// written in batches with INSERT OR IGNORE (or a NOT EXISTS check on
+// importMergeKeys), so importing the same archive twice inserts nothing new.
+// Existing data is kept. The archive's config is returned for MergeConfig.
+func ImportStream(db *sql.DB, r io.Reader) (*AppConfig, *ImportResult, error) {
+	gz, err := gzip.NewReader(r)
+	if err != nil {
+		return nil, nil, fmt.Errorf("%w: %v", ErrInvalidExport, err)
+	}
+	defer gz.Close()
+
+	dec := json.NewDecoder(gz)
+	dec.UseNumber()
+
+	var header ExportHeader
+	if err := dec.Decode(&header); err != nil {
+		return nil, nil, fmt.Errorf("%w: %v", ErrInvalidExport, err)
+	}
+	if header.Format != ExportFormat {
+		return nil, nil, fmt.Errorf("%w: not a vstats export (format %q)", ErrInvalidExport, header.Format)
+	}
+	if header.Version != 2 {
+		return nil, nil, fmt.Errorf("%w: expected version 2, got version %d", ErrInvalidExport, header.Version)
+	}
+	if header.Config == nil {
+		return nil, nil, fmt.Errorf("%w: no config", ErrInvalidExport)
+	}
+
+	if metricsBuffer != nil {
+		metricsBuffer.Flush()
+	}
+	if aggBuffer != nil {
+		aggBuffer.Flush()
+	}
+
+	localTables, err := listTables(db)
+	if err != nil {
+		return nil, nil, err
+	}
+	known := make(map[string]bool, len(localTables))
+	for _, t := range localTables {
+		known[t] = true
+	}
+
+	result := &ImportResult{Tables: make(map[string]int)}
+	var batch *importBatch
+	flush := func() error {
+		if batch == nil {
+			return nil
+		}
+		n, err := batch.flush(db)
+		result.Tables[batch.table] += n
+		return err
+	}
+
+	for {
+		var line interface{}
+		if err := dec.Decode(&line); err == io.EOF {
+			break
+		} else if err != nil {
+			return nil, nil, fmt.Errorf("%w: %v", ErrInvalidExport, err)
+		}
+
+		switch v := line.(type) {
+		case map[string]interface{}:
+			// Table line: finish the previous table first
+			if err := flush(); err != nil {
+				return nil, nil, err
+			}
+			var th ExportTableHeader
+			data, _ := json.Marshal(v)
+			if err := json.Unmarshal(data, &th); err != nil || th.Table == "" {
+				return nil, nil, fmt.Errorf("%w: bad table line", ErrInvalidExport)
+			}
+			if !known[th.Table] {
+				result.SkippedTables = append(result.SkippedTables, th.Table)
+				batch = nil
+				continue
+			}
+			if batch, err = newImportBatch(db, th); err != nil {
+				return nil, nil, err
+			}
+		case []interface{}:
+			if batch == nil {
+				continue // Row of a skipped table
+			}
+			batch.rows = append(batch.rows, v)
+			if len(batch.rows) >= importBatchRows {
+				if err := flush(); err != nil {
+					return nil, nil, err
+				}
+			}
+		default:
+			return nil, nil, fmt.Errorf("%w: unexpected line", ErrInvalidExport)
+		}
+	}
+	if err := flush(); err != nil {
+		return nil, nil, err
+	}
+
+	// Cached history would not include the imported rows
+	if historyCache != nil {
+		historyCache.Clear()
+	}
+	if dbWriter != nil {
+		dbWriter.WriteAsync(func(db *sql.DB) error {
+			_, err := db.Exec("ANALYZE")
+			return err
+		})
+	} else {
+		db.Exec("ANALYZE")
+	}
+	return header.Config, result, nil
+}
+
+// ImportFile merges an export file into the local database and config. Run
+// it while the server is stopped, since a running server keeps its own copy
+// of the config and would overwrite the merged servers.
+func ImportFile(path string) (*ImportResult, error) {
+	f, err := os.Open(path)
+	if err != nil {
+		return nil, err
+	}
+	defer f.Close()
+
+	r := bufio.NewReader(f)
+	if !IsGzipExport(r) {
+		return nil, fmt.Errorf("%w: only gzip NDJSON exports can be imported from the command line; "+
+			"restore version 1 exports with POST /api/admin/import?confirm=true", ErrInvalidExport)
+	}
+
+	db, err := InitDatabase()
+	if err != nil {
+		return nil, err
+	}
+	defer db.Close()
+
+	config, initialPassword := LoadConfig()
+	if initialPassword != nil {
+		fmt.Printf("🔑 Created a new config, admin password: %s\n", *initialPassword)
+	}
+
+	archiveConfig, result, err := ImportStream(db, r)
+	if err != nil {
+		return nil, err
+	}
+	result.Servers = MergeConfig(config, archiveConfig)
+	SaveConfig(config)
+	return result, nil
+}
+
+// MergeConfig adds servers and group dimensions from an imported config whose
+// ids are not present yet, keeping their tokens so agents can reconnect.
+// Admin credentials and site settings stay as they are. Returns the number of
+// servers added.
+func MergeConfig(dst, src *AppConfig) int {
+	servers := make(map[string]bool, len(dst.Servers))
+	for _, s := range dst.Servers {
+		servers[s.ID] = true
+	}
+	added := 0
+	for _, s := range src.Servers {
+		if !servers[s.ID] {
+			dst.Servers = append(dst.Servers, s)
+			added++
+		}
+	}
+
+	dimensions := make(map[string]bool, len(dst.GroupDimensions))
+	for _, d := range dst.GroupDimensions {
+		dimensions[d.ID] = true
+	}
+	for _, d := range src.GroupDimensions {
+		if !dimensions[d.ID] {
+			dst.GroupDimensions = append(dst.GroupDimensions, d)
+		}
+	}
+	return added
+}
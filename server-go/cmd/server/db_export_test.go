@@ -0,0 +1,227 @@
+package main
+
+import (
+	"bytes"
+	"database/sql"
+	"encoding/json"
+	"errors"
+	"strings"
+	"testing"
+)
+
+func openTestDB(t *testing.T) *sql.DB {
+	t.Helper()
+	db, err := InitDatabaseAt(MemoryDBPath)
+	if err != nil {
+		t.Fatal(err)
+	}
+	t.Cleanup(func() { db.Close() })
+	return db
+}
+
+func execAll(t *testing.T, db *sql.DB, statements ...string) {
+	t.Helper()
+	for _, s := range statements {
+		if _, err := db.Exec(s); err != nil {
+			t.Fatalf("%s: %v", s, err)
+		}
+	}
+}
+
+func countRows(t *testing.T, db *sql.DB, table string) int {
+	t.Helper()
+	var n int
+	if err := db.QueryRow("SELECT COUNT(*) FROM " + table).Scan(&n); err != nil {
+		t.Fatal(err)
+	}
+	return n
+}
+
+// exportedTables are the tables seeded by seedExportData
+var exportedTables = map[string]int{
+	"revoked_tokens":      2,
+	"availability_events": 3,
+	"alerts":              2,
+	"login_audit":         2,
+	"agent_events":        1,
+	"metrics_15min":       2,
+}
+
+func seedExportData(t *testing.T, db *sql.DB) {
+	execAll(t, db,
+		`INSERT INTO revoked_tokens (id, expires_at) VALUES ('0b6e2c9a-jti', '2030-01-01T00:00:00Z'), ('sha256:9f86d0', '2030-01-01T00:00:00Z')`,
+		`INSERT INTO availability_events (server_id, timestamp, state) VALUES
+			('s1', '2026-01-01T00:00:00Z', 'online'),
+			('s1', '2026-01-01T01:00:00Z', 'offline'),
+			('s2', '2026-01-01T00:00:00Z', 'online')`,
+		`INSERT INTO alerts (server_id, rule, severity, opened_at, resolved_at, peak_value) VALUES
+			('s1', 'cpu', 'warning', '2026-01-01T00:00:00Z', '2026-01-01T00:10:00Z', 97.5),
+			('s1', 'offline', 'critical', '2026-01-01T01:00:00Z', NULL, NULL)`,
+		`INSERT INTO login_audit (timestamp, username, method, client_ip, success) VALUES
+			('2026-01-01T00:00:00Z', 'admin', 'password', '192.0.2.1', 1),
+			('2026-01-01T00:01:00Z', 'admin', 'password', '192.0.2.2', 0)`,
+		`INSERT INTO agent_events (timestamp, server_id, event, client_ip) VALUES ('2026-01-01T00:00:00Z', 's1', 'connect', '192.0.2.1')`,
+		`INSERT INTO metrics_15min (server_id, bucket_start, cpu_avg, cpu_max, memory_avg, memory_max, disk_avg, net_rx_total, net_tx_total, sample_count) VALUES
+			('s1', '2026-01-01T00:00:00Z', 10, 20, 30, 40, 50, 1099511627776, 2, 180),
+			('s1', '2026-01-01T00:15:00Z', 11, 21, 31, 41, 51, 3, 4, 180)`,
+	)
+}
+
+func TestExportImportRoundTrip(t *testing.T) {
+	src := openTestDB(t)
+	seedExportData(t, src)
+	for table, n := range exportedTables {
+		if got := countRows(t, src, table); got != n {
+			t.Fatalf("seeded %d rows into %s, want %d", got, table, n)
+		}
+	}
+
+	var archive bytes.Buffer
+	if err := ExportDatabase(src, &AppConfig{Servers: []RemoteServer{{ID: "s1"}, {ID: "s2"}}}, &archive); err != nil {
+		t.Fatal(err)
+	}
+
+	dst := openTestDB(t)
+	config, result, err := ImportStream(dst, bytes.NewReader(archive.Bytes()))
+	if err != nil {
+		t.Fatal(err)
+	}
+	if len(config.Servers) != 2 {
+		t.Errorf("imported config has %d servers, want 2", len(config.Servers))
+	}
+	for table, n := range exportedTables {
+		if got := countRows(t, dst, table); got != n {
+			t.Errorf("%s: %d rows after import, want %d", table, got, n)
+		}
+		if result.Tables[table] != n {
+			t.Errorf("%s: result counts %d rows, want %d", table, result.Tables[table], n)
+		}
+	}
+
+	// Token IDs are the key of revoked_tokens, not a rowid to renumber
+	rows, err := dst.Query("SELECT id FROM revoked_tokens ORDER BY id")
+	if err != nil {
+		t.Fatal(err)
+	}
+	var ids []string
+	for rows.Next() {
+		var id string
+		rows.Scan(&id)
+		ids = append(ids, id)
+	}
+	rows.Close()
+	if strings.Join(ids, ",") != "0b6e2c9a-jti,sha256:9f86d0" {
+		t.Errorf("revoked token IDs = %v", ids)
+	}
+
+	var rx int64
+	dst.QueryRow("SELECT net_rx_total FROM metrics_15min WHERE bucket_start = '2026-01-01T00:00:00Z'").Scan(&rx)
+	if rx != 1099511627776 {
+		t.Errorf("net_rx_total = %d, integer not kept exact", rx)
+	}
+
+	// Importing the same archive again, or into the source, adds nothing
+	for name, db := range map[string]*sql.DB{"destination": dst, "source": src} {
+		_, again, err := ImportStream(db, bytes.NewReader(archive.Bytes()))
+		if err != nil {
+			t.Fatalf("%s: %v", name, err)
+		}
+		for table, n := range exportedTables {
+			if again.Tables[table] != 0 {
+				t.Errorf("%s: re-import inserted %d rows into %s", name, again.Tables[table], table)
+			}
+			if got := countRows(t, db, table); got != n {
+				t.Errorf("%s: %s has %d rows after re-import, want %d", name, table, got, n)
+			}
+		}
+	}
+}
+
+func TestRowidAlias(t *testing.T) {
+	db := openTestDB(t)
+	tables := map[string]string{
+		"metrics_raw":             "id",
+		"availability_checkpoint": "id",
+		"revoked_tokens":          "",
+		"availability_events":     "",
+		"metrics_ping":            "",
+		"bandwidth_counters":      "",
+	}
+	for table, want := range tables {
+		info, err := tableInfo(db, table)
+		if err != nil || len(info) == 0 {
+			t.Fatalf("%s: %v", table, err)
+		}
+		if got := rowidAlias(info); got != want {
+			t.Errorf("rowidAlias(%s) = %q, want %q", table, got, want)
+		}
+	}
+}
+
+func TestImportDatabaseV1(t *testing.T) {
+	db := openTestDB(t)
+	seedExportData(t, db)
+
+	archive := ExportArchive{
+		Format:  ExportFormat,
+		Version: 1,
+		Config:  &AppConfig{Servers: []RemoteServer{{ID: "s9"}}},
+		Tables: map[string]ExportTable{
+			"availability_events": {
+				Columns: []string{"server_id", "timestamp", "state", "dropped_column"},
+				Rows: [][]interface{}{
+					{"s9", "2026-02-01T00:00:00Z", "online", 1},
+					{"s9", "2026-02-01T01:00:00Z", "offline", 2},
+				},
+			},
+			"revoked_tokens": {Columns: []string{"id", "expires_at"}},
+			"no_such_table":  {Columns: []string{"x"}, Rows: [][]interface{}{{1}}},
+		},
+	}
+	doc, err := json.Marshal(archive)
+	if err != nil {
+		t.Fatal(err)
+	}
+
+	// A bad header is only known at the end, and must leave the data alone
+	bad := bytes.Replace(doc, []byte(`"version":1`), []byte(`"version":3`), 1)
+	var importErr error
+	withTimeout(t, "ImportDatabase", func() { _, _, importErr = ImportDatabase(db, bytes.NewReader(bad)) })
+	if !errors.Is(importErr, ErrInvalidExport) {
+		t.Errorf("version 3 document: %v", importErr)
+	}
+	for _, truncated := range [][]byte{doc[:len(doc)/2], doc[:len(doc)-1], []byte(`[]`)} {
+		if _, _, err := ImportDatabase(db, bytes.NewReader(truncated)); !errors.Is(err, ErrInvalidExport) {
+			t.Errorf("malformed document %.20q: %v", truncated, err)
+		}
+	}
+	for table, n := range exportedTables {
+		if got := countRows(t, db, table); got != n {
+			t.Fatalf("%s has %d rows after failed imports, want %d", table, got, n)
+		}
+	}
+
+	config, result, err := ImportDatabase(db, bytes.NewReader(doc))
+	if err != nil {
+		t.Fatal(err)
+	}
+	if len(config.Servers) != 1 || config.Servers[0].ID != "s9" || result.Servers != 1 {
+		t.Errorf("config = %+v, result = %+v", config, result)
+	}
+	if result.Tables["availability_events"] != 2 || len(result.SkippedTables) != 1 || result.SkippedTables[0] != "no_such_table" {
+		t.Errorf("result = %+v", result)
+	}
+	var servers string
+	db.QueryRow("SELECT GROUP_CONCAT(DISTINCT server_id) FROM availability_events").Scan(&servers)
+	if countRows(t, db, "availability_events") != 2 || servers != "s9" {
+		t.Errorf("availability_events not replaced: %s", servers)
+	}
+	// An empty table is encoded as null and still replaced
+	if n := countRows(t, db, "revoked_tokens"); n != 0 {
+		t.Errorf("revoked_tokens has %d rows, want 0", n)
+	}
+	// Tables missing from the document are kept
+	if n := countRows(t, db, "alerts"); n != exportedTables["alerts"] {
+		t.Errorf("alerts has %d rows, want %d", n, exportedTables["alerts"])
+	}
+}
@@ -0,0 +1,193 @@
+package main
+
+import (
+	"database/sql"
+	"errors"
+	"fmt"
+	"time"
+)
+
+// ============================================================================
+// Custom History Ranges
+// ============================================================================
+//
+// from/to and max_points queries read a single table, grouped into buckets
+// of span / max_points seconds: the coarsest table whose native step still
+// fits that width and that retains data from `from`.
+
+const (
+	// MaxHistoryRangeSpan is the longest from/to span accepted
+	MaxHistoryRangeSpan = 400 * 24 * time.Hour
+	// DefaultHistoryMaxPoints is the bucket count for from/to queries
+	// without max_points; max_points is capped at MaxHistoryPageLimit
+	DefaultHistoryMaxPoints = 500
+)
+
+// historyRangeSpans are the preset ranges, for max_points queries
+var historyRangeSpans = map[string]time.Duration{
+	"1h":  time.Hour,
+	"24h": 24 * time.Hour,
+	"7d":  7 * 24 * time.Hour,
+	"30d": 30 * 24 * time.Hour,
+	"1y":  365 * 24 * time.Hour,
+}
+
+// HistoryResolution is a table a from/to query can be served from
+type HistoryResolution struct {
+	Name        string // Reported as the response's resolution
+	table       string
+	bucketSecs  int64 // 0 for metrics_raw, which is sampled per raw_sample_interval_secs
+	retention   time.Duration
+	percentiles bool // Has cpu_p95/memory_p95/ping_max columns
+}
+
+// historyResolutions are ordered finest first, with the retention enforced
+// by cleanupOldDataInternal
+var historyResolutions = []HistoryResolution{
+	{"raw", "metrics_raw", 0, 24 * time.Hour, false},
+	{"5sec", "metrics_5sec", 5, 2 * time.Hour, false},
+	{"2min", "metrics_2min", 120, 26 * time.Hour, false},
+	{"15min", "metrics_15min_agg", 900, 8 * 24 * time.Hour, true},
+	{"hourly", "metrics_hourly_agg", 3600, 32 * 24 * time.Hour, true},
+	{"daily", "metrics_daily_agg", 86400, 400 * 24 * time.Hour, true},
+}
+
+var (
+	ErrHistoryRangeInverted = errors.New("from must be before to")
+	ErrHistoryRangeTooLarge = errors.New("from/to span must not exceed 400 days")
+)
+
+// SelectHistoryResolution picks the table for [from, to) as of now and the
+// bucket width (a multiple of the table's step) that yields at most
+// maxPoints buckets. Raw rows are assumed to be rawIntervalSecs apart.
+// Ranges older than every retention window are served from the daily table.
+func SelectHistoryResolution(from, to, now time.Time, rawIntervalSecs int64, maxPoints int) (HistoryResolution, int64, error) {
+	if !from.Before(to) {
+		return HistoryResolution{}, 0, ErrHistoryRangeInverted
+	}
+	span := int64(to.Sub(from) / time.Second)
+	if to.Sub(from) > MaxHistoryRangeSpan {
+		return HistoryResolution{}, 0, ErrHistoryRangeTooLarge
+	}
+	if rawIntervalSecs < 1 {
+		rawIntervalSecs = 1
+	}
+	if maxPoints < 1 {
+		maxPoints = DefaultHistoryMaxPoints
+	}
+	width := (span + int64(maxPoints) - 1) / int64(maxPoints)
+
+	// Finest retained table, then coarser ones while their step fits
+	chosen, step := -1, int64(0)
+	for i, r := range historyResolutions {
+		if from.Before(now.Add(-r.retention)) {
+			continue
+		}
+		s := r.bucketSecs
+		if s == 0 {
+			s = rawIntervalSecs
+		}
+		if chosen >= 0 && s > width {
+			break
+		}
+		chosen, step = i, s
+	}
+	if chosen < 0 {
+		chosen, step = len(historyResolutions)-1, 86400
+	}
+
+	bucketSecs := step
+	if width > step {
+		bucketSecs = (width + step - 1) / step * step
+	}
+	return historyResolutions[chosen], bucketSecs, nil
+}
+
+// GetHistoryRange returns the history in [from, to) downsampled to at most
+// maxPoints points, with the resolution read and the effective bucket width
+func GetHistoryRange(db *sql.DB, serverID string, from, to time.Time, stat string, maxPoints int) ([]HistoryPoint, string, int64, error) {
+	rawSampler.mu.Lock()
+	rawInterval := rawSampler.interval
+	rawSampler.mu.Unlock()
+
+	from, to = from.UTC(), to.UTC()
+	res, bucketSecs, err := SelectHistoryResolution(from, to, time.Now().UTC(), rawInterval, maxPoints)
+	if err != nil {
+		return nil, "", 0, err
+	}
+	// One extra bucket may straddle each end of the range
+	statReplacer := historyStatReplacer(stat, MaxHistoryPageLimit+2)
+
+	// p95 values of several buckets cannot be combined, so they are only
+	// passed through when each point is a single stored bucket
+	var rows *sql.Rows
+	if res.bucketSecs == 0 {
+		rows, err = db.Query(statReplacer.Replace(fmt.Sprintf(`
+			SELECT
+				strftime('%%Y-%%m-%%dT%%H:%%M:%%SZ', (strftime('%%s', timestamp) / %[1]d) * %[1]d, 'unixepoch') as ts,
+				{cpu_raw_group},
+				{memory_raw_group},
+				AVG(disk_usage),
+				MAX(net_rx),
+				MAX(net_tx),
+				AVG(ping_ms),
+				MAX(cpu_usage), NULL, MAX(memory_usage), NULL, MAX(ping_ms),
+				{load_swap_raw_group}, {net_rate_raw_group}
+			FROM metrics_raw
+			WHERE server_id = ? AND timestamp >= ? AND timestamp < ?
+			GROUP BY strftime('%%s', timestamp) / %[1]d
+			ORDER BY ts ASC
+			LIMIT {limit}`, bucketSecs)), serverID, from.Format(time.RFC3339), to.Format(time.RFC3339))
+	} else {
+		cpu, memory := "CASE WHEN SUM(sample_count) > 0 THEN SUM(cpu_sum) / SUM(sample_count) ELSE 0 END",
+			"CASE WHEN SUM(sample_count) > 0 THEN SUM(memory_sum) / SUM(sample_count) ELSE 0 END"
+		if stat == HistoryStatMax {
+			cpu, memory = "MAX(cpu_max)", "MAX(memory_max)"
+		}
+		peaks := "MAX(cpu_max), NULL, MAX(memory_max), NULL, NULL"
+		loadSwap := "{load_swap_bucket_group}, {net_rate_bucket_group}"
+		if res.percentiles {
+			peaks = "MAX(cpu_max), CASE WHEN COUNT(*) = 1 THEN MAX(cpu_p95) END, " +
+				"MAX(memory_max), CASE WHEN COUNT(*) = 1 THEN MAX(memory_p95) END, MAX(ping_max)"
+			loadSwap = "{load_swap_rollup_group}, {net_rate_rollup_group}"
+		}
+		factor := bucketSecs / res.bucketSecs
+		rows, err = db.Query(statReplacer.Replace(fmt.Sprintf(`
+			SELECT
+				strftime('%%Y-%%m-%%dT%%H:%%M:%%SZ', (bucket / %[1]d) * %[2]d, 'unixepoch') as ts,
+				%[3]s,
+				%[4]s,
+				CASE WHEN SUM(sample_count) > 0 THEN SUM(disk_sum) / SUM(sample_count) ELSE 0 END,
+				MAX(net_rx),
+				MAX(net_tx),
+				CASE WHEN SUM(ping_count) > 0 THEN SUM(ping_sum) / SUM(ping_count) ELSE NULL END,
+				%[5]s,
+				%[7]s
+			FROM %[6]s
+			WHERE server_id = ? AND bucket >= ? AND bucket < ?
+			GROUP BY bucket / %[1]d
+			ORDER BY ts ASC
+			LIMIT {limit}`, factor, bucketSecs, cpu, memory, peaks, res.table, loadSwap)), serverID,
+			from.Unix()/res.bucketSecs, (to.Unix()+res.bucketSecs-1)/res.bucketSecs)
+	}
+	if err != nil {
+		return nil, "", 0, err
+	}
+	defer rows.Close()
+
+	data := []HistoryPoint{}
+	for rows.Next() {
+		var point HistoryPoint
+		var loadSwap historyLoadSwap
+		var netRate historyNetRate
+		dest := append([]interface{}{&point.Timestamp, &point.CPU, &point.Memory, &point.Disk, &point.NetRx, &point.NetTx, &point.PingMs,
+			&point.CPUMax, &point.CPUP95, &point.MemoryMax, &point.MemoryP95, &point.PingMax}, loadSwap.dest()...)
+		if err := rows.Scan(append(dest, netRate.dest()...)...); err != nil {
+			continue
+		}
+		loadSwap.apply(&point)
+		netRate.apply(&point)
+		data = append(data, point)
+	}
+	return data, res.Name, bucketSecs, rows.Err()
+}
@@ -0,0 +1,67 @@
+package main
+
+import (
+	"errors"
+	"testing"
+	"time"
+)
+
+func TestSelectHistoryResolution(t *testing.T) {
+	now := time.Date(2026, 3, 1, 12, 0, 0, 0, time.UTC)
+	ago := func(d time.Duration) time.Time { return now.Add(-d) }
+	const day = 24 * time.Hour
+
+	cases := []struct {
+		name        string
+		from, to    time.Time
+		rawInterval int64
+		maxPoints   int
+		resolution  string
+		bucketSecs  int64
+	}{
+		{"500s at one point per second", ago(500 * time.Second), now, 1, 500, "raw", 1},
+		{"10 minutes", ago(10 * time.Minute), now, 1, 500, "raw", 2},
+		{"10 minutes of 10s samples", ago(10 * time.Minute), now, 10, 500, "raw", 10},
+		{"unknown raw interval", ago(10 * time.Minute), now, 0, 0, "raw", 2},
+		{"1 hour", ago(time.Hour), now, 1, 500, "5sec", 10},
+		{"1 hour at full detail", ago(time.Hour), now, 1, 3600, "raw", 1},
+		{"24 hours", ago(day), now, 1, 500, "2min", 240},
+		// raw keeps exactly 24 hours
+		{"raw retention edge", ago(day), ago(23 * time.Hour), 1, 500, "raw", 8},
+		{"past raw retention", ago(day + time.Second), ago(23 * time.Hour), 1, 500, "2min", 120},
+		{"7 days", ago(7 * day), now, 1, 500, "15min", 1800},
+		{"30 days", ago(30 * day), now, 1, 500, "hourly", 7200},
+		{"past hourly retention", ago(32*day + time.Second), ago(31 * day), 1, 500, "daily", 86400},
+		{"older than every table", ago(500 * day), ago(499 * day), 1, 500, "daily", 86400},
+		{"longest span", ago(400 * day), now, 1, 500, "daily", 86400},
+	}
+	for _, c := range cases {
+		res, bucketSecs, err := SelectHistoryResolution(c.from, c.to, now, c.rawInterval, c.maxPoints)
+		if err != nil {
+			t.Errorf("%s: %v", c.name, err)
+			continue
+		}
+		if res.Name != c.resolution || bucketSecs != c.bucketSecs {
+			t.Errorf("%s: got %s/%ds, want %s/%ds", c.name, res.Name, bucketSecs, c.resolution, c.bucketSecs)
+		}
+		// A bucket is a whole number of the table's steps
+		if res.bucketSecs > 0 && bucketSecs%res.bucketSecs != 0 {
+			t.Errorf("%s: bucket %ds is not a multiple of %ds", c.name, bucketSecs, res.bucketSecs)
+		}
+	}
+
+	errorCases := []struct {
+		name     string
+		from, to time.Time
+		err      error
+	}{
+		{"empty", now, now, ErrHistoryRangeInverted},
+		{"inverted", now, ago(time.Hour), ErrHistoryRangeInverted},
+		{"too large", ago(400*day + time.Second), now, ErrHistoryRangeTooLarge},
+	}
+	for _, c := range errorCases {
+		if _, _, err := SelectHistoryResolution(c.from, c.to, now, 1, 500); !errors.Is(err, c.err) {
+			t.Errorf("%s: got %v, want %v", c.name, err, c.err)
+		}
+	}
+}
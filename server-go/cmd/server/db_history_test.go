@@ -0,0 +1,73 @@
+package main
+
+import (
+	"fmt"
+	"testing"
+	"time"
+)
+
+func TestHistory24hBuckets(t *testing.T) {
+	db := openTestDB(t)
+	// The last whole hour, in 2-minute buckets
+	end := time.Now().Unix() / 120 * 120
+	start := end - 3600
+
+	for _, interval := range []int64{1, 10, 120} {
+		serverID := fmt.Sprintf("every-%ds", interval)
+
+		type bucketSum struct {
+			cpu, memory float64
+			count       int
+			rx          uint64
+		}
+		want := map[int64]*bucketSum{}
+		var items []MetricsBufferItem
+		for ts := start; ts < end; ts += interval {
+			m := &SystemMetrics{Timestamp: time.Unix(ts, 0).UTC()}
+			m.CPU.Usage = float32(ts % 7)
+			m.Memory.UsagePercent = float32(50 + ts%3)
+			m.Network.TotalRx = uint64(ts - start)
+			items = append(items, MetricsBufferItem{ServerID: serverID, Metrics: m})
+
+			b := want[ts/120]
+			if b == nil {
+				b = &bucketSum{}
+				want[ts/120] = b
+			}
+			b.cpu += float64(m.CPU.Usage)
+			b.memory += float64(m.Memory.UsagePercent)
+			b.count++
+			b.rx = m.Network.TotalRx
+		}
+		if err := batchStoreMetrics(db, items); err != nil {
+			t.Fatal(err)
+		}
+
+		points, err := GetHistory(db, serverID, "24h")
+		if err != nil {
+			t.Fatal(err)
+		}
+		// One averaged point per 2-minute bucket, whatever the agent interval
+		if len(points) != 30 {
+			t.Errorf("%ds: %d points, want 30", interval, len(points))
+			continue
+		}
+		for i, p := range points {
+			bucket := start/120 + int64(i)
+			b := want[bucket]
+			if ts := time.Unix(bucket*120, 0).UTC().Format(time.RFC3339); p.Timestamp != ts {
+				t.Errorf("%ds point %d: timestamp %s, want %s", interval, i, p.Timestamp, ts)
+			}
+			if wantCPU := float32(b.cpu / float64(b.count)); p.CPU != wantCPU {
+				t.Errorf("%ds point %d: cpu %v, want %v", interval, i, p.CPU, wantCPU)
+			}
+			if wantMemory := float32(b.memory / float64(b.count)); p.Memory != wantMemory {
+				t.Errorf("%ds point %d: memory %v, want %v", interval, i, p.Memory, wantMemory)
+			}
+			// Counters report their latest value in the bucket
+			if p.NetRx != int64(b.rx) {
+				t.Errorf("%ds point %d: net_rx %d, want %d", interval, i, p.NetRx, b.rx)
+			}
+		}
+	}
+}
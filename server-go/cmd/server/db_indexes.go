@@ -0,0 +1,104 @@
+package main
+
+import (
+	"database/sql"
+	"fmt"
+	"strings"
+	"time"
+)
+
+// ============================================================================
+// History Indexes and Timestamp Format
+// ============================================================================
+//
+// History queries filter on server_id and a time column, which the
+// (server_id, time) indexes below serve. The time columns are TEXT, so range
+// filters only work (and use the index) while every value has the same
+// format: UTC RFC3339 with a "Z" suffix and no fractional seconds, as
+// written by storeTimestamp. Older builds wrote the agent's own UTC offset
+// when it sent one; those rows are rewritten once on upgrade.
+
+// historyIndexes are the (server_id, time) indexes of the history tables
+var historyIndexes = []struct {
+	name, table, column string
+}{
+	{"idx_metrics_raw_server_time", "metrics_raw", "timestamp"},
+	{"idx_metrics_hourly_server_time", "metrics_hourly", "hour_start"},
+	{"idx_metrics_daily_server_time", "metrics_daily", "date"},
+}
+
+// timestampTables hold sample timestamps copied from the agent
+var timestampTables = []string{"metrics_raw", "ping_raw", "metrics_disk", "metrics_ping"}
+
+// storeTimestamp formats a sample time the way history tables store it
+func storeTimestamp(t time.Time) string {
+	return t.UTC().Format(time.RFC3339)
+}
+
+// migrateHistoryIndexes creates missing history indexes and, for databases
+// from before schema version 3, normalizes stored timestamps to UTC
+func migrateHistoryIndexes(db *sql.DB) {
+	for _, idx := range historyIndexes {
+		db.Exec(fmt.Sprintf("CREATE INDEX IF NOT EXISTS %s ON %s(server_id, %s)", idx.name, idx.table, idx.column))
+	}
+
+	var version int
+	if err := db.QueryRow("PRAGMA user_version").Scan(&version); err != nil || version >= 3 {
+		return
+	}
+	for _, table := range timestampTables {
+		// strftime converts "+hh:mm" offsets to UTC and drops fractions
+		result, err := db.Exec(fmt.Sprintf(`
+			UPDATE %s SET timestamp = strftime('%%Y-%%m-%%dT%%H:%%M:%%SZ', timestamp)
+			WHERE timestamp NOT GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9]Z'
+			AND strftime('%%s', timestamp) IS NOT NULL`, table))
+		if err != nil {
+			continue
+		}
+		if n, _ := result.RowsAffected(); n > 0 {
+			fmt.Printf("🕒 Normalized %d timestamps in %s to UTC\n", n, table)
+		}
+	}
+}
+
+// historyPlanQueries are representative history range queries, with the
+// index each is expected to use and its time column
+var historyPlanQueries = []struct {
+	index, column string
+	query         string
+}{
+	{"idx_metrics_raw_server_time", "timestamp", "SELECT cpu_usage FROM metrics_raw WHERE server_id = ? AND timestamp >= ? AND timestamp < ?"},
+	{"idx_metrics_hourly_server_time", "hour_start", "SELECT cpu_avg FROM metrics_hourly WHERE server_id = ? AND hour_start >= ?"},
+	{"idx_metrics_daily_server_time", "date", "SELECT cpu_avg FROM metrics_daily WHERE server_id = ? AND date >= ?"},
+}
+
+// checkHistoryQueryPlans runs EXPLAIN QUERY PLAN on the history queries and
+// returns those that do not search an index on (server_id, time), e.g.
+// because the index was dropped or the planner prefers a full scan. The
+// UNIQUE constraints of metrics_hourly and metrics_daily serve as well as
+// the named indexes, so the plan is matched on the search, not the name.
+func checkHistoryQueryPlans(db *sql.DB) []string {
+	var problems []string
+	for _, q := range historyPlanQueries {
+		args := make([]interface{}, strings.Count(q.query, "?"))
+		rows, err := db.Query("EXPLAIN QUERY PLAN "+q.query, args...)
+		if err != nil {
+			problems = append(problems, fmt.Sprintf("%s: %v", q.index, err))
+			continue
+		}
+		var plan []string
+		for rows.Next() {
+			var id, parent, notused int
+			var detail string
+			if rows.Scan(&id, &parent, &notused, &detail) == nil {
+				plan = append(plan, detail)
+			}
+		}
+		rows.Close()
+		joined := strings.Join(plan, "; ")
+		if !strings.Contains(joined, "INDEX") || !strings.Contains(joined, "(server_id=? AND "+q.column+">") {
+			problems = append(problems, fmt.Sprintf("%s not used: %s", q.index, joined))
+		}
+	}
+	return problems
+}
@@ -0,0 +1,88 @@
+package main
+
+import (
+	"fmt"
+	"testing"
+	"time"
+)
+
+func TestHistoryQueryPlans(t *testing.T) {
+	db := openTestDB(t)
+	if problems := checkHistoryQueryPlans(db); len(problems) > 0 {
+		t.Errorf("empty database: %v", problems)
+	}
+
+	// With a few servers' worth of rows and fresh statistics the planner
+	// still searches the index rather than scanning
+	tx, err := db.Begin()
+	if err != nil {
+		t.Fatal(err)
+	}
+	start := time.Date(2026, 1, 1, 0, 0, 0, 0, time.UTC)
+	for s := 0; s < 20; s++ {
+		serverID := fmt.Sprintf("s%d", s)
+		for i := 0; i < 1000; i++ {
+			ts := start.Add(time.Duration(i) * time.Minute)
+			tx.Exec(`INSERT INTO metrics_raw (server_id, timestamp, cpu_usage, memory_usage, disk_usage, net_rx, net_tx, load_1, load_5, load_15)
+				VALUES (?, ?, 1, 1, 1, 0, 0, 0, 0, 0)`, serverID, storeTimestamp(ts))
+			if i%60 == 0 {
+				tx.Exec(`INSERT INTO metrics_hourly (server_id, hour_start, cpu_avg, cpu_max, memory_avg, memory_max, disk_avg, net_rx_total, net_tx_total, sample_count)
+					VALUES (?, ?, 1, 1, 1, 1, 1, 0, 0, 60)`, serverID, storeTimestamp(ts))
+			}
+		}
+	}
+	if err := tx.Commit(); err != nil {
+		t.Fatal(err)
+	}
+	if n := countRows(t, db, "metrics_raw"); n != 20000 {
+		t.Fatalf("%d raw rows", n)
+	}
+	execAll(t, db, "ANALYZE")
+	if problems := checkHistoryQueryPlans(db); len(problems) > 0 {
+		t.Errorf("populated database: %v", problems)
+	}
+
+	// A database whose index went missing gets it back
+	execAll(t, db, "DROP INDEX idx_metrics_raw_server_time")
+	if problems := checkHistoryQueryPlans(db); len(problems) != 1 {
+		t.Errorf("without the raw index: %v", problems)
+	}
+	migrateHistoryIndexes(db)
+	if problems := checkHistoryQueryPlans(db); len(problems) > 0 {
+		t.Errorf("after migration: %v", problems)
+	}
+}
+
+func TestNormalizeHistoryTimestamps(t *testing.T) {
+	db := openTestDB(t)
+	if got := storeTimestamp(time.Date(2026, 1, 1, 9, 30, 0, 500, time.FixedZone("", 2*3600))); got != "2026-01-01T07:30:00Z" {
+		t.Errorf("storeTimestamp = %s", got)
+	}
+
+	// Rows from a build that kept the agent's offset and fractions
+	execAll(t, db,
+		`INSERT INTO metrics_raw (server_id, timestamp, cpu_usage, memory_usage, disk_usage, net_rx, net_tx, load_1, load_5, load_15)
+			VALUES ('s1', '2026-01-01T09:30:00+02:00', 1, 1, 1, 0, 0, 0, 0, 0),
+			('s1', '2026-01-01T07:31:00.250Z', 1, 1, 1, 0, 0, 0, 0, 0),
+			('s1', '2026-01-01T07:32:00Z', 1, 1, 1, 0, 0, 0, 0, 0)`,
+		"PRAGMA user_version = 2")
+	migrateHistoryIndexes(db)
+
+	// They now sort, and range filter, as the instants they are
+	rows, err := db.Query(`SELECT timestamp FROM metrics_raw WHERE server_id = 's1' AND timestamp >= ? ORDER BY timestamp`,
+		"2026-01-01T07:30:00Z")
+	if err != nil {
+		t.Fatal(err)
+	}
+	var got []string
+	for rows.Next() {
+		var ts string
+		rows.Scan(&ts)
+		got = append(got, ts)
+	}
+	rows.Close()
+	want := []string{"2026-01-01T07:30:00Z", "2026-01-01T07:31:00Z", "2026-01-01T07:32:00Z"}
+	if fmt.Sprint(got) != fmt.Sprint(want) {
+		t.Errorf("timestamps = %v, want %v", got, want)
+	}
+}
@@ -0,0 +1,137 @@
+package main
+
+import (
+	"database/sql"
+	"fmt"
+	"os"
+	"strings"
+	"time"
+)
+
+// ============================================================================
+// Startup Integrity Check and Pre-Migration Backup
+// ============================================================================
+//
+// Before the database is opened for real it gets a PRAGMA quick_check. A
+// corrupt file (e.g. after a power loss) is moved aside together with its
+// WAL, timestamped, and the server starts with a fresh database instead of
+// failing. The schema version is kept in PRAGMA user_version: when this build
+// opens a database with an older version, it is first copied to
+// <db>.bak-<server version>, so a failed migration can be undone by hand.
+
+// schemaVersion must be bumped by every change that adds tables, columns or
+// indexes, so existing databases are backed up before the change applies
+const schemaVersion = 3
+
+// checkDatabaseIntegrity runs PRAGMA quick_check on the database file and
+// returns the problems found, none if it is healthy
+func checkDatabaseIntegrity(path string) ([]string, error) {
+	db, err := sql.Open("sqlite", path)
+	if err != nil {
+		return nil, err
+	}
+	defer db.Close()
+
+	rows, err := db.Query("PRAGMA quick_check")
+	if err != nil {
+		if isCorruptionError(err) {
+			return []string{err.Error()}, nil
+		}
+		return nil, err
+	}
+	defer rows.Close()
+
+	var problems []string
+	for rows.Next() {
+		var line string
+		if err := rows.Scan(&line); err != nil {
+			continue
+		}
+		if line != "ok" {
+			problems = append(problems, line)
+		}
+	}
+	if err := rows.Err(); err != nil {
+		if isCorruptionError(err) {
+			return append(problems, err.Error()), nil
+		}
+		return nil, err
+	}
+	return problems, nil
+}
+
+// isCorruptionError reports whether err is SQLite refusing a damaged file,
+// as opposed to e.g. a permission problem
+func isCorruptionError(err error) bool {
+	msg := err.Error()
+	return strings.Contains(msg, "malformed") || strings.Contains(msg, "not a database")
+}
+
+// quarantineCorruptDatabase checks the database file and, if it is corrupt,
+// renames it and its WAL files aside so a fresh database is created
+func quarantineCorruptDatabase(path string) error {
+	if !fileExists(path) {
+		return nil
+	}
+	problems, err := checkDatabaseIntegrity(path)
+	if err != nil {
+		return fmt.Errorf("failed to check database integrity: %w", err)
+	}
+	if len(problems) == 0 {
+		return nil
+	}
+
+	suffix := ".corrupt-" + time.Now().Format("20060102-150405")
+	if err := os.Rename(path, path+suffix); err != nil {
+		return fmt.Errorf("database is corrupt and could not be moved aside: %w", err)
+	}
+	for _, ext := range []string{"-wal", "-shm"} {
+		if fileExists(path + ext) {
+			os.Rename(path+ext, path+suffix+ext)
+		}
+	}
+
+	fmt.Println("╔════════════════════════════════════════════════════════════════╗")
+	fmt.Println("║  ❌ DATABASE CORRUPTION DETECTED                               ║")
+	fmt.Println("╚════════════════════════════════════════════════════════════════╝")
+	for i, line := range problems {
+		if i == 10 {
+			fmt.Printf("   ... and %d more\n", len(problems)-i)
+			break
+		}
+		fmt.Printf("   %s\n", line)
+	}
+	fmt.Printf("   Moved the damaged database to %s\n", path+suffix)
+	fmt.Println("   Starting with a fresh database; history before now is not available.")
+	return nil
+}
+
+// backupBeforeMigration copies a database with an older schema version
+// before this build migrates it
+func backupBeforeMigration(db *sql.DB, path string) error {
+	var version, tables int
+	if err := db.QueryRow("PRAGMA user_version").Scan(&version); err != nil {
+		return err
+	}
+	if err := db.QueryRow("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'").Scan(&tables); err != nil {
+		return err
+	}
+	// Nothing to lose in a new database
+	if version >= schemaVersion || tables == 0 {
+		return nil
+	}
+
+	backup := path + ".bak-" + ServerVersion
+	os.Remove(backup)
+	// VACUUM INTO includes what is still in the WAL, unlike copying the file
+	if _, err := db.Exec("VACUUM INTO ?", backup); err != nil {
+		return fmt.Errorf("failed to back up database before migration to %s: %w", backup, err)
+	}
+	fmt.Printf("💾 Backed up database (schema %d → %d) to %s\n", version, schemaVersion, backup)
+	return nil
+}
+
+// setSchemaVersion records that the database has the current schema
+func setSchemaVersion(db *sql.DB) {
+	db.Exec(fmt.Sprintf("PRAGMA user_version = %d", schemaVersion))
+}
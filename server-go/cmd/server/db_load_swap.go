@@ -0,0 +1,80 @@
+package main
+
+import (
+	"database/sql"
+	"fmt"
+)
+
+// ============================================================================
+// Load Average and Swap History
+// ============================================================================
+//
+// metrics_raw stores load_1/load_5/load_15 and swap_usage per sample, and
+// the 5-second and 2-minute tables sum them over load_count samples. The
+// 15-min, hourly and daily tables get averages from metrics_raw along with
+// the percentiles (see db_percentiles.go). Rows stored before these columns
+// existed have a load_count of 0 or NULL averages, and history returns null
+// for them rather than zero.
+
+// migrateLoadSwapColumns adds the load/swap columns to existing databases
+func migrateLoadSwapColumns(db *sql.DB) {
+	db.Exec("ALTER TABLE metrics_raw ADD COLUMN swap_usage REAL")
+	for _, table := range []string{"metrics_5sec", "metrics_2min"} {
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN load_1_sum REAL NOT NULL DEFAULT 0", table))
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN load_5_sum REAL NOT NULL DEFAULT 0", table))
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN load_15_sum REAL NOT NULL DEFAULT 0", table))
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN swap_sum REAL NOT NULL DEFAULT 0", table))
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN load_count INTEGER NOT NULL DEFAULT 0", table))
+	}
+	for _, table := range []string{"metrics_15min_agg", "metrics_hourly_agg", "metrics_daily_agg", "metrics_hourly", "metrics_daily"} {
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN load_1_avg REAL", table))
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN load_5_avg REAL", table))
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN load_15_avg REAL", table))
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN swap_avg REAL", table))
+	}
+}
+
+// swapUsagePercent returns the swap usage of a sample, 0 without swap
+func swapUsagePercent(m *SystemMetrics) float64 {
+	if m.Memory.SwapTotal == 0 {
+		return 0
+	}
+	return float64(m.Memory.SwapUsed) / float64(m.Memory.SwapTotal) * 100
+}
+
+// historyLoadSwapColumns are the load_1, load_5, load_15 and swap
+// expressions appended to the history queries, per kind of source table.
+// They are averaged whatever the stat.
+var historyLoadSwapColumns = []string{
+	"{load_swap_bucket}", "CASE WHEN load_count > 0 THEN load_1_sum / load_count END, " +
+		"CASE WHEN load_count > 0 THEN load_5_sum / load_count END, " +
+		"CASE WHEN load_count > 0 THEN load_15_sum / load_count END, " +
+		"CASE WHEN load_count > 0 THEN swap_sum / load_count END",
+	"{load_swap_bucket_group}", "CASE WHEN SUM(load_count) > 0 THEN SUM(load_1_sum) / SUM(load_count) END, " +
+		"CASE WHEN SUM(load_count) > 0 THEN SUM(load_5_sum) / SUM(load_count) END, " +
+		"CASE WHEN SUM(load_count) > 0 THEN SUM(load_15_sum) / SUM(load_count) END, " +
+		"CASE WHEN SUM(load_count) > 0 THEN SUM(swap_sum) / SUM(load_count) END",
+	"{load_swap_rollup}", "load_1_avg, load_5_avg, load_15_avg, swap_avg",
+	"{load_swap_rollup_group}", "AVG(load_1_avg), AVG(load_5_avg), AVG(load_15_avg), AVG(swap_avg)",
+	"{load_swap_raw_group}", "AVG(load_1), AVG(load_5), AVG(load_15), AVG(swap_usage)",
+}
+
+// historyLoadSwap receives the load/swap columns of a history row
+type historyLoadSwap struct {
+	load1, load5, load15, swap sql.NullFloat64
+}
+
+func (l *historyLoadSwap) dest() []interface{} {
+	return []interface{}{&l.load1, &l.load5, &l.load15, &l.swap}
+}
+
+// apply sets the load and swap of a point, leaving them nil without data
+func (l *historyLoadSwap) apply(point *HistoryPoint) {
+	if l.load1.Valid {
+		point.Load = &LoadAverage{One: l.load1.Float64, Five: l.load5.Float64, Fifteen: l.load15.Float64}
+	}
+	if l.swap.Valid {
+		swap := float32(l.swap.Float64)
+		point.Swap = &swap
+	}
+}
@@ -0,0 +1,163 @@
+package main
+
+import (
+	"database/sql"
+	"fmt"
+	"os"
+	"sync/atomic"
+	"time"
+)
+
+// ============================================================================
+// Database Maintenance (integrity check + VACUUM + ANALYZE)
+// ============================================================================
+
+// MaintenanceInterval is how often the background maintenance task runs
+const MaintenanceInterval = 7 * 24 * time.Hour
+
+// maintenanceHour is the local hour during which scheduled maintenance may run.
+// Early morning is usually the quietest time for dashboards.
+const maintenanceHour = 4
+
+// MaintenanceResult describes the outcome of a maintenance run
+type MaintenanceResult struct {
+	IntegrityOK     bool     `json:"integrity_ok"`
+	IntegrityErrors []string `json:"integrity_errors,omitempty"`
+	SizeBefore      int64    `json:"size_before"`
+	SizeAfter       int64    `json:"size_after"`
+	DurationMs      int64    `json:"duration_ms"`
+	StartedAt       string   `json:"started_at"`
+}
+
+// ErrMaintenanceRunning is returned when a maintenance run is already in progress
+var ErrMaintenanceRunning = fmt.Errorf("database maintenance already running")
+
+var maintenanceRunning atomic.Bool
+
+// RunDatabaseMaintenance runs an integrity check, a full VACUUM and refreshes
+// the query planner statistics.
+// The work is queued on the DB writer so it never interleaves with metric
+// writes; pending writes simply wait until the VACUUM finishes.
+func RunDatabaseMaintenance(db *sql.DB) (*MaintenanceResult, error) {
+	if !maintenanceRunning.CompareAndSwap(false, true) {
+		return nil, ErrMaintenanceRunning
+	}
+	defer maintenanceRunning.Store(false)
+
+	// Flush buffered metrics first so they are not held up behind the VACUUM
+	if metricsBuffer != nil {
+		metricsBuffer.Flush()
+	}
+	if aggBuffer != nil {
+		aggBuffer.Flush()
+	}
+
+	var result *MaintenanceResult
+	run := func(db *sql.DB) error {
+		var err error
+		result, err = databaseMaintenanceInternal(db)
+		return err
+	}
+
+	var err error
+	if dbWriter != nil {
+		err = dbWriter.WriteSync(run)
+	} else {
+		err = run(db)
+	}
+	return result, err
+}
+
+func databaseMaintenanceInternal(db *sql.DB) (*MaintenanceResult, error) {
+	start := time.Now()
+	result := &MaintenanceResult{
+		StartedAt:  start.UTC().Format(time.RFC3339),
+		SizeBefore: databaseFileSize(),
+	}
+
+	// Integrity check
+	rows, err := db.Query("PRAGMA integrity_check")
+	if err != nil {
+		return nil, fmt.Errorf("integrity check failed: %w", err)
+	}
+	for rows.Next() {
+		var line string
+		if err := rows.Scan(&line); err != nil {
+			continue
+		}
+		if line != "ok" {
+			result.IntegrityErrors = append(result.IntegrityErrors, line)
+		}
+	}
+	rows.Close()
+	result.IntegrityOK = len(result.IntegrityErrors) == 0
+
+	if !result.IntegrityOK {
+		fmt.Println("╔════════════════════════════════════════════════════════════════╗")
+		fmt.Println("║  ❌ DATABASE INTEGRITY CHECK FAILED                            ║")
+		fmt.Println("╚════════════════════════════════════════════════════════════════╝")
+		for _, line := range result.IntegrityErrors {
+			fmt.Printf("   %s\n", line)
+		}
+		fmt.Printf("   Database: %s\n", GetDBPath())
+		fmt.Println("   Skipping VACUUM; back up the database before attempting repair.")
+		result.SizeAfter = result.SizeBefore
+		result.DurationMs = time.Since(start).Milliseconds()
+		return result, nil
+	}
+
+	// Rebuild the database file to release free pages back to the filesystem
+	if _, err := db.Exec("VACUUM"); err != nil {
+		return nil, fmt.Errorf("vacuum failed: %w", err)
+	}
+
+	// Truncate the WAL so the freed space is actually returned
+	db.Exec("PRAGMA wal_checkpoint(TRUNCATE)")
+
+	// The rebuilt tables and indexes need fresh statistics
+	if _, err := db.Exec("ANALYZE"); err != nil {
+		return nil, fmt.Errorf("analyze failed: %w", err)
+	}
+	db.Exec("PRAGMA optimize")
+
+	result.SizeAfter = databaseFileSize()
+	result.DurationMs = time.Since(start).Milliseconds()
+	return result, nil
+}
+
+// databaseFileSize returns the combined size of the database and its WAL file
+func databaseFileSize() int64 {
+	var total int64
+	path := GetDBPath()
+	for _, p := range []string{path, path + "-wal"} {
+		if info, err := os.Stat(p); err == nil {
+			total += info.Size()
+		}
+	}
+	return total
+}
+
+// maintenanceLoop runs database maintenance weekly during the quiet hour
+func maintenanceLoop(db *sql.DB) {
+	ticker := time.NewTicker(1 * time.Hour)
+	defer ticker.Stop()
+
+	lastRun := time.Now()
+	for now := range ticker.C {
+		if now.Sub(lastRun) < MaintenanceInterval || now.Hour() != maintenanceHour {
+			continue
+		}
+		lastRun = now
+
+		fmt.Println("🧹 Running scheduled database maintenance...")
+		result, err := RunDatabaseMaintenance(db)
+		if err != nil {
+			fmt.Printf("Failed to run database maintenance: %v\n", err)
+			continue
+		}
+		if result.IntegrityOK {
+			fmt.Printf("🧹 Database maintenance done in %dms (%s → %s)\n",
+				result.DurationMs, FormatBytes(uint64(result.SizeBefore)), FormatBytes(uint64(result.SizeAfter)))
+		}
+	}
+}
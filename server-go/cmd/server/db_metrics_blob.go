@@ -0,0 +1,174 @@
+package main
+
+import (
+	"bytes"
+	"compress/flate"
+	"database/sql"
+	"encoding/json"
+	"fmt"
+	"io"
+	"sync"
+	"sync/atomic"
+	"time"
+)
+
+// ============================================================================
+// Stored Metrics Snapshots
+// ============================================================================
+//
+// With metrics_blobs enabled, each metrics_raw row also keeps the sample's
+// full metrics (per-core CPU, every disk and interface, ping targets,
+// services...) as compressed JSON in metrics_blob, served by
+// /api/history/:server_id?type=detail. The numeric columns stay as they are
+// for the aggregation and history queries. Blobs are DEFLATE from the
+// standard library rather than zstd, which would add a module dependency;
+// the first byte names the encoding so another can be added later. They
+// follow metrics_raw's 24-hour retention and raw_sample_interval_secs, and
+// are left out of exports.
+
+const (
+	metricsBlobDeflate = 1 // DEFLATE-compressed JSON
+
+	// DefaultMetricsBlobLevel is the DEFLATE level when none is configured
+	DefaultMetricsBlobLevel = 6
+)
+
+// MetricsBlobSettings configure the stored snapshots
+type MetricsBlobSettings struct {
+	Enabled bool `json:"enabled"`
+	Level   int  `json:"level,omitempty"` // DEFLATE level 1-9 (default: 6)
+}
+
+// Validate checks the settings
+func (s *MetricsBlobSettings) Validate() error {
+	if s == nil || !s.Enabled || s.Level == 0 {
+		return nil
+	}
+	if s.Level < flate.BestSpeed || s.Level > flate.BestCompression {
+		return fmt.Errorf("metrics_blobs.level must be between 1 and 9")
+	}
+	return nil
+}
+
+// metricsBlobLevel is the configured DEFLATE level, 0 while disabled
+var metricsBlobLevel atomic.Int32
+
+// metricsBlobWriters reuses compressors, which are costly to allocate, per
+// level
+var metricsBlobWriters [flate.BestCompression + 1]sync.Pool
+
+// SetMetricsBlobs enables or disables stored snapshots
+func SetMetricsBlobs(settings *MetricsBlobSettings) {
+	level := 0
+	if settings != nil && settings.Enabled {
+		level = DefaultMetricsBlobLevel
+		if settings.Level != 0 && settings.Validate() == nil {
+			level = settings.Level
+		}
+	}
+	metricsBlobLevel.Store(int32(level))
+}
+
+// migrateMetricsBlobColumn adds the snapshot column to existing databases
+func migrateMetricsBlobColumn(db *sql.DB) {
+	db.Exec("ALTER TABLE metrics_raw ADD COLUMN metrics_blob BLOB")
+}
+
+// encodeMetricsBlob returns the compressed snapshot of a sample, or nil
+// while snapshots are disabled
+func encodeMetricsBlob(metrics *SystemMetrics) []byte {
+	level := int(metricsBlobLevel.Load())
+	if level == 0 {
+		return nil
+	}
+	data, err := json.Marshal(metrics)
+	if err != nil {
+		return nil
+	}
+
+	var buf bytes.Buffer
+	buf.WriteByte(metricsBlobDeflate)
+	w, _ := metricsBlobWriters[level].Get().(*flate.Writer)
+	if w == nil {
+		w, _ = flate.NewWriter(&buf, level)
+	} else {
+		w.Reset(&buf)
+	}
+	defer metricsBlobWriters[level].Put(w)
+	if _, err := w.Write(data); err != nil {
+		return nil
+	}
+	if err := w.Close(); err != nil {
+		return nil
+	}
+	return buf.Bytes()
+}
+
+// decodeMetricsBlob reverses encodeMetricsBlob
+func decodeMetricsBlob(blob []byte) (*SystemMetrics, error) {
+	if len(blob) == 0 || blob[0] != metricsBlobDeflate {
+		return nil, fmt.Errorf("unknown metrics blob encoding")
+	}
+	r := flate.NewReader(bytes.NewReader(blob[1:]))
+	defer r.Close()
+	data, err := io.ReadAll(r)
+	if err != nil {
+		return nil, err
+	}
+	var metrics SystemMetrics
+	if err := json.Unmarshal(data, &metrics); err != nil {
+		return nil, err
+	}
+	return &metrics, nil
+}
+
+// GetHistorySamples returns up to limit of a server's stored snapshots since
+// `since`, and before `before` if set, oldest first. With a full page it also
+// returns the cursor of the next, older page. Rows stored without a snapshot
+// are skipped.
+func GetHistorySamples(db *sql.DB, serverID string, since time.Time, before string, limit int) ([]HistorySample, string, error) {
+	query := `SELECT timestamp, metrics_blob FROM metrics_raw
+		WHERE server_id = ? AND timestamp >= ? AND metrics_blob IS NOT NULL`
+	args := []interface{}{serverID, storeTimestamp(since)}
+	if before != "" {
+		query += ` AND timestamp < ?`
+		args = append(args, before)
+	}
+	query += ` ORDER BY timestamp DESC LIMIT ?`
+	args = append(args, limit)
+
+	rows, err := db.Query(query, args...)
+	if err != nil {
+		return nil, "", err
+	}
+	defer rows.Close()
+
+	samples := []HistorySample{}
+	var scanned int
+	var oldest string
+	for rows.Next() {
+		var sample HistorySample
+		var blob []byte
+		if err := rows.Scan(&sample.Timestamp, &blob); err != nil {
+			return nil, "", err
+		}
+		scanned++
+		oldest = sample.Timestamp
+		if sample.Metrics, err = decodeMetricsBlob(blob); err != nil {
+			continue
+		}
+		samples = append(samples, sample)
+	}
+	if err := rows.Err(); err != nil {
+		return nil, "", err
+	}
+
+	for i, j := 0, len(samples)-1; i < j; i, j = i+1, j-1 {
+		samples[i], samples[j] = samples[j], samples[i]
+	}
+	var next string
+	if scanned == limit {
+		next = oldest
+	}
+	return samples, next, nil
+}
@@ -0,0 +1,68 @@
+package main
+
+import (
+	"encoding/json"
+	"testing"
+	"time"
+)
+
+func TestMetricsBlobs(t *testing.T) {
+	db := openTestDB(t)
+	t.Cleanup(func() { SetMetricsBlobs(nil) })
+	start := time.Now().UTC().Add(-10 * time.Minute).Truncate(time.Second)
+	sample := func(i int) *SystemMetrics {
+		m := &SystemMetrics{Timestamp: start.Add(time.Duration(i) * time.Second), Hostname: "web-1"}
+		m.CPU.Usage = float32(i)
+		m.CPU.PerCore = []float32{float32(i), 50, 50, 50}
+		m.Disks = []DiskMetrics{{Name: "sda", MountPoints: []string{"/"}, UsagePercent: 40}, {Name: "sdb", MountPoints: []string{"/data"}, UsagePercent: 70}}
+		return m
+	}
+
+	// Off by default: only the numeric columns are written
+	if err := batchStoreMetrics(db, []MetricsBufferItem{{ServerID: "s1", Metrics: sample(0)}}); err != nil {
+		t.Fatal(err)
+	}
+	SetMetricsBlobs(&MetricsBlobSettings{Enabled: true, Level: 9})
+	var items []MetricsBufferItem
+	for i := 1; i <= 5; i++ {
+		items = append(items, MetricsBufferItem{ServerID: "s1", Metrics: sample(i)})
+	}
+	if err := batchStoreMetrics(db, items); err != nil {
+		t.Fatal(err)
+	}
+	if err := storeMetricsInternal(db, "s1", sample(6)); err != nil {
+		t.Fatal(err)
+	}
+
+	var rows, blobs, blobSize int
+	if err := db.QueryRow(`SELECT COUNT(*), COUNT(metrics_blob), MAX(LENGTH(metrics_blob)) FROM metrics_raw WHERE server_id = 's1'`).
+		Scan(&rows, &blobs, &blobSize); err != nil {
+		t.Fatal(err)
+	}
+	full, _ := json.Marshal(sample(6))
+	if rows != 7 || blobs != 6 || blobSize >= len(full) {
+		t.Errorf("%d rows, %d blobs of up to %d bytes (JSON is %d)", rows, blobs, blobSize, len(full))
+	}
+	if n := countRows(t, db, "metrics_raw WHERE server_id = 's1' AND cpu_usage = 6"); n != 1 {
+		t.Errorf("numeric columns not written: %d", n)
+	}
+
+	// Read back in pages, oldest first within each
+	samples, next, err := GetHistorySamples(db, "s1", start.Add(-time.Hour), "", 4)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if len(samples) != 4 || samples[0].Metrics.CPU.Usage != 3 || samples[3].Metrics.CPU.Usage != 6 || next != samples[0].Timestamp {
+		t.Fatalf("first page: %d samples, next %q", len(samples), next)
+	}
+	if m := samples[3].Metrics; m.Hostname != "web-1" || len(m.CPU.PerCore) != 4 || len(m.Disks) != 2 || m.Disks[1].MountPoints[0] != "/data" {
+		t.Errorf("detail lost: %+v", m)
+	}
+	samples, next, err = GetHistorySamples(db, "s1", start.Add(-time.Hour), next, 4)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if len(samples) != 2 || samples[0].Metrics.CPU.Usage != 1 || next != "" {
+		t.Errorf("last page: %d samples, next %q", len(samples), next)
+	}
+}
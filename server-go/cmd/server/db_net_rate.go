@@ -0,0 +1,66 @@
+package main
+
+import (
+	"database/sql"
+	"fmt"
+)
+
+// ============================================================================
+// Network Throughput History
+// ============================================================================
+//
+// net_rx/net_tx in the metrics tables are the interface counters since boot,
+// which chart as a rising line that drops on every reboot. Alongside them the
+// server keeps the agent-computed rx_speed/tx_speed (bytes per second):
+// metrics_raw stores them per sample, the 5-second and 2-minute tables sum
+// them over net_rate_count samples, and the 15-min, hourly and daily tables
+// get averages from metrics_raw with the percentiles (see db_percentiles.go).
+// Rows stored before these columns existed have a net_rate_count of 0 or NULL
+// averages, and history returns null for them. Traffic totals for accounting
+// come from bandwidth_daily (see db_bandwidth.go), which handles counter
+// resets.
+
+// migrateNetRateColumns adds the network rate columns to existing databases
+func migrateNetRateColumns(db *sql.DB) {
+	db.Exec("ALTER TABLE metrics_raw ADD COLUMN net_rx_rate REAL")
+	db.Exec("ALTER TABLE metrics_raw ADD COLUMN net_tx_rate REAL")
+	for _, table := range []string{"metrics_5sec", "metrics_2min"} {
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN net_rx_rate_sum REAL NOT NULL DEFAULT 0", table))
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN net_tx_rate_sum REAL NOT NULL DEFAULT 0", table))
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN net_rate_count INTEGER NOT NULL DEFAULT 0", table))
+	}
+	for _, table := range []string{"metrics_15min_agg", "metrics_hourly_agg", "metrics_daily_agg", "metrics_hourly", "metrics_daily"} {
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN net_rx_rate_avg REAL", table))
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN net_tx_rate_avg REAL", table))
+	}
+}
+
+// historyNetRateColumns are the rx and tx rate expressions appended to the
+// history queries, per kind of source table. They are averaged whatever the
+// stat.
+var historyNetRateColumns = []string{
+	"{net_rate_bucket}", "CASE WHEN net_rate_count > 0 THEN net_rx_rate_sum / net_rate_count END, " +
+		"CASE WHEN net_rate_count > 0 THEN net_tx_rate_sum / net_rate_count END",
+	"{net_rate_bucket_group}", "CASE WHEN SUM(net_rate_count) > 0 THEN SUM(net_rx_rate_sum) / SUM(net_rate_count) END, " +
+		"CASE WHEN SUM(net_rate_count) > 0 THEN SUM(net_tx_rate_sum) / SUM(net_rate_count) END",
+	"{net_rate_rollup}", "net_rx_rate_avg, net_tx_rate_avg",
+	"{net_rate_rollup_group}", "AVG(net_rx_rate_avg), AVG(net_tx_rate_avg)",
+	"{net_rate_raw_group}", "AVG(net_rx_rate), AVG(net_tx_rate)",
+}
+
+// historyNetRate receives the rate columns of a history row
+type historyNetRate struct {
+	rx, tx sql.NullFloat64
+}
+
+func (r *historyNetRate) dest() []interface{} {
+	return []interface{}{&r.rx, &r.tx}
+}
+
+// apply sets the rates of a point, leaving them nil without data
+func (r *historyNetRate) apply(point *HistoryPoint) {
+	if r.rx.Valid {
+		rx, tx := r.rx.Float64, r.tx.Float64
+		point.NetRxRate, point.NetTxRate = &rx, &tx
+	}
+}
@@ -0,0 +1,166 @@
+package main
+
+import (
+	"database/sql"
+	"fmt"
+	"time"
+)
+
+// ============================================================================
+// Bucket Percentiles
+// ============================================================================
+//
+// Agents send sums and maxima per bucket, which cannot yield a percentile.
+// Once a 15-min, hourly or daily bucket closes, the server fills cpu_p95,
+// memory_p95 and ping_max from metrics_raw, along with the load, swap and
+// network rate averages agents don't aggregate. Raw data is kept for 24 hours,
+// so buckets that closed before the server saw them (or before this column
+// existed) keep NULL.
+
+// PercentileInterval is how often closed buckets are checked for percentiles
+const PercentileInterval = 15 * time.Minute
+
+// percentileTables are the agent-aggregated tables that carry percentiles
+var percentileTables = []struct {
+	name       string
+	bucketSecs int64
+}{
+	{"metrics_15min_agg", 900},
+	{"metrics_hourly_agg", 3600},
+	{"metrics_daily_agg", 86400},
+}
+
+// migratePercentileColumns adds the percentile columns to existing databases
+func migratePercentileColumns(db *sql.DB) {
+	for _, table := range []string{"metrics_15min_agg", "metrics_hourly_agg", "metrics_daily_agg", "metrics_hourly", "metrics_daily"} {
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN cpu_p95 REAL", table))
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN memory_p95 REAL", table))
+		db.Exec(fmt.Sprintf("ALTER TABLE %s ADD COLUMN ping_max REAL", table))
+	}
+}
+
+// bucketPercentiles holds the values computed from metrics_raw for a bucket
+type bucketPercentiles struct {
+	CPUP95    float64
+	MemoryP95 float64
+	PingMax   sql.NullFloat64
+	Load1Avg  sql.NullFloat64
+	Load5Avg  sql.NullFloat64
+	Load15Avg sql.NullFloat64
+	SwapAvg   sql.NullFloat64 // NULL if no sample recorded swap
+	RxRateAvg sql.NullFloat64
+	TxRateAvg sql.NullFloat64
+}
+
+// rawPercentiles computes p95 CPU/memory, max ping and average load/swap of
+// a server's raw samples in [start, end). It returns nil if there are none.
+func rawPercentiles(db *sql.DB, serverID string, start, end time.Time) (*bucketPercentiles, error) {
+	from, to := start.UTC().Format(time.RFC3339), end.UTC().Format(time.RFC3339)
+
+	var count int64
+	var p bucketPercentiles
+	err := db.QueryRow(`
+		SELECT COUNT(*), MAX(ping_ms), AVG(load_1), AVG(load_5), AVG(load_15), AVG(swap_usage), AVG(net_rx_rate), AVG(net_tx_rate)
+		FROM metrics_raw
+		WHERE server_id = ? AND timestamp >= ? AND timestamp < ?`,
+		serverID, from, to).Scan(&count, &p.PingMax, &p.Load1Avg, &p.Load5Avg, &p.Load15Avg, &p.SwapAvg, &p.RxRateAvg, &p.TxRateAvg)
+	if err != nil || count == 0 {
+		return nil, err
+	}
+
+	// Nearest-rank percentile: the ceil(0.95 * n)-th smallest sample
+	offset := (count*95+99)/100 - 1
+	for _, c := range []struct {
+		column string
+		dest   *float64
+	}{
+		{"cpu_usage", &p.CPUP95},
+		{"memory_usage", &p.MemoryP95},
+	} {
+		err := db.QueryRow(fmt.Sprintf(`
+			SELECT %[1]s FROM metrics_raw
+			WHERE server_id = ? AND timestamp >= ? AND timestamp < ?
+			ORDER BY %[1]s LIMIT 1 OFFSET ?`, c.column),
+			serverID, from, to, offset).Scan(c.dest)
+		if err != nil {
+			return nil, err
+		}
+	}
+	return &p, nil
+}
+
+// fillPercentiles sets the percentile columns of every row of table whose
+// keyColumn equals key and that has none yet, from raw samples in [start, end)
+func fillPercentiles(db *sql.DB, table, keyColumn string, key interface{}, start, end time.Time) error {
+	rows, err := db.Query(fmt.Sprintf(`SELECT server_id FROM %s WHERE %s = ? AND cpu_p95 IS NULL`, table, keyColumn), key)
+	if err != nil {
+		return err
+	}
+	var serverIDs []string
+	for rows.Next() {
+		var id string
+		if rows.Scan(&id) == nil {
+			serverIDs = append(serverIDs, id)
+		}
+	}
+	rows.Close()
+
+	for _, serverID := range serverIDs {
+		p, err := rawPercentiles(db, serverID, start, end)
+		if err != nil {
+			return err
+		}
+		if p == nil {
+			continue
+		}
+		if _, err := db.Exec(fmt.Sprintf(`UPDATE %s SET cpu_p95 = ?, memory_p95 = ?, ping_max = ?,
+			load_1_avg = ?, load_5_avg = ?, load_15_avg = ?, swap_avg = ?, net_rx_rate_avg = ?, net_tx_rate_avg = ?
+			WHERE server_id = ? AND %s = ?`, table, keyColumn),
+			p.CPUP95, p.MemoryP95, p.PingMax, p.Load1Avg, p.Load5Avg, p.Load15Avg, p.SwapAvg, p.RxRateAvg, p.TxRateAvg, serverID, key); err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
+func FillBucketPercentiles(db *sql.DB) error {
+	if dbWriter != nil {
+		return dbWriter.WriteSync(fillBucketPercentilesInternal)
+	}
+	return fillBucketPercentilesInternal(db)
+}
+
+// fillBucketPercentilesInternal fills closed buckets that still overlap the
+// raw retention window
+func fillBucketPercentilesInternal(db *sql.DB) error {
+	now := time.Now().UTC()
+	rawCutoff := now.Add(-24 * time.Hour).Unix()
+
+	for _, t := range percentileTables {
+		current := now.Unix() / t.bucketSecs
+		oldest := rawCutoff / t.bucketSecs
+
+		rows, err := db.Query(fmt.Sprintf(`SELECT DISTINCT bucket FROM %s WHERE bucket >= ? AND bucket < ? AND cpu_p95 IS NULL`, t.name),
+			oldest, current)
+		if err != nil {
+			return err
+		}
+		var buckets []int64
+		for rows.Next() {
+			var b int64
+			if rows.Scan(&b) == nil {
+				buckets = append(buckets, b)
+			}
+		}
+		rows.Close()
+
+		for _, b := range buckets {
+			start := time.Unix(b*t.bucketSecs, 0)
+			end := start.Add(time.Duration(t.bucketSecs) * time.Second)
+			if err := fillPercentiles(db, t.name, "bucket", b, start, end); err != nil {
+				return err
+			}
+		}
+	}
+	return nil
+}
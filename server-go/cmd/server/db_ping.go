@@ -0,0 +1,289 @@
+package main
+
+import (
+	"database/sql"
+	"fmt"
+	"net"
+	"strings"
+	"sync"
+	"time"
+)
+
+// ============================================================================
+// Per-Target Ping History
+// ============================================================================
+//
+// metrics_raw.ping_ms holds a single headline target (see headlinePing), and
+// the agent's ping aggregates carry no packet loss. metrics_ping keeps one row per target,
+// sampled every ping_history_interval_secs, for 8 days and rolls it up into
+// metrics_ping_hourly (avg/max latency and loss) for 400 days.
+
+const DefaultPingHistoryIntervalSecs = 30
+
+var pingSampler = &RawSampler{interval: DefaultPingHistoryIntervalSecs, last: make(map[string]int64)}
+
+// SetPingHistoryInterval sets the metrics_ping sampling interval in seconds
+func SetPingHistoryInterval(secs int) {
+	if secs < 1 {
+		secs = DefaultPingHistoryIntervalSecs
+	}
+	pingSampler.mu.Lock()
+	defer pingSampler.mu.Unlock()
+	pingSampler.interval = int64(secs)
+	pingSampler.last = make(map[string]int64)
+}
+
+// primaryPingTargets holds the primary_ping_target of the probe settings and
+// the per-server overrides
+var primaryPingTargets struct {
+	mu      sync.RWMutex
+	global  string
+	servers map[string]string
+}
+
+// SetPrimaryPingTargets records the configured primary ping targets
+func SetPrimaryPingTargets(config *AppConfig) {
+	servers := make(map[string]string)
+	for _, s := range config.Servers {
+		if s.PrimaryPingTarget != "" {
+			servers[s.ID] = s.PrimaryPingTarget
+		}
+	}
+	primaryPingTargets.mu.Lock()
+	primaryPingTargets.global = config.ProbeSettings.PrimaryPingTarget
+	primaryPingTargets.servers = servers
+	primaryPingTargets.mu.Unlock()
+}
+
+// headlinePing returns the latency that stands for a server's ping: the
+// server's primary_ping_target, else the global one, matched by name or host.
+// When that target is missing or has no result, the first non-gateway target
+// with a result is used, and a gateway only if nothing else answered.
+func headlinePing(serverID string, ping *PingMetrics) *float64 {
+	if ping == nil {
+		return nil
+	}
+	primaryPingTargets.mu.RLock()
+	primary := primaryPingTargets.servers[serverID]
+	if primary == "" {
+		primary = primaryPingTargets.global
+	}
+	primaryPingTargets.mu.RUnlock()
+
+	if primary != "" {
+		for _, t := range ping.Targets {
+			if (t.Name == primary || t.Host == primary) && t.LatencyMs != nil {
+				return t.LatencyMs
+			}
+		}
+	}
+	var gateway *float64
+	for _, t := range ping.Targets {
+		if t.LatencyMs == nil {
+			continue
+		}
+		if !isGatewayTarget(&t) {
+			return t.LatencyMs
+		}
+		if gateway == nil {
+			gateway = t.LatencyMs
+		}
+	}
+	return gateway
+}
+
+// headlineLatency returns the headline ping latency of a sample, if any
+func headlineLatency(serverID string, metrics *SystemMetrics) *float64 {
+	if metrics == nil {
+		return nil
+	}
+	return headlinePing(serverID, metrics.Ping)
+}
+
+// isGatewayTarget reports whether a ping target is on the local network,
+// i.e. named "gateway" or a private, loopback or link-local address
+func isGatewayTarget(t *PingTarget) bool {
+	if strings.EqualFold(t.Name, "gateway") {
+		return true
+	}
+	ip := net.ParseIP(t.Host)
+	return ip != nil && (ip.IsPrivate() || ip.IsLoopback() || ip.IsLinkLocalUnicast())
+}
+
+// createPingTables creates the per-target ping history tables
+func createPingTables(db *sql.DB) {
+	db.Exec(`
+		-- Per-target ping results, sampled every ping_history_interval_secs (keep 8 days)
+		CREATE TABLE IF NOT EXISTS metrics_ping (
+			server_id TEXT NOT NULL,
+			timestamp TEXT NOT NULL,
+			target_name TEXT NOT NULL,
+			target_host TEXT NOT NULL,
+			latency_ms REAL,
+			packet_loss REAL NOT NULL DEFAULT 0,
+			PRIMARY KEY (server_id, timestamp, target_name)
+		) WITHOUT ROWID;
+
+		CREATE INDEX IF NOT EXISTS idx_metrics_ping_target ON metrics_ping(server_id, target_name, timestamp);
+
+		-- Hourly per-target ping rollup (keep 400 days)
+		CREATE TABLE IF NOT EXISTS metrics_ping_hourly (
+			server_id TEXT NOT NULL,
+			hour_start TEXT NOT NULL,
+			target_name TEXT NOT NULL,
+			target_host TEXT NOT NULL,
+			latency_avg REAL,
+			latency_max REAL,
+			packet_loss_avg REAL NOT NULL DEFAULT 0,
+			sample_count INTEGER NOT NULL,
+			PRIMARY KEY (server_id, hour_start, target_name)
+		) WITHOUT ROWID;
+	`)
+}
+
+const insertPingRowSQL = `
+	INSERT OR REPLACE INTO metrics_ping (server_id, timestamp, target_name, target_host, latency_ms, packet_loss)
+	VALUES (?, ?, ?, ?, ?, ?)`
+
+// storePingRows writes the per-target rows of a sample through exec (a
+// prepared insertPingRowSQL statement or db.Exec) if its sampling window is new
+func storePingRows(exec func(args ...interface{}) (sql.Result, error), serverID string, metrics *SystemMetrics, timestamp string) {
+	if metrics.Ping == nil || len(metrics.Ping.Targets) == 0 || !pingSampler.ShouldStore(serverID, metrics.Timestamp) {
+		return
+	}
+	for _, t := range metrics.Ping.Targets {
+		exec(serverID, timestamp, t.Name, t.Host, t.LatencyMs, t.PacketLoss)
+	}
+}
+
+// aggregatePingHourly rolls the last day of metrics_ping up into
+// metrics_ping_hourly. The current hour is rewritten on the next run.
+func aggregatePingHourly(db *sql.DB) error {
+	since := time.Now().UTC().Add(-24 * time.Hour).Truncate(time.Hour).Format(time.RFC3339)
+	_, err := db.Exec(`
+		INSERT OR REPLACE INTO metrics_ping_hourly (server_id, hour_start, target_name, target_host, latency_avg, latency_max, packet_loss_avg, sample_count)
+		SELECT
+			server_id,
+			strftime('%Y-%m-%dT%H:00:00Z', timestamp) as hour,
+			target_name,
+			MAX(target_host),
+			AVG(latency_ms),
+			MAX(latency_ms),
+			AVG(packet_loss),
+			COUNT(*)
+		FROM metrics_ping
+		WHERE timestamp >= ?
+		GROUP BY server_id, hour, target_name`, since)
+	return err
+}
+
+// cleanupPingHistory deletes per-target ping rows past their retention
+func cleanupPingHistory(db *sql.DB) {
+	now := time.Now().UTC()
+	db.Exec("DELETE FROM metrics_ping WHERE timestamp < ?", now.Add(-8*24*time.Hour).Format(time.RFC3339))
+	db.Exec("DELETE FROM metrics_ping_hourly WHERE hour_start < ?", now.AddDate(0, 0, -400).Format(time.RFC3339))
+}
+
+// PingTargetPoint is a target's latency and loss over one bucket
+type PingTargetPoint struct {
+	Timestamp  string   `json:"timestamp"`
+	LatencyAvg *float64 `json:"latency_avg"` // nil if every probe failed
+	LatencyMax *float64 `json:"latency_max"`
+	PacketLoss float64  `json:"packet_loss"` // Percent
+}
+
+// PingTargetSeries is the history of one ping target
+type PingTargetSeries struct {
+	Name string            `json:"name"`
+	Host string            `json:"host"`
+	Data []PingTargetPoint `json:"data"`
+}
+
+type PingTargetHistoryResponse struct {
+	ServerID string             `json:"server_id"`
+	Range    string             `json:"range"`
+	Targets  []PingTargetSeries `json:"targets"`
+}
+
+// GetPingTargetHistory returns per-target latency and loss for a range,
+// optionally for a single target. 1h and 24h are grouped into fixed buckets
+// from metrics_ping; longer ranges read metrics_ping_hourly.
+func GetPingTargetHistory(db *sql.DB, serverID, rangeStr, target string) ([]PingTargetSeries, error) {
+	now := time.Now().UTC()
+	var query, groupBy string
+	var args []interface{}
+
+	switch rangeStr {
+	case "7d", "30d":
+		days := 7
+		if rangeStr == "30d" {
+			days = 30
+		}
+		query = `
+			SELECT target_name, target_host, hour_start, latency_avg, latency_max, packet_loss_avg
+			FROM metrics_ping_hourly
+			WHERE server_id = ? AND hour_start >= ?`
+		args = []interface{}{serverID, now.AddDate(0, 0, -days).Format(time.RFC3339)}
+	case "1y":
+		query = `
+			SELECT
+				target_name,
+				MAX(target_host),
+				date(hour_start) || 'T00:00:00Z',
+				SUM(latency_avg * sample_count) / SUM(CASE WHEN latency_avg IS NOT NULL THEN sample_count END),
+				MAX(latency_max),
+				SUM(packet_loss_avg * sample_count) / SUM(sample_count)
+			FROM metrics_ping_hourly
+			WHERE server_id = ? AND hour_start >= ?`
+		args = []interface{}{serverID, now.AddDate(0, 0, -365).Format(time.RFC3339)}
+		groupBy = " GROUP BY target_name, date(hour_start)"
+	default:
+		span, bucketSecs := 24*time.Hour, 600
+		if rangeStr == "1h" {
+			span, bucketSecs = time.Hour, 60
+		}
+		query = fmt.Sprintf(`
+			SELECT
+				target_name,
+				MAX(target_host),
+				strftime('%%Y-%%m-%%dT%%H:%%M:%%SZ', (strftime('%%s', timestamp) / %[1]d) * %[1]d, 'unixepoch'),
+				AVG(latency_ms),
+				MAX(latency_ms),
+				AVG(packet_loss)
+			FROM metrics_ping
+			WHERE server_id = ? AND timestamp >= ?`, bucketSecs)
+		args = []interface{}{serverID, now.Add(-span).Format(time.RFC3339)}
+		groupBy = fmt.Sprintf(" GROUP BY target_name, strftime('%%s', timestamp) / %d", bucketSecs)
+	}
+	if target != "" {
+		query += " AND target_name = ?"
+		args = append(args, target)
+	}
+	query += groupBy + " ORDER BY 1, 3"
+
+	rows, err := db.Query(query, args...)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	series := []PingTargetSeries{}
+	index := make(map[string]int)
+	for rows.Next() {
+		var name, host string
+		var p PingTargetPoint
+		if err := rows.Scan(&name, &host, &p.Timestamp, &p.LatencyAvg, &p.LatencyMax, &p.PacketLoss); err != nil {
+			continue
+		}
+		i, ok := index[name]
+		if !ok {
+			i = len(series)
+			index[name] = i
+			series = append(series, PingTargetSeries{Name: name})
+		}
+		// Report the most recent host if a target was repointed
+		series[i].Host = host
+		series[i].Data = append(series[i].Data, p)
+	}
+	return series, rows.Err()
+}
@@ -0,0 +1,140 @@
+package main
+
+import (
+	"database/sql"
+	"fmt"
+	"sync"
+	"time"
+)
+
+// ============================================================================
+// Per-Server Retention
+// ============================================================================
+//
+// Servers with retention_days keep no history older than that many days, in
+// any resolution. The global retention of each table still applies, so the
+// override can only shorten it: a year works because daily rollups are kept
+// for 400 days, but raw samples are gone after a day regardless. Rows are
+// deleted in batches, each its own write on the DB writer, so a large purge
+// does not hold the write lock for long.
+
+// retentionBatchRows bounds the rows deleted per statement
+const retentionBatchRows = 5000
+
+// retentionTables are the per-server history tables and their time columns
+var retentionTables = []storageTable{
+	{"metrics_raw", "timestamp", 0},
+	{"metrics_5sec", "bucket", 5},
+	{"metrics_2min", "bucket", 120},
+	{"metrics_15min_agg", "bucket", 900},
+	{"metrics_hourly_agg", "bucket", 3600},
+	{"metrics_daily_agg", "bucket", 86400},
+	{"metrics_15min", "bucket_start", 0},
+	{"metrics_hourly", "hour_start", 0},
+	{"metrics_daily", "date", 0},
+	{"ping_raw", "timestamp", 0},
+	{"ping_5sec", "bucket", 5},
+	{"ping_2min", "bucket", 120},
+	{"ping_15min_agg", "bucket", 900},
+	{"ping_hourly_agg", "bucket", 3600},
+	{"ping_daily_agg", "bucket", 86400},
+	{"ping_15min", "bucket_start", 0},
+	{"ping_hourly", "hour_start", 0},
+	{"ping_daily", "date", 0},
+	{"metrics_disk", "timestamp", 0},
+	{"metrics_disk_daily", "date", 0},
+	{"metrics_ping", "timestamp", 0},
+	{"metrics_ping_hourly", "hour_start", 0},
+	{"bandwidth_daily", "date", 0},
+}
+
+// cutoff returns the time column value of the instant at
+func (t storageTable) cutoff(at time.Time) interface{} {
+	switch {
+	case t.bucketSecs > 0:
+		return at.Unix() / int64(t.bucketSecs)
+	case t.timeColumn == "date":
+		return at.Format("2006-01-02")
+	default:
+		return at.Format(time.RFC3339)
+	}
+}
+
+// serverRetention maps server IDs to their retention_days override
+var serverRetention struct {
+	mu   sync.RWMutex
+	days map[string]int
+}
+
+// SetServerRetention records the retention_days overrides of the servers
+func SetServerRetention(servers []RemoteServer) {
+	days := make(map[string]int)
+	for _, s := range servers {
+		if s.RetentionDays > 0 {
+			days[s.ID] = s.RetentionDays
+		}
+	}
+	serverRetention.mu.Lock()
+	serverRetention.days = days
+	serverRetention.mu.Unlock()
+}
+
+// cleanupServerRetention deletes the history of servers with a retention
+// override that is older than the override
+func cleanupServerRetention(db *sql.DB) error {
+	serverRetention.mu.RLock()
+	days := make(map[string]int, len(serverRetention.days))
+	for id, d := range serverRetention.days {
+		days[id] = d
+	}
+	serverRetention.mu.RUnlock()
+
+	now := time.Now().UTC()
+	for serverID, d := range days {
+		before := now.AddDate(0, 0, -d)
+		for _, t := range retentionTables {
+			if _, err := deleteServerRowsBefore(db, t, serverID, before); err != nil {
+				return fmt.Errorf("failed to apply retention to %s: %w", t.name, err)
+			}
+		}
+	}
+	return nil
+}
+
+// deleteServerRowsBefore deletes a server's rows older than before from a
+// table, retentionBatchRows per write, and returns the rows deleted
+func deleteServerRowsBefore(db *sql.DB, t storageTable, serverID string, before time.Time) (int64, error) {
+	// Selecting time values rather than rowids also works for WITHOUT ROWID
+	// tables; rows sharing a time value are deleted together
+	query := fmt.Sprintf(`
+		DELETE FROM %[1]s WHERE server_id = ? AND %[2]s IN (
+			SELECT %[2]s FROM %[1]s WHERE server_id = ? AND %[2]s < ? LIMIT %[3]d
+		)`, t.name, t.timeColumn, retentionBatchRows)
+	cutoff := t.cutoff(before)
+
+	var total int64
+	for {
+		var n int64
+		run := func(db *sql.DB) error {
+			res, err := db.Exec(query, serverID, serverID, cutoff)
+			if err != nil {
+				return err
+			}
+			n, _ = res.RowsAffected()
+			return nil
+		}
+		var err error
+		if dbWriter != nil {
+			err = dbWriter.WriteSync(run)
+		} else {
+			err = run(db)
+		}
+		if err != nil {
+			return total, err
+		}
+		total += n
+		if n < retentionBatchRows {
+			return total, nil
+		}
+	}
+}
@@ -0,0 +1,115 @@
+package main
+
+import (
+	"database/sql"
+	"fmt"
+	"time"
+)
+
+// ============================================================================
+// Rollup Backfill
+// ============================================================================
+//
+// Hourly and daily buckets normally come from agents. When they never arrive,
+// e.g. the dashboard was down when the agent would have sent them, the
+// 30-day and 1-year charts get holes. The backfill rebuilds missing hourly
+// buckets from metrics_2min and missing daily buckets from metrics_hourly_agg
+// (pings likewise). It only inserts buckets that have no row yet, so it is
+// safe to run any number of times, and a bucket the agent sends later
+// replaces the rebuilt one. It runs at startup and before every percentile
+// pass, so rebuilt buckets get their percentiles too.
+
+// backfillHourlyWindow is how far back hourly buckets are rebuilt, within the
+// 26-hour retention of metrics_2min
+const backfillHourlyWindow = 25 * time.Hour
+
+// backfillDailyWindow is how far back daily buckets are rebuilt, within the
+// 32-day retention of metrics_hourly_agg
+const backfillDailyWindow = 31 * 24 * time.Hour
+
+func BackfillRollups(db *sql.DB) error {
+	if dbWriter != nil {
+		return dbWriter.WriteSync(backfillRollupsInternal)
+	}
+	return backfillRollupsInternal(db)
+}
+
+// backfillRollupsInternal inserts the closed hourly and daily buckets that
+// are missing but can be rebuilt from finer data
+func backfillRollupsInternal(db *sql.DB) error {
+	now := time.Now().UTC()
+
+	// Whole hours only: [fromHour, currentHour), in 2-minute buckets
+	fromHour := now.Add(-backfillHourlyWindow).Unix()/3600 + 1
+	currentHour := now.Unix() / 3600
+	hourly, err := execCount(db, `
+		INSERT OR IGNORE INTO metrics_hourly_agg (server_id, bucket, cpu_sum, cpu_max, memory_sum, memory_max, disk_sum,
+			net_rx, net_tx, ping_sum, ping_count, sample_count, load_1_avg, load_5_avg, load_15_avg, swap_avg,
+			net_rx_rate_avg, net_tx_rate_avg)
+		SELECT server_id, bucket / 30 AS hour,
+			SUM(cpu_sum), MAX(cpu_max), SUM(memory_sum), MAX(memory_max), SUM(disk_sum),
+			MAX(net_rx), MAX(net_tx), SUM(ping_sum), SUM(ping_count), SUM(sample_count),
+			CASE WHEN SUM(load_count) > 0 THEN SUM(load_1_sum) / SUM(load_count) END,
+			CASE WHEN SUM(load_count) > 0 THEN SUM(load_5_sum) / SUM(load_count) END,
+			CASE WHEN SUM(load_count) > 0 THEN SUM(load_15_sum) / SUM(load_count) END,
+			CASE WHEN SUM(load_count) > 0 THEN SUM(swap_sum) / SUM(load_count) END,
+			CASE WHEN SUM(net_rate_count) > 0 THEN SUM(net_rx_rate_sum) / SUM(net_rate_count) END,
+			CASE WHEN SUM(net_rate_count) > 0 THEN SUM(net_tx_rate_sum) / SUM(net_rate_count) END
+		FROM metrics_2min
+		WHERE bucket >= ? AND bucket < ?
+		GROUP BY server_id, hour`, fromHour*30, currentHour*30)
+	if err != nil {
+		return err
+	}
+	if _, err := db.Exec(`
+		INSERT OR IGNORE INTO ping_hourly_agg (server_id, bucket, target_name, target_host, latency_sum, latency_max, latency_count, ok_count, fail_count)
+		SELECT server_id, bucket / 30 AS hour, target_name, MAX(target_host),
+			SUM(latency_sum), MAX(latency_max), SUM(latency_count), SUM(ok_count), SUM(fail_count)
+		FROM ping_2min
+		WHERE bucket >= ? AND bucket < ?
+		GROUP BY server_id, target_name, hour`, fromHour*30, currentHour*30); err != nil {
+		return err
+	}
+
+	// Whole days only: [fromDay, currentDay), in hourly buckets
+	fromDay := now.Add(-backfillDailyWindow).Unix()/86400 + 1
+	currentDay := now.Unix() / 86400
+	daily, err := execCount(db, `
+		INSERT OR IGNORE INTO metrics_daily_agg (server_id, bucket, cpu_sum, cpu_max, memory_sum, memory_max, disk_sum,
+			net_rx, net_tx, ping_sum, ping_count, sample_count, load_1_avg, load_5_avg, load_15_avg, swap_avg,
+			net_rx_rate_avg, net_tx_rate_avg)
+		SELECT server_id, bucket / 24 AS day,
+			SUM(cpu_sum), MAX(cpu_max), SUM(memory_sum), MAX(memory_max), SUM(disk_sum),
+			MAX(net_rx), MAX(net_tx), SUM(ping_sum), SUM(ping_count), SUM(sample_count),
+			AVG(load_1_avg), AVG(load_5_avg), AVG(load_15_avg), AVG(swap_avg),
+			AVG(net_rx_rate_avg), AVG(net_tx_rate_avg)
+		FROM metrics_hourly_agg
+		WHERE bucket >= ? AND bucket < ?
+		GROUP BY server_id, day`, fromDay*24, currentDay*24)
+	if err != nil {
+		return err
+	}
+	if _, err := db.Exec(`
+		INSERT OR IGNORE INTO ping_daily_agg (server_id, bucket, target_name, target_host, latency_sum, latency_max, latency_count, ok_count, fail_count)
+		SELECT server_id, bucket / 24 AS day, target_name, MAX(target_host),
+			SUM(latency_sum), MAX(latency_max), SUM(latency_count), SUM(ok_count), SUM(fail_count)
+		FROM ping_hourly_agg
+		WHERE bucket >= ? AND bucket < ?
+		GROUP BY server_id, target_name, day`, fromDay*24, currentDay*24); err != nil {
+		return err
+	}
+
+	if hourly > 0 || daily > 0 {
+		fmt.Printf("📈 Backfilled %d hourly and %d daily rollup buckets\n", hourly, daily)
+	}
+	return nil
+}
+
+// execCount runs a statement and returns the number of rows it changed
+func execCount(db *sql.DB, query string, args ...interface{}) (int64, error) {
+	result, err := db.Exec(query, args...)
+	if err != nil {
+		return 0, err
+	}
+	return result.RowsAffected()
+}
@@ -0,0 +1,159 @@
+package main
+
+import (
+	"database/sql"
+	"math"
+	"testing"
+	"time"
+
+	"vstats/internal/common"
+)
+
+// hourOfSamples returns a minute-by-minute hour of live metrics for serverID,
+// the same for every hour
+func hourOfSamples(serverID string, hour int64) []MetricsBufferItem {
+	items := make([]MetricsBufferItem, 0, 60)
+	for i := int64(0); i < 60; i++ {
+		m := &SystemMetrics{Timestamp: time.Unix(hour*3600+i*60, 0).UTC()}
+		m.CPU.Usage = float32(i%10) * 5
+		m.Memory.UsagePercent = float32(40 + i%4)
+		m.Network.TotalRx = uint64(1000 + i)
+		m.Network.TotalTx = uint64(2000 + 2*i)
+		m.Network.RxSpeed = uint64(i)
+		m.LoadAverage.One = float64(i%4) / 2
+		items = append(items, MetricsBufferItem{ServerID: serverID, Metrics: m})
+	}
+	return items
+}
+
+// aggregate sums samples into one bucket, as an agent running through the
+// hour does
+func aggregate(bucket int64, items []MetricsBufferItem) common.BucketData {
+	b := common.BucketData{Bucket: bucket}
+	for _, item := range items {
+		m := item.Metrics
+		b.CPUSum += float64(m.CPU.Usage)
+		b.CPUMax = math.Max(b.CPUMax, float64(m.CPU.Usage))
+		b.MemorySum += float64(m.Memory.UsagePercent)
+		b.MemoryMax = math.Max(b.MemoryMax, float64(m.Memory.UsagePercent))
+		if m.Network.TotalRx > b.NetRx {
+			b.NetRx = m.Network.TotalRx
+		}
+		if m.Network.TotalTx > b.NetTx {
+			b.NetTx = m.Network.TotalTx
+		}
+		b.SampleCount++
+	}
+	return b
+}
+
+// rollupBucket reads a rollup row back as the bucket an agent would send
+func rollupBucket(t *testing.T, db *sql.DB, table, serverID string, bucket int64) (common.BucketData, bool) {
+	t.Helper()
+	b := common.BucketData{Bucket: bucket}
+	err := db.QueryRow(`SELECT cpu_sum, cpu_max, memory_sum, memory_max, disk_sum, net_rx, net_tx, ping_sum, ping_count, sample_count
+		FROM `+table+` WHERE server_id = ? AND bucket = ?`, serverID, bucket).Scan(
+		&b.CPUSum, &b.CPUMax, &b.MemorySum, &b.MemoryMax, &b.DiskSum, &b.NetRx, &b.NetTx, &b.PingSum, &b.PingCount, &b.SampleCount)
+	if err == sql.ErrNoRows {
+		return b, false
+	}
+	if err != nil {
+		t.Fatal(err)
+	}
+	return b, true
+}
+
+func TestBackfillHourlyRollups(t *testing.T) {
+	db := openTestDB(t)
+	hour := time.Now().Unix() / 3600
+
+	// The agent sent the hourly bucket three hours ago; the two hours after
+	// it were missed, and the current hour is still open
+	var items []MetricsBufferItem
+	for h := hour - 3; h <= hour; h++ {
+		items = append(items, hourOfSamples("s1", h)...)
+	}
+	if err := batchStoreMetrics(db, items); err != nil {
+		t.Fatal(err)
+	}
+	sent := aggregate(hour-3, hourOfSamples("s1", hour-3))
+	sent.CPUSum++ // so a rebuilt bucket replacing it shows
+	storeMultiGranularityMetricsInternal(db, "s1", []common.GranularityData{
+		{Granularity: "hourly", Interval: common.GranularityHourly, Metrics: []common.BucketData{sent}},
+	})
+
+	if err := BackfillRollups(db); err != nil {
+		t.Fatal(err)
+	}
+
+	for h := hour - 2; h < hour; h++ {
+		got, ok := rollupBucket(t, db, "metrics_hourly_agg", "s1", h)
+		if want := aggregate(h, hourOfSamples("s1", h)); !ok || got != want {
+			t.Errorf("hour %d: got %+v, %v; want %+v", h, got, ok, want)
+		}
+	}
+	// The rebuilt averages match the live samples too
+	var load, rxRate float64
+	if err := db.QueryRow(`SELECT load_1_avg, net_rx_rate_avg FROM metrics_hourly_agg WHERE server_id = 's1' AND bucket = ?`,
+		hour-1).Scan(&load, &rxRate); err != nil || load != 0.75 || rxRate != 29.5 {
+		t.Errorf("averages: load %v, rx rate %v, %v", load, rxRate, err)
+	}
+	if got, _ := rollupBucket(t, db, "metrics_hourly_agg", "s1", hour-3); got != sent {
+		t.Errorf("agent bucket overwritten: got %+v, want %+v", got, sent)
+	}
+	if _, ok := rollupBucket(t, db, "metrics_hourly_agg", "s1", hour); ok {
+		t.Error("the open hour was rolled up")
+	}
+
+	before := countRows(t, db, "metrics_hourly_agg")
+	if err := BackfillRollups(db); err != nil {
+		t.Fatal(err)
+	}
+	if after := countRows(t, db, "metrics_hourly_agg"); after != before {
+		t.Errorf("second backfill: %d hourly rows, want %d", after, before)
+	}
+}
+
+func TestBackfillDailyRollups(t *testing.T) {
+	db := openTestDB(t)
+	day := time.Now().Unix() / 86400
+
+	// Hourly buckets for two whole past days; the agent sent the daily
+	// bucket for the first, and the second was missed
+	var sent common.BucketData
+	for d := day - 3; d <= day-2; d++ {
+		var hours []common.BucketData
+		var items []MetricsBufferItem
+		for h := d * 24; h < (d+1)*24; h++ {
+			samples := hourOfSamples("s2", h)
+			hours = append(hours, aggregate(h, samples))
+			items = append(items, samples...)
+		}
+		storeMultiGranularityMetricsInternal(db, "s2", []common.GranularityData{
+			{Granularity: "hourly", Interval: common.GranularityHourly, Metrics: hours},
+		})
+		if d == day-3 {
+			sent = aggregate(d, items)
+			storeMultiGranularityMetricsInternal(db, "s2", []common.GranularityData{
+				{Granularity: "daily", Interval: common.GranularityDaily, Metrics: []common.BucketData{sent}},
+			})
+		}
+	}
+
+	if err := BackfillRollups(db); err != nil {
+		t.Fatal(err)
+	}
+
+	got, ok := rollupBucket(t, db, "metrics_daily_agg", "s2", day-2)
+	want := sent
+	want.Bucket = day - 2
+	if !ok || got != want {
+		t.Errorf("rebuilt day: got %+v, %v; want the same as the continuous day %+v", got, ok, want)
+	}
+	if got, _ := rollupBucket(t, db, "metrics_daily_agg", "s2", day-3); got != sent {
+		t.Errorf("agent bucket overwritten: got %+v, want %+v", got, sent)
+	}
+	if _, ok := rollupBucket(t, db, "metrics_daily_agg", "s2", day); ok {
+		t.Error("the open day was rolled up")
+	}
+}
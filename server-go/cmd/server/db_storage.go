@@ -0,0 +1,228 @@
+package main
+
+import (
+	"context"
+	"database/sql"
+	"fmt"
+	"sort"
+	"sync"
+	"time"
+)
+
+// ============================================================================
+// Storage Usage
+// ============================================================================
+
+// StorageQueryTimeout bounds each COUNT/MIN/MAX query so a huge table cannot
+// hold up the request; rowid tables fall back to an estimate on timeout
+const StorageQueryTimeout = 2 * time.Second
+
+// storageTable is a metrics table and its time column. Bucket tables store
+// unix time / bucketSecs; the others store RFC 3339 text.
+type storageTable struct {
+	name       string
+	timeColumn string
+	bucketSecs int
+}
+
+var storageTables = []storageTable{
+	{"metrics_raw", "timestamp", 0},
+	{"metrics_5sec", "bucket", 5},
+	{"metrics_2min", "bucket", 120},
+	{"metrics_15min_agg", "bucket", 900},
+	{"metrics_hourly_agg", "bucket", 3600},
+	{"metrics_daily_agg", "bucket", 86400},
+	{"metrics_hourly", "hour_start", 0},
+	{"metrics_daily", "date", 0},
+	{"ping_raw", "timestamp", 0},
+	{"metrics_disk", "timestamp", 0},
+	{"metrics_disk_daily", "date", 0},
+	{"metrics_ping", "timestamp", 0},
+	{"metrics_ping_hourly", "hour_start", 0},
+	{"bandwidth_daily", "date", 0},
+	{"availability_events", "timestamp", 0},
+	{"agent_events", "timestamp", 0},
+	{"alerts", "opened_at", 0},
+}
+
+// rangeQuery returns the oldest/newest row time of the table
+func (t storageTable) rangeQuery() string {
+	if t.bucketSecs == 0 {
+		return fmt.Sprintf("SELECT MIN(%s), MAX(%s) FROM %s", t.timeColumn, t.timeColumn, t.name)
+	}
+	return fmt.Sprintf("SELECT strftime('%%Y-%%m-%%dT%%H:%%M:%%SZ', MIN(%[1]s) * %[2]d, 'unixepoch'), "+
+		"strftime('%%Y-%%m-%%dT%%H:%%M:%%SZ', MAX(%[1]s) * %[2]d, 'unixepoch') FROM %[3]s",
+		t.timeColumn, t.bucketSecs, t.name)
+}
+
+// StorageStats describes database size and per-table contents
+type StorageStats struct {
+	SizeBytes int64               `json:"size_bytes"` // Database plus WAL file
+	Size      string              `json:"size"`       // Human readable, e.g. "1.2 GB"
+	Tables    []TableStorageStats `json:"tables"`
+	// Per-server rows of the history tables, largest first
+	Servers []ServerStorageStats `json:"servers"`
+}
+
+// ServerStorageStats describes the history rows of one server. Bytes are
+// estimated from each table's average row size.
+type ServerStorageStats struct {
+	ServerID       string           `json:"server_id"`
+	Rows           int64            `json:"rows"`
+	EstimatedBytes int64            `json:"estimated_bytes"`
+	Tables         map[string]int64 `json:"tables"`
+}
+
+// TableStorageStats describes a single metrics table
+type TableStorageStats struct {
+	Name          string `json:"name"`
+	Rows          *int64 `json:"rows"`                     // nil if counting timed out
+	RowsEstimated bool   `json:"rows_estimated,omitempty"` // Rows derived from rowid range
+	Oldest        string `json:"oldest,omitempty"`
+	Newest        string `json:"newest,omitempty"`
+}
+
+// GetStorageStats reports database size, row counts and time ranges of the
+// metrics tables. Reads go through the connection pool, not the DB writer.
+func GetStorageStats(db *sql.DB) StorageStats {
+	size := databaseFileSize()
+	stats := StorageStats{
+		SizeBytes: size,
+		Size:      FormatBytes(uint64(size)),
+		Tables:    make([]TableStorageStats, 0, len(storageTables)),
+	}
+
+	for _, t := range storageTables {
+		ts := TableStorageStats{Name: t.name}
+
+		var count int64
+		if err := queryRowTimeout(db, fmt.Sprintf("SELECT COUNT(*) FROM %s", t.name), &count); err == nil {
+			ts.Rows = &count
+		} else if err := queryRowTimeout(db, fmt.Sprintf("SELECT COALESCE(MAX(rowid) - MIN(rowid) + 1, 0) FROM %s", t.name), &count); err == nil {
+			ts.Rows = &count
+			ts.RowsEstimated = true
+		}
+
+		var oldest, newest sql.NullString
+		if err := queryRowTimeout(db, t.rangeQuery(), &oldest, &newest); err == nil {
+			ts.Oldest = oldest.String
+			ts.Newest = newest.String
+		}
+
+		stats.Tables = append(stats.Tables, ts)
+	}
+	stats.Servers = getServerStorageStats(db, size)
+	return stats
+}
+
+// getServerStorageStats counts the rows of each server in the history tables
+// (skipping tables where counting times out) and estimates their size. Table
+// sizes come from the dbstat virtual table; without it the database size is
+// spread evenly over all counted rows.
+func getServerStorageStats(db *sql.DB, dbSize int64) []ServerStorageStats {
+	tableBytes := make(map[string]int64)
+	ctx, cancel := context.WithTimeout(context.Background(), StorageQueryTimeout)
+	if rows, err := db.QueryContext(ctx, "SELECT name, SUM(pgsize) FROM dbstat GROUP BY name"); err == nil {
+		for rows.Next() {
+			var name string
+			var size int64
+			if rows.Scan(&name, &size) == nil {
+				tableBytes[name] = size
+			}
+		}
+		rows.Close()
+	}
+	cancel()
+
+	servers := make(map[string]*ServerStorageStats)
+	tableRows := make(map[string]int64)
+	var totalRows int64
+	for _, t := range retentionTables {
+		counts, err := countRowsByServer(db, t.name)
+		if err != nil {
+			continue
+		}
+		for id, n := range counts {
+			s := servers[id]
+			if s == nil {
+				s = &ServerStorageStats{ServerID: id, Tables: make(map[string]int64)}
+				servers[id] = s
+			}
+			s.Tables[t.name] = n
+			s.Rows += n
+			tableRows[t.name] += n
+			totalRows += n
+		}
+	}
+
+	result := make([]ServerStorageStats, 0, len(servers))
+	for _, s := range servers {
+		var bytes float64
+		for table, n := range s.Tables {
+			if size, ok := tableBytes[table]; ok && tableRows[table] > 0 {
+				bytes += float64(size) * float64(n) / float64(tableRows[table])
+			} else if totalRows > 0 {
+				bytes += float64(dbSize) * float64(n) / float64(totalRows)
+			}
+		}
+		s.EstimatedBytes = int64(bytes)
+		result = append(result, *s)
+	}
+	sort.Slice(result, func(i, j int) bool {
+		return result[i].EstimatedBytes > result[j].EstimatedBytes
+	})
+	return result
+}
+
+// countRowsByServer returns the rows per server_id of a table, giving up
+// after StorageQueryTimeout
+func countRowsByServer(db *sql.DB, table string) (map[string]int64, error) {
+	ctx, cancel := context.WithTimeout(context.Background(), StorageQueryTimeout)
+	defer cancel()
+	rows, err := db.QueryContext(ctx, fmt.Sprintf("SELECT server_id, COUNT(*) FROM %s GROUP BY server_id", table))
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	counts := make(map[string]int64)
+	for rows.Next() {
+		var id string
+		var n int64
+		if err := rows.Scan(&id, &n); err != nil {
+			return nil, err
+		}
+		counts[id] = n
+	}
+	return counts, rows.Err()
+}
+
+// storageStatsCache keeps the last GetStorageStats result for
+// CachedStorageStats
+var storageStatsCache struct {
+	sync.Mutex
+	stats StorageStats
+	at    time.Time
+}
+
+// CachedStorageStats returns storage stats at most maxAge old, so pollers
+// don't run the COUNT queries every time. The size is always current.
+func CachedStorageStats(db *sql.DB, maxAge time.Duration) StorageStats {
+	storageStatsCache.Lock()
+	defer storageStatsCache.Unlock()
+	if storageStatsCache.at.IsZero() || time.Since(storageStatsCache.at) > maxAge {
+		storageStatsCache.stats = GetStorageStats(db)
+		storageStatsCache.at = time.Now()
+	}
+	stats := storageStatsCache.stats
+	stats.SizeBytes = databaseFileSize()
+	stats.Size = FormatBytes(uint64(stats.SizeBytes))
+	return stats
+}
+
+// queryRowTimeout runs a single-row query, giving up after StorageQueryTimeout
+func queryRowTimeout(db *sql.DB, query string, dest ...interface{}) error {
+	ctx, cancel := context.WithTimeout(context.Background(), StorageQueryTimeout)
+	defer cancel()
+	return db.QueryRowContext(ctx, query).Scan(dest...)
+}
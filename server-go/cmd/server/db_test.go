@@ -0,0 +1,161 @@
+package main
+
+import (
+	"context"
+	"database/sql"
+	"fmt"
+	"path/filepath"
+	"sync"
+	"testing"
+	"time"
+)
+
+// withTimeout fails the test if fn blocks, e.g. on the single connection of
+// an in-memory database
+func withTimeout(t *testing.T, name string, fn func()) {
+	t.Helper()
+	done := make(chan struct{})
+	go func() {
+		defer close(done)
+		fn()
+	}()
+	select {
+	case <-done:
+	case <-time.After(10 * time.Second):
+		t.Fatalf("%s did not return; is a query waiting for the connection?", name)
+	}
+}
+
+func TestInitDatabaseAtMemory(t *testing.T) {
+	db := openTestDB(t)
+	execAll(t, db,
+		`INSERT INTO alerts (server_id, rule, severity, opened_at) VALUES ('s1', 'offline', 'critical', '2026-01-01T00:00:00Z')`,
+		`INSERT INTO availability_events (server_id, timestamp, state) VALUES ('s1', '2026-01-01T00:00:00Z', 'online'), ('s2', '2026-01-01T00:00:00Z', 'online')`,
+	)
+
+	state := NewAppState(&AppConfig{Servers: []RemoteServer{{ID: "s1"}, {ID: "s2"}}}, db)
+	if state.DB != db || state.Alerts == nil || state.LoginLimiter == nil || state.AgentConns == nil {
+		t.Fatal("NewAppState left state uninitialized")
+	}
+	// Open alerts are loaded so they are not opened twice
+	if state.Alerts.Open("s1", "offline", "critical", "s1 is offline", time.Now(), nil) {
+		t.Error("alert open in the database opened again")
+	}
+
+	// Looking up the schema inside the purge transaction must not wait for
+	// a second connection
+	var result *PurgeResult
+	var err error
+	withTimeout(t, "PurgeServerData", func() { result, err = PurgeServerData(state.DB, "s1") })
+	if err != nil {
+		t.Fatal(err)
+	}
+	if result.Tables["alerts"] != 1 || result.Tables["availability_events"] != 1 || result.Rows != 2 {
+		t.Errorf("purge result = %+v", result)
+	}
+	if n := countRows(t, db, "availability_events"); n != 1 {
+		t.Errorf("%d availability events left, want 1", n)
+	}
+
+	// Every in-memory database is a fresh one
+	other := openTestDB(t)
+	if n := countRows(t, other, "availability_events"); n != 0 {
+		t.Errorf("second in-memory database shares %d rows", n)
+	}
+	var tables int
+	other.QueryRow("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'").Scan(&tables)
+	if tables == 0 {
+		t.Error("second in-memory database not migrated")
+	}
+}
+
+func TestSQLiteConcurrentConnections(t *testing.T) {
+	db, err := InitDatabaseAt(filepath.Join(t.TempDir(), "vstats.db"))
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer db.Close()
+	ctx := context.Background()
+
+	// The DSN configures every pooled connection, not just the first
+	writer, err := db.Conn(ctx)
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer writer.Close()
+	reader, err := db.Conn(ctx)
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer reader.Close()
+	for name, conn := range map[string]*sql.Conn{"writer": writer, "reader": reader} {
+		var journal string
+		var timeout, synchronous int
+		conn.QueryRowContext(ctx, "PRAGMA journal_mode").Scan(&journal)
+		conn.QueryRowContext(ctx, "PRAGMA busy_timeout").Scan(&timeout)
+		conn.QueryRowContext(ctx, "PRAGMA synchronous").Scan(&synchronous)
+		if journal != "wal" || timeout != 5000 || synchronous != 1 {
+			t.Errorf("%s: journal_mode=%s busy_timeout=%d synchronous=%d", name, journal, timeout, synchronous)
+		}
+	}
+
+	// A reader is not blocked by an open write transaction, and a second
+	// writer waits for it instead of failing
+	if _, err := writer.ExecContext(ctx, "BEGIN IMMEDIATE"); err != nil {
+		t.Fatal(err)
+	}
+	if _, err := writer.ExecContext(ctx, "INSERT INTO availability_events (server_id, timestamp, state) VALUES ('s1', '2026-01-01T00:00:00Z', 'online')"); err != nil {
+		t.Fatal(err)
+	}
+	var n int
+	if err := reader.QueryRowContext(ctx, "SELECT COUNT(*) FROM availability_events").Scan(&n); err != nil || n != 0 {
+		t.Errorf("read during a write transaction: %d rows, %v", n, err)
+	}
+	second := make(chan error, 1)
+	go func() {
+		_, err := reader.ExecContext(ctx, "INSERT INTO availability_events (server_id, timestamp, state) VALUES ('s2', '2026-01-01T00:00:00Z', 'online')")
+		second <- err
+	}()
+	time.Sleep(100 * time.Millisecond)
+	if _, err := writer.ExecContext(ctx, "COMMIT"); err != nil {
+		t.Fatal(err)
+	}
+	if err := <-second; err != nil {
+		t.Errorf("second writer: %v", err)
+	}
+
+	// Writers and readers hammering the pool
+	var wg sync.WaitGroup
+	errs := make(chan error, 8)
+	for w := 0; w < 4; w++ {
+		wg.Add(2)
+		go func() {
+			defer wg.Done()
+			for i := 0; i < 50; i++ {
+				if _, err := db.Exec("INSERT INTO availability_events (server_id, timestamp, state) VALUES (?, ?, 'online')",
+					fmt.Sprintf("w%d", w), fmt.Sprintf("2026-01-01T00:00:%02dZ", i)); err != nil {
+					errs <- err
+					return
+				}
+			}
+		}()
+		go func() {
+			defer wg.Done()
+			for i := 0; i < 50; i++ {
+				var count int
+				if err := db.QueryRow("SELECT COUNT(*) FROM availability_events").Scan(&count); err != nil {
+					errs <- err
+					return
+				}
+			}
+		}()
+	}
+	wg.Wait()
+	close(errs)
+	for err := range errs {
+		t.Error(err)
+	}
+	if n := countRows(t, db, "availability_events"); n != 2+4*50 {
+		t.Errorf("%d rows, want %d", n, 2+4*50)
+	}
+}
@@ -0,0 +1,82 @@
+package main
+
+import "fmt"
+
+// ============================================================================
+// Unit Formatting
+// ============================================================================
+//
+// Unit contract for metrics: sizes and counters (total_rx, disk used, ...) are
+// bytes, speeds (rx_speed, tx_speed) are bytes per second. Convert only when
+// presenting values, using the helpers below.
+
+var byteUnits = []string{"B", "KB", "MB", "GB", "TB", "PB", "EB"}
+
+// FormatBytes renders a byte count with binary (1024) units, e.g. "1.5 GB"
+func FormatBytes(b uint64) string {
+	if b < 1024 {
+		return fmt.Sprintf("%d B", b)
+	}
+	v := float64(b)
+	i := 0
+	for v >= 1024 && i < len(byteUnits)-1 {
+		v /= 1024
+		i++
+	}
+	return fmt.Sprintf("%.1f %s", v, byteUnits[i])
+}
+
+// FormatBytesPerSecond renders a speed in bytes per second, e.g. "12.3 MB/s"
+func FormatBytesPerSecond(bps uint64) string {
+	return FormatBytes(bps) + "/s"
+}
+
+// BitsPerSecond converts bytes per second to bits per second
+func BitsPerSecond(bytesPerSec uint64) uint64 {
+	return bytesPerSec * 8
+}
+
+// NetworkSpeed is the current throughput of a server, pre-formatted so API
+// clients need not convert units themselves
+type NetworkSpeed struct {
+	RxBitsPerSec uint64 `json:"rx_bits_per_sec"`
+	TxBitsPerSec uint64 `json:"tx_bits_per_sec"`
+	// e.g. "12.3 MB/s" and "98.4 Mbps"
+	Rx     string `json:"rx"`
+	Tx     string `json:"tx"`
+	RxBits string `json:"rx_bits"`
+	TxBits string `json:"tx_bits"`
+}
+
+// networkSpeed formats a sample's rx_speed/tx_speed, nil without a sample
+func networkSpeed(metrics *SystemMetrics) *NetworkSpeed {
+	if metrics == nil {
+		return nil
+	}
+	rx, tx := metrics.Network.RxSpeed, metrics.Network.TxSpeed
+	return &NetworkSpeed{
+		RxBitsPerSec: BitsPerSecond(rx),
+		TxBitsPerSec: BitsPerSecond(tx),
+		Rx:           FormatBytesPerSecond(rx),
+		Tx:           FormatBytesPerSecond(tx),
+		RxBits:       FormatBitsPerSecond(rx),
+		TxBits:       FormatBitsPerSecond(tx),
+	}
+}
+
+var bitUnits = []string{"bps", "Kbps", "Mbps", "Gbps", "Tbps"}
+
+// FormatBitsPerSecond renders a speed given in bytes per second as bits per
+// second with decimal (1000) units, as link speeds are quoted, e.g. "98.4 Mbps"
+func FormatBitsPerSecond(bytesPerSec uint64) string {
+	v := float64(BitsPerSecond(bytesPerSec))
+	i := 0
+	for v >= 1000 && i < len(bitUnits)-1 {
+		v /= 1000
+		i++
+	}
+	if i == 0 {
+		return fmt.Sprintf("%.0f %s", v, bitUnits[i])
+	}
+	return fmt.Sprintf("%.1f %s", v, bitUnits[i])
+}
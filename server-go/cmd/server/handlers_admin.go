@@ -0,0 +1,195 @@
+package main
+
+import (
+	"bufio"
+	"errors"
+	"fmt"
+	"io"
+	"net/http"
+	"time"
+
+	"github.com/gin-gonic/gin"
+)
+
+// ============================================================================
+// Admin Handlers
+// ============================================================================
+
+// adminStatsStorageMaxAge is how stale the table stats of /api/admin/stats
+// may be; counting rows of large tables is too slow to repeat on every poll
+const adminStatsStorageMaxAge = time.Minute
+
+// AdminStats reports server-side write and ingestion statistics
+type AdminStats struct {
+	MetricsBuffer MetricsBufferStats `json:"metrics_buffer"`
+	AggBuffer     AggBufferStats     `json:"agg_buffer"`
+	// Database size (current) and per-table stats (up to a minute old)
+	Database StorageStats `json:"database"`
+	// Metrics samples received from agents in the last 60 seconds
+	MetricsLastMinute int64          `json:"metrics_last_minute"`
+	ConnectedAgents   int            `json:"connected_agents"`
+	DashboardClients  int            `json:"dashboard_clients"`
+	Broadcast         BroadcastStats `json:"broadcast"`
+	// Outcome of the last scheduled or manual backup since startup
+	LastBackupStatus *BackupStatus `json:"last_backup_status"`
+	// Password login failures, lockouts and throttled attempts
+	Logins LoginLimitStats `json:"logins"`
+}
+
+// AggBufferStats reports buckets waiting in the aggregation buffer
+type AggBufferStats struct {
+	PendingMetrics int `json:"pending_metrics"`
+	PendingPing    int `json:"pending_ping"`
+}
+
+// GetAdminStats returns metrics buffer counters (buffered vs flushed rows),
+// database usage and ingestion counters. Cheap enough to poll every few
+// seconds.
+func (s *AppState) GetAdminStats(c *gin.Context) {
+	var stats AdminStats
+	if metricsBuffer != nil {
+		stats.MetricsBuffer = metricsBuffer.Stats()
+	}
+	if aggBuffer != nil {
+		stats.AggBuffer.PendingMetrics, stats.AggBuffer.PendingPing = aggBuffer.Stats()
+	}
+	stats.Database = CachedStorageStats(s.DB, adminStatsStorageMaxAge)
+	stats.MetricsLastMinute = s.Ingest.MetricsLastMinute()
+	stats.Broadcast = s.Ingest.Broadcast()
+	stats.LastBackupStatus = LastBackupStatus()
+	stats.Logins = s.LoginLimiter.Stats(time.Now())
+	stats.Logins.FailedLast24h = CountFailedLogins(s.DB, time.Now().Add(-24*time.Hour))
+
+	s.AgentConnsMu.RLock()
+	stats.ConnectedAgents = len(s.AgentConns)
+	s.AgentConnsMu.RUnlock()
+	s.DashboardMu.RLock()
+	stats.DashboardClients = len(s.DashboardClients)
+	s.DashboardMu.RUnlock()
+
+	c.JSON(http.StatusOK, stats)
+}
+
+// GetStorage reports database size and per-table row counts and time ranges
+func (s *AppState) GetStorage(c *gin.Context) {
+	c.JSON(http.StatusOK, GetStorageStats(s.DB))
+}
+
+// VacuumDatabase runs the integrity check, VACUUM and ANALYZE on demand
+func (s *AppState) VacuumDatabase(c *gin.Context) {
+	result, err := RunDatabaseMaintenance(s.DB)
+	if err == ErrMaintenanceRunning {
+		c.JSON(http.StatusConflict, gin.H{"error": err.Error()})
+		return
+	}
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, result)
+}
+
+// BackupDatabase makes a backup immediately, using the backup settings for
+// the directory and keep count even if scheduled backups are disabled
+func (s *AppState) BackupDatabase(c *gin.Context) {
+	s.ConfigMu.RLock()
+	var settings *BackupSettings
+	if s.Config.Backup != nil {
+		copied := *s.Config.Backup
+		settings = &copied
+	}
+	s.ConfigMu.RUnlock()
+
+	result, err := RunBackup(s.DB, settings, true)
+	if err == ErrBackupRunning {
+		c.JSON(http.StatusConflict, gin.H{"error": err.Error()})
+		return
+	}
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, result)
+}
+
+// ExportData streams config and all metric tables as gzip-compressed NDJSON
+func (s *AppState) ExportData(c *gin.Context) {
+	s.ConfigMu.RLock()
+	configCopy := *s.Config
+	s.ConfigMu.RUnlock()
+
+	filename := fmt.Sprintf("vstats-export-%s.ndjson.gz", time.Now().UTC().Format("20060102-150405"))
+	c.Header("Content-Type", "application/gzip")
+	c.Header("Content-Disposition", fmt.Sprintf("attachment; filename=%q", filename))
+	c.Status(http.StatusOK)
+
+	if err := ExportDatabase(s.DB, &configCopy, c.Writer); err != nil {
+		// Headers are already sent; the truncated document will fail to import
+		fmt.Printf("Failed to export data: %v\n", err)
+	}
+}
+
+// ImportData restores an export produced by ExportData. Current (gzip NDJSON)
+// exports are merged: existing rows and servers are kept. Version 1 JSON
+// exports replace all data and require ?confirm=true.
+func (s *AppState) ImportData(c *gin.Context) {
+	body := bufio.NewReader(c.Request.Body)
+	if IsGzipExport(body) {
+		s.importMerge(c, body)
+		return
+	}
+
+	if c.Query("confirm") != "true" {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Import replaces all existing config and history; pass ?confirm=true to proceed"})
+		return
+	}
+
+	// Hold the config lock across the import so config and tables switch
+	// together. The upload is streamed into the database as it arrives.
+	s.ConfigMu.Lock()
+	config, result, err := ImportDatabase(s.DB, body)
+	if err != nil {
+		s.ConfigMu.Unlock()
+		status := http.StatusInternalServerError
+		if errors.Is(err, ErrInvalidExport) {
+			status = http.StatusBadRequest
+		}
+		c.JSON(status, gin.H{"error": err.Error()})
+		return
+	}
+	*s.Config = *config
+	if s.Config.JWTSecret != "" {
+		InitJWTSecret(s.Config.JWTSecret)
+	}
+	SaveConfig(s.Config)
+	s.ConfigMu.Unlock()
+
+	// Drop stale live state; agents reconnect with their preserved tokens
+	s.AgentMetricsMu.Lock()
+	s.AgentMetrics = make(map[string]*AgentMetricsData)
+	s.AgentMetricsMu.Unlock()
+
+	fmt.Printf("📦 Imported %d servers and %d tables\n", result.Servers, len(result.Tables))
+	c.JSON(http.StatusOK, result)
+}
+
+// importMerge streams a v2 export into the database and adds its servers
+func (s *AppState) importMerge(c *gin.Context, body io.Reader) {
+	archiveConfig, result, err := ImportStream(s.DB, body)
+	if errors.Is(err, ErrInvalidExport) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	s.ConfigMu.Lock()
+	result.Servers = MergeConfig(s.Config, archiveConfig)
+	SaveConfig(s.Config)
+	s.ConfigMu.Unlock()
+
+	fmt.Printf("📦 Imported %d new servers and %d tables\n", result.Servers, len(result.Tables))
+	c.JSON(http.StatusOK, result)
+}
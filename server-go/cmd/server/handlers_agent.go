@@ -1,10 +1,17 @@
 package main
 
 import (
+	"encoding/hex"
 	"encoding/json"
+	"errors"
 	"fmt"
+	"io"
 	"net/http"
 	"os"
+	"sort"
+	"strconv"
+	"strings"
+	"time"
 
 	"github.com/gin-gonic/gin"
 	"github.com/google/uuid"
@@ -21,15 +28,44 @@ func (s *AppState) RegisterAgent(c *gin.Context) {
 		return
 	}
 
-	serverID := uuid.New().String()
 	agentToken := uuid.New().String()
 
+	// Same machine registering again: hand out a fresh token for the existing
+	// entry instead of creating a duplicate
+	if req.MachineID != "" {
+		s.ConfigMu.Lock()
+		var existingID string
+		for i := range s.Config.Servers {
+			if s.Config.Servers[i].MachineID == req.MachineID {
+				existingID = s.Config.Servers[i].ID
+				s.Config.Servers[i].Token = agentToken
+				SaveConfig(s.Config)
+				break
+			}
+		}
+		s.ConfigMu.Unlock()
+
+		if existingID != "" {
+			// The old token is no longer valid, so drop its connection
+			s.disconnectAgent(existingID)
+			c.JSON(http.StatusOK, AgentRegisterResponse{
+				ID:       existingID,
+				Token:    agentToken,
+				Existing: true,
+			})
+			return
+		}
+	}
+
+	serverID := uuid.New().String()
 	server := RemoteServer{
-		ID:       serverID,
-		Name:     req.Name,
-		Location: req.Location,
-		Provider: req.Provider,
-		Token:    agentToken,
+		ID:        serverID,
+		Name:      req.Name,
+		Location:  req.Location,
+		Provider:  req.Provider,
+		Tag:       req.Tag,
+		Token:     agentToken,
+		MachineID: req.MachineID,
 	}
 
 	s.ConfigMu.Lock()
@@ -43,6 +79,26 @@ func (s *AppState) RegisterAgent(c *gin.Context) {
 	})
 }
 
+// UnregisterAgent removes a server registered by an agent, e.g. before the
+// agent registers again with `register --replace`
+func (s *AppState) UnregisterAgent(c *gin.Context) {
+	if !s.removeServer(c.Param("id")) {
+		c.JSON(http.StatusNotFound, gin.H{"error": "Server not found"})
+		return
+	}
+	c.JSON(http.StatusOK, gin.H{"success": true})
+}
+
+// disconnectAgent closes the live connection of an agent, if any
+func (s *AppState) disconnectAgent(serverID string) {
+	s.AgentConnsMu.RLock()
+	conn := s.AgentConns[serverID]
+	s.AgentConnsMu.RUnlock()
+	if conn != nil {
+		conn.Conn.Close()
+	}
+}
+
 // ============================================================================
 // Installation Script Handlers
 // ============================================================================
@@ -138,7 +194,7 @@ func (s *AppState) GetInstallCommand(c *gin.Context) {
 		protocol = "http"
 	}
 
-	baseURL := fmt.Sprintf("%s://%s", protocol, host)
+	baseURL := fmt.Sprintf("%s://%s%s", protocol, host, BasePath())
 
 	authHeader := c.GetHeader("Authorization")
 	token := ""
@@ -167,6 +223,11 @@ func (s *AppState) UpdateAgent(c *gin.Context) {
 	var req UpdateAgentRequest
 	c.ShouldBindJSON(&req)
 
+	if req.SHA256 != "" && !isHexSHA256(req.SHA256) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "sha256 must be a 64-character hex string"})
+		return
+	}
+
 	s.AgentConnsMu.RLock()
 	conn := s.AgentConns[serverID]
 	s.AgentConnsMu.RUnlock()
@@ -179,24 +240,388 @@ func (s *AppState) UpdateAgent(c *gin.Context) {
 		return
 	}
 
+	s.AgentUpdatesMu.RLock()
+	lastUpdate := s.AgentUpdates[serverID]
+	s.AgentUpdatesMu.RUnlock()
+
+	if err := s.sendUpdateCommand(serverID, conn, &req); err != nil {
+		c.JSON(http.StatusOK, UpdateAgentResponse{
+			Success:    false,
+			Message:    "Failed to send update command: " + err.Error(),
+			LastUpdate: lastUpdate,
+		})
+	} else {
+		c.JSON(http.StatusOK, UpdateAgentResponse{
+			Success:    true,
+			Message:    "Update command sent to agent",
+			LastUpdate: lastUpdate,
+		})
+	}
+}
+
+// agentTokenRotateGrace is how long an agent's connection is kept after its
+// token was rotated
+const agentTokenRotateGrace = 5 * time.Second
+
+// RotateAgentToken replaces a server's agent token. The old token stops
+// authenticating at once; a connected agent is sent the new one, saves it
+// and reconnects with it.
+func (s *AppState) RotateAgentToken(c *gin.Context) {
+	serverID := c.Param("id")
+	token := uuid.New().String()
+
+	s.ConfigMu.Lock()
+	found := false
+	for i := range s.Config.Servers {
+		if s.Config.Servers[i].ID == serverID {
+			s.Config.Servers[i].Token = token
+			found = true
+			break
+		}
+	}
+	if found {
+		SaveConfig(s.Config)
+	}
+	s.ConfigMu.Unlock()
+
+	if !found {
+		c.JSON(http.StatusNotFound, gin.H{"error": "Server not found"})
+		return
+	}
+
+	resp := RotateTokenResponse{ID: serverID, Token: token}
+	s.AgentConnsMu.RLock()
+	conn := s.AgentConns[serverID]
+	s.AgentConnsMu.RUnlock()
+	if conn != nil {
+		data, _ := json.Marshal(AgentCommand{Type: "command", Command: "rotate_token", Token: token})
+		select {
+		case conn.SendChan <- data:
+			resp.AgentNotified = true
+		default:
+		}
+		// The connection was authenticated with the old token, so it must
+		// not outlive it. A notified agent gets a moment to save the new
+		// token and reconnect on its own; agents too old to understand the
+		// command are dropped then too.
+		if resp.AgentNotified {
+			time.AfterFunc(agentTokenRotateGrace, func() { conn.Conn.Close() })
+		} else {
+			conn.Conn.Close()
+		}
+	}
+
+	fmt.Printf("🔑 Rotated agent token of server %s (agent notified: %v)\n", serverID, resp.AgentNotified)
+	c.JSON(http.StatusOK, resp)
+}
+
+// Reasons sendUpdateCommand did not queue an update command
+var (
+	errAgentSendQueueFull    = errors.New("agent send queue is full")
+	errAgentCannotSelfUpdate = errors.New("agent cannot replace its own binary; run `sudo vstats-agent update` on the host")
+)
+
+// sendUpdateCommand queues an update command for a connected agent and
+// records it as pending
+func (s *AppState) sendUpdateCommand(serverID string, conn *AgentConnection, req *UpdateAgentRequest) error {
+	if conn.SelfUpdateUnsupported {
+		return errAgentCannotSelfUpdate
+	}
+
 	cmd := AgentCommand{
 		Type:        "command",
 		Command:     "update",
 		DownloadURL: req.DownloadURL,
 		Force:       req.Force,
+		SHA256:      req.SHA256,
+		Signature:   req.Signature,
 	}
 
 	data, _ := json.Marshal(cmd)
 	select {
 	case conn.SendChan <- data:
-		c.JSON(http.StatusOK, UpdateAgentResponse{
-			Success: true,
-			Message: "Update command sent to agent",
-		})
 	default:
-		c.JSON(http.StatusOK, UpdateAgentResponse{
-			Success: false,
-			Message: "Failed to send update command",
-		})
+		return errAgentSendQueueFull
+	}
+
+	s.ConfigMu.RLock()
+	var fromVersion string
+	for _, server := range s.Config.Servers {
+		if server.ID == serverID {
+			fromVersion = server.Version
+			break
+		}
+	}
+	s.ConfigMu.RUnlock()
+
+	now := time.Now().UTC().Format(time.RFC3339)
+	s.AgentUpdatesMu.Lock()
+	s.AgentUpdates[serverID] = &AgentUpdateStatus{
+		Status:      "pending",
+		FromVersion: fromVersion,
+		RequestedAt: now,
+		UpdatedAt:   now,
+	}
+	s.AgentUpdatesMu.Unlock()
+	return nil
+}
+
+// UpdateAllStagger is the pause between update commands in a bulk rollout,
+// so agents don't all download the new binary at the same moment
+const UpdateAllStagger = 200 * time.Millisecond
+
+// UpdateAllAgents starts a rollout of the update command to every connected
+// agent, optionally limited to servers matching a tag and/or provider. An
+// empty body updates all of them. The job is returned at once, with every
+// target queued; GetUpdateAllJob follows its progress.
+func (s *AppState) UpdateAllAgents(c *gin.Context) {
+	var req UpdateAllAgentsRequest
+	if err := c.ShouldBindJSON(&req); err != nil && !errors.Is(err, io.EOF) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request"})
+		return
+	}
+
+	if req.SHA256 != "" && !isHexSHA256(req.SHA256) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "sha256 must be a 64-character hex string"})
+		return
+	}
+
+	// Pick targets from config so the filters apply to dashboard metadata
+	type target struct {
+		id   string
+		name string
+		conn *AgentConnection
+	}
+	var targets []target
+	s.ConfigMu.RLock()
+	s.AgentConnsMu.RLock()
+	for _, server := range s.Config.Servers {
+		conn := s.AgentConns[server.ID]
+		if conn == nil {
+			continue
+		}
+		if req.Tag != "" && !strings.EqualFold(server.Tag, req.Tag) {
+			continue
+		}
+		if req.Provider != "" && !strings.EqualFold(server.Provider, req.Provider) {
+			continue
+		}
+		targets = append(targets, target{id: server.ID, name: server.Name, conn: conn})
+	}
+	s.AgentConnsMu.RUnlock()
+	s.ConfigMu.RUnlock()
+
+	sort.Slice(targets, func(i, j int) bool { return targets[i].name < targets[j].name })
+
+	job := &UpdateAllJob{
+		ID:        uuid.New().String(),
+		Status:    "running",
+		StartedAt: time.Now().UTC().Format(time.RFC3339),
+		Total:     len(targets),
+		Results:   make([]UpdateAllAgentsResult, len(targets)),
+	}
+	for i, t := range targets {
+		job.Results[i] = UpdateAllAgentsResult{ServerID: t.id, Name: t.name, Status: "queued"}
+	}
+
+	s.AgentUpdatesMu.Lock()
+	if running := s.UpdateAllJob; running != nil && running.Status == "running" {
+		s.AgentUpdatesMu.Unlock()
+		c.JSON(http.StatusConflict, gin.H{"error": "A rollout is already running", "job_id": running.ID})
+		return
+	}
+	s.UpdateAllJob = job
+	snapshot := job.snapshot()
+	s.AgentUpdatesMu.Unlock()
+
+	single := req.UpdateAgentRequest
+	go func() {
+		for i, t := range targets {
+			if i > 0 {
+				time.Sleep(UpdateAllStagger)
+			}
+			err := s.sendUpdateCommand(t.id, t.conn, &single)
+
+			s.AgentUpdatesMu.Lock()
+			if err == nil {
+				job.Results[i].Status = "sent"
+				job.Sent++
+			} else {
+				job.Results[i].Status = "failed"
+				job.Results[i].Message = err.Error()
+				job.Failed++
+			}
+			s.AgentUpdatesMu.Unlock()
+		}
+
+		s.AgentUpdatesMu.Lock()
+		job.Status = "done"
+		job.FinishedAt = time.Now().UTC().Format(time.RFC3339)
+		s.AgentUpdatesMu.Unlock()
+	}()
+
+	c.JSON(http.StatusAccepted, snapshot)
+}
+
+// GetUpdateAllJob returns the progress of the most recent rollout
+func (s *AppState) GetUpdateAllJob(c *gin.Context) {
+	s.AgentUpdatesMu.RLock()
+	job := s.UpdateAllJob
+	var snapshot UpdateAllJob
+	if job != nil {
+		snapshot = job.snapshot()
+	}
+	s.AgentUpdatesMu.RUnlock()
+
+	if job == nil {
+		c.JSON(http.StatusNotFound, gin.H{"error": "No rollout has been started"})
+		return
+	}
+	c.JSON(http.StatusOK, snapshot)
+}
+
+// snapshot copies the job for a response. Callers hold AgentUpdatesMu.
+func (j *UpdateAllJob) snapshot() UpdateAllJob {
+	snapshot := *j
+	snapshot.Results = make([]UpdateAllAgentsResult, len(j.Results))
+	copy(snapshot.Results, j.Results)
+	return snapshot
+}
+
+// isHexSHA256 reports whether s looks like a hex-encoded SHA-256 digest
+func isHexSHA256(s string) bool {
+	if len(s) != 64 {
+		return false
+	}
+	_, err := hex.DecodeString(s)
+	return err == nil
+}
+
+// GetAgentUpdateStatus returns the outcome of the latest self-update
+func (s *AppState) GetAgentUpdateStatus(c *gin.Context) {
+	s.AgentUpdatesMu.RLock()
+	status := s.AgentUpdates[c.Param("id")]
+	s.AgentUpdatesMu.RUnlock()
+
+	if status == nil {
+		c.JSON(http.StatusNotFound, gin.H{"error": "No update has been requested for this server"})
+		return
+	}
+	c.JSON(http.StatusOK, status)
+}
+
+// GetAgentConnections lists every server with its connection history, most
+// frequently connecting first, so flapping agents stand out
+func (s *AppState) GetAgentConnections(c *gin.Context) {
+	s.ConfigMu.RLock()
+	servers := s.Config.Servers
+	s.ConfigMu.RUnlock()
+
+	s.AgentConnsMu.RLock()
+	list := make([]AgentConnectionInfo, 0, len(servers))
+	for _, server := range servers {
+		info := AgentConnectionInfo{ServerID: server.ID, ServerName: server.Name}
+		_, info.Connected = s.AgentConns[server.ID]
+		if stats := s.AgentConnStats[server.ID]; stats != nil {
+			info.AgentConnStats = *stats
+		}
+		list = append(list, info)
+	}
+	s.AgentConnsMu.RUnlock()
+
+	sort.SliceStable(list, func(i, j int) bool {
+		return list[i].Connects > list[j].Connects
+	})
+	c.JSON(http.StatusOK, list)
+}
+
+// GetServerConnectionEvents returns the recent connection events of a server
+// (?limit=, default 100)
+func (s *AppState) GetServerConnectionEvents(c *gin.Context) {
+	s.writeAgentEvents(c, c.Param("id"))
+}
+
+// GetAgentEvents returns the recent connection events of all agents,
+// including connects and failed auths not tied to a known server
+// (?limit=, default 100; ?event= to filter)
+func (s *AppState) GetAgentEvents(c *gin.Context) {
+	s.writeAgentEvents(c, "")
+}
+
+func (s *AppState) writeAgentEvents(c *gin.Context, serverID string) {
+	limit := 100
+	if v := c.Query("limit"); v != "" {
+		n, err := strconv.Atoi(v)
+		if err != nil || n <= 0 {
+			c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid limit"})
+			return
+		}
+		limit = min(n, MaxAgentEventEntries)
+	}
+
+	events, err := GetAgentEvents(s.DB, serverID, c.Query("event"), limit)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, events)
+}
+
+// AgentUpdateTimeout is how long an update may stay pending. An agent that
+// neither reconnects with a new version nor reports a failure by then, e.g.
+// because it went offline mid-update, is marked failed.
+const AgentUpdateTimeout = 15 * time.Minute
+
+// expireAgentUpdates marks updates pending since before now-AgentUpdateTimeout
+// as failed
+func (s *AppState) expireAgentUpdates(now time.Time) {
+	s.AgentUpdatesMu.Lock()
+	defer s.AgentUpdatesMu.Unlock()
+
+	for serverID, status := range s.AgentUpdates {
+		if status.Status != "pending" {
+			continue
+		}
+		requested, err := time.Parse(time.RFC3339, status.RequestedAt)
+		if err != nil || now.Sub(requested) < AgentUpdateTimeout {
+			continue
+		}
+		status.Status = "failed"
+		status.Reason = fmt.Sprintf("no result from the agent within %s", AgentUpdateTimeout)
+		status.UpdatedAt = now.UTC().Format(time.RFC3339)
+		fmt.Printf("⏱️ Update of server %s timed out\n", serverID)
+	}
+}
+
+// resolveAgentUpdate marks a pending update succeeded once the agent
+// reconnects with a different version
+func (s *AppState) resolveAgentUpdate(serverID, version string) {
+	s.AgentUpdatesMu.Lock()
+	defer s.AgentUpdatesMu.Unlock()
+
+	status := s.AgentUpdates[serverID]
+	if status == nil || status.Status != "pending" || version == "" || version == status.FromVersion {
+		return
+	}
+	status.Status = "succeeded"
+	status.ToVersion = version
+	status.UpdatedAt = time.Now().UTC().Format(time.RFC3339)
+}
+
+// recordAgentUpdateFailure stores a rollback reported by the agent
+func (s *AppState) recordAgentUpdateFailure(serverID string, msg *AgentMessage) {
+	s.AgentUpdatesMu.Lock()
+	defer s.AgentUpdatesMu.Unlock()
+
+	now := time.Now().UTC().Format(time.RFC3339)
+	status := s.AgentUpdates[serverID]
+	if status == nil {
+		status = &AgentUpdateStatus{RequestedAt: now}
+		s.AgentUpdates[serverID] = status
 	}
+	status.Status = "failed"
+	status.FromVersion = msg.FromVersion
+	status.ToVersion = msg.ToVersion
+	status.Reason = msg.Reason
+	status.UpdatedAt = now
 }
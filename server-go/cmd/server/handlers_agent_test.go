@@ -0,0 +1,98 @@
+package main
+
+import (
+	"encoding/json"
+	"net/http"
+	"net/http/httptest"
+	"strings"
+	"testing"
+	"time"
+
+	"github.com/gin-gonic/gin"
+)
+
+func TestUpdateAllAgents(t *testing.T) {
+	gin.SetMode(gin.TestMode)
+	state := NewAppState(&AppConfig{Servers: []RemoteServer{
+		{ID: "a", Name: "a", Tag: "prod"},
+		{ID: "b", Name: "b", Tag: "prod"},
+		{ID: "c", Name: "c", Tag: "dev"},
+		{ID: "d", Name: "d", Tag: "prod"}, // offline
+		{ID: "e", Name: "e", Tag: "prod"},
+	}}, openTestDB(t))
+	state.AgentConns["a"] = &AgentConnection{SendChan: make(chan []byte, 1)}
+	state.AgentConns["b"] = &AgentConnection{SendChan: make(chan []byte)} // queue full
+	state.AgentConns["c"] = &AgentConnection{SendChan: make(chan []byte, 1)}
+	state.AgentConns["e"] = &AgentConnection{SendChan: make(chan []byte, 1), SelfUpdateUnsupported: true}
+
+	router := gin.New()
+	router.POST("/api/agents/update-all", state.UpdateAllAgents)
+	router.GET("/api/agents/update-all", state.GetUpdateAllJob)
+	request := func(method, body string) (int, UpdateAllJob) {
+		w := httptest.NewRecorder()
+		router.ServeHTTP(w, httptest.NewRequest(method, "/api/agents/update-all", strings.NewReader(body)))
+		var job UpdateAllJob
+		json.Unmarshal(w.Body.Bytes(), &job)
+		return w.Code, job
+	}
+
+	if code, _ := request(http.MethodGet, ""); code != http.StatusNotFound {
+		t.Errorf("status before any rollout: %d", code)
+	}
+	for _, body := range []string{`{"tag": `, `{"tag": 1}`, `[]`} {
+		if code, _ := request(http.MethodPost, body); code != http.StatusBadRequest {
+			t.Errorf("body %s: %d, want %d", body, code, http.StatusBadRequest)
+		}
+	}
+
+	// The job comes back at once with its targets queued
+	code, job := request(http.MethodPost, `{"tag": "prod"}`)
+	if code != http.StatusAccepted || job.Status != "running" || job.Total != 3 ||
+		job.Results[0].ServerID != "a" || job.Results[1].Status != "queued" {
+		t.Fatalf("start: %d, %+v", code, job)
+	}
+	if code, _ := request(http.MethodPost, ""); code != http.StatusConflict {
+		t.Errorf("second rollout while running: %d", code)
+	}
+
+	deadline := time.Now().Add(5 * time.Second)
+	for job.Status == "running" && time.Now().Before(deadline) {
+		time.Sleep(UpdateAllStagger / 4)
+		_, job = request(http.MethodGet, "")
+	}
+	if job.Status != "done" || job.Sent != 1 || job.Failed != 2 ||
+		job.Results[0].Status != "sent" || job.Results[1].Status != "failed" ||
+		job.Results[2].Message != errAgentCannotSelfUpdate.Error() {
+		t.Errorf("finished job: %+v", job)
+	}
+	if len(state.AgentConns["a"].SendChan) != 1 || len(state.AgentConns["c"].SendChan) != 0 ||
+		len(state.AgentConns["e"].SendChan) != 0 {
+		t.Error("command sent to the wrong agents")
+	}
+
+	// An empty body rolls out to every connected agent
+	code, job = request(http.MethodPost, "")
+	if code != http.StatusAccepted || job.Total != 4 {
+		t.Errorf("empty body: %d, %+v", code, job)
+	}
+}
+
+func TestExpireAgentUpdates(t *testing.T) {
+	state := NewAppState(&AppConfig{}, openTestDB(t))
+	now := time.Now()
+	requested := func(ago time.Duration) string { return now.Add(-ago).UTC().Format(time.RFC3339) }
+	state.AgentUpdates["stale"] = &AgentUpdateStatus{Status: "pending", RequestedAt: requested(AgentUpdateTimeout + time.Minute)}
+	state.AgentUpdates["fresh"] = &AgentUpdateStatus{Status: "pending", RequestedAt: requested(time.Minute)}
+	state.AgentUpdates["done"] = &AgentUpdateStatus{Status: "succeeded", RequestedAt: requested(time.Hour)}
+
+	state.expireAgentUpdates(now)
+	if s := state.AgentUpdates["stale"]; s.Status != "failed" || s.Reason == "" {
+		t.Errorf("stale update: %+v", s)
+	}
+	if s := state.AgentUpdates["fresh"]; s.Status != "pending" {
+		t.Errorf("fresh update: %+v", s)
+	}
+	if s := state.AgentUpdates["done"]; s.Status != "succeeded" {
+		t.Errorf("finished update: %+v", s)
+	}
+}
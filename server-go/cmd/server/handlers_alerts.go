@@ -0,0 +1,64 @@
+package main
+
+import (
+	"net/http"
+	"strconv"
+	"time"
+
+	"github.com/gin-gonic/gin"
+)
+
+// ============================================================================
+// Alert Handlers
+// ============================================================================
+
+// GetAlerts returns the alert history (?state=open|resolved, ?server_id=,
+// ?range=24h|7d|30d|90d|1y, default 30d; ?limit=, default 100). Open alerts
+// are included whatever the range.
+func (s *AppState) GetAlerts(c *gin.Context) {
+	state := c.Query("state")
+	if state != "" && state != AlertStateOpen && state != AlertStateResolved {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid state, expected open or resolved"})
+		return
+	}
+	span, ok := uptimeRanges[c.DefaultQuery("range", "30d")]
+	if !ok {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid range, expected 24h, 7d, 30d, 90d or 1y"})
+		return
+	}
+	limit := 100
+	if v := c.Query("limit"); v != "" {
+		n, err := strconv.Atoi(v)
+		if err != nil || n <= 0 {
+			c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid limit"})
+			return
+		}
+		limit = min(n, MaxAlertEntries)
+	}
+
+	alerts, err := GetAlerts(s.DB, state, c.Query("server_id"), time.Now().Add(-span), limit)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, alerts)
+}
+
+// AckAlert acknowledges an alert
+func (s *AppState) AckAlert(c *gin.Context) {
+	id, err := strconv.ParseInt(c.Param("id"), 10, 64)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid alert id"})
+		return
+	}
+	found, err := AckAlert(s.DB, id)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	if !found {
+		c.JSON(http.StatusNotFound, gin.H{"error": "Alert not found"})
+		return
+	}
+	c.JSON(http.StatusOK, gin.H{"status": "acknowledged"})
+}
@@ -1,12 +1,13 @@
 package main
 
 import (
+	"fmt"
+	"math"
 	"net/http"
+	"strconv"
 	"time"
 
 	"github.com/gin-gonic/gin"
-	"github.com/golang-jwt/jwt/v5"
-	"golang.org/x/crypto/bcrypt"
 )
 
 // ============================================================================
@@ -20,11 +21,19 @@ func (s *AppState) Login(c *gin.Context) {
 		return
 	}
 
+	clientIP := c.ClientIP()
+	if ok, wait := s.LoginLimiter.Allow(clientIP, time.Now()); !ok {
+		retryAfter := int(math.Ceil(wait.Seconds()))
+		c.Header("Retry-After", strconv.Itoa(retryAfter))
+		c.JSON(http.StatusTooManyRequests, gin.H{"error": "Too many login attempts", "retry_after": retryAfter})
+		return
+	}
+
 	s.ConfigMu.RLock()
 	passwordHash := s.Config.AdminPasswordHash
 	s.ConfigMu.RUnlock()
 
-	if err := bcrypt.CompareHashAndPassword([]byte(passwordHash), []byte(req.Password)); err != nil {
+	if !VerifyPassword(passwordHash, req.Password) {
 		// If password verification fails, try reloading config from disk
 		// This handles the case where password was reset while server is running
 		if newConfig, _ := LoadConfig(); newConfig != nil {
@@ -34,33 +43,47 @@ func (s *AppState) Login(c *gin.Context) {
 			s.ConfigMu.Unlock()
 
 			// Try again with reloaded password hash
-			if err := bcrypt.CompareHashAndPassword([]byte(newConfig.AdminPasswordHash), []byte(req.Password)); err != nil {
+			if !VerifyPassword(newConfig.AdminPasswordHash, req.Password) {
 				// Still failed, restore old hash and return error
 				s.ConfigMu.Lock()
 				s.Config.AdminPasswordHash = oldHash
 				s.ConfigMu.Unlock()
+				s.LoginLimiter.Failure(clientIP, time.Now())
+				RecordLogin("admin", "password", clientIP, false, "invalid password")
 				c.JSON(http.StatusUnauthorized, gin.H{"error": "Invalid password"})
 				return
 			}
 			// Success after reload, continue with login
 		} else {
+			s.LoginLimiter.Failure(clientIP, time.Now())
+			RecordLogin("admin", "password", clientIP, false, "invalid password")
 			c.JSON(http.StatusUnauthorized, gin.H{"error": "Invalid password"})
 			return
 		}
 	}
 
-	expiresAt := time.Now().Add(7 * 24 * time.Hour)
-	token := jwt.NewWithClaims(jwt.SigningMethodHS256, jwt.MapClaims{
-		"sub": "admin",
-		"exp": expiresAt.Unix(),
-	})
+	s.upgradePasswordHash(req.Password)
 
-	tokenString, err := token.SignedString([]byte(GetJWTSecret()))
+	// With two-factor auth the password only earns a short-lived token,
+	// exchanged for a session at /api/auth/2fa/verify
+	if s.twoFactorEnabled() {
+		challenge, err := generateTwoFactorChallenge()
+		if err != nil {
+			c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to generate token"})
+			return
+		}
+		c.JSON(http.StatusOK, challenge)
+		return
+	}
+
+	tokenString, expiresAt, err := generateJWTToken("admin", "password")
 	if err != nil {
 		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to generate token"})
 		return
 	}
 
+	s.LoginLimiter.Success(clientIP)
+	RecordLogin("admin", "password", clientIP, true, "")
 	c.JSON(http.StatusOK, LoginResponse{
 		Token:     tokenString,
 		ExpiresAt: expiresAt,
@@ -71,6 +94,27 @@ func (s *AppState) VerifyToken(c *gin.Context) {
 	c.JSON(http.StatusOK, gin.H{"status": "valid"})
 }
 
+// GetLoginAudit returns recent login attempts (?limit=, default 100;
+// ?failed=true for failed attempts only)
+func (s *AppState) GetLoginAudit(c *gin.Context) {
+	limit := 100
+	if v := c.Query("limit"); v != "" {
+		n, err := strconv.Atoi(v)
+		if err != nil || n <= 0 {
+			c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid limit"})
+			return
+		}
+		limit = min(n, MaxLoginAuditEntries)
+	}
+
+	entries, err := GetLoginAudit(s.DB, limit, c.Query("failed") == "true")
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, entries)
+}
+
 func (s *AppState) ChangePassword(c *gin.Context) {
 	var req ChangePasswordRequest
 	if err := c.ShouldBindJSON(&req); err != nil {
@@ -81,18 +125,36 @@ func (s *AppState) ChangePassword(c *gin.Context) {
 	s.ConfigMu.Lock()
 	defer s.ConfigMu.Unlock()
 
-	if err := bcrypt.CompareHashAndPassword([]byte(s.Config.AdminPasswordHash), []byte(req.CurrentPassword)); err != nil {
+	if !VerifyPassword(s.Config.AdminPasswordHash, req.CurrentPassword) {
 		c.JSON(http.StatusUnauthorized, gin.H{"error": "Invalid current password"})
 		return
 	}
 
-	hash, err := bcrypt.GenerateFromPassword([]byte(req.NewPassword), bcrypt.DefaultCost)
+	hash, err := HashPassword(req.NewPassword, s.Config.PasswordHashing)
 	if err != nil {
 		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to hash password"})
 		return
 	}
 
-	s.Config.AdminPasswordHash = string(hash)
+	s.Config.AdminPasswordHash = hash
 	SaveConfig(s.Config)
 	c.Status(http.StatusOK)
 }
+
+// upgradePasswordHash rehashes the admin password after a successful login
+// when password_hashing asks for a different algorithm or cost
+func (s *AppState) upgradePasswordHash(password string) {
+	s.ConfigMu.Lock()
+	defer s.ConfigMu.Unlock()
+
+	if !NeedsRehash(s.Config.AdminPasswordHash, s.Config.PasswordHashing) {
+		return
+	}
+	hash, err := HashPassword(password, s.Config.PasswordHashing)
+	if err != nil {
+		return
+	}
+	s.Config.AdminPasswordHash = hash
+	SaveConfig(s.Config)
+	fmt.Println("🔑 Admin password rehashed with the configured algorithm")
+}
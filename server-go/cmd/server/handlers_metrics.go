@@ -1,9 +1,16 @@
 package main
 
 import (
+	"bufio"
 	"database/sql"
+	"encoding/csv"
+	"encoding/json"
+	"errors"
 	"fmt"
 	"net/http"
+	"sort"
+	"strconv"
+	"strings"
 	"sync"
 	"time"
 
@@ -16,7 +23,8 @@ import (
 
 type LocalMetricsResponse struct {
 	SystemMetrics
-	LocalNode LocalNodeConfig `json:"local_node"`
+	LocalNode    LocalNodeConfig `json:"local_node"`
+	NetworkSpeed *NetworkSpeed   `json:"network_speed"`
 }
 
 func (s *AppState) GetMetrics(c *gin.Context) {
@@ -29,23 +37,52 @@ func (s *AppState) GetMetrics(c *gin.Context) {
 	c.JSON(http.StatusOK, LocalMetricsResponse{
 		SystemMetrics: metrics,
 		LocalNode:     localNode,
+		NetworkSpeed:  networkSpeed(&metrics),
 	})
 }
 
+// GetAllMetrics returns the remote servers, optionally filtered by ?tag=,
+// ?provider=, ?status=online|degraded|offline and ?q= (name substring, case
+// insensitive) and sorted by ?sort=name|cpu|memory
 func (s *AppState) GetAllMetrics(c *gin.Context) {
+	tag, provider, status := c.Query("tag"), c.Query("provider"), c.Query("status")
+	query, sortBy := strings.ToLower(c.Query("q")), c.Query("sort")
+	if status != "" && status != ServerStatusOnline && status != ServerStatusDegraded && status != ServerStatusOffline {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid status, expected online, degraded or offline"})
+		return
+	}
+	if sortBy != "" && sortBy != "name" && sortBy != "cpu" && sortBy != "memory" {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid sort, expected name, cpu or memory"})
+		return
+	}
+
 	s.ConfigMu.RLock()
 	servers := s.Config.Servers
+	degradedAfter := s.Config.DegradedAfter()
 	s.ConfigMu.RUnlock()
 
 	s.AgentMetricsMu.RLock()
 	defer s.AgentMetricsMu.RUnlock()
 
-	var updates []ServerMetricsUpdate
+	updates := []ServerMetricsUpdate{}
 	for _, server := range servers {
+		if tag != "" && !strings.EqualFold(server.Tag, tag) {
+			continue
+		}
+		if provider != "" && !strings.EqualFold(server.Provider, provider) {
+			continue
+		}
+		if query != "" && !strings.Contains(strings.ToLower(server.Name), query) {
+			continue
+		}
+
 		metricsData := s.AgentMetrics[server.ID]
-		online := false
-		if metricsData != nil {
-			online = time.Since(metricsData.LastUpdated).Seconds() < 30
+		online := metricsData.IsOnline()
+		serverStatus := metricsData.Status(degradedAfter)
+		// Degraded servers are still online
+		if status == ServerStatusDegraded && serverStatus != ServerStatusDegraded ||
+			status != "" && status != ServerStatusDegraded && online != (status == ServerStatusOnline) {
+			continue
 		}
 
 		version := server.Version
@@ -54,31 +91,67 @@ func (s *AppState) GetAllMetrics(c *gin.Context) {
 		}
 
 		var metrics *SystemMetrics
+		var offlineReason string
 		if metricsData != nil {
-			metrics = &metricsData.Metrics
+			metrics = metricsData.sample()
+			offlineReason = metricsData.OfflineReason
 		}
 
 		updates = append(updates, ServerMetricsUpdate{
-			ServerID:     server.ID,
-			ServerName:   server.Name,
-			Location:     server.Location,
-			Provider:     server.Provider,
-			Tag:          server.Tag,
-			GroupID:      server.GroupID,
-			Version:      version,
-			IP:           server.IP,
-			Online:       online,
-			Metrics:      metrics,
-			PriceAmount:  server.PriceAmount,
-			PricePeriod:  server.PricePeriod,
-			PurchaseDate: server.PurchaseDate,
-			TipBadge:     server.TipBadge,
+			ServerID:        server.ID,
+			ServerName:      server.Name,
+			Location:        server.Location,
+			Provider:        server.Provider,
+			Tag:             server.Tag,
+			GroupID:         server.GroupID,
+			Version:         version,
+			IP:              server.IP,
+			Online:          online,
+			Status:          serverStatus,
+			OfflineReason:   offlineReason,
+			LastSeen:        metricsData.LastSeen(),
+			ClockSkewMs:     metricsData.ClockSkew(),
+			UpdateAvailable: s.agentUpdateAvailable(version),
+			Metrics:         metrics,
+			PrimaryMount:    primaryDiskMount(server.ID, metrics),
+			LatencyMs:       headlineLatency(server.ID, metrics),
+			NetworkSpeed:    networkSpeed(metrics),
+			PriceAmount:     server.PriceAmount,
+			PricePeriod:     server.PricePeriod,
+			PurchaseDate:    server.PurchaseDate,
+			TipBadge:        server.TipBadge,
 		})
 	}
 
+	sortServerUpdates(updates, sortBy)
 	c.JSON(http.StatusOK, updates)
 }
 
+// sortServerUpdates sorts by name ascending, or by CPU or memory usage
+// descending with offline servers last. Other values keep config order.
+func sortServerUpdates(updates []ServerMetricsUpdate, sortBy string) {
+	usage := func(u *ServerMetricsUpdate) float32 {
+		if !u.Online || u.Metrics == nil {
+			return -1
+		}
+		if sortBy == "cpu" {
+			return u.Metrics.CPU.Usage
+		}
+		return u.Metrics.Memory.UsagePercent
+	}
+
+	switch sortBy {
+	case "name":
+		sort.SliceStable(updates, func(i, j int) bool {
+			return strings.ToLower(updates[i].ServerName) < strings.ToLower(updates[j].ServerName)
+		})
+	case "cpu", "memory":
+		sort.SliceStable(updates, func(i, j int) bool {
+			return usage(&updates[i]) > usage(&updates[j])
+		})
+	}
+}
+
 // ============================================================================
 // History Handler
 // ============================================================================
@@ -88,14 +161,40 @@ func (s *AppState) GetHistory(c *gin.Context, db *sql.DB) {
 	rangeStr := c.DefaultQuery("range", "24h")
 	dataType := c.DefaultQuery("type", "all") // "ping", "metrics", or "all"
 	sinceStr := c.Query("since")              // Bucket number for incremental updates
+	stat := c.DefaultQuery("stat", HistoryStatAvg)
+
+	if !IsValidHistoryStat(stat) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid stat, expected avg or max"})
+		return
+	}
 
 	var sinceBucket int64
 	if sinceStr != "" {
 		fmt.Sscanf(sinceStr, "%d", &sinceBucket)
 	}
 
-	// Only use cache for 1h and 24h ranges with type=all
-	useCache := (rangeStr == "1h" || rangeStr == "24h" || rangeStr == "") && dataType == "all" && historyCache != nil
+	page, err := parseHistoryPage(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	if dataType == "detail" {
+		s.getHistoryDetail(c, db, serverID, rangeStr, page)
+		return
+	}
+	if page != nil && dataType == "ping" {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "limit is not supported for type=ping"})
+		return
+	}
+
+	if c.Query("from") != "" || c.Query("to") != "" || c.Query("max_points") != "" {
+		s.getHistoryRange(c, db, serverID, rangeStr, dataType, stat, page)
+		return
+	}
+
+	// Only use cache for 1h and 24h ranges with type=all and averaged values
+	useCache := (rangeStr == "1h" || rangeStr == "24h" || rangeStr == "") && dataType == "all" &&
+		stat == HistoryStatAvg && page == nil && historyCache != nil
 
 	// Check cache first (for full queries only, not incremental)
 	if useCache && sinceBucket == 0 {
@@ -115,6 +214,7 @@ func (s *AppState) GetHistory(c *gin.Context, db *sql.DB) {
 	var pingTargets []PingHistoryTarget
 	var metricsErr, pingErr error
 	var lastBucket int64
+	var nextCursor string
 
 	if dataType == "all" {
 		// Run both queries in parallel for better performance
@@ -123,7 +223,7 @@ func (s *AppState) GetHistory(c *gin.Context, db *sql.DB) {
 
 		go func() {
 			defer wg.Done()
-			data, metricsErr = GetHistorySince(db, serverID, rangeStr, sinceBucket)
+			data, nextCursor, metricsErr = GetHistoryPage(db, serverID, rangeStr, stat, sinceBucket, page)
 		}()
 
 		go func() {
@@ -139,8 +239,11 @@ func (s *AppState) GetHistory(c *gin.Context, db *sql.DB) {
 		}
 		// Ignore ping errors, just return empty if failed
 		_ = pingErr
+		if page != nil {
+			pingTargets = trimPingHistory(pingTargets, data)
+		}
 	} else if dataType == "metrics" {
-		data, metricsErr = GetHistorySince(db, serverID, rangeStr, sinceBucket)
+		data, nextCursor, metricsErr = GetHistoryPage(db, serverID, rangeStr, stat, sinceBucket, page)
 		if metricsErr != nil {
 			c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to fetch history"})
 			return
@@ -169,13 +272,554 @@ func (s *AppState) GetHistory(c *gin.Context, db *sql.DB) {
 	c.JSON(http.StatusOK, HistoryResponse{
 		ServerID:    serverID,
 		Range:       rangeStr,
+		Stat:        stat,
 		Data:        data,
 		PingTargets: pingTargets,
 		LastBucket:  lastBucket,
 		Incremental: sinceBucket > 0,
+		NextCursor:  nextCursor,
+	})
+}
+
+// getHistoryDetail serves type=detail: the full snapshots stored with
+// metrics_blobs over the last hour or day, the newest page (100 by default)
+// first
+func (s *AppState) getHistoryDetail(c *gin.Context, db *sql.DB, serverID, rangeStr string, page *HistoryPage) {
+	var span time.Duration
+	switch rangeStr {
+	case "1h":
+		span = time.Hour
+	case "24h":
+		span = 24 * time.Hour
+	default:
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid range for type=detail, expected 1h or 24h"})
+		return
+	}
+	limit, before := 100, ""
+	if page != nil {
+		if page.After != "" {
+			c.JSON(http.StatusBadRequest, gin.H{"error": "after is not supported for type=detail"})
+			return
+		}
+		limit, before = page.Limit, page.Before
+	}
+
+	samples, next, err := GetHistorySamples(db, serverID, time.Now().Add(-span), before, limit)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to fetch history"})
+		return
+	}
+	c.JSON(http.StatusOK, HistoryResponse{
+		ServerID:   serverID,
+		Range:      rangeStr,
+		Data:       []HistoryPoint{},
+		Samples:    samples,
+		NextCursor: next,
+	})
+}
+
+// getHistoryRange serves /api/history/:server_id?from=&to= (from/to override
+// range, to defaults to now) and preset ranges with max_points, downsampled
+// to at most max_points points
+func (s *AppState) getHistoryRange(c *gin.Context, db *sql.DB, serverID, rangeStr, dataType, stat string, page *HistoryPage) {
+	if page != nil || dataType == "ping" {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "from/to and max_points cannot be combined with limit/before/after or type=ping"})
+		return
+	}
+
+	maxPoints := DefaultHistoryMaxPoints
+	if mp := c.Query("max_points"); mp != "" {
+		n, err := strconv.Atoi(mp)
+		if err != nil || n < 1 {
+			c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid max_points"})
+			return
+		}
+		maxPoints = min(n, MaxHistoryPageLimit)
+	}
+
+	to := time.Now().UTC()
+	var from time.Time
+	if c.Query("from") == "" && c.Query("to") == "" {
+		span, ok := historyRangeSpans[rangeStr]
+		if !ok {
+			c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid range"})
+			return
+		}
+		from = to.Add(-span)
+	} else {
+		var err error
+		if from, err = time.Parse(time.RFC3339, c.Query("from")); err != nil {
+			c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid from, expected an RFC 3339 timestamp"})
+			return
+		}
+		if toStr := c.Query("to"); toStr != "" {
+			if to, err = time.Parse(time.RFC3339, toStr); err != nil {
+				c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid to, expected an RFC 3339 timestamp"})
+				return
+			}
+		}
+		rangeStr = "custom"
+	}
+
+	data, resolution, bucketSecs, err := GetHistoryRange(db, serverID, from, to, stat, maxPoints)
+	if errors.Is(err, ErrHistoryRangeInverted) || errors.Is(err, ErrHistoryRangeTooLarge) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to fetch history"})
+		return
+	}
+
+	c.JSON(http.StatusOK, HistoryResponse{
+		ServerID:   serverID,
+		Range:      rangeStr,
+		Stat:       stat,
+		Data:       data,
+		From:       from.UTC().Format(time.RFC3339),
+		To:         to.UTC().Format(time.RFC3339),
+		Resolution: resolution,
+		BucketSecs: bucketSecs,
 	})
 }
 
+const (
+	// MaxCompareTotalPoints caps servers × points of a compare response
+	MaxCompareTotalPoints = 20000
+	// minComparePoints is the fewest points per server a compare response
+	// is downsampled to before too many servers are refused
+	minComparePoints = 24
+)
+
+// compareMetrics extract the metrics /api/history/compare accepts from a
+// history point; nil where the point has no value
+var compareMetrics = map[string]func(p *HistoryPoint) *float64{
+	"cpu":    func(p *HistoryPoint) *float64 { return float64Ptr(float64(p.CPU)) },
+	"memory": func(p *HistoryPoint) *float64 { return float64Ptr(float64(p.Memory)) },
+	"disk":   func(p *HistoryPoint) *float64 { return float64Ptr(float64(p.Disk)) },
+	"ping":   func(p *HistoryPoint) *float64 { return p.PingMs },
+	"load": func(p *HistoryPoint) *float64 {
+		if p.Load == nil {
+			return nil
+		}
+		return float64Ptr(p.Load.One)
+	},
+	"swap": func(p *HistoryPoint) *float64 {
+		if p.Swap == nil {
+			return nil
+		}
+		return float64Ptr(float64(*p.Swap))
+	},
+	"net_rx": func(p *HistoryPoint) *float64 { return p.NetRxRate },
+	"net_tx": func(p *HistoryPoint) *float64 { return p.NetTxRate },
+}
+
+func float64Ptr(v float64) *float64 {
+	return &v
+}
+
+// GetHistoryCompare returns one metric of several servers over a preset
+// range, downsampled onto a shared bucket grid so the series line up. The
+// servers are ?servers=id1,id2, all servers with ?tag=, or every server.
+func (s *AppState) GetHistoryCompare(c *gin.Context, db *sql.DB) {
+	metric := c.DefaultQuery("metric", "cpu")
+	extract, ok := compareMetrics[metric]
+	if !ok {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid metric, expected cpu, memory, disk, ping, load, swap, net_rx or net_tx"})
+		return
+	}
+	rangeStr := c.DefaultQuery("range", "24h")
+	span, ok := historyRangeSpans[rangeStr]
+	if !ok {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid range"})
+		return
+	}
+	stat := c.DefaultQuery("stat", HistoryStatAvg)
+	if !IsValidHistoryStat(stat) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid stat, expected avg or max"})
+		return
+	}
+
+	servers, err := s.compareServers(c.Query("servers"), c.Query("tag"))
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	maxPoints := DefaultHistoryMaxPoints
+	if mp := c.Query("max_points"); mp != "" {
+		n, err := strconv.Atoi(mp)
+		if err != nil || n < 1 {
+			c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid max_points"})
+			return
+		}
+		maxPoints = min(n, MaxHistoryPageLimit)
+	}
+	if len(servers) > 0 {
+		maxPoints = min(maxPoints, MaxCompareTotalPoints/len(servers))
+		if maxPoints < minComparePoints {
+			c.JSON(http.StatusBadRequest, gin.H{"error": fmt.Sprintf("Too many servers, at most %d can be compared", MaxCompareTotalPoints/minComparePoints)})
+			return
+		}
+	}
+
+	to := time.Now().UTC()
+	from := to.Add(-span)
+	resp := CompareResponse{
+		Metric: metric,
+		Range:  rangeStr,
+		Stat:   stat,
+		From:   from.Format(time.RFC3339),
+		To:     to.Format(time.RFC3339),
+		Series: []CompareSeries{},
+	}
+
+	// Every server gets the same resolution and bucket width for the same
+	// from/to, so the first one defines the grid
+	index := make(map[string]int)
+	for _, server := range servers {
+		data, resolution, bucketSecs, err := GetHistoryRange(db, server.ID, from, to, stat, maxPoints)
+		if err != nil {
+			c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to fetch history"})
+			return
+		}
+		if resp.Timestamps == nil {
+			resp.Resolution, resp.BucketSecs = resolution, bucketSecs
+			resp.Timestamps = []string{}
+			for t := from.Unix() / bucketSecs * bucketSecs; t < to.Unix(); t += bucketSecs {
+				ts := time.Unix(t, 0).UTC().Format(time.RFC3339)
+				index[ts] = len(resp.Timestamps)
+				resp.Timestamps = append(resp.Timestamps, ts)
+			}
+		}
+
+		values := make([]*float64, len(resp.Timestamps))
+		for i := range data {
+			if j, ok := index[data[i].Timestamp]; ok {
+				values[j] = extract(&data[i])
+			}
+		}
+		resp.Series = append(resp.Series, CompareSeries{ServerID: server.ID, Name: server.Name, Values: values})
+	}
+	if resp.Timestamps == nil {
+		resp.Timestamps = []string{}
+	}
+
+	c.JSON(http.StatusOK, resp)
+}
+
+type compareServer struct {
+	ID, Name string
+}
+
+// compareServers resolves the servers of a compare query: the listed ids
+// (restricted to tag if set), else every server with the tag, else every
+// server including the local node
+func (s *AppState) compareServers(ids, tag string) ([]compareServer, error) {
+	s.ConfigMu.RLock()
+	defer s.ConfigMu.RUnlock()
+
+	var candidates []compareServer
+	if tag == "" && s.Config.SiteSettings.LocalNodeVisible() {
+		name := s.Config.LocalNode.Name
+		if name == "" {
+			name = "local"
+		}
+		candidates = append(candidates, compareServer{"local", name})
+	}
+	for _, server := range s.Config.Servers {
+		if tag == "" || server.Tag == tag {
+			candidates = append(candidates, compareServer{server.ID, server.Name})
+		}
+	}
+	if ids == "" {
+		return candidates, nil
+	}
+
+	byID := make(map[string]compareServer, len(candidates))
+	for _, server := range candidates {
+		byID[server.ID] = server
+	}
+	var selected []compareServer
+	for _, id := range strings.Split(ids, ",") {
+		id = strings.TrimSpace(id)
+		if id == "" {
+			continue
+		}
+		server, ok := byID[id]
+		if !ok {
+			if tag != "" {
+				return nil, fmt.Errorf("Server %s not found or not tagged %q", id, tag)
+			}
+			return nil, fmt.Errorf("Server %s not found", id)
+		}
+		delete(byID, id)
+		selected = append(selected, server)
+	}
+	return selected, nil
+}
+
+// GetDiskHistory returns per-mount disk usage, optionally for one ?mount=
+func (s *AppState) GetDiskHistory(c *gin.Context, db *sql.DB) {
+	serverID := c.Param("server_id")
+	rangeStr := c.DefaultQuery("range", "24h")
+
+	mounts, err := GetDiskHistory(db, serverID, rangeStr, c.Query("mount"))
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to fetch disk history"})
+		return
+	}
+
+	c.JSON(http.StatusOK, DiskHistoryResponse{
+		ServerID: serverID,
+		Range:    rangeStr,
+		Mounts:   mounts,
+	})
+}
+
+func (s *AppState) GetPingTargetHistory(c *gin.Context, db *sql.DB) {
+	serverID := c.Param("server_id")
+	rangeStr := c.DefaultQuery("range", "24h")
+
+	targets, err := GetPingTargetHistory(db, serverID, rangeStr, c.Query("target"))
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to fetch ping history"})
+		return
+	}
+
+	c.JSON(http.StatusOK, PingTargetHistoryResponse{
+		ServerID: serverID,
+		Range:    rangeStr,
+		Targets:  targets,
+	})
+}
+
+// GetBandwidth returns per billing cycle traffic, shifted by the server's
+// billing_cycle_day
+func (s *AppState) GetBandwidth(c *gin.Context, db *sql.DB) {
+	serverID := c.Param("id")
+	months, err := strconv.Atoi(c.DefaultQuery("months", "6"))
+	if err != nil || months < 1 || months > MaxBandwidthMonths {
+		c.JSON(http.StatusBadRequest, gin.H{"error": fmt.Sprintf("Invalid months, expected 1 to %d", MaxBandwidthMonths)})
+		return
+	}
+
+	cycleDay, found := 0, serverID == "local"
+	s.ConfigMu.RLock()
+	for _, server := range s.Config.Servers {
+		if server.ID == serverID {
+			cycleDay, found = server.BillingCycleDay, true
+			break
+		}
+	}
+	s.ConfigMu.RUnlock()
+	if !found {
+		c.JSON(http.StatusNotFound, gin.H{"error": "Server not found"})
+		return
+	}
+	if cycleDay == 0 {
+		cycleDay = 1
+	}
+
+	cycles, err := GetBandwidthCycles(db, serverID, months, cycleDay)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to fetch bandwidth"})
+		return
+	}
+
+	c.JSON(http.StatusOK, BandwidthResponse{
+		ServerID:        serverID,
+		BillingCycleDay: cycleDay,
+		Cycles:          cycles,
+	})
+}
+
+// GetUptime returns the availability of a server over a range
+func (s *AppState) GetUptime(c *gin.Context, db *sql.DB) {
+	serverID := c.Param("id")
+	rangeStr := c.DefaultQuery("range", "30d")
+	span, ok := uptimeRanges[rangeStr]
+	if !ok {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid range, expected 24h, 7d, 30d, 90d or 1y"})
+		return
+	}
+
+	found := false
+	s.ConfigMu.RLock()
+	for _, server := range s.Config.Servers {
+		if server.ID == serverID {
+			found = true
+			break
+		}
+	}
+	s.ConfigMu.RUnlock()
+	if !found {
+		c.JSON(http.StatusNotFound, gin.H{"error": "Server not found"})
+		return
+	}
+
+	resp, err := GetUptime(db, serverID, span)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to fetch uptime"})
+		return
+	}
+	resp.Range = rangeStr
+	c.JSON(http.StatusOK, resp)
+}
+
+// ExportHistory streams the history of a range as CSV or NDJSON, reading the
+// same rows as GetHistory without buffering them
+func (s *AppState) ExportHistory(c *gin.Context, db *sql.DB) {
+	serverID := c.Param("server_id")
+	rangeStr := c.DefaultQuery("range", "24h")
+	format := c.DefaultQuery("format", "csv")
+	stat := c.DefaultQuery("stat", HistoryStatAvg)
+
+	if _, ok := historyRangeSpans[rangeStr]; !ok {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid range, expected 1h, 24h, 7d, 30d or 1y"})
+		return
+	}
+	if format != "csv" && format != "ndjson" {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid format, expected csv or ndjson"})
+		return
+	}
+	if !IsValidHistoryStat(stat) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid stat, expected avg or max"})
+		return
+	}
+
+	name := serverID
+	s.ConfigMu.RLock()
+	if serverID == "local" && s.Config.LocalNode.Name != "" {
+		name = s.Config.LocalNode.Name
+	}
+	for _, server := range s.Config.Servers {
+		if server.ID == serverID && server.Name != "" {
+			name = server.Name
+		}
+	}
+	s.ConfigMu.RUnlock()
+
+	rows, err := queryHistory(db, serverID, rangeStr, stat, 0, -1)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to fetch history"})
+		return
+	}
+	defer rows.Close()
+
+	filename := fmt.Sprintf("vstats-%s-%s.%s", exportFilenamePart(name), rangeStr, format)
+	if format == "csv" {
+		c.Header("Content-Type", "text/csv; charset=utf-8")
+	} else {
+		c.Header("Content-Type", "application/x-ndjson")
+	}
+	c.Header("Content-Disposition", fmt.Sprintf("attachment; filename=%q", filename))
+	c.Status(http.StatusOK)
+
+	w := bufio.NewWriter(c.Writer)
+	csvWriter := csv.NewWriter(w)
+	encoder := json.NewEncoder(w)
+	if format == "csv" {
+		csvWriter.Write([]string{"timestamp", "cpu", "memory", "disk", "net_rx", "net_tx", "ping_ms"})
+	}
+	for rows.Next() {
+		point, err := scanHistoryPoint(rows, rangeStr)
+		if err != nil {
+			continue
+		}
+		if format == "ndjson" {
+			err = encoder.Encode(point)
+		} else {
+			ping := ""
+			if point.PingMs != nil {
+				ping = strconv.FormatFloat(*point.PingMs, 'f', -1, 64)
+			}
+			err = csvWriter.Write([]string{
+				point.Timestamp,
+				strconv.FormatFloat(float64(point.CPU), 'f', -1, 32),
+				strconv.FormatFloat(float64(point.Memory), 'f', -1, 32),
+				strconv.FormatFloat(float64(point.Disk), 'f', -1, 32),
+				strconv.FormatInt(point.NetRx, 10),
+				strconv.FormatInt(point.NetTx, 10),
+				ping,
+			})
+		}
+		if err != nil {
+			// Headers are already sent; the client sees a truncated file
+			fmt.Printf("Failed to export history for %s: %v\n", serverID, err)
+			return
+		}
+	}
+	csvWriter.Flush()
+	if err := rows.Err(); err != nil {
+		fmt.Printf("Failed to export history for %s: %v\n", serverID, err)
+	}
+	w.Flush()
+}
+
+// exportFilenamePart replaces characters that are unsafe in a download
+// filename with '_'
+func exportFilenamePart(s string) string {
+	return strings.Map(func(r rune) rune {
+		if r >= 'a' && r <= 'z' || r >= 'A' && r <= 'Z' || r >= '0' && r <= '9' || r == '-' || r == '.' {
+			return r
+		}
+		return '_'
+	}, s)
+}
+
+// parseHistoryPage reads the limit/before/after params. It returns nil if
+// the request is not paginated.
+func parseHistoryPage(c *gin.Context) (*HistoryPage, error) {
+	limitStr, before, after := c.Query("limit"), c.Query("before"), c.Query("after")
+	if limitStr == "" && before == "" && after == "" {
+		return nil, nil
+	}
+
+	page := &HistoryPage{Limit: MaxHistoryPageLimit}
+	if limitStr != "" {
+		limit, err := strconv.Atoi(limitStr)
+		if err != nil || limit < 1 {
+			return nil, fmt.Errorf("Invalid limit")
+		}
+		if limit < MaxHistoryPageLimit {
+			page.Limit = limit
+		}
+	}
+	for _, cursor := range []struct {
+		value string
+		dest  *string
+	}{{before, &page.Before}, {after, &page.After}} {
+		if cursor.value == "" {
+			continue
+		}
+		t, err := time.Parse(time.RFC3339, cursor.value)
+		if err != nil {
+			return nil, fmt.Errorf("Invalid cursor, expected an RFC 3339 timestamp")
+		}
+		*cursor.dest = t.UTC().Format("2006-01-02T15:04:05Z")
+	}
+	return page, nil
+}
+
+// trimPingHistory drops ping points outside the time span of a metrics page
+func trimPingHistory(targets []PingHistoryTarget, data []HistoryPoint) []PingHistoryTarget {
+	if len(data) == 0 {
+		return nil
+	}
+	first, last := data[0].Timestamp, data[len(data)-1].Timestamp
+	for i := range targets {
+		kept := targets[i].Data[:0]
+		for _, p := range targets[i].Data {
+			if p.Timestamp >= first && p.Timestamp <= last {
+				kept = append(kept, p)
+			}
+		}
+		targets[i].Data = kept
+	}
+	return targets
+}
+
 // ============================================================================
 // Health Check
 // ============================================================================
@@ -10,7 +10,6 @@ import (
 	"time"
 
 	"github.com/gin-gonic/gin"
-	"github.com/golang-jwt/jwt/v5"
 	"github.com/google/uuid"
 )
 
@@ -260,6 +259,7 @@ func (s *AppState) GitHubOAuthCallback(c *gin.Context) {
 
 	// Check if user is allowed
 	if !isUserAllowed(oauth.GitHub.AllowedUsers, user.Login) {
+		RecordLogin(user.Login, "github", c.ClientIP(), false, "user not authorized")
 		redirectWithError(c, "User not authorized: "+user.Login)
 		return
 	}
@@ -272,6 +272,7 @@ func (s *AppState) GitHubOAuthCallback(c *gin.Context) {
 	}
 
 	// Redirect to frontend with token
+	RecordLogin(user.Login, "github", c.ClientIP(), true, "")
 	redirectWithToken(c, token, expiresAt, "github", user.Login)
 }
 
@@ -373,6 +374,7 @@ func (s *AppState) GoogleOAuthCallback(c *gin.Context) {
 
 	// Check if user is allowed
 	if !isUserAllowed(oauth.Google.AllowedUsers, user.Email) {
+		RecordLogin(user.Email, "google", c.ClientIP(), false, "user not authorized")
 		redirectWithError(c, "User not authorized: "+user.Email)
 		return
 	}
@@ -385,6 +387,7 @@ func (s *AppState) GoogleOAuthCallback(c *gin.Context) {
 	}
 
 	// Redirect to frontend with token
+	RecordLogin(user.Email, "google", c.ClientIP(), true, "")
 	redirectWithToken(c, token, expiresAt, "google", user.Email)
 }
 
@@ -438,6 +441,7 @@ func (s *AppState) ProxyOAuthCallback(c *gin.Context) {
 
 	// Check allowed users (from centralized config)
 	if !isUserAllowed(oauth.AllowedUsers, user) {
+		RecordLogin(user, provider, c.ClientIP(), false, "user not authorized")
 		redirectWithError(c, "User not authorized: "+user)
 		return
 	}
@@ -450,6 +454,7 @@ func (s *AppState) ProxyOAuthCallback(c *gin.Context) {
 	}
 
 	// Redirect to frontend with token
+	RecordLogin(user, provider, c.ClientIP(), true, "")
 	redirectWithToken(c, token, expiresAt, provider, user)
 }
 
@@ -472,7 +477,7 @@ func getCallbackURL(c *gin.Context, provider string) string {
 		protocol = "http"
 	}
 
-	return fmt.Sprintf("%s://%s/api/auth/oauth/%s/callback", protocol, c.Request.Host, provider)
+	return fmt.Sprintf("%s://%s%s/api/auth/oauth/%s/callback", protocol, c.Request.Host, BasePath(), provider)
 }
 
 func exchangeGitHubCode(code, clientID, clientSecret, redirectURI string) (*GitHubTokenResponse, error) {
@@ -588,25 +593,10 @@ func isUserAllowed(allowedUsers []string, identifier string) bool {
 	return false
 }
 
-func generateJWTToken(sub, provider string) (string, time.Time, error) {
-	expiresAt := time.Now().Add(7 * 24 * time.Hour)
-	token := jwt.NewWithClaims(jwt.SigningMethodHS256, jwt.MapClaims{
-		"sub":      sub,
-		"provider": provider,
-		"exp":      expiresAt.Unix(),
-	})
-
-	tokenString, err := token.SignedString([]byte(GetJWTSecret()))
-	if err != nil {
-		return "", time.Time{}, err
-	}
-
-	return tokenString, expiresAt, nil
-}
-
 func redirectWithToken(c *gin.Context, token string, expiresAt time.Time, provider, username string) {
 	// Redirect to frontend OAuth callback page
-	redirectURL := fmt.Sprintf("/oauth-callback?token=%s&expires=%d&provider=%s&user=%s",
+	redirectURL := fmt.Sprintf("%s/oauth-callback?token=%s&expires=%d&provider=%s&user=%s",
+		BasePath(),
 		url.QueryEscape(token),
 		expiresAt.Unix(),
 		provider,
@@ -616,7 +606,7 @@ func redirectWithToken(c *gin.Context, token string, expiresAt time.Time, provid
 }
 
 func redirectWithError(c *gin.Context, message string) {
-	redirectURL := fmt.Sprintf("/oauth-callback?error=%s", url.QueryEscape(message))
+	redirectURL := fmt.Sprintf("%s/oauth-callback?error=%s", BasePath(), url.QueryEscape(message))
 	c.Redirect(http.StatusTemporaryRedirect, redirectURL)
 }
 
@@ -0,0 +1,99 @@
+package main
+
+import (
+	"net/http"
+	"time"
+
+	"github.com/gin-gonic/gin"
+)
+
+// ============================================================================
+// Public Status Handler
+// ============================================================================
+
+// DefaultPublicFields is used when public mode is enabled without an allowlist
+var DefaultPublicFields = []string{"cpu"}
+
+// GetPublicStatus returns an unauthenticated, allowlisted view of server
+// status. Sensitive data (IP, hostname, disks, interfaces, ping targets)
+// is never copied into the response.
+func (s *AppState) GetPublicStatus(c *gin.Context) {
+	s.ConfigMu.RLock()
+	publicMode := s.Config.SiteSettings.PublicMode
+	siteName := s.Config.SiteSettings.SiteName
+	servers := s.Config.Servers
+	s.ConfigMu.RUnlock()
+
+	if publicMode == nil || !publicMode.Enabled {
+		c.JSON(http.StatusNotFound, gin.H{"error": "Public status page is disabled"})
+		return
+	}
+
+	fieldList := publicMode.Fields
+	if len(fieldList) == 0 {
+		fieldList = DefaultPublicFields
+	}
+	fields := make(map[string]bool, len(fieldList))
+	for _, f := range fieldList {
+		fields[f] = true
+	}
+
+	s.AgentMetricsMu.RLock()
+	defer s.AgentMetricsMu.RUnlock()
+
+	statuses := make([]PublicServerStatus, 0, len(servers))
+	for _, server := range servers {
+		metricsData := s.AgentMetrics[server.ID]
+		online := metricsData.IsOnline()
+
+		status := PublicServerStatus{
+			Name:   server.Name,
+			Online: online,
+		}
+		if fields["location"] {
+			status.Location = server.Location
+		}
+		if fields["provider"] {
+			status.Provider = server.Provider
+		}
+		if fields["tag"] {
+			status.Tag = server.Tag
+		}
+		if fields["version"] {
+			status.Version = server.Version
+		}
+
+		if sample := metricsData.sample(); sample != nil && online {
+			m := *sample
+			if fields["uptime"] {
+				status.Uptime = &m.Uptime
+			}
+			if fields["cpu"] {
+				status.CPU = &m.CPU.Usage
+			}
+			if fields["memory"] {
+				status.Memory = &m.Memory.UsagePercent
+			}
+			if fields["disk"] {
+				if d, _ := primaryDisk(server.ID, m.Disks); d != nil {
+					status.Disk = &d.UsagePercent
+				}
+			}
+			if fields["load"] {
+				status.Load = &m.LoadAverage.One
+			}
+			if fields["network"] {
+				status.RxSpeed = &m.Network.RxSpeed
+				status.TxSpeed = &m.Network.TxSpeed
+			}
+		}
+
+		statuses = append(statuses, status)
+	}
+
+	c.JSON(http.StatusOK, PublicStatusResponse{
+		SiteName:  siteName,
+		Servers:   statuses,
+		UpdatedAt: time.Now().UTC().Format(time.RFC3339),
+	})
+}
@@ -1,7 +1,9 @@
 package main
 
 import (
+	"fmt"
 	"net/http"
+	"strings"
 
 	"github.com/gin-gonic/gin"
 	"github.com/google/uuid"
@@ -11,10 +13,60 @@ import (
 // Server Management Handlers
 // ============================================================================
 
+// Limits on the informational notes and metadata of a server
+const (
+	MaxServerNotesLength   = 4096
+	MaxServerMetadataKeys  = 32
+	MaxServerMetadataValue = 1024
+)
+
+// validateServerNotes checks notes and metadata against the limits above
+func validateServerNotes(notes string, metadata map[string]string) error {
+	if len(notes) > MaxServerNotesLength {
+		return fmt.Errorf("notes must be at most %d bytes", MaxServerNotesLength)
+	}
+	if len(metadata) > MaxServerMetadataKeys {
+		return fmt.Errorf("metadata may have at most %d keys", MaxServerMetadataKeys)
+	}
+	for k, v := range metadata {
+		if strings.TrimSpace(k) == "" {
+			return fmt.Errorf("metadata keys must not be empty")
+		}
+		if len(k) > MaxServerMetadataValue || len(v) > MaxServerMetadataValue {
+			return fmt.Errorf("metadata keys and values must be at most %d bytes", MaxServerMetadataValue)
+		}
+	}
+	return nil
+}
+
+// publicServer returns a copy of server without what only admins may see
+func publicServer(server RemoteServer) RemoteServer {
+	server.Token = ""
+	server.Notes = ""
+	server.Metadata = nil
+	if server.SNMP != nil {
+		snmp := *server.SNMP
+		snmp.Community, snmp.AuthPassword, snmp.PrivPassword = "", "", ""
+		server.SNMP = &snmp
+	}
+	return server
+}
+
+// GetServers lists the configured servers. The route is public, so agent
+// tokens, SNMP credentials, notes and metadata are only included for
+// requests with an admin JWT.
 func (s *AppState) GetServers(c *gin.Context) {
 	s.ConfigMu.RLock()
 	defer s.ConfigMu.RUnlock()
-	c.JSON(http.StatusOK, s.Config.Servers)
+	if requestIsAdmin(c) {
+		c.JSON(http.StatusOK, s.Config.Servers)
+		return
+	}
+	servers := make([]RemoteServer, len(s.Config.Servers))
+	for i, server := range s.Config.Servers {
+		servers[i] = publicServer(server)
+	}
+	c.JSON(http.StatusOK, servers)
 }
 
 func (s *AppState) AddServer(c *gin.Context) {
@@ -23,22 +75,26 @@ func (s *AppState) AddServer(c *gin.Context) {
 		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request"})
 		return
 	}
-
-	server := RemoteServer{
-		ID:           uuid.New().String(),
-		Name:         req.Name,
-		URL:          req.URL,
-		Location:     req.Location,
-		Provider:     req.Provider,
-		Tag:          req.Tag,
-		Token:        uuid.New().String(),
-		GroupID:      req.GroupID,
-		GroupValues:  req.GroupValues,
-		PriceAmount:  req.PriceAmount,
-		PricePeriod:  req.PricePeriod,
-		PurchaseDate: req.PurchaseDate,
-		TipBadge:     req.TipBadge,
+	if req.BillingCycleDay < 0 || req.BillingCycleDay > 28 {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "billing_cycle_day must be between 1 and 28"})
+		return
 	}
+	if req.SNMP != nil {
+		if err := req.SNMP.Validate(); err != nil {
+			c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+			return
+		}
+	}
+	if err := validateServerNotes(req.Notes, req.Metadata); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	if req.RetentionDays < 0 {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "retention_days must not be negative"})
+		return
+	}
+
+	server := newRemoteServer(req)
 
 	s.ConfigMu.Lock()
 	s.Config.Servers = append(s.Config.Servers, server)
@@ -48,9 +104,116 @@ func (s *AppState) AddServer(c *gin.Context) {
 	c.JSON(http.StatusOK, server)
 }
 
+// AddServersBulk adds a batch of servers with a single config write. The
+// batch is validated first and rejected as a whole if any entry is invalid.
+func (s *AppState) AddServersBulk(c *gin.Context) {
+	var reqs []AddServerRequest
+	if err := c.ShouldBindJSON(&reqs); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request, expected an array of servers"})
+		return
+	}
+	if len(reqs) == 0 {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "No servers given"})
+		return
+	}
+	for i, req := range reqs {
+		if strings.TrimSpace(req.Name) == "" {
+			c.JSON(http.StatusBadRequest, gin.H{"error": fmt.Sprintf("Server %d: name is required", i)})
+			return
+		}
+		if req.BillingCycleDay < 0 || req.BillingCycleDay > 28 {
+			c.JSON(http.StatusBadRequest, gin.H{"error": fmt.Sprintf("Server %d: billing_cycle_day must be between 1 and 28", i)})
+			return
+		}
+		if req.SNMP != nil {
+			if err := req.SNMP.Validate(); err != nil {
+				c.JSON(http.StatusBadRequest, gin.H{"error": fmt.Sprintf("Server %d: %v", i, err)})
+				return
+			}
+		}
+		if err := validateServerNotes(req.Notes, req.Metadata); err != nil {
+			c.JSON(http.StatusBadRequest, gin.H{"error": fmt.Sprintf("Server %d: %v", i, err)})
+			return
+		}
+		if req.RetentionDays < 0 {
+			c.JSON(http.StatusBadRequest, gin.H{"error": fmt.Sprintf("Server %d: retention_days must not be negative", i)})
+			return
+		}
+	}
+
+	servers := make([]RemoteServer, len(reqs))
+	for i, req := range reqs {
+		servers[i] = newRemoteServer(req)
+	}
+
+	s.ConfigMu.Lock()
+	s.Config.Servers = append(s.Config.Servers, servers...)
+	SaveConfig(s.Config)
+	s.ConfigMu.Unlock()
+
+	c.JSON(http.StatusOK, servers)
+}
+
+// newRemoteServer creates a server with a new ID and agent token
+func newRemoteServer(req AddServerRequest) RemoteServer {
+	return RemoteServer{
+		ID:                uuid.New().String(),
+		Name:              req.Name,
+		URL:               req.URL,
+		Location:          req.Location,
+		Provider:          req.Provider,
+		Tag:               req.Tag,
+		Token:             uuid.New().String(),
+		GroupID:           req.GroupID,
+		GroupValues:       req.GroupValues,
+		PriceAmount:       req.PriceAmount,
+		PricePeriod:       req.PricePeriod,
+		PurchaseDate:      req.PurchaseDate,
+		TipBadge:          req.TipBadge,
+		BillingCycleDay:   req.BillingCycleDay,
+		SNMP:              req.SNMP,
+		PrimaryMount:      strings.TrimSpace(req.PrimaryMount),
+		PrimaryPingTarget: strings.TrimSpace(req.PrimaryPingTarget),
+		Notes:             req.Notes,
+		Metadata:          req.Metadata,
+		RetentionDays:     req.RetentionDays,
+	}
+}
+
+// DeleteServer removes a server and, unless ?purge=false, its history
 func (s *AppState) DeleteServer(c *gin.Context) {
 	id := c.Param("id")
+	s.removeServer(id)
 
+	if c.Query("purge") == "false" {
+		c.Status(http.StatusOK)
+		return
+	}
+	result, err := PurgeServerData(s.DB, id)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Server deleted but its history could not be purged: " + err.Error()})
+		return
+	}
+	fmt.Printf("🗑️  Purged %d rows of history for server %s\n", result.Rows, id)
+	c.JSON(http.StatusOK, result)
+}
+
+// PurgeServerHistory deletes a server's history but keeps the server. It also
+// works for ids that are no longer configured, to clean up orphaned rows.
+func (s *AppState) PurgeServerHistory(c *gin.Context) {
+	id := c.Param("id")
+	result, err := PurgeServerData(s.DB, id)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	fmt.Printf("🗑️  Purged %d rows of history for server %s\n", result.Rows, id)
+	c.JSON(http.StatusOK, result)
+}
+
+// removeServer deletes a server from the config and drops its agent.
+// Returns false if no server has that id.
+func (s *AppState) removeServer(id string) bool {
 	s.ConfigMu.Lock()
 	servers := make([]RemoteServer, 0)
 	for _, srv := range s.Config.Servers {
@@ -58,15 +221,20 @@ func (s *AppState) DeleteServer(c *gin.Context) {
 			servers = append(servers, srv)
 		}
 	}
+	found := len(servers) != len(s.Config.Servers)
 	s.Config.Servers = servers
-	SaveConfig(s.Config)
+	if found {
+		SaveConfig(s.Config)
+	}
 	s.ConfigMu.Unlock()
 
 	s.AgentMetricsMu.Lock()
 	delete(s.AgentMetrics, id)
 	s.AgentMetricsMu.Unlock()
 
-	c.Status(http.StatusOK)
+	// The agent would otherwise keep reporting until it reconnects
+	s.disconnectAgent(id)
+	return found
 }
 
 func (s *AppState) UpdateServer(c *gin.Context) {
@@ -77,6 +245,34 @@ func (s *AppState) UpdateServer(c *gin.Context) {
 		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request"})
 		return
 	}
+	if req.BillingCycleDay != nil && (*req.BillingCycleDay < 0 || *req.BillingCycleDay > 28) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "billing_cycle_day must be between 1 and 28"})
+		return
+	}
+	if req.RetentionDays != nil && *req.RetentionDays < 0 {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "retention_days must not be negative"})
+		return
+	}
+	if req.SNMP != nil && req.SNMP.Host != "" {
+		if err := req.SNMP.Validate(); err != nil {
+			c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+			return
+		}
+	}
+	if req.Notes != nil || req.Metadata != nil {
+		var notes string
+		var metadata map[string]string
+		if req.Notes != nil {
+			notes = *req.Notes
+		}
+		if req.Metadata != nil {
+			metadata = *req.Metadata
+		}
+		if err := validateServerNotes(notes, metadata); err != nil {
+			c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+			return
+		}
+	}
 
 	s.ConfigMu.Lock()
 	defer s.ConfigMu.Unlock()
@@ -114,6 +310,30 @@ func (s *AppState) UpdateServer(c *gin.Context) {
 			if req.TipBadge != nil {
 				s.Config.Servers[i].TipBadge = *req.TipBadge
 			}
+			if req.BillingCycleDay != nil {
+				s.Config.Servers[i].BillingCycleDay = *req.BillingCycleDay
+			}
+			if req.SNMP != nil {
+				s.Config.Servers[i].SNMP = req.SNMP
+				if req.SNMP.Host == "" {
+					s.Config.Servers[i].SNMP = nil
+				}
+			}
+			if req.PrimaryMount != nil {
+				s.Config.Servers[i].PrimaryMount = strings.TrimSpace(*req.PrimaryMount)
+			}
+			if req.PrimaryPingTarget != nil {
+				s.Config.Servers[i].PrimaryPingTarget = strings.TrimSpace(*req.PrimaryPingTarget)
+			}
+			if req.Notes != nil {
+				s.Config.Servers[i].Notes = *req.Notes
+			}
+			if req.Metadata != nil {
+				s.Config.Servers[i].Metadata = *req.Metadata
+			}
+			if req.RetentionDays != nil {
+				s.Config.Servers[i].RetentionDays = *req.RetentionDays
+			}
 			updated = &s.Config.Servers[i]
 			break
 		}
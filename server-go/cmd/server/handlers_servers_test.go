@@ -0,0 +1,148 @@
+package main
+
+import (
+	"database/sql"
+	"encoding/json"
+	"fmt"
+	"net/http"
+	"net/http/httptest"
+	"path/filepath"
+	"strings"
+	"testing"
+
+	"github.com/gin-gonic/gin"
+)
+
+// seedServerRows inserts one row for serverID into every table with a
+// server_id column and returns those tables
+func seedServerRows(t *testing.T, db *sql.DB, serverID string) []string {
+	t.Helper()
+	tables, err := listTables(db)
+	if err != nil {
+		t.Fatal(err)
+	}
+	var seeded []string
+	for _, table := range tables {
+		rows, err := db.Query(fmt.Sprintf("PRAGMA table_info(%q)", table))
+		if err != nil {
+			t.Fatal(err)
+		}
+		var cols, values []string
+		var args []interface{}
+		hasServerID := false
+		for rows.Next() {
+			var cid, notNull, pk int
+			var name, colType string
+			var dflt sql.NullString
+			rows.Scan(&cid, &name, &colType, &notNull, &dflt, &pk)
+			switch {
+			case name == "server_id":
+				hasServerID = true
+				args = append(args, serverID)
+			case notNull == 0 || dflt.Valid || (pk > 0 && name == "id"):
+				continue
+			case strings.EqualFold(colType, "TEXT"):
+				args = append(args, "2026-01-01T00:00:00Z")
+			default:
+				args = append(args, 1)
+			}
+			cols = append(cols, fmt.Sprintf("%q", name))
+			values = append(values, "?")
+		}
+		rows.Close()
+		if !hasServerID {
+			continue
+		}
+		query := fmt.Sprintf("INSERT INTO %q (%s) VALUES (%s)", table, strings.Join(cols, ","), strings.Join(values, ","))
+		if _, err := db.Exec(query, args...); err != nil {
+			t.Fatalf("%s: %v", table, err)
+		}
+		seeded = append(seeded, table)
+	}
+	if len(seeded) < 10 {
+		t.Fatalf("only %d tables have a server_id column: %v", len(seeded), seeded)
+	}
+	return seeded
+}
+
+func serverRows(t *testing.T, db *sql.DB, tables []string, serverID string) int {
+	t.Helper()
+	total := 0
+	for _, table := range tables {
+		var n int
+		if err := db.QueryRow(fmt.Sprintf("SELECT COUNT(*) FROM %q WHERE server_id = ?", table), serverID).Scan(&n); err != nil {
+			t.Fatal(err)
+		}
+		total += n
+	}
+	return total
+}
+
+func TestPurgeServerData(t *testing.T) {
+	t.Setenv("VSTATS_CONFIG_PATH", filepath.Join(t.TempDir(), ConfigFilename))
+	gin.SetMode(gin.TestMode)
+
+	db := openTestDB(t)
+	tables := seedServerRows(t, db, "s1")
+	seedServerRows(t, db, "s2")
+	seedServerRows(t, db, "s3")
+
+	state := NewAppState(&AppConfig{Servers: []RemoteServer{{ID: "s1"}, {ID: "s2"}, {ID: "s3"}}}, db)
+	router := gin.New()
+	router.DELETE("/api/servers/:id", state.DeleteServer)
+	router.DELETE("/api/servers/:id/history", state.PurgeServerHistory)
+	request := func(path string) (int, PurgeResult) {
+		w := httptest.NewRecorder()
+		router.ServeHTTP(w, httptest.NewRequest(http.MethodDelete, path, nil))
+		var result PurgeResult
+		json.Unmarshal(w.Body.Bytes(), &result)
+		return w.Code, result
+	}
+	configured := func(id string) bool {
+		for _, s := range state.Config.Servers {
+			if s.ID == id {
+				return true
+			}
+		}
+		return false
+	}
+
+	// Deleting purges by default
+	code, result := request("/api/servers/s1")
+	if code != http.StatusOK || result.Rows != int64(len(tables)) || len(result.Tables) != len(tables) {
+		t.Errorf("delete: %d, %+v; want %d rows", code, result, len(tables))
+	}
+	if n := serverRows(t, db, tables, "s1"); n != 0 || configured("s1") {
+		t.Errorf("s1: %d rows left, configured %v", n, configured("s1"))
+	}
+	if n := serverRows(t, db, tables, "s2"); n != len(tables) {
+		t.Errorf("s2 lost rows: %d left, want %d", n, len(tables))
+	}
+
+	// purge=false keeps the history
+	if code, _ := request("/api/servers/s2?purge=false"); code != http.StatusOK {
+		t.Errorf("delete without purge: %d", code)
+	}
+	if n := serverRows(t, db, tables, "s2"); n != len(tables) || configured("s2") {
+		t.Errorf("s2: %d rows left, configured %v", n, configured("s2"))
+	}
+
+	// The history endpoint keeps the server, and cleans up orphans too
+	for _, id := range []string{"s3", "s2"} {
+		code, result := request("/api/servers/" + id + "/history")
+		if code != http.StatusOK || result.Rows != int64(len(tables)) {
+			t.Errorf("purge %s: %d, %+v", id, code, result)
+		}
+		if n := serverRows(t, db, tables, id); n != 0 {
+			t.Errorf("%s: %d rows left", id, n)
+		}
+	}
+	if !configured("s3") {
+		t.Error("purging history removed the server")
+	}
+
+	// Nothing left to purge
+	if code, result := request("/api/servers/s3/history"); code != http.StatusOK || result.Rows != 0 {
+		t.Errorf("second purge: %d, %+v", code, result)
+	}
+}
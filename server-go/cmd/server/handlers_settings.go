@@ -4,6 +4,7 @@ import (
 	"encoding/json"
 	"log"
 	"net/http"
+	"strings"
 
 	"vstats/internal/common"
 
@@ -28,6 +29,10 @@ func (s *AppState) UpdateSiteSettings(c *gin.Context) {
 	}
 
 	s.ConfigMu.Lock()
+	// Older dashboards don't send public_mode; keep the existing setting
+	if settings.PublicMode == nil {
+		settings.PublicMode = s.Config.SiteSettings.PublicMode
+	}
 	s.Config.SiteSettings = settings
 	SaveConfig(s.Config)
 	s.ConfigMu.Unlock()
@@ -102,6 +107,8 @@ func (s *AppState) UpdateProbeSettings(c *gin.Context) {
 		return
 	}
 
+	settings.PrimaryPingTarget = strings.TrimSpace(settings.PrimaryPingTarget)
+
 	s.ConfigMu.Lock()
 	s.Config.ProbeSettings = settings
 	SaveConfig(s.Config)
@@ -6,6 +6,9 @@ import (
 	"io"
 	"net/http"
 	"os/exec"
+	"strconv"
+	"strings"
+	"sync"
 	"time"
 
 	"github.com/gin-gonic/gin"
@@ -23,20 +26,129 @@ func GetServerVersion(c *gin.Context) {
 	c.JSON(http.StatusOK, ServerVersionInfo{Version: ServerVersion})
 }
 
-func CheckLatestVersion(c *gin.Context) {
-	latest, err := fetchLatestGitHubVersion("zsai001", "vstats")
+// CheckLatestVersion compares the server version, or the version given in
+// ?current= (used by agents), against the latest release
+func (s *AppState) CheckLatestVersion(c *gin.Context) {
+	current := c.DefaultQuery("current", ServerVersion)
+	latest, err := s.LatestVersion.Get()
 	updateAvailable := false
-	if err == nil && latest != nil && *latest != ServerVersion {
+	if err == nil && latest != nil && compareVersions(current, *latest) < 0 {
 		updateAvailable = true
 	}
 
 	c.JSON(http.StatusOK, VersionInfo{
-		Current:         ServerVersion,
+		Current:         current,
 		Latest:          latest,
 		UpdateAvailable: updateAvailable,
 	})
 }
 
+// ============================================================================
+// Latest Release Cache
+// ============================================================================
+
+const (
+	// LatestVersionTTL is how long a GitHub release lookup is reused
+	LatestVersionTTL = time.Hour
+	// latestVersionRetry is how soon a failed lookup is retried
+	latestVersionRetry = 5 * time.Minute
+)
+
+// LatestVersionCache holds the latest vstats release so dashboard users and
+// agent checks don't each query the rate-limited GitHub API
+type LatestVersionCache struct {
+	mu        sync.Mutex
+	version   *string
+	err       error
+	fetchedAt time.Time
+	fetching  bool
+}
+
+// fresh reports whether the cached result is still valid. Callers hold mu.
+func (c *LatestVersionCache) fresh() bool {
+	ttl := LatestVersionTTL
+	if c.err != nil {
+		ttl = latestVersionRetry
+	}
+	return !c.fetchedAt.IsZero() && time.Since(c.fetchedAt) < ttl
+}
+
+// Get returns the latest release, querying GitHub only once the cached
+// result has expired
+func (c *LatestVersionCache) Get() (*string, error) {
+	c.mu.Lock()
+	if c.fresh() || (c.fetching && c.version != nil) {
+		defer c.mu.Unlock()
+		return c.version, c.err
+	}
+	c.fetching = true
+	c.mu.Unlock()
+	return c.refresh()
+}
+
+// Peek returns the cached latest release without waiting for GitHub. An
+// expired result is refreshed in the background.
+func (c *LatestVersionCache) Peek() *string {
+	c.mu.Lock()
+	defer c.mu.Unlock()
+	if !c.fresh() && !c.fetching {
+		c.fetching = true
+		go c.refresh()
+	}
+	return c.version
+}
+
+// refresh queries GitHub. A failed lookup keeps the last known version.
+func (c *LatestVersionCache) refresh() (*string, error) {
+	version, err := fetchLatestGitHubVersion("zsai001", "vstats")
+
+	c.mu.Lock()
+	defer c.mu.Unlock()
+	c.fetching = false
+	c.fetchedAt = time.Now()
+	c.err = err
+	if err == nil {
+		c.version = version
+	} else if c.version != nil {
+		c.err = nil
+		c.fetchedAt = time.Now().Add(latestVersionRetry - LatestVersionTTL)
+	}
+	return c.version, c.err
+}
+
+// agentUpdateAvailable reports whether an agent version is older than the
+// latest release. It never waits for GitHub.
+func (s *AppState) agentUpdateAvailable(version string) bool {
+	if version == "" || s.LatestVersion == nil {
+		return false
+	}
+	latest := s.LatestVersion.Peek()
+	return latest != nil && compareVersions(version, *latest) < 0
+}
+
+// compareVersions compares dotted numeric versions such as "1.2.10",
+// ignoring a leading "v" and any "-suffix"
+func compareVersions(a, b string) int {
+	pa := strings.Split(strings.SplitN(strings.TrimPrefix(a, "v"), "-", 2)[0], ".")
+	pb := strings.Split(strings.SplitN(strings.TrimPrefix(b, "v"), "-", 2)[0], ".")
+	for i := 0; i < len(pa) || i < len(pb); i++ {
+		var na, nb int
+		if i < len(pa) {
+			na, _ = strconv.Atoi(pa[i])
+		}
+		if i < len(pb) {
+			nb, _ = strconv.Atoi(pb[i])
+		}
+		if na != nb {
+			if na < nb {
+				return -1
+			}
+			return 1
+		}
+	}
+	return 0
+}
+
 // ============================================================================
 // Server Upgrade Handler
 // ============================================================================
@@ -309,7 +309,7 @@ func GetCustomWallpaper(c *gin.Context) {
 	}
 
 	// For external URLs, return proxy URL
-	proxyURL := fmt.Sprintf("/api/wallpaper/proxy/image?url=%s", url.QueryEscape(imageURL))
+	proxyURL := fmt.Sprintf("%s/api/wallpaper/proxy/image?url=%s", BasePath(), url.QueryEscape(imageURL))
 	c.JSON(http.StatusOK, gin.H{"url": proxyURL, "proxy": true})
 }
 
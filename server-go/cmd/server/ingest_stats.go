@@ -0,0 +1,87 @@
+package main
+
+import (
+	"sync"
+	"sync/atomic"
+	"time"
+)
+
+// ============================================================================
+// Ingestion Statistics
+// ============================================================================
+//
+// Cheap counters for /api/admin/stats: metrics samples received from agents
+// per second over the last minute, and dashboard broadcast activity.
+
+// IngestStats counts received metrics and dashboard broadcasts
+type IngestStats struct {
+	mu      sync.Mutex
+	samples [60]int64 // Samples received per second, indexed by unix second % 60
+	seconds [60]int64 // Unix second each slot counts
+
+	broadcasts     atomic.Uint64
+	droppedClients atomic.Uint64
+	lastBroadcast  atomic.Int64 // Duration of the last broadcast in ns
+	maxBroadcast   atomic.Int64
+}
+
+func NewIngestStats() *IngestStats {
+	return &IngestStats{}
+}
+
+// RecordMetrics counts n metrics samples received now
+func (i *IngestStats) RecordMetrics(n int) {
+	now := time.Now().Unix()
+	slot := now % 60
+	i.mu.Lock()
+	if i.seconds[slot] != now {
+		i.seconds[slot], i.samples[slot] = now, 0
+	}
+	i.samples[slot] += int64(n)
+	i.mu.Unlock()
+}
+
+// MetricsLastMinute returns the samples received in the last 60 seconds
+func (i *IngestStats) MetricsLastMinute() int64 {
+	now := time.Now().Unix()
+	var total int64
+	i.mu.Lock()
+	for slot := range i.samples {
+		if now-i.seconds[slot] < 60 {
+			total += i.samples[slot]
+		}
+	}
+	i.mu.Unlock()
+	return total
+}
+
+// RecordBroadcast counts a broadcast that took d and dropped clients whose
+// connection failed
+func (i *IngestStats) RecordBroadcast(d time.Duration, dropped int) {
+	i.broadcasts.Add(1)
+	i.droppedClients.Add(uint64(dropped))
+	i.lastBroadcast.Store(int64(d))
+	for {
+		prev := i.maxBroadcast.Load()
+		if int64(d) <= prev || i.maxBroadcast.CompareAndSwap(prev, int64(d)) {
+			break
+		}
+	}
+}
+
+// BroadcastStats reports dashboard broadcast activity since startup
+type BroadcastStats struct {
+	Broadcasts     uint64  `json:"broadcasts"`
+	DroppedClients uint64  `json:"dropped_clients"` // Closed after a failed write
+	LastMs         float64 `json:"last_ms"`         // Time to write the last broadcast to all clients
+	MaxMs          float64 `json:"max_ms"`
+}
+
+func (i *IngestStats) Broadcast() BroadcastStats {
+	return BroadcastStats{
+		Broadcasts:     i.broadcasts.Load(),
+		DroppedClients: i.droppedClients.Load(),
+		LastMs:         float64(i.lastBroadcast.Load()) / float64(time.Millisecond),
+		MaxMs:          float64(i.maxBroadcast.Load()) / float64(time.Millisecond),
+	}
+}
@@ -0,0 +1,101 @@
+package main
+
+import (
+	"database/sql"
+	"log/slog"
+	"sync/atomic"
+	"time"
+)
+
+// ============================================================================
+// Login Audit Log
+// ============================================================================
+
+// DefaultLoginAuditRetentionDays is how long login_audit entries are kept
+const DefaultLoginAuditRetentionDays = 90
+
+// MaxLoginAuditEntries caps the number of entries returned per request
+const MaxLoginAuditEntries = 1000
+
+var loginAuditRetentionDays atomic.Int64
+
+func init() {
+	loginAuditRetentionDays.Store(DefaultLoginAuditRetentionDays)
+}
+
+// SetLoginAuditRetention sets how many days of login_audit entries to keep
+func SetLoginAuditRetention(days int) {
+	if days <= 0 {
+		days = DefaultLoginAuditRetentionDays
+	}
+	loginAuditRetentionDays.Store(int64(days))
+}
+
+// LoginAuditEntry is a single login attempt
+type LoginAuditEntry struct {
+	ID        int64  `json:"id"`
+	Timestamp string `json:"timestamp"`
+	Username  string `json:"username"`
+	Method    string `json:"method"` // password, github, google, or the OAuth proxy provider
+	ClientIP  string `json:"client_ip"`
+	Success   bool   `json:"success"`
+	Reason    string `json:"reason,omitempty"` // Why a failed attempt was refused
+}
+
+// RecordLogin queues a login attempt for the audit log. Failed attempts are
+// also logged at warn level with their source IP, so brute-forcing shows up
+// in the server log.
+func RecordLogin(username, method, clientIP string, success bool, reason string) {
+	if !success {
+		slog.Warn("login failed", "ip", clientIP, "method", method, "user", username, "reason", reason)
+	}
+	if dbWriter == nil {
+		return
+	}
+	timestamp := time.Now().UTC().Format(time.RFC3339)
+	dbWriter.WriteAsync(func(db *sql.DB) error {
+		_, err := db.Exec(`INSERT INTO login_audit (timestamp, username, method, client_ip, success, reason)
+			VALUES (?, ?, ?, ?, ?, ?)`, timestamp, username, method, clientIP, success, reason)
+		return err
+	})
+}
+
+// GetLoginAudit returns the most recent login attempts, newest first
+func GetLoginAudit(db *sql.DB, limit int, failedOnly bool) ([]LoginAuditEntry, error) {
+	query := `SELECT id, timestamp, username, method, client_ip, success, reason FROM login_audit`
+	if failedOnly {
+		query += ` WHERE success = 0`
+	}
+	query += ` ORDER BY timestamp DESC, id DESC LIMIT ?`
+
+	rows, err := db.Query(query, limit)
+	if err != nil {
+		return nil, err
+	}
+	defer rows.Close()
+
+	entries := []LoginAuditEntry{}
+	for rows.Next() {
+		var e LoginAuditEntry
+		if err := rows.Scan(&e.ID, &e.Timestamp, &e.Username, &e.Method, &e.ClientIP, &e.Success, &e.Reason); err != nil {
+			return nil, err
+		}
+		entries = append(entries, e)
+	}
+	return entries, rows.Err()
+}
+
+// CountFailedLogins returns the failed password logins since the given time
+func CountFailedLogins(db *sql.DB, since time.Time) int64 {
+	var n int64
+	db.QueryRow(`SELECT COUNT(*) FROM login_audit WHERE success = 0 AND method = 'password' AND timestamp >= ?`,
+		since.UTC().Format(time.RFC3339)).Scan(&n)
+	return n
+}
+
+// pruneLoginAudit deletes entries older than the retention period
+func pruneLoginAudit(db *sql.DB) {
+	days := loginAuditRetentionDays.Load()
+	cutoff := time.Now().UTC().Add(-time.Duration(days) * 24 * time.Hour).Format(time.RFC3339)
+	db.Exec("DELETE FROM login_audit WHERE timestamp < ?", cutoff)
+}
@@ -0,0 +1,154 @@
+package main
+
+import (
+	"net/http"
+	"net/http/httptest"
+	"path/filepath"
+	"strings"
+	"testing"
+	"time"
+
+	"github.com/gin-gonic/gin"
+)
+
+func TestLoginLimiterBucket(t *testing.T) {
+	l := NewLoginLimiter()
+	now := time.Date(2026, 1, 1, 0, 0, 0, 0, time.UTC)
+
+	for i := 0; i < loginBucketSize; i++ {
+		if ok, _ := l.Allow("10.0.0.1", now); !ok {
+			t.Fatalf("attempt %d of the burst refused", i+1)
+		}
+	}
+	ok, wait := l.Allow("10.0.0.1", now)
+	if ok || wait != loginBucketRefill {
+		t.Errorf("past the burst: %v, wait %s; want refused for %s", ok, wait, loginBucketRefill)
+	}
+	// Other clients have their own bucket
+	if ok, _ := l.Allow("10.0.0.2", now); !ok {
+		t.Error("another IP refused")
+	}
+
+	// One attempt comes back per refill interval
+	now = now.Add(loginBucketRefill)
+	if ok, _ := l.Allow("10.0.0.1", now); !ok {
+		t.Error("attempt refused after a refill")
+	}
+	if ok, _ := l.Allow("10.0.0.1", now); ok {
+		t.Error("refill gave more than one attempt")
+	}
+	if stats := l.Stats(now); stats.Throttled != 2 || stats.Lockouts != 0 {
+		t.Errorf("stats = %+v", stats)
+	}
+}
+
+func TestLoginLimiterLockout(t *testing.T) {
+	l := NewLoginLimiter()
+	now := time.Date(2026, 1, 1, 0, 0, 0, 0, time.UTC)
+	const ip = "10.0.0.1"
+
+	// Failures spaced out so the bucket never runs dry
+	fail := func() {
+		now = now.Add(loginBucketSize * loginBucketRefill)
+		if ok, _ := l.Allow(ip, now); !ok {
+			t.Fatalf("attempt refused at %s", now)
+		}
+		l.Failure(ip, now)
+	}
+	for i := 0; i < LoginLockoutThreshold; i++ {
+		fail()
+	}
+	ok, wait := l.Allow(ip, now)
+	if ok || wait != loginLockoutBase {
+		t.Fatalf("after %d failures: %v, wait %s; want locked out for %s", LoginLockoutThreshold, ok, wait, loginLockoutBase)
+	}
+	if stats := l.Stats(now); stats.LockedIPs != 1 || stats.Lockouts != 1 {
+		t.Errorf("stats = %+v", stats)
+	}
+
+	// The lockout ends with its window, and doubles with the next failure
+	now = now.Add(loginLockoutBase)
+	if ok, _ := l.Allow(ip, now); !ok {
+		t.Fatal("still locked out after the window")
+	}
+	l.Failure(ip, now)
+	if _, wait := l.Allow(ip, now); wait != 2*loginLockoutBase {
+		t.Errorf("second lockout: wait %s, want %s", wait, 2*loginLockoutBase)
+	}
+
+	// and never exceeds the maximum
+	for i := 0; i < 10; i++ {
+		now = now.Add(loginLockoutMax)
+		fail()
+	}
+	if _, wait := l.Allow(ip, now); wait != loginLockoutMax {
+		t.Errorf("long lockout: wait %s, want %s", wait, loginLockoutMax)
+	}
+
+	// A successful login clears it all
+	l.Success(ip)
+	if ok, _ := l.Allow(ip, now); !ok {
+		t.Error("refused after a successful login")
+	}
+	if stats := l.Stats(now); stats.LockedIPs != 0 {
+		t.Errorf("stats = %+v", stats)
+	}
+}
+
+// elapse moves the limiter d into the future, as far as its clients are
+// concerned
+func (l *LoginLimiter) elapse(d time.Duration) {
+	l.mu.Lock()
+	defer l.mu.Unlock()
+	for _, a := range l.ips {
+		a.updated = a.updated.Add(-d)
+		a.lockedUntil = a.lockedUntil.Add(-d)
+	}
+}
+
+func TestLoginRateLimit(t *testing.T) {
+	t.Setenv("VSTATS_CONFIG_PATH", filepath.Join(t.TempDir(), ConfigFilename))
+	gin.SetMode(gin.TestMode)
+
+	settings := &PasswordHashSettings{BcryptCost: 4}
+	hash, err := HashPassword("correct horse", settings)
+	if err != nil {
+		t.Fatal(err)
+	}
+	config := &AppConfig{AdminPasswordHash: hash, PasswordHashing: settings, JWTSecret: "test-secret"}
+	// A failed login reloads the config from disk
+	SaveConfig(config)
+	InitJWTSecret(config.JWTSecret)
+	state := NewAppState(config, openTestDB(t))
+
+	router := gin.New()
+	router.POST("/api/auth/login", state.Login)
+	login := func(password string) *httptest.ResponseRecorder {
+		w := httptest.NewRecorder()
+		req := httptest.NewRequest(http.MethodPost, "/api/auth/login", strings.NewReader(`{"password": "`+password+`"}`))
+		req.Header.Set("Content-Type", "application/json")
+		req.RemoteAddr = "192.0.2.1:40000"
+		router.ServeHTTP(w, req)
+		return w
+	}
+
+	for i := 0; i < LoginLockoutThreshold; i++ {
+		if w := login("guess"); w.Code != http.StatusUnauthorized {
+			t.Fatalf("guess %d: %d, want %d", i+1, w.Code, http.StatusUnauthorized)
+		}
+	}
+	// Locked out: even the right password is refused, with a Retry-After
+	w := login("correct horse")
+	if w.Code != http.StatusTooManyRequests || w.Header().Get("Retry-After") != "60" {
+		t.Errorf("while locked out: %d, Retry-After %q", w.Code, w.Header().Get("Retry-After"))
+	}
+
+	// Once the window has passed the admin gets in, and the slate is clean
+	state.LoginLimiter.elapse(loginLockoutBase)
+	if w := login("correct horse"); w.Code != http.StatusOK || !strings.Contains(w.Body.String(), `"token"`) {
+		t.Errorf("after the window: %d %s", w.Code, w.Body.String())
+	}
+	if w := login("guess"); w.Code != http.StatusUnauthorized {
+		t.Errorf("guess after a successful login: %d, want %d", w.Code, http.StatusUnauthorized)
+	}
+}
@@ -5,12 +5,13 @@ import (
 	"encoding/json"
 	"fmt"
 	"os"
+	"os/signal"
 	"path/filepath"
 	"strings"
+	"syscall"
 	"time"
 
 	"github.com/gin-gonic/gin"
-	"github.com/gorilla/websocket"
 )
 
 // Version will be set at build time via -ldflags
@@ -18,7 +19,11 @@ var ServerVersion = "dev"
 
 func main() {
 	// Check for command line arguments
-	args := os.Args[1:]
+	args, err := ParsePathFlags(os.Args[1:])
+	if err != nil {
+		fmt.Printf("❌ %v\n", err)
+		os.Exit(1)
+	}
 
 	if len(args) > 0 {
 		switch args[0] {
@@ -28,6 +33,21 @@ func main() {
 		case "--check":
 			showDiagnostics()
 			return
+		case "--import":
+			if len(args) < 2 {
+				fmt.Println("Usage: vstats-server --import <vstats-export.ndjson.gz>")
+				os.Exit(1)
+			}
+			result, err := ImportFile(args[1])
+			if err != nil {
+				fmt.Printf("❌ Import failed: %v\n", err)
+				os.Exit(1)
+			}
+			fmt.Printf("📦 Imported %d new servers\n", result.Servers)
+			for table, rows := range result.Tables {
+				fmt.Printf("   %s: %d rows\n", table, rows)
+			}
+			return
 		case "--reset-password":
 			password := ResetAdminPassword()
 			fmt.Println("\n╔════════════════════════════════════════════════════════════════╗")
@@ -75,6 +95,12 @@ func main() {
 		}
 	}
 
+	if err := PrepareDataPaths(); err != nil {
+		fmt.Printf("❌ %v\n", err)
+		fmt.Println("   Use --config/--data-dir or VSTATS_CONFIG_PATH/VSTATS_DATA_DIR/VSTATS_DB_PATH to choose a writable location")
+		os.Exit(1)
+	}
+
 	// Initialize database
 	db, err := InitDatabase()
 	if err != nil {
@@ -88,16 +114,6 @@ func main() {
 	dbWriter = NewDBWriter(db, 100)
 	defer dbWriter.Close()
 
-	// Initialize metrics buffer for batched real-time metrics writes
-	// Flush every 1 second or when buffer reaches 1000 items
-	metricsBuffer = NewMetricsBuffer(1*time.Second, 1000)
-	defer metricsBuffer.Close()
-
-	// Initialize aggregation buffer for batched writes (flush every 1 second)
-	aggBuffer = NewAggBuffer(1 * time.Second)
-	defer aggBuffer.Close()
-	fmt.Println("📊 Batch write buffers initialized (flush every 1s, supports 3000+ agents)")
-
 	// Initialize history cache with 10 second TTL
 	InitHistoryCache(10 * time.Second)
 
@@ -117,28 +133,51 @@ func main() {
 		fmt.Println("╚════════════════════════════════════════════════════════════════╝")
 	}
 
-	// Create app state
-	state := &AppState{
-		Config:           config,
-		MetricsBroadcast: make(chan string, 16),
-		AgentMetrics:     make(map[string]*AgentMetricsData),
-		AgentConns:       make(map[string]*AgentConnection),
-		LastSent: &LastSentState{
-			Servers: make(map[string]*struct {
-				Online  bool
-				Metrics *CompactMetrics
-			}),
-		},
-		DashboardClients: make(map[*websocket.Conn]*DashboardClient),
-		DB:               db,
+	// Sample metrics_raw writes if configured (live updates stay per-second)
+	SetRawSampleInterval(config.RawSampleIntervalSecs)
+	SetMetricsBlobs(config.MetricsBlobs)
+	SetLoginAuditRetention(config.LoginAuditRetentionDays)
+	SetDiskHistory(config.DiskHistoryIntervalSecs, config.DiskHistoryMounts)
+	SetPrimaryMounts(config.Servers)
+	SetPrimaryPingTargets(config)
+	SetServerRetention(config.Servers)
+	SetMetricsListLimits(config.WSLimits)
+	SetTokenGeneration(config.TokenGeneration)
+	SetPingHistoryInterval(config.PingHistoryIntervalSecs)
+	SetBasePath(config.BasePath)
+	if err := SetAggregationTimezone(config.Timezone); err != nil {
+		fmt.Printf("⚠️  %v; using UTC\n", err)
+	} else if config.Timezone != "" {
+		fmt.Printf("🕐 Daily aggregation timezone: %s\n", config.Timezone)
+	}
+	if config.RawSampleIntervalSecs > 1 {
+		fmt.Printf("📉 Raw metrics sampled every %ds\n", config.RawSampleIntervalSecs)
 	}
 
+	// Initialize metrics buffer for batched real-time metrics writes
+	// Flush every metrics_flush_interval_ms or when metrics_flush_max_rows are pending
+	metricsBuffer = NewMetricsBuffer(config.MetricsFlushInterval(), config.MetricsFlushRows())
+	defer metricsBuffer.Close()
+
+	// Initialize aggregation buffer for batched writes (flush every 1 second)
+	aggBuffer = NewAggBuffer(1 * time.Second)
+	defer aggBuffer.Close()
+	fmt.Printf("📊 Batch write buffers initialized (metrics flush every %v or %d rows)\n",
+		config.MetricsFlushInterval(), config.MetricsFlushRows())
+
+	// Create app state
+	state := NewAppState(config, db)
+
+	// Flush buffered metrics and pending config changes on SIGINT/SIGTERM
+	SetupShutdownHandler(state)
+
 	// Initialize local metrics collector with ping targets
 	localCollector := GetLocalCollector()
 	if len(config.ProbeSettings.PingTargets) > 0 {
 		localCollector.SetPingTargets(config.ProbeSettings.PingTargets)
 		fmt.Printf("📡 Ping targets configured: %d targets\n", len(config.ProbeSettings.PingTargets))
 	}
+	localCollector.StartMetricsLoop()
 
 	// Setup signal handler for config reload (SIGHUP)
 	SetupSignalHandler(state)
@@ -148,6 +187,12 @@ func main() {
 	go metricsBroadcastLoop(state) // Broadcast delta updates to connected dashboards
 	// NOTE: aggregation15MinLoop and aggregationLoop removed - aggregation now done on agent side
 	go cleanupLoop(db)
+	go percentileLoop(db)
+	go agentUpdateExpiryLoop(state)
+	go snmpPollLoop(state)
+	go otlpLoop(state)
+	go backupLoop(state)
+	go maintenanceLoop(db)
 
 	// Setup routes
 	gin.SetMode(gin.ReleaseMode)
@@ -156,6 +201,14 @@ func main() {
 	// Trust proxy headers (for X-Forwarded-Proto, X-Forwarded-For, etc.)
 	// This allows the app to correctly detect HTTPS when behind nginx
 	r.SetTrustedProxies([]string{"127.0.0.1", "::1"}) // Trust localhost proxies
+	// trusted_proxies replaces the localhost default. c.ClientIP() only reads
+	// X-Forwarded-For/X-Real-IP when the direct peer is one of them.
+	if len(config.TrustedProxies) > 0 {
+		if err := r.SetTrustedProxies(config.TrustedProxies); err != nil {
+			fmt.Printf("⚠️  Invalid trusted_proxies (%v); trusting localhost only\n", err)
+			r.SetTrustedProxies([]string{"127.0.0.1", "::1"})
+		}
+	}
 	// Also trust all proxies if VSTATS_TRUST_ALL_PROXIES is set
 	if os.Getenv("VSTATS_TRUST_ALL_PROXIES") == "true" {
 		r.SetTrustedProxies(nil) // nil means trust all proxies
@@ -173,53 +226,96 @@ func main() {
 		c.Next()
 	})
 
+	// Every route is nested under base_path ("" serves them at the root)
+	app := r.Group(BasePath())
+
 	// Public routes
-	r.GET("/health", HealthCheck)
-	r.GET("/api/metrics", state.GetMetrics)
-	r.GET("/api/metrics/all", state.GetAllMetrics)
-	r.GET("/api/online-users", state.GetOnlineUsers)
-	r.GET("/api/history/:server_id", func(c *gin.Context) {
+	app.GET("/health", HealthCheck)
+	app.GET("/api/metrics", state.GetMetrics)
+	app.GET("/api/metrics/all", state.GetAllMetrics)
+	app.GET("/api/online-users", state.GetOnlineUsers)
+	app.GET("/api/history/compare", func(c *gin.Context) {
+		state.GetHistoryCompare(c, db)
+	})
+	app.GET("/api/history/:server_id", func(c *gin.Context) {
 		state.GetHistory(c, db)
 	})
-	r.GET("/api/servers", state.GetServers)
-	r.GET("/api/groups", state.GetGroups)
-	r.GET("/api/dimensions", state.GetDimensions) // Public: get all dimensions for grouping
-	r.GET("/api/settings/site", state.GetSiteSettings)
-	r.GET("/api/wallpaper/bing", GetBingWallpaper)
-	r.GET("/api/wallpaper/unsplash", GetUnsplashWallpaper)
-	r.GET("/api/wallpaper/proxy", GetCustomWallpaper)
-	r.GET("/api/wallpaper/proxy/image", GetCustomWallpaperImage)
-	r.POST("/api/auth/login", state.Login)
-	r.GET("/api/auth/verify", AuthMiddleware(), state.VerifyToken)
+	app.GET("/api/history/:server_id/disks", func(c *gin.Context) {
+		state.GetDiskHistory(c, db)
+	})
+	app.GET("/api/history/:server_id/ping", func(c *gin.Context) {
+		state.GetPingTargetHistory(c, db)
+	})
+	app.GET("/api/servers", state.GetServers)
+	app.GET("/api/servers/:id/bandwidth", func(c *gin.Context) {
+		state.GetBandwidth(c, db)
+	})
+	app.GET("/api/servers/:id/uptime", func(c *gin.Context) {
+		state.GetUptime(c, db)
+	})
+	app.GET("/api/groups", state.GetGroups)
+	app.GET("/api/dimensions", state.GetDimensions) // Public: get all dimensions for grouping
+	app.GET("/api/settings/site", state.GetSiteSettings)
+	app.GET("/api/public/status", state.GetPublicStatus)
+	app.GET("/api/wallpaper/bing", GetBingWallpaper)
+	app.GET("/api/wallpaper/unsplash", GetUnsplashWallpaper)
+	app.GET("/api/wallpaper/proxy", GetCustomWallpaper)
+	app.GET("/api/wallpaper/proxy/image", GetCustomWallpaperImage)
+	app.POST("/api/auth/login", state.Login)
+	app.POST("/api/auth/2fa/verify", state.VerifyTwoFactor)
+	app.GET("/api/auth/verify", AuthMiddleware(state), state.VerifyToken)
 
 	// OAuth 2.0 routes (public)
-	r.GET("/api/auth/oauth/providers", state.GetOAuthProviders)
-	r.GET("/api/auth/oauth/github", state.GitHubOAuthStart)
-	r.GET("/api/auth/oauth/github/callback", state.GitHubOAuthCallback)
-	r.GET("/api/auth/oauth/google", state.GoogleOAuthStart)
-	r.GET("/api/auth/oauth/google/callback", state.GoogleOAuthCallback)
-	r.GET("/api/auth/oauth/proxy/callback", state.ProxyOAuthCallback) // Centralized OAuth callback
-	r.GET("/api/install-command", AuthMiddleware(), state.GetInstallCommand)
-	r.GET("/api/version", GetServerVersion)
-	r.GET("/version", GetServerVersion)
-	r.GET("/api/version/check", CheckLatestVersion)
-	r.GET("/agent.sh", state.GetAgentScript)
-	r.GET("/agent.ps1", state.GetAgentPowerShellScript)
-	r.GET("/agent-upgrade.ps1", state.GetAgentUpgradePowerShellScript)
-	r.GET("/agent-uninstall.ps1", state.GetAgentUninstallPowerShellScript)
-	r.GET("/ws", state.HandleDashboardWS)
-	r.GET("/ws/agent", state.HandleAgentWS)
+	app.GET("/api/auth/oauth/providers", state.GetOAuthProviders)
+	app.GET("/api/auth/oauth/github", state.GitHubOAuthStart)
+	app.GET("/api/auth/oauth/github/callback", state.GitHubOAuthCallback)
+	app.GET("/api/auth/oauth/google", state.GoogleOAuthStart)
+	app.GET("/api/auth/oauth/google/callback", state.GoogleOAuthCallback)
+	app.GET("/api/auth/oauth/proxy/callback", state.ProxyOAuthCallback) // Centralized OAuth callback
+	app.GET("/api/install-command", AuthMiddleware(state), state.GetInstallCommand)
+	app.GET("/api/version", GetServerVersion)
+	app.GET("/version", GetServerVersion)
+	app.GET("/api/version/check", state.CheckLatestVersion)
+	app.GET("/agent.sh", state.GetAgentScript)
+	app.GET("/agent.ps1", state.GetAgentPowerShellScript)
+	app.GET("/agent-upgrade.ps1", state.GetAgentUpgradePowerShellScript)
+	app.GET("/agent-uninstall.ps1", state.GetAgentUninstallPowerShellScript)
+	app.GET("/ws", state.HandleDashboardWS)
+	app.GET("/ws/agent", state.HandleAgentWS)
 
 	// Protected routes
-	protected := r.Group("/")
-	protected.Use(AuthMiddleware())
+	protected := app.Group("/")
+	protected.Use(AuthMiddleware(state))
 	{
 		protected.POST("/api/servers", state.AddServer)
+		protected.POST("/api/servers/bulk", state.AddServersBulk)
 		protected.DELETE("/api/servers/:id", state.DeleteServer)
+		protected.DELETE("/api/servers/:id/history", state.PurgeServerHistory)
 		protected.PUT("/api/servers/:id", state.UpdateServer)
 		protected.POST("/api/servers/:id/update", state.UpdateAgent)
+		protected.GET("/api/servers/:id/update", state.GetAgentUpdateStatus)
+		protected.POST("/api/servers/:id/rotate-token", state.RotateAgentToken)
+		protected.POST("/api/agents/update-all", state.UpdateAllAgents)
+		protected.GET("/api/agents/update-all", state.GetUpdateAllJob)
+		protected.GET("/api/agents/connections", state.GetAgentConnections)
+		protected.GET("/api/servers/:id/connections", state.GetServerConnectionEvents)
+		protected.GET("/api/events/agents", state.GetAgentEvents)
+		protected.GET("/api/alerts", state.GetAlerts)
+		protected.POST("/api/alerts/:id/ack", state.AckAlert)
 		protected.POST("/api/auth/password", state.ChangePassword)
+		protected.POST("/api/auth/logout", state.Logout)
+		protected.POST("/api/auth/revoke-all", state.RevokeAllTokens)
+		protected.GET("/api/auth/2fa", state.GetTwoFactorStatus)
+		protected.POST("/api/auth/2fa/setup", state.SetupTwoFactor)
+		protected.POST("/api/auth/2fa/enable", state.EnableTwoFactor)
+		protected.POST("/api/auth/2fa/disable", state.DisableTwoFactor)
+		protected.GET("/api/auth/audit", state.GetLoginAudit)
+		// API keys (full keys are only returned on creation)
+		protected.GET("/api/keys", state.GetAPIKeys)
+		protected.POST("/api/keys", state.CreateAPIKey)
+		protected.DELETE("/api/keys/:id", state.DeleteAPIKey)
 		protected.POST("/api/agent/register", state.RegisterAgent)
+		protected.DELETE("/api/agent/register/:id", state.UnregisterAgent)
 		protected.PUT("/api/settings/site", state.UpdateSiteSettings)
 		protected.GET("/api/settings/local-node", state.GetLocalNodeConfig)
 		protected.PUT("/api/settings/local-node", state.UpdateLocalNodeConfig)
@@ -241,23 +337,35 @@ func main() {
 		protected.POST("/api/dimensions/:id/options", state.AddOption)
 		protected.PUT("/api/dimensions/:id/options/:option_id", state.UpdateOption)
 		protected.DELETE("/api/dimensions/:id/options/:option_id", state.DeleteOption)
+		// Database administration
+		protected.GET("/api/admin/stats", state.GetAdminStats)
+		protected.GET("/api/admin/storage", state.GetStorage)
+		protected.POST("/api/admin/vacuum", state.VacuumDatabase)
+		protected.POST("/api/admin/db/optimize", state.VacuumDatabase)
+		protected.POST("/api/admin/backup", state.BackupDatabase)
+		protected.GET("/api/admin/export", state.ExportData)
+		protected.GET("/api/history/:server_id/export", func(c *gin.Context) {
+			state.ExportHistory(c, db)
+		})
+		protected.POST("/api/admin/import", state.ImportData)
 	}
 
 	// Static file serving
 	webDir := getWebDir()
 	if webDir != "" {
 		// Serve static files from web directory
-		r.Static("/assets", webDir+"/assets")
-		r.Static("/logos", webDir+"/logos") // Serve logo files
-		r.StaticFile("/favicon.ico", webDir+"/favicon.ico")
-		r.StaticFile("/vite.svg", webDir+"/vite.svg")
-		r.GET("/", func(c *gin.Context) {
-			c.File(webDir + "/index.html")
+		app.Static("/assets", webDir+"/assets")
+		app.Static("/logos", webDir+"/logos") // Serve logo files
+		app.StaticFile("/favicon.ico", webDir+"/favicon.ico")
+		app.StaticFile("/vite.svg", webDir+"/vite.svg")
+		app.GET("/", func(c *gin.Context) {
+			serveIndexHTML(c, webDir+"/index.html")
 		})
 		r.NoRoute(func(c *gin.Context) {
 			// For SPA, serve index.html for all non-API routes
-			path := c.Request.URL.Path
-			if !strings.HasPrefix(path, "/api") &&
+			path, underBase := strings.CutPrefix(c.Request.URL.Path, BasePath())
+			if underBase &&
+				!strings.HasPrefix(path, "/api") &&
 				!strings.HasPrefix(path, "/ws") &&
 				!strings.HasPrefix(path, "/agent.sh") &&
 				!strings.HasPrefix(path, "/agent.ps1") &&
@@ -265,7 +373,7 @@ func main() {
 				!strings.HasPrefix(path, "/agent-uninstall.ps1") &&
 				!strings.HasPrefix(path, "/logos") &&
 				!strings.HasPrefix(path, "/assets") {
-				c.File(webDir + "/index.html")
+				serveIndexHTML(c, webDir+"/index.html")
 			} else {
 				c.Status(404)
 			}
@@ -273,7 +381,8 @@ func main() {
 	} else {
 		// Fallback to embedded HTML
 		r.NoRoute(func(c *gin.Context) {
-			if c.Request.URL.Path == "/" || c.Request.URL.Path == "/index.html" {
+			path, underBase := strings.CutPrefix(c.Request.URL.Path, BasePath())
+			if underBase && (path == "" || path == "/" || path == "/index.html") {
 				c.Header("Content-Type", "text/html")
 				c.String(200, embeddedIndexHTML)
 				return
@@ -291,8 +400,8 @@ func main() {
 		port = "3001"
 	}
 
-	fmt.Printf("🚀 Server running on http://0.0.0.0:%s\n", port)
-	fmt.Printf("📡 Agent WebSocket: ws://0.0.0.0:%s/ws/agent\n", port)
+	fmt.Printf("🚀 Server running on http://0.0.0.0:%s%s\n", port, BasePath())
+	fmt.Printf("📡 Agent WebSocket: ws://0.0.0.0:%s%s/ws/agent\n", port, BasePath())
 	fmt.Printf("🔑 Reset password: sudo /opt/vstats/vstats-server --reset-password\n")
 
 	if err := r.Run("0.0.0.0:" + port); err != nil {
@@ -301,9 +410,27 @@ func main() {
 	}
 }
 
+// SetupShutdownHandler flushes the write buffers and exits on SIGINT/SIGTERM.
+// The deferred Close calls in main never run because r.Run only returns on
+// error, so without this a restart would lose the buffered samples.
+func SetupShutdownHandler(state *AppState) {
+	sigs := make(chan os.Signal, 1)
+	signal.Notify(sigs, os.Interrupt, syscall.SIGTERM)
+
+	go func() {
+		<-sigs
+		fmt.Println("\n🛑 Shutting down, flushing buffered metrics...")
+		state.flushConfig()
+		metricsBuffer.Close()
+		aggBuffer.Close()
+		dbWriter.Close()
+		os.Exit(0)
+	}()
+}
+
 func showDiagnostics() {
-	configPath := GetConfigPath()
-	dbPath := GetDBPath()
+	configPath, configSource := ConfigPathSource()
+	dbPath, dbSource := DBPathSource()
 
 	fmt.Println("\n╔════════════════════════════════════════════════════════════════╗")
 	fmt.Println("║                    🔍 DIAGNOSTICS                              ║")
@@ -312,26 +439,49 @@ func showDiagnostics() {
 	exe, _ := os.Executable()
 	fmt.Printf("║  Executable: %-48s ║\n", exe)
 	fmt.Printf("║  Config: %-52s ║\n", configPath)
+	fmt.Printf("║  Config source: %-45s ║\n", configSource)
 	fmt.Printf("║  Config exists: %-45s ║\n", boolToStr(fileExists(configPath)))
 	fmt.Printf("║  Database: %-50s ║\n", dbPath)
+	fmt.Printf("║  Database source: %-43s ║\n", dbSource)
 	fmt.Printf("║  Database exists: %-43s ║\n", boolToStr(fileExists(dbPath)))
 
+	var integrityProblems []string
+	if fileExists(dbPath) {
+		problems, err := checkDatabaseIntegrity(dbPath)
+		integrity := "ok"
+		if err != nil {
+			integrity = "check failed: " + err.Error()
+		} else if len(problems) > 0 {
+			integrity = fmt.Sprintf("CORRUPT (%d problems)", len(problems))
+			integrityProblems = problems
+		}
+		fmt.Printf("║  Database integrity: %-40s ║\n", integrity)
+	}
+
 	if fileExists(configPath) {
 		data, err := os.ReadFile(configPath)
 		if err == nil {
 			var config map[string]interface{}
 			if json.Unmarshal(data, &config) == nil {
 				hash, _ := config["admin_password_hash"].(string)
-				hasHash := hash != "" && (hash[:3] == "$2a" || hash[:3] == "$2b")
+				hasHash := IsValidPasswordHash(hash)
 				fmt.Printf("║  Password hash valid: %-39s ║\n", boolToStr(hasHash))
 
 				servers, _ := config["servers"].([]interface{})
 				fmt.Printf("║  Servers configured: %-40d ║\n", len(servers))
+
+				version, _ := config["config_version"].(float64)
+				fmt.Printf("║  Config version: %-44s ║\n", fmt.Sprintf("%d (current: %d)", int(version), CurrentConfigVersion))
+			} else {
+				fmt.Printf("║  Config valid JSON: %-41s ║\n", "false")
 			}
 		}
 	}
 
 	fmt.Println("╚════════════════════════════════════════════════════════════════╝")
+	for _, line := range integrityProblems {
+		fmt.Printf("   %s\n", line)
+	}
 }
 
 func fileExists(path string) bool {
@@ -362,57 +512,74 @@ func metricsBroadcastLoop(state *AppState) {
 		}
 		state.AgentMetricsMu.RUnlock()
 
-		// Collect local metrics
-		localMetrics := CollectMetrics()
-
 		// Build compact delta updates
 		var deltaUpdates []CompactServerUpdate
 
-		// Check local server
-		localCompact := CompactMetricsFromSystem(&localMetrics)
-		state.LastSentMu.Lock()
-		localPrev := state.LastSent.Servers["local"]
-		state.LastSentMu.Unlock()
-
-		localChanged := localPrev == nil || localCompact.HasChanged(localPrev.Metrics)
-		if localChanged {
-			var diffMetrics *CompactMetrics
-			if localPrev != nil {
-				diffMetrics = localCompact.Diff(localPrev.Metrics)
-			} else {
-				diffMetrics = localCompact
-			}
+		// Check local server (unless hidden via site_settings.show_local_node)
+		if config.SiteSettings.LocalNodeVisible() {
+			// Collect local metrics
+			localMetrics := CollectMetrics()
+			localCompact := CompactMetricsFromSystem("local", &localMetrics)
+			state.LastSentMu.Lock()
+			localPrev := state.LastSent.Servers["local"]
+			state.LastSentMu.Unlock()
 
-			if !diffMetrics.IsEmpty() {
-				deltaUpdates = append(deltaUpdates, CompactServerUpdate{
-					ID: "local",
-					On: boolPtr(true),
-					M:  diffMetrics,
-				})
-			}
+			localChanged := localPrev == nil || localCompact.HasChanged(localPrev.Metrics)
+			if localChanged {
+				var diffMetrics *CompactMetrics
+				if localPrev != nil {
+					diffMetrics = localCompact.Diff(localPrev.Metrics)
+				} else {
+					diffMetrics = localCompact
+				}
 
-			state.LastSentMu.Lock()
-			state.LastSent.Servers["local"] = &struct {
-				Online  bool
-				Metrics *CompactMetrics
-			}{
-				Online:  true,
-				Metrics: localCompact,
+				if !diffMetrics.IsEmpty() {
+					deltaUpdates = append(deltaUpdates, CompactServerUpdate{
+						ID: "local",
+						On: boolPtr(true),
+						M:  diffMetrics,
+					})
+				}
+
+				state.LastSentMu.Lock()
+				state.LastSent.Servers["local"] = &LastSentEntry{
+					Online:  true,
+					Status:  ServerStatusOnline,
+					Metrics: localCompact,
+				}
+				state.LastSentMu.Unlock()
 			}
+		} else {
+			// Send a full update again if it is shown later
+			state.LastSentMu.Lock()
+			delete(state.LastSent.Servers, "local")
 			state.LastSentMu.Unlock()
 		}
 
 		// Check remote servers
 		for _, server := range config.Servers {
 			metricsData := agentMetrics[server.ID]
-			online := false
-			if metricsData != nil {
-				online = time.Since(metricsData.LastUpdated).Seconds() < 30
+			online := metricsData.IsOnline()
+			status := metricsData.Status(config.DegradedAfter())
+
+			// A server that timed out went down with its last sample
+			since := time.Now()
+			if !online && metricsData != nil && metricsData.lastAlive().Before(since) {
+				since = metricsData.lastAlive()
+			}
+			if recorded, at := state.Availability.Observe(server.ID, online, since); recorded != "" {
+				if state.Alerts.ObserveAvailability(server.ID, server.Name, recorded, at) {
+					go sendStatusWebhook(config.StatusWebhook, server.ID, server.Name, recorded, at)
+				}
+			}
+			sample := metricsData.sample()
+			if online && sample != nil && len(config.AlertRules) > 0 {
+				state.Alerts.ObserveMetrics(config.AlertRules, server.ID, server.Name, sample, time.Now())
 			}
 
 			currentMetrics := &CompactMetrics{}
-			if metricsData != nil {
-				currentMetrics = CompactMetricsFromSystem(&metricsData.Metrics)
+			if sample != nil {
+				currentMetrics = CompactMetricsFromSystem(server.ID, sample)
 			}
 
 			state.LastSentMu.Lock()
@@ -420,45 +587,55 @@ func metricsBroadcastLoop(state *AppState) {
 			state.LastSentMu.Unlock()
 
 			prevOnline := false
+			prevStatus := ""
 			var prevMetrics *CompactMetrics
 			if prev != nil {
 				prevOnline = prev.Online
+				prevStatus = prev.Status
 				prevMetrics = prev.Metrics
 			} else {
 				prevMetrics = &CompactMetrics{}
 			}
 
 			onlineChanged := online != prevOnline
+			statusChanged := status != prevStatus
 			metricsChanged := online && currentMetrics.HasChanged(prevMetrics)
 
-			if onlineChanged || metricsChanged {
+			if onlineChanged || statusChanged || metricsChanged {
 				update := CompactServerUpdate{
 					ID: server.ID,
 				}
 
 				if onlineChanged {
 					update.On = &online
+					if !online && metricsData != nil {
+						update.R = metricsData.OfflineReason
+						update.LS = metricsData.lastAlive().Unix()
+					}
+				}
+
+				if statusChanged {
+					update.St = status
 				}
 
 				if metricsChanged && online {
 					update.M = currentMetrics.Diff(prevMetrics)
 				}
 
-				if update.On != nil || (update.M != nil && !update.M.IsEmpty()) {
+				if update.On != nil || update.St != "" || (update.M != nil && !update.M.IsEmpty()) {
 					deltaUpdates = append(deltaUpdates, update)
 				}
 
 				state.LastSentMu.Lock()
-				state.LastSent.Servers[server.ID] = &struct {
-					Online  bool
-					Metrics *CompactMetrics
-				}{
+				state.LastSent.Servers[server.ID] = &LastSentEntry{
 					Online:  online,
+					Status:  status,
 					Metrics: currentMetrics,
 				}
 				state.LastSentMu.Unlock()
 			}
 		}
+		state.Availability.Checkpoint()
 
 		// Broadcast if there are changes
 		if len(deltaUpdates) > 0 {
@@ -503,6 +680,36 @@ func cleanupLoop(db *sql.DB) {
 	}
 }
 
+// percentileLoop rebuilds missing hourly/daily buckets, once at startup and
+// then along with filling p95/max columns of closed aggregate buckets
+func percentileLoop(db *sql.DB) {
+	if err := BackfillRollups(db); err != nil {
+		fmt.Printf("Failed to backfill rollups: %v\n", err)
+	}
+
+	ticker := time.NewTicker(PercentileInterval)
+	defer ticker.Stop()
+
+	for range ticker.C {
+		if err := BackfillRollups(db); err != nil {
+			fmt.Printf("Failed to backfill rollups: %v\n", err)
+		}
+		if err := FillBucketPercentiles(db); err != nil {
+			fmt.Printf("Failed to compute bucket percentiles: %v\n", err)
+		}
+	}
+}
+
+// agentUpdateExpiryLoop fails update commands the agents never answered
+func agentUpdateExpiryLoop(state *AppState) {
+	ticker := time.NewTicker(time.Minute)
+	defer ticker.Stop()
+
+	for now := range ticker.C {
+		state.expireAgentUpdates(now)
+	}
+}
+
 func boolPtr(b bool) *bool {
 	return &b
 }
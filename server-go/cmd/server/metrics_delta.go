@@ -0,0 +1,44 @@
+package main
+
+import (
+	"encoding/json"
+	"fmt"
+
+	"vstats/internal/common"
+)
+
+// ============================================================================
+// Delta Metrics
+// ============================================================================
+//
+// Agents that negotiate delta metrics send a full "metrics" snapshot after
+// connecting and periodically afterwards; in between they send
+// "metrics_delta" messages holding a JSON merge patch of the fields that
+// changed. The handler keeps the last snapshot per connection and expands
+// each delta back into a full SystemMetrics before it is stored or broadcast.
+
+// applyMetricsDelta merges a delta into base (in place) and returns the
+// resulting snapshot. It fails if there is no baseline to merge into.
+func applyMetricsDelta(base, delta map[string]interface{}) (*SystemMetrics, error) {
+	if base == nil {
+		return nil, fmt.Errorf("no baseline snapshot")
+	}
+	common.MergePatch(base, delta)
+	metrics, err := common.MapToMetrics(base)
+	if err != nil {
+		return nil, fmt.Errorf("invalid delta: %w", err)
+	}
+	return metrics, nil
+}
+
+// requestFullSnapshot asks the agent to send a full metrics snapshot next
+func requestFullSnapshot(sendChan chan []byte) {
+	data, _ := json.Marshal(AgentCommand{
+		Type:    "command",
+		Command: "full_snapshot",
+	})
+	select {
+	case sendChan <- data:
+	default:
+	}
+}
@@ -0,0 +1,60 @@
+package main
+
+import (
+	"encoding/json"
+	"testing"
+	"time"
+
+	"vstats/internal/common"
+)
+
+func TestApplyMetricsDelta(t *testing.T) {
+	if _, err := applyMetricsDelta(nil, map[string]interface{}{"uptime": float64(1)}); err == nil {
+		t.Error("delta without a baseline accepted")
+	}
+
+	prev := &common.SystemMetrics{
+		Timestamp: time.Date(2026, 1, 1, 0, 0, 0, 0, time.UTC),
+		Hostname:  "web-1",
+		CPU:       common.CpuMetrics{Cores: 2, Usage: 10, PerCore: []float32{5, 15}},
+		Disks:     []common.DiskMetrics{{Name: "sda", Total: 100, Used: 40}},
+		Services:  []common.ServiceStatus{{Name: "nginx", Active: true, State: "active"}},
+	}
+	next := *prev
+	next.Timestamp = prev.Timestamp.Add(5 * time.Second)
+	next.CPU.PerCore = []float32{50, 15}
+	next.CPU.Usage = 32.5
+	next.Disks = []common.DiskMetrics{{Name: "sda", Total: 100, Used: 41}, {Name: "sdb", Total: 200}}
+	next.Services = nil
+
+	base, _ := common.MetricsToMap(prev)
+	cur, _ := common.MetricsToMap(&next)
+	data, _ := json.Marshal(common.DiffPatch(base, cur))
+	var delta map[string]interface{}
+	if err := json.Unmarshal(data, &delta); err != nil {
+		t.Fatal(err)
+	}
+
+	// A chain of deltas keeps building on the same baseline, in place
+	got, err := applyMetricsDelta(base, delta)
+	if err != nil {
+		t.Fatal(err)
+	}
+	gotJSON, _ := json.Marshal(got)
+	wantJSON, _ := json.Marshal(&next)
+	if string(gotJSON) != string(wantJSON) {
+		t.Errorf("got  %s\nwant %s", gotJSON, wantJSON)
+	}
+	if _, ok := base["services"]; ok {
+		t.Error("baseline not updated in place")
+	}
+
+	got, err = applyMetricsDelta(base, map[string]interface{}{"cpu": map[string]interface{}{"usage": float64(1)}})
+	if err != nil || got.CPU.Usage != 1 || len(got.Disks) != 2 || !got.Timestamp.Equal(next.Timestamp) {
+		t.Errorf("second delta: %+v, %v", got, err)
+	}
+
+	if _, err := applyMetricsDelta(base, map[string]interface{}{"cpu": map[string]interface{}{"usage": "high"}}); err == nil {
+		t.Error("delta of the wrong type accepted")
+	}
+}
@@ -0,0 +1,254 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"math"
+	"time"
+
+	"vstats/internal/common"
+)
+
+// ============================================================================
+// Metric Sanity Validation
+// ============================================================================
+//
+// A misbehaving agent can report values such as 4000% CPU, which would skew
+// every aggregate built from them. Out-of-range percentages are clamped to
+// 0-100, negative loads and latencies are zeroed or dropped, and samples
+// containing NaN/Infinity are rejected outright. Network counters are
+// unsigned, so a negative value already fails to decode; values beyond what
+// a signed SQLite INTEGER holds are clamped to it, as SQLite cannot store
+// them at all.
+
+// DefaultMaxClockSkew is how far an agent timestamp may be from server time
+// before it is replaced with the receive time
+const DefaultMaxClockSkew = 2 * time.Minute
+
+// CorrectClockSkew replaces the timestamp of a live sample with the receive
+// time when the two differ by more than maxSkew. It returns the skew in
+// milliseconds (agent minus server) if the timestamp was replaced, else 0.
+func CorrectClockSkew(m *SystemMetrics, received time.Time, maxSkew time.Duration) int64 {
+	skew := m.Timestamp.Sub(received)
+	if skew >= -maxSkew && skew <= maxSkew {
+		return 0
+	}
+	m.Timestamp = received.UTC()
+	return skew.Milliseconds()
+}
+
+// clampPercent32 clamps a percentage to 0-100, reporting whether it changed
+func clampPercent32(v *float32) bool {
+	switch {
+	case *v < 0:
+		*v = 0
+		return true
+	case *v > 100:
+		*v = 100
+		return true
+	}
+	return false
+}
+
+// clampPercent64 clamps a percentage to 0-100, reporting whether it changed
+func clampPercent64(v *float64) bool {
+	switch {
+	case *v < 0:
+		*v = 0
+		return true
+	case *v > 100:
+		*v = 100
+		return true
+	}
+	return false
+}
+
+// clampCounter caps an unsigned counter or byte size at math.MaxInt64 and
+// reports whether it changed
+func clampCounter(v *uint64) bool {
+	if *v > math.MaxInt64 {
+		*v = math.MaxInt64
+		return true
+	}
+	return false
+}
+
+func isBadFloat(v float64) bool {
+	return math.IsNaN(v) || math.IsInf(v, 0)
+}
+
+// checkFinite returns an error naming the first NaN/Infinity field in a
+// raw metrics sample
+func checkFinite(m *SystemMetrics) error {
+	if isBadFloat(float64(m.CPU.Usage)) {
+		return fmt.Errorf("cpu.usage is %v", m.CPU.Usage)
+	}
+	for i, v := range m.CPU.PerCore {
+		if isBadFloat(float64(v)) {
+			return fmt.Errorf("cpu.per_core[%d] is %v", i, v)
+		}
+	}
+	if isBadFloat(float64(m.Memory.UsagePercent)) {
+		return fmt.Errorf("memory.usage_percent is %v", m.Memory.UsagePercent)
+	}
+	for i, d := range m.Disks {
+		if isBadFloat(float64(d.UsagePercent)) {
+			return fmt.Errorf("disks[%d].usage_percent is %v", i, d.UsagePercent)
+		}
+	}
+	for _, v := range []float64{m.LoadAverage.One, m.LoadAverage.Five, m.LoadAverage.Fifteen} {
+		if isBadFloat(v) {
+			return fmt.Errorf("load_average is %v", v)
+		}
+	}
+	if m.Ping != nil {
+		for _, t := range m.Ping.Targets {
+			if isBadFloat(t.PacketLoss) || (t.LatencyMs != nil && isBadFloat(*t.LatencyMs)) {
+				return fmt.Errorf("ping target %s has a non-finite value", t.Name)
+			}
+		}
+	}
+	return nil
+}
+
+// SanitizeMetrics validates a raw metrics sample in place. It returns false
+// if the sample must be rejected; out-of-range values are clamped and logged.
+func SanitizeMetrics(serverID string, m *SystemMetrics) bool {
+	if m == nil {
+		return false
+	}
+	truncateMetricsLists(serverID, m)
+	if err := checkFinite(m); err != nil {
+		log.Printf("Rejected metrics from %s: %v", serverID, err)
+		return false
+	}
+
+	clamped := clampPercent32(&m.CPU.Usage)
+	for i := range m.CPU.PerCore {
+		clamped = clampPercent32(&m.CPU.PerCore[i]) || clamped
+	}
+	clamped = clampPercent32(&m.Memory.UsagePercent) || clamped
+	for i := range m.Disks {
+		clamped = clampPercent32(&m.Disks[i].UsagePercent) || clamped
+		clamped = clampCounter(&m.Disks[i].Used) || clamped
+		clamped = clampCounter(&m.Disks[i].Total) || clamped
+	}
+	for _, v := range []*uint64{&m.Network.TotalRx, &m.Network.TotalTx, &m.Network.RxSpeed, &m.Network.TxSpeed,
+		&m.Network.DailyRx, &m.Network.DailyTx} {
+		clamped = clampCounter(v) || clamped
+	}
+	for _, v := range []*float64{&m.LoadAverage.One, &m.LoadAverage.Five, &m.LoadAverage.Fifteen} {
+		if *v < 0 {
+			*v = 0
+			clamped = true
+		}
+	}
+	if m.Ping != nil {
+		for i := range m.Ping.Targets {
+			t := &m.Ping.Targets[i]
+			clamped = clampPercent64(&t.PacketLoss) || clamped
+			if t.LatencyMs != nil && *t.LatencyMs < 0 {
+				t.LatencyMs = nil
+				clamped = true
+			}
+		}
+	}
+
+	if clamped {
+		log.Printf("Clamped out-of-range metrics from %s (cpu=%.1f%%, memory=%.1f%%)",
+			serverID, m.CPU.Usage, m.Memory.UsagePercent)
+	}
+	return true
+}
+
+// SanitizeAggregatedMetrics validates a pre-aggregated sample in place
+func SanitizeAggregatedMetrics(serverID string, agg *common.AggregatedMetrics) bool {
+	for _, v := range []float32{agg.CPUAvg, agg.CPUMax, agg.MemoryAvg, agg.MemoryMax, agg.DiskAvg, agg.DiskMax} {
+		if isBadFloat(float64(v)) {
+			log.Printf("Rejected aggregated metrics from %s: non-finite value", serverID)
+			return false
+		}
+	}
+	if isBadFloat(agg.LoadOneAvg) || isBadFloat(agg.LoadFiveAvg) || isBadFloat(agg.LoadFifteenAvg) ||
+		(agg.PingAvg != nil && isBadFloat(*agg.PingAvg)) {
+		log.Printf("Rejected aggregated metrics from %s: non-finite value", serverID)
+		return false
+	}
+
+	clamped := false
+	for _, v := range []*float32{&agg.CPUAvg, &agg.CPUMax, &agg.MemoryAvg, &agg.MemoryMax, &agg.DiskAvg, &agg.DiskMax} {
+		clamped = clampPercent32(v) || clamped
+	}
+	if agg.PingAvg != nil && *agg.PingAvg < 0 {
+		agg.PingAvg = nil
+		clamped = true
+	}
+	clamped = clampCounter(&agg.NetRxMax) || clamped
+	clamped = clampCounter(&agg.NetTxMax) || clamped
+	if clamped {
+		log.Printf("Clamped out-of-range aggregated metrics from %s", serverID)
+	}
+
+	if agg.LastMetrics != nil && !SanitizeMetrics(serverID, agg.LastMetrics) {
+		agg.LastMetrics = nil
+	}
+	return true
+}
+
+// SanitizeGranularities drops buckets with non-finite values and clamps
+// percentage sums/maxima to what SampleCount samples of 0-100 allow
+func SanitizeGranularities(serverID string, granularities []common.GranularityData) {
+	dropped, clamped := 0, 0
+	for gi := range granularities {
+		g := &granularities[gi]
+		kept := g.Metrics[:0]
+		for _, b := range g.Metrics {
+			if isBadFloat(b.CPUSum) || isBadFloat(b.CPUMax) || isBadFloat(b.MemorySum) ||
+				isBadFloat(b.MemoryMax) || isBadFloat(b.DiskSum) || isBadFloat(b.PingSum) ||
+				b.SampleCount <= 0 {
+				dropped++
+				continue
+			}
+			maxSum := 100 * float64(b.SampleCount)
+			changed := clampPercent64(&b.CPUMax)
+			changed = clampPercent64(&b.MemoryMax) || changed
+			for _, sum := range []*float64{&b.CPUSum, &b.MemorySum, &b.DiskSum} {
+				if *sum < 0 {
+					*sum = 0
+					changed = true
+				} else if *sum > maxSum {
+					*sum = maxSum
+					changed = true
+				}
+			}
+			if b.PingSum < 0 {
+				b.PingSum, b.PingCount = 0, 0
+				changed = true
+			}
+			changed = clampCounter(&b.NetRx) || changed
+			changed = clampCounter(&b.NetTx) || changed
+			if changed {
+				clamped++
+			}
+			kept = append(kept, b)
+		}
+		g.Metrics = kept
+
+		keptPing := g.Ping[:0]
+		for _, p := range g.Ping {
+			if isBadFloat(p.LatencySum) || isBadFloat(p.LatencyMax) || p.LatencySum < 0 || p.LatencyMax < 0 {
+				dropped++
+				continue
+			}
+			keptPing = append(keptPing, p)
+		}
+		g.Ping = keptPing
+	}
+
+	if dropped > 0 {
+		log.Printf("Rejected %d aggregated buckets from %s: invalid values", dropped, serverID)
+	}
+	if clamped > 0 {
+		log.Printf("Clamped %d aggregated buckets from %s", clamped, serverID)
+	}
+}
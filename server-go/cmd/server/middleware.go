@@ -5,10 +5,10 @@ import (
 	"strings"
 
 	"github.com/gin-gonic/gin"
-	"github.com/golang-jwt/jwt/v5"
 )
 
-func AuthMiddleware() gin.HandlerFunc {
+// AuthMiddleware accepts the admin JWT, or an API key for read-only requests
+func AuthMiddleware(state *AppState) gin.HandlerFunc {
 	return func(c *gin.Context) {
 		authHeader := c.GetHeader("Authorization")
 		if authHeader == "" {
@@ -22,11 +22,14 @@ func AuthMiddleware() gin.HandlerFunc {
 			return
 		}
 
-		token, err := jwt.Parse(tokenString, func(token *jwt.Token) (interface{}, error) {
-			return []byte(GetJWTSecret()), nil
-		})
+		if strings.HasPrefix(tokenString, APIKeyPrefix) {
+			if state.authorizeAPIKey(c, tokenString) {
+				c.Next()
+			}
+			return
+		}
 
-		if err != nil || !token.Valid {
+		if !ValidateAuthToken(tokenString) {
 			c.AbortWithStatusJSON(http.StatusUnauthorized, gin.H{"error": "Invalid token"})
 			return
 		}
@@ -35,3 +38,18 @@ func AuthMiddleware() gin.HandlerFunc {
 	}
 }
 
+// requestIsAdmin reports whether a request to a public route carries a valid
+// admin JWT. API keys don't count: they are read-only and never see
+// credentials.
+func requestIsAdmin(c *gin.Context) bool {
+	tokenString := strings.TrimPrefix(c.GetHeader("Authorization"), "Bearer ")
+	return tokenString != "" && !strings.HasPrefix(tokenString, APIKeyPrefix) && ValidateAuthToken(tokenString)
+}
+
+// ValidateAuthToken reports whether tokenString is a valid, unexpired admin
+// JWT that has not been revoked
+func ValidateAuthToken(tokenString string) bool {
+	claims, ok := parseAuthToken(tokenString)
+	return ok && !tokenRevoked(tokenString, claims)
+}
+
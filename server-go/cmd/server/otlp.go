@@ -0,0 +1,299 @@
+package main
+
+import (
+	"bytes"
+	"encoding/json"
+	"fmt"
+	"io"
+	"log"
+	"net/http"
+	"net/url"
+	"strconv"
+	"strings"
+	"time"
+)
+
+// ============================================================================
+// OTLP Metrics Export
+// ============================================================================
+//
+// When "otlp" is configured, the latest sample of every server is pushed to
+// an OpenTelemetry collector as gauges, using OTLP over HTTP with the JSON
+// encoding. Samples are batched: each export interval sends one request for
+// all servers that reported since the previous export. Server name, id and
+// tag are resource attributes, so every server is its own resource.
+
+const (
+	DefaultOTLPIntervalSecs = 15
+	MinOTLPIntervalSecs     = 5
+	otlpRequestTimeout      = 10 * time.Second
+	otlpMaxErrorBody        = 512
+)
+
+// OTLPSettings configure the OTLP metrics exporter
+type OTLPSettings struct {
+	// Collector URL, e.g. "http://collector:4318"; "/v1/metrics" is appended
+	// when the URL has no path
+	Endpoint string `json:"endpoint"`
+	// Extra request headers, e.g. {"Authorization": "Bearer ..."}
+	Headers      map[string]string `json:"headers,omitempty"`
+	IntervalSecs int               `json:"interval_secs,omitempty"` // Default: 15
+}
+
+// Validate checks the settings; zero values mean the defaults
+func (s *OTLPSettings) Validate() error {
+	if s == nil || s.Endpoint == "" {
+		return nil
+	}
+	u, err := url.Parse(s.Endpoint)
+	if err != nil || (u.Scheme != "http" && u.Scheme != "https") || u.Host == "" {
+		return fmt.Errorf("otlp.endpoint must be an http(s) URL")
+	}
+	if s.IntervalSecs != 0 && s.IntervalSecs < MinOTLPIntervalSecs {
+		return fmt.Errorf("otlp.interval_secs must be at least %d", MinOTLPIntervalSecs)
+	}
+	return nil
+}
+
+func (s *OTLPSettings) interval() time.Duration {
+	if s.IntervalSecs < MinOTLPIntervalSecs {
+		return DefaultOTLPIntervalSecs * time.Second
+	}
+	return time.Duration(s.IntervalSecs) * time.Second
+}
+
+// metricsURL returns the endpoint the export requests are posted to
+func (s *OTLPSettings) metricsURL() string {
+	u, err := url.Parse(s.Endpoint)
+	if err != nil {
+		return s.Endpoint
+	}
+	if u.Path == "" || u.Path == "/" {
+		u.Path = "/v1/metrics"
+	}
+	return u.String()
+}
+
+// otlpLoop exports metrics while "otlp" is configured and valid, and is a
+// no-op otherwise
+func otlpLoop(state *AppState) {
+	client := &http.Client{Timeout: otlpRequestTimeout}
+	exported := make(map[string]time.Time)
+	failing := false
+	for {
+		state.ConfigMu.RLock()
+		var settings *OTLPSettings
+		if state.Config.OTLP != nil && state.Config.OTLP.Endpoint != "" && state.Config.OTLP.Validate() == nil {
+			s := *state.Config.OTLP
+			settings = &s
+		}
+		state.ConfigMu.RUnlock()
+
+		if settings == nil {
+			time.Sleep(DefaultOTLPIntervalSecs * time.Second)
+			continue
+		}
+
+		body := state.otlpBatch(exported)
+		if body != nil {
+			err := postOTLP(client, settings, body)
+			if err != nil && !failing {
+				log.Printf("OTLP export to %s failed: %v", settings.metricsURL(), err)
+			} else if err == nil && failing {
+				log.Printf("OTLP export to %s recovered", settings.metricsURL())
+			}
+			failing = err != nil
+		}
+		time.Sleep(settings.interval())
+	}
+}
+
+// otlpBatch encodes the samples newer than the last export of each server,
+// or returns nil if there are none
+func (s *AppState) otlpBatch(exported map[string]time.Time) []byte {
+	s.ConfigMu.RLock()
+	servers := make([]RemoteServer, len(s.Config.Servers))
+	copy(servers, s.Config.Servers)
+	s.ConfigMu.RUnlock()
+
+	var resources []otlpResourceMetrics
+	s.AgentMetricsMu.RLock()
+	for _, server := range servers {
+		data := s.AgentMetrics[server.ID]
+		if data == nil || !data.LastUpdated.After(exported[server.ID]) {
+			continue
+		}
+		exported[server.ID] = data.LastUpdated
+		resources = append(resources, otlpServerMetrics(server, &data.Metrics))
+	}
+	s.AgentMetricsMu.RUnlock()
+
+	if len(resources) == 0 {
+		return nil
+	}
+	body, err := json.Marshal(otlpExportRequest{ResourceMetrics: resources})
+	if err != nil {
+		return nil
+	}
+	return body
+}
+
+func postOTLP(client *http.Client, settings *OTLPSettings, body []byte) error {
+	req, err := http.NewRequest("POST", settings.metricsURL(), bytes.NewReader(body))
+	if err != nil {
+		return err
+	}
+	req.Header.Set("Content-Type", "application/json")
+	for k, v := range settings.Headers {
+		req.Header.Set(k, v)
+	}
+	resp, err := client.Do(req)
+	if err != nil {
+		return err
+	}
+	defer resp.Body.Close()
+	if resp.StatusCode/100 != 2 {
+		msg, _ := io.ReadAll(io.LimitReader(resp.Body, otlpMaxErrorBody))
+		return fmt.Errorf("%s: %s", resp.Status, strings.TrimSpace(string(msg)))
+	}
+	io.Copy(io.Discard, resp.Body)
+	return nil
+}
+
+// ============================================================================
+// OTLP/JSON Encoding
+// ============================================================================
+
+type otlpExportRequest struct {
+	ResourceMetrics []otlpResourceMetrics `json:"resourceMetrics"`
+}
+
+type otlpResourceMetrics struct {
+	Resource     otlpResource       `json:"resource"`
+	ScopeMetrics []otlpScopeMetrics `json:"scopeMetrics"`
+}
+
+type otlpResource struct {
+	Attributes []otlpAttribute `json:"attributes"`
+}
+
+type otlpScopeMetrics struct {
+	Scope   otlpScope    `json:"scope"`
+	Metrics []otlpMetric `json:"metrics"`
+}
+
+type otlpScope struct {
+	Name    string `json:"name"`
+	Version string `json:"version"`
+}
+
+type otlpMetric struct {
+	Name  string    `json:"name"`
+	Unit  string    `json:"unit,omitempty"`
+	Gauge otlpGauge `json:"gauge"`
+}
+
+type otlpGauge struct {
+	DataPoints []otlpDataPoint `json:"dataPoints"`
+}
+
+type otlpDataPoint struct {
+	Attributes   []otlpAttribute `json:"attributes,omitempty"`
+	TimeUnixNano string          `json:"timeUnixNano"`
+	AsDouble     float64         `json:"asDouble"`
+}
+
+type otlpAttribute struct {
+	Key   string         `json:"key"`
+	Value otlpAttrString `json:"value"`
+}
+
+type otlpAttrString struct {
+	StringValue string `json:"stringValue"`
+}
+
+func otlpAttr(key, value string) otlpAttribute {
+	return otlpAttribute{Key: key, Value: otlpAttrString{StringValue: value}}
+}
+
+// otlpServerMetrics maps a sample to one gauge per SystemMetrics field; disks,
+// interfaces and ping targets are data points of the same gauge
+func otlpServerMetrics(server RemoteServer, m *SystemMetrics) otlpResourceMetrics {
+	ts := strconv.FormatInt(m.Timestamp.UnixNano(), 10)
+	var metrics []otlpMetric
+	gauge := func(name, unit string, value float64, attrs ...otlpAttribute) {
+		point := otlpDataPoint{Attributes: attrs, TimeUnixNano: ts, AsDouble: value}
+		if n := len(metrics); n > 0 && metrics[n-1].Name == name {
+			metrics[n-1].Gauge.DataPoints = append(metrics[n-1].Gauge.DataPoints, point)
+			return
+		}
+		metrics = append(metrics, otlpMetric{Name: name, Unit: unit, Gauge: otlpGauge{DataPoints: []otlpDataPoint{point}}})
+	}
+
+	gauge("vstats.cpu.usage", "%", float64(m.CPU.Usage))
+	gauge("vstats.memory.usage", "%", float64(m.Memory.UsagePercent))
+	gauge("vstats.memory.used", "By", float64(m.Memory.Used))
+	gauge("vstats.memory.total", "By", float64(m.Memory.Total))
+	gauge("vstats.swap.used", "By", float64(m.Memory.SwapUsed))
+	gauge("vstats.swap.total", "By", float64(m.Memory.SwapTotal))
+	gauge("vstats.load.1m", "1", m.LoadAverage.One)
+	gauge("vstats.load.5m", "1", m.LoadAverage.Five)
+	gauge("vstats.load.15m", "1", m.LoadAverage.Fifteen)
+	gauge("vstats.uptime", "s", float64(m.Uptime))
+
+	disks := make([]otlpAttribute, len(m.Disks))
+	for i := range m.Disks {
+		mount := m.Disks[i].Name
+		if len(m.Disks[i].MountPoints) > 0 {
+			mount = m.Disks[i].MountPoints[0]
+		}
+		disks[i] = otlpAttr("mount", mount)
+	}
+	for i, d := range m.Disks {
+		gauge("vstats.disk.usage", "%", float64(d.UsagePercent), disks[i])
+	}
+	for i, d := range m.Disks {
+		gauge("vstats.disk.used", "By", float64(d.Used), disks[i])
+	}
+	for i, d := range m.Disks {
+		gauge("vstats.disk.total", "By", float64(d.Total), disks[i])
+	}
+
+	gauge("vstats.network.rx.rate", "By/s", float64(m.Network.RxSpeed))
+	gauge("vstats.network.tx.rate", "By/s", float64(m.Network.TxSpeed))
+	gauge("vstats.network.rx.total", "By", float64(m.Network.TotalRx))
+	gauge("vstats.network.tx.total", "By", float64(m.Network.TotalTx))
+	for _, iface := range m.Network.Interfaces {
+		gauge("vstats.network.interface.rx", "By", float64(iface.RxBytes), otlpAttr("interface", iface.Name))
+	}
+	for _, iface := range m.Network.Interfaces {
+		gauge("vstats.network.interface.tx", "By", float64(iface.TxBytes), otlpAttr("interface", iface.Name))
+	}
+
+	if m.Ping != nil {
+		for _, t := range m.Ping.Targets {
+			if t.LatencyMs != nil {
+				gauge("vstats.ping.latency", "ms", *t.LatencyMs, otlpAttr("target", t.Name), otlpAttr("host", t.Host))
+			}
+		}
+		for _, t := range m.Ping.Targets {
+			gauge("vstats.ping.loss", "%", t.PacketLoss, otlpAttr("target", t.Name), otlpAttr("host", t.Host))
+		}
+	}
+
+	attrs := []otlpAttribute{
+		otlpAttr("service.name", "vstats"),
+		otlpAttr("host.name", server.Name),
+		otlpAttr("vstats.server.id", server.ID),
+	}
+	if server.Tag != "" {
+		attrs = append(attrs, otlpAttr("vstats.server.tag", server.Tag))
+	}
+	return otlpResourceMetrics{
+		Resource: otlpResource{Attributes: attrs},
+		ScopeMetrics: []otlpScopeMetrics{{
+			Scope:   otlpScope{Name: "vstats", Version: ServerVersion},
+			Metrics: metrics,
+		}},
+	}
+}
@@ -0,0 +1,189 @@
+package main
+
+import (
+	"crypto/rand"
+	"crypto/subtle"
+	"encoding/base64"
+	"fmt"
+	"strings"
+
+	"golang.org/x/crypto/argon2"
+	"golang.org/x/crypto/bcrypt"
+)
+
+// ============================================================================
+// Password Hashing
+// ============================================================================
+//
+// New hashes use the algorithm from the password_hashing config. Verification
+// detects the algorithm from the stored hash, so existing bcrypt hashes keep
+// working after switching to argon2id (and are rehashed on the next login).
+
+// Supported password hashing algorithms
+const (
+	HashBcrypt   = "bcrypt"
+	HashArgon2id = "argon2id"
+)
+
+// Argon2id defaults (RFC 9106, second recommended option)
+const (
+	DefaultArgon2MemoryKiB  = 64 * 1024
+	DefaultArgon2Iterations = 3
+	DefaultArgon2Threads    = 4
+	argon2SaltLen           = 16
+	argon2KeyLen            = 32
+)
+
+// PasswordHashSettings selects the algorithm and cost for new password hashes
+type PasswordHashSettings struct {
+	Algorithm        string `json:"algorithm,omitempty"`         // bcrypt (default) or argon2id
+	BcryptCost       int    `json:"bcrypt_cost,omitempty"`       // 4-31, default 10
+	Argon2MemoryKiB  uint32 `json:"argon2_memory_kib,omitempty"` // Default 65536 (64 MiB)
+	Argon2Iterations uint32 `json:"argon2_iterations,omitempty"` // Default 3
+	Argon2Threads    uint8  `json:"argon2_threads,omitempty"`    // Default 4
+}
+
+// withDefaults fills in unset fields. A nil receiver yields bcrypt defaults.
+func (p *PasswordHashSettings) withDefaults() PasswordHashSettings {
+	var s PasswordHashSettings
+	if p != nil {
+		s = *p
+	}
+	if s.Algorithm == "" {
+		s.Algorithm = HashBcrypt
+	}
+	if s.BcryptCost == 0 {
+		s.BcryptCost = bcrypt.DefaultCost
+	}
+	if s.Argon2MemoryKiB == 0 {
+		s.Argon2MemoryKiB = DefaultArgon2MemoryKiB
+	}
+	if s.Argon2Iterations == 0 {
+		s.Argon2Iterations = DefaultArgon2Iterations
+	}
+	if s.Argon2Threads == 0 {
+		s.Argon2Threads = DefaultArgon2Threads
+	}
+	return s
+}
+
+// Validate reports settings that HashPassword would reject
+func (p *PasswordHashSettings) Validate() error {
+	s := p.withDefaults()
+	switch s.Algorithm {
+	case HashBcrypt:
+		if s.BcryptCost < bcrypt.MinCost || s.BcryptCost > bcrypt.MaxCost {
+			return fmt.Errorf("bcrypt_cost must be between %d and %d", bcrypt.MinCost, bcrypt.MaxCost)
+		}
+	case HashArgon2id:
+		if s.Argon2MemoryKiB < 8*uint32(s.Argon2Threads) {
+			return fmt.Errorf("argon2_memory_kib must be at least 8 per thread")
+		}
+	default:
+		return fmt.Errorf("unknown password hashing algorithm %q (use %s or %s)", s.Algorithm, HashBcrypt, HashArgon2id)
+	}
+	return nil
+}
+
+// HashPassword hashes a password with the configured algorithm. Invalid
+// settings fall back to bcrypt with the default cost.
+func HashPassword(password string, settings *PasswordHashSettings) (string, error) {
+	if err := settings.Validate(); err != nil {
+		fmt.Printf("⚠️  %v; using bcrypt\n", err)
+		settings = nil
+	}
+	s := settings.withDefaults()
+
+	if s.Algorithm == HashArgon2id {
+		salt := make([]byte, argon2SaltLen)
+		if _, err := rand.Read(salt); err != nil {
+			return "", err
+		}
+		key := argon2.IDKey([]byte(password), salt, s.Argon2Iterations, s.Argon2MemoryKiB, s.Argon2Threads, argon2KeyLen)
+		return fmt.Sprintf("$argon2id$v=%d$m=%d,t=%d,p=%d$%s$%s", argon2.Version,
+			s.Argon2MemoryKiB, s.Argon2Iterations, s.Argon2Threads,
+			base64.RawStdEncoding.EncodeToString(salt), base64.RawStdEncoding.EncodeToString(key)), nil
+	}
+
+	hash, err := bcrypt.GenerateFromPassword([]byte(password), s.BcryptCost)
+	return string(hash), err
+}
+
+// VerifyPassword checks a password against a bcrypt or argon2id hash
+func VerifyPassword(hash, password string) bool {
+	if strings.HasPrefix(hash, "$argon2id$") {
+		return verifyArgon2id(hash, password)
+	}
+	return bcrypt.CompareHashAndPassword([]byte(hash), []byte(password)) == nil
+}
+
+// argon2Params holds the parameters encoded in an argon2id hash
+type argon2Params struct {
+	memory     uint32
+	iterations uint32
+	threads    uint8
+	salt       []byte
+	key        []byte
+}
+
+// parseArgon2id decodes "$argon2id$v=19$m=..,t=..,p=..$salt$key"
+func parseArgon2id(hash string) (*argon2Params, error) {
+	parts := strings.Split(hash, "$")
+	if len(parts) != 6 || parts[1] != HashArgon2id {
+		return nil, fmt.Errorf("malformed argon2id hash")
+	}
+	var version int
+	if _, err := fmt.Sscanf(parts[2], "v=%d", &version); err != nil || version != argon2.Version {
+		return nil, fmt.Errorf("unsupported argon2 version")
+	}
+	var p argon2Params
+	if _, err := fmt.Sscanf(parts[3], "m=%d,t=%d,p=%d", &p.memory, &p.iterations, &p.threads); err != nil {
+		return nil, fmt.Errorf("malformed argon2id parameters: %w", err)
+	}
+	var err error
+	if p.salt, err = base64.RawStdEncoding.DecodeString(parts[4]); err != nil {
+		return nil, err
+	}
+	if p.key, err = base64.RawStdEncoding.DecodeString(parts[5]); err != nil {
+		return nil, err
+	}
+	if len(p.key) == 0 {
+		return nil, fmt.Errorf("malformed argon2id hash")
+	}
+	return &p, nil
+}
+
+func verifyArgon2id(hash, password string) bool {
+	p, err := parseArgon2id(hash)
+	if err != nil {
+		return false
+	}
+	key := argon2.IDKey([]byte(password), p.salt, p.iterations, p.memory, p.threads, uint32(len(p.key)))
+	return subtle.ConstantTimeCompare(key, p.key) == 1
+}
+
+// IsValidPasswordHash reports whether a stored hash is one VerifyPassword
+// understands
+func IsValidPasswordHash(hash string) bool {
+	if strings.HasPrefix(hash, "$argon2id$") {
+		_, err := parseArgon2id(hash)
+		return err == nil
+	}
+	return len(hash) >= 4 && (hash[:3] == "$2a" || hash[:3] == "$2b" || hash[:3] == "$2y")
+}
+
+// NeedsRehash reports whether a hash was made with a different algorithm or
+// cost than the settings ask for
+func NeedsRehash(hash string, settings *PasswordHashSettings) bool {
+	if settings.Validate() != nil {
+		return false
+	}
+	s := settings.withDefaults()
+
+	if s.Algorithm == HashArgon2id {
+		p, err := parseArgon2id(hash)
+		return err != nil || p.memory != s.Argon2MemoryKiB || p.iterations != s.Argon2Iterations || p.threads != s.Argon2Threads
+	}
+	cost, err := bcrypt.Cost([]byte(hash))
+	return err != nil || cost != s.BcryptCost
+}
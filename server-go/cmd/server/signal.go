@@ -45,8 +45,7 @@ func reloadConfig(state *AppState) {
 	}
 
 	// Validate the new password hash
-	if len(newConfig.AdminPasswordHash) < 4 ||
-		(newConfig.AdminPasswordHash[:3] != "$2a" && newConfig.AdminPasswordHash[:3] != "$2b") {
+	if !IsValidPasswordHash(newConfig.AdminPasswordHash) {
 		fmt.Println("❌ Invalid password hash format in config")
 		return
 	}
@@ -0,0 +1,452 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"math/rand"
+	"net"
+	"strconv"
+	"strings"
+	"time"
+)
+
+// ============================================================================
+// SNMP Client
+// ============================================================================
+//
+// A minimal SNMP client: GET and GETNEXT walks over UDP, enough for the
+// poller in snmp_poller.go. v1 and v2c use a community string; v3 security
+// lives in snmp_v3.go. Messages are BER-encoded by hand so the server needs
+// no extra dependency.
+
+// BER and SNMP tags
+const (
+	berInteger     = 0x02
+	berOctetString = 0x04
+	berNull        = 0x05
+	berOID         = 0x06
+	berSequence    = 0x30
+
+	snmpIPAddress   = 0x40
+	snmpCounter32   = 0x41
+	snmpGauge32     = 0x42
+	snmpTimeTicks   = 0x43
+	snmpCounter64   = 0x46
+	snmpNoSuchObj   = 0x80
+	snmpNoSuchInst  = 0x81
+	snmpEndOfMib    = 0x82
+	snmpGetRequest  = 0xa0
+	snmpGetNext     = 0xa1
+	snmpGetResponse = 0xa2
+	snmpReport      = 0xa8
+)
+
+// snmpMaxWalk bounds a walk, so a broken agent cannot loop it forever
+const snmpMaxWalk = 2048
+
+// snmpVar is a variable binding of a response. Value is an int64 (INTEGER),
+// uint64 (counters, gauges, time ticks), string (OCTET STRING, OID or IP
+// address) or nil (NULL and missing objects).
+type snmpVar struct {
+	OID   string
+	Type  byte
+	Value interface{}
+}
+
+// Uint returns a numeric value as uint64
+func (v snmpVar) Uint() (uint64, bool) {
+	switch n := v.Value.(type) {
+	case uint64:
+		return n, true
+	case int64:
+		if n >= 0 {
+			return uint64(n), true
+		}
+	case string:
+		// Some devices report numbers as strings, e.g. "12.5"
+		if f, err := strconv.ParseFloat(strings.TrimSpace(n), 64); err == nil && f >= 0 {
+			return uint64(f), true
+		}
+	}
+	return 0, false
+}
+
+// Float returns a numeric value as float64
+func (v snmpVar) Float() (float64, bool) {
+	if s, ok := v.Value.(string); ok {
+		f, err := strconv.ParseFloat(strings.TrimSpace(s), 64)
+		return f, err == nil
+	}
+	if n, ok := v.Value.(int64); ok {
+		return float64(n), true
+	}
+	n, ok := v.Uint()
+	return float64(n), ok
+}
+
+// snmpClient talks to one device
+type snmpClient struct {
+	conn      net.Conn
+	community string
+	version   int      // 0 for v1, 1 for v2c, 3 for v3
+	usm       *snmpUSM // v3 only
+	timeout   time.Duration
+	retries   int
+}
+
+func dialSNMP(host string, port int, community, version string, timeout time.Duration) (*snmpClient, error) {
+	conn, err := net.Dial("udp", net.JoinHostPort(host, strconv.Itoa(port)))
+	if err != nil {
+		return nil, err
+	}
+	v := 1
+	switch version {
+	case "1":
+		v = 0
+	case "3":
+		v = snmpVersion3
+	}
+	return &snmpClient{conn: conn, community: community, version: v, timeout: timeout, retries: 1}, nil
+}
+
+func (c *snmpClient) Close() error {
+	return c.conn.Close()
+}
+
+// Get returns the values of the given OIDs. Missing objects come back with
+// a nil Value on v2c; v1 devices fail the whole request instead.
+func (c *snmpClient) Get(oids ...string) ([]snmpVar, error) {
+	return c.request(snmpGetRequest, oids)
+}
+
+// Walk returns every variable below root, in order
+func (c *snmpClient) Walk(root string) ([]snmpVar, error) {
+	var vars []snmpVar
+	prefix := root + "."
+	oid := root
+	for len(vars) < snmpMaxWalk {
+		resp, err := c.request(snmpGetNext, []string{oid})
+		if err != nil {
+			// v1 ends a walk with noSuchName
+			if errors.Is(err, errSNMPNoSuchName) {
+				break
+			}
+			return vars, err
+		}
+		if len(resp) == 0 {
+			break
+		}
+		v := resp[0]
+		if v.Type == snmpEndOfMib || !strings.HasPrefix(v.OID, prefix) || v.OID == oid {
+			break
+		}
+		vars = append(vars, v)
+		oid = v.OID
+	}
+	return vars, nil
+}
+
+var (
+	errSNMPNoSuchName = errors.New("noSuchName")
+	errSNMPMalformed  = errors.New("malformed response")
+)
+
+func (c *snmpClient) request(pduType byte, oids []string) ([]snmpVar, error) {
+	if c.usm != nil && len(c.usm.engineID) == 0 {
+		if err := c.discoverEngine(); err != nil {
+			return nil, fmt.Errorf("engine discovery: %w", err)
+		}
+	}
+	vars, err := c.exchange(pduType, oids)
+	// The device's clock was too far from ours; its report carried the time
+	if errors.Is(err, errSNMPNotInTimeWindow) {
+		vars, err = c.exchange(pduType, oids)
+	}
+	return vars, err
+}
+
+// exchange sends one request and waits for its response
+func (c *snmpClient) exchange(pduType byte, oids []string) ([]snmpVar, error) {
+	requestID := rand.Int31()
+	pdu, err := encodeSNMPPDU(pduType, requestID, oids)
+	if err != nil {
+		return nil, err
+	}
+	msg, err := c.encodeMessage(requestID, pdu)
+	if err != nil {
+		return nil, err
+	}
+	return c.roundTrip(msg, requestID)
+}
+
+// encodeMessage wraps a PDU for the client's SNMP version. v3 messages use
+// the request ID as their msgID.
+func (c *snmpClient) encodeMessage(requestID int32, pdu []byte) ([]byte, error) {
+	if c.usm != nil {
+		return c.usm.encodeMessage(requestID, pdu, false)
+	}
+	return berTLV(berSequence, concatBytes(
+		berTLV(berInteger, berEncodeInt(int64(c.version))),
+		berTLV(berOctetString, []byte(c.community)),
+		pdu,
+	)), nil
+}
+
+// decodeMessage unwraps a response and returns its variables and request ID
+func (c *snmpClient) decodeMessage(b []byte) ([]snmpVar, int32, error) {
+	if c.usm == nil {
+		return parseSNMPResponse(b)
+	}
+	msgID, pdu, err := c.usm.decodeMessage(b)
+	if err != nil {
+		return nil, 0, err
+	}
+	vars, _, err := parseSNMPPDU(pdu)
+	return vars, msgID, err
+}
+
+// roundTrip sends msg, retrying on timeout, until the response to
+// requestID arrives
+func (c *snmpClient) roundTrip(msg []byte, requestID int32) ([]snmpVar, error) {
+	buf := make([]byte, 65535)
+	var lastErr error
+	for attempt := 0; attempt <= c.retries; attempt++ {
+		if _, err := c.conn.Write(msg); err != nil {
+			return nil, err
+		}
+		deadline := time.Now().Add(c.timeout)
+		c.conn.SetReadDeadline(deadline)
+		for {
+			n, err := c.conn.Read(buf)
+			if err != nil {
+				lastErr = err
+				break
+			}
+			vars, id, err := c.decodeMessage(buf[:n])
+			// A late answer to an earlier attempt
+			if err == nil && id != requestID {
+				continue
+			}
+			return vars, err
+		}
+	}
+	return nil, fmt.Errorf("no response: %w", lastErr)
+}
+
+// encodeSNMPPDU builds a request PDU asking for oids
+func encodeSNMPPDU(pduType byte, requestID int32, oids []string) ([]byte, error) {
+	var bindings []byte
+	for _, oid := range oids {
+		encoded, err := berEncodeOID(oid)
+		if err != nil {
+			return nil, err
+		}
+		bindings = append(bindings, berTLV(berSequence, append(berTLV(berOID, encoded), berNull, 0))...)
+	}
+	return berTLV(pduType, concatBytes(
+		berTLV(berInteger, berEncodeInt(int64(requestID))),
+		berTLV(berInteger, berEncodeInt(0)),
+		berTLV(berInteger, berEncodeInt(0)),
+		berTLV(berSequence, bindings),
+	)), nil
+}
+
+// parseSNMPResponse decodes a v1/v2c GetResponse message
+func parseSNMPResponse(b []byte) ([]snmpVar, int32, error) {
+	tag, msg, _, err := berRead(b)
+	if err != nil || tag != berSequence {
+		return nil, 0, errSNMPMalformed
+	}
+	// version, community
+	for i := 0; i < 2; i++ {
+		if _, _, msg, err = berRead(msg); err != nil {
+			return nil, 0, err
+		}
+	}
+	return parseSNMPPDU(msg)
+}
+
+// parseSNMPPDU decodes a GetResponse PDU, or turns a v3 Report into an error
+func parseSNMPPDU(b []byte) ([]snmpVar, int32, error) {
+	tag, pdu, _, err := berRead(b)
+	if err != nil {
+		return nil, 0, err
+	}
+	if tag != snmpGetResponse && tag != snmpReport {
+		return nil, 0, fmt.Errorf("unexpected PDU type 0x%x", tag)
+	}
+
+	var fields [3]int64
+	for i := range fields {
+		var content []byte
+		if _, content, pdu, err = berRead(pdu); err != nil {
+			return nil, 0, err
+		}
+		fields[i] = berDecodeInt(content)
+	}
+	requestID, errorStatus := int32(fields[0]), fields[1]
+	if errorStatus == 2 {
+		return nil, requestID, errSNMPNoSuchName
+	}
+	if errorStatus != 0 {
+		return nil, requestID, fmt.Errorf("device returned error status %d", errorStatus)
+	}
+
+	_, list, _, err := berRead(pdu)
+	if err != nil {
+		return nil, 0, err
+	}
+	var vars []snmpVar
+	for len(list) > 0 {
+		var binding []byte
+		if _, binding, list, err = berRead(list); err != nil {
+			return nil, 0, err
+		}
+		_, oid, rest, err := berRead(binding)
+		if err != nil {
+			return nil, 0, err
+		}
+		typ, value, _, err := berRead(rest)
+		if err != nil {
+			return nil, 0, err
+		}
+		v := snmpVar{OID: berDecodeOID(oid), Type: typ}
+		switch typ {
+		case berInteger:
+			v.Value = berDecodeInt(value)
+		case snmpCounter32, snmpGauge32, snmpTimeTicks, snmpCounter64:
+			v.Value = berDecodeUint(value)
+		case berOctetString:
+			v.Value = string(value)
+		case berOID:
+			v.Value = berDecodeOID(value)
+		case snmpIPAddress:
+			if len(value) == 4 {
+				v.Value = net.IP(value).String()
+			}
+		}
+		vars = append(vars, v)
+	}
+	if tag == snmpReport {
+		return nil, requestID, usmReportError(vars)
+	}
+	return vars, requestID, nil
+}
+
+// ============================================================================
+// BER encoding
+// ============================================================================
+
+func concatBytes(parts ...[]byte) []byte {
+	var out []byte
+	for _, p := range parts {
+		out = append(out, p...)
+	}
+	return out
+}
+
+func berTLV(tag byte, content []byte) []byte {
+	out := []byte{tag}
+	n := len(content)
+	switch {
+	case n < 0x80:
+		out = append(out, byte(n))
+	case n <= 0xff:
+		out = append(out, 0x81, byte(n))
+	default:
+		out = append(out, 0x82, byte(n>>8), byte(n))
+	}
+	return append(out, content...)
+}
+
+func berEncodeInt(v int64) []byte {
+	out := []byte{byte(v)}
+	for v > 0x7f || v < -0x80 {
+		v >>= 8
+		out = append([]byte{byte(v)}, out...)
+	}
+	return out
+}
+
+func berEncodeOID(oid string) ([]byte, error) {
+	parts := strings.Split(strings.TrimPrefix(oid, "."), ".")
+	if len(parts) < 2 {
+		return nil, fmt.Errorf("invalid OID %q", oid)
+	}
+	nums := make([]uint64, len(parts))
+	for i, p := range parts {
+		n, err := strconv.ParseUint(p, 10, 32)
+		if err != nil {
+			return nil, fmt.Errorf("invalid OID %q", oid)
+		}
+		nums[i] = n
+	}
+	out := []byte{byte(nums[0]*40 + nums[1])}
+	for _, n := range nums[2:] {
+		chunk := []byte{byte(n & 0x7f)}
+		for n >>= 7; n > 0; n >>= 7 {
+			chunk = append([]byte{byte(n&0x7f) | 0x80}, chunk...)
+		}
+		out = append(out, chunk...)
+	}
+	return out, nil
+}
+
+// berRead splits the first TLV off b
+func berRead(b []byte) (tag byte, content, rest []byte, err error) {
+	if len(b) < 2 {
+		return 0, nil, nil, fmt.Errorf("truncated message")
+	}
+	tag = b[0]
+	n, offset := int(b[1]), 2
+	if n&0x80 != 0 {
+		size := n & 0x7f
+		if size == 0 || size > 3 || len(b) < 2+size {
+			return 0, nil, nil, fmt.Errorf("invalid length")
+		}
+		n = 0
+		for _, c := range b[2 : 2+size] {
+			n = n<<8 | int(c)
+		}
+		offset += size
+	}
+	if len(b) < offset+n {
+		return 0, nil, nil, fmt.Errorf("truncated message")
+	}
+	return tag, b[offset : offset+n], b[offset+n:], nil
+}
+
+func berDecodeInt(b []byte) int64 {
+	var v int64
+	for i, c := range b {
+		if i == 0 && c&0x80 != 0 {
+			v = -1
+		}
+		v = v<<8 | int64(c)
+	}
+	return v
+}
+
+func berDecodeUint(b []byte) uint64 {
+	var v uint64
+	for _, c := range b {
+		v = v<<8 | uint64(c)
+	}
+	return v
+}
+
+func berDecodeOID(b []byte) string {
+	if len(b) == 0 {
+		return ""
+	}
+	parts := []string{strconv.Itoa(int(b[0]) / 40), strconv.Itoa(int(b[0]) % 40)}
+	var n uint64
+	for _, c := range b[1:] {
+		n = n<<7 | uint64(c&0x7f)
+		if c&0x80 == 0 {
+			parts = append(parts, strconv.FormatUint(n, 10))
+			n = 0
+		}
+	}
+	return strings.Join(parts, ".")
+}
@@ -0,0 +1,470 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"path"
+	"strings"
+	"sync"
+	"time"
+)
+
+// ============================================================================
+// SNMP Polling
+// ============================================================================
+//
+// Devices that cannot run the agent (switches, appliances) are added as
+// regular servers with an "snmp" block. The server polls them over SNMP v1,
+// v2c or v3 and feeds the result through the same path as agent samples, so
+// they get history, alerts and live updates like any other server. CPU and
+// memory come from HOST-RESOURCES-MIB unless the device needs vendor OIDs
+// (cpu_oid/memory_oid, read as percentages); traffic comes from the IF-MIB
+// interface counters.
+
+const (
+	DefaultSNMPPort         = 161
+	DefaultSNMPIntervalSecs = 10
+	DefaultSNMPTimeoutSecs  = 2
+	MaxSNMPIntervalSecs     = 20 // Below the online timeout, or the device flaps offline
+	snmpPollerTick          = time.Second
+)
+
+// snmpCounter32Wrap is where 32-bit interface counters wrap to zero
+const snmpCounter32Wrap = 1 << 32
+
+// Standard OIDs
+const (
+	oidSysDescr        = "1.3.6.1.2.1.1.1.0"
+	oidSysUpTime       = "1.3.6.1.2.1.1.3.0"
+	oidSysName         = "1.3.6.1.2.1.1.5.0"
+	oidHrProcessorLoad = "1.3.6.1.2.1.25.3.3.1.2"
+	oidHrStorageType   = "1.3.6.1.2.1.25.2.3.1.2"
+	oidHrStorageUnits  = "1.3.6.1.2.1.25.2.3.1.4"
+	oidHrStorageSize   = "1.3.6.1.2.1.25.2.3.1.5"
+	oidHrStorageUsed   = "1.3.6.1.2.1.25.2.3.1.6"
+	oidHrStorageRAM    = "1.3.6.1.2.1.25.2.1.2"
+	oidIfDescr         = "1.3.6.1.2.1.2.2.1.2"
+	oidIfInOctets      = "1.3.6.1.2.1.2.2.1.10"
+	oidIfOutOctets     = "1.3.6.1.2.1.2.2.1.16"
+	oidIfHCInOctets    = "1.3.6.1.2.1.31.1.1.1.6"
+	oidIfHCOutOctets   = "1.3.6.1.2.1.31.1.1.1.10"
+)
+
+// DefaultSNMPInterfaceExclude skips loopback and null interfaces
+var DefaultSNMPInterfaceExclude = []string{"lo", "Loopback*", "InLoopBack*", "Null*"}
+
+// SNMPSettings make the server poll a device over SNMP instead of waiting
+// for an agent
+type SNMPSettings struct {
+	Host         string `json:"host"`
+	Port         int    `json:"port,omitempty"`          // Default: 161
+	Version      string `json:"version,omitempty"`       // "1", "2c" (default) or "3"
+	Community    string `json:"community,omitempty"`     // v1/v2c, default: "public"
+	IntervalSecs int    `json:"interval_secs,omitempty"` // Default: 10, at most 20
+	TimeoutSecs  int    `json:"timeout_secs,omitempty"`  // Per request, default: 2
+	// Vendor OIDs returning CPU and memory usage in percent, for devices
+	// without HOST-RESOURCES-MIB
+	CPUOID    string `json:"cpu_oid,omitempty"`
+	MemoryOID string `json:"memory_oid,omitempty"`
+	// Interfaces counted towards traffic (glob patterns on ifDescr); all but
+	// loopbacks by default
+	Interfaces []string `json:"interfaces,omitempty"`
+	// SNMPv3 user. Without auth_password the user is noAuthNoPriv; with
+	// priv_password the requests are encrypted too.
+	Username     string `json:"username,omitempty"`
+	AuthProtocol string `json:"auth_protocol,omitempty"` // MD5, SHA (default) or SHA256
+	AuthPassword string `json:"auth_password,omitempty"`
+	PrivProtocol string `json:"priv_protocol,omitempty"` // DES or AES (default, AES-128)
+	PrivPassword string `json:"priv_password,omitempty"`
+}
+
+// Validate checks the settings; zero values mean the defaults
+func (s *SNMPSettings) Validate() error {
+	if s.Host == "" {
+		return fmt.Errorf("snmp.host is required")
+	}
+	switch s.Version {
+	case "", "2c", "1":
+	case "3":
+		if err := s.validateV3(); err != nil {
+			return err
+		}
+	default:
+		return fmt.Errorf("snmp.version must be 1, 2c or 3")
+	}
+	if s.Port < 0 || s.Port > 65535 {
+		return fmt.Errorf("snmp.port is out of range")
+	}
+	if s.IntervalSecs < 0 || s.IntervalSecs > MaxSNMPIntervalSecs {
+		return fmt.Errorf("snmp.interval_secs must be between 1 and %d", MaxSNMPIntervalSecs)
+	}
+	for _, oid := range []string{s.CPUOID, s.MemoryOID} {
+		if oid == "" {
+			continue
+		}
+		if _, err := berEncodeOID(oid); err != nil {
+			return err
+		}
+	}
+	for _, pattern := range s.Interfaces {
+		if _, err := path.Match(pattern, ""); err != nil {
+			return fmt.Errorf("invalid interface pattern %q", pattern)
+		}
+	}
+	return nil
+}
+
+func (s *SNMPSettings) interval() time.Duration {
+	if s.IntervalSecs <= 0 {
+		return DefaultSNMPIntervalSecs * time.Second
+	}
+	return time.Duration(s.IntervalSecs) * time.Second
+}
+
+// snmpCounter tracks one interface counter across polls. Counter32 values
+// wrap at 4 GiB, so the total is accumulated from wrap-aware deltas.
+type snmpCounter struct {
+	raw, total uint64
+}
+
+func (c *snmpCounter) update(raw uint64, wrap bool) uint64 {
+	delta := raw - c.raw
+	if raw < c.raw {
+		delta = 0
+		if wrap {
+			delta = raw + snmpCounter32Wrap - c.raw
+		}
+	}
+	c.raw = raw
+	c.total += delta
+	return delta
+}
+
+// snmpDevice is the polling state of one device
+type snmpDevice struct {
+	running    bool
+	lastPoll   time.Time // Start of the last poll
+	lastSample time.Time // When the counters were last read
+	uptime     uint64
+	rx, tx     map[string]*snmpCounter
+	failing    bool
+}
+
+// snmpPoller polls every server with SNMP settings
+type snmpPoller struct {
+	mu      sync.Mutex
+	devices map[string]*snmpDevice
+}
+
+// snmpPollLoop starts due polls until the process exits
+func snmpPollLoop(state *AppState) {
+	poller := &snmpPoller{devices: make(map[string]*snmpDevice)}
+	ticker := time.NewTicker(snmpPollerTick)
+	defer ticker.Stop()
+	for range ticker.C {
+		poller.tick(state)
+	}
+}
+
+func (p *snmpPoller) tick(state *AppState) {
+	type target struct {
+		id       string
+		settings SNMPSettings
+	}
+	state.ConfigMu.RLock()
+	var targets []target
+	for _, server := range state.Config.Servers {
+		if server.SNMP != nil && server.SNMP.Host != "" {
+			targets = append(targets, target{server.ID, *server.SNMP})
+		}
+	}
+	state.ConfigMu.RUnlock()
+
+	now := time.Now()
+	p.mu.Lock()
+	defer p.mu.Unlock()
+	configured := make(map[string]bool, len(targets))
+	for _, t := range targets {
+		configured[t.id] = true
+		device := p.devices[t.id]
+		if device == nil {
+			device = &snmpDevice{rx: make(map[string]*snmpCounter), tx: make(map[string]*snmpCounter)}
+			p.devices[t.id] = device
+		}
+		if device.running || now.Sub(device.lastPoll) < t.settings.interval() {
+			continue
+		}
+		device.running, device.lastPoll = true, now
+		go p.poll(state, t.id, t.settings, device)
+	}
+	// Forget devices that were removed or lost their SNMP settings
+	for id, device := range p.devices {
+		if !configured[id] && !device.running {
+			delete(p.devices, id)
+		}
+	}
+}
+
+func (p *snmpPoller) poll(state *AppState, serverID string, settings SNMPSettings, device *snmpDevice) {
+	metrics, err := p.collect(settings, device)
+
+	p.mu.Lock()
+	device.running = false
+	if err != nil {
+		if !device.failing {
+			log.Printf("SNMP poll of %s (%s) failed: %v", serverID, settings.Host, err)
+		}
+		device.failing = true
+		p.mu.Unlock()
+		return
+	}
+	if device.failing {
+		log.Printf("SNMP poll of %s (%s) recovered", serverID, settings.Host)
+	}
+	device.failing = false
+	p.mu.Unlock()
+
+	state.recordPolledMetrics(serverID, metrics)
+}
+
+// collect queries a device and maps the answers to a metrics sample
+func (p *snmpPoller) collect(settings SNMPSettings, device *snmpDevice) (*SystemMetrics, error) {
+	port, community := settings.Port, settings.Community
+	if port == 0 {
+		port = DefaultSNMPPort
+	}
+	if community == "" {
+		community = "public"
+	}
+	timeout := time.Duration(settings.TimeoutSecs) * time.Second
+	if timeout <= 0 {
+		timeout = DefaultSNMPTimeoutSecs * time.Second
+	}
+	client, err := dialSNMP(settings.Host, port, community, settings.Version, timeout)
+	if err != nil {
+		return nil, err
+	}
+	defer client.Close()
+	if settings.Version == "3" {
+		client.usm = newSNMPUSM(&settings)
+	}
+
+	system, err := client.Get(oidSysDescr, oidSysUpTime, oidSysName)
+	if err != nil {
+		return nil, err
+	}
+	m := &SystemMetrics{Timestamp: time.Now().UTC()}
+	var uptime uint64
+	for _, v := range system {
+		switch v.OID {
+		case oidSysDescr:
+			descr, _ := v.Value.(string)
+			if i := strings.IndexAny(descr, "\r\n"); i >= 0 {
+				descr = descr[:i]
+			}
+			m.OS.Name = descr
+		case oidSysUpTime:
+			ticks, _ := v.Uint()
+			uptime = ticks / 100
+		case oidSysName:
+			m.Hostname, _ = v.Value.(string)
+		}
+	}
+	m.Uptime = uptime
+
+	if err := collectSNMPCPU(client, settings, m); err != nil {
+		return nil, err
+	}
+	if err := collectSNMPMemory(client, settings, m); err != nil {
+		return nil, err
+	}
+
+	// Counters restart with the device
+	p.mu.Lock()
+	rebooted := uptime < device.uptime
+	device.uptime = uptime
+	if rebooted {
+		device.rx, device.tx = make(map[string]*snmpCounter), make(map[string]*snmpCounter)
+	}
+	p.mu.Unlock()
+	if err := p.collectTraffic(client, settings, device, m); err != nil {
+		return nil, err
+	}
+	return m, nil
+}
+
+func collectSNMPCPU(client *snmpClient, settings SNMPSettings, m *SystemMetrics) error {
+	if settings.CPUOID != "" {
+		vars, err := client.Get(settings.CPUOID)
+		if err != nil {
+			return fmt.Errorf("cpu_oid: %w", err)
+		}
+		if len(vars) > 0 {
+			usage, _ := vars[0].Float()
+			m.CPU.Usage = float32(usage)
+		}
+		return nil
+	}
+	loads, err := client.Walk(oidHrProcessorLoad)
+	if err != nil {
+		return err
+	}
+	var sum float64
+	for _, v := range loads {
+		load, _ := v.Float()
+		m.CPU.PerCore = append(m.CPU.PerCore, float32(load))
+		sum += load
+	}
+	if len(loads) > 0 {
+		m.CPU.Cores = len(loads)
+		m.CPU.Usage = float32(sum / float64(len(loads)))
+	}
+	return nil
+}
+
+func collectSNMPMemory(client *snmpClient, settings SNMPSettings, m *SystemMetrics) error {
+	if settings.MemoryOID != "" {
+		vars, err := client.Get(settings.MemoryOID)
+		if err != nil {
+			return fmt.Errorf("memory_oid: %w", err)
+		}
+		if len(vars) > 0 {
+			usage, _ := vars[0].Float()
+			m.Memory.UsagePercent = float32(usage)
+		}
+		return nil
+	}
+	types, err := client.Walk(oidHrStorageType)
+	if err != nil {
+		return err
+	}
+	for _, v := range types {
+		if v.Value != oidHrStorageRAM {
+			continue
+		}
+		index := strings.TrimPrefix(v.OID, oidHrStorageType+".")
+		vars, err := client.Get(oidHrStorageUnits+"."+index, oidHrStorageSize+"."+index, oidHrStorageUsed+"."+index)
+		if err != nil || len(vars) != 3 {
+			return err
+		}
+		units, _ := vars[0].Uint()
+		size, _ := vars[1].Uint()
+		used, _ := vars[2].Uint()
+		m.Memory.Total, m.Memory.Used = size*units, used*units
+		m.Memory.Available = m.Memory.Total - min(m.Memory.Used, m.Memory.Total)
+		if m.Memory.Total > 0 {
+			m.Memory.UsagePercent = float32(float64(m.Memory.Used) / float64(m.Memory.Total) * 100)
+		}
+		break
+	}
+	return nil
+}
+
+// collectTraffic reads the interface counters, preferring the 64-bit ones
+func (p *snmpPoller) collectTraffic(client *snmpClient, settings SNMPSettings, device *snmpDevice, m *SystemMetrics) error {
+	descrs, err := client.Walk(oidIfDescr)
+	if err != nil {
+		return err
+	}
+	in, out, wrap := oidIfHCInOctets, oidIfHCOutOctets, false
+	if settings.Version == "1" {
+		in, out, wrap = oidIfInOctets, oidIfOutOctets, true
+	}
+	rxVars, err := client.Walk(in)
+	if err == nil && len(rxVars) == 0 && !wrap {
+		// No 64-bit counters on this device
+		in, out, wrap = oidIfInOctets, oidIfOutOctets, true
+		rxVars, err = client.Walk(in)
+	}
+	if err != nil {
+		return err
+	}
+	txVars, err := client.Walk(out)
+	if err != nil {
+		return err
+	}
+
+	names := make(map[string]string, len(descrs))
+	for _, v := range descrs {
+		name, _ := v.Value.(string)
+		names[strings.TrimPrefix(v.OID, oidIfDescr+".")] = name
+	}
+	counters := func(vars []snmpVar, root string) map[string]uint64 {
+		values := make(map[string]uint64, len(vars))
+		for _, v := range vars {
+			if n, ok := v.Uint(); ok {
+				values[strings.TrimPrefix(v.OID, root+".")] = n
+			}
+		}
+		return values
+	}
+	rx, tx := counters(rxVars, in), counters(txVars, out)
+
+	p.mu.Lock()
+	defer p.mu.Unlock()
+	elapsed := time.Since(device.lastSample).Seconds()
+	device.lastSample = time.Now()
+	first := len(device.rx) == 0
+	var deltaRx, deltaTx uint64
+	for index, name := range names {
+		if !snmpInterfaceCounted(name, settings.Interfaces) {
+			continue
+		}
+		rxRaw, ok := rx[index]
+		if !ok {
+			continue
+		}
+		txRaw := tx[index]
+		rc, tc := device.rx[name], device.tx[name]
+		if rc == nil {
+			rc, tc = &snmpCounter{raw: rxRaw, total: rxRaw}, &snmpCounter{raw: txRaw, total: txRaw}
+			device.rx[name], device.tx[name] = rc, tc
+		} else {
+			deltaRx += rc.update(rxRaw, wrap)
+			deltaTx += tc.update(txRaw, wrap)
+		}
+		m.Network.Interfaces = append(m.Network.Interfaces, NetworkInterface{Name: name, RxBytes: rc.total, TxBytes: tc.total})
+		m.Network.TotalRx += rc.total
+		m.Network.TotalTx += tc.total
+	}
+	if !first && elapsed > 0 {
+		m.Network.RxSpeed = uint64(float64(deltaRx) / elapsed)
+		m.Network.TxSpeed = uint64(float64(deltaTx) / elapsed)
+	}
+	return nil
+}
+
+// snmpInterfaceCounted reports whether an interface counts towards traffic
+func snmpInterfaceCounted(name string, include []string) bool {
+	patterns, want := include, true
+	if len(patterns) == 0 {
+		patterns, want = DefaultSNMPInterfaceExclude, false
+	}
+	for _, pattern := range patterns {
+		if ok, _ := path.Match(pattern, name); ok {
+			return want
+		}
+	}
+	return !want
+}
+
+// recordPolledMetrics stores a sample the server collected itself and makes
+// it the live state of the server, like a sample received from an agent
+func (s *AppState) recordPolledMetrics(serverID string, metrics *SystemMetrics) {
+	if !SanitizeMetrics(serverID, metrics) {
+		return
+	}
+	StoreMetricsWithDedup(serverID, metrics)
+	s.Ingest.RecordMetrics(1)
+
+	s.AgentMetricsMu.Lock()
+	now := time.Now()
+	prev := s.AgentMetrics[serverID]
+	s.AgentMetrics[serverID] = &AgentMetricsData{
+		ServerID:    serverID,
+		Metrics:     *metrics,
+		LastUpdated: now,
+		Interval:    prev.nextInterval(now),
+	}
+	s.AgentMetricsMu.Unlock()
+}
@@ -0,0 +1,578 @@
+package main
+
+import (
+	"bytes"
+	"encoding/hex"
+	"errors"
+	"math"
+	"net"
+	"strconv"
+	"strings"
+	"testing"
+	"time"
+)
+
+func mustHex(t *testing.T, s string) []byte {
+	t.Helper()
+	b, err := hex.DecodeString(strings.Join(strings.Fields(s), ""))
+	if err != nil {
+		t.Fatal(err)
+	}
+	return b
+}
+
+func TestBERInteger(t *testing.T) {
+	vectors := []struct {
+		value int64
+		hex   string
+	}{
+		{0, "00"},
+		{127, "7f"},
+		{128, "0080"},
+		{255, "00ff"},
+		{256, "0100"},
+		{-1, "ff"},
+		{-128, "80"},
+		{-129, "ff7f"},
+		{65507, "00ffe3"},
+		{math.MaxInt32, "7fffffff"},
+	}
+	for _, v := range vectors {
+		got := berEncodeInt(v.value)
+		if hex.EncodeToString(got) != v.hex {
+			t.Errorf("berEncodeInt(%d) = %x, want %s", v.value, got, v.hex)
+		}
+		if back := berDecodeInt(got); back != v.value {
+			t.Errorf("berDecodeInt(%x) = %d, want %d", got, back, v.value)
+		}
+	}
+}
+
+func TestBEROID(t *testing.T) {
+	vectors := []struct {
+		oid string
+		hex string
+	}{
+		{"1.3.6.1.2.1.1.1.0", "2b06010201010100"},
+		{"1.3.6.1.4.1.2021.11.9.0", "2b060104018f650b0900"},
+		{"1.3.6.1.2.1.1.1.4294967295", "2b0601020101018fffffff7f"},
+	}
+	for _, v := range vectors {
+		got, err := berEncodeOID(v.oid)
+		if err != nil {
+			t.Fatalf("berEncodeOID(%s): %v", v.oid, err)
+		}
+		if hex.EncodeToString(got) != v.hex {
+			t.Errorf("berEncodeOID(%s) = %x, want %s", v.oid, got, v.hex)
+		}
+		if back := berDecodeOID(got); back != v.oid {
+			t.Errorf("berDecodeOID(%x) = %s, want %s", got, back, v.oid)
+		}
+	}
+
+	if got, err := berEncodeOID(".1.3.6.1"); err != nil || hex.EncodeToString(got) != "2b0601" {
+		t.Errorf("leading dot: got %x, %v", got, err)
+	}
+	for _, bad := range []string{"", "1", "1.3.x", "1.3.6.-1", "1.3.4294967296"} {
+		if _, err := berEncodeOID(bad); err == nil {
+			t.Errorf("berEncodeOID(%q) accepted", bad)
+		}
+	}
+}
+
+func TestBERLengths(t *testing.T) {
+	for _, n := range []int{0, 127, 128, 255, 256, 300, 65535} {
+		content := bytes.Repeat([]byte{0xaa}, n)
+		encoded := berTLV(berOctetString, content)
+		var header string
+		switch {
+		case n < 0x80:
+			header = hex.EncodeToString([]byte{berOctetString, byte(n)})
+		case n <= 0xff:
+			header = hex.EncodeToString([]byte{berOctetString, 0x81, byte(n)})
+		default:
+			header = hex.EncodeToString([]byte{berOctetString, 0x82, byte(n >> 8), byte(n)})
+		}
+		if got := hex.EncodeToString(encoded[:len(encoded)-n]); got != header {
+			t.Errorf("length %d: header %s, want %s", n, got, header)
+		}
+
+		tag, got, rest, err := berRead(append(encoded, 0x05, 0x00))
+		if err != nil || tag != berOctetString || !bytes.Equal(got, content) || !bytes.Equal(rest, []byte{0x05, 0x00}) {
+			t.Errorf("length %d: berRead = %x, %d bytes, rest %x, %v", n, tag, len(got), rest, err)
+		}
+	}
+}
+
+func TestEncodeSNMPRequest(t *testing.T) {
+	// GetRequest for sysDescr.0, community "public", v2c, request ID 1
+	want := "302602010104067075626c6963a019020101020100020100300e300c06082b060102010101000500"
+
+	pdu, err := encodeSNMPPDU(snmpGetRequest, 1, []string{oidSysDescr})
+	if err != nil {
+		t.Fatal(err)
+	}
+	client := &snmpClient{community: "public", version: 1}
+	msg, err := client.encodeMessage(1, pdu)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if hex.EncodeToString(msg) != want {
+		t.Errorf("got  %x\nwant %s", msg, want)
+	}
+}
+
+func TestParseSNMPResponse(t *testing.T) {
+	msg := mustHex(t, `
+		3081a302010104067075626c6963a28195020212340201000201003081883011
+		06082b0601020101010004054c696e7578300e06082b06010201010300430201
+		003018060b2b060102011f0101010602460900ffffffffffffffff300f060a2b
+		060104018f650b09000201fb3015060d2b06010201041401010a00000140040a
+		000001300c06082b060102010109008000301306082b0601020101020006072b
+		060104018f65`)
+
+	vars, requestID, err := parseSNMPResponse(msg)
+	if err != nil {
+		t.Fatal(err)
+	}
+	if requestID != 0x1234 {
+		t.Errorf("request ID %#x, want 0x1234", requestID)
+	}
+	want := []snmpVar{
+		{OID: "1.3.6.1.2.1.1.1.0", Type: berOctetString, Value: "Linux"},
+		{OID: "1.3.6.1.2.1.1.3.0", Type: snmpTimeTicks, Value: uint64(256)},
+		{OID: "1.3.6.1.2.1.31.1.1.1.6.2", Type: snmpCounter64, Value: uint64(math.MaxUint64)},
+		{OID: "1.3.6.1.4.1.2021.11.9.0", Type: berInteger, Value: int64(-5)},
+		{OID: "1.3.6.1.2.1.4.20.1.1.10.0.0.1", Type: snmpIPAddress, Value: "10.0.0.1"},
+		{OID: "1.3.6.1.2.1.1.9.0", Type: snmpNoSuchObj, Value: nil},
+		{OID: "1.3.6.1.2.1.1.2.0", Type: berOID, Value: "1.3.6.1.4.1.2021"},
+	}
+	if len(vars) != len(want) {
+		t.Fatalf("got %d variables, want %d", len(vars), len(want))
+	}
+	for i := range want {
+		if vars[i] != want[i] {
+			t.Errorf("variable %d = %+v, want %+v", i, vars[i], want[i])
+		}
+	}
+
+	if n, ok := vars[1].Uint(); !ok || n != 256 {
+		t.Errorf("Uint of time ticks = %d, %v", n, ok)
+	}
+	if _, ok := vars[3].Uint(); ok {
+		t.Error("negative INTEGER converted to uint")
+	}
+	if f, ok := vars[3].Float(); !ok || f != -5 {
+		t.Errorf("Float of INTEGER = %v, %v", f, ok)
+	}
+	if f, ok := (snmpVar{Value: " 12.5 "}).Float(); !ok || f != 12.5 {
+		t.Errorf("Float of numeric string = %v, %v", f, ok)
+	}
+}
+
+func TestParseSNMPResponseErrors(t *testing.T) {
+	// noSuchName (v1), and genErr
+	if _, id, err := parseSNMPResponse(mustHex(t, "301802010004067075626c6963a20b0201070201020201013000")); !errors.Is(err, errSNMPNoSuchName) || id != 7 {
+		t.Errorf("noSuchName: id %d, err %v", id, err)
+	}
+	if _, _, err := parseSNMPResponse(mustHex(t, "301802010104067075626c6963a20b0201070201050201013000")); err == nil {
+		t.Error("error status 5 accepted")
+	}
+
+	malformed := map[string]string{
+		"empty":                 "",
+		"tag only":              "30",
+		"not a sequence":        "3100",
+		"length past the end":   "3005020101",
+		"length of length 4":    "3084000000000201",
+		"length of length 0":    "3080",
+		"missing PDU":           "300b02010104067075626c6963",
+		"request PDU":           "302602010104067075626c6963a019020101020100020100300e300c06082b060102010101000500",
+		"truncated PDU fields":  "300d02010104067075626c6963a200",
+		"missing binding list":  "301602010104067075626c6963a209020101020100020100",
+		"binding without value": "302402010104067075626c6963a217020101020100020100300c300a06082b06010201010100",
+		"truncated binding":     "301c02010104067075626c6963a20f020101020100020100300430040608",
+	}
+	for name, h := range malformed {
+		if _, _, err := parseSNMPResponse(mustHex(t, h)); err == nil {
+			t.Errorf("%s: accepted", name)
+		}
+	}
+}
+
+// testBinding is a variable of a fake device: an OID and its encoded value
+type testBinding struct {
+	oid   string
+	value []byte
+}
+
+func compareOIDs(a, b string) int {
+	pa, pb := strings.Split(a, "."), strings.Split(b, ".")
+	for i := 0; i < len(pa) && i < len(pb); i++ {
+		x, _ := strconv.ParseUint(pa[i], 10, 32)
+		y, _ := strconv.ParseUint(pb[i], 10, 32)
+		if x != y {
+			if x < y {
+				return -1
+			}
+			return 1
+		}
+	}
+	return len(pa) - len(pb)
+}
+
+// parseTestRequest decodes a request PDU the way a device would
+func parseTestRequest(b []byte) (byte, int32, []string, error) {
+	tag, pdu, _, err := berRead(b)
+	if err != nil {
+		return 0, 0, nil, err
+	}
+	var fields [3][]byte
+	for i := range fields {
+		if _, fields[i], pdu, err = berRead(pdu); err != nil {
+			return 0, 0, nil, err
+		}
+	}
+	_, list, _, err := berRead(pdu)
+	if err != nil {
+		return 0, 0, nil, err
+	}
+	var oids []string
+	for len(list) > 0 {
+		var binding []byte
+		if _, binding, list, err = berRead(list); err != nil {
+			return 0, 0, nil, err
+		}
+		_, oid, _, err := berRead(binding)
+		if err != nil {
+			return 0, 0, nil, err
+		}
+		oids = append(oids, berDecodeOID(oid))
+	}
+	return tag, int32(berDecodeInt(fields[0])), oids, nil
+}
+
+// answerFromMIB builds the response PDU of a device holding mib, which is
+// sorted by OID
+func answerFromMIB(tag byte, requestID int32, oids []string, mib []testBinding) []byte {
+	var bindings []byte
+	for _, oid := range oids {
+		value := []byte{snmpNoSuchObj, 0}
+		name := oid
+		if tag == snmpGetNext {
+			value = []byte{snmpEndOfMib, 0}
+		}
+		for _, b := range mib {
+			if (tag == snmpGetRequest && b.oid == oid) || (tag == snmpGetNext && compareOIDs(b.oid, oid) > 0) {
+				name, value = b.oid, b.value
+				break
+			}
+		}
+		encoded, _ := berEncodeOID(name)
+		bindings = append(bindings, berTLV(berSequence, append(berTLV(berOID, encoded), value...))...)
+	}
+	return berTLV(snmpGetResponse, concatBytes(
+		berTLV(berInteger, berEncodeInt(int64(requestID))),
+		berTLV(berInteger, berEncodeInt(0)),
+		berTLV(berInteger, berEncodeInt(0)),
+		berTLV(berSequence, bindings),
+	))
+}
+
+// serveSNMP answers UDP datagrams with handle until the test ends. A nil
+// answer drops the request.
+func serveSNMP(t *testing.T, handle func([]byte) []byte) (string, int) {
+	t.Helper()
+	pc, err := net.ListenPacket("udp", "127.0.0.1:0")
+	if err != nil {
+		t.Fatal(err)
+	}
+	t.Cleanup(func() { pc.Close() })
+	go func() {
+		buf := make([]byte, 65535)
+		for {
+			n, addr, err := pc.ReadFrom(buf)
+			if err != nil {
+				return
+			}
+			if resp := handle(append([]byte(nil), buf[:n]...)); resp != nil {
+				pc.WriteTo(resp, addr)
+			}
+		}
+	}()
+	addr := pc.LocalAddr().(*net.UDPAddr)
+	return addr.IP.String(), addr.Port
+}
+
+var testMIB = []testBinding{
+	{"1.3.6.1.2.1.1.5.0", berTLV(berOctetString, []byte("switch-1"))},
+	{"1.3.6.1.2.1.2.2.1.2.1", berTLV(berOctetString, []byte("lo"))},
+	{"1.3.6.1.2.1.2.2.1.2.2", berTLV(berOctetString, []byte("eth0"))},
+	{"1.3.6.1.2.1.2.2.1.2.10", berTLV(berOctetString, []byte("eth1"))},
+	{"1.3.6.1.2.1.2.2.1.10.1", berTLV(snmpCounter32, []byte{0x00, 0xff, 0xff, 0xff, 0xff})},
+}
+
+func TestSNMPClientV2c(t *testing.T) {
+	host, port := serveSNMP(t, func(req []byte) []byte {
+		_, msg, _, err := berRead(req)
+		if err != nil {
+			return nil
+		}
+		var version, community []byte
+		if _, version, msg, err = berRead(msg); err != nil {
+			return nil
+		}
+		if _, community, msg, err = berRead(msg); err != nil || string(community) != "secret" {
+			return nil
+		}
+		tag, requestID, oids, err := parseTestRequest(msg)
+		if err != nil {
+			return nil
+		}
+		return berTLV(berSequence, concatBytes(
+			berTLV(berInteger, version),
+			berTLV(berOctetString, community),
+			answerFromMIB(tag, requestID, oids, testMIB),
+		))
+	})
+
+	client, err := dialSNMP(host, port, "secret", "2c", time.Second)
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer client.Close()
+
+	vars, err := client.Get(oidSysName, "1.3.6.1.2.1.1.6.0")
+	if err != nil {
+		t.Fatal(err)
+	}
+	if len(vars) != 2 || vars[0].Value != "switch-1" || vars[1].Type != snmpNoSuchObj || vars[1].Value != nil {
+		t.Errorf("Get = %+v", vars)
+	}
+
+	// The walk stops at the end of the subtree, and orders .10 after .2
+	walked, err := client.Walk(oidIfDescr)
+	if err != nil {
+		t.Fatal(err)
+	}
+	var names []string
+	for _, v := range walked {
+		names = append(names, v.Value.(string))
+	}
+	if strings.Join(names, ",") != "lo,eth0,eth1" {
+		t.Errorf("Walk = %v", names)
+	}
+
+	if n, ok := mustWalkOne(t, client, oidIfInOctets).Uint(); !ok || n != math.MaxUint32 {
+		t.Errorf("Counter32 = %d, %v", n, ok)
+	}
+
+	// A wrong community gets no answer at all
+	wrong, err := dialSNMP(host, port, "public", "2c", 100*time.Millisecond)
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer wrong.Close()
+	if _, err := wrong.Get(oidSysName); err == nil {
+		t.Error("request with the wrong community answered")
+	}
+}
+
+func mustWalkOne(t *testing.T, client *snmpClient, root string) snmpVar {
+	t.Helper()
+	vars, err := client.Walk(root)
+	if err != nil || len(vars) != 1 {
+		t.Fatalf("Walk(%s) = %+v, %v", root, vars, err)
+	}
+	return vars[0]
+}
+
+func TestLocalizeSNMPKey(t *testing.T) {
+	// RFC 3414 A.3, plus the same inputs with SHA-256
+	engineID := mustHex(t, "000000000000000000000002")
+	vectors := []struct {
+		protocol string
+		key      string
+	}{
+		{"MD5", "526f5eed9fcce26f8964c2930787d82b"},
+		{"SHA", "6695febc9288e36282235fc7151f128497b38f3f"},
+		{"SHA256", "8982e0e549e866db361a6b625d84cccc11162d453ee8ce3a6445c2d6776f0f8b"},
+	}
+	for _, v := range vectors {
+		got := localizeSNMPKey(snmpAuthProtocols[v.protocol].hash, "maplesyrup", engineID)
+		if hex.EncodeToString(got) != v.key {
+			t.Errorf("%s: got %x, want %s", v.protocol, got, v.key)
+		}
+	}
+}
+
+func TestSNMPv3Validate(t *testing.T) {
+	base := SNMPSettings{Host: "10.0.0.1", Version: "3", Username: "monitor"}
+	cases := []struct {
+		name   string
+		modify func(*SNMPSettings)
+		ok     bool
+	}{
+		{"noAuthNoPriv", func(s *SNMPSettings) {}, true},
+		{"authNoPriv", func(s *SNMPSettings) { s.AuthPassword = "authpass1" }, true},
+		{"authPriv", func(s *SNMPSettings) { s.AuthPassword, s.PrivPassword = "authpass1", "privpass1" }, true},
+		{"lowercase protocols", func(s *SNMPSettings) {
+			s.AuthProtocol, s.AuthPassword, s.PrivProtocol, s.PrivPassword = "sha256", "authpass1", "des", "privpass1"
+		}, true},
+		{"no user", func(s *SNMPSettings) { s.Username = "" }, false},
+		{"priv without auth", func(s *SNMPSettings) { s.PrivPassword = "privpass1" }, false},
+		{"short password", func(s *SNMPSettings) { s.AuthPassword = "short" }, false},
+		{"unknown auth", func(s *SNMPSettings) { s.AuthProtocol, s.AuthPassword = "SHA512", "authpass1" }, false},
+		{"unknown priv", func(s *SNMPSettings) {
+			s.AuthPassword, s.PrivProtocol, s.PrivPassword = "authpass1", "3DES", "privpass1"
+		}, false},
+	}
+	for _, c := range cases {
+		s := base
+		c.modify(&s)
+		if err := s.Validate(); (err == nil) != c.ok {
+			t.Errorf("%s: Validate() = %v", c.name, err)
+		}
+	}
+}
+
+var snmpV3Levels = []SNMPSettings{
+	{Username: "noauth"},
+	{Username: "md5", AuthProtocol: "MD5", AuthPassword: "maplesyrup"},
+	{Username: "md5des", AuthProtocol: "MD5", AuthPassword: "maplesyrup", PrivProtocol: "DES", PrivPassword: "pancakes1"},
+	{Username: "shaaes", AuthPassword: "maplesyrup", PrivPassword: "pancakes1"},
+	{Username: "sha256aes", AuthProtocol: "SHA256", AuthPassword: "maplesyrup", PrivPassword: "pancakes1"},
+}
+
+func TestSNMPv3MessageRoundTrip(t *testing.T) {
+	engineID := mustHex(t, "80001f8880e9630000d61ff449")
+	for _, settings := range snmpV3Levels {
+		sender, receiver := newSNMPUSM(&settings), newSNMPUSM(&settings)
+		sender.setEngine(engineID, 3, 12345)
+		receiver.setEngine(engineID, 3, 12345)
+
+		pdu := answerFromMIB(snmpGetRequest, 42, []string{oidSysName}, testMIB)
+		msg, err := sender.encodeMessage(42, pdu, false)
+		if err != nil {
+			t.Fatalf("%s: %v", settings.Username, err)
+		}
+		if sender.priv != "" && bytes.Contains(msg, []byte("switch-1")) {
+			t.Errorf("%s: value sent in the clear", settings.Username)
+		}
+
+		msgID, got, err := receiver.decodeMessage(msg)
+		if err != nil {
+			t.Fatalf("%s: decode: %v", settings.Username, err)
+		}
+		vars, requestID, err := parseSNMPPDU(got)
+		if err != nil || msgID != 42 || requestID != 42 || len(vars) != 1 || vars[0].Value != "switch-1" {
+			t.Errorf("%s: got msgID %d, request %d, %+v, %v", settings.Username, msgID, requestID, vars, err)
+		}
+
+		if sender.auth == nil {
+			continue
+		}
+		// Any change to an authenticated message is caught
+		tampered := append([]byte(nil), msg...)
+		tampered[len(tampered)-1] ^= 0x01
+		if _, _, err := receiver.decodeMessage(tampered); err == nil {
+			t.Errorf("%s: tampered message accepted", settings.Username)
+		}
+		wrong := settings
+		wrong.AuthPassword = "maplesyrup2"
+		other := newSNMPUSM(&wrong)
+		other.setEngine(engineID, 3, 12345)
+		if _, _, err := other.decodeMessage(msg); err == nil {
+			t.Errorf("%s: message accepted with the wrong auth password", settings.Username)
+		}
+	}
+}
+
+func TestSNMPv3MalformedMessages(t *testing.T) {
+	u := newSNMPUSM(&snmpV3Levels[3])
+	u.setEngine([]byte("engine"), 1, 1)
+	msg, err := u.encodeMessage(1, answerFromMIB(snmpGetRequest, 1, []string{oidSysName}, testMIB), false)
+	if err != nil {
+		t.Fatal(err)
+	}
+	// Every truncation of a valid message fails cleanly
+	for n := 0; n < len(msg); n++ {
+		if _, _, err := u.decodeMessage(msg[:n]); err == nil {
+			t.Errorf("message truncated to %d bytes accepted", n)
+		}
+	}
+	// A v2c message is not a v3 one
+	v2c := mustHex(t, "301802010104067075626c6963a20b0201070201050201013000")
+	if _, _, err := u.decodeMessage(v2c); err == nil {
+		t.Error("v2c message accepted")
+	}
+}
+
+func TestSNMPClientV3Discovery(t *testing.T) {
+	engineID := mustHex(t, "80001f8880e9630000d61ff449")
+	for _, settings := range snmpV3Levels {
+		device := newSNMPUSM(&settings)
+		device.setEngine(engineID, 7, 1000)
+		// Reports to discovery requests are not authenticated
+		anonymous := &snmpUSM{}
+		anonymous.setEngine(engineID, 7, 1000)
+
+		host, port := serveSNMP(t, func(req []byte) []byte {
+			msgID, pdu, err := device.decodeMessage(req)
+			if err != nil {
+				return nil
+			}
+			tag, requestID, oids, err := parseTestRequest(pdu)
+			if err != nil {
+				return nil
+			}
+			if len(oids) == 0 {
+				encoded, _ := berEncodeOID("1.3.6.1.6.3.15.1.1.4.0")
+				report := berTLV(snmpReport, concatBytes(
+					berTLV(berInteger, berEncodeInt(int64(requestID))),
+					berTLV(berInteger, berEncodeInt(0)),
+					berTLV(berInteger, berEncodeInt(0)),
+					berTLV(berSequence, berTLV(berSequence, append(berTLV(berOID, encoded), berTLV(snmpCounter32, []byte{1})...))),
+				))
+				resp, _ := anonymous.encodeMessage(msgID, report, false)
+				return resp
+			}
+			resp, _ := device.encodeMessage(msgID, answerFromMIB(tag, requestID, oids, testMIB), false)
+			return resp
+		})
+
+		client, err := dialSNMP(host, port, "", "3", time.Second)
+		if err != nil {
+			t.Fatal(err)
+		}
+		client.usm = newSNMPUSM(&settings)
+
+		vars, err := client.Get(oidSysName)
+		if err != nil {
+			t.Errorf("%s: %v", settings.Username, err)
+		} else if len(vars) != 1 || vars[0].Value != "switch-1" {
+			t.Errorf("%s: Get = %+v", settings.Username, vars)
+		}
+		if !bytes.Equal(client.usm.engineID, engineID) || client.usm.boots != 7 {
+			t.Errorf("%s: engine %x boots %d", settings.Username, client.usm.engineID, client.usm.boots)
+		}
+		client.Close()
+	}
+}
+
+func TestPublicServerHidesSNMPCredentials(t *testing.T) {
+	server := RemoteServer{ID: "s1", SNMP: &SNMPSettings{
+		Host: "10.0.0.1", Community: "private", Version: "3",
+		Username: "monitor", AuthPassword: "authpass1", PrivPassword: "privpass1",
+	}}
+	public := publicServer(server)
+	if public.SNMP.Community != "" || public.SNMP.AuthPassword != "" || public.SNMP.PrivPassword != "" {
+		t.Errorf("credentials exposed: %+v", public.SNMP)
+	}
+	if public.SNMP.Host != "10.0.0.1" || public.SNMP.Username != "monitor" {
+		t.Errorf("non-secret settings dropped: %+v", public.SNMP)
+	}
+	if server.SNMP.Community != "private" {
+		t.Error("configured server modified")
+	}
+}
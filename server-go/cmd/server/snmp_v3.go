@@ -0,0 +1,452 @@
+package main
+
+import (
+	"bytes"
+	"crypto/aes"
+	"crypto/cipher"
+	"crypto/des"
+	"crypto/hmac"
+	"crypto/md5"
+	"crypto/sha1"
+	"crypto/sha256"
+	"encoding/binary"
+	"errors"
+	"fmt"
+	"hash"
+	"math/rand"
+	"strings"
+	"time"
+)
+
+// ============================================================================
+// SNMPv3 User-based Security Model
+// ============================================================================
+//
+// RFC 3414 USM for the client in snmp.go. The first request discovers the
+// device's engine ID, boots and time with an empty unauthenticated request;
+// the passwords are then localized to that engine. Messages are
+// authenticated with HMAC-MD5-96, HMAC-SHA-96 or HMAC-SHA-256-192 (RFC 7860)
+// and optionally encrypted with DES-CBC or AES-128-CFB (RFC 3826). Contexts
+// other than the default one are not supported.
+
+const (
+	snmpVersion3         = 3
+	usmSecurityModel     = 3
+	snmpV3MaxMessageSize = 65507
+	// RFC 3414 A.2: passwords are stretched to 1 MB before hashing
+	usmKeyStretchBytes = 1048576
+	usmMinPassword     = 8
+
+	snmpFlagAuth       = 0x01
+	snmpFlagPriv       = 0x02
+	snmpFlagReportable = 0x04
+)
+
+var (
+	errSNMPUnknownEngineID = errors.New("unknown engine ID")
+	errSNMPNotInTimeWindow = errors.New("not in time window")
+)
+
+// usmReportErrors maps the usmStats counters a device reports when it
+// refuses a request to what went wrong
+var usmReportErrors = map[string]error{
+	"1.3.6.1.6.3.15.1.1.1.0": errors.New("unsupported security level"),
+	"1.3.6.1.6.3.15.1.1.2.0": errSNMPNotInTimeWindow,
+	"1.3.6.1.6.3.15.1.1.3.0": errors.New("unknown user name"),
+	"1.3.6.1.6.3.15.1.1.4.0": errSNMPUnknownEngineID,
+	"1.3.6.1.6.3.15.1.1.5.0": errors.New("wrong digest, check auth_protocol and auth_password"),
+	"1.3.6.1.6.3.15.1.1.6.0": errors.New("decryption error, check priv_protocol and priv_password"),
+}
+
+// usmReportError returns the error a Report PDU stands for
+func usmReportError(vars []snmpVar) error {
+	if len(vars) > 0 {
+		if err, ok := usmReportErrors[vars[0].OID]; ok {
+			return err
+		}
+		return fmt.Errorf("device reported %s", vars[0].OID)
+	}
+	return fmt.Errorf("device sent an empty report")
+}
+
+// snmpAuthProtocol is an HMAC authentication protocol and the length its
+// MAC is truncated to
+type snmpAuthProtocol struct {
+	hash   func() hash.Hash
+	macLen int
+}
+
+var snmpAuthProtocols = map[string]snmpAuthProtocol{
+	"MD5":    {md5.New, 12},
+	"SHA":    {sha1.New, 12},
+	"SHA256": {sha256.New, 24},
+}
+
+// snmpAuthProtocolName and snmpPrivProtocolName normalize the settings,
+// applying the defaults
+func snmpAuthProtocolName(name string) string {
+	if name == "" {
+		return "SHA"
+	}
+	return strings.ToUpper(name)
+}
+
+func snmpPrivProtocolName(name string) string {
+	if name == "" {
+		return "AES"
+	}
+	return strings.ToUpper(name)
+}
+
+// validateV3 checks the SNMPv3 credentials. Without an auth_password the
+// user is noAuthNoPriv; a priv_password adds encryption.
+func (s *SNMPSettings) validateV3() error {
+	if s.Username == "" {
+		return fmt.Errorf("snmp.username is required for SNMPv3")
+	}
+	if s.AuthPassword == "" {
+		if s.PrivPassword != "" {
+			return fmt.Errorf("snmp.priv_password requires an auth_password")
+		}
+		return nil
+	}
+	if _, ok := snmpAuthProtocols[snmpAuthProtocolName(s.AuthProtocol)]; !ok {
+		return fmt.Errorf("snmp.auth_protocol must be MD5, SHA or SHA256")
+	}
+	if len(s.AuthPassword) < usmMinPassword {
+		return fmt.Errorf("snmp.auth_password must be at least %d characters", usmMinPassword)
+	}
+	if s.PrivPassword == "" {
+		return nil
+	}
+	if p := snmpPrivProtocolName(s.PrivProtocol); p != "DES" && p != "AES" {
+		return fmt.Errorf("snmp.priv_protocol must be DES or AES")
+	}
+	if len(s.PrivPassword) < usmMinPassword {
+		return fmt.Errorf("snmp.priv_password must be at least %d characters", usmMinPassword)
+	}
+	return nil
+}
+
+// snmpUSM is the security state of a v3 client
+type snmpUSM struct {
+	user         string
+	auth         *snmpAuthProtocol // nil for noAuthNoPriv
+	priv         string            // "DES" or "AES", empty without privacy
+	authPassword string
+	privPassword string
+
+	// Learned from the device
+	engineID   []byte
+	boots      int64
+	engineTime int64
+	synced     time.Time // When engineTime was learned
+
+	authKey, privKey []byte
+	salt             uint64
+}
+
+func newSNMPUSM(s *SNMPSettings) *snmpUSM {
+	u := &snmpUSM{user: s.Username, salt: rand.Uint64()}
+	if s.AuthPassword != "" {
+		auth := snmpAuthProtocols[snmpAuthProtocolName(s.AuthProtocol)]
+		u.auth, u.authPassword = &auth, s.AuthPassword
+		if s.PrivPassword != "" {
+			u.priv, u.privPassword = snmpPrivProtocolName(s.PrivProtocol), s.PrivPassword
+		}
+	}
+	return u
+}
+
+// localizeSNMPKey derives the key of a password for one engine (RFC 3414 A.2)
+func localizeSNMPKey(newHash func() hash.Hash, password string, engineID []byte) []byte {
+	h := newHash()
+	buf := make([]byte, 64)
+	for i := 0; i < usmKeyStretchBytes; i += len(buf) {
+		for j := range buf {
+			buf[j] = password[(i+j)%len(password)]
+		}
+		h.Write(buf)
+	}
+	ku := h.Sum(nil)
+
+	h.Reset()
+	h.Write(ku)
+	h.Write(engineID)
+	h.Write(ku)
+	return h.Sum(nil)
+}
+
+// setEngine records the device's engine, localizing the keys if it changed
+func (u *snmpUSM) setEngine(engineID []byte, boots, engineTime int64) {
+	if !bytes.Equal(engineID, u.engineID) {
+		u.engineID = append([]byte(nil), engineID...)
+		if u.auth != nil {
+			u.authKey = localizeSNMPKey(u.auth.hash, u.authPassword, engineID)
+		}
+		if u.priv != "" {
+			u.privKey = localizeSNMPKey(u.auth.hash, u.privPassword, engineID)
+		}
+	}
+	u.boots, u.engineTime, u.synced = boots, engineTime, time.Now()
+}
+
+func (u *snmpUSM) mac(msg []byte) []byte {
+	m := hmac.New(u.auth.hash, u.authKey)
+	m.Write(msg)
+	return m.Sum(nil)[:u.auth.macLen]
+}
+
+// discoverEngine learns the device's engine ID, boots and time from the
+// report it sends back for an empty unauthenticated request
+func (c *snmpClient) discoverEngine() error {
+	requestID := rand.Int31()
+	pdu, err := encodeSNMPPDU(snmpGetRequest, requestID, nil)
+	if err != nil {
+		return err
+	}
+	msg, err := c.usm.encodeMessage(requestID, pdu, true)
+	if err != nil {
+		return err
+	}
+	_, err = c.roundTrip(msg, requestID)
+	if len(c.usm.engineID) == 0 {
+		if err == nil || errors.Is(err, errSNMPUnknownEngineID) {
+			err = fmt.Errorf("device did not report its engine ID")
+		}
+		return err
+	}
+	return nil
+}
+
+// encodeMessage builds a v3 message around a PDU. Discovery messages carry
+// no engine, user or credentials.
+func (u *snmpUSM) encodeMessage(msgID int32, pdu []byte, discovery bool) ([]byte, error) {
+	flags := byte(snmpFlagReportable)
+	engineID, user := u.engineID, u.user
+	var boots, engineTime int64
+	if discovery {
+		engineID, user = nil, ""
+	} else {
+		boots = u.boots
+		engineTime = u.engineTime + int64(time.Since(u.synced)/time.Second)
+		if u.auth != nil {
+			flags |= snmpFlagAuth
+		}
+		if u.priv != "" {
+			flags |= snmpFlagPriv
+		}
+	}
+
+	msgData := berTLV(berSequence, concatBytes(
+		berTLV(berOctetString, engineID), // contextEngineID
+		berTLV(berOctetString, nil),      // contextName
+		pdu,
+	))
+	var privParams, authParams []byte
+	if flags&snmpFlagPriv != 0 {
+		encrypted, salt, err := u.encrypt(msgData, boots, engineTime)
+		if err != nil {
+			return nil, err
+		}
+		msgData, privParams = berTLV(berOctetString, encrypted), salt
+	}
+	if flags&snmpFlagAuth != 0 {
+		authParams = make([]byte, u.auth.macLen)
+	}
+
+	usmPrefix := concatBytes(
+		berTLV(berOctetString, engineID),
+		berTLV(berInteger, berEncodeInt(boots)),
+		berTLV(berInteger, berEncodeInt(engineTime)),
+		berTLV(berOctetString, []byte(user)),
+	)
+	usmRest := concatBytes(berTLV(berOctetString, authParams), berTLV(berOctetString, privParams))
+	usmParams := berTLV(berSequence, concatBytes(usmPrefix, usmRest))
+	secParams := berTLV(berOctetString, usmParams)
+	header := concatBytes(
+		berTLV(berInteger, berEncodeInt(snmpVersion3)),
+		berTLV(berSequence, concatBytes(
+			berTLV(berInteger, berEncodeInt(int64(msgID))),
+			berTLV(berInteger, berEncodeInt(snmpV3MaxMessageSize)),
+			berTLV(berOctetString, []byte{flags}),
+			berTLV(berInteger, berEncodeInt(usmSecurityModel)),
+		)),
+	)
+	msg := berTLV(berSequence, concatBytes(header, secParams, msgData))
+
+	if flags&snmpFlagAuth != 0 {
+		// The MAC covers the whole message with its own field zeroed. Only
+		// the privacy parameters and the data follow the field, whose
+		// header is two bytes since MACs are short.
+		offset := len(msg) - len(msgData) - len(usmRest) + 2
+		copy(msg[offset:], u.mac(msg))
+	}
+	return msg, nil
+}
+
+// decodeMessage verifies and decrypts a v3 message and returns its msgID and
+// PDU. Engine boots and time are taken from authenticated messages, and from
+// unauthenticated ones only while the engine is still being discovered.
+func (u *snmpUSM) decodeMessage(b []byte) (int32, []byte, error) {
+	tag, msg, _, err := berRead(b)
+	if err != nil || tag != berSequence {
+		return 0, nil, errSNMPMalformed
+	}
+	var version, header, secParams []byte
+	for _, field := range []*[]byte{&version, &header, &secParams} {
+		if _, *field, msg, err = berRead(msg); err != nil {
+			return 0, nil, err
+		}
+	}
+	dataTag, msgData, _, err := berRead(msg)
+	if err != nil {
+		return 0, nil, err
+	}
+	if berDecodeInt(version) != snmpVersion3 {
+		return 0, nil, fmt.Errorf("unexpected SNMP version %d", berDecodeInt(version))
+	}
+
+	var msgID, maxSize, flags, model []byte
+	for _, field := range []*[]byte{&msgID, &maxSize, &flags, &model} {
+		if _, *field, header, err = berRead(header); err != nil {
+			return 0, nil, err
+		}
+	}
+	if len(flags) != 1 || berDecodeInt(model) != usmSecurityModel {
+		return 0, nil, errSNMPMalformed
+	}
+
+	tag, usm, _, err := berRead(secParams)
+	if err != nil || tag != berSequence {
+		return 0, nil, errSNMPMalformed
+	}
+	var engineID, boots, engineTime, user, authParams, privParams []byte
+	for _, field := range []*[]byte{&engineID, &boots, &engineTime, &user, &authParams, &privParams} {
+		if _, *field, usm, err = berRead(usm); err != nil {
+			return 0, nil, err
+		}
+	}
+
+	authenticated := flags[0]&snmpFlagAuth != 0
+	if authenticated {
+		if u.auth == nil || len(u.authKey) == 0 || !bytes.Equal(engineID, u.engineID) {
+			return 0, nil, fmt.Errorf("authenticated message from an unknown engine")
+		}
+		if len(authParams) != u.auth.macLen {
+			return 0, nil, errSNMPMalformed
+		}
+		// authParams is a subslice of b, so the difference of their
+		// capacities is its offset
+		offset := cap(b) - cap(authParams)
+		zeroed := append([]byte(nil), b...)
+		clear(zeroed[offset : offset+len(authParams)])
+		if !hmac.Equal(u.mac(zeroed), authParams) {
+			return 0, nil, fmt.Errorf("response failed authentication")
+		}
+	}
+	if authenticated || len(u.engineID) == 0 {
+		u.setEngine(engineID, berDecodeInt(boots), berDecodeInt(engineTime))
+	}
+
+	var scoped []byte
+	switch {
+	case flags[0]&snmpFlagPriv != 0:
+		if !authenticated || u.priv == "" || dataTag != berOctetString {
+			return 0, nil, errSNMPMalformed
+		}
+		plain, err := u.decrypt(msgData, privParams, berDecodeInt(boots), berDecodeInt(engineTime))
+		if err != nil {
+			return 0, nil, err
+		}
+		// DES pads the plaintext, so anything after the scoped PDU is ignored
+		if tag, scoped, _, err = berRead(plain); err != nil || tag != berSequence {
+			return 0, nil, fmt.Errorf("response failed decryption")
+		}
+	case dataTag == berSequence:
+		scoped = msgData
+	default:
+		return 0, nil, errSNMPMalformed
+	}
+
+	// contextEngineID, contextName
+	for i := 0; i < 2; i++ {
+		if _, _, scoped, err = berRead(scoped); err != nil {
+			return 0, nil, err
+		}
+	}
+	return int32(berDecodeInt(msgID)), scoped, nil
+}
+
+// encrypt encrypts a scoped PDU and returns it with the salt that goes into
+// msgPrivacyParameters
+func (u *snmpUSM) encrypt(plain []byte, boots, engineTime int64) ([]byte, []byte, error) {
+	u.salt++
+	salt := make([]byte, 8)
+	if u.priv == "DES" {
+		// RFC 3414 8.1.1.1: the salt is engine boots and a local counter, and
+		// the IV is the pre-IV half of the key XORed with it
+		binary.BigEndian.PutUint32(salt, uint32(boots))
+		binary.BigEndian.PutUint32(salt[4:], uint32(u.salt))
+		block, err := des.NewCipher(u.privKey[:8])
+		if err != nil {
+			return nil, nil, err
+		}
+		iv := make([]byte, des.BlockSize)
+		for i := range iv {
+			iv[i] = u.privKey[8+i] ^ salt[i]
+		}
+		padded := append(append([]byte(nil), plain...), make([]byte, (des.BlockSize-len(plain)%des.BlockSize)%des.BlockSize)...)
+		out := make([]byte, len(padded))
+		cipher.NewCBCEncrypter(block, iv).CryptBlocks(out, padded)
+		return out, salt, nil
+	}
+
+	binary.BigEndian.PutUint64(salt, u.salt)
+	block, err := aes.NewCipher(u.privKey[:16])
+	if err != nil {
+		return nil, nil, err
+	}
+	out := make([]byte, len(plain))
+	cipher.NewCFBEncrypter(block, snmpAESIV(boots, engineTime, salt)).XORKeyStream(out, plain)
+	return out, salt, nil
+}
+
+func (u *snmpUSM) decrypt(data, salt []byte, boots, engineTime int64) ([]byte, error) {
+	if len(salt) != 8 {
+		return nil, fmt.Errorf("invalid privacy parameters")
+	}
+	if u.priv == "DES" {
+		if len(data)%des.BlockSize != 0 {
+			return nil, fmt.Errorf("invalid DES ciphertext length %d", len(data))
+		}
+		block, err := des.NewCipher(u.privKey[:8])
+		if err != nil {
+			return nil, err
+		}
+		iv := make([]byte, des.BlockSize)
+		for i := range iv {
+			iv[i] = u.privKey[8+i] ^ salt[i]
+		}
+		out := make([]byte, len(data))
+		cipher.NewCBCDecrypter(block, iv).CryptBlocks(out, data)
+		return out, nil
+	}
+
+	block, err := aes.NewCipher(u.privKey[:16])
+	if err != nil {
+		return nil, err
+	}
+	out := make([]byte, len(data))
+	cipher.NewCFBDecrypter(block, snmpAESIV(boots, engineTime, salt)).XORKeyStream(out, data)
+	return out, nil
+}
+
+// snmpAESIV is the RFC 3826 IV: engine boots, engine time and the salt
+func snmpAESIV(boots, engineTime int64, salt []byte) []byte {
+	iv := make([]byte, aes.BlockSize)
+	binary.BigEndian.PutUint32(iv, uint32(boots))
+	binary.BigEndian.PutUint32(iv[4:], uint32(engineTime))
+	copy(iv[8:], salt)
+	return iv
+}
@@ -0,0 +1,113 @@
+package main
+
+import (
+	"bytes"
+	"encoding/json"
+	"fmt"
+	"io"
+	"log"
+	"net/http"
+	"net/url"
+	"time"
+)
+
+// ============================================================================
+// Status Webhook
+// ============================================================================
+//
+// status_webhook is POSTed a small JSON event when a server goes down or
+// comes back, separate from metric alerts. Events follow the offline alert:
+// a state must hold for the availability debounce (on top of the online
+// timeout) before it counts, so agent restarts and brief blips send nothing,
+// and the first state seen after startup is not a transition.
+
+const (
+	StatusWebhookEventUp   = "up"
+	StatusWebhookEventDown = "down"
+
+	statusWebhookTimeout  = 10 * time.Second
+	statusWebhookAttempts = 3
+)
+
+// StatusWebhookSettings configure the online/offline callback
+type StatusWebhookSettings struct {
+	URL string `json:"url"`
+	// Extra request headers, e.g. {"Authorization": "Bearer ..."}
+	Headers map[string]string `json:"headers,omitempty"`
+}
+
+// Validate checks the settings; an empty URL disables the webhook
+func (s *StatusWebhookSettings) Validate() error {
+	if s == nil || s.URL == "" {
+		return nil
+	}
+	u, err := url.Parse(s.URL)
+	if err != nil || (u.Scheme != "http" && u.Scheme != "https") || u.Host == "" {
+		return fmt.Errorf("status_webhook.url must be an http(s) URL")
+	}
+	return nil
+}
+
+// StatusWebhookEvent is the body POSTed to the status webhook
+type StatusWebhookEvent struct {
+	ServerID  string `json:"server_id"`
+	Name      string `json:"name"`
+	Event     string `json:"event"`     // "up" or "down"
+	Timestamp string `json:"timestamp"` // When the new state began
+}
+
+// sendStatusWebhook posts a transition to the configured webhook, retrying
+// failed attempts with a growing delay
+func sendStatusWebhook(settings *StatusWebhookSettings, serverID, name, state string, at time.Time) {
+	if settings == nil || settings.URL == "" || settings.Validate() != nil {
+		return
+	}
+	event := StatusWebhookEvent{
+		ServerID:  serverID,
+		Name:      name,
+		Event:     StatusWebhookEventDown,
+		Timestamp: at.UTC().Format(time.RFC3339),
+	}
+	if state == AvailabilityOnline {
+		event.Event = StatusWebhookEventUp
+	}
+	body, err := json.Marshal(event)
+	if err != nil {
+		return
+	}
+
+	client := &http.Client{Timeout: statusWebhookTimeout}
+	for attempt := 1; ; attempt++ {
+		err = postStatusWebhook(client, settings, body)
+		if err == nil {
+			return
+		}
+		if attempt == statusWebhookAttempts {
+			log.Printf("Status webhook for %s (%s) failed: %v", serverID, event.Event, err)
+			return
+		}
+		time.Sleep(time.Duration(attempt) * 5 * time.Second)
+	}
+}
+
+func postStatusWebhook(client *http.Client, settings *StatusWebhookSettings, body []byte) error {
+	req, err := http.NewRequest("POST", settings.URL, bytes.NewReader(body))
+	if err != nil {
+		return err
+	}
+	req.Header.Set("Content-Type", "application/json")
+	req.Header.Set("User-Agent", "vstats-server/"+ServerVersion)
+	for k, v := range settings.Headers {
+		req.Header.Set(k, v)
+	}
+	resp, err := client.Do(req)
+	if err != nil {
+		return err
+	}
+	defer resp.Body.Close()
+	io.Copy(io.Discard, resp.Body)
+	if resp.StatusCode/100 != 2 {
+		return fmt.Errorf("%s", resp.Status)
+	}
+	return nil
+}
@@ -0,0 +1,59 @@
+package main
+
+import (
+	"fmt"
+	"sync/atomic"
+	"time"
+	_ "time/tzdata" // Timezones work without a system zoneinfo, e.g. in scratch containers
+)
+
+// ============================================================================
+// Aggregation Timezone
+// ============================================================================
+//
+// The day boundaries the server computes itself (bandwidth days and billing
+// cycles, per-disk daily history) follow the timezone setting, UTC by
+// default. Days already stored keep the boundaries they were written with,
+// so changing the timezone only affects days aggregated afterwards. The
+// hourly and daily metric buckets come from agents and stay UTC.
+
+var aggregationLocation atomic.Pointer[time.Location]
+
+// SetAggregationTimezone sets the timezone of server-side day boundaries from
+// an IANA name; "" means UTC
+func SetAggregationTimezone(name string) error {
+	if name == "" {
+		aggregationLocation.Store(time.UTC)
+		return nil
+	}
+	loc, err := time.LoadLocation(name)
+	if err != nil {
+		return fmt.Errorf("unknown timezone %q: %w", name, err)
+	}
+	aggregationLocation.Store(loc)
+	return nil
+}
+
+// aggregationTZ returns the timezone of server-side day boundaries
+func aggregationTZ() *time.Location {
+	if loc := aggregationLocation.Load(); loc != nil {
+		return loc
+	}
+	return time.UTC
+}
+
+// aggregationDate returns the day of t in the aggregation timezone
+func aggregationDate(t time.Time) string {
+	return t.In(aggregationTZ()).Format("2006-01-02")
+}
+
+// aggregationOffsetModifier returns a SQLite date modifier that shifts UTC
+// timestamps to the aggregation timezone's wall clock at t, or back if
+// inverse is set
+func aggregationOffsetModifier(t time.Time, inverse bool) string {
+	_, offset := t.In(aggregationTZ()).Zone()
+	if inverse {
+		offset = -offset
+	}
+	return fmt.Sprintf("%+d seconds", offset)
+}
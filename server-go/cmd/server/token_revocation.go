@@ -0,0 +1,195 @@
+package main
+
+import (
+	"crypto/sha256"
+	"database/sql"
+	"encoding/hex"
+	"net/http"
+	"strings"
+	"sync"
+	"sync/atomic"
+	"time"
+
+	"github.com/gin-gonic/gin"
+	"github.com/golang-jwt/jwt/v5"
+	"github.com/google/uuid"
+)
+
+// ============================================================================
+// Token Revocation
+// ============================================================================
+//
+// Dashboard JWTs carry a jti and the config's token_generation. Logging out
+// records the jti in revoked_tokens until the token would have expired
+// anyway; the table is loaded into memory at startup so checking a token
+// needs no query. Bumping token_generation (POST /api/auth/revoke-all)
+// invalidates every token issued before, without listing them. Tokens from
+// before jti existed are revoked by a hash of the token itself.
+
+// adminTokenLifetime is how long dashboard JWTs are valid
+const adminTokenLifetime = 7 * 24 * time.Hour
+
+var tokenGeneration atomic.Int64
+
+// SetTokenGeneration records the config's token_generation
+func SetTokenGeneration(generation int) {
+	tokenGeneration.Store(int64(generation))
+}
+
+// revokedTokens maps revoked token IDs to when the token expires
+var revokedTokens = struct {
+	sync.RWMutex
+	ids map[string]time.Time
+}{ids: make(map[string]time.Time)}
+
+// generateJWTToken issues a dashboard JWT for sub
+func generateJWTToken(sub, provider string) (string, time.Time, error) {
+	now := time.Now()
+	expiresAt := now.Add(adminTokenLifetime)
+	token := jwt.NewWithClaims(jwt.SigningMethodHS256, jwt.MapClaims{
+		"sub":      sub,
+		"provider": provider,
+		"jti":      uuid.New().String(),
+		"iat":      now.Unix(),
+		"exp":      expiresAt.Unix(),
+		"gen":      tokenGeneration.Load(),
+	})
+
+	tokenString, err := token.SignedString([]byte(GetJWTSecret()))
+	if err != nil {
+		return "", time.Time{}, err
+	}
+
+	return tokenString, expiresAt, nil
+}
+
+// parseAuthToken returns the claims of a valid, unexpired admin JWT
+func parseAuthToken(tokenString string) (jwt.MapClaims, bool) {
+	claims := jwt.MapClaims{}
+	token, err := jwt.ParseWithClaims(tokenString, claims, func(token *jwt.Token) (interface{}, error) {
+		return []byte(GetJWTSecret()), nil
+	})
+	return claims, err == nil && token.Valid
+}
+
+// tokenID returns the ID a token is revoked by: its jti, or for older tokens
+// without one, a hash of the token
+func tokenID(tokenString string, claims jwt.MapClaims) string {
+	if jti, ok := claims["jti"].(string); ok && jti != "" {
+		return jti
+	}
+	sum := sha256.Sum256([]byte(tokenString))
+	return "sha256:" + hex.EncodeToString(sum[:])
+}
+
+// tokenRevoked reports whether a valid token was logged out or issued before
+// the current token generation
+func tokenRevoked(tokenString string, claims jwt.MapClaims) bool {
+	gen, _ := claims["gen"].(float64)
+	if int64(gen) != tokenGeneration.Load() {
+		return true
+	}
+	revokedTokens.RLock()
+	_, revoked := revokedTokens.ids[tokenID(tokenString, claims)]
+	revokedTokens.RUnlock()
+	return revoked
+}
+
+func createRevokedTokensTable(db *sql.DB) {
+	db.Exec(`
+		-- Logged out dashboard tokens, kept until they expire
+		CREATE TABLE IF NOT EXISTS revoked_tokens (
+			id TEXT PRIMARY KEY,
+			expires_at TEXT NOT NULL
+		);
+	`)
+}
+
+// loadRevokedTokens reads the unexpired revoked tokens into memory
+func loadRevokedTokens(db *sql.DB) {
+	rows, err := db.Query("SELECT id, expires_at FROM revoked_tokens WHERE expires_at >= ?",
+		time.Now().UTC().Format(time.RFC3339))
+	if err != nil {
+		return
+	}
+	defer rows.Close()
+
+	revokedTokens.Lock()
+	defer revokedTokens.Unlock()
+	for rows.Next() {
+		var id, expiresAt string
+		if rows.Scan(&id, &expiresAt) != nil {
+			continue
+		}
+		if t, err := time.Parse(time.RFC3339, expiresAt); err == nil {
+			revokedTokens.ids[id] = t
+		}
+	}
+}
+
+// revokeToken revokes a token until it expires
+func revokeToken(db *sql.DB, id string, expiresAt time.Time) error {
+	revokedTokens.Lock()
+	revokedTokens.ids[id] = expiresAt
+	revokedTokens.Unlock()
+
+	run := func(db *sql.DB) error {
+		_, err := db.Exec("INSERT OR REPLACE INTO revoked_tokens (id, expires_at) VALUES (?, ?)",
+			id, expiresAt.UTC().Format(time.RFC3339))
+		return err
+	}
+	if dbWriter != nil {
+		return dbWriter.WriteSync(run)
+	}
+	return run(db)
+}
+
+// cleanupRevokedTokens forgets revoked tokens that have expired
+func cleanupRevokedTokens(db *sql.DB) {
+	now := time.Now().UTC()
+	db.Exec("DELETE FROM revoked_tokens WHERE expires_at < ?", now.Format(time.RFC3339))
+
+	revokedTokens.Lock()
+	for id, expiresAt := range revokedTokens.ids {
+		if expiresAt.Before(now) {
+			delete(revokedTokens.ids, id)
+		}
+	}
+	revokedTokens.Unlock()
+}
+
+// ============================================================================
+// Logout Handlers
+// ============================================================================
+
+// Logout revokes the token the request was made with
+func (s *AppState) Logout(c *gin.Context) {
+	tokenString := strings.TrimPrefix(c.GetHeader("Authorization"), "Bearer ")
+	claims, ok := parseAuthToken(tokenString)
+	if !ok {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Only dashboard tokens can be logged out"})
+		return
+	}
+
+	expiresAt := time.Now().Add(adminTokenLifetime)
+	if exp, err := claims.GetExpirationTime(); err == nil && exp != nil {
+		expiresAt = exp.Time
+	}
+	if err := revokeToken(s.DB, tokenID(tokenString, claims), expiresAt); err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.Status(http.StatusOK)
+}
+
+// RevokeAllTokens invalidates every dashboard token issued so far, the
+// caller's included
+func (s *AppState) RevokeAllTokens(c *gin.Context) {
+	s.ConfigMu.Lock()
+	s.Config.TokenGeneration++
+	generation := s.Config.TokenGeneration
+	SaveConfig(s.Config)
+	s.ConfigMu.Unlock()
+
+	c.JSON(http.StatusOK, gin.H{"token_generation": generation})
+}
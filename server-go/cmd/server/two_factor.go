@@ -0,0 +1,355 @@
+package main
+
+import (
+	"crypto/hmac"
+	"crypto/rand"
+	"crypto/sha1"
+	"crypto/sha256"
+	"crypto/subtle"
+	"encoding/base32"
+	"encoding/binary"
+	"encoding/hex"
+	"fmt"
+	"math"
+	"net/http"
+	"net/url"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+
+	"github.com/gin-gonic/gin"
+	"github.com/golang-jwt/jwt/v5"
+)
+
+// ============================================================================
+// Two-Factor Authentication (TOTP)
+// ============================================================================
+//
+// With two_factor set, a correct password no longer yields a session: login
+// returns a five-minute two_factor_token instead, which POST
+// /api/auth/2fa/verify exchanges for the real JWT together with a code from
+// an authenticator app (RFC 6238: SHA-1, 6 digits, 30 second steps, one step
+// of clock skew either way) or an unused recovery code. Each step is only
+// accepted once. Code attempts count against the login rate limit of the
+// client IP. OAuth logins are not affected.
+
+const (
+	totpDigits          = 6
+	totpPeriod          = 30 // Seconds
+	totpSkewSteps       = 1
+	totpSecretBytes     = 20
+	totpIssuer          = "vStats"
+	recoveryCodeCount   = 10
+	twoFactorTokenTTL   = 5 * time.Minute
+	twoFactorSetupTTL   = 10 * time.Minute
+	twoFactorTokenScope = "2fa"
+)
+
+var totpEncoding = base32.StdEncoding.WithPadding(base32.NoPadding)
+
+// TwoFactorConfig is the enabled TOTP second factor of the admin login
+type TwoFactorConfig struct {
+	Secret string `json:"secret"` // Base32, as shown to authenticator apps
+	// SHA-256 hashes of the unused recovery codes
+	RecoveryCodes []string `json:"recovery_codes"`
+	EnabledAt     string   `json:"enabled_at"`
+	// Last time step a code was accepted for, so codes cannot be replayed
+	LastStep int64 `json:"last_step,omitempty"`
+}
+
+type TwoFactorCodeRequest struct {
+	Code string `json:"code"`
+}
+
+type TwoFactorVerifyRequest struct {
+	Token string `json:"two_factor_token"`
+	Code  string `json:"code"`
+}
+
+// TwoFactorChallenge is returned by login when a code is still required
+type TwoFactorChallenge struct {
+	TwoFactorRequired bool      `json:"two_factor_required"`
+	Token             string    `json:"two_factor_token"`
+	ExpiresAt         time.Time `json:"expires_at"`
+}
+
+// pendingTwoFactor holds the secret of a setup awaiting its first code
+var pendingTwoFactor struct {
+	sync.Mutex
+	secret  string
+	expires time.Time
+}
+
+// totpCode returns the code of a secret for a time step
+func totpCode(secret []byte, step int64) string {
+	var msg [8]byte
+	binary.BigEndian.PutUint64(msg[:], uint64(step))
+	mac := hmac.New(sha1.New, secret)
+	mac.Write(msg[:])
+	sum := mac.Sum(nil)
+	offset := sum[len(sum)-1] & 0x0f
+	value := binary.BigEndian.Uint32(sum[offset:offset+4]) & 0x7fffffff
+	return fmt.Sprintf("%0*d", totpDigits, value%uint32(math.Pow10(totpDigits)))
+}
+
+// verifyTOTP returns the time step code is valid for, within the tolerated
+// skew and after lastStep, or 0
+func verifyTOTP(secret, code string, now time.Time, lastStep int64) int64 {
+	key, err := totpEncoding.DecodeString(strings.ToUpper(secret))
+	if err != nil || len(code) != totpDigits {
+		return 0
+	}
+	current := now.Unix() / totpPeriod
+	for step := current - totpSkewSteps; step <= current+totpSkewSteps; step++ {
+		if step > lastStep && subtle.ConstantTimeCompare([]byte(totpCode(key, step)), []byte(code)) == 1 {
+			return step
+		}
+	}
+	return 0
+}
+
+func hashRecoveryCode(code string) string {
+	code = strings.ToLower(strings.NewReplacer("-", "", " ", "").Replace(code))
+	sum := sha256.Sum256([]byte(code))
+	return hex.EncodeToString(sum[:])
+}
+
+// generateRecoveryCodes returns new recovery codes and their hashes
+func generateRecoveryCodes() (codes, hashes []string) {
+	for i := 0; i < recoveryCodeCount; i++ {
+		code := strings.ToLower(GenerateRandomString(10))
+		code = code[:5] + "-" + code[5:]
+		codes = append(codes, code)
+		hashes = append(hashes, hashRecoveryCode(code))
+	}
+	return codes, hashes
+}
+
+// useTwoFactorCode checks a TOTP or recovery code against the enabled second
+// factor, consuming it. Callers hold ConfigMu for writing.
+func (s *AppState) useTwoFactorCode(code string) bool {
+	tf := s.Config.TwoFactor
+	if tf == nil {
+		return false
+	}
+	code = strings.TrimSpace(code)
+	if step := verifyTOTP(tf.Secret, code, time.Now(), tf.LastStep); step != 0 {
+		tf.LastStep = step
+		SaveConfig(s.Config)
+		return true
+	}
+	hash := hashRecoveryCode(code)
+	for i, h := range tf.RecoveryCodes {
+		if subtle.ConstantTimeCompare([]byte(h), []byte(hash)) == 1 {
+			tf.RecoveryCodes = append(tf.RecoveryCodes[:i], tf.RecoveryCodes[i+1:]...)
+			SaveConfig(s.Config)
+			fmt.Printf("⚠️ Two-factor recovery code used, %d left\n", len(tf.RecoveryCodes))
+			return true
+		}
+	}
+	return false
+}
+
+func (s *AppState) twoFactorEnabled() bool {
+	s.ConfigMu.RLock()
+	defer s.ConfigMu.RUnlock()
+	return s.Config.TwoFactor != nil
+}
+
+// twoFactorKey signs two_factor_tokens. It differs from the session key, so
+// a two_factor_token is never accepted as a session.
+func twoFactorKey() []byte {
+	return []byte(GetJWTSecret() + ":" + twoFactorTokenScope)
+}
+
+func generateTwoFactorChallenge() (*TwoFactorChallenge, error) {
+	expiresAt := time.Now().Add(twoFactorTokenTTL)
+	token := jwt.NewWithClaims(jwt.SigningMethodHS256, jwt.MapClaims{
+		"sub":   "admin",
+		"scope": twoFactorTokenScope,
+		"exp":   expiresAt.Unix(),
+	})
+	tokenString, err := token.SignedString(twoFactorKey())
+	if err != nil {
+		return nil, err
+	}
+	return &TwoFactorChallenge{TwoFactorRequired: true, Token: tokenString, ExpiresAt: expiresAt}, nil
+}
+
+func validTwoFactorToken(tokenString string) bool {
+	claims := jwt.MapClaims{}
+	token, err := jwt.ParseWithClaims(tokenString, claims, func(token *jwt.Token) (interface{}, error) {
+		return twoFactorKey(), nil
+	})
+	return err == nil && token.Valid && claims["scope"] == twoFactorTokenScope
+}
+
+// ============================================================================
+// Two-Factor Handlers
+// ============================================================================
+
+// GetTwoFactorStatus reports whether two-factor auth is enabled
+func (s *AppState) GetTwoFactorStatus(c *gin.Context) {
+	s.ConfigMu.RLock()
+	defer s.ConfigMu.RUnlock()
+
+	response := gin.H{"enabled": s.Config.TwoFactor != nil}
+	if tf := s.Config.TwoFactor; tf != nil {
+		response["enabled_at"] = tf.EnabledAt
+		response["recovery_codes_left"] = len(tf.RecoveryCodes)
+	}
+	c.JSON(http.StatusOK, response)
+}
+
+// SetupTwoFactor generates a secret to add to an authenticator app. It takes
+// effect once confirmed with a code through EnableTwoFactor.
+func (s *AppState) SetupTwoFactor(c *gin.Context) {
+	if s.twoFactorEnabled() {
+		c.JSON(http.StatusConflict, gin.H{"error": "Two-factor authentication is already enabled"})
+		return
+	}
+
+	key := make([]byte, totpSecretBytes)
+	if _, err := rand.Read(key); err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to generate secret"})
+		return
+	}
+	secret := totpEncoding.EncodeToString(key)
+
+	pendingTwoFactor.Lock()
+	pendingTwoFactor.secret = secret
+	pendingTwoFactor.expires = time.Now().Add(twoFactorSetupTTL)
+	pendingTwoFactor.Unlock()
+
+	query := url.Values{}
+	query.Set("secret", secret)
+	query.Set("issuer", totpIssuer)
+	query.Set("algorithm", "SHA1")
+	query.Set("digits", strconv.Itoa(totpDigits))
+	query.Set("period", strconv.Itoa(totpPeriod))
+	uri := fmt.Sprintf("otpauth://totp/%s?%s", url.PathEscape(totpIssuer+":admin"), query.Encode())
+
+	c.JSON(http.StatusOK, gin.H{"secret": secret, "otpauth_uri": uri})
+}
+
+// EnableTwoFactor turns on two-factor auth with the pending secret, once a
+// code from it verifies, and returns the recovery codes, which are not shown
+// again
+func (s *AppState) EnableTwoFactor(c *gin.Context) {
+	var req TwoFactorCodeRequest
+	if err := c.ShouldBindJSON(&req); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request"})
+		return
+	}
+
+	pendingTwoFactor.Lock()
+	secret := pendingTwoFactor.secret
+	if time.Now().After(pendingTwoFactor.expires) {
+		secret = ""
+	}
+	pendingTwoFactor.Unlock()
+	if secret == "" {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "No two-factor setup in progress"})
+		return
+	}
+	step := verifyTOTP(secret, strings.TrimSpace(req.Code), time.Now(), 0)
+	if step == 0 {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "Invalid code"})
+		return
+	}
+
+	codes, hashes := generateRecoveryCodes()
+	s.ConfigMu.Lock()
+	if s.Config.TwoFactor != nil {
+		s.ConfigMu.Unlock()
+		c.JSON(http.StatusConflict, gin.H{"error": "Two-factor authentication is already enabled"})
+		return
+	}
+	s.Config.TwoFactor = &TwoFactorConfig{
+		Secret:        secret,
+		RecoveryCodes: hashes,
+		EnabledAt:     time.Now().UTC().Format(time.RFC3339),
+		LastStep:      step,
+	}
+	SaveConfig(s.Config)
+	s.ConfigMu.Unlock()
+
+	pendingTwoFactor.Lock()
+	pendingTwoFactor.secret = ""
+	pendingTwoFactor.Unlock()
+
+	fmt.Println("🔐 Two-factor authentication enabled")
+	c.JSON(http.StatusOK, gin.H{"recovery_codes": codes})
+}
+
+// DisableTwoFactor turns off two-factor auth; it takes a current code
+func (s *AppState) DisableTwoFactor(c *gin.Context) {
+	var req TwoFactorCodeRequest
+	if err := c.ShouldBindJSON(&req); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request"})
+		return
+	}
+
+	s.ConfigMu.Lock()
+	defer s.ConfigMu.Unlock()
+
+	if s.Config.TwoFactor == nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Two-factor authentication is not enabled"})
+		return
+	}
+	if !s.useTwoFactorCode(req.Code) {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "Invalid code"})
+		return
+	}
+	s.Config.TwoFactor = nil
+	SaveConfig(s.Config)
+
+	fmt.Println("🔓 Two-factor authentication disabled")
+	c.Status(http.StatusOK)
+}
+
+// VerifyTwoFactor completes a password login that returned a two-factor
+// challenge
+func (s *AppState) VerifyTwoFactor(c *gin.Context) {
+	var req TwoFactorVerifyRequest
+	if err := c.ShouldBindJSON(&req); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request"})
+		return
+	}
+
+	clientIP := c.ClientIP()
+	if ok, wait := s.LoginLimiter.Allow(clientIP, time.Now()); !ok {
+		retryAfter := int(math.Ceil(wait.Seconds()))
+		c.Header("Retry-After", strconv.Itoa(retryAfter))
+		c.JSON(http.StatusTooManyRequests, gin.H{"error": "Too many login attempts", "retry_after": retryAfter})
+		return
+	}
+	if !validTwoFactorToken(req.Token) {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "Invalid or expired two_factor_token"})
+		return
+	}
+
+	s.ConfigMu.Lock()
+	ok := s.useTwoFactorCode(req.Code)
+	s.ConfigMu.Unlock()
+	if !ok {
+		s.LoginLimiter.Failure(clientIP, time.Now())
+		RecordLogin("admin", "password", clientIP, false, "invalid two-factor code")
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "Invalid code"})
+		return
+	}
+
+	tokenString, expiresAt, err := generateJWTToken("admin", "password")
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to generate token"})
+		return
+	}
+
+	s.LoginLimiter.Success(clientIP)
+	RecordLogin("admin", "password", clientIP, true, "")
+	c.JSON(http.StatusOK, LoginResponse{
+		Token:     tokenString,
+		ExpiresAt: expiresAt,
+	})
+}
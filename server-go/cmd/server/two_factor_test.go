@@ -0,0 +1,121 @@
+package main
+
+import (
+	"path/filepath"
+	"strings"
+	"testing"
+	"time"
+)
+
+// RFC 6238 appendix B uses this ASCII secret for its SHA-1 vectors
+var rfc6238Secret = []byte("12345678901234567890")
+
+func TestTOTPCodeRFC6238(t *testing.T) {
+	// The RFC lists 8-digit codes; six digits are their last six
+	vectors := []struct {
+		unix int64
+		code string
+	}{
+		{59, "287082"},
+		{1111111109, "081804"},
+		{1111111111, "050471"},
+		{1234567890, "005924"},
+		{2000000000, "279037"},
+		{20000000000, "353130"},
+	}
+	for _, v := range vectors {
+		if got := totpCode(rfc6238Secret, v.unix/totpPeriod); got != v.code {
+			t.Errorf("totpCode at %d = %s, want %s", v.unix, got, v.code)
+		}
+	}
+}
+
+func TestVerifyTOTPSkew(t *testing.T) {
+	secret := totpEncoding.EncodeToString(rfc6238Secret)
+	now := time.Unix(1111111111, 0)
+	current := now.Unix() / totpPeriod
+
+	for _, offset := range []int64{-1, 0, 1} {
+		code := totpCode(rfc6238Secret, current+offset)
+		if got := verifyTOTP(secret, code, now, 0); got != current+offset {
+			t.Errorf("code of step %+d: got step %d, want %d", offset, got, current+offset)
+		}
+	}
+	for _, offset := range []int64{-2, 2} {
+		code := totpCode(rfc6238Secret, current+offset)
+		if got := verifyTOTP(secret, code, now, 0); got != 0 {
+			t.Errorf("code of step %+d accepted outside the skew window", offset)
+		}
+	}
+
+	// Authenticator apps show the secret in lowercase too
+	code := totpCode(rfc6238Secret, current)
+	if verifyTOTP(strings.ToLower(secret), code, now, 0) != current {
+		t.Error("lowercase secret rejected")
+	}
+}
+
+func TestVerifyTOTPReplay(t *testing.T) {
+	secret := totpEncoding.EncodeToString(rfc6238Secret)
+	now := time.Unix(1234567890, 0)
+	current := now.Unix() / totpPeriod
+
+	code := totpCode(rfc6238Secret, current)
+	step := verifyTOTP(secret, code, now, 0)
+	if step != current {
+		t.Fatalf("got step %d, want %d", step, current)
+	}
+	if verifyTOTP(secret, code, now, step) != 0 {
+		t.Error("code accepted twice for the same step")
+	}
+	if verifyTOTP(secret, totpCode(rfc6238Secret, current-1), now, step) != 0 {
+		t.Error("code of an earlier step accepted after a later one")
+	}
+	if verifyTOTP(secret, totpCode(rfc6238Secret, current+1), now, step) != current+1 {
+		t.Error("code of the next step rejected")
+	}
+}
+
+func TestUseTwoFactorCode(t *testing.T) {
+	t.Setenv("VSTATS_CONFIG_PATH", filepath.Join(t.TempDir(), ConfigFilename))
+
+	codes, hashes := generateRecoveryCodes()
+	state := &AppState{Config: &AppConfig{TwoFactor: &TwoFactorConfig{
+		Secret:        totpEncoding.EncodeToString(rfc6238Secret),
+		RecoveryCodes: hashes,
+	}}}
+	tf := state.Config.TwoFactor
+
+	// Recovery codes are accepted regardless of case, dashes and spacing
+	typed := " " + strings.ToUpper(strings.ReplaceAll(codes[0], "-", "")) + " "
+	if !state.useTwoFactorCode(typed) {
+		t.Fatal("recovery code rejected")
+	}
+	if len(tf.RecoveryCodes) != recoveryCodeCount-1 {
+		t.Errorf("%d recovery codes left, want %d", len(tf.RecoveryCodes), recoveryCodeCount-1)
+	}
+	if state.useTwoFactorCode(codes[0]) {
+		t.Error("recovery code accepted twice")
+	}
+	if !state.useTwoFactorCode(codes[1]) {
+		t.Error("second recovery code rejected")
+	}
+	if len(tf.RecoveryCodes) != recoveryCodeCount-2 {
+		t.Errorf("%d recovery codes left, want %d", len(tf.RecoveryCodes), recoveryCodeCount-2)
+	}
+
+	code := totpCode(rfc6238Secret, time.Now().Unix()/totpPeriod)
+	if !state.useTwoFactorCode(code) {
+		t.Fatal("current TOTP code rejected")
+	}
+	if tf.LastStep == 0 {
+		t.Error("accepted step not recorded")
+	}
+	if state.useTwoFactorCode(code) {
+		t.Error("TOTP code replayed")
+	}
+
+	if state.useTwoFactorCode("not-a-code") {
+		t.Error("garbage accepted")
+	}
+}
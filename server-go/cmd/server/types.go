@@ -109,6 +109,19 @@ type AddServerRequest struct {
 	PricePeriod  string            `json:"price_period,omitempty"`
 	PurchaseDate string            `json:"purchase_date,omitempty"`
 	TipBadge     string            `json:"tip_badge,omitempty"`
+	// Day of the month (1-28) the bandwidth billing cycle starts
+	BillingCycleDay int `json:"billing_cycle_day,omitempty"`
+	// Poll the server over SNMP instead of waiting for an agent
+	SNMP *SNMPSettings `json:"snmp,omitempty"`
+	// Mount reported as the server's disk usage (default: "/" or the largest)
+	PrimaryMount string `json:"primary_mount,omitempty"`
+	// Ping target shown as the headline latency (default: probe settings)
+	PrimaryPingTarget string `json:"primary_ping_target,omitempty"`
+	// Informational notes and key/value metadata
+	Notes    string            `json:"notes,omitempty"`
+	Metadata map[string]string `json:"metadata,omitempty"`
+	// Days of history to keep for this server (default: global retention)
+	RetentionDays int `json:"retention_days,omitempty"`
 }
 
 type UpdateServerRequest struct {
@@ -122,6 +135,20 @@ type UpdateServerRequest struct {
 	PricePeriod  *string            `json:"price_period,omitempty"`
 	PurchaseDate *string            `json:"purchase_date,omitempty"`
 	TipBadge     *string            `json:"tip_badge,omitempty"`
+	// Day of the month (1-28) the bandwidth billing cycle starts; 0 resets
+	// to calendar months
+	BillingCycleDay *int `json:"billing_cycle_day,omitempty"`
+	// SNMP polling settings; an empty host stops polling
+	SNMP *SNMPSettings `json:"snmp,omitempty"`
+	// Mount reported as the server's disk usage; empty restores the default
+	PrimaryMount *string `json:"primary_mount,omitempty"`
+	// Ping target shown as the headline latency; empty uses the probe settings
+	PrimaryPingTarget *string `json:"primary_ping_target,omitempty"`
+	// Informational notes and metadata; metadata replaces the stored map
+	Notes    *string            `json:"notes,omitempty"`
+	Metadata *map[string]string `json:"metadata,omitempty"`
+	// Days of history to keep for this server; 0 restores the global retention
+	RetentionDays *int `json:"retention_days,omitempty"`
 }
 
 // ============================================================================
@@ -178,18 +205,66 @@ type HistoryPoint struct {
 	CPU       float32  `json:"cpu"`
 	Memory    float32  `json:"memory"`
 	Disk      float32  `json:"disk"`
-	NetRx     int64    `json:"net_rx"`
+	NetRx     int64    `json:"net_rx"` // Counter since boot, see NetRxRate for throughput
 	NetTx     int64    `json:"net_tx"`
 	PingMs    *float64 `json:"ping_ms,omitempty"`
+	// Per-bucket peaks for 7d/30d/1y; null where the bucket has none
+	CPUMax    *float32 `json:"cpu_max"`
+	CPUP95    *float32 `json:"cpu_p95"`
+	MemoryMax *float32 `json:"memory_max"`
+	MemoryP95 *float32 `json:"memory_p95"`
+	PingMax   *float64 `json:"ping_max"`
+
+	// Averages; null for data stored before load and swap were recorded
+	Load *LoadAverage `json:"load"`
+	Swap *float32     `json:"swap"`
+
+	// Average throughput in bytes per second; null for data stored before
+	// rates were recorded
+	NetRxRate *float64 `json:"net_rx_rate"`
+	NetTxRate *float64 `json:"net_tx_rate"`
+}
+
+// HistorySample is a stored full snapshot of a sample, see metrics_blobs
+type HistorySample struct {
+	Timestamp string         `json:"timestamp"`
+	Metrics   *SystemMetrics `json:"metrics"`
 }
 
 type HistoryResponse struct {
 	ServerID    string              `json:"server_id"`
-	Range       string              `json:"range"`
+	Range       string              `json:"range"`          // "custom" for from/to queries
+	Stat        string              `json:"stat,omitempty"` // "avg" or "max" for CPU/memory
 	Data        []HistoryPoint      `json:"data"`
 	PingTargets []PingHistoryTarget `json:"ping_targets,omitempty"`
 	LastBucket  int64               `json:"last_bucket,omitempty"`  // For incremental updates
 	Incremental bool                `json:"incremental,omitempty"` // True if this is an incremental response
+	NextCursor  string              `json:"next_cursor,omitempty"` // Timestamp of the next page when paginated
+	From        string              `json:"from,omitempty"`
+	To          string              `json:"to,omitempty"`
+	Resolution  string              `json:"resolution,omitempty"`  // Table a from/to or max_points query was read from
+	BucketSecs  int64               `json:"bucket_secs,omitempty"` // Width of each downsampled point
+	Samples     []HistorySample     `json:"samples,omitempty"`     // Full snapshots for type=detail
+}
+
+// CompareResponse holds one metric of several servers on a shared grid:
+// Values[i] of every series belongs to Timestamps[i]
+type CompareResponse struct {
+	Metric     string          `json:"metric"`
+	Range      string          `json:"range"`
+	Stat       string          `json:"stat"`
+	From       string          `json:"from"`
+	To         string          `json:"to"`
+	Resolution string          `json:"resolution"`  // Table the series were read from
+	BucketSecs int64           `json:"bucket_secs"` // Width of each point
+	Timestamps []string        `json:"timestamps"`
+	Series     []CompareSeries `json:"series"`
+}
+
+type CompareSeries struct {
+	ServerID string     `json:"server_id"`
+	Name     string     `json:"name"`
+	Values   []*float64 `json:"values"` // null where the server has no data
 }
 
 type PingHistoryTarget struct {
@@ -204,6 +279,34 @@ type PingHistoryPoint struct {
 	Status    string   `json:"status"`
 }
 
+// ============================================================================
+// Public Status Types
+// ============================================================================
+
+// PublicServerStatus is the stripped-down per-server view for the public page.
+// Only name and online are always present; everything else is opt-in.
+type PublicServerStatus struct {
+	Name     string   `json:"name"`
+	Online   bool     `json:"online"`
+	Location string   `json:"location,omitempty"`
+	Provider string   `json:"provider,omitempty"`
+	Tag      string   `json:"tag,omitempty"`
+	Version  string   `json:"version,omitempty"`
+	Uptime   *uint64  `json:"uptime,omitempty"`
+	CPU      *float32 `json:"cpu,omitempty"`
+	Memory   *float32 `json:"memory,omitempty"`
+	Disk     *float32 `json:"disk,omitempty"`
+	Load     *float64 `json:"load,omitempty"`
+	RxSpeed  *uint64  `json:"rx_speed,omitempty"`
+	TxSpeed  *uint64  `json:"tx_speed,omitempty"`
+}
+
+type PublicStatusResponse struct {
+	SiteName  string               `json:"site_name"`
+	Servers   []PublicServerStatus `json:"servers"`
+	UpdatedAt string               `json:"updated_at"`
+}
+
 // ============================================================================
 // WebSocket Message Types
 // ============================================================================
@@ -212,6 +315,110 @@ type AgentMetricsData struct {
 	ServerID    string
 	Metrics     SystemMetrics
 	LastUpdated time.Time
+	// Last heartbeat, which the agent sends while it has no metrics to send.
+	// Liveness is the later of this and LastUpdated.
+	LastHeartbeat time.Time
+	// Set when the agent said goodbye; cleared by the next metrics sample
+	OfflineReason string
+	// Agent clock skew in ms when its timestamp was replaced (0 = in sync)
+	ClockSkewMs int64
+	// Smoothed gap between live samples, 0 until known
+	Interval time.Duration
+}
+
+// AgentOnlineTimeout is how long after its last sample or heartbeat an agent
+// counts as online
+const AgentOnlineTimeout = 30 * time.Second
+
+// Default agent keepalive: a half-open connection is dropped at most
+// interval + timeout after the agent's last frame
+const (
+	DefaultAgentPingInterval = 20 * time.Second
+	DefaultAgentPongTimeout  = 10 * time.Second
+)
+
+// Server status tiers. Degraded servers are online but their last sample is
+// overdue.
+const (
+	ServerStatusOnline   = "online"
+	ServerStatusDegraded = "degraded"
+	ServerStatusOffline  = "offline"
+)
+
+// DefaultDegradedAfterIntervals is how many report intervals a sample may be
+// overdue before the server counts as degraded
+const DefaultDegradedAfterIntervals = 1.5
+
+// lastAlive returns the later of the last sample and the last heartbeat
+func (m *AgentMetricsData) lastAlive() time.Time {
+	if m.LastHeartbeat.After(m.LastUpdated) {
+		return m.LastHeartbeat
+	}
+	return m.LastUpdated
+}
+
+// sample returns the latest metrics, or nil while only heartbeats have
+// arrived
+func (m *AgentMetricsData) sample() *SystemMetrics {
+	if m == nil || m.LastUpdated.IsZero() {
+		return nil
+	}
+	return &m.Metrics
+}
+
+// IsOnline reports whether the agent is connected and alive. Its metrics
+// may lag behind while heartbeats keep arriving.
+func (m *AgentMetricsData) IsOnline() bool {
+	return m != nil && m.OfflineReason == "" && time.Since(m.lastAlive()) < AgentOnlineTimeout
+}
+
+// Status returns the status tier: degraded when the agent is online but its
+// last sample is older than degradedAfter report intervals. Without a known
+// interval, or with degradedAfter <= 0, it is online or offline.
+func (m *AgentMetricsData) Status(degradedAfter float64) string {
+	if !m.IsOnline() {
+		return ServerStatusOffline
+	}
+	if m.Interval > 0 && degradedAfter > 0 &&
+		time.Since(m.LastUpdated) > time.Duration(float64(m.Interval)*degradedAfter) {
+		return ServerStatusDegraded
+	}
+	return ServerStatusOnline
+}
+
+// nextInterval folds the gap since the previous sample into the smoothed
+// report interval. Gaps of an offline agent are not intervals.
+func (m *AgentMetricsData) nextInterval(now time.Time) time.Duration {
+	if m == nil || m.OfflineReason != "" {
+		return 0
+	}
+	gap := now.Sub(m.LastUpdated)
+	if gap <= 0 || gap >= AgentOnlineTimeout {
+		return m.Interval
+	}
+	if m.Interval == 0 {
+		return gap
+	}
+	return (3*m.Interval + gap) / 4
+}
+
+// LastSeen returns when the agent last reported or sent a heartbeat, or nil
+// if it never did
+func (m *AgentMetricsData) LastSeen() *time.Time {
+	if m == nil || m.lastAlive().IsZero() {
+		return nil
+	}
+	lastSeen := m.lastAlive()
+	return &lastSeen
+}
+
+// ClockSkew returns the recorded clock skew, or nil if the clock is in sync
+func (m *AgentMetricsData) ClockSkew() *int64 {
+	if m == nil || m.ClockSkewMs == 0 {
+		return nil
+	}
+	skew := m.ClockSkewMs
+	return &skew
 }
 
 type DashboardMessage struct {
@@ -223,21 +430,29 @@ type DashboardMessage struct {
 }
 
 type ServerMetricsUpdate struct {
-	ServerID     string            `json:"server_id"`
-	ServerName   string            `json:"server_name"`
-	Location     string            `json:"location"`
-	Provider     string            `json:"provider"`
-	Tag          string            `json:"tag"`
-	GroupID      string            `json:"group_id,omitempty"`     // Deprecated
-	GroupValues  map[string]string `json:"group_values,omitempty"` // dimension_id -> option_id
-	Version      string            `json:"version"`
-	IP           string            `json:"ip"`
-	Online       bool              `json:"online"`
-	Metrics      *SystemMetrics    `json:"metrics"`
-	PriceAmount  string            `json:"price_amount,omitempty"`
-	PricePeriod  string            `json:"price_period,omitempty"`
-	PurchaseDate string            `json:"purchase_date,omitempty"`
-	TipBadge     string            `json:"tip_badge,omitempty"`
+	ServerID        string            `json:"server_id"`
+	ServerName      string            `json:"server_name"`
+	Location        string            `json:"location"`
+	Provider        string            `json:"provider"`
+	Tag             string            `json:"tag"`
+	GroupID         string            `json:"group_id,omitempty"`         // Deprecated
+	GroupValues     map[string]string `json:"group_values,omitempty"`     // dimension_id -> option_id
+	Version         string            `json:"version"`
+	IP              string            `json:"ip"`
+	Online          bool              `json:"online"`
+	Status          string            `json:"status"`                     // online, degraded or offline
+	OfflineReason   string            `json:"offline_reason,omitempty"`
+	LastSeen        *time.Time        `json:"last_seen,omitempty"`        // Last report, unset if the agent never reported
+	ClockSkewMs     *int64            `json:"clock_skew_ms,omitempty"`    // Set when the agent clock is off
+	UpdateAvailable bool              `json:"update_available,omitempty"` // Agent is older than the latest release
+	Metrics         *SystemMetrics    `json:"metrics"`
+	PrimaryMount    string            `json:"primary_mount,omitempty"` // Mount the disk usage in metrics refers to
+	LatencyMs       *float64          `json:"latency_ms,omitempty"`    // Headline ping latency (primary target)
+	NetworkSpeed    *NetworkSpeed     `json:"network_speed,omitempty"` // Set by /api/metrics/all
+	PriceAmount     string            `json:"price_amount,omitempty"`
+	PricePeriod     string            `json:"price_period,omitempty"`
+	PurchaseDate    string            `json:"purchase_date,omitempty"`
+	TipBadge        string            `json:"tip_badge,omitempty"`
 }
 
 type DeltaMessage struct {
@@ -249,6 +464,9 @@ type DeltaMessage struct {
 type CompactServerUpdate struct {
 	ID string          `json:"id"`
 	On *bool           `json:"on,omitempty"`
+	R  string          `json:"r,omitempty"`  // Offline reason
+	LS int64           `json:"ls,omitempty"` // Last seen (unix seconds), sent when going offline
+	St string          `json:"st,omitempty"` // Status tier, sent when it changes
 	M  *CompactMetrics `json:"m,omitempty"`
 }
 
@@ -289,12 +507,12 @@ func (cm *CompactMetrics) Diff(prev *CompactMetrics) *CompactMetrics {
 	return diff
 }
 
-func CompactMetricsFromSystem(m *SystemMetrics) *CompactMetrics {
+func CompactMetricsFromSystem(serverID string, m *SystemMetrics) *CompactMetrics {
 	cpu := uint8(m.CPU.Usage)
 	mem := uint8(m.Memory.UsagePercent)
 	var disk *uint8
-	if len(m.Disks) > 0 {
-		d := uint8(m.Disks[0].UsagePercent)
+	if usage, ok := primaryDiskUsage(serverID, m); ok {
+		d := uint8(usage)
 		disk = &d
 	}
 	rx := m.Network.RxSpeed
@@ -315,7 +533,15 @@ type AgentMessage struct {
 	ServerID string         `json:"server_id,omitempty"`
 	Token    string         `json:"token,omitempty"`
 	Version  string         `json:"version,omitempty"`
+	Encoding string         `json:"encoding,omitempty"`
 	Metrics  *SystemMetrics `json:"metrics,omitempty"`
+	// Agent protocol version sent with auth (0 for agents that predate it)
+	ProtocolVersion int `json:"protocol_version,omitempty"`
+	// Delta metrics (negotiated via DeltaVersion in the auth message)
+	DeltaVersion int                    `json:"delta_version,omitempty"`
+	Delta        map[string]interface{} `json:"delta,omitempty"`
+	// Agent reconnect history sent with auth
+	ConnectionStats *common.ConnectionStats `json:"connection_stats,omitempty"`
 	// Batch metrics fields
 	BatchID    string                       `json:"batch_id,omitempty"`
 	BatchItems []common.TimestampedMetrics  `json:"metrics_batch,omitempty"` // For batch raw metrics
@@ -323,6 +549,10 @@ type AgentMessage struct {
 	// Multi-granularity aggregated metrics (new)
 	Granularities []common.GranularityData `json:"granularities,omitempty"` // For multi-granularity data
 	LastMetrics   *SystemMetrics           `json:"last_metrics,omitempty"`  // Latest metrics snapshot
+	// Self-update failure report
+	FromVersion string `json:"from_version,omitempty"`
+	ToVersion   string `json:"to_version,omitempty"`
+	Reason      string `json:"reason,omitempty"`
 }
 
 type AgentCommand struct {
@@ -330,16 +560,70 @@ type AgentCommand struct {
 	Command     string `json:"command"`
 	DownloadURL string `json:"download_url,omitempty"`
 	Force       bool   `json:"force,omitempty"`
+	SHA256      string `json:"sha256,omitempty"`
+	Signature   string `json:"signature,omitempty"`
+	Token       string `json:"token,omitempty"` // rotate_token only
 }
 
 type UpdateAgentRequest struct {
 	DownloadURL string `json:"download_url,omitempty"`
 	Force       bool   `json:"force,omitempty"`
+	// Required by agents for any download_url outside the official releases
+	SHA256    string `json:"sha256,omitempty"`
+	Signature string `json:"signature,omitempty"` // Optional base64 ed25519 signature over sha256
+}
+
+// UpdateAllAgentsRequest is an update request applied to every connected
+// agent matching the optional filters
+type UpdateAllAgentsRequest struct {
+	UpdateAgentRequest
+	Tag      string `json:"tag,omitempty"`
+	Provider string `json:"provider,omitempty"`
+}
+
+type UpdateAllAgentsResult struct {
+	ServerID string `json:"server_id"`
+	Name     string `json:"name"`
+	Status   string `json:"status"` // queued, sent, failed
+	Message  string `json:"message,omitempty"`
+}
+
+// UpdateAllJob is a bulk rollout. The commands go out in the background,
+// UpdateAllStagger apart; each agent's own outcome is in AgentUpdates.
+type UpdateAllJob struct {
+	ID         string                  `json:"id"`
+	Status     string                  `json:"status"` // running, done
+	StartedAt  string                  `json:"started_at"`
+	FinishedAt string                  `json:"finished_at,omitempty"`
+	Total      int                     `json:"total"`
+	Sent       int                     `json:"sent"`
+	Failed     int                     `json:"failed"`
+	Results    []UpdateAllAgentsResult `json:"results"`
 }
 
 type UpdateAgentResponse struct {
 	Success bool   `json:"success"`
 	Message string `json:"message"`
+	// Outcome of the previous update for this agent, e.g. a rolled-back failure
+	LastUpdate *AgentUpdateStatus `json:"last_update,omitempty"`
+}
+
+type RotateTokenResponse struct {
+	ID    string `json:"id"`
+	Token string `json:"token"`
+	// Whether the new token was sent to a connected agent. If not, it has to
+	// be put into the agent's config by hand.
+	AgentNotified bool `json:"agent_notified"`
+}
+
+// AgentUpdateStatus tracks a self-update from command to confirmation
+type AgentUpdateStatus struct {
+	Status      string `json:"status"` // pending, succeeded, failed
+	FromVersion string `json:"from_version,omitempty"`
+	ToVersion   string `json:"to_version,omitempty"`
+	Reason      string `json:"reason,omitempty"`
+	RequestedAt string `json:"requested_at"`
+	UpdatedAt   string `json:"updated_at"`
 }
 
 type InstallCommand struct {
@@ -370,15 +654,39 @@ type DashboardSnapshot struct {
 // ============================================================================
 
 type LastSentState struct {
-	Servers map[string]*struct {
-		Online  bool
-		Metrics *CompactMetrics
-	}
+	Servers map[string]*LastSentEntry
+}
+
+// LastSentEntry is what the dashboards last received for a server
+type LastSentEntry struct {
+	Online  bool
+	Status  string
+	Metrics *CompactMetrics
 }
 
 type AgentConnection struct {
 	Conn     *websocket.Conn
 	SendChan chan []byte
+	// The agent reported at auth that it cannot replace its own binary
+	SelfUpdateUnsupported bool
+}
+
+// AgentConnStats is the connection history of one server since this
+// dashboard started, plus what the agent reported at its last auth
+type AgentConnStats struct {
+	Connects         uint64                  `json:"connects"`
+	LastConnectAt    time.Time               `json:"last_connect_at"`
+	LastDisconnectAt *time.Time              `json:"last_disconnect_at,omitempty"`
+	RemoteIP         string                  `json:"remote_ip"`
+	Agent            *common.ConnectionStats `json:"agent,omitempty"`
+}
+
+// AgentConnectionInfo is an entry of GET /api/agents/connections
+type AgentConnectionInfo struct {
+	ServerID   string `json:"server_id"`
+	ServerName string `json:"server_name"`
+	Connected  bool   `json:"connected"`
+	AgentConnStats
 }
 
 // DashboardClient represents a connected dashboard client with its IP
@@ -396,6 +704,8 @@ type AppState struct {
 	AgentMetricsMu   sync.RWMutex
 	AgentConns       map[string]*AgentConnection
 	AgentConnsMu     sync.RWMutex
+	// Reconnect counts per server, guarded by AgentConnsMu
+	AgentConnStats   map[string]*AgentConnStats
 	LastSent         *LastSentState
 	LastSentMu       sync.RWMutex
 	DashboardClients map[*websocket.Conn]*DashboardClient
@@ -404,6 +714,49 @@ type AppState struct {
 	// Pre-built snapshot for fast dashboard delivery
 	Snapshot         *DashboardSnapshot
 	SnapshotMu       sync.RWMutex
+	// Result of the most recent self-update per agent
+	AgentUpdates     map[string]*AgentUpdateStatus
+	AgentUpdatesMu   sync.RWMutex
+	// Most recent bulk rollout, guarded by AgentUpdatesMu
+	UpdateAllJob     *UpdateAllJob
+	// Cached latest GitHub release
+	LatestVersion    *LatestVersionCache
+	// Records online/offline transitions
+	Availability     *AvailabilityTracker
+	// Open alerts, reloaded from the alerts table at startup
+	Alerts           *AlertManager
+	// Received metrics and broadcast counters for /api/admin/stats
+	Ingest           *IngestStats
+	// Password login attempts per client IP
+	LoginLimiter     *LoginLimiter
+	// Pending deferred config save, see saveConfigLater
+	configSaveMu     sync.Mutex
+	configSaveTimer  *time.Timer
+}
+
+// NewAppState creates the state of a server with the given config and
+// database. Tests can pass a database from InitDatabaseAt(MemoryDBPath).
+func NewAppState(config *AppConfig, db *sql.DB) *AppState {
+	state := &AppState{
+		Config:           config,
+		MetricsBroadcast: make(chan string, 16),
+		AgentMetrics:     make(map[string]*AgentMetricsData),
+		AgentConns:       make(map[string]*AgentConnection),
+		AgentConnStats:   make(map[string]*AgentConnStats),
+		LatestVersion:    &LatestVersionCache{},
+		Availability:     NewAvailabilityTracker(),
+		Alerts:           NewAlertManager(),
+		Ingest:           NewIngestStats(),
+		LoginLimiter:     NewLoginLimiter(),
+		LastSent: &LastSentState{
+			Servers: make(map[string]*LastSentEntry),
+		},
+		DashboardClients: make(map[*websocket.Conn]*DashboardClient),
+		DB:               db,
+		AgentUpdates:     make(map[string]*AgentUpdateStatus),
+	}
+	state.Alerts.Load(db)
+	return state
 }
 
 // GetOnlineUsersCount returns the number of unique IPs connected to the dashboard
@@ -2,10 +2,15 @@ package main
 
 import (
 	"encoding/json"
+	"errors"
 	"log"
+	"net"
 	"net/http"
+	"os"
 	"time"
 
+	"vstats/internal/common"
+
 	"github.com/gin-gonic/gin"
 	"github.com/gorilla/websocket"
 )
@@ -14,20 +19,61 @@ var upgrader = websocket.Upgrader{
 	CheckOrigin: func(r *http.Request) bool {
 		return true
 	},
+	// permessage-deflate is negotiated per connection, so peers that don't
+	// offer it keep talking uncompressed. VSTATS_WS_COMPRESSION=false disables it.
+	EnableCompression: os.Getenv("VSTATS_WS_COMPRESSION") != "false",
 }
 
 // ============================================================================
 // Dashboard WebSocket Handler
 // ============================================================================
 
+// DashboardAuthProtocol is the WebSocket subprotocol browsers use to pass the
+// JWT: new WebSocket(url, ["vstats.auth", token]). Keeps the token out of
+// URLs and access logs; ?token= is accepted for non-browser clients.
+const DashboardAuthProtocol = "vstats.auth"
+
+// dashboardWSToken extracts the JWT from the subprotocol list or query string
+func dashboardWSToken(c *gin.Context) (token string, viaProtocol bool) {
+	protocols := websocket.Subprotocols(c.Request)
+	for i, p := range protocols {
+		if p == DashboardAuthProtocol && i+1 < len(protocols) {
+			return protocols[i+1], true
+		}
+	}
+	return c.Query("token"), false
+}
+
 func (s *AppState) HandleDashboardWS(c *gin.Context) {
-	conn, err := upgrader.Upgrade(c.Writer, c.Request, nil)
+	token, viaProtocol := dashboardWSToken(c)
+	// Anonymous viewers use /api/public/status, which only exposes opt-in fields
+	authorized := token != "" && ValidateAuthToken(token)
+
+	// Browsers drop the connection unless the server echoes a subprotocol
+	var header http.Header
+	if viaProtocol {
+		header = http.Header{"Sec-WebSocket-Protocol": {DashboardAuthProtocol}}
+	}
+
+	conn, err := upgrader.Upgrade(c.Writer, c.Request, header)
 	if err != nil {
 		log.Printf("WebSocket upgrade error: %v", err)
 		return
 	}
 	defer conn.Close()
 
+	s.ConfigMu.RLock()
+	maxMessage := s.Config.WSLimits.dashboardMaxMessageBytes()
+	s.ConfigMu.RUnlock()
+	conn.SetReadLimit(maxMessage)
+
+	if !authorized {
+		conn.WriteControl(websocket.CloseMessage,
+			websocket.FormatCloseMessage(websocket.ClosePolicyViolation, "authentication required"),
+			time.Now().Add(time.Second))
+		return
+	}
+
 	// Get client IP
 	clientIP := c.ClientIP()
 
@@ -52,7 +98,7 @@ func (s *AppState) HandleDashboardWS(c *gin.Context) {
 
 	// Handle incoming messages
 	for {
-		_, _, err := conn.ReadMessage()
+		_, _, err := readLimitedMessage(conn, maxMessage)
 		if err != nil {
 			break
 		}
@@ -126,7 +172,7 @@ func (s *AppState) sendInitialStateFresh(client *DashboardClient) {
 	}
 	s.AgentMetricsMu.RUnlock()
 
-	totalServers := 1 + len(config.Servers) // local + remote
+	totalServers := config.VisibleServerCount()
 
 	// Helper function to write with lock
 	writeMessage := func(data []byte) error {
@@ -152,52 +198,24 @@ func (s *AppState) sendInitialStateFresh(client *DashboardClient) {
 	index := 0
 
 	// Local node first (usually fastest)
-	localMetrics := CollectMetrics()
-	localNode := config.LocalNode
-	localName := "Dashboard Server"
-	if localNode.Name != "" {
-		localName = localNode.Name
-	}
-	provider := "Local"
-	if localNode.Provider != "" {
-		provider = localNode.Provider
-	}
-
-	localServer := StreamServerMessage{
-		Type:  "stream_server",
-		Index: index,
-		Total: totalServers,
-		Server: ServerMetricsUpdate{
-			ServerID:     "local",
-			ServerName:   localName,
-			Location:     localNode.Location,
-			Provider:     provider,
-			Tag:          localNode.Tag,
-			GroupID:      localNode.GroupID,
-			GroupValues:  localNode.GroupValues,
-			Version:      ServerVersion,
-			IP:           "",
-			Online:       true,
-			Metrics:      &localMetrics,
-			PriceAmount:  localNode.PriceAmount,
-			PricePeriod:  localNode.PricePeriod,
-			PurchaseDate: localNode.PurchaseDate,
-			TipBadge:     localNode.TipBadge,
-		},
-	}
-	localData, _ := json.Marshal(localServer)
-	if err := writeMessage(localData); err != nil {
-		return
+	if config.SiteSettings.LocalNodeVisible() {
+		localServer := StreamServerMessage{
+			Type:   "stream_server",
+			Index:  index,
+			Total:  totalServers,
+			Server: localServerUpdate(config),
+		}
+		localData, _ := json.Marshal(localServer)
+		if err := writeMessage(localData); err != nil {
+			return
+		}
+		index++
 	}
-	index++
 
 	// Remote servers
 	for _, server := range config.Servers {
 		metricsData := agentMetrics[server.ID]
-		online := false
-		if metricsData != nil {
-			online = time.Since(metricsData.LastUpdated).Seconds() < 30
-		}
+		online := metricsData.IsOnline()
 
 		version := server.Version
 		if metricsData != nil && metricsData.Metrics.Version != "" {
@@ -205,8 +223,10 @@ func (s *AppState) sendInitialStateFresh(client *DashboardClient) {
 		}
 
 		var metrics *SystemMetrics
+		var offlineReason string
 		if metricsData != nil {
-			metrics = &metricsData.Metrics
+			metrics = metricsData.sample()
+			offlineReason = metricsData.OfflineReason
 		}
 
 		serverMsg := StreamServerMessage{
@@ -214,21 +234,28 @@ func (s *AppState) sendInitialStateFresh(client *DashboardClient) {
 			Index: index,
 			Total: totalServers,
 			Server: ServerMetricsUpdate{
-				ServerID:     server.ID,
-				ServerName:   server.Name,
-				Location:     server.Location,
-				Provider:     server.Provider,
-				Tag:          server.Tag,
-				GroupID:      server.GroupID,
-				GroupValues:  server.GroupValues,
-				Version:      version,
-				IP:           server.IP,
-				Online:       online,
-				Metrics:      metrics,
-				PriceAmount:  server.PriceAmount,
-				PricePeriod:  server.PricePeriod,
-				PurchaseDate: server.PurchaseDate,
-				TipBadge:     server.TipBadge,
+				ServerID:        server.ID,
+				ServerName:      server.Name,
+				Location:        server.Location,
+				Provider:        server.Provider,
+				Tag:             server.Tag,
+				GroupID:         server.GroupID,
+				GroupValues:     server.GroupValues,
+				Version:         version,
+				IP:              server.IP,
+				Online:          online,
+				Status:          metricsData.Status(config.DegradedAfter()),
+				OfflineReason:   offlineReason,
+				LastSeen:        metricsData.LastSeen(),
+				ClockSkewMs:     metricsData.ClockSkew(),
+				UpdateAvailable: s.agentUpdateAvailable(version),
+				Metrics:         metrics,
+				PrimaryMount:    primaryDiskMount(server.ID, metrics),
+				LatencyMs:       headlineLatency(server.ID, metrics),
+				PriceAmount:     server.PriceAmount,
+				PricePeriod:     server.PricePeriod,
+				PurchaseDate:    server.PurchaseDate,
+				TipBadge:        server.TipBadge,
 			},
 		}
 		serverData, _ := json.Marshal(serverMsg)
@@ -244,6 +271,42 @@ func (s *AppState) sendInitialStateFresh(client *DashboardClient) {
 	writeMessage(endData)
 }
 
+// localServerUpdate builds the dashboard entry for the machine running the
+// server, using the local_node config for its name and labels
+func localServerUpdate(config *AppConfig) ServerMetricsUpdate {
+	localMetrics := CollectMetrics()
+	localNode := config.LocalNode
+	localName := "Dashboard Server"
+	if localNode.Name != "" {
+		localName = localNode.Name
+	}
+	provider := "Local"
+	if localNode.Provider != "" {
+		provider = localNode.Provider
+	}
+
+	return ServerMetricsUpdate{
+		ServerID:     "local",
+		ServerName:   localName,
+		Location:     localNode.Location,
+		Provider:     provider,
+		Tag:          localNode.Tag,
+		GroupID:      localNode.GroupID,
+		GroupValues:  localNode.GroupValues,
+		Version:      ServerVersion,
+		IP:           "",
+		Online:       true,
+		Status:       ServerStatusOnline,
+		Metrics:      &localMetrics,
+		PrimaryMount: primaryDiskMount("local", &localMetrics),
+		LatencyMs:    headlineLatency("local", &localMetrics),
+		PriceAmount:  localNode.PriceAmount,
+		PricePeriod:  localNode.PricePeriod,
+		PurchaseDate: localNode.PurchaseDate,
+		TipBadge:     localNode.TipBadge,
+	}
+}
+
 // RefreshSnapshot rebuilds the dashboard snapshot (called periodically)
 func (s *AppState) RefreshSnapshot() {
 	s.ConfigMu.RLock()
@@ -257,7 +320,7 @@ func (s *AppState) RefreshSnapshot() {
 	}
 	s.AgentMetricsMu.RUnlock()
 
-	totalServers := 1 + len(config.Servers)
+	totalServers := config.VisibleServerCount()
 	snapshot := &DashboardSnapshot{
 		ServerMessages: make([][]byte, 0, totalServers),
 		LastUpdated:    time.Now(),
@@ -274,50 +337,23 @@ func (s *AppState) RefreshSnapshot() {
 	snapshot.InitMessage, _ = json.Marshal(initMsg)
 
 	// Build local server message
-	localMetrics := CollectMetrics()
-	localNode := config.LocalNode
-	localName := "Dashboard Server"
-	if localNode.Name != "" {
-		localName = localNode.Name
-	}
-	provider := "Local"
-	if localNode.Provider != "" {
-		provider = localNode.Provider
+	index := 0
+	if config.SiteSettings.LocalNodeVisible() {
+		localServer := StreamServerMessage{
+			Type:   "stream_server",
+			Index:  0,
+			Total:  totalServers,
+			Server: localServerUpdate(config),
+		}
+		localData, _ := json.Marshal(localServer)
+		snapshot.ServerMessages = append(snapshot.ServerMessages, localData)
+		index++
 	}
 
-	localServer := StreamServerMessage{
-		Type:  "stream_server",
-		Index: 0,
-		Total: totalServers,
-		Server: ServerMetricsUpdate{
-			ServerID:     "local",
-			ServerName:   localName,
-			Location:     localNode.Location,
-			Provider:     provider,
-			Tag:          localNode.Tag,
-			GroupID:      localNode.GroupID,
-			GroupValues:  localNode.GroupValues,
-			Version:      ServerVersion,
-			IP:           "",
-			Online:       true,
-			Metrics:      &localMetrics,
-			PriceAmount:  localNode.PriceAmount,
-			PricePeriod:  localNode.PricePeriod,
-			PurchaseDate: localNode.PurchaseDate,
-			TipBadge:     localNode.TipBadge,
-		},
-	}
-	localData, _ := json.Marshal(localServer)
-	snapshot.ServerMessages = append(snapshot.ServerMessages, localData)
-
 	// Build remote server messages
-	index := 1
 	for _, server := range config.Servers {
 		metricsData := agentMetrics[server.ID]
-		online := false
-		if metricsData != nil {
-			online = time.Since(metricsData.LastUpdated).Seconds() < 30
-		}
+		online := metricsData.IsOnline()
 
 		version := server.Version
 		if metricsData != nil && metricsData.Metrics.Version != "" {
@@ -325,8 +361,10 @@ func (s *AppState) RefreshSnapshot() {
 		}
 
 		var metrics *SystemMetrics
+		var offlineReason string
 		if metricsData != nil {
-			metrics = &metricsData.Metrics
+			metrics = metricsData.sample()
+			offlineReason = metricsData.OfflineReason
 		}
 
 		serverMsg := StreamServerMessage{
@@ -334,21 +372,28 @@ func (s *AppState) RefreshSnapshot() {
 			Index: index,
 			Total: totalServers,
 			Server: ServerMetricsUpdate{
-				ServerID:     server.ID,
-				ServerName:   server.Name,
-				Location:     server.Location,
-				Provider:     server.Provider,
-				Tag:          server.Tag,
-				GroupID:      server.GroupID,
-				GroupValues:  server.GroupValues,
-				Version:      version,
-				IP:           server.IP,
-				Online:       online,
-				Metrics:      metrics,
-				PriceAmount:  server.PriceAmount,
-				PricePeriod:  server.PricePeriod,
-				PurchaseDate: server.PurchaseDate,
-				TipBadge:     server.TipBadge,
+				ServerID:        server.ID,
+				ServerName:      server.Name,
+				Location:        server.Location,
+				Provider:        server.Provider,
+				Tag:             server.Tag,
+				GroupID:         server.GroupID,
+				GroupValues:     server.GroupValues,
+				Version:         version,
+				IP:              server.IP,
+				Online:          online,
+				Status:          metricsData.Status(config.DegradedAfter()),
+				OfflineReason:   offlineReason,
+				LastSeen:        metricsData.LastSeen(),
+				ClockSkewMs:     metricsData.ClockSkew(),
+				UpdateAvailable: s.agentUpdateAvailable(version),
+				Metrics:         metrics,
+				PrimaryMount:    primaryDiskMount(server.ID, metrics),
+				LatencyMs:       headlineLatency(server.ID, metrics),
+				PriceAmount:     server.PriceAmount,
+				PricePeriod:     server.PricePeriod,
+				PurchaseDate:    server.PurchaseDate,
+				TipBadge:        server.TipBadge,
 			},
 		}
 		serverData, _ := json.Marshal(serverMsg)
@@ -366,6 +411,50 @@ func (s *AppState) RefreshSnapshot() {
 	s.SnapshotMu.Unlock()
 }
 
+// markAgentOffline flags an agent that disconnected deliberately and pushes
+// the status change to dashboards right away instead of waiting for the
+// online timeout to lapse
+func (s *AppState) markAgentOffline(serverID, reason string) {
+	lastSeen := time.Now()
+	s.AgentMetricsMu.Lock()
+	if current := s.AgentMetrics[serverID]; current != nil {
+		lastSeen = current.lastAlive()
+		updated := *current
+		updated.OfflineReason = reason
+		s.AgentMetrics[serverID] = &updated
+	} else {
+		s.AgentMetrics[serverID] = &AgentMetricsData{
+			ServerID:      serverID,
+			LastUpdated:   time.Now(),
+			OfflineReason: reason,
+		}
+	}
+	s.AgentMetricsMu.Unlock()
+
+	// Record the offline state so the broadcast loop doesn't repeat it
+	s.LastSentMu.Lock()
+	prevMetrics := &CompactMetrics{}
+	if prev := s.LastSent.Servers[serverID]; prev != nil && prev.Metrics != nil {
+		prevMetrics = prev.Metrics
+	}
+	s.LastSent.Servers[serverID] = &LastSentEntry{
+		Online:  false,
+		Status:  ServerStatusOffline,
+		Metrics: prevMetrics,
+	}
+	s.LastSentMu.Unlock()
+
+	msg := DeltaMessage{
+		Type: "delta",
+		Ts:   time.Now().Unix(),
+		D:    []CompactServerUpdate{{ID: serverID, On: boolPtr(false), St: ServerStatusOffline, R: reason, LS: lastSeen.Unix()}},
+	}
+	if data, err := json.Marshal(msg); err == nil {
+		s.BroadcastMetrics(string(data))
+	}
+	go s.RefreshSnapshot()
+}
+
 func (s *AppState) BroadcastMetrics(msg string) {
 	s.DashboardMu.RLock()
 	clients := make([]*DashboardClient, 0, len(s.DashboardClients))
@@ -377,6 +466,8 @@ func (s *AppState) BroadcastMetrics(msg string) {
 	s.DashboardMu.RUnlock()
 
 	msgBytes := []byte(msg)
+	start := time.Now()
+	dropped := 0
 	for _, client := range clients {
 		client.WriteMu.Lock()
 		err := client.Conn.WriteMessage(websocket.TextMessage, msgBytes)
@@ -387,8 +478,10 @@ func (s *AppState) BroadcastMetrics(msg string) {
 			delete(s.DashboardClients, client.Conn)
 			s.DashboardMu.Unlock()
 			client.Conn.Close()
+			dropped++
 		}
 	}
+	s.Ingest.RecordBroadcast(time.Since(start), dropped)
 }
 
 // ============================================================================
@@ -405,20 +498,65 @@ func (s *AppState) HandleAgentWS(c *gin.Context) {
 
 	clientIP := c.ClientIP()
 	var authenticatedServerID string
+	RecordAgentEvent("", AgentEventConnect, "", clientIP, "")
+
+	// Last snapshot the agent diffs against, in generic JSON form.
+	// Only tracked when delta metrics were negotiated.
+	var deltaEnabled bool
+	var deltaBase map[string]interface{}
+
+	// Tolerated agent clock skew, read from config at auth time
+	maxClockSkew := DefaultMaxClockSkew
+	skewLogged := false
+
+	// Logged once per connection: newer metrics schema, unknown message types
+	schemaLogged := false
+	unknownTypes := make(map[string]bool)
+
+	// Keepalive: every frame received, pongs included, extends the read
+	// deadline, so a half-open connection fails the read loop below
+	s.ConfigMu.RLock()
+	pingInterval, pongTimeout := s.Config.AgentKeepalive()
+	maxMessage := s.Config.WSLimits.agentMaxMessageBytes()
+	s.ConfigMu.RUnlock()
+	conn.SetReadLimit(maxMessage)
+	extendDeadline := func() {
+		if pingInterval > 0 {
+			conn.SetReadDeadline(time.Now().Add(pingInterval + pongTimeout))
+		}
+	}
+	extendDeadline()
+	conn.SetPongHandler(func(string) error {
+		extendDeadline()
+		return nil
+	})
+	var pingTick <-chan time.Time
+	if pingInterval > 0 {
+		ticker := time.NewTicker(pingInterval)
+		defer ticker.Stop()
+		pingTick = ticker.C
+	}
 
 	// Create channel for sending commands
 	sendChan := make(chan []byte, 16)
 	done := make(chan struct{})
+	sendFailed := make(chan error, 1)
 
-	// Goroutine to send commands to agent
+	// Goroutine to send commands and pings to agent. A failed send closes
+	// the connection, which ends the read loop below.
 	go func() {
 		for {
 			select {
 			case msg := <-sendChan:
 				if err := conn.WriteMessage(websocket.TextMessage, msg); err != nil {
 					log.Printf("Failed to send message to agent: %v", err)
+					sendFailed <- err
+					conn.Close()
 					return
 				}
+			case <-pingTick:
+				// A failed ping is left to the read deadline
+				conn.WriteControl(websocket.PingMessage, nil, time.Now().Add(pongTimeout))
 			case <-done:
 				return
 			}
@@ -426,40 +564,92 @@ func (s *AppState) HandleAgentWS(c *gin.Context) {
 	}()
 
 	// Handle incoming messages
+	var readErr error
 	for {
-		_, message, err := conn.ReadMessage()
+		msgType, message, err := readLimitedMessage(conn, maxMessage)
 		if err != nil {
+			readErr = err
 			break
 		}
+		extendDeadline()
 
 		var agentMsg AgentMessage
-		if err := json.Unmarshal(message, &agentMsg); err != nil {
+		if msgType == websocket.BinaryMessage {
+			// Binary frames carry msgpack from agents that negotiated it
+			if err := common.DecodeMsgpack(message, &agentMsg); err != nil {
+				log.Printf("Dropped undecodable binary message from %s: %v", clientIP, err)
+				conn.WriteMessage(websocket.TextMessage, []byte(`{"type":"error","message":"Unsupported binary message"}`))
+				continue
+			}
+		} else if err := json.Unmarshal(message, &agentMsg); err != nil {
 			continue
 		}
 
+		// Expand deltas into full snapshots so the rest of the pipeline is unchanged
+		if agentMsg.Type == "metrics_delta" && authenticatedServerID != "" {
+			metrics, err := applyMetricsDelta(deltaBase, agentMsg.Delta)
+			if err != nil {
+				log.Printf("Requesting full snapshot from %s: %v", authenticatedServerID, err)
+				deltaBase = nil
+				requestFullSnapshot(sendChan)
+				continue
+			}
+			agentMsg.Type = "metrics"
+			agentMsg.Metrics = metrics
+		} else if agentMsg.Type == "metrics" && deltaEnabled && agentMsg.Metrics != nil {
+			// Keep the unsanitized snapshot: it is what the agent diffs against
+			if base, err := common.MetricsToMap(agentMsg.Metrics); err == nil {
+				deltaBase = base
+			}
+		}
+
 		switch agentMsg.Type {
 		case "auth":
 			if agentMsg.ServerID != "" && agentMsg.Token != "" {
+				if agentAuthRateLimited(s.DB, clientIP) {
+					log.Printf("Rejected auth for %s from %s: too many failed attempts", agentMsg.ServerID, clientIP)
+					conn.WriteMessage(websocket.TextMessage, []byte(`{"type":"auth","status":"error","code":"`+common.AuthErrorRateLimited+`","message":"Too many failed attempts"}`))
+					RecordAgentEvent(agentMsg.ServerID, AgentEventAuthFailed, common.AuthErrorRateLimited, clientIP, "")
+					continue
+				}
+
 				s.ConfigMu.Lock()
 				var server *RemoteServer
 				for i := range s.Config.Servers {
 					if s.Config.Servers[i].ID == agentMsg.ServerID {
 						if s.Config.Servers[i].Token == agentMsg.Token {
 							server = &s.Config.Servers[i]
+
+							// Refuse agents whose payloads this server can no longer read
+							if agentMsg.ProtocolVersion < s.Config.MinAgentProtocolVersion {
+								log.Printf("Rejected agent %s (version %s): protocol version %d is below the minimum %d",
+									agentMsg.ServerID, agentMsg.Version, agentMsg.ProtocolVersion, s.Config.MinAgentProtocolVersion)
+								conn.WriteMessage(websocket.TextMessage, []byte(`{"type":"auth","status":"error","code":"`+common.AuthErrorUpgradeRequired+`","message":"upgrade required"}`))
+								RecordAgentEvent(agentMsg.ServerID, AgentEventAuthFailed, common.AuthErrorUpgradeRequired, clientIP, "")
+								break
+							}
+							if agentMsg.ProtocolVersion > common.ProtocolVersion {
+								log.Printf("Agent %s speaks protocol version %d, newer than this server (%d); consider upgrading the server",
+									agentMsg.ServerID, agentMsg.ProtocolVersion, common.ProtocolVersion)
+							}
+
 							authenticatedServerID = agentMsg.ServerID
+							maxClockSkew = s.Config.MaxClockSkew()
 
 							// Update version
 							if agentMsg.Version != "" && server.Version != agentMsg.Version {
 								server.Version = agentMsg.Version
-								SaveConfig(s.Config)
+								s.saveConfigLater()
 							}
 
 							// Register connection
 							s.AgentConnsMu.Lock()
 							s.AgentConns[agentMsg.ServerID] = &AgentConnection{
-								Conn:     conn,
-								SendChan: sendChan,
+								Conn:                  conn,
+								SendChan:              sendChan,
+								SelfUpdateUnsupported: agentMsg.SelfUpdateUnsupported,
 							}
+							s.recordAgentConnect(agentMsg.ServerID, clientIP, agentMsg.ConnectionStats)
 							s.AgentConnsMu.Unlock()
 
 							// Send auth success with probe config and last data time
@@ -480,26 +670,61 @@ func (s *AppState) HandleAgentWS(c *gin.Context) {
 							if lastBuckets := GetLastAggregationBuckets(agentMsg.ServerID); len(lastBuckets) > 0 {
 								response["last_buckets"] = lastBuckets
 							}
+
+							// Accept msgpack if requested; older agents never ask
+							if agentMsg.Encoding == common.EncodingMsgpack {
+								response["encoding"] = common.EncodingMsgpack
+							}
+
+							// Accept delta metrics if the agent speaks a version we understand
+							deltaEnabled = agentMsg.DeltaVersion >= common.DeltaProtocolVersion
+							deltaBase = nil
+							if deltaEnabled {
+								response["delta_version"] = common.DeltaProtocolVersion
+							}
 							
 							data, _ := json.Marshal(response)
 							conn.WriteMessage(websocket.TextMessage, data)
 							log.Printf("Agent %s authenticated", agentMsg.ServerID)
+							RecordAgentEvent(agentMsg.ServerID, AgentEventAuthOK, "", clientIP, "")
+							s.resolveAgentUpdate(agentMsg.ServerID, agentMsg.Version)
 						} else {
-							conn.WriteMessage(websocket.TextMessage, []byte(`{"type":"auth","status":"error","message":"Invalid token"}`))
+							conn.WriteMessage(websocket.TextMessage, []byte(`{"type":"auth","status":"error","code":"`+common.AuthErrorInvalidToken+`","message":"Invalid token"}`))
+							RecordAgentEvent(agentMsg.ServerID, AgentEventAuthFailed, common.AuthErrorInvalidToken, clientIP, "")
 						}
 						break
 					}
 				}
 				if server == nil {
-					conn.WriteMessage(websocket.TextMessage, []byte(`{"type":"auth","status":"error","message":"Server not found"}`))
+					conn.WriteMessage(websocket.TextMessage, []byte(`{"type":"auth","status":"error","code":"`+common.AuthErrorServerNotFound+`","message":"Server not found"}`))
+					RecordAgentEvent(agentMsg.ServerID, AgentEventAuthFailed, common.AuthErrorServerNotFound, clientIP, "")
 				}
 				s.ConfigMu.Unlock()
 			}
 
 		case "metrics":
 			if authenticatedServerID != "" && agentMsg.Metrics != nil {
+				if !SanitizeMetrics(authenticatedServerID, agentMsg.Metrics) {
+					continue
+				}
+
+				// Don't trust a broken agent clock: use the receive time instead
+				clockSkewMs := CorrectClockSkew(agentMsg.Metrics, time.Now(), maxClockSkew)
+				if clockSkewMs != 0 && !skewLogged {
+					log.Printf("Agent %s clock is off by %s, using server time",
+						authenticatedServerID, time.Duration(clockSkewMs)*time.Millisecond)
+				}
+				skewLogged = clockSkewMs != 0
+
+				if v := agentMsg.Metrics.SchemaVersion; v > common.MetricsSchemaVersion && !schemaLogged {
+					log.Printf("Agent %s sends metrics schema version %d, newer than this server (%d); fields added since are ignored until the server is upgraded",
+						authenticatedServerID, v, common.MetricsSchemaVersion)
+					schemaLogged = true
+				}
+
 				// Store to database asynchronously via channel queue with deduplication
 				StoreMetricsWithDedup(authenticatedServerID, agentMsg.Metrics)
+				s.Ingest.RecordMetrics(1)
 
 				// Determine IP address
 				agentIP := clientIP
@@ -521,7 +746,7 @@ func (s *AppState) HandleAgentWS(c *gin.Context) {
 							changed = true
 						}
 						if changed {
-							SaveConfig(s.Config)
+							s.saveConfigLater()
 						}
 						break
 					}
@@ -530,10 +755,17 @@ func (s *AppState) HandleAgentWS(c *gin.Context) {
 
 				// Update in-memory state
 				s.AgentMetricsMu.Lock()
+				now := time.Now()
+				prev := s.AgentMetrics[authenticatedServerID]
+				if prev != nil {
+					logServiceChanges(authenticatedServerID, prev.Metrics.Services, agentMsg.Metrics.Services)
+				}
 				s.AgentMetrics[authenticatedServerID] = &AgentMetricsData{
 					ServerID:    authenticatedServerID,
 					Metrics:     *agentMsg.Metrics,
-					LastUpdated: time.Now(),
+					LastUpdated: now,
+					ClockSkewMs: clockSkewMs,
+					Interval:    prev.nextInterval(now),
 				}
 				s.AgentMetricsMu.Unlock()
 			} else {
@@ -546,7 +778,8 @@ func (s *AppState) HandleAgentWS(c *gin.Context) {
 				continue
 			}
 
-			accepted, rejected := s.handleBatchMetrics(authenticatedServerID, &agentMsg)
+			accepted, rejected := s.handleBatchMetrics(authenticatedServerID, &agentMsg, maxClockSkew)
+			s.Ingest.RecordMetrics(accepted)
 			
 			// Send acknowledgment
 			ackResponse := map[string]interface{}{
@@ -561,6 +794,47 @@ func (s *AppState) HandleAgentWS(c *gin.Context) {
 			log.Printf("Batch %s from %s: accepted=%d, rejected=%d", 
 				agentMsg.BatchID, authenticatedServerID, accepted, rejected)
 
+		case "update_failed":
+			if authenticatedServerID == "" {
+				conn.WriteMessage(websocket.TextMessage, []byte(`{"type":"error","message":"Not authenticated"}`))
+				continue
+			}
+			log.Printf("Agent %s update %s -> %s failed: %s",
+				authenticatedServerID, agentMsg.FromVersion, agentMsg.ToVersion, agentMsg.Reason)
+			s.recordAgentUpdateFailure(authenticatedServerID, &agentMsg)
+
+		case "heartbeat":
+			if authenticatedServerID == "" {
+				continue
+			}
+			// Liveness only; nothing is stored. The snapshot is copied since
+			// the broadcast loop reads it without the lock. An agent that has
+			// not sent a sample yet, e.g. since this server restarted, gets an
+			// entry without metrics so it still shows as online.
+			s.AgentMetricsMu.Lock()
+			if prev := s.AgentMetrics[authenticatedServerID]; prev != nil {
+				updated := *prev
+				updated.LastHeartbeat = time.Now()
+				s.AgentMetrics[authenticatedServerID] = &updated
+			} else {
+				s.AgentMetrics[authenticatedServerID] = &AgentMetricsData{
+					ServerID:      authenticatedServerID,
+					LastHeartbeat: time.Now(),
+				}
+			}
+			s.AgentMetricsMu.Unlock()
+
+		case "goodbye":
+			if authenticatedServerID == "" {
+				continue
+			}
+			reason := agentMsg.Reason
+			if reason == "" {
+				reason = "shutdown"
+			}
+			log.Printf("Agent %s is going offline: %s", authenticatedServerID, reason)
+			s.markAgentOffline(authenticatedServerID, reason)
+
 		case "aggregated_metrics":
 			if authenticatedServerID == "" {
 				conn.WriteMessage(websocket.TextMessage, []byte(`{"type":"error","message":"Not authenticated"}`))
@@ -569,11 +843,12 @@ func (s *AppState) HandleAgentWS(c *gin.Context) {
 
 			// Store multi-granularity aggregated data from agent
 			if len(agentMsg.Granularities) > 0 {
+				SanitizeGranularities(authenticatedServerID, agentMsg.Granularities)
 				StoreMultiGranularityMetrics(authenticatedServerID, agentMsg.Granularities)
 			}
 
 			// Update in-memory state with last metrics if provided
-			if agentMsg.LastMetrics != nil {
+			if agentMsg.LastMetrics != nil && SanitizeMetrics(authenticatedServerID, agentMsg.LastMetrics) {
 				s.AgentMetricsMu.Lock()
 				s.AgentMetrics[authenticatedServerID] = &AgentMetricsData{
 					ServerID:    authenticatedServerID,
@@ -582,24 +857,109 @@ func (s *AppState) HandleAgentWS(c *gin.Context) {
 				}
 				s.AgentMetricsMu.Unlock()
 			}
+
+		default:
+			// Newer agents may send message types this server predates
+			if !unknownTypes[agentMsg.Type] {
+				unknownTypes[agentMsg.Type] = true
+				log.Printf("Ignoring unknown message type %q from agent %s", agentMsg.Type, authenticatedServerID)
+			}
 		}
 	}
 
 	// Cleanup on disconnect
 	close(done) // Stop the send goroutine
+	reason, detail := AgentDisconnectError, readErr.Error()
+	var netErr net.Error
+	select {
+	case err := <-sendFailed:
+		reason, detail = AgentDisconnectCommand, err.Error()
+	default:
+		if websocket.IsCloseError(readErr, websocket.CloseNormalClosure, websocket.CloseGoingAway) {
+			reason, detail = AgentDisconnectClean, ""
+		} else if errors.As(readErr, &netErr) && netErr.Timeout() {
+			reason, detail = AgentDisconnectTimeout, ""
+		} else if readErr == errMessageTooBig || readErr == websocket.ErrReadLimit {
+			reason = AgentDisconnectTooBig
+			log.Printf("Dropped agent connection from %s: message larger than %d bytes", clientIP, maxMessage)
+		}
+	}
+	RecordAgentEvent(authenticatedServerID, AgentEventDisconnect, reason, clientIP, detail)
 	if authenticatedServerID != "" {
 		log.Printf("Agent %s disconnected", authenticatedServerID)
 		s.AgentConnsMu.Lock()
-		delete(s.AgentConns, authenticatedServerID)
+		// A reconnect may already have replaced this connection
+		current := false
+		if agentConn := s.AgentConns[authenticatedServerID]; agentConn != nil && agentConn.Conn == conn {
+			delete(s.AgentConns, authenticatedServerID)
+			current = true
+		}
+		if stats := s.AgentConnStats[authenticatedServerID]; stats != nil {
+			now := time.Now()
+			stats.LastDisconnectAt = &now
+		}
 		s.AgentConnsMu.Unlock()
+
+		// The agent went silent, so its last sample is stale already
+		if current && reason == AgentDisconnectTimeout {
+			log.Printf("Agent %s timed out after %v without a pong", authenticatedServerID, pingInterval+pongTimeout)
+			s.markAgentOffline(authenticatedServerID, "timeout")
+		}
+	}
+}
+
+// AgentFlappingThreshold is the agent-reported reconnect count from which a
+// reconnect is logged as a warning
+const AgentFlappingThreshold = 10
+
+// recordAgentConnect updates the connection history of a server. Callers
+// hold AgentConnsMu.
+func (s *AppState) recordAgentConnect(serverID, clientIP string, agentStats *common.ConnectionStats) {
+	stats := s.AgentConnStats[serverID]
+	if stats == nil {
+		stats = &AgentConnStats{}
+		s.AgentConnStats[serverID] = stats
+	}
+	stats.Connects++
+	stats.LastConnectAt = time.Now()
+	stats.RemoteIP = clientIP
+	stats.Agent = agentStats
+
+	if agentStats != nil && agentStats.Reconnects >= AgentFlappingThreshold {
+		log.Printf("Agent %s has reconnected %d times since %s (last: %s)",
+			serverID, agentStats.Reconnects, agentStats.StartedAt, agentStats.LastDisconnectReason)
+	}
+}
+
+// logServiceChanges logs watched services that stopped or recovered since
+// the previous sample
+func logServiceChanges(serverID string, prev, cur []common.ServiceStatus) {
+	was := make(map[string]bool, len(prev))
+	for _, svc := range prev {
+		was[svc.Name] = svc.Active
+	}
+	for _, svc := range cur {
+		active, known := was[svc.Name]
+		switch {
+		case known && active && !svc.Active:
+			log.Printf("Service %s on %s is %s", svc.Name, serverID, svc.State)
+		case known && !active && svc.Active:
+			log.Printf("Service %s on %s is active again", svc.Name, serverID)
+		case !known && !svc.Active:
+			log.Printf("Watched service %s on %s is %s", svc.Name, serverID, svc.State)
+		}
 	}
 }
 
 // handleBatchMetrics processes batch metrics from an agent
-func (s *AppState) handleBatchMetrics(serverID string, msg *AgentMessage) (accepted, rejected int) {
+func (s *AppState) handleBatchMetrics(serverID string, msg *AgentMessage, maxClockSkew time.Duration) (accepted, rejected int) {
+	// Offline samples are legitimately old, but never from the future
+	future := time.Now().Add(maxClockSkew)
+
 	// Process raw metrics
+	var latest *SystemMetrics
 	for _, tm := range msg.BatchItems {
-		if tm.Metrics == nil {
+		if tm.Metrics == nil || !SanitizeMetrics(serverID, tm.Metrics) {
 			rejected++
 			continue
 		}
@@ -614,9 +974,16 @@ func (s *AppState) handleBatchMetrics(serverID string, msg *AgentMessage) (accep
 			}
 		}
 
+		if ts.After(future) {
+			rejected++
+			continue
+		}
+
 		// Update metrics timestamp
 		tm.Metrics.Timestamp = ts
 
+		latest = tm.Metrics
+
 		// Store with deduplication
 		if StoreBatchMetrics(serverID, tm.Metrics) {
 			accepted++
@@ -631,6 +998,14 @@ func (s *AppState) handleBatchMetrics(serverID string, msg *AgentMessage) (accep
 			rejected++
 			continue
 		}
+		if !SanitizeAggregatedMetrics(serverID, agg) {
+			agg.LastMetrics = nil
+			rejected++
+			continue
+		}
+		if agg.LastMetrics != nil && agg.LastMetrics.Timestamp.After(future) {
+			agg.LastMetrics = nil
+		}
 
 		// Store aggregated metrics
 		if StoreAggregatedMetrics(serverID, agg) {
@@ -642,12 +1017,11 @@ func (s *AppState) handleBatchMetrics(serverID string, msg *AgentMessage) (accep
 
 	// Update in-memory state with the latest metrics if available
 	if len(msg.BatchItems) > 0 {
-		lastItem := msg.BatchItems[len(msg.BatchItems)-1]
-		if lastItem.Metrics != nil {
+		if latest != nil {
 			s.AgentMetricsMu.Lock()
 			s.AgentMetrics[serverID] = &AgentMetricsData{
 				ServerID:    serverID,
-				Metrics:     *lastItem.Metrics,
+				Metrics:     *latest,
 				LastUpdated: time.Now(),
 			}
 			s.AgentMetricsMu.Unlock()
@@ -0,0 +1,147 @@
+package main
+
+import (
+	"errors"
+	"fmt"
+	"io"
+	"log"
+	"sync"
+	"time"
+
+	"github.com/gorilla/websocket"
+)
+
+// ============================================================================
+// WebSocket Message Limits
+// ============================================================================
+//
+// WebSocket messages are read into memory whole, so without a limit a buggy
+// or malicious agent could make the server allocate until it runs out of
+// memory. Messages over the limit close the connection with 1009 (message too
+// big). The limit applies to the decompressed message, so a small deflated
+// frame cannot expand past it. Accepted metrics are bounded too: lists that
+// grow with the host (disks, interfaces, watched services) are truncated.
+
+const (
+	// Batches of samples buffered while offline are the largest messages
+	DefaultAgentMaxMessageBytes = 8 << 20
+	// Dashboards only send control frames
+	DefaultDashboardMaxMessageBytes = 64 << 10
+	DefaultMaxMetricsDisks          = 64
+	DefaultMaxMetricsInterfaces     = 64
+	DefaultMaxMetricsServices       = 64
+)
+
+// WSLimitSettings bound WebSocket messages and the lists in agent metrics;
+// zero values mean the defaults
+type WSLimitSettings struct {
+	AgentMaxMessageBytes     int64 `json:"agent_max_message_bytes,omitempty"`     // Default: 8 MiB
+	DashboardMaxMessageBytes int64 `json:"dashboard_max_message_bytes,omitempty"` // Default: 64 KiB
+	MaxDisks                 int   `json:"max_disks,omitempty"`                   // Default: 64
+	MaxInterfaces            int   `json:"max_interfaces,omitempty"`              // Default: 64
+	MaxServices              int   `json:"max_services,omitempty"`                // Default: 64
+}
+
+// Validate rejects negative limits
+func (s *WSLimitSettings) Validate() error {
+	if s == nil {
+		return nil
+	}
+	if s.AgentMaxMessageBytes < 0 || s.DashboardMaxMessageBytes < 0 {
+		return fmt.Errorf("message sizes must not be negative")
+	}
+	if s.MaxDisks < 0 || s.MaxInterfaces < 0 || s.MaxServices < 0 {
+		return fmt.Errorf("list limits must not be negative")
+	}
+	return nil
+}
+
+func limitOrDefault[T int | int64](v, def T) T {
+	if v <= 0 {
+		return def
+	}
+	return v
+}
+
+func (s *WSLimitSettings) agentMaxMessageBytes() int64 {
+	if s == nil {
+		return DefaultAgentMaxMessageBytes
+	}
+	return limitOrDefault(s.AgentMaxMessageBytes, DefaultAgentMaxMessageBytes)
+}
+
+func (s *WSLimitSettings) dashboardMaxMessageBytes() int64 {
+	if s == nil {
+		return DefaultDashboardMaxMessageBytes
+	}
+	return limitOrDefault(s.DashboardMaxMessageBytes, DefaultDashboardMaxMessageBytes)
+}
+
+// metricsListLimits holds the list limits applied by SanitizeMetrics
+var (
+	metricsListLimitsMu sync.RWMutex
+	metricsListLimits   = struct{ disks, interfaces, services int }{
+		DefaultMaxMetricsDisks, DefaultMaxMetricsInterfaces, DefaultMaxMetricsServices,
+	}
+)
+
+// SetMetricsListLimits records the configured metrics list limits
+func SetMetricsListLimits(s *WSLimitSettings) {
+	disks, interfaces, services := DefaultMaxMetricsDisks, DefaultMaxMetricsInterfaces, DefaultMaxMetricsServices
+	if s != nil {
+		disks = limitOrDefault(s.MaxDisks, disks)
+		interfaces = limitOrDefault(s.MaxInterfaces, interfaces)
+		services = limitOrDefault(s.MaxServices, services)
+	}
+	metricsListLimitsMu.Lock()
+	metricsListLimits.disks = disks
+	metricsListLimits.interfaces = interfaces
+	metricsListLimits.services = services
+	metricsListLimitsMu.Unlock()
+}
+
+// truncateMetricsLists cuts the per-host lists of a sample down to the
+// configured limits, logging what was dropped
+func truncateMetricsLists(serverID string, m *SystemMetrics) {
+	metricsListLimitsMu.RLock()
+	limits := metricsListLimits
+	metricsListLimitsMu.RUnlock()
+
+	if n := len(m.Disks); n > limits.disks {
+		m.Disks = m.Disks[:limits.disks]
+		log.Printf("Truncated metrics from %s: %d disks, keeping %d", serverID, n, limits.disks)
+	}
+	if n := len(m.Network.Interfaces); n > limits.interfaces {
+		m.Network.Interfaces = m.Network.Interfaces[:limits.interfaces]
+		log.Printf("Truncated metrics from %s: %d network interfaces, keeping %d", serverID, n, limits.interfaces)
+	}
+	if n := len(m.Services); n > limits.services {
+		m.Services = m.Services[:limits.services]
+		log.Printf("Truncated metrics from %s: %d services, keeping %d", serverID, n, limits.services)
+	}
+}
+
+// errMessageTooBig is returned by readLimitedMessage for oversized messages
+var errMessageTooBig = errors.New("websocket: message exceeds the size limit")
+
+// readLimitedMessage reads the next message like conn.ReadMessage, but fails
+// with errMessageTooBig, and closes the connection with 1009, once the
+// message exceeds limit bytes. The frame-level read limit must be set too:
+// it stops oversized frames before they are buffered at all.
+func readLimitedMessage(conn *websocket.Conn, limit int64) (int, []byte, error) {
+	msgType, r, err := conn.NextReader()
+	if err != nil {
+		return msgType, nil, err
+	}
+	data, err := io.ReadAll(io.LimitReader(r, limit+1))
+	if err != nil {
+		return msgType, nil, err
+	}
+	if int64(len(data)) > limit {
+		conn.WriteControl(websocket.CloseMessage,
+			websocket.FormatCloseMessage(websocket.CloseMessageTooBig, "message too big"),
+			time.Now().Add(time.Second))
+		return msgType, nil, errMessageTooBig
+	}
+	return msgType, data, nil
+}
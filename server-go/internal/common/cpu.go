@@ -0,0 +1,61 @@
+package common
+
+import (
+	"fmt"
+	"os"
+	"runtime"
+	"strconv"
+	"strings"
+
+	"github.com/shirou/gopsutil/v4/cpu"
+)
+
+// ============================================================================
+// CPU Details
+// ============================================================================
+//
+// Shared by the agent and the server's local node collector.
+
+// CollectPhysicalCores returns the physical core count, or nil if unknown
+func CollectPhysicalCores() *int {
+	n, err := cpu.Counts(false)
+	if err != nil || n <= 0 {
+		return nil
+	}
+	return &n
+}
+
+// CollectPerCoreFrequency returns the current frequency in MHz of each
+// logical CPU. On Linux this reads cpufreq, which reflects turbo and
+// throttling; elsewhere it falls back to the per-CPU info from gopsutil.
+func CollectPerCoreFrequency(logicalCores int, cpuInfo []cpu.InfoStat) []uint64 {
+	if runtime.GOOS == "linux" && logicalCores > 0 {
+		freqs := make([]uint64, 0, logicalCores)
+		for i := 0; i < logicalCores; i++ {
+			path := fmt.Sprintf("/sys/devices/system/cpu/cpu%d/cpufreq/scaling_cur_freq", i)
+			data, err := os.ReadFile(path)
+			if err != nil {
+				break
+			}
+			khz, err := strconv.ParseUint(strings.TrimSpace(string(data)), 10, 64)
+			if err != nil {
+				break
+			}
+			freqs = append(freqs, khz/1000)
+		}
+		if len(freqs) == logicalCores {
+			return freqs
+		}
+	}
+
+	// /proc/cpuinfo (Linux without cpufreq) reports one entry per logical CPU
+	if len(cpuInfo) > 1 && len(cpuInfo) == logicalCores {
+		freqs := make([]uint64, len(cpuInfo))
+		for i, info := range cpuInfo {
+			freqs[i] = uint64(info.Mhz)
+		}
+		return freqs
+	}
+
+	return nil
+}
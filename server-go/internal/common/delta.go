@@ -0,0 +1,97 @@
+package common
+
+import (
+	"encoding/json"
+	"reflect"
+)
+
+// ============================================================================
+// Delta Metrics (JSON merge patch, RFC 7386)
+// ============================================================================
+
+// DeltaProtocolVersion is the delta format agents and servers negotiate in
+// the auth handshake. Agents send a full "metrics" snapshot first, then
+// "metrics_delta" messages carrying only the fields that changed.
+const DeltaProtocolVersion = 1
+
+// MetricsDeltaMessage carries a merge patch against the previous snapshot
+type MetricsDeltaMessage struct {
+	Type  string                 `json:"type"` // "metrics_delta"
+	Delta map[string]interface{} `json:"delta"`
+}
+
+// MetricsToMap converts metrics to their generic JSON form
+func MetricsToMap(m *SystemMetrics) (map[string]interface{}, error) {
+	data, err := json.Marshal(m)
+	if err != nil {
+		return nil, err
+	}
+	var out map[string]interface{}
+	if err := json.Unmarshal(data, &out); err != nil {
+		return nil, err
+	}
+	return out, nil
+}
+
+// MapToMetrics converts a generic JSON object back to metrics
+func MapToMetrics(m map[string]interface{}) (*SystemMetrics, error) {
+	data, err := json.Marshal(m)
+	if err != nil {
+		return nil, err
+	}
+	var out SystemMetrics
+	if err := json.Unmarshal(data, &out); err != nil {
+		return nil, err
+	}
+	return &out, nil
+}
+
+// DiffPatch returns a merge patch turning prev into cur. Objects are diffed
+// recursively; arrays and scalars are replaced whole; removed keys map to nil.
+func DiffPatch(prev, cur map[string]interface{}) map[string]interface{} {
+	patch := make(map[string]interface{})
+	for k, cv := range cur {
+		pv, ok := prev[k]
+		if !ok {
+			patch[k] = cv
+			continue
+		}
+		cm, cIsMap := cv.(map[string]interface{})
+		pm, pIsMap := pv.(map[string]interface{})
+		if cIsMap && pIsMap {
+			if sub := DiffPatch(pm, cm); len(sub) > 0 {
+				patch[k] = sub
+			}
+			continue
+		}
+		if !reflect.DeepEqual(pv, cv) {
+			patch[k] = cv
+		}
+	}
+	for k := range prev {
+		if _, ok := cur[k]; !ok {
+			patch[k] = nil
+		}
+	}
+	return patch
+}
+
+// MergePatch applies a merge patch to target in place and returns it
+func MergePatch(target, patch map[string]interface{}) map[string]interface{} {
+	if target == nil {
+		target = make(map[string]interface{})
+	}
+	for k, pv := range patch {
+		if pv == nil {
+			delete(target, k)
+			continue
+		}
+		if pm, ok := pv.(map[string]interface{}); ok {
+			tm, _ := target[k].(map[string]interface{})
+			target[k] = MergePatch(tm, pm)
+			continue
+		}
+		target[k] = pv
+	}
+	return target
+}
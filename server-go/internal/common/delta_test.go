@@ -0,0 +1,173 @@
+package common
+
+import (
+	"encoding/json"
+	"reflect"
+	"testing"
+	"time"
+)
+
+func testMetrics() *SystemMetrics {
+	latency := 12.5
+	return &SystemMetrics{
+		Timestamp: time.Date(2026, 1, 1, 0, 0, 0, 0, time.UTC),
+		Hostname:  "web-1",
+		OS:        OsInfo{Name: "Ubuntu", Version: "24.04", Kernel: "6.8.0", Arch: "x86_64"},
+		CPU:       CpuMetrics{Brand: "EPYC", Cores: 4, Usage: 25, PerCore: []float32{10, 20, 30, 40}},
+		Memory:    MemoryMetrics{Total: 8 << 30, Used: 2 << 30, Available: 6 << 30, UsagePercent: 25},
+		Disks: []DiskMetrics{
+			{Name: "sda", Total: 100 << 30, Used: 40 << 30, UsagePercent: 40, MountPoints: []string{"/"}},
+			{Name: "sdb", Total: 500 << 30, Used: 100 << 30, UsagePercent: 20, MountPoints: []string{"/data", "/backup"}},
+		},
+		Network: NetworkMetrics{
+			Interfaces: []NetworkInterface{{Name: "eth0", RxBytes: 1000, TxBytes: 2000}, {Name: "eth1", RxBytes: 10, TxBytes: 20}},
+			TotalRx:    1010,
+			TotalTx:    2020,
+		},
+		Uptime:      3600,
+		LoadAverage: LoadAverage{One: 0.5, Five: 0.4, Fifteen: 0.3},
+		Ping:        &PingMetrics{Targets: []PingTarget{{Name: "gw", Host: "10.0.0.1", LatencyMs: &latency, Status: "ok"}}},
+		Services:    []ServiceStatus{{Name: "nginx", Active: true, State: "active"}},
+	}
+}
+
+func mustMap(t *testing.T, m *SystemMetrics) map[string]interface{} {
+	t.Helper()
+	out, err := MetricsToMap(m)
+	if err != nil {
+		t.Fatal(err)
+	}
+	return out
+}
+
+// overTheWire sends a patch through JSON, as a metrics_delta message does
+func overTheWire(t *testing.T, patch map[string]interface{}) map[string]interface{} {
+	t.Helper()
+	data, err := json.Marshal(MetricsDeltaMessage{Type: "metrics_delta", Delta: patch})
+	if err != nil {
+		t.Fatal(err)
+	}
+	var msg MetricsDeltaMessage
+	if err := json.Unmarshal(data, &msg); err != nil {
+		t.Fatal(err)
+	}
+	return msg.Delta
+}
+
+// roundTrip diffs prev and cur, applies the patch to prev and checks the
+// result is cur. It returns the patch.
+func roundTrip(t *testing.T, prev, cur *SystemMetrics) map[string]interface{} {
+	t.Helper()
+	patch := overTheWire(t, DiffPatch(mustMap(t, prev), mustMap(t, cur)))
+	merged, err := MapToMetrics(MergePatch(mustMap(t, prev), patch))
+	if err != nil {
+		t.Fatal(err)
+	}
+	if !reflect.DeepEqual(mustMap(t, merged), mustMap(t, cur)) {
+		got, _ := json.Marshal(merged)
+		want, _ := json.Marshal(cur)
+		t.Errorf("merged snapshot differs\n got: %s\nwant: %s", got, want)
+	}
+	return patch
+}
+
+func TestDiffPatchUnchanged(t *testing.T) {
+	if patch := DiffPatch(mustMap(t, testMetrics()), mustMap(t, testMetrics())); len(patch) != 0 {
+		t.Errorf("patch of identical snapshots = %v", patch)
+	}
+}
+
+func TestDiffPatchRoundTrip(t *testing.T) {
+	prev, cur := testMetrics(), testMetrics()
+	cur.Timestamp = cur.Timestamp.Add(time.Second)
+	cur.CPU.Usage = 50
+	cur.Memory.Used += 1 << 20
+	cur.LoadAverage.One = 1.5
+	cur.Uptime++
+
+	patch := roundTrip(t, prev, cur)
+	// Only changed leaves are sent, nested inside their objects
+	want := map[string]interface{}{
+		"timestamp":    "2026-01-01T00:00:01Z",
+		"cpu":          map[string]interface{}{"usage": float64(50)},
+		"memory":       map[string]interface{}{"used": float64(2<<30 + 1<<20)},
+		"load_average": map[string]interface{}{"one": 1.5},
+		"uptime":       float64(3601),
+	}
+	if !reflect.DeepEqual(patch, want) {
+		t.Errorf("patch = %v, want %v", patch, want)
+	}
+}
+
+func TestDiffPatchRemovedKeys(t *testing.T) {
+	prev, cur := testMetrics(), testMetrics()
+	cur.Ping = nil
+	cur.Services = nil
+	cur.Disks[1].MountPoints = nil
+
+	patch := roundTrip(t, prev, cur)
+	for _, key := range []string{"ping", "services"} {
+		if v, ok := patch[key]; !ok || v != nil {
+			t.Errorf("patch[%q] = %v, %v; want null", key, v, ok)
+		}
+	}
+
+	// A removed key comes back when it reappears
+	roundTrip(t, cur, prev)
+
+	// A null for a key the target lacks is harmless
+	target := map[string]interface{}{"a": float64(1)}
+	MergePatch(target, map[string]interface{}{"b": nil, "c": map[string]interface{}{"d": nil}})
+	if !reflect.DeepEqual(target, map[string]interface{}{"a": float64(1), "c": map[string]interface{}{}}) {
+		t.Errorf("target = %v", target)
+	}
+}
+
+func TestDiffPatchNestedArrays(t *testing.T) {
+	prev, cur := testMetrics(), testMetrics()
+	cur.CPU.PerCore[2] = 99
+	cur.Disks[1].Used += 1 << 30
+	cur.Disks[1].MountPoints = []string{"/data"}
+
+	patch := roundTrip(t, prev, cur)
+	// Arrays are replaced whole, even when a single element changed
+	cpu, _ := patch["cpu"].(map[string]interface{})
+	if len(cpu) != 1 || !reflect.DeepEqual(cpu["per_core"], []interface{}{float64(10), float64(20), float64(99), float64(40)}) {
+		t.Errorf("cpu patch = %v", patch["cpu"])
+	}
+	disks, _ := patch["disks"].([]interface{})
+	if len(disks) != 2 {
+		t.Fatalf("disks patch = %v", patch["disks"])
+	}
+	if first, _ := disks[0].(map[string]interface{}); first["name"] != "sda" {
+		t.Errorf("unchanged disk missing from the replaced array: %v", disks[0])
+	}
+	if _, ok := patch["network"]; ok {
+		t.Errorf("unchanged network in the patch: %v", patch["network"])
+	}
+
+	// Shrinking and growing arrays
+	cur.Network.Interfaces = cur.Network.Interfaces[:1]
+	roundTrip(t, prev, cur)
+	cur.Disks = append(cur.Disks, DiskMetrics{Name: "sdc", Total: 1 << 40})
+	roundTrip(t, prev, cur)
+	cur.Disks = nil
+	roundTrip(t, prev, cur)
+}
+
+func TestMergePatchObjects(t *testing.T) {
+	// A nil target and a scalar replaced by an object both start empty
+	got := MergePatch(nil, map[string]interface{}{"a": map[string]interface{}{"b": float64(1)}})
+	if !reflect.DeepEqual(got, map[string]interface{}{"a": map[string]interface{}{"b": float64(1)}}) {
+		t.Errorf("merge into nil = %v", got)
+	}
+	got = MergePatch(map[string]interface{}{"a": "x"}, map[string]interface{}{"a": map[string]interface{}{"b": float64(2)}})
+	if !reflect.DeepEqual(got, map[string]interface{}{"a": map[string]interface{}{"b": float64(2)}}) {
+		t.Errorf("object over scalar = %v", got)
+	}
+	// and an object replaced by a scalar is gone
+	got = MergePatch(map[string]interface{}{"a": map[string]interface{}{"b": float64(2)}}, map[string]interface{}{"a": "x"})
+	if !reflect.DeepEqual(got, map[string]interface{}{"a": "x"}) {
+		t.Errorf("scalar over object = %v", got)
+	}
+}
@@ -0,0 +1,42 @@
+package common
+
+import (
+	"reflect"
+
+	"github.com/ugorji/go/codec"
+)
+
+// ============================================================================
+// Agent Protocol Encoding
+// ============================================================================
+
+// Wire encodings an agent can request during auth. JSON is the default; with
+// msgpack the agent sends metrics as binary frames once the server agrees.
+const (
+	EncodingJSON    = "json"
+	EncodingMsgpack = "msgpack"
+)
+
+// msgpackHandle encodes structs by their json tags so both encodings share
+// the same field names
+var msgpackHandle = func() *codec.MsgpackHandle {
+	h := &codec.MsgpackHandle{}
+	h.TypeInfos = codec.NewTypeInfos([]string{"json"})
+	h.WriteExt = true
+	h.RawToString = true
+	// Decode nested objects like encoding/json does (delta patches rely on it)
+	h.MapType = reflect.TypeOf(map[string]interface{}(nil))
+	return h
+}()
+
+// EncodeMsgpack serializes v as MessagePack
+func EncodeMsgpack(v interface{}) ([]byte, error) {
+	var out []byte
+	err := codec.NewEncoderBytes(&out, msgpackHandle).Encode(v)
+	return out, err
+}
+
+// DecodeMsgpack deserializes MessagePack data into v
+func DecodeMsgpack(data []byte, v interface{}) error {
+	return codec.NewDecoderBytes(data, msgpackHandle).Decode(v)
+}
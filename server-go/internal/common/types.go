@@ -6,19 +6,28 @@ import "time"
 // System Metrics Types
 // ============================================================================
 
+// MetricsSchemaVersion is the SystemMetrics layout sent by this build. Bump
+// it when fields are added so servers can tell they are missing some. New
+// fields must be optional (omitempty, with a zero value that means "not
+// reported"): JSON decoding leaves fields absent from older payloads at their
+// zero value and ignores fields it does not know.
+const MetricsSchemaVersion = 1
+
 type SystemMetrics struct {
-	Timestamp   time.Time      `json:"timestamp"`
-	Hostname    string         `json:"hostname"`
-	OS          OsInfo         `json:"os"`
-	CPU         CpuMetrics     `json:"cpu"`
-	Memory      MemoryMetrics  `json:"memory"`
-	Disks       []DiskMetrics  `json:"disks"`
-	Network     NetworkMetrics `json:"network"`
-	Uptime      uint64         `json:"uptime"`
-	LoadAverage LoadAverage    `json:"load_average"`
-	Ping        *PingMetrics   `json:"ping,omitempty"`
-	Version     string         `json:"version,omitempty"`
-	IPAddresses []string       `json:"ip_addresses,omitempty"`
+	Timestamp     time.Time       `json:"timestamp"`
+	Hostname      string          `json:"hostname"`
+	OS            OsInfo          `json:"os"`
+	CPU           CpuMetrics      `json:"cpu"`
+	Memory        MemoryMetrics   `json:"memory"`
+	Disks         []DiskMetrics   `json:"disks"`
+	Network       NetworkMetrics  `json:"network"`
+	Uptime        uint64          `json:"uptime"`
+	LoadAverage   LoadAverage     `json:"load_average"`
+	Ping          *PingMetrics    `json:"ping,omitempty"`
+	Version       string          `json:"version,omitempty"`
+	IPAddresses   []string        `json:"ip_addresses,omitempty"`
+	Services      []ServiceStatus `json:"services,omitempty"`       // Watched systemd services
+	SchemaVersion int             `json:"schema_version,omitempty"` // See MetricsSchemaVersion; 0 from older agents
 }
 
 type OsInfo struct {
@@ -30,10 +39,13 @@ type OsInfo struct {
 
 type CpuMetrics struct {
 	Brand     string    `json:"brand"`
-	Cores     int       `json:"cores"`
+	Cores     int       `json:"cores"` // Logical CPUs
 	Usage     float32   `json:"usage"`
 	Frequency uint64    `json:"frequency"`
 	PerCore   []float32 `json:"per_core"`
+	// Physical core count (nil if unknown) and current MHz per logical CPU
+	PhysicalCores    *int     `json:"physical_cores,omitempty"`
+	PerCoreFrequency []uint64 `json:"per_core_frequency,omitempty"`
 }
 
 type MemoryMetrics struct {
@@ -65,14 +77,20 @@ type DiskMetrics struct {
 	Used         uint64   `json:"used"`
 	ReadSpeed    uint64   `json:"read_speed,omitempty"`  // Bytes per second
 	WriteSpeed   uint64   `json:"write_speed,omitempty"` // Bytes per second
+	// Inodes summed over the mounted filesystems; 0 where the platform or
+	// filesystem (e.g. NTFS, btrfs) has no fixed inode table
+	InodesTotal        uint64  `json:"inodes_total,omitempty"`
+	InodesUsed         uint64  `json:"inodes_used,omitempty"`
+	InodesUsagePercent float32 `json:"inodes_usage_percent,omitempty"`
 }
 
+// NetworkMetrics totals are bytes and speeds are bytes per second
 type NetworkMetrics struct {
 	Interfaces []NetworkInterface `json:"interfaces"`
-	TotalRx    uint64             `json:"total_rx"`
-	TotalTx    uint64             `json:"total_tx"`
-	RxSpeed    uint64             `json:"rx_speed"`
-	TxSpeed    uint64             `json:"tx_speed"`
+	TotalRx    uint64             `json:"total_rx"` // Bytes since boot
+	TotalTx    uint64             `json:"total_tx"` // Bytes since boot
+	RxSpeed    uint64             `json:"rx_speed"` // Bytes per second
+	TxSpeed    uint64             `json:"tx_speed"` // Bytes per second
 	DailyRx    uint64             `json:"daily_rx,omitempty"` // Daily received bytes
 	DailyTx    uint64             `json:"daily_tx,omitempty"` // Daily transmitted bytes
 }
@@ -107,6 +125,14 @@ type PingTarget struct {
 	Status     string   `json:"status"`
 }
 
+// ServiceStatus is the state of a watched systemd service as reported by
+// systemctl is-active (e.g. "active", "inactive", "failed")
+type ServiceStatus struct {
+	Name   string `json:"name"`
+	Active bool   `json:"active"`
+	State  string `json:"state"`
+}
+
 type PingTargetConfig struct {
 	Name string `json:"name"`
 	Host string `json:"host"`
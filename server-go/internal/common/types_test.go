@@ -0,0 +1,94 @@
+package common
+
+import (
+	"encoding/json"
+	"reflect"
+	"strings"
+	"testing"
+)
+
+// A metrics payload as sent by agents from before schema_version existed
+const unversionedMetricsPayload = `{
+	"timestamp": "2025-06-01T12:00:00Z",
+	"hostname": "web-1",
+	"os": {"name": "Debian", "version": "12", "kernel": "6.1.0", "arch": "x86_64"},
+	"cpu": {"brand": "Xeon", "cores": 2, "usage": 12.5, "frequency": 2400, "per_core": [10, 15]},
+	"memory": {"total": 4096, "used": 1024, "available": 3072, "swap_total": 0, "swap_used": 0, "usage_percent": 25},
+	"disks": [{"name": "vda", "total": 1000, "usage_percent": 40, "used": 400}],
+	"network": {"interfaces": [{"name": "eth0", "rx_bytes": 1, "tx_bytes": 2, "rx_packets": 3, "tx_packets": 4}],
+		"total_rx": 1, "total_tx": 2, "rx_speed": 0, "tx_speed": 0},
+	"uptime": 86400,
+	"load_average": {"one": 0.1, "five": 0.2, "fifteen": 0.3},
+	"version": "1.0.0"
+}`
+
+func TestDecodeUnversionedMetrics(t *testing.T) {
+	var m SystemMetrics
+	if err := json.Unmarshal([]byte(unversionedMetricsPayload), &m); err != nil {
+		t.Fatal(err)
+	}
+	if m.SchemaVersion != 0 {
+		t.Errorf("schema_version = %d, want 0 for an unversioned payload", m.SchemaVersion)
+	}
+	if m.Hostname != "web-1" || m.CPU.Usage != 12.5 || len(m.Disks) != 1 || m.Disks[0].Used != 400 ||
+		m.Network.Interfaces[0].TxPackets != 4 || m.LoadAverage.Fifteen != 0.3 || m.Uptime != 86400 {
+		t.Errorf("fields lost: %+v", m)
+	}
+	// Fields added since are left unreported
+	if m.Ping != nil || m.Services != nil || m.CPU.PhysicalCores != nil || m.CPU.PerCoreFrequency != nil {
+		t.Errorf("newer fields set: %+v", m)
+	}
+}
+
+func TestDecodeNewerMetrics(t *testing.T) {
+	// A future agent: a higher schema and fields this build does not know,
+	// at the top level and nested
+	payload := strings.Replace(unversionedMetricsPayload, `"version": "1.0.0"`,
+		`"version": "9.0.0", "schema_version": 7, "gpu": [{"name": "A100", "usage": 99}]`, 1)
+	payload = strings.Replace(payload, `"brand": "Xeon"`, `"brand": "Xeon", "temperature": {"package": 61}`, 1)
+
+	var m SystemMetrics
+	if err := json.Unmarshal([]byte(payload), &m); err != nil {
+		t.Fatal(err)
+	}
+	if m.SchemaVersion != 7 || m.Version != "9.0.0" || m.CPU.Brand != "Xeon" {
+		t.Errorf("got %+v", m)
+	}
+}
+
+// metricsFieldsV0 are the JSON fields of the metrics types before
+// MetricsSchemaVersion; everything added since must be optional
+var metricsFieldsV0 = map[reflect.Type][]string{
+	reflect.TypeOf(SystemMetrics{}): {"timestamp", "hostname", "os", "cpu", "memory", "disks", "network",
+		"uptime", "load_average", "ping", "version", "ip_addresses"},
+	reflect.TypeOf(OsInfo{}):           {"name", "version", "kernel", "arch"},
+	reflect.TypeOf(CpuMetrics{}):       {"brand", "cores", "usage", "frequency", "per_core"},
+	reflect.TypeOf(MemoryMetrics{}):    {"total", "used", "available", "swap_total", "swap_used", "usage_percent", "modules"},
+	reflect.TypeOf(MemoryModule{}):     {"slot", "size", "mem_type", "speed", "manufacturer"},
+	reflect.TypeOf(NetworkMetrics{}):   {"interfaces", "total_rx", "total_tx", "rx_speed", "tx_speed", "daily_rx", "daily_tx"},
+	reflect.TypeOf(NetworkInterface{}): {"name", "mac", "speed", "rx_bytes", "tx_bytes", "rx_packets", "tx_packets"},
+	reflect.TypeOf(LoadAverage{}):      {"one", "five", "fifteen"},
+	reflect.TypeOf(PingMetrics{}):      {"targets"},
+	reflect.TypeOf(PingTarget{}):       {"name", "host", "type", "port", "latency_ms", "packet_loss", "status"},
+	reflect.TypeOf(DiskMetrics{}): {"name", "model", "serial", "total", "disk_type", "mount_points",
+		"usage_percent", "used", "read_speed", "write_speed"},
+}
+
+func TestNewMetricsFieldsAreOptional(t *testing.T) {
+	for typ, fields := range metricsFieldsV0 {
+		known := make(map[string]bool, len(fields))
+		for _, f := range fields {
+			known[f] = true
+		}
+		for i := 0; i < typ.NumField(); i++ {
+			tag := typ.Field(i).Tag.Get("json")
+			name, opts, _ := strings.Cut(tag, ",")
+			if tag == "-" || known[name] {
+				continue
+			}
+			if !strings.Contains(opts, "omitempty") {
+				t.Errorf("%s.%s was added after schema version 0 and must be omitempty", typ.Name(), typ.Field(i).Name)
+			}
+		}
+	}
+}
@@ -4,13 +4,51 @@ package common
 // WebSocket Message Types
 // ============================================================================
 
+// ProtocolVersion is the agent protocol version spoken by this build. Bump it
+// when agent payloads change incompatibly; servers can then refuse older
+// agents via min_agent_protocol_version. Agents that predate the field send 0.
+const ProtocolVersion = 1
+
 type AuthMessage struct {
 	Type     string `json:"type"`
 	ServerID string `json:"server_id"`
 	Token    string `json:"token"`
 	Version  string `json:"version"`
+	Encoding string `json:"encoding,omitempty"` // Requested wire encoding (EncodingJSON if empty)
+	// Agent protocol version (see ProtocolVersion)
+	ProtocolVersion int `json:"protocol_version,omitempty"`
+	// Highest delta metrics version the agent speaks (0 = full snapshots only)
+	DeltaVersion int `json:"delta_version,omitempty"`
+	// Reconnect history since the agent started
+	ConnectionStats *ConnectionStats `json:"connection_stats,omitempty"`
+	// Set when the agent cannot replace its own binary, e.g. an unprivileged
+	// service with a root-owned executable; update commands are refused
+	SelfUpdateUnsupported bool `json:"self_update_unsupported,omitempty"`
+}
+
+// ConnectionStats is the agent's view of its connection quality. Frequent
+// reconnects usually point at a network problem or an overloaded server.
+type ConnectionStats struct {
+	Reconnects           uint64 `json:"reconnects"` // Connections lost since the agent started
+	LastDisconnectReason string `json:"last_disconnect_reason,omitempty"`
+	LastDisconnectAt     string `json:"last_disconnect_at,omitempty"` // RFC 3339
+	StartedAt            string `json:"started_at"`                   // Agent start, RFC 3339
 }
 
+// Auth error codes sent in ServerResponse.Code. Both mean the credentials
+// will never work again, so agents should stop retrying at normal speed.
+const (
+	AuthErrorServerNotFound = "server_not_found"
+	AuthErrorInvalidToken   = "invalid_token"
+)
+
+// AuthErrorUpgradeRequired means the agent protocol is older than the server accepts
+const AuthErrorUpgradeRequired = "upgrade_required"
+
+// AuthErrorRateLimited means too many auths failed from the agent's IP; the
+// agent should back off and retry later
+const AuthErrorRateLimited = "rate_limited"
+
 type MetricsMessage struct {
 	Type    string        `json:"type"`
 	Metrics SystemMetrics `json:"metrics"`
@@ -20,10 +58,17 @@ type ServerResponse struct {
 	Type        string             `json:"type"`
 	Status      string             `json:"status,omitempty"`
 	Message     string             `json:"message,omitempty"`
+	Code        string             `json:"code,omitempty"` // Machine-readable error code (see AuthError*)
 	Command     string             `json:"command,omitempty"`
 	DownloadURL string             `json:"download_url,omitempty"`
 	Force       bool               `json:"force,omitempty"`
+	SHA256      string             `json:"sha256,omitempty"`    // Expected checksum of the update binary
+	Signature   string             `json:"signature,omitempty"` // Base64 ed25519 signature over sha256
+	Token       string             `json:"token,omitempty"`     // New agent token of a rotate_token command
 	PingTargets []PingTargetConfig `json:"ping_targets,omitempty"`
+	Encoding    string             `json:"encoding,omitempty"` // Encoding accepted in the auth response
+	// Delta metrics version accepted in the auth response (0 = disabled)
+	DeltaVersion int `json:"delta_version,omitempty"`
 	// Batch metrics response fields
 	BatchID   string  `json:"batch_id,omitempty"`
 	Accepted  int     `json:"accepted,omitempty"`
@@ -33,6 +78,23 @@ type ServerResponse struct {
 	LastBuckets map[string]int64 `json:"last_buckets,omitempty"` // granularity -> last bucket
 }
 
+// UpdateFailedMessage is sent by an agent when an update command fails, or
+// after it rolled back a self-update whose new binary failed to start or
+// authenticate
+type UpdateFailedMessage struct {
+	Type        string `json:"type"` // "update_failed"
+	FromVersion string `json:"from_version"`
+	ToVersion   string `json:"to_version,omitempty"`
+	Reason      string `json:"reason"`
+}
+
+// GoodbyeMessage is sent by an agent right before a deliberate disconnect so
+// the dashboard can mark it offline immediately instead of waiting for a timeout
+type GoodbyeMessage struct {
+	Type   string `json:"type"` // "goodbye"
+	Reason string `json:"reason"`
+}
+
 // ============================================================================
 // Registration Types
 // ============================================================================
@@ -41,10 +103,15 @@ type RegisterRequest struct {
 	Name     string `json:"name"`
 	Location string `json:"location"`
 	Provider string `json:"provider"`
+	Tag      string `json:"tag,omitempty"`
+	// Stable host identifier; registering again with the same id returns the
+	// existing server with a fresh token instead of creating a duplicate
+	MachineID string `json:"machine_id,omitempty"`
 }
 
 type RegisterResponse struct {
-	ID    string `json:"id"`
-	Token string `json:"token"`
+	ID       string `json:"id"`
+	Token    string `json:"token"`
+	Existing bool   `json:"existing,omitempty"` // Matched an existing server by machine_id
 }
 
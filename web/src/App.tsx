@@ -3,6 +3,7 @@ import { AuthProvider } from './context/AuthContext';
 import { ThemeProvider } from './context/ThemeContext';
 import { WebSocketProvider } from './context/WebSocketContext';
 import { ToastContainer } from './components/Toast';
+import { BASE_PATH } from './utils/basePath';
 import Dashboard from './pages/Dashboard';
 import ServerDetail from './pages/ServerDetail';
 import Login from './pages/Login';
@@ -14,7 +15,7 @@ function App() {
     <ThemeProvider>
       <AuthProvider>
         <WebSocketProvider>
-          <BrowserRouter>
+          <BrowserRouter basename={BASE_PATH || undefined}>
             <Routes>
               <Route path="/" element={<Dashboard />} />
               <Route path="/server/:id" element={<ServerDetail />} />
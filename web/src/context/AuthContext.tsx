@@ -5,10 +5,15 @@ interface OAuthProviders {
   google?: boolean;
 }
 
+// 'two_factor_required' means the password was accepted and verifyTwoFactor
+// must be called with a TOTP or recovery code to finish logging in
+export type LoginResult = 'success' | 'two_factor_required' | 'failed';
+
 interface AuthContextType {
   isAuthenticated: boolean;
   token: string | null;
-  login: (password: string) => Promise<boolean>;
+  login: (password: string) => Promise<LoginResult>;
+  verifyTwoFactor: (code: string) => Promise<boolean>;
   logout: () => void;
   isLoading: boolean;
   oauthProviders: OAuthProviders;
@@ -26,6 +31,7 @@ export function AuthProvider({ children }: { children: ReactNode }) {
   const [oauthProviders, setOauthProviders] = useState<OAuthProviders>({});
   const [oauthUser, setOauthUser] = useState<string | null>(() => localStorage.getItem('vstats_oauth_user'));
   const [oauthProvider, setOauthProvider] = useState<string | null>(() => localStorage.getItem('vstats_oauth_provider'));
+  const [twoFactorToken, setTwoFactorToken] = useState<string | null>(null);
 
   // Fetch available OAuth providers
   useEffect(() => {
@@ -68,7 +74,16 @@ export function AuthProvider({ children }: { children: ReactNode }) {
     verifyToken();
   }, [token]);
 
-  const login = async (password: string): Promise<boolean> => {
+  const storePasswordToken = (newToken: string) => {
+    setToken(newToken);
+    setOauthUser(null);
+    setOauthProvider(null);
+    localStorage.setItem('vstats_token', newToken);
+    localStorage.removeItem('vstats_oauth_user');
+    localStorage.removeItem('vstats_oauth_provider');
+  };
+
+  const login = async (password: string): Promise<LoginResult> => {
     try {
       const res = await fetch('/api/auth/login', {
         method: 'POST',
@@ -78,12 +93,32 @@ export function AuthProvider({ children }: { children: ReactNode }) {
       
       if (res.ok) {
         const data = await res.json();
-        setToken(data.token);
-        setOauthUser(null);
-        setOauthProvider(null);
-        localStorage.setItem('vstats_token', data.token);
-        localStorage.removeItem('vstats_oauth_user');
-        localStorage.removeItem('vstats_oauth_provider');
+        if (data.two_factor_required) {
+          setTwoFactorToken(data.two_factor_token);
+          return 'two_factor_required';
+        }
+        storePasswordToken(data.token);
+        return 'success';
+      }
+      return 'failed';
+    } catch {
+      return 'failed';
+    }
+  };
+
+  const verifyTwoFactor = async (code: string): Promise<boolean> => {
+    if (!twoFactorToken) return false;
+    try {
+      const res = await fetch('/api/auth/2fa/verify', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ two_factor_token: twoFactorToken, code })
+      });
+
+      if (res.ok) {
+        const data = await res.json();
+        setTwoFactorToken(null);
+        storePasswordToken(data.token);
         return true;
       }
       return false;
@@ -134,6 +169,7 @@ export function AuthProvider({ children }: { children: ReactNode }) {
       isAuthenticated: !!token, 
       token, 
       login, 
+      verifyTwoFactor,
       logout,
       isLoading,
       oauthProviders,
@@ -1,6 +1,7 @@
 import { createContext, useContext, useEffect, useRef, useState, useCallback, type ReactNode } from 'react';
 import type { SystemMetrics, SiteSettings, ServerGroup, GroupDimension } from '../types';
 import { sanitizeSiteSettings } from '../utils/security';
+import { BASE_PATH } from '../utils/basePath';
 
 // Types
 interface NetworkSpeed {
@@ -32,6 +33,12 @@ export interface ServerState {
   metrics: SystemMetrics | null;
   speed: NetworkSpeed;
   isConnected: boolean;
+  // Set when the agent disconnected deliberately (e.g. 'shutdown')
+  offlineReason?: string;
+  // When the agent last reported (ISO 8601), unset if it never did
+  lastSeen?: string;
+  // Agent clock skew in ms, set when the server replaced its timestamps
+  clockSkewMs?: number;
   error: string | null;
 }
 
@@ -73,6 +80,8 @@ interface DeltaMessage {
 interface CompactServerUpdate {
   id: string;
   on?: boolean;
+  r?: string; // offline reason
+  ls?: number; // last seen (unix seconds), sent when going offline
   m?: CompactMetrics;
 }
 
@@ -95,6 +104,10 @@ interface ServerMetricsUpdate {
   group_values?: Record<string, string>;
   version?: string;
   online: boolean;
+  offline_reason?: string;
+  last_seen?: string;
+  clock_skew_ms?: number;
+  update_available?: boolean;
   metrics: SystemMetrics | null;
   price_amount?: string;
   price_period?: string;
@@ -102,6 +115,62 @@ interface ServerMetricsUpdate {
   tip_badge?: string;
 }
 
+// Opt-in fields of /api/public/status, shown to viewers who aren't logged in
+interface PublicServerStatus {
+  name: string;
+  online: boolean;
+  location?: string;
+  provider?: string;
+  tag?: string;
+  version?: string;
+  uptime?: number;
+  cpu?: number;
+  memory?: number;
+  disk?: number;
+  load?: number;
+  rx_speed?: number;
+  tx_speed?: number;
+}
+
+interface PublicStatusResponse {
+  site_name: string;
+  servers: PublicServerStatus[];
+  updated_at: string;
+}
+
+// How often anonymous viewers poll the public status
+const PUBLIC_STATUS_INTERVAL = 10000;
+
+// Fields the public status leaves out are zero or empty
+const publicServerState = (s: PublicServerStatus, updatedAt: string): ServerState => {
+  const speed = { rx_sec: s.rx_speed ?? 0, tx_sec: s.tx_speed ?? 0 };
+  return {
+    config: {
+      id: `public-${s.name}`,
+      name: s.name,
+      type: 'real',
+      location: s.location,
+      provider: s.provider,
+      tag: s.tag,
+      version: s.version,
+    },
+    metrics: s.online ? {
+      timestamp: updatedAt,
+      hostname: '',
+      os: { name: '', version: '', kernel: '', arch: '' },
+      cpu: { brand: '', cores: 0, usage: s.cpu ?? 0, frequency: 0, per_core: [] },
+      memory: { total: 0, used: 0, available: 0, swap_total: 0, swap_used: 0, usage_percent: s.memory ?? 0 },
+      disks: s.disk !== undefined ? [{ name: '', total: 0, used: 0, usage_percent: s.disk }] : [],
+      network: { interfaces: [], total_rx: 0, total_tx: 0, rx_speed: speed.rx_sec, tx_speed: speed.tx_sec },
+      uptime: s.uptime ?? 0,
+      load_average: { one: s.load ?? 0, five: 0, fifteen: 0 },
+    } : null,
+    speed,
+    isConnected: s.online,
+    error: null,
+  };
+};
+
 // Context interface
 interface WebSocketContextValue {
   servers: ServerState[];
@@ -178,6 +247,10 @@ export function WebSocketProvider({ children }: { children: ReactNode }) {
     
     if (delta.on !== undefined) {
       updated.isConnected = delta.on;
+      updated.offlineReason = delta.on ? undefined : delta.r;
+      if (delta.ls) {
+        updated.lastSeen = new Date(delta.ls * 1000).toISOString();
+      }
     }
     
     if (delta.m && updated.metrics) {
@@ -214,12 +287,22 @@ export function WebSocketProvider({ children }: { children: ReactNode }) {
 
   // Global WebSocket connection - persists across page navigations
   useEffect(() => {
+    let closed = false;
+
     const connect = () => {
       try {
         const protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
-        const wsUrl = `${protocol}//${window.location.host}/ws`;
+        const wsUrl = `${protocol}//${window.location.host}${BASE_PATH}/ws`;
+
+        // The stream needs a login; anonymous viewers get the public status
+        const token = localStorage.getItem('vstats_token');
+        if (!token) {
+          pollPublicStatus();
+          return;
+        }
 
-        const ws = new WebSocket(wsUrl);
+        // The JWT travels as a subprotocol so it stays out of URLs and logs
+        const ws = new WebSocket(wsUrl, ['vstats.auth', token]);
         wsRef.current = ws;
 
         ws.onopen = () => {
@@ -318,6 +401,9 @@ export function WebSocketProvider({ children }: { children: ReactNode }) {
                   metrics: metricsToUse,
                   speed: newSpeed,
                   isConnected: serverUpdate.online,
+                  offlineReason: serverUpdate.offline_reason,
+                  lastSeen: serverUpdate.last_seen,
+                  clockSkewMs: serverUpdate.clock_skew_ms,
                   error: null
                 };
                 
@@ -452,6 +538,9 @@ export function WebSocketProvider({ children }: { children: ReactNode }) {
                 metrics: metricsToUse,
                 speed: newSpeed,
                 isConnected: serverUpdate.online,
+                offlineReason: serverUpdate.offline_reason,
+                lastSeen: serverUpdate.last_seen,
+                clockSkewMs: serverUpdate.clock_skew_ms,
                 error: null
               };
               
@@ -474,10 +563,17 @@ export function WebSocketProvider({ children }: { children: ReactNode }) {
           }
         };
 
-        ws.onclose = () => {
-          console.log('[WebSocket] Disconnected, reconnecting...');
+        ws.onclose = (event) => {
           wsRef.current = null;
           setIsConnected(false);
+          // 1008 = policy violation: expired or revoked login token.
+          // Fall back to the public status until the user logs in again.
+          if (event.code === 1008) {
+            console.warn('[WebSocket] Authentication required');
+            pollPublicStatus();
+            return;
+          }
+          console.log('[WebSocket] Disconnected, reconnecting...');
           if (!initialDataReceived.current) {
             setLoadingState('loading');
           }
@@ -494,11 +590,36 @@ export function WebSocketProvider({ children }: { children: ReactNode }) {
       }
     };
 
+    // Polls /api/public/status, then tries to stream again in case the user
+    // has logged in meanwhile. A disabled public page leaves the error state.
+    const pollPublicStatus = async () => {
+      try {
+        const res = await fetch(`${BASE_PATH}/api/public/status`);
+        if (closed) return;
+        if (res.ok) {
+          const data: PublicStatusResponse = await res.json();
+          if (closed) return;
+          setServers(data.servers.map(s => publicServerState(s, data.updated_at)));
+          setLoadingState('ready');
+          setIsInitialLoad(false);
+        } else {
+          setLoadingState('error');
+        }
+      } catch (e) {
+        console.error('[WebSocket] Public status error', e);
+        setLoadingState('error');
+      }
+      if (!closed) {
+        reconnectTimeoutRef.current = window.setTimeout(connect, PUBLIC_STATUS_INTERVAL);
+      }
+    };
+
     // Connect immediately
     connect();
 
     // Cleanup only when the entire app unmounts (not on page navigation)
     return () => {
+      closed = true;
       if (reconnectTimeoutRef.current) {
         clearTimeout(reconnectTimeoutRef.current);
       }
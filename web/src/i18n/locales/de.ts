@@ -144,6 +144,10 @@ export default {
     pleaseEnterPassword: 'Bitte geben Sie ein Passwort ein',
     invalidPassword: 'Ungültiges Passwort',
     oauthFailed: 'OAuth-Anmeldung fehlgeschlagen',
+    twoFactorCode: 'Authentifizierungscode',
+    twoFactorHint: 'Geben Sie den 6-stelligen Code aus Ihrer Authenticator-App oder einen Wiederherstellungscode ein.',
+    pleaseEnterCode: 'Bitte geben Sie einen Code ein',
+    invalidCode: 'Ungültiger oder abgelaufener Code',
   },
 
   // OAuth Callback
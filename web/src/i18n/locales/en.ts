@@ -109,6 +109,7 @@ export default {
     // Storage
     storageSection: 'Storage',
     free: 'free',
+    inodes: 'Inodes',
     // Network
     networkSection: 'Network',
     uploadSpeed: 'Upload Speed',
@@ -154,6 +155,10 @@ export default {
     pleaseEnterPassword: 'Please enter a password',
     invalidPassword: 'Invalid password',
     oauthFailed: 'OAuth login failed',
+    twoFactorCode: 'Authentication Code',
+    twoFactorHint: 'Enter the 6-digit code from your authenticator app, or a recovery code.',
+    pleaseEnterCode: 'Please enter a code',
+    invalidCode: 'Invalid or expired code',
   },
 
   // OAuth Callback
@@ -144,6 +144,10 @@ export default {
     pleaseEnterPassword: 'Por favor ingrese una contraseña',
     invalidPassword: 'Contraseña inválida',
     oauthFailed: 'Error de inicio de sesión OAuth',
+    twoFactorCode: 'Código de autenticación',
+    twoFactorHint: 'Introduce el código de 6 dígitos de tu aplicación de autenticación o un código de recuperación.',
+    pleaseEnterCode: 'Introduce un código',
+    invalidCode: 'Código no válido o caducado',
   },
 
   // OAuth Callback
@@ -144,6 +144,10 @@ export default {
     pleaseEnterPassword: 'Veuillez entrer un mot de passe',
     invalidPassword: 'Mot de passe invalide',
     oauthFailed: 'Échec de la connexion OAuth',
+    twoFactorCode: 'Code d\'authentification',
+    twoFactorHint: 'Saisissez le code à 6 chiffres de votre application d\'authentification ou un code de récupération.',
+    pleaseEnterCode: 'Veuillez saisir un code',
+    invalidCode: 'Code invalide ou expiré',
   },
 
   // OAuth Callback
@@ -144,6 +144,10 @@ export default {
     pleaseEnterPassword: 'パスワードを入力してください',
     invalidPassword: 'パスワードが正しくありません',
     oauthFailed: 'OAuthログインに失敗しました',
+    twoFactorCode: '認証コード',
+    twoFactorHint: '認証アプリの6桁のコード、またはリカバリーコードを入力してください。',
+    pleaseEnterCode: 'コードを入力してください',
+    invalidCode: 'コードが無効か期限切れです',
   },
 
   // OAuth Callback
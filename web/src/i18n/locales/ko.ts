@@ -144,6 +144,10 @@ export default {
     pleaseEnterPassword: '비밀번호를 입력하세요',
     invalidPassword: '잘못된 비밀번호',
     oauthFailed: 'OAuth 로그인 실패',
+    twoFactorCode: '인증 코드',
+    twoFactorHint: '인증 앱의 6자리 코드 또는 복구 코드를 입력하세요.',
+    pleaseEnterCode: '코드를 입력하세요',
+    invalidCode: '코드가 잘못되었거나 만료되었습니다',
   },
 
   // OAuth Callback
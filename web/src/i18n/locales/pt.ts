@@ -144,6 +144,10 @@ export default {
     pleaseEnterPassword: 'Por favor, digite uma senha',
     invalidPassword: 'Senha inválida',
     oauthFailed: 'Falha no login OAuth',
+    twoFactorCode: 'Código de autenticação',
+    twoFactorHint: 'Digite o código de 6 dígitos do seu aplicativo autenticador ou um código de recuperação.',
+    pleaseEnterCode: 'Digite um código',
+    invalidCode: 'Código inválido ou expirado',
   },
 
   // OAuth Callback
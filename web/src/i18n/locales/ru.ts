@@ -144,6 +144,10 @@ export default {
     pleaseEnterPassword: 'Пожалуйста, введите пароль',
     invalidPassword: 'Неверный пароль',
     oauthFailed: 'Ошибка OAuth входа',
+    twoFactorCode: 'Код аутентификации',
+    twoFactorHint: 'Введите 6-значный код из приложения-аутентификатора или код восстановления.',
+    pleaseEnterCode: 'Введите код',
+    invalidCode: 'Неверный или просроченный код',
   },
 
   // OAuth Callback
@@ -109,6 +109,7 @@ export default {
     // Storage
     storageSection: '存储',
     free: '空闲',
+    inodes: 'Inode',
     // Network
     networkSection: '网络',
     uploadSpeed: '上传速度',
@@ -154,6 +155,10 @@ export default {
     pleaseEnterPassword: '请输入密码',
     invalidPassword: '密码错误',
     oauthFailed: 'OAuth 登录失败',
+    twoFactorCode: '验证码',
+    twoFactorHint: '输入身份验证器应用中的 6 位验证码，或一个恢复码。',
+    pleaseEnterCode: '请输入验证码',
+    invalidCode: '验证码错误或已过期',
   },
 
   // OAuth Callback
@@ -1605,6 +1605,14 @@ export default function ServerDetail() {
                   <span>{formatBytes(disk.total - disk.used)} {t('serverDetail.free')}</span>
                   <span>{formatBytes(disk.total)} {t('serverDetail.total').toLowerCase()}</span>
                 </div>
+                {!!disk.inodes_total && (
+                  <div className="flex justify-between text-xs text-gray-500 mt-1">
+                    <span>{t('serverDetail.inodes')}</span>
+                    <span className={`font-mono ${(disk.inodes_usage_percent ?? 0) > 90 ? 'text-red-400' : ''}`}>
+                      {(disk.inodes_usage_percent ?? 0).toFixed(1)}% ({(disk.inodes_used ?? 0).toLocaleString()} / {disk.inodes_total.toLocaleString()})
+                    </span>
+                  </div>
+                )}
               </div>
             ))}
           </div>
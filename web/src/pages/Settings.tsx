@@ -1512,7 +1512,10 @@ export default function Settings() {
 
   const fetchServers = async () => {
     try {
-      const res = await fetch('/api/servers');
+      // Agent tokens are only included for authenticated requests
+      const res = await fetch('/api/servers', {
+        headers: { 'Authorization': `Bearer ${token}` }
+      });
       if (res.ok) {
         const data = await res.json();
         setServers(data);
@@ -10,6 +10,13 @@ export interface SystemMetrics {
   load_average: LoadAverage;
   ping?: PingMetrics;
   version?: string;
+  services?: ServiceStatus[];
+}
+
+export interface ServiceStatus {
+  name: string;
+  active: boolean;
+  state: string;
 }
 
 export interface OsInfo {
@@ -25,6 +32,8 @@ export interface CpuMetrics {
   usage: number;
   frequency: number;
   per_core: number[];
+  physical_cores?: number;
+  per_core_frequency?: number[];
 }
 
 export interface MemoryMetrics {
@@ -54,6 +63,38 @@ export interface DiskMetrics {
   mount_points?: string[];
   usage_percent: number;
   used: number;
+  inodes_total?: number;
+  inodes_used?: number;
+  inodes_usage_percent?: number;  // Absent where the filesystem has no inode limit
+}
+
+export interface BandwidthCycle {
+  start: string;  // YYYY-MM-DD, inclusive
+  end: string;    // YYYY-MM-DD, exclusive
+  rx_bytes: number;
+  tx_bytes: number;
+}
+
+export interface BandwidthResponse {
+  server_id: string;
+  billing_cycle_day: number;
+  cycles: BandwidthCycle[];  // Oldest first; the last is the current cycle
+}
+
+export interface OutageWindow {
+  start: string;
+  end: string | null;  // null while the server is still offline
+  duration_secs: number;
+}
+
+export interface UptimeResponse {
+  server_id: string;
+  range: string;
+  uptime_percent: number | null;  // Of the time with data
+  outage_count: number;
+  downtime_secs: number;
+  no_data_secs: number;
+  outages: OutageWindow[];
 }
 
 export interface NetworkMetrics {
@@ -139,6 +180,13 @@ export interface SiteSettings {
   site_description: string;
   social_links: SocialLink[];
   theme?: ThemeSettings;
+  public_mode?: PublicModeSettings;
+  show_local_node?: boolean; // Default: true
+}
+
+export interface PublicModeSettings {
+  enabled: boolean;
+  fields?: string[];  // location, provider, tag, version, uptime, cpu, memory, disk, load, network
 }
 
 export interface SocialLink {
@@ -156,13 +204,28 @@ export interface HistoryPoint {
   net_rx: number;
   net_tx: number;
   ping_ms?: number;
+  // Per-bucket peaks, only for 7d/30d/1y; null for older buckets
+  cpu_max?: number | null;
+  cpu_p95?: number | null;
+  memory_max?: number | null;
+  memory_p95?: number | null;
+  ping_max?: number | null;
+  // Averages; null for data stored before they were recorded
+  load?: LoadAverage | null;
+  swap?: number | null;
 }
 
 export interface HistoryResponse {
   server_id: string;
   range: string;
+  stat?: 'avg' | 'max';
   data: HistoryPoint[];
   ping_targets?: PingHistoryTarget[];
+  next_cursor?: string;
+  from?: string;
+  to?: string;
+  resolution?: 'raw' | '5sec' | '2min' | '15min' | 'hourly' | 'daily';
+  bucket_secs?: number;
 }
 
 export interface PingHistoryTarget {
@@ -176,3 +239,22 @@ export interface PingHistoryPoint {
   latency_ms: number | null;
   status: string;
 }
+
+export interface PingTargetPoint {
+  timestamp: string;
+  latency_avg: number | null;
+  latency_max: number | null;
+  packet_loss: number;
+}
+
+export interface PingTargetSeries {
+  name: string;
+  host: string;
+  data: PingTargetPoint[];
+}
+
+export interface PingTargetHistoryResponse {
+  server_id: string;
+  range: string;
+  targets: PingTargetSeries[];
+}
@@ -0,0 +1,24 @@
+/**
+ * Base path when vStats is served under a subpath (base_path / VSTATS_BASE_PATH).
+ * The server injects it into index.html; it is '' at the root.
+ */
+
+declare global {
+  interface Window {
+    __VSTATS_BASE_PATH__?: string;
+  }
+}
+
+export const BASE_PATH = (typeof window !== 'undefined' && window.__VSTATS_BASE_PATH__) || '';
+
+// Prefixes a root-relative path with the base path
+export function withBasePath(path: string): string {
+  return path.startsWith('/') && !path.startsWith('//') ? BASE_PATH + path : path;
+}
+
+// Existing fetch('/api/...') calls reach the server under the base path
+if (BASE_PATH) {
+  const originalFetch = window.fetch.bind(window);
+  window.fetch = (input: RequestInfo | URL, init?: RequestInit) =>
+    originalFetch(typeof input === 'string' ? withBasePath(input) : input, init);
+}
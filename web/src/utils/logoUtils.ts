@@ -3,6 +3,7 @@
  */
 
 import React from 'react';
+import { withBasePath } from './basePath';
 
 // Logo索引数据（从index.json加载）
 let logoIndex: {
@@ -81,7 +82,7 @@ export function getProviderLogo(providerName: string): string | null {
   });
 
   if (match) {
-    return `/${match.path}`;
+    return withBasePath(`/${match.path}`);
   }
 
   // 特殊匹配规则
@@ -124,7 +125,7 @@ export function getProviderLogo(providerName: string): string | null {
       normalizeName(p.name) === normalizeName(specialMatch)
     );
     if (found) {
-      return `/${found.path}`;
+      return withBasePath(`/${found.path}`);
     }
   }
 
@@ -153,7 +154,7 @@ export function getDistributionLogo(osName: string): string | null {
   });
 
   if (match) {
-    return `/${match.path}`;
+    return withBasePath(`/${match.path}`);
   }
 
   // 特殊匹配规则
@@ -201,7 +202,7 @@ export function getDistributionLogo(osName: string): string | null {
       normalizeName(d.name) === normalizeName(specialMatch)
     );
     if (found) {
-      return `/${found.path}`;
+      return withBasePath(`/${found.path}`);
     }
   }
 